@@ -0,0 +1,102 @@
+//! LruKReplacer operation cost as the pool grows.
+//!
+//! Run with `cargo bench --bench replacer`. Each round evicts a frame and
+//! reloads it (two accesses, then unpinned), the replacer traffic of a buffer
+//! pool miss. `scan` is the previous replacer, which walked every frame on
+//! each eviction; it is kept here as the baseline.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crio::buffer::LruKReplacer;
+use crio::common::FrameId;
+
+const K: usize = 2;
+const ROUNDS: usize = 2_000;
+
+/// The full-scan replacer, reduced to what the benchmark exercises.
+#[derive(Default)]
+struct ScanReplacer {
+    now: u64,
+    frames: HashMap<FrameId, (VecDeque<u64>, bool)>,
+}
+
+impl ScanReplacer {
+    fn record_access(&mut self, frame_id: FrameId) {
+        let (history, _) = self.frames.entry(frame_id).or_default();
+        history.push_back(self.now);
+        if history.len() > K {
+            history.pop_front();
+        }
+        self.now += 1;
+    }
+
+    fn set_evictable(&mut self, frame_id: FrameId, evictable: bool) {
+        self.frames.entry(frame_id).or_default().1 = evictable;
+    }
+
+    fn evict(&mut self) -> Option<FrameId> {
+        let victim = self
+            .frames
+            .iter()
+            .filter(|(_, (_, evictable))| *evictable)
+            .min_by_key(|(_, (history, _))| (history.len() >= K, history[0]))
+            .map(|(&frame_id, _)| frame_id)?;
+        self.frames.remove(&victim);
+        Some(victim)
+    }
+}
+
+/// Fills `frames` frames, then times ROUNDS evict-and-reload rounds.
+fn run(
+    frames: usize,
+    mut record_access: impl FnMut(FrameId),
+    mut set_evictable: impl FnMut(FrameId, bool),
+    mut evict: impl FnMut() -> Option<FrameId>,
+) -> Duration {
+    for i in 0..frames {
+        let frame_id = FrameId::new(i as u32);
+        record_access(frame_id);
+        record_access(frame_id);
+        set_evictable(frame_id, true);
+    }
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let frame_id = evict().unwrap();
+        record_access(frame_id);
+        set_evictable(frame_id, false);
+        record_access(frame_id);
+        set_evictable(frame_id, true);
+    }
+    start.elapsed()
+}
+
+fn main() {
+    for frames in [1_000, 10_000, 100_000] {
+        let replacer = LruKReplacer::new(K, frames);
+        let lru_k = run(
+            frames,
+            |f| replacer.record_access(f),
+            |f, e| replacer.set_evictable(f, e),
+            || replacer.evict(),
+        );
+
+        let scan = std::cell::RefCell::new(ScanReplacer::default());
+        let baseline = run(
+            frames,
+            |f| scan.borrow_mut().record_access(f),
+            |f, e| scan.borrow_mut().set_evictable(f, e),
+            || scan.borrow_mut().evict(),
+        );
+
+        let per_round = |d: Duration| d.as_nanos() / ROUNDS as u128;
+        println!(
+            "{:>7} frames  lru_k {:>8} ns/round  scan {:>10} ns/round  ({:.0}x)",
+            frames,
+            per_round(lru_k),
+            per_round(baseline),
+            baseline.as_secs_f64() / lru_k.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Standard workloads against a file-backed buffer pool.
+//!
+//! Run with `cargo bench --bench workloads [filter]`; only workloads whose
+//! name contains `filter` run. Compare the printed throughput and latency
+//! percentiles across releases.
+
+use std::sync::Arc;
+
+use crio::buffer::BufferPoolManager;
+use crio::storage::disk::{DiskManager, DurabilityLevel};
+use crio::workload::{TpcbConfig, WorkloadRunner, YcsbConfig, YcsbTarget};
+
+/// Small enough that the larger workloads spill to disk
+const POOL_SIZE: usize = 256;
+
+fn runner(dir: &tempfile::TempDir, name: &str) -> WorkloadRunner {
+    let disk_manager = Arc::new(DiskManager::new(dir.path().join(name)).unwrap());
+    disk_manager.set_durability(DurabilityLevel::None);
+    WorkloadRunner::new(Arc::new(BufferPoolManager::new(POOL_SIZE, 2, disk_manager)))
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`; any other argument filters by name
+    let filter = std::env::args()
+        .skip(1)
+        .find(|arg| !arg.starts_with("--"))
+        .unwrap_or_default();
+    let dir = tempfile::tempdir().unwrap();
+
+    let ycsb = [
+        ("a", YcsbConfig::workload_a()),
+        ("b", YcsbConfig::workload_b()),
+        ("c", YcsbConfig::workload_c()),
+        ("d", YcsbConfig::workload_d()),
+    ];
+    let targets = [
+        ("buffer_pool", YcsbTarget::BufferPool),
+        ("table_heap", YcsbTarget::TableHeap),
+        ("btree", YcsbTarget::BTree),
+    ];
+    for (workload, config) in &ycsb {
+        for (target_name, target) in targets {
+            let name = format!("ycsb_{}/{}", workload, target_name);
+            if !name.contains(&filter) {
+                continue;
+            }
+            let report = runner(&dir, &name.replace('/', "_"))
+                .run_ycsb(config, target)
+                .unwrap();
+            println!("{:<24} {}", name, report);
+        }
+    }
+
+    if "tpcb".contains(&filter) {
+        let report = runner(&dir, "tpcb")
+            .run_tpcb(&TpcbConfig::default())
+            .unwrap();
+        println!("{:<24} {}", "tpcb", report);
+    }
+}
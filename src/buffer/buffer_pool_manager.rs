@@ -1,46 +1,134 @@
-use std::collections::{HashMap, LinkedList, VecDeque};
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet, LinkedList};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+#[cfg(feature = "pin-tracking")]
+use std::time::Instant;
 
 use parking_lot::Mutex;
 
 use crate::common::{CrioError, FrameId, PageId, Result, INVALID_PAGE_ID, PAGE_SIZE};
-use crate::storage::disk::{DiskManager, DiskScheduler};
+use crate::db::CrioConfig;
+use crate::storage::disk::{
+    DiskCompletion, DiskRequest, DiskScheduler, IoBudget, IoClass, StorageBackend,
+};
+
+use super::{
+    Access, FrameHeader, FrameSlab, LruKReplacer, OptimisticReadGuard, PinRelease, ReadAhead,
+    ReadAheadPolicy, ReadAheadStats, ReadPageGuard, ScanContext, SecondaryCache,
+    SecondaryCacheStats, WritePageGuard,
+};
+
+/// Information about a single outstanding pin, recorded with the
+/// `pin-tracking` feature.
+#[derive(Debug, Clone)]
+pub struct PinHolder {
+    /// Name (or ID) of the thread that took the pin
+    pub thread: String,
+    /// How long the pin has been held
+    pub held_for: Duration,
+    /// Backtrace captured when the pin was taken
+    pub backtrace: String,
+}
 
-use super::{FrameHeader, LruKReplacer, ReadPageGuard, WritePageGuard};
+/// Diagnostic entry describing a pinned page.
+#[derive(Debug, Clone)]
+pub struct PinInfo {
+    /// The pinned page
+    pub page_id: PageId,
+    /// Current pin count of the page
+    pub pin_count: u32,
+    /// Outstanding guards on the page (empty without the `pin-tracking` feature)
+    pub holders: Vec<PinHolder>,
+}
 
-const PREFETCH_LOOKAHEAD: u32 = 4;
-const SEQUENTIAL_THRESHOLD: usize = 3;
+#[cfg(feature = "pin-tracking")]
+struct PinRecord {
+    page_id: PageId,
+    thread: String,
+    since: Instant,
+    backtrace: std::backtrace::Backtrace,
+}
 
-struct AccessTracker {
-    recent_accesses: VecDeque<PageId>,
+#[cfg(feature = "pin-tracking")]
+#[derive(Default)]
+struct PinTracker {
+    next_pin_id: AtomicU64,
+    records: Mutex<HashMap<u64, PinRecord>>,
 }
 
-impl AccessTracker {
-    fn new() -> Self {
-        Self {
-            recent_accesses: VecDeque::with_capacity(SEQUENTIAL_THRESHOLD + 1),
-        }
+#[cfg(feature = "pin-tracking")]
+impl PinTracker {
+    fn register(&self, page_id: PageId) -> u64 {
+        let pin_id = self.next_pin_id.fetch_add(1, Ordering::Relaxed);
+        let current = std::thread::current();
+        let thread = current
+            .name()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{:?}", current.id()));
+        self.records.lock().insert(
+            pin_id,
+            PinRecord {
+                page_id,
+                thread,
+                since: Instant::now(),
+                backtrace: std::backtrace::Backtrace::force_capture(),
+            },
+        );
+        pin_id
     }
 
-    fn record(&mut self, page_id: PageId) {
-        if self.recent_accesses.len() >= SEQUENTIAL_THRESHOLD + 1 {
-            self.recent_accesses.pop_front();
-        }
-        self.recent_accesses.push_back(page_id);
+    fn unregister(&self, pin_id: u64) {
+        self.records.lock().remove(&pin_id);
     }
+}
 
-    fn is_sequential(&self) -> bool {
-        if self.recent_accesses.len() < SEQUENTIAL_THRESHOLD {
-            return false;
+enum WriteBackStatus {
+    InFlight(mpsc::Receiver<DiskCompletion>),
+    Written,
+    Failed,
+}
+
+/// A dirty page evicted from its frame whose write may still be in flight.
+/// Until the write lands, fetches of the page are served from this copy.
+struct WriteBack {
+    data: Vec<u8>,
+    status: Mutex<WriteBackStatus>,
+    /// Set once a fetch has copied the data back into a frame; the frame then
+    /// holds the page, marked dirty, and this copy is stale
+    reclaimed: AtomicBool,
+}
+
+impl WriteBack {
+    /// Returns whether the write landed, or None while it is still in flight.
+    /// With `block`, waits for it instead.
+    fn poll(&self, block: bool) -> Option<bool> {
+        let mut status = self.status.lock();
+        if let WriteBackStatus::InFlight(done) = &*status {
+            let outcome = if block {
+                done.recv().ok()
+            } else {
+                match done.try_recv() {
+                    Ok(outcome) => Some(outcome),
+                    Err(mpsc::TryRecvError::Empty) => return None,
+                    Err(mpsc::TryRecvError::Disconnected) => None,
+                }
+            };
+            *status = match outcome {
+                Some(Ok(_)) => WriteBackStatus::Written,
+                _ => WriteBackStatus::Failed,
+            };
         }
+        Some(matches!(*status, WriteBackStatus::Written))
+    }
 
-        let accesses: Vec<_> = self.recent_accesses.iter().collect();
-        for i in 1..accesses.len() {
-            if accesses[i].as_u32() != accesses[i - 1].as_u32() + 1 {
-                return false;
-            }
+    /// Returns true once the copy is no longer needed: the write landed, or
+    /// it failed but the page is back in a frame.
+    fn is_retired(&self) -> bool {
+        match self.poll(false) {
+            Some(written) => written || self.reclaimed.load(Ordering::Acquire),
+            None => false,
         }
-        true
     }
 }
 
@@ -49,12 +137,77 @@ struct BufferPoolState {
     page_table: Mutex<HashMap<PageId, FrameId>>,
     free_list: Mutex<LinkedList<FrameId>>,
     replacer: LruKReplacer,
-    access_tracker: Mutex<AccessTracker>,
+    /// Sequential access detection and read-ahead window. Locked after the
+    /// page table.
+    read_ahead: Mutex<ReadAhead>,
+    /// Evicted dirty pages whose write-back has not been retired yet. Locked
+    /// after the page table.
+    write_backs: Mutex<HashMap<PageId, Arc<WriteBack>>>,
+    /// Guards released against a frame whose pin count was already zero
+    unbalanced_unpins: AtomicU64,
+    /// Pages whose frames the replacer evicts last. Locked after the page
+    /// table.
+    sticky_pages: Mutex<HashSet<PageId>>,
+    /// Pages deleted and not allocated again since. Fetching one fails with
+    /// `PageDeleted` instead of reading freed storage into a frame. Locked
+    /// after the page table.
+    deleted_pages: Mutex<HashSet<PageId>>,
+    #[cfg(feature = "pin-tracking")]
+    pin_tracker: PinTracker,
+}
+
+impl BufferPoolState {
+    /// Drops one pin on a page. The page becomes evictable once its last pin
+    /// is gone.
+    fn release(&self, page_id: PageId) {
+        let page_table = self.page_table.lock();
+        if let Some(&frame_id) = page_table.get(&page_id) {
+            let frame = &self.frames[frame_id.as_usize()];
+            match frame.unpin() {
+                Some(0) => self.replacer.set_evictable(frame_id, true),
+                Some(_) => {}
+                None => {
+                    self.unbalanced_unpins.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Marks the frame just given to `page_id` sticky if the page is. Called
+    /// under the page table lock.
+    fn track_sticky(&self, page_id: PageId, frame_id: FrameId) {
+        if self.sticky_pages.lock().contains(&page_id) {
+            self.replacer.set_sticky(frame_id, true);
+        }
+    }
+
+    /// Records a new guard's pin with the `pin-tracking` feature and returns
+    /// its ID (always 0 without it).
+    fn register_pin(&self, page_id: PageId) -> u64 {
+        #[cfg(feature = "pin-tracking")]
+        return self.pin_tracker.register(page_id);
+        #[cfg(not(feature = "pin-tracking"))]
+        {
+            let _ = page_id;
+            0
+        }
+    }
+}
+
+impl PinRelease for BufferPoolState {
+    fn release_pin(&self, page_id: PageId, pin_id: u64) {
+        #[cfg(feature = "pin-tracking")]
+        self.pin_tracker.unregister(pin_id);
+        #[cfg(not(feature = "pin-tracking"))]
+        let _ = pin_id;
+        self.release(page_id);
+    }
 }
 
 /// BufferPoolManager is responsible for fetching database pages from disk
 /// and storing them in memory. It manages a fixed number of frames and uses
-/// the LRU-K replacement policy to decide which pages to evict.
+/// the LRU-K replacement policy to decide which pages to evict. With a
+/// `SecondaryCache`, evicted pages are kept a tier below the pool too.
 pub struct BufferPoolManager {
     /// Number of frames in the buffer pool
     pool_size: usize,
@@ -62,18 +215,80 @@ pub struct BufferPoolManager {
     state: Arc<BufferPoolState>,
     /// Disk scheduler for async I/O
     disk_scheduler: DiskScheduler,
+    /// Second tier evicted pages are copied to, if any
+    secondary_cache: Option<SecondaryCache>,
+    /// True if every page write is refused
+    read_only: bool,
 }
 
 impl BufferPoolManager {
     /// Creates a new BufferPoolManager with the given pool size, k value for LRU-K,
-    /// and disk manager.
-    pub fn new(pool_size: usize, k: usize, disk_manager: Arc<DiskManager>) -> Self {
+    /// and storage backend (usually an `Arc<DiskManager>`).
+    pub fn new(pool_size: usize, k: usize, disk_manager: Arc<dyn StorageBackend>) -> Self {
+        Self::with_huge_pages(pool_size, k, disk_manager, false)
+    }
+
+    /// Creates a new BufferPoolManager whose frames all live in one
+    /// page-aligned slab. With `huge_pages`, the slab is laid out for 2 MiB
+    /// pages and the kernel is asked to back it with them, which cuts TLB
+    /// misses for large pools.
+    pub fn with_huge_pages(
+        pool_size: usize,
+        k: usize,
+        disk_manager: Arc<dyn StorageBackend>,
+        huge_pages: bool,
+    ) -> Self {
+        Self::build(
+            pool_size,
+            LruKReplacer::new(k, pool_size),
+            DiskScheduler::new(disk_manager),
+            huge_pages,
+        )
+    }
+
+    /// Creates a new BufferPoolManager with the pool size, replacer, huge
+    /// pages, read-ahead and read-only settings of `config`, over a disk
+    /// scheduler built from it too.
+    pub fn from_config(config: &CrioConfig, disk_manager: Arc<dyn StorageBackend>) -> Self {
+        let bpm = Self::build(
+            config.pool_size,
+            LruKReplacer::new(config.lru_k, config.pool_size),
+            DiskScheduler::from_config(disk_manager, config),
+            config.huge_pages,
+        )
+        .with_read_only(config.read_only);
+        bpm.set_read_ahead_policy(config.read_ahead);
+        bpm
+    }
+
+    /// Creates a new BufferPoolManager that evicts with `replacer`, e.g. one
+    /// built with `LruKReplacer::with_clock`. The replacer must be able to
+    /// track `pool_size` frames.
+    pub fn with_replacer(
+        pool_size: usize,
+        replacer: LruKReplacer,
+        disk_manager: Arc<dyn StorageBackend>,
+    ) -> Self {
+        assert!(
+            replacer.max_frames() >= pool_size,
+            "replacer tracks fewer frames than the pool holds"
+        );
+        Self::build(pool_size, replacer, DiskScheduler::new(disk_manager), false)
+    }
+
+    fn build(
+        pool_size: usize,
+        replacer: LruKReplacer,
+        disk_scheduler: DiskScheduler,
+        huge_pages: bool,
+    ) -> Self {
+        let slab = Arc::new(FrameSlab::new(pool_size, huge_pages));
         let mut frames = Vec::with_capacity(pool_size);
         let mut free_list = LinkedList::new();
 
         for i in 0..pool_size {
             let frame_id = FrameId::new(i as u32);
-            frames.push(Arc::new(FrameHeader::new(frame_id)));
+            frames.push(Arc::new(FrameHeader::in_slab(frame_id, &slab)));
             free_list.push_back(frame_id);
         }
 
@@ -81,15 +296,55 @@ impl BufferPoolManager {
             frames,
             page_table: Mutex::new(HashMap::new()),
             free_list: Mutex::new(free_list),
-            replacer: LruKReplacer::new(k, pool_size),
-            access_tracker: Mutex::new(AccessTracker::new()),
+            replacer,
+            read_ahead: Mutex::new(ReadAhead::new(ReadAheadPolicy::default())),
+            write_backs: Mutex::new(HashMap::new()),
+            unbalanced_unpins: AtomicU64::new(0),
+            sticky_pages: Mutex::new(HashSet::new()),
+            deleted_pages: Mutex::new(HashSet::new()),
+            #[cfg(feature = "pin-tracking")]
+            pin_tracker: PinTracker::default(),
         });
 
         Self {
             pool_size,
             state,
-            disk_scheduler: DiskScheduler::new(disk_manager),
+            disk_scheduler,
+            secondary_cache: None,
+            read_only: false,
+        }
+    }
+
+    /// Adds `cache` as a second tier below the pool: evicted pages are
+    /// copied into it, and fetches look there before reading the backend.
+    pub fn with_secondary_cache(mut self, cache: SecondaryCache) -> Self {
+        self.secondary_cache = Some(cache);
+        self
+    }
+
+    /// With `read_only`, refuses every page write up front with `ReadOnly`:
+    /// new pages, write guards, direct writes and deletes. Nothing is ever
+    /// dirtied, so shutdown neither flushes nor closes the backend.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Returns true if the pool refuses page writes.
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(CrioError::ReadOnly);
         }
+        Ok(())
+    }
+
+    /// Returns the secondary cache's counters, if the pool has one.
+    pub fn secondary_cache_stats(&self) -> Option<SecondaryCacheStats> {
+        self.secondary_cache.as_ref().map(SecondaryCache::stats)
     }
 
     /// Creates a new page in the buffer pool.
@@ -97,88 +352,242 @@ impl BufferPoolManager {
     /// The page is initially evictable. Use checked_write_page or checked_read_page
     /// to get a guard that pins the page.
     pub fn new_page(&self) -> Result<PageId> {
-        let frame_id = self.get_free_frame()?;
+        self.check_writable()?;
+        let mut page_table = self.state.page_table.lock();
+        let frame_id = self.get_free_frame(&mut page_table)?;
         let frame = &self.state.frames[frame_id.as_usize()];
 
         // Allocate a new page on disk
-        let page_id = self.disk_scheduler.disk_manager().allocate_page()?;
+        let page_id = match self.disk_scheduler.disk_manager().allocate_page() {
+            Ok(page_id) => page_id,
+            Err(e) => {
+                self.state.free_list.lock().push_back(frame_id);
+                return Err(e);
+            }
+        };
+
+        self.state.deleted_pages.lock().remove(&page_id);
 
         // Initialize the frame (don't pin - let the guard handle pinning)
         frame.reset();
         frame.set_page_id(page_id);
 
         // Update page table
-        self.state.page_table.lock().insert(page_id, frame_id);
+        page_table.insert(page_id, frame_id);
 
         // Record access and mark as evictable (caller should get a guard to pin)
         self.state.replacer.record_access(frame_id);
         self.state.replacer.set_evictable(frame_id, true);
+        self.state.track_sticky(page_id, frame_id);
 
         Ok(page_id)
     }
 
-    /// Deletes a page from the buffer pool and disk.
-    /// Returns true if the page was successfully deleted.
+    /// Allocates a whole extent of pages on disk for `table_id` without
+    /// loading them into the buffer pool. Fill them with `write_pages_direct`
+    /// or fetch them like any other page.
+    pub fn allocate_extent(&self, table_id: u32) -> Result<Vec<PageId>> {
+        self.check_writable()?;
+        let pages = self
+            .disk_scheduler
+            .disk_manager()
+            .allocate_extent(table_id)?;
+        let mut deleted_pages = self.state.deleted_pages.lock();
+        for page_id in &pages {
+            deleted_pages.remove(page_id);
+        }
+        Ok(pages)
+    }
+
+    /// Writes pages straight to disk in one vectored batch, bypassing the
+    /// buffer pool; any resident copy is discarded first. Meant for pages no
+    /// one else can reach yet, such as a freshly allocated extent. Fails with
+    /// `PageStillPinned` if one of the pages is pinned.
+    pub fn write_pages_direct(&self, pages: &[(PageId, &[u8])]) -> Result<()> {
+        self.check_writable()?;
+        let page_ids: Vec<_> = pages.iter().map(|&(page_id, _)| page_id).collect();
+        self.discard_pages(page_ids)?;
+
+        let mut pages = pages.to_vec();
+        pages.sort_by_key(|&(page_id, _)| page_id.as_u32());
+        self.disk_scheduler.schedule_write_vectored_sync(&pages)
+    }
+
+    /// Keeps `page_id` resident in preference to other pages: while it is in
+    /// the pool the replacer evicts it only when every evictable page is
+    /// sticky. Meant for hot metadata such as catalog pages and B+Tree roots.
+    /// The page stays sticky across evictions until `clear_sticky` or
+    /// `delete_page`.
+    pub fn set_sticky(&self, page_id: PageId) {
+        self.set_stickiness(page_id, true);
+    }
+
+    /// Makes `page_id` an ordinary page again.
+    pub fn clear_sticky(&self, page_id: PageId) {
+        self.set_stickiness(page_id, false);
+    }
+
+    /// Returns true if `page_id` is sticky.
+    pub fn is_sticky(&self, page_id: PageId) -> bool {
+        self.state.sticky_pages.lock().contains(&page_id)
+    }
+
+    fn set_stickiness(&self, page_id: PageId, sticky: bool) {
+        let page_table = self.state.page_table.lock();
+        let mut sticky_pages = self.state.sticky_pages.lock();
+        let changed = if sticky {
+            sticky_pages.insert(page_id)
+        } else {
+            sticky_pages.remove(&page_id)
+        };
+        if let (true, Some(&frame_id)) = (changed, page_table.get(&page_id)) {
+            self.state.replacer.set_sticky(frame_id, sticky);
+        }
+    }
+
+    /// Deletes a page from the buffer pool, discarding any unwritten changes,
+    /// and deallocates it on disk whether or not it was resident.
+    /// Returns true if the page was in the buffer pool.
+    ///
+    /// Fails with `PageStillPinned` while any guard holds the page. All of
+    /// this happens under the page table lock, which every pin takes, so no
+    /// fetch can slip in between; fetches after it fail with `PageDeleted`
+    /// until the page is allocated again.
     pub fn delete_page(&self, page_id: PageId) -> Result<bool> {
+        self.check_writable()?;
         let mut page_table = self.state.page_table.lock();
+        let resident = self.discard_locked(&mut page_table, &[page_id])? > 0;
+        self.state.sticky_pages.lock().remove(&page_id);
 
-        if let Some(frame_id) = page_table.remove(&page_id) {
-            let frame = &self.state.frames[frame_id.as_usize()];
+        // Deallocate the page on disk
+        self.disk_scheduler
+            .disk_manager()
+            .deallocate_page(page_id)?;
+        self.state.deleted_pages.lock().insert(page_id);
 
-            // Cannot delete a pinned page
-            if frame.pin_count() > 0 {
-                // Put it back in the page table
-                page_table.insert(page_id, frame_id);
-                return Err(CrioError::PageStillPinned(page_id));
-            }
+        Ok(resident)
+    }
 
-            // Reset the frame and add it to the free list
-            frame.reset();
-            self.state.replacer.remove(frame_id);
-            self.state.free_list.lock().push_back(frame_id);
+    /// Drops a page from the buffer pool without writing it back, throwing
+    /// away any unwritten changes; the page stays allocated with whatever was
+    /// last written to disk. Returns true if the page was in the buffer pool.
+    pub fn discard_page(&self, page_id: PageId) -> Result<bool> {
+        Ok(self.discard_pages([page_id])? > 0)
+    }
 
-            // Deallocate the page on disk
-            self.disk_scheduler
-                .disk_manager()
-                .deallocate_page(page_id)?;
+    /// Discards every page in `page_ids` as `discard_page` does, e.g. all
+    /// pages of a dropped table. If any of them is pinned, none are
+    /// discarded. Returns the number that were in the buffer pool.
+    pub fn discard_pages(&self, page_ids: impl IntoIterator<Item = PageId>) -> Result<usize> {
+        let page_ids: Vec<_> = page_ids.into_iter().collect();
+        let mut page_table = self.state.page_table.lock();
+        self.discard_locked(&mut page_table, &page_ids)
+    }
 
-            Ok(true)
-        } else {
-            Ok(false)
+    /// Discards the `num_pages` pages starting at `start_page_id`.
+    pub fn discard_page_range(&self, start_page_id: PageId, num_pages: u32) -> Result<usize> {
+        let start = start_page_id.as_u32();
+        self.discard_pages((start..start.saturating_add(num_pages)).map(PageId::new))
+    }
+
+    /// Unmaps and frees the frames of `page_ids` under the page table lock,
+    /// which keeps new pins out. Pending write-backs of the pages are
+    /// forgotten once they land, so nothing reclaims or rewrites them.
+    fn discard_locked(
+        &self,
+        page_table: &mut HashMap<PageId, FrameId>,
+        page_ids: &[PageId],
+    ) -> Result<usize> {
+        let pinned = page_ids.iter().find(|page_id| {
+            page_table
+                .get(page_id)
+                .is_some_and(|frame_id| self.state.frames[frame_id.as_usize()].pin_count() > 0)
+        });
+        if let Some(&page_id) = pinned {
+            return Err(CrioError::PageStillPinned(page_id));
+        }
+
+        let mut discarded = 0;
+        for page_id in page_ids {
+            if let Some(cache) = &self.secondary_cache {
+                cache.invalidate(*page_id);
+            }
+            if let Some(frame_id) = page_table.remove(page_id) {
+                self.state.read_ahead.lock().evicted(*page_id, false);
+                // Reset the frame and add it to the free list
+                self.state.frames[frame_id.as_usize()].reset();
+                self.state.replacer.remove(frame_id);
+                self.state.free_list.lock().push_back(frame_id);
+                discarded += 1;
+            }
+        }
+
+        // Let earlier evictions' writes land before the pages go away
+        let write_backs: Vec<_> = {
+            let mut write_backs = self.state.write_backs.lock();
+            page_ids
+                .iter()
+                .filter_map(|page_id| write_backs.remove(page_id))
+                .collect()
+        };
+        for write_back in write_backs {
+            write_back.poll(true);
         }
+
+        Ok(discarded)
+    }
+
+    /// Starts a latch-free optimistic read of a resident page; see
+    /// `OptimisticReadGuard`. Returns None if the page is not in the buffer
+    /// pool or a writer holds it, in which case read it with
+    /// `checked_read_page` instead. The page is not pinned.
+    pub fn optimistic_read_page(&self, page_id: PageId) -> Option<OptimisticReadGuard> {
+        let page_table = self.state.page_table.lock();
+        let frame = &self.state.frames[page_table.get(&page_id)?.as_usize()];
+        // Read under the page table lock: moving the frame to another page
+        // takes that lock and bumps the version
+        let version = frame.version();
+        drop(page_table);
+        OptimisticReadGuard::new(page_id, Arc::clone(frame), version)
     }
 
     /// Fetches a page for read access.
     /// Returns None if the page doesn't exist and cannot be created.
     pub fn checked_read_page(&self, page_id: PageId) -> Result<Option<ReadPageGuard>> {
+        self.read_page_with(page_id, Access::Thread)
+    }
+
+    /// Fetches a page for read access as part of `scan`, whose accesses
+    /// alone decide whether it reads sequentially and should read ahead.
+    pub fn checked_read_page_in(
+        &self,
+        page_id: PageId,
+        scan: &mut ScanContext,
+    ) -> Result<Option<ReadPageGuard>> {
+        self.read_page_with(page_id, Access::Scan(scan))
+    }
+
+    /// Fetches a page for read access without reading ahead of it or
+    /// counting it towards a sequential pattern, e.g. for a scan that won't
+    /// benefit from read-ahead.
+    pub fn checked_read_page_without_read_ahead(
+        &self,
+        page_id: PageId,
+    ) -> Result<Option<ReadPageGuard>> {
+        self.read_page_with(page_id, Access::Untracked)
+    }
+
+    fn read_page_with(&self, page_id: PageId, access: Access) -> Result<Option<ReadPageGuard>> {
         if page_id == INVALID_PAGE_ID {
             return Err(CrioError::InvalidPageId(page_id));
         }
 
-        let frame_id = self.fetch_page(page_id)?;
+        let frame_id = self.fetch_page(page_id, access)?;
         let frame = Arc::clone(&self.state.frames[frame_id.as_usize()]);
 
-        // Clone state for the callback
-        let state = Arc::clone(&self.state);
-
-        let guard = unsafe {
-            ReadPageGuard::new(
-                page_id,
-                frame,
-                Box::new(move |pid, is_dirty| {
-                    let pt = state.page_table.lock();
-                    if let Some(&fid) = pt.get(&pid) {
-                        let frm = &state.frames[fid.as_usize()];
-                        if is_dirty {
-                            frm.set_dirty(true);
-                        }
-                        if let Some(0) = frm.unpin() {
-                            state.replacer.set_evictable(fid, true);
-                        }
-                    }
-                }),
-            )
-        };
+        let pin_id = self.state.register_pin(page_id);
+        let owner = Arc::clone(&self.state) as Arc<dyn PinRelease>;
+        let guard = ReadPageGuard::new(page_id, frame, owner, pin_id);
 
         Ok(Some(guard))
     }
@@ -186,122 +595,200 @@ impl BufferPoolManager {
     /// Fetches a page for write access.
     /// Returns None if the page doesn't exist and cannot be created.
     pub fn checked_write_page(&self, page_id: PageId) -> Result<Option<WritePageGuard>> {
+        self.check_writable()?;
         if page_id == INVALID_PAGE_ID {
             return Err(CrioError::InvalidPageId(page_id));
         }
 
-        let frame_id = self.fetch_page(page_id)?;
+        let frame_id = self.fetch_page(page_id, Access::Thread)?;
         let frame = Arc::clone(&self.state.frames[frame_id.as_usize()]);
 
-        // Clone state for the callback
-        let state = Arc::clone(&self.state);
-
-        let guard = unsafe {
-            WritePageGuard::new(
-                page_id,
-                frame,
-                Box::new(move |pid, is_dirty| {
-                    let pt = state.page_table.lock();
-                    if let Some(&fid) = pt.get(&pid) {
-                        let frm = &state.frames[fid.as_usize()];
-                        if is_dirty {
-                            frm.set_dirty(true);
-                        }
-                        if let Some(0) = frm.unpin() {
-                            state.replacer.set_evictable(fid, true);
-                        }
-                    }
-                }),
-            )
-        };
+        let pin_id = self.state.register_pin(page_id);
+        let owner = Arc::clone(&self.state) as Arc<dyn PinRelease>;
+        let guard = WritePageGuard::new(page_id, frame, owner, pin_id);
 
         Ok(Some(guard))
     }
 
+    /// Pins a set of pages for writing, runs `f` over their guards, and writes all
+    /// of them back in a single vectored batch once `f` returns successfully.
+    ///
+    /// Guards are handed to `f` in the same order as `page_ids`, but latches are
+    /// acquired in page ID order so concurrent batches cannot deadlock. Every page
+    /// is treated as dirty. If `f` returns an error nothing is written and the
+    /// modified pages stay dirty in the buffer pool.
+    pub fn with_write_batch<F, R>(&self, page_ids: &[PageId], f: F) -> Result<R>
+    where
+        F: FnOnce(&mut [WritePageGuard]) -> Result<R>,
+    {
+        let mut seen = HashSet::with_capacity(page_ids.len());
+        for &page_id in page_ids {
+            if !seen.insert(page_id) {
+                return Err(CrioError::DuplicatePageInBatch(page_id));
+            }
+        }
+
+        let mut order: Vec<usize> = (0..page_ids.len()).collect();
+        order.sort_by_key(|&i| page_ids[i].as_u32());
+
+        let mut slots: Vec<Option<WritePageGuard>> = (0..page_ids.len()).map(|_| None).collect();
+        for &i in &order {
+            let mut guard = self
+                .checked_write_page(page_ids[i])?
+                .ok_or(CrioError::PageNotFound(page_ids[i]))?;
+            guard.mark_dirty();
+            slots[i] = Some(guard);
+        }
+        let mut guards: Vec<WritePageGuard> = slots.into_iter().flatten().collect();
+
+        let result = f(&mut guards)?;
+
+        // Write everything back in one vectored batch; the backend coalesces
+        // contiguous runs
+        guards.sort_by_key(|g| g.page_id().as_u32());
+        let pages: Vec<(PageId, &[u8])> = guards.iter().map(|g| (g.page_id(), g.data())).collect();
+        self.disk_scheduler.schedule_write_vectored_sync(&pages)?;
+
+        // Written through; release the pages as clean
+        for guard in &mut guards {
+            guard.mark_clean();
+            let frame_id = self.state.page_table.lock().get(&guard.page_id()).copied();
+            if let Some(frame_id) = frame_id {
+                self.state.frames[frame_id.as_usize()].set_dirty(false);
+            }
+        }
+
+        Ok(result)
+    }
+
     /// Flushes a specific page to disk.
     pub fn flush_page(&self, page_id: PageId) -> Result<bool> {
         if page_id == INVALID_PAGE_ID {
             return Err(CrioError::InvalidPageId(page_id));
         }
 
-        let page_table = self.state.page_table.lock();
-
-        if let Some(&frame_id) = page_table.get(&page_id) {
-            let frame = &self.state.frames[frame_id.as_usize()];
-
-            let mut data = [0u8; PAGE_SIZE];
-            frame.copy_to(&mut data);
+        // An older copy of the page may still be on its way to disk from an
+        // eviction; it must not land after this one
+        self.finish_write_backs(IoClass::Background)?;
 
-            // Write to disk
-            self.disk_scheduler.schedule_write_sync(page_id, &data)?;
+        // Pin the page rather than latching it under the page table lock: a
+        // writer holding the latch may be waiting for the page table
+        let Some(frame_id) = self.pin_resident(page_id) else {
+            return Ok(false);
+        };
+        let frame = &self.state.frames[frame_id.as_usize()];
 
-            // Clear dirty flag
-            frame.set_dirty(false);
+        // Clear the dirty flag before copying, so a write that lands after the
+        // copy marks the page dirty again instead of being lost
+        frame.set_dirty(false);
+        let mut data = vec![0u8; PAGE_SIZE];
+        frame.copy_to(&mut data);
 
-            Ok(true)
-        } else {
-            Ok(false)
+        let request = DiskRequest::write(page_id, data).with_class(IoClass::Background);
+        let written = self.disk_scheduler.schedule_sync(request);
+        if written.is_err() {
+            frame.set_dirty(true);
         }
+        self.state.release(page_id);
+        written.map(|_| true)
     }
 
-    /// Flushes all dirty pages to disk using sequential I/O when possible.
-    /// Groups contiguous dirty pages and writes them in single I/O operations.
-    pub fn flush_all_pages(&self) -> Result<()> {
+    /// Pins a page if it is resident and returns its frame.
+    fn pin_resident(&self, page_id: PageId) -> Option<FrameId> {
         let page_table = self.state.page_table.lock();
+        let &frame_id = page_table.get(&page_id)?;
+        self.state.frames[frame_id.as_usize()].pin();
+        self.state.replacer.set_evictable(frame_id, false);
+        Some(frame_id)
+    }
 
-        let mut dirty_pages: Vec<(PageId, FrameId)> = page_table
-            .iter()
-            .filter(|(_, &frame_id)| self.state.frames[frame_id.as_usize()].is_dirty())
-            .map(|(&pid, &fid)| (pid, fid))
-            .collect();
+    /// Flushes all dirty pages to disk in a single vectored batch. Dirty pages
+    /// need not be adjacent: the backend writes each contiguous run with one
+    /// `writev` and visits each segment file once.
+    pub fn flush_all_pages(&self) -> Result<()> {
+        self.flush_all_pages_as(IoClass::Background)
+    }
+
+    fn flush_all_pages_as(&self, class: IoClass) -> Result<()> {
+        // Still flush the resident pages if a write-back can't be finished
+        let finished = self.finish_write_backs(class);
+
+        // Pin the dirty pages under the page table lock, then copy them out
+        // without it (see flush_page)
+        let mut dirty_pages: Vec<(PageId, FrameId)> = {
+            let page_table = self.state.page_table.lock();
+            page_table
+                .iter()
+                .filter(|(_, &frame_id)| self.state.frames[frame_id.as_usize()].is_dirty())
+                .map(|(&pid, &fid)| {
+                    self.state.frames[fid.as_usize()].pin();
+                    self.state.replacer.set_evictable(fid, false);
+                    (pid, fid)
+                })
+                .collect()
+        };
 
         if dirty_pages.is_empty() {
-            return Ok(());
+            return finished;
         }
 
         dirty_pages.sort_by_key(|(pid, _)| pid.as_u32());
 
-        let mut i = 0;
-        while i < dirty_pages.len() {
-            let start_idx = i;
-            let start_page = dirty_pages[start_idx].0;
-
-            while i + 1 < dirty_pages.len()
-                && dirty_pages[i + 1].0.as_u32() == dirty_pages[i].0.as_u32() + 1
-            {
-                i += 1;
-            }
+        let mut bulk_data = vec![0u8; dirty_pages.len() * PAGE_SIZE];
+        for (chunk, &(_, frame_id)) in bulk_data.chunks_exact_mut(PAGE_SIZE).zip(&dirty_pages) {
+            let frame = &self.state.frames[frame_id.as_usize()];
+            frame.set_dirty(false);
+            frame.copy_to(chunk);
+        }
 
-            let count = i - start_idx + 1;
+        let pages: Vec<(PageId, &[u8])> = dirty_pages
+            .iter()
+            .zip(bulk_data.chunks_exact(PAGE_SIZE))
+            .map(|(&(pid, _), chunk)| (pid, chunk))
+            .collect();
+        let written = self
+            .disk_scheduler
+            .schedule_write_vectored_sync_as(class, &pages);
 
-            if count == 1 {
-                let frame = &self.state.frames[dirty_pages[start_idx].1.as_usize()];
-                let mut data = [0u8; PAGE_SIZE];
-                frame.copy_to(&mut data);
-                self.disk_scheduler.schedule_write_sync(start_page, &data)?;
-                frame.set_dirty(false);
-            } else {
-                let mut bulk_data = vec![0u8; count * PAGE_SIZE];
-                for j in 0..count {
-                    let frame = &self.state.frames[dirty_pages[start_idx + j].1.as_usize()];
-                    let offset = j * PAGE_SIZE;
-                    frame.copy_to(&mut bulk_data[offset..offset + PAGE_SIZE]);
-                }
-                self.disk_scheduler.schedule_write_pages_sync(
-                    start_page,
-                    count as u32,
-                    &bulk_data,
-                )?;
-                for j in 0..count {
-                    let frame = &self.state.frames[dirty_pages[start_idx + j].1.as_usize()];
-                    frame.set_dirty(false);
-                }
+        for &(page_id, frame_id) in &dirty_pages {
+            if written.is_err() {
+                self.state.frames[frame_id.as_usize()].set_dirty(true);
             }
+            self.state.release(page_id);
+        }
+
+        finished.and(written)
+    }
 
-            i += 1;
+    /// Shuts the buffer pool down cleanly: flushes every dirty page, quiesces
+    /// the disk scheduler (queued I/O completes, new I/O is refused) and closes
+    /// the storage backend, which syncs it and records the clean shutdown.
+    /// Pages still pinned are flushed as they are now; any later change to
+    /// them cannot be written back.
+    ///
+    /// Dropping the buffer pool calls this implicitly and ignores errors, so
+    /// call it explicitly to observe a failed flush. Calling it again is a
+    /// no-op.
+    pub fn shutdown(&self) -> Result<()> {
+        if self.disk_scheduler.is_shut_down() {
+            return Ok(());
+        }
+        if self.read_only {
+            self.disk_scheduler.shutdown();
+            return Ok(());
         }
 
-        Ok(())
+        // Someone is waiting on the shutdown, so don't hold it to the
+        // background budget
+        let flushed = self.flush_all_pages_as(IoClass::ForegroundWrite);
+        self.disk_scheduler.shutdown();
+        flushed?;
+        self.disk_scheduler.disk_manager().close()
+    }
+
+    /// Returns true once `shutdown` has run.
+    pub fn is_shut_down(&self) -> bool {
+        self.disk_scheduler.is_shut_down()
     }
 
     /// Returns the pin count for a page.
@@ -313,6 +800,138 @@ impl BufferPoolManager {
             .map(|&frame_id| self.state.frames[frame_id.as_usize()].pin_count())
     }
 
+    /// Lists pinned pages to help track down leaked guards.
+    ///
+    /// With the `pin-tracking` feature, only pages with at least one pin held
+    /// longer than `threshold` are reported, and each entry lists the thread,
+    /// age and backtrace of those pins. Without it, every pinned page is
+    /// reported with its pin count and `threshold` is ignored.
+    pub fn dump_pins(&self, threshold: Duration) -> Vec<PinInfo> {
+        let page_table = self.state.page_table.lock();
+
+        let mut pinned: Vec<PinInfo> = page_table
+            .iter()
+            .filter_map(|(&page_id, &frame_id)| {
+                let pin_count = self.state.frames[frame_id.as_usize()].pin_count();
+                (pin_count > 0).then(|| PinInfo {
+                    page_id,
+                    pin_count,
+                    holders: Vec::new(),
+                })
+            })
+            .collect();
+        drop(page_table);
+
+        #[cfg(feature = "pin-tracking")]
+        {
+            let records = self.state.pin_tracker.records.lock();
+            for info in &mut pinned {
+                info.holders = records
+                    .values()
+                    .filter(|r| r.page_id == info.page_id && r.since.elapsed() >= threshold)
+                    .map(|r| PinHolder {
+                        thread: r.thread.clone(),
+                        held_for: r.since.elapsed(),
+                        backtrace: r.backtrace.to_string(),
+                    })
+                    .collect();
+            }
+            pinned.retain(|info| !info.holders.is_empty());
+        }
+        #[cfg(not(feature = "pin-tracking"))]
+        let _ = threshold;
+
+        pinned.sort_by_key(|info| info.page_id.as_u32());
+        pinned
+    }
+
+    /// Checks that the page table and the frames agree and returns a
+    /// description of every violation: a mapping to a frame that holds another
+    /// page, two pages sharing a frame, a free frame that is still mapped, a
+    /// pinned frame outside the page table (a second copy of a page, or a
+    /// leaked pin), a resident page whose evicted copy is still waiting to be
+    /// fetched back, or a guard released more often than its page was pinned.
+    pub fn check_invariants(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+        let page_table = self.state.page_table.lock();
+        let free_list = self.state.free_list.lock();
+
+        let mut mapped: HashMap<FrameId, PageId> = HashMap::new();
+        for (&page_id, &frame_id) in page_table.iter() {
+            let frame = &self.state.frames[frame_id.as_usize()];
+            if frame.page_id() != page_id {
+                violations.push(format!(
+                    "{} maps to frame {} which holds {}",
+                    page_id,
+                    frame_id.as_usize(),
+                    frame.page_id()
+                ));
+            }
+            if let Some(other) = mapped.insert(frame_id, page_id) {
+                violations.push(format!(
+                    "{} and {} both map to frame {}",
+                    other,
+                    page_id,
+                    frame_id.as_usize()
+                ));
+            }
+        }
+
+        for &frame_id in free_list.iter() {
+            if let Some(page_id) = mapped.get(&frame_id) {
+                violations.push(format!(
+                    "frame {} is free but still maps {}",
+                    frame_id.as_usize(),
+                    page_id
+                ));
+            }
+        }
+
+        for frame in &self.state.frames {
+            let pin_count = frame.pin_count();
+            if pin_count > 0 && !mapped.contains_key(&frame.frame_id()) {
+                violations.push(format!(
+                    "frame {} holding {} is pinned {} times but not in the page table",
+                    frame.frame_id().as_usize(),
+                    frame.page_id(),
+                    pin_count
+                ));
+            }
+        }
+
+        for (page_id, write_back) in self.state.write_backs.lock().iter() {
+            if page_table.contains_key(page_id) && !write_back.reclaimed.load(Ordering::Acquire) {
+                violations.push(format!(
+                    "{} is resident but its evicted copy was never reclaimed",
+                    page_id
+                ));
+            }
+        }
+
+        let unbalanced = self.state.unbalanced_unpins.load(Ordering::Relaxed);
+        if unbalanced > 0 {
+            violations.push(format!(
+                "{} guards were released against an unpinned frame",
+                unbalanced
+            ));
+        }
+
+        violations
+    }
+
+    /// Limits the rate of one class of disk I/O, or lifts the limit with
+    /// `None`. Prefetches are charged to `IoClass::Prefetch` and
+    /// `flush_page`/`flush_all_pages` to `IoClass::Background`; fetches,
+    /// evictions and the shutdown flush are foreground.
+    pub fn set_io_budget(&self, class: IoClass, budget: Option<IoBudget>) {
+        self.disk_scheduler.set_io_budget(class, budget);
+    }
+
+    /// Returns how many requests of a class had to wait for their I/O budget.
+    pub fn throttled_requests(&self, class: IoClass) -> u64 {
+        self.disk_scheduler.throttled_requests(class)
+    }
+
     /// Returns the pool size.
     pub fn pool_size(&self) -> usize {
         self.pool_size
@@ -323,6 +942,23 @@ impl BufferPoolManager {
         self.state.free_list.lock().len()
     }
 
+    /// Sets how the pool reads ahead of sequential accesses. Resets the
+    /// read-ahead window and the sequential pattern seen so far.
+    pub fn set_read_ahead_policy(&self, policy: ReadAheadPolicy) {
+        self.state.read_ahead.lock().set_policy(policy);
+    }
+
+    /// Returns how the pool reads ahead of sequential accesses.
+    pub fn read_ahead_policy(&self) -> ReadAheadPolicy {
+        self.state.read_ahead.lock().policy()
+    }
+
+    /// Returns the current read-ahead window and how read-ahead pages were
+    /// used.
+    pub fn read_ahead_stats(&self) -> ReadAheadStats {
+        self.state.read_ahead.lock().stats()
+    }
+
     /// Prefetches multiple contiguous pages into the buffer pool using sequential I/O.
     /// This reads all pages in a SINGLE disk operation, then distributes them to frames.
     ///
@@ -334,35 +970,60 @@ impl BufferPoolManager {
     /// Pages already in the buffer pool are skipped (not re-read).
     /// If there aren't enough free frames, prefetches as many as possible.
     pub fn prefetch_pages(&self, start_page_id: PageId, num_pages: u32) -> Result<u32> {
+        self.prefetch_range(start_page_id, num_pages, false)
+    }
+
+    /// Prefetches pages as `prefetch_pages` does; with `read_ahead`, the
+    /// installed pages count towards the read-ahead window.
+    fn prefetch_range(
+        &self,
+        start_page_id: PageId,
+        num_pages: u32,
+        read_ahead: bool,
+    ) -> Result<u32> {
         if num_pages == 0 {
             return Ok(0);
         }
 
         // First, figure out which pages need to be fetched (not already in buffer pool)
         let mut pages_to_fetch: Vec<PageId> = Vec::new();
+        let mut frame_ids: Vec<FrameId> = Vec::new();
         {
-            let page_table = self.state.page_table.lock();
+            let mut page_table = self.state.page_table.lock();
+            let write_backs = self.state.write_backs.lock();
+            let deleted_pages = self.state.deleted_pages.lock();
             for i in 0..num_pages {
                 let page_id = PageId::new(start_page_id.as_u32() + i);
-                if !page_table.contains_key(&page_id) {
+                // Pages still being written back would read stale from disk,
+                // and pages in the secondary cache are cheaper to read there
+                if !page_table.contains_key(&page_id)
+                    && !write_backs.contains_key(&page_id)
+                    && !deleted_pages.contains(&page_id)
+                    && !self
+                        .secondary_cache
+                        .as_ref()
+                        .is_some_and(|cache| cache.contains(page_id))
+                {
                     pages_to_fetch.push(page_id);
                 }
             }
+            drop(deleted_pages);
+            drop(write_backs);
+
+            // Get free frames for the pages we need to fetch. They belong to
+            // no page until installed below, so nothing else can touch them.
+            for _ in 0..pages_to_fetch.len() {
+                match self.get_free_frame(&mut page_table) {
+                    Ok(frame_id) => frame_ids.push(frame_id),
+                    Err(_) => break, // No more free frames available
+                }
+            }
         }
 
         if pages_to_fetch.is_empty() {
             return Ok(0); // All pages already in buffer pool
         }
 
-        // Get free frames for the pages we need to fetch
-        let mut frame_ids: Vec<FrameId> = Vec::new();
-        for _ in 0..pages_to_fetch.len() {
-            match self.get_free_frame() {
-                Ok(frame_id) => frame_ids.push(frame_id),
-                Err(_) => break, // No more free frames available
-            }
-        }
-
         if frame_ids.is_empty() {
             return Ok(0); // No frames available
         }
@@ -370,7 +1031,6 @@ impl BufferPoolManager {
         // Limit pages to fetch based on available frames
         let pages_to_fetch: Vec<PageId> =
             pages_to_fetch.into_iter().take(frame_ids.len()).collect();
-        let actual_count = pages_to_fetch.len() as u32;
 
         // Find contiguous ranges and read them sequentially
         // For simplicity, we'll read the entire range from first to last page needed
@@ -379,18 +1039,39 @@ impl BufferPoolManager {
         let range_size = (last_page - first_page + 1) as usize;
 
         // Read all pages in the range with ONE I/O operation
-        let mut bulk_data = vec![0u8; range_size * PAGE_SIZE];
-        self.disk_scheduler.schedule_read_pages_sync(
-            PageId::new(first_page),
-            range_size as u32,
-            &mut bulk_data,
-        )?;
+        let request = DiskRequest::read_sequential(PageId::new(first_page), range_size as u32)
+            .with_class(IoClass::Prefetch);
+        let bulk_data = match self.disk_scheduler.schedule_sync(request) {
+            Ok(bulk_data) => bulk_data,
+            Err(e) => {
+                self.state.free_list.lock().extend(frame_ids);
+                return Err(e);
+            }
+        };
 
         // Distribute pages to frames
         let mut page_table = self.state.page_table.lock();
-        for (i, page_id) in pages_to_fetch.iter().enumerate() {
-            let frame_id = frame_ids[i];
+        let write_backs = self.state.write_backs.lock();
+        let deleted_pages = self.state.deleted_pages.lock();
+        let mut installed = 0;
+        for (i, &frame_id) in frame_ids.iter().enumerate() {
             let frame = &self.state.frames[frame_id.as_usize()];
+            let page_id = match pages_to_fetch.get(i) {
+                // Fetched (and perhaps evicted again) or deleted by another
+                // thread while we were reading
+                Some(page_id)
+                    if !page_table.contains_key(page_id)
+                        && !write_backs.contains_key(page_id)
+                        && !deleted_pages.contains(page_id) =>
+                {
+                    page_id
+                }
+                _ => {
+                    self.state.free_list.lock().push_back(frame_id);
+                    continue;
+                }
+            };
+            installed += 1;
 
             // Calculate offset within bulk_data for this page
             let page_offset = (page_id.as_u32() - first_page) as usize;
@@ -409,102 +1090,296 @@ impl BufferPoolManager {
             // Record access and mark as evictable
             self.state.replacer.record_access(frame_id);
             self.state.replacer.set_evictable(frame_id, true);
+            self.state.track_sticky(*page_id, frame_id);
+            if read_ahead {
+                self.state.read_ahead.lock().installed(*page_id);
+            }
         }
 
-        Ok(actual_count)
+        Ok(installed)
     }
 
     /// Fetches a page into the buffer pool and returns its frame ID.
     /// If the page is already in the pool, returns its current frame.
     /// Otherwise, evicts a page if necessary and reads the page from disk.
-    /// Automatically prefetches ahead if sequential access pattern is detected.
-    fn fetch_page(&self, page_id: PageId) -> Result<FrameId> {
-        {
-            let page_table = self.state.page_table.lock();
-            if let Some(&frame_id) = page_table.get(&page_id) {
-                let frame = &self.state.frames[frame_id.as_usize()];
-                frame.pin();
-                self.state.replacer.record_access(frame_id);
-                self.state.replacer.set_evictable(frame_id, false);
-                return Ok(frame_id);
-            }
+    /// Reads ahead if the access pattern it extends is sequential, as the
+    /// read-ahead policy says.
+    fn fetch_page(&self, page_id: PageId, mut access: Access) -> Result<FrameId> {
+        let mut page_table = self.state.page_table.lock();
+        if let Some(&frame_id) = page_table.get(&page_id) {
+            let frame = &self.state.frames[frame_id.as_usize()];
+            frame.pin();
+            self.state.replacer.record_access(frame_id);
+            self.state.replacer.set_evictable(frame_id, false);
+            // Counts the hit even if this fetch doesn't read ahead
+            let ahead = self
+                .state
+                .read_ahead
+                .lock()
+                .record_hit(page_id, &mut access);
+            drop(page_table);
+            self.read_ahead_of(page_id, ahead);
+            return Ok(frame_id);
         }
 
-        let frame_id = self.get_free_frame()?;
-        let frame = &self.state.frames[frame_id.as_usize()];
-
-        let mut data = [0u8; PAGE_SIZE];
-        self.disk_scheduler.schedule_read_sync(page_id, &mut data)?;
+        if self.state.deleted_pages.lock().contains(&page_id) {
+            return Err(CrioError::PageDeleted(page_id));
+        }
 
+        // Claim a frame and publish the mapping before reading, all under the
+        // page table lock, so concurrent misses on the same page share one
+        // frame. The frame's latch is held across the read; anyone who finds
+        // the page in the meantime waits on it for the data.
+        let frame_id = self.get_free_frame(&mut page_table)?;
+        let frame = &self.state.frames[frame_id.as_usize()];
         frame.set_page_id(page_id);
-        frame.copy_from(&data);
         frame.set_dirty(false);
         frame.pin();
-
-        self.state.page_table.lock().insert(page_id, frame_id);
-
+        page_table.insert(page_id, frame_id);
         self.state.replacer.record_access(frame_id);
         self.state.replacer.set_evictable(frame_id, false);
+        self.state.track_sticky(page_id, frame_id);
+
+        // A page evicted moments ago may not be on disk yet. Take it back from
+        // its write-back copy; the frame stays dirty in case that write fails.
+        let write_back = self
+            .state
+            .write_backs
+            .lock()
+            .get(&page_id)
+            .filter(|write_back| !write_back.reclaimed.swap(true, Ordering::AcqRel))
+            .cloned();
+        if write_back.is_some() {
+            frame.set_dirty(true);
+        }
 
-        self.maybe_prefetch(page_id);
-
-        Ok(frame_id)
-    }
-
-    fn maybe_prefetch(&self, page_id: PageId) {
-        let should_prefetch = {
-            let mut tracker = self.state.access_tracker.lock();
-            tracker.record(page_id);
-            tracker.is_sequential()
+        let mut latch = frame.write_data();
+        drop(page_table);
+        let read = match write_back {
+            Some(write_back) => {
+                latch.copy_from_slice(&write_back.data[..]);
+                Ok(())
+            }
+            None => match &self.secondary_cache {
+                // A cache that fails to read falls back to the backend
+                Some(cache) if cache.read(page_id, &mut latch[..]).unwrap_or(false) => Ok(()),
+                _ => self
+                    .disk_scheduler
+                    .schedule_read_sync(page_id, &mut latch[..]),
+            },
         };
+        if read.is_err() {
+            latch.fill(0);
+        }
+        drop(latch);
 
-        if should_prefetch {
-            let next_page = PageId::new(page_id.as_u32() + 1);
-            let _ = self.prefetch_pages(next_page, PREFETCH_LOOKAHEAD);
+        if let Err(e) = read {
+            self.abandon_fetch(page_id, frame_id);
+            return Err(e);
         }
-    }
+
+        let ahead = self
+            .state
+            .read_ahead
+            .lock()
+            .record_miss(page_id, &mut access);
+        self.read_ahead_of(page_id, ahead);
+
+        Ok(frame_id)
+    }
+
+    /// Drops the pin of a fetch whose read failed, and frees the frame unless
+    /// another thread has pinned the page since.
+    fn abandon_fetch(&self, page_id: PageId, frame_id: FrameId) {
+        let mut page_table = self.state.page_table.lock();
+        let frame = &self.state.frames[frame_id.as_usize()];
+        if frame.unpin() == Some(0) {
+            page_table.remove(&page_id);
+            self.state.replacer.remove(frame_id);
+            frame.reset();
+            self.state.free_list.lock().push_back(frame_id);
+        }
+    }
+
+    /// Prefetches up to `num_pages` pages following `page_id`, as far as the
+    /// backend's `read_ahead_limit` allows: never past the allocated pages
+    /// or into another table. Best effort: a failed read-ahead leaves the
+    /// pages to be fetched on demand.
+    fn read_ahead_of(&self, page_id: PageId, num_pages: u32) {
+        let num_pages = match num_pages {
+            0 => 0,
+            n => self
+                .disk_scheduler
+                .disk_manager()
+                .read_ahead_limit(page_id, n),
+        };
+        if num_pages > 0 {
+            let next_page = PageId::new(page_id.as_u32() + 1);
+            let _ = self.prefetch_range(next_page, num_pages, true);
+        }
+    }
 
     /// Gets a free frame, either from the free list or by evicting a page.
-    fn get_free_frame(&self) -> Result<FrameId> {
+    ///
+    /// Takes the locked page table so that choosing a victim and unmapping
+    /// it is atomic with respect to fetches, which pin under the same lock.
+    /// The returned frame is unmapped and unpinned, and belongs to the caller.
+    fn get_free_frame(&self, page_table: &mut HashMap<PageId, FrameId>) -> Result<FrameId> {
         // Try to get from free list first
-        {
-            let mut free_list = self.state.free_list.lock();
-            if let Some(frame_id) = free_list.pop_front() {
-                return Ok(frame_id);
-            }
+        if let Some(frame_id) = self.state.free_list.lock().pop_front() {
+            return Ok(frame_id);
         }
 
         // Need to evict a page
-        if let Some(frame_id) = self.state.replacer.evict() {
-            let frame = &self.state.frames[frame_id.as_usize()];
-            let old_page_id = frame.page_id();
-
-            // If the page is dirty, flush it to disk first
-            if frame.is_dirty() {
-                let mut data = [0u8; PAGE_SIZE];
-                frame.copy_to(&mut data);
-                self.disk_scheduler
-                    .schedule_write_sync(old_page_id, &data)?;
+        let frame_id = self
+            .state
+            .replacer
+            .evict()
+            .ok_or(CrioError::BufferPoolFull)?;
+        let frame = &self.state.frames[frame_id.as_usize()];
+        let old_page_id = frame.page_id();
+
+        // If the page is dirty, start writing it back; the caller doesn't wait
+        // for the write. Unpinned pages are not latched, so the copy cannot
+        // block.
+        if frame.is_dirty() {
+            if let Err(e) = self.start_write_back(old_page_id, frame) {
+                // Keep the page and leave it evictable for a later attempt
+                self.state.replacer.record_access(frame_id);
+                self.state.replacer.set_evictable(frame_id, true);
+                self.state.track_sticky(old_page_id, frame_id);
+                return Err(e);
             }
+        }
 
-            // Remove from page table
-            self.state.page_table.lock().remove(&old_page_id);
+        // Keep a copy in the secondary cache. Best effort: the backend has
+        // or is getting the page anyway
+        if let Some(cache) = &self.secondary_cache {
+            let mut data = vec![0u8; PAGE_SIZE];
+            frame.copy_to(&mut data);
+            let _ = cache.insert(old_page_id, &data);
+        }
 
-            // Reset the frame
-            frame.reset();
+        // Remove from page table
+        page_table.remove(&old_page_id);
+        self.state.read_ahead.lock().evicted(old_page_id, true);
 
-            Ok(frame_id)
-        } else {
-            Err(CrioError::BufferPoolFull)
+        // Reset the frame
+        frame.reset();
+
+        Ok(frame_id)
+    }
+
+    /// Copies an evicted dirty page aside and schedules its write without
+    /// waiting for it. Retires earlier write-backs that have finished.
+    fn start_write_back(&self, page_id: PageId, frame: &FrameHeader) -> Result<()> {
+        let mut write_backs = self.state.write_backs.lock();
+        write_backs.retain(|_, write_back| !write_back.is_retired());
+
+        // The page was evicted before and fetched back since; that write must
+        // land before this one
+        if let Some(previous) = write_backs.remove(&page_id) {
+            previous.poll(true);
         }
+
+        let mut data = vec![0u8; PAGE_SIZE];
+        frame.copy_to(&mut data);
+        let (tx, rx) = mpsc::channel();
+        let request = DiskRequest::write(page_id, data.clone()).with_callback(tx);
+        self.disk_scheduler.schedule(request)?;
+        write_backs.insert(
+            page_id,
+            Arc::new(WriteBack {
+                data,
+                status: Mutex::new(WriteBackStatus::InFlight(rx)),
+                reclaimed: AtomicBool::new(false),
+            }),
+        );
+        Ok(())
+    }
+
+    /// Waits for every write-back in flight, and rewrites any that failed
+    /// while their page is still out of the pool. Returns the first error of
+    /// a rewrite; that page stays pending so it can be retried.
+    fn finish_write_backs(&self, class: IoClass) -> Result<()> {
+        let pending: Vec<Arc<WriteBack>> =
+            self.state.write_backs.lock().values().cloned().collect();
+        for write_back in &pending {
+            write_back.poll(true);
+        }
+
+        // Rewrite under the lock so that a fetch can't reclaim the page midway
+        let mut result = Ok(());
+        self.state
+            .write_backs
+            .lock()
+            .retain(|&page_id, write_back| {
+                if write_back.poll(false).is_none() {
+                    return true; // Evicted again since we waited
+                }
+                if write_back.is_retired() {
+                    return false;
+                }
+                let request =
+                    DiskRequest::write(page_id, write_back.data.clone()).with_class(class);
+                match self.disk_scheduler.schedule_sync(request) {
+                    Ok(_) => false,
+                    Err(e) => {
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                        true
+                    }
+                }
+            });
+        result
+    }
+}
+
+impl Drop for BufferPoolManager {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::buffer::{Clock, ManualClock};
+    use crate::storage::disk::{DiskManager, MemDiskManager};
     use tempfile::NamedTempFile;
 
+    /// Holds every page write until the test releases `gate`.
+    struct SlowWriteBackend {
+        inner: MemDiskManager,
+        gate: Mutex<()>,
+    }
+
+    impl StorageBackend for SlowWriteBackend {
+        fn read_page(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
+            self.inner.read_page(page_id, data)
+        }
+        fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()> {
+            drop(self.gate.lock());
+            self.inner.write_page(page_id, data)
+        }
+        fn allocate_page(&self) -> Result<PageId> {
+            self.inner.allocate_page()
+        }
+        fn deallocate_page(&self, page_id: PageId) -> Result<()> {
+            self.inner.deallocate_page(page_id)
+        }
+        fn sync(&self) -> Result<()> {
+            Ok(())
+        }
+        fn get_num_reads(&self) -> u32 {
+            self.inner.get_num_reads()
+        }
+        fn get_num_writes(&self) -> u32 {
+            self.inner.get_num_writes()
+        }
+    }
+
     fn create_bpm(pool_size: usize) -> (BufferPoolManager, NamedTempFile) {
         let temp_file = NamedTempFile::new().unwrap();
         let dm = Arc::new(DiskManager::new(temp_file.path()).unwrap());
@@ -578,6 +1453,101 @@ mod tests {
         assert_eq!(guard.data()[0], 42);
     }
 
+    #[test]
+    fn test_discard_page_drops_unwritten_changes() {
+        let (bpm, _temp) = create_bpm(10);
+
+        let page_ids: Vec<_> = (0..3).map(|_| bpm.new_page().unwrap()).collect();
+        for &pid in &page_ids {
+            bpm.checked_write_page(pid).unwrap().unwrap().data_mut()[0] = 1;
+        }
+        bpm.flush_all_pages().unwrap();
+        for &pid in &page_ids {
+            bpm.checked_write_page(pid).unwrap().unwrap().data_mut()[0] = 2;
+        }
+
+        assert!(bpm.discard_page(page_ids[0]).unwrap());
+        assert!(!bpm.discard_page(page_ids[0]).unwrap());
+        assert_eq!(bpm.get_pin_count(page_ids[0]), None);
+        assert_eq!(
+            bpm.checked_read_page(page_ids[0]).unwrap().unwrap().data()[0],
+            1
+        );
+
+        // A pinned page fails the whole batch
+        {
+            let _guard = bpm.checked_read_page(page_ids[2]).unwrap().unwrap();
+            assert!(matches!(
+                bpm.discard_page_range(page_ids[1], 2),
+                Err(CrioError::PageStillPinned(pid)) if pid == page_ids[2]
+            ));
+            assert!(bpm.get_pin_count(page_ids[1]).is_some());
+        }
+
+        assert_eq!(bpm.discard_page_range(page_ids[0], 3).unwrap(), 3);
+        bpm.flush_all_pages().unwrap();
+        for &pid in &page_ids {
+            assert_eq!(bpm.checked_read_page(pid).unwrap().unwrap().data()[0], 1);
+        }
+        assert!(bpm.check_invariants().is_empty());
+    }
+
+    #[test]
+    fn test_untouched_write_guard_is_not_flushed() {
+        let disk = Arc::new(MemDiskManager::new());
+        let bpm = BufferPoolManager::new(4, 2, Arc::clone(&disk) as _);
+
+        let page_id = bpm.new_page().unwrap();
+        bpm.checked_write_page(page_id).unwrap().unwrap().data_mut()[0] = 1;
+        bpm.flush_all_pages().unwrap();
+        let writes = disk.get_num_writes();
+
+        // Latching for write and only reading leaves nothing to flush
+        {
+            let guard = bpm.checked_write_page(page_id).unwrap().unwrap();
+            assert_eq!(guard.data()[0], 1);
+        }
+        {
+            let mut guard = bpm.checked_write_page(page_id).unwrap().unwrap();
+            let _ = guard.data_mut();
+            guard.mark_clean();
+        }
+        bpm.flush_all_pages().unwrap();
+        assert_eq!(disk.get_num_writes(), writes);
+
+        bpm.checked_write_page(page_id).unwrap().unwrap().data_mut()[0] = 2;
+        bpm.flush_all_pages().unwrap();
+        assert_eq!(disk.get_num_writes(), writes + 1);
+    }
+
+    #[test]
+    fn test_optimistic_read_page() {
+        let (bpm, _temp) = create_bpm(1);
+
+        let page_id = bpm.new_page().unwrap();
+        bpm.checked_write_page(page_id).unwrap().unwrap().data_mut()[7] = 9;
+
+        let guard = bpm.optimistic_read_page(page_id).unwrap();
+        let mut byte = [0u8];
+        guard.copy_bytes(7, &mut byte);
+        assert_eq!(byte[0], 9);
+        assert!(guard.validate());
+        assert_eq!(bpm.get_pin_count(page_id), Some(0));
+
+        // Not while a writer holds the page
+        {
+            let _writer = bpm.checked_write_page(page_id).unwrap().unwrap();
+            assert!(bpm.optimistic_read_page(page_id).is_none());
+            assert!(!guard.validate());
+        }
+
+        // Evicting the page fails reads that started before
+        let guard = bpm.optimistic_read_page(page_id).unwrap();
+        bpm.new_page().unwrap();
+        assert!(bpm.optimistic_read_page(page_id).is_none());
+        assert!(!guard.validate());
+    }
+
     #[test]
     fn test_buffer_pool_manager_eviction() {
         let (bpm, _temp) = create_bpm(3);
@@ -601,6 +1571,45 @@ mod tests {
         assert_eq!(new_page_id, PageId::new(4)); // 1,2,3 + new = 4
     }
 
+    #[test]
+    fn test_secondary_cache() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cache = SecondaryCache::new(temp_dir.path().join("cache"), 8).unwrap();
+        let bpm = BufferPoolManager::new(2, 2, Arc::new(MemDiskManager::new()))
+            .with_secondary_cache(cache);
+        let disk = bpm.disk_scheduler.disk_manager();
+
+        let page_ids: Vec<_> = (0..4u8)
+            .map(|i| {
+                let page_id = bpm.new_page().unwrap();
+                bpm.checked_write_page(page_id).unwrap().unwrap().data_mut()[0] = i;
+                page_id
+            })
+            .collect();
+        bpm.flush_all_pages().unwrap();
+
+        // Every page evicted from the pool comes back from the cache
+        let reads_before = disk.get_num_reads();
+        for _ in 0..2 {
+            for (i, &page_id) in page_ids.iter().enumerate() {
+                let guard = bpm.checked_read_page(page_id).unwrap().unwrap();
+                assert_eq!(guard.data()[0], i as u8);
+            }
+        }
+        assert_eq!(disk.get_num_reads(), reads_before);
+        let stats = bpm.secondary_cache_stats().unwrap();
+        assert!(stats.hits >= 6);
+        assert_eq!(stats.misses, 0);
+
+        // A discarded page is read from the backend again
+        let evicted = page_ids[0];
+        bpm.discard_page(evicted).unwrap();
+        let guard = bpm.checked_read_page(evicted).unwrap().unwrap();
+        assert_eq!(guard.data()[0], 0);
+        assert_eq!(disk.get_num_reads(), reads_before + 1);
+        assert_eq!(bpm.secondary_cache_stats().unwrap().misses, 1);
+    }
+
     #[test]
     fn test_buffer_pool_manager_delete_page() {
         let (bpm, _temp) = create_bpm(10);
@@ -618,6 +1627,66 @@ mod tests {
         assert_eq!(bpm.get_pin_count(page_id), None);
     }
 
+    #[test]
+    fn test_deleted_page_cannot_be_fetched() {
+        let bpm = BufferPoolManager::new(10, 2, Arc::new(MemDiskManager::new()));
+        let page_id = bpm.new_page().unwrap();
+        let next = bpm.new_page().unwrap();
+        bpm.checked_write_page(page_id).unwrap().unwrap().data_mut()[0] = 7;
+        assert!(bpm.delete_page(page_id).unwrap());
+
+        assert!(matches!(
+            bpm.checked_read_page(page_id),
+            Err(CrioError::PageDeleted(_))
+        ));
+        assert!(matches!(
+            bpm.checked_write_page(page_id),
+            Err(CrioError::PageDeleted(_))
+        ));
+        assert_eq!(bpm.prefetch_pages(page_id, 2).unwrap(), 0);
+        assert!(bpm.get_pin_count(page_id).is_none());
+        assert!(bpm.get_pin_count(next).is_some());
+
+        // Usable again once the backend hands the page out anew
+        assert_eq!(bpm.new_page().unwrap(), page_id);
+        let guard = bpm.checked_read_page(page_id).unwrap().unwrap();
+        assert_eq!(guard.data()[0], 0);
+    }
+
+    #[test]
+    fn test_delete_page_races_with_fetches() {
+        let bpm = BufferPoolManager::new(16, 2, Arc::new(MemDiskManager::new()));
+        let page_ids: Vec<_> = (0..8).map(|_| bpm.new_page().unwrap()).collect();
+
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..200 {
+                        for &page_id in &page_ids {
+                            match bpm.checked_read_page(page_id) {
+                                Ok(guard) => assert_eq!(guard.unwrap().page_id(), page_id),
+                                Err(CrioError::PageDeleted(_)) => {}
+                                Err(e) => panic!("unexpected error: {}", e),
+                            }
+                        }
+                    }
+                });
+            }
+            s.spawn(|| {
+                for &page_id in &page_ids {
+                    while let Err(CrioError::PageStillPinned(_)) = bpm.delete_page(page_id) {
+                        std::thread::yield_now();
+                    }
+                }
+            });
+        });
+
+        assert!(page_ids
+            .iter()
+            .all(|&page_id| bpm.get_pin_count(page_id).is_none()));
+        assert!(bpm.check_invariants().is_empty());
+    }
+
     #[test]
     fn test_buffer_pool_manager_buffer_pool_full() {
         let (bpm, _temp) = create_bpm(2);
@@ -668,6 +1737,271 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_from_config() {
+        let config = CrioConfig::builder()
+            .pool_size(4)
+            .read_ahead(ReadAheadPolicy::disabled())
+            .build()
+            .unwrap();
+        let bpm = BufferPoolManager::from_config(&config, Arc::new(MemDiskManager::new()));
+        assert_eq!(bpm.pool_size(), 4);
+        assert_eq!(bpm.read_ahead_policy(), ReadAheadPolicy::disabled());
+        let guards: Vec<_> = (0..4)
+            .map(|_| {
+                let page_id = bpm.new_page().unwrap();
+                bpm.checked_read_page(page_id).unwrap().unwrap()
+            })
+            .collect();
+        assert!(matches!(bpm.new_page(), Err(CrioError::BufferPoolFull)));
+        drop(guards);
+    }
+
+    #[test]
+    fn test_read_ahead_policy() {
+        let (bpm, temp) = create_bpm(10);
+        for _ in 0..60 {
+            bpm.new_page().unwrap();
+        }
+        bpm.flush_all_pages().unwrap();
+        drop(bpm);
+
+        let reopen = |policy: ReadAheadPolicy| {
+            let dm = Arc::new(DiskManager::new(temp.path()).unwrap());
+            let bpm = BufferPoolManager::new(100, 2, dm);
+            bpm.set_read_ahead_policy(policy);
+            bpm
+        };
+
+        // A sequential read grows the window as read-ahead pages are used
+        let bpm = reopen(ReadAheadPolicy {
+            max_window: 16,
+            ..ReadAheadPolicy::default()
+        });
+        for page in 1..=60 {
+            bpm.checked_read_page(PageId::new(page)).unwrap().unwrap();
+        }
+        let stats = bpm.read_ahead_stats();
+        assert_eq!(stats.window, 16);
+        assert!(stats.hits >= 50);
+        assert_eq!(stats.wasted, 0);
+
+        // Disabled, or per read, nothing is read ahead
+        let bpm = reopen(ReadAheadPolicy::disabled());
+        for page in 1..=20 {
+            bpm.checked_read_page(PageId::new(page)).unwrap().unwrap();
+        }
+        assert_eq!(bpm.free_frame_count(), 80);
+
+        let bpm = reopen(ReadAheadPolicy::default());
+        for page in 1..=20 {
+            bpm.checked_read_page_without_read_ahead(PageId::new(page))
+                .unwrap()
+                .unwrap();
+        }
+        assert_eq!(bpm.free_frame_count(), 80);
+        assert_eq!(bpm.read_ahead_stats().hits, 0);
+    }
+
+    #[test]
+    fn test_interleaved_scans_read_ahead_separately() {
+        let (bpm, temp) = create_bpm(10);
+        for _ in 0..40 {
+            bpm.new_page().unwrap();
+        }
+        bpm.flush_all_pages().unwrap();
+        drop(bpm);
+        let reopen = || {
+            let dm = Arc::new(DiskManager::new(temp.path()).unwrap());
+            BufferPoolManager::new(100, 2, dm)
+        };
+
+        // One thread alternating between two scans looks random
+        let bpm = reopen();
+        for i in 0..6 {
+            bpm.checked_read_page(PageId::new(1 + i)).unwrap().unwrap();
+            bpm.checked_read_page(PageId::new(21 + i)).unwrap().unwrap();
+        }
+        assert_eq!(bpm.free_frame_count(), 100 - 12);
+
+        // Each with its own context, both read ahead
+        let bpm = reopen();
+        let (mut left, mut right) = (ScanContext::new(), ScanContext::new());
+        for i in 0..6 {
+            bpm.checked_read_page_in(PageId::new(1 + i), &mut left)
+                .unwrap()
+                .unwrap();
+            bpm.checked_read_page_in(PageId::new(21 + i), &mut right)
+                .unwrap()
+                .unwrap();
+        }
+        assert!(bpm.get_pin_count(PageId::new(8)).is_some());
+        assert!(bpm.get_pin_count(PageId::new(28)).is_some());
+
+        // So do scans on different threads
+        let bpm = reopen();
+        std::thread::scope(|s| {
+            for start in [1, 21] {
+                let bpm = &bpm;
+                s.spawn(move || {
+                    for page in start..start + 6 {
+                        bpm.checked_read_page(PageId::new(page)).unwrap().unwrap();
+                    }
+                });
+            }
+        });
+        assert!(bpm.get_pin_count(PageId::new(8)).is_some());
+        assert!(bpm.get_pin_count(PageId::new(28)).is_some());
+    }
+
+    #[test]
+    fn test_read_ahead_stays_within_table() {
+        let temp = NamedTempFile::new().unwrap();
+        let dm = Arc::new(DiskManager::new(temp.path()).unwrap());
+        let t1 = dm.allocate_extent_for_table(1).unwrap();
+        let t2 = dm.allocate_extent_for_table(2).unwrap();
+        let bpm = BufferPoolManager::new(32, 2, dm);
+
+        // Reading a table's extent front to back reads ahead only up to its end
+        for &page_id in &t1 {
+            bpm.checked_read_page(page_id).unwrap().unwrap();
+        }
+        assert!(bpm.read_ahead_stats().hits > 0);
+        assert!(t2
+            .iter()
+            .all(|&page_id| bpm.get_pin_count(page_id).is_none()));
+
+        // Nor past the last allocated page
+        for &page_id in &t2 {
+            bpm.checked_read_page(page_id).unwrap().unwrap();
+        }
+        assert_eq!(bpm.free_frame_count(), 32 - 16);
+        assert_eq!(bpm.read_ahead_stats().outstanding, 0);
+    }
+
+    #[test]
+    fn test_read_ahead_shrinks_when_pages_are_wasted() {
+        let (bpm, temp) = create_bpm(10);
+        for _ in 0..60 {
+            bpm.new_page().unwrap();
+        }
+        bpm.flush_all_pages().unwrap();
+        drop(bpm);
+
+        // A tiny pool evicts read-ahead pages before the reader gets to them
+        let dm = Arc::new(DiskManager::new(temp.path()).unwrap());
+        let bpm = BufferPoolManager::new(6, 2, dm);
+        bpm.set_read_ahead_policy(ReadAheadPolicy {
+            window: 8,
+            ..ReadAheadPolicy::default()
+        });
+        for page in (1..=40).step_by(3) {
+            for page in page..page + 3 {
+                bpm.checked_read_page(PageId::new(page)).unwrap().unwrap();
+            }
+            // Random reads elsewhere push the read-ahead pages out
+            for page in [55, 42, 58, 47] {
+                bpm.checked_read_page(PageId::new(page)).unwrap().unwrap();
+            }
+        }
+        let stats = bpm.read_ahead_stats();
+        assert!(stats.wasted > 0);
+        assert!(stats.window < 8);
+    }
+
+    #[test]
+    fn test_with_write_batch() {
+        let (bpm, temp) = create_bpm(10);
+
+        let page_ids: Vec<_> = (0..3).map(|_| bpm.new_page().unwrap()).collect();
+        let writes_before = bpm.disk_scheduler.disk_manager().get_num_writes();
+
+        // Hand pages over out of order; guards come back in the requested order
+        let batch = [page_ids[2], page_ids[0], page_ids[1]];
+        let total = bpm
+            .with_write_batch(&batch, |guards| {
+                for guard in guards.iter_mut() {
+                    let pid = guard.page_id().as_u32() as u8;
+                    guard.data_mut()[0] = pid;
+                }
+                Ok(guards.len())
+            })
+            .unwrap();
+        assert_eq!(total, 3);
+
+        // One coalesced write for the contiguous run
+        assert_eq!(
+            bpm.disk_scheduler.disk_manager().get_num_writes(),
+            writes_before + 1
+        );
+        for &pid in &page_ids {
+            assert_eq!(bpm.get_pin_count(pid), Some(0));
+        }
+
+        drop(bpm);
+        let dm = Arc::new(DiskManager::new(temp.path()).unwrap());
+        let bpm2 = BufferPoolManager::new(10, 2, dm);
+        for &pid in &page_ids {
+            let guard = bpm2.checked_read_page(pid).unwrap().unwrap();
+            assert_eq!(guard.data()[0], pid.as_u32() as u8);
+        }
+    }
+
+    #[test]
+    fn test_with_write_batch_errors() {
+        let (bpm, _temp) = create_bpm(10);
+
+        let page_id = bpm.new_page().unwrap();
+        assert!(matches!(
+            bpm.with_write_batch(&[page_id, page_id], |_| Ok(())),
+            Err(CrioError::DuplicatePageInBatch(_))
+        ));
+
+        // A failing closure writes nothing but keeps the changes buffered
+        let writes_before = bpm.disk_scheduler.disk_manager().get_num_writes();
+        let result: Result<()> = bpm.with_write_batch(&[page_id], |guards| {
+            guards[0].data_mut()[0] = 7;
+            Err(CrioError::PageFull)
+        });
+        assert!(result.is_err());
+        assert_eq!(
+            bpm.disk_scheduler.disk_manager().get_num_writes(),
+            writes_before
+        );
+
+        let guard = bpm.checked_read_page(page_id).unwrap().unwrap();
+        assert_eq!(guard.data()[0], 7);
+        let frame_id = *bpm.state.page_table.lock().get(&page_id).unwrap();
+        assert!(bpm.state.frames[frame_id.as_usize()].is_dirty());
+    }
+
+    #[test]
+    fn test_dump_pins() {
+        let (bpm, _temp) = create_bpm(10);
+
+        let page_id1 = bpm.new_page().unwrap();
+        let page_id2 = bpm.new_page().unwrap();
+        assert!(bpm.dump_pins(Duration::ZERO).is_empty());
+
+        let _leaked = bpm.checked_read_page(page_id2).unwrap().unwrap();
+        let _leaked2 = bpm.checked_read_page(page_id2).unwrap().unwrap();
+        {
+            let _short = bpm.checked_read_page(page_id1).unwrap().unwrap();
+        }
+
+        let pins = bpm.dump_pins(Duration::ZERO);
+        assert_eq!(pins.len(), 1);
+        assert_eq!(pins[0].page_id, page_id2);
+        assert_eq!(pins[0].pin_count, 2);
+
+        #[cfg(feature = "pin-tracking")]
+        {
+            assert_eq!(pins[0].holders.len(), 2);
+            assert!(!pins[0].holders[0].backtrace.is_empty());
+            assert!(bpm.dump_pins(Duration::from_secs(3600)).is_empty());
+        }
+    }
+
     #[test]
     fn test_prefetch_skips_cached_pages() {
         let (bpm, _temp) = create_bpm(10);
@@ -682,4 +2016,146 @@ mod tests {
 
         assert_eq!(prefetched, 0);
     }
+
+    #[test]
+    fn test_with_replacer_uses_its_clock() {
+        let clock = Arc::new(ManualClock::new(0));
+        let replacer = LruKReplacer::with_clock(2, 2, Arc::clone(&clock) as Arc<dyn Clock>)
+            .with_correlated_reference_period(10);
+        let bpm = BufferPoolManager::with_replacer(2, replacer, Arc::new(MemDiskManager::new()));
+
+        let page_a = bpm.new_page().unwrap();
+        clock.advance(20);
+        drop(bpm.checked_read_page(page_a).unwrap());
+
+        // Three quick reads of b are one correlated reference
+        let page_b = bpm.new_page().unwrap();
+        clock.advance(1);
+        for _ in 0..3 {
+            drop(bpm.checked_read_page(page_b).unwrap());
+        }
+
+        bpm.new_page().unwrap();
+        assert!(bpm.get_pin_count(page_a).is_some());
+        assert!(bpm.get_pin_count(page_b).is_none());
+    }
+
+    #[test]
+    fn test_sticky_pages_are_evicted_last() {
+        let bpm = BufferPoolManager::new(2, 2, Arc::new(MemDiskManager::new()));
+        let resident = |page_id| bpm.get_pin_count(page_id).is_some();
+
+        // a is the coldest page but sticky, so b and then c make room
+        let page_a = bpm.new_page().unwrap();
+        let page_b = bpm.new_page().unwrap();
+        bpm.set_sticky(page_a);
+        let page_c = bpm.new_page().unwrap();
+        assert!(resident(page_a) && !resident(page_b));
+        let page_d = bpm.new_page().unwrap();
+        assert!(resident(page_a) && !resident(page_c));
+
+        // Only sticky pages left: the coldest of them goes
+        bpm.set_sticky(page_d);
+        let page_e = bpm.new_page().unwrap();
+        assert!(!resident(page_a) && resident(page_d));
+
+        // Stickiness outlives eviction and applies when the page comes back
+        assert!(bpm.is_sticky(page_a));
+        bpm.clear_sticky(page_d);
+        drop(bpm.checked_read_page(page_a).unwrap());
+        assert!(!resident(page_d));
+        bpm.new_page().unwrap();
+        assert!(resident(page_a) && !resident(page_e));
+
+        bpm.clear_sticky(page_a);
+        assert!(!bpm.is_sticky(page_a));
+        bpm.new_page().unwrap();
+        assert!(!resident(page_a));
+    }
+
+    #[test]
+    fn test_eviction_does_not_wait_for_write_back() {
+        let backend = Arc::new(SlowWriteBackend {
+            inner: MemDiskManager::new(),
+            gate: Mutex::new(()),
+        });
+        let bpm = BufferPoolManager::new(1, 2, Arc::clone(&backend) as Arc<dyn StorageBackend>);
+
+        let page_a = bpm.new_page().unwrap();
+        bpm.checked_write_page(page_a)
+            .unwrap()
+            .unwrap()
+            .data_mut()
+            .fill(0xAB);
+
+        // With writes stuck, evicting the dirty page must not block
+        let gate = backend.gate.lock();
+        let page_b = bpm.new_page().unwrap();
+        assert!(bpm.get_pin_count(page_a).is_none());
+
+        // Fetching it back before the write lands sees the evicted data
+        {
+            let guard = bpm.checked_read_page(page_a).unwrap().unwrap();
+            assert!(guard.data().iter().all(|&b| b == 0xAB));
+        }
+        assert_eq!(backend.get_num_writes(), 0);
+        assert!(bpm.get_pin_count(page_b).is_none());
+
+        drop(gate);
+        bpm.flush_all_pages().unwrap();
+        let mut on_disk = [0u8; PAGE_SIZE];
+        backend.read_page(page_a, &mut on_disk).unwrap();
+        assert!(on_disk.iter().all(|&b| b == 0xAB));
+        assert!(bpm.check_invariants().is_empty());
+    }
+
+    #[test]
+    fn test_write_back_lands_after_eviction() {
+        let (bpm, _temp_file) = create_bpm(2);
+        let pages: Vec<PageId> = (0..6).map(|_| bpm.new_page().unwrap()).collect();
+        for (i, &page_id) in pages.iter().enumerate() {
+            bpm.checked_write_page(page_id).unwrap().unwrap().data_mut()[0] = i as u8 + 1;
+        }
+
+        // Cycle every page through the two frames a few times
+        for _ in 0..3 {
+            for (i, &page_id) in pages.iter().enumerate() {
+                let mut guard = bpm.checked_write_page(page_id).unwrap().unwrap();
+                assert_eq!(guard.data()[0], i as u8 + 1);
+                guard.data_mut()[1] += 1;
+            }
+        }
+
+        bpm.flush_all_pages().unwrap();
+        let mut data = [0u8; PAGE_SIZE];
+        for (i, &page_id) in pages.iter().enumerate() {
+            bpm.disk_scheduler
+                .schedule_read_sync(page_id, &mut data)
+                .unwrap();
+            assert_eq!(&data[..2], &[i as u8 + 1, 3]);
+        }
+    }
+
+    #[test]
+    fn test_frames_share_one_slab() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let dm = Arc::new(DiskManager::new(temp_file.path()).unwrap());
+        let bpm = BufferPoolManager::with_huge_pages(4, 2, dm, true);
+
+        let frames = &bpm.state.frames;
+        let slab = frames[0].slab();
+        for (i, frame) in frames.iter().enumerate() {
+            assert!(Arc::ptr_eq(frame.slab(), slab));
+            let offset = frame.read_data().as_ptr() as usize - slab.as_ptr() as usize;
+            assert_eq!(offset, i * PAGE_SIZE);
+        }
+
+        let page_id = bpm.new_page().unwrap();
+        bpm.checked_write_page(page_id).unwrap().unwrap().data_mut()[7] = 7;
+        bpm.flush_page(page_id).unwrap();
+        assert_eq!(
+            bpm.checked_read_page(page_id).unwrap().unwrap().data()[7],
+            7
+        );
+    }
 }
@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use crate::common::Timestamp;
+
+/// Source of the access timestamps recorded by the LRU-K replacer.
+///
+/// Timestamps must never go backwards. They are only compared with each
+/// other, so their unit is up to the clock; a correlated reference period is
+/// measured in the same unit.
+pub trait Clock: Send + Sync {
+    /// Returns the timestamp of an access happening now.
+    fn now(&self) -> Timestamp;
+}
+
+/// Counts accesses: every call returns the next integer. The default clock,
+/// which makes the replacer's behaviour independent of wall time.
+#[derive(Debug, Default)]
+pub struct LogicalClock {
+    next: AtomicU64,
+}
+
+impl LogicalClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clock for LogicalClock {
+    fn now(&self) -> Timestamp {
+        self.next.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// Microseconds since the clock was created, for policies that age pages
+/// by elapsed time rather than by the number of accesses in between.
+#[derive(Debug)]
+pub struct WallClock {
+    start: Instant,
+}
+
+impl WallClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for WallClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for WallClock {
+    fn now(&self) -> Timestamp {
+        self.start.elapsed().as_micros() as Timestamp
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: AtomicU64,
+}
+
+impl ManualClock {
+    pub fn new(start: Timestamp) -> Self {
+        Self {
+            now: AtomicU64::new(start),
+        }
+    }
+
+    /// Moves the clock forward by `delta`.
+    pub fn advance(&self, delta: Timestamp) {
+        self.now.fetch_add(delta, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Timestamp {
+        self.now.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clocks() {
+        let logical = LogicalClock::new();
+        assert_eq!((logical.now(), logical.now()), (0, 1));
+
+        let manual = ManualClock::new(10);
+        assert_eq!(manual.now(), 10);
+        assert_eq!(manual.now(), 10);
+        manual.advance(5);
+        assert_eq!(manual.now(), 15);
+
+        let wall = WallClock::new();
+        let first = wall.now();
+        assert!(wall.now() >= first);
+    }
+}
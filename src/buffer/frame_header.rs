@@ -1,9 +1,12 @@
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
 
 use parking_lot::RwLock;
 
 use crate::common::{FrameId, PageId, INVALID_PAGE_ID, PAGE_SIZE};
 
+use super::{FrameData, FrameSlab};
+
 /// FrameHeader manages a single buffer frame in the buffer pool.
 /// It stores metadata about the frame and the actual page data.
 pub struct FrameHeader {
@@ -15,19 +18,43 @@ pub struct FrameHeader {
     pin_count: AtomicU32,
     /// Whether the page has been modified since being read from disk
     is_dirty: AtomicBool,
-    /// The actual page data (pub(crate) for page guard access)
-    pub(crate) data: RwLock<Box<[u8; PAGE_SIZE]>>,
+    /// Incremented every time a write latch is taken on the page data or the
+    /// frame changes pages
+    version: AtomicU64,
+    /// The actual page data, a frame of `slab` (pub(crate) for page guard access)
+    pub(crate) data: Arc<RwLock<FrameData>>,
+    /// Address of the page data, for latch-free reads
+    data_addr: usize,
+    /// The slab `data` belongs to
+    slab: Arc<FrameSlab>,
 }
 
 impl FrameHeader {
-    /// Creates a new FrameHeader for the given frame ID.
+    /// Creates a new FrameHeader for the given frame ID, with its own memory.
     pub fn new(frame_id: FrameId) -> Self {
+        let slab = Arc::new(FrameSlab::new(1, false));
+        let data = slab.take_frame(FrameId::new(0));
+        Self::with_data(frame_id, data, slab)
+    }
+
+    /// Creates the header of frame `frame_id` of a shared slab. Panics if
+    /// another header already has that frame.
+    pub(crate) fn in_slab(frame_id: FrameId, slab: &Arc<FrameSlab>) -> Self {
+        let data = slab.take_frame(frame_id);
+        Self::with_data(frame_id, data, Arc::clone(slab))
+    }
+
+    fn with_data(frame_id: FrameId, data: FrameData, slab: Arc<FrameSlab>) -> Self {
+        let data_addr = data.as_ptr() as usize;
         Self {
             frame_id,
             page_id: RwLock::new(INVALID_PAGE_ID),
             pin_count: AtomicU32::new(0),
             is_dirty: AtomicBool::new(false),
-            data: RwLock::new(Box::new([0u8; PAGE_SIZE])),
+            version: AtomicU64::new(0),
+            data: Arc::new(RwLock::new(data)),
+            data_addr,
+            slab,
         }
     }
 
@@ -44,6 +71,7 @@ impl FrameHeader {
     /// Sets the page ID stored in this frame.
     pub fn set_page_id(&self, page_id: PageId) {
         *self.page_id.write() = page_id;
+        self.bump_version();
     }
 
     /// Returns the current pin count.
@@ -84,20 +112,76 @@ impl FrameHeader {
         self.is_dirty.store(dirty, Ordering::Release);
     }
 
+    /// Returns the write-latch version of the frame.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Bumps the write-latch version, failing optimistic reads in progress.
+    /// Called whenever the data or the page it holds changes.
+    pub(crate) fn bump_version(&self) {
+        self.version.fetch_add(1, Ordering::AcqRel);
+    }
+
     /// Returns a read guard to the page data.
-    pub fn read_data(&self) -> parking_lot::RwLockReadGuard<'_, Box<[u8; PAGE_SIZE]>> {
+    pub fn read_data(&self) -> parking_lot::RwLockReadGuard<'_, FrameData> {
         self.data.read()
     }
 
     /// Returns a write guard to the page data.
-    pub fn write_data(&self) -> parking_lot::RwLockWriteGuard<'_, Box<[u8; PAGE_SIZE]>> {
-        self.data.write()
+    pub fn write_data(&self) -> parking_lot::RwLockWriteGuard<'_, FrameData> {
+        let guard = self.data.write();
+        self.bump_version();
+        guard
+    }
+
+    /// Returns true if a writer holds the page data latch.
+    pub fn is_write_latched(&self) -> bool {
+        self.data.is_locked_exclusive()
+    }
+
+    /// Copies `buf.len()` bytes at `offset` out of the page data without
+    /// taking the latch. A concurrent writer can tear the copy, so callers
+    /// must check `version` and `is_write_latched` before and after.
+    ///
+    /// The bytes are read with relaxed atomic loads, a word at a time where
+    /// aligned, so a racing write gives a stale or torn value rather than
+    /// undefined behavior. The caller's acquire fence orders the loads
+    /// before its version check.
+    pub(crate) fn copy_unlatched(&self, offset: usize, buf: &mut [u8]) {
+        assert!(
+            offset + buf.len() <= PAGE_SIZE,
+            "copy past the end of the page"
+        );
+        let src = self.data_addr + offset;
+        let word = std::mem::size_of::<u64>();
+        let mut i = 0;
+        // Safety (all loads): within the frame, which `slab` keeps alive, and
+        // suitably aligned for the atomic type read
+        while i < buf.len() && !(src + i).is_multiple_of(word) {
+            buf[i] = unsafe { &*((src + i) as *const AtomicU8) }.load(Ordering::Relaxed);
+            i += 1;
+        }
+        while i + word <= buf.len() {
+            let value = unsafe { &*((src + i) as *const AtomicU64) }.load(Ordering::Relaxed);
+            buf[i..i + word].copy_from_slice(&value.to_ne_bytes());
+            i += word;
+        }
+        while i < buf.len() {
+            buf[i] = unsafe { &*((src + i) as *const AtomicU8) }.load(Ordering::Relaxed);
+            i += 1;
+        }
+    }
+
+    /// Returns the slab holding this frame's data.
+    pub fn slab(&self) -> &Arc<FrameSlab> {
+        &self.slab
     }
 
     /// Copies data from the given slice into the frame.
     pub fn copy_from(&self, data: &[u8]) {
         assert_eq!(data.len(), PAGE_SIZE);
-        let mut guard = self.data.write();
+        let mut guard = self.write_data();
         guard.copy_from_slice(data);
     }
 
@@ -110,10 +194,11 @@ impl FrameHeader {
 
     /// Resets the frame to its initial state.
     pub fn reset(&self) {
+        let mut data = self.write_data();
         *self.page_id.write() = INVALID_PAGE_ID;
         self.pin_count.store(0, Ordering::Release);
         self.is_dirty.store(false, Ordering::Release);
-        self.data.write().fill(0);
+        data.fill(0);
     }
 }
 
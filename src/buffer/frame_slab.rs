@@ -0,0 +1,179 @@
+use std::alloc::{self, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::common::{FrameId, PAGE_SIZE};
+
+/// Alignment of every frame: a whole OS page, as O_DIRECT requires.
+pub const FRAME_ALIGN: usize = 4096;
+
+/// Alignment of a slab allocated for huge pages.
+pub const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// One contiguous, page-aligned allocation holding the data of every frame in
+/// a buffer pool. Frame `i` lives at offset `i * PAGE_SIZE`.
+pub struct FrameSlab {
+    ptr: NonNull<u8>,
+    layout: Layout,
+    frames: usize,
+    huge_pages: bool,
+    /// Which frames have been handed out
+    taken: Box<[AtomicBool]>,
+}
+
+// Safety: the slab hands each frame out at most once, and FrameData owns it
+unsafe impl Send for FrameSlab {}
+unsafe impl Sync for FrameSlab {}
+
+impl FrameSlab {
+    /// Allocates zeroed memory for `frames` frames. With `huge_pages`, the
+    /// slab is aligned and sized for 2 MiB pages and, on Linux, the kernel is
+    /// advised to back it with transparent huge pages.
+    pub fn new(frames: usize, huge_pages: bool) -> Self {
+        let frames = frames.max(1);
+        let (align, size) = if huge_pages {
+            let size = (frames * PAGE_SIZE).div_ceil(HUGE_PAGE_SIZE) * HUGE_PAGE_SIZE;
+            (HUGE_PAGE_SIZE, size)
+        } else {
+            (FRAME_ALIGN.max(PAGE_SIZE), frames * PAGE_SIZE)
+        };
+        let layout = Layout::from_size_align(size, align).expect("frame slab too large");
+
+        // Safety: the layout has a non-zero size
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+
+        let huge_pages = huge_pages && advise_huge_pages(ptr, size);
+        Self {
+            ptr,
+            layout,
+            frames,
+            huge_pages,
+            taken: (0..frames).map(|_| AtomicBool::new(false)).collect(),
+        }
+    }
+
+    /// Returns the number of frames in the slab.
+    pub fn frame_count(&self) -> usize {
+        self.frames
+    }
+
+    /// Returns true if the kernel accepted the huge page advice.
+    pub fn huge_pages(&self) -> bool {
+        self.huge_pages
+    }
+
+    /// Returns the start of the slab.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr.as_ptr()
+    }
+
+    /// Hands out the data of one frame. Each frame can be taken only once,
+    /// so its `FrameData` is the only way to reach that memory.
+    pub(crate) fn take_frame(self: &Arc<Self>, frame_id: FrameId) -> FrameData {
+        let index = frame_id.as_usize();
+        assert!(index < self.frames, "frame {} outside the slab", index);
+        assert!(
+            !self.taken[index].swap(true, Ordering::AcqRel),
+            "frame {} taken twice",
+            index
+        );
+        // Safety: in bounds, and unaliased since it was never taken before
+        let ptr = unsafe { self.ptr.as_ptr().add(index * PAGE_SIZE) };
+        FrameData {
+            ptr: NonNull::new(ptr as *mut [u8; PAGE_SIZE]).unwrap(),
+            _slab: Arc::clone(self),
+        }
+    }
+}
+
+impl Drop for FrameSlab {
+    fn drop(&mut self) {
+        // Safety: allocated in `new` with this layout
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn advise_huge_pages(ptr: NonNull<u8>, size: usize) -> bool {
+    // Safety: the range is a live allocation aligned to the huge page size
+    unsafe { libc::madvise(ptr.as_ptr() as *mut libc::c_void, size, libc::MADV_HUGEPAGE) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_huge_pages(_ptr: NonNull<u8>, _size: usize) -> bool {
+    false
+}
+
+/// The data of a single frame: a page-aligned `PAGE_SIZE` region of a
+/// `FrameSlab`, which it keeps alive.
+pub struct FrameData {
+    ptr: NonNull<[u8; PAGE_SIZE]>,
+    _slab: Arc<FrameSlab>,
+}
+
+// Safety: FrameData is the only handle to its frame's memory
+unsafe impl Send for FrameData {}
+unsafe impl Sync for FrameData {}
+
+impl Deref for FrameData {
+    type Target = [u8; PAGE_SIZE];
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: the frame is live and not aliased
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl DerefMut for FrameData {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: the frame is live and not aliased
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_are_aligned_and_contiguous() {
+        let slab = Arc::new(FrameSlab::new(8, false));
+        assert_eq!(slab.as_ptr() as usize % FRAME_ALIGN, 0);
+
+        for i in 0..8 {
+            let frame = slab.take_frame(FrameId::new(i));
+            let offset = frame.as_ptr() as usize - slab.as_ptr() as usize;
+            assert_eq!(offset, i as usize * PAGE_SIZE);
+            assert!(frame.iter().all(|&b| b == 0));
+        }
+    }
+
+    #[test]
+    fn test_huge_page_slab_alignment() {
+        let slab = Arc::new(FrameSlab::new(3, true));
+        assert_eq!(slab.as_ptr() as usize % HUGE_PAGE_SIZE, 0);
+        assert_eq!(slab.frame_count(), 3);
+
+        let mut last = slab.take_frame(FrameId::new(2));
+        last.fill(0x5A);
+        assert_eq!(last[PAGE_SIZE - 1], 0x5A);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside the slab")]
+    fn test_frame_outside_slab() {
+        let slab = Arc::new(FrameSlab::new(2, false));
+        let _ = slab.take_frame(FrameId::new(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "taken twice")]
+    fn test_frame_taken_twice() {
+        let slab = Arc::new(FrameSlab::new(2, false));
+        let _first = slab.take_frame(FrameId::new(1));
+        let _second = slab.take_frame(FrameId::new(1));
+    }
+}
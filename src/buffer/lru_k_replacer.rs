@@ -1,8 +1,9 @@
-use std::collections::{HashMap, VecDeque};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Arc;
 
 use parking_lot::Mutex;
 
+use super::{Clock, LogicalClock};
 use crate::common::{FrameId, Timestamp};
 
 /// Tracks access history for a single frame
@@ -10,43 +11,74 @@ use crate::common::{FrameId, Timestamp};
 struct FrameAccessInfo {
     /// History of access timestamps (most recent at back)
     history: VecDeque<Timestamp>,
+    /// Most recent access, correlated or not
+    last_access: Timestamp,
     /// Whether this frame is currently evictable
     is_evictable: bool,
+    /// Whether this frame is only evicted when no other frame can be
+    is_sticky: bool,
 }
 
 impl FrameAccessInfo {
     fn new() -> Self {
         Self {
             history: VecDeque::new(),
+            last_access: 0,
             is_evictable: false,
+            is_sticky: false,
         }
     }
 
-    /// Records an access at the given timestamp
-    fn record_access(&mut self, timestamp: Timestamp, k: usize) {
+    /// Records an access at the given timestamp. An access less than
+    /// `correlated_period` after the previous one belongs to the same burst
+    /// and only moves `last_access`. When the next uncorrelated access
+    /// arrives, the older history is shifted forward by the burst's length,
+    /// so time spent inside a burst does not count towards the k-distance.
+    fn record_access(&mut self, timestamp: Timestamp, k: usize, correlated_period: Timestamp) {
+        if let Some(&latest) = self.history.back() {
+            if timestamp.saturating_sub(self.last_access) < correlated_period {
+                self.last_access = self.last_access.max(timestamp);
+                return;
+            }
+            let burst = self.last_access - latest;
+            for access in self.history.iter_mut() {
+                *access += burst;
+            }
+        }
         self.history.push_back(timestamp);
+        self.last_access = timestamp;
         // Keep only the last k accesses
         while self.history.len() > k {
             self.history.pop_front();
         }
     }
 
-    /// Returns the k-distance (backward k-distance from current timestamp)
-    /// Returns None if this frame has fewer than k accesses (meaning +inf distance)
-    fn k_distance(&self, current_timestamp: Timestamp, k: usize) -> Option<Timestamp> {
-        if self.history.len() < k {
-            None // +inf distance
-        } else {
-            // The kth previous access is at index (len - k)
-            // k-distance = current_timestamp - timestamp_of_kth_previous_access
-            Some(current_timestamp - self.history[self.history.len() - k])
-        }
+    /// Returns this frame's position in the eviction order; the smallest key
+    /// is evicted first.
+    ///
+    /// Frames with fewer than k accesses have +inf backward k-distance and
+    /// come first, by earliest access; frames never accessed come last among
+    /// them. The other frames follow by their kth previous access: the
+    /// earlier it is, the larger the k-distance. Either way the timestamp is
+    /// the front of the history, so keys never depend on the current time.
+    /// Sticky frames come after all others, in the same order among
+    /// themselves.
+    fn eviction_key(&self, frame_id: FrameId, k: usize) -> EvictionKey {
+        let infinite = self.history.len() < k;
+        let timestamp = self.history.front().copied().unwrap_or(Timestamp::MAX);
+        (self.is_sticky, !infinite, timestamp, frame_id)
     }
+}
 
-    /// Returns the earliest timestamp in the history
-    fn earliest_timestamp(&self) -> Option<Timestamp> {
-        self.history.front().copied()
-    }
+/// (sticky, finite k-distance, timestamp, frame), ordered by eviction priority
+type EvictionKey = (bool, bool, Timestamp, FrameId);
+
+/// Replacer state guarded by one mutex.
+struct ReplacerState {
+    /// Access information, indexed by frame ID
+    frames: Vec<Option<FrameAccessInfo>>,
+    /// Eviction keys of the evictable frames
+    evictable: BTreeSet<EvictionKey>,
 }
 
 /// LRU-K Replacement Policy
@@ -58,87 +90,60 @@ impl FrameAccessInfo {
 /// A frame with fewer than k historical accesses is given +inf as its backward k-distance.
 /// If multiple frames have +inf backward k-distance, the replacer evicts the frame
 /// with the earliest overall timestamp.
+///
+/// Evictable frames are kept in a set ordered by eviction priority, so `evict`,
+/// `record_access`, `set_evictable` and `remove` are all O(log n).
+///
+/// Timestamps come from a `Clock`, by default a `LogicalClock` counting
+/// accesses. With a correlated reference period, repeated accesses to a
+/// frame in quick succession (such as the reads of one query) count as one.
 pub struct LruKReplacer {
     /// K value for the LRU-K algorithm
     k: usize,
     /// Maximum number of frames the replacer can track
     max_frames: usize,
-    /// Current timestamp (monotonically increasing)
-    current_timestamp: AtomicU64,
-    /// Access information for each frame
-    frame_info: Mutex<HashMap<FrameId, FrameAccessInfo>>,
-    /// Number of evictable frames
-    num_evictable: Mutex<usize>,
+    /// Source of access timestamps
+    clock: Arc<dyn Clock>,
+    /// Accesses closer than this to a frame's previous one are correlated
+    correlated_period: Timestamp,
+    state: Mutex<ReplacerState>,
 }
 
 impl LruKReplacer {
     /// Creates a new LRU-K replacer with the given k value and maximum frame count.
     pub fn new(k: usize, max_frames: usize) -> Self {
+        Self::with_clock(k, max_frames, Arc::new(LogicalClock::new()))
+    }
+
+    /// Creates a new LRU-K replacer that takes its timestamps from `clock`.
+    pub fn with_clock(k: usize, max_frames: usize, clock: Arc<dyn Clock>) -> Self {
         Self {
             k,
             max_frames,
-            current_timestamp: AtomicU64::new(0),
-            frame_info: Mutex::new(HashMap::new()),
-            num_evictable: Mutex::new(0),
+            clock,
+            correlated_period: 0,
+            state: Mutex::new(ReplacerState {
+                frames: (0..max_frames).map(|_| None).collect(),
+                evictable: BTreeSet::new(),
+            }),
         }
     }
 
+    /// Treats accesses to a frame less than `period` apart, in the clock's
+    /// unit, as one correlated reference. Zero, the default, correlates
+    /// nothing.
+    pub fn with_correlated_reference_period(mut self, period: Timestamp) -> Self {
+        self.correlated_period = period;
+        self
+    }
+
     /// Evicts the frame with the largest backward k-distance.
     /// Returns None if there are no evictable frames.
     pub fn evict(&self) -> Option<FrameId> {
-        let mut frame_info = self.frame_info.lock();
-        let mut num_evictable = self.num_evictable.lock();
-
-        if *num_evictable == 0 {
-            return None;
-        }
-
-        let current_ts = self.current_timestamp.load(Ordering::Relaxed);
-
-        // Find the frame with the largest k-distance
-        // Frames with +inf distance (fewer than k accesses) have priority
-        // Among +inf frames, pick the one with earliest timestamp
-
-        let mut victim: Option<FrameId> = None;
-        let mut victim_k_dist: Option<Timestamp> = None;
-        let mut victim_earliest_ts: Option<Timestamp> = None;
-
-        for (frame_id, info) in frame_info.iter() {
-            if !info.is_evictable {
-                continue;
-            }
-
-            let k_dist = info.k_distance(current_ts, self.k);
-            let earliest_ts = info.earliest_timestamp();
-
-            let should_replace = match (victim_k_dist, k_dist) {
-                // Current victim has +inf, candidate has finite -> don't replace
-                (None, Some(_)) => false,
-                // Current victim has finite, candidate has +inf -> replace
-                (Some(_), None) => true,
-                // Both have +inf -> compare earliest timestamps
-                (None, None) => match (victim_earliest_ts, earliest_ts) {
-                    (Some(v_ts), Some(c_ts)) => c_ts < v_ts,
-                    (None, Some(_)) => true,
-                    _ => false,
-                },
-                // Both have finite k-distance -> pick larger one
-                (Some(v_dist), Some(c_dist)) => c_dist > v_dist,
-            };
-
-            if victim.is_none() || should_replace {
-                victim = Some(*frame_id);
-                victim_k_dist = k_dist;
-                victim_earliest_ts = earliest_ts;
-            }
-        }
-
-        if let Some(frame_id) = victim {
-            frame_info.remove(&frame_id);
-            *num_evictable -= 1;
-        }
-
-        victim
+        let mut state = self.state.lock();
+        let (_, _, _, frame_id) = state.evictable.pop_first()?;
+        state.frames[frame_id.as_usize()] = None;
+        Some(frame_id)
     }
 
     /// Records that the given frame was accessed at the current timestamp.
@@ -148,13 +153,18 @@ impl LruKReplacer {
             return;
         }
 
-        let timestamp = self.current_timestamp.fetch_add(1, Ordering::Relaxed);
-        let mut frame_info = self.frame_info.lock();
+        let timestamp = self.clock.now();
+        let mut state = self.state.lock();
+        let ReplacerState { frames, evictable } = &mut *state;
 
-        frame_info
-            .entry(frame_id)
-            .or_insert_with(FrameAccessInfo::new)
-            .record_access(timestamp, self.k);
+        let info = frames[frame_id.as_usize()].get_or_insert_with(FrameAccessInfo::new);
+        if info.is_evictable {
+            evictable.remove(&info.eviction_key(frame_id, self.k));
+        }
+        info.record_access(timestamp, self.k, self.correlated_period);
+        if info.is_evictable {
+            evictable.insert(info.eviction_key(frame_id, self.k));
+        }
     }
 
     /// Sets whether a frame is evictable.
@@ -164,54 +174,87 @@ impl LruKReplacer {
             return;
         }
 
-        let mut frame_info = self.frame_info.lock();
-        let mut num_evictable = self.num_evictable.lock();
+        let mut state = self.state.lock();
+        let ReplacerState { frames, evictable } = &mut *state;
 
-        if let Some(info) = frame_info.get_mut(&frame_id) {
-            if info.is_evictable != is_evictable {
-                if is_evictable {
-                    *num_evictable += 1;
-                } else {
-                    *num_evictable -= 1;
-                }
-                info.is_evictable = is_evictable;
-            }
-        } else if is_evictable {
-            // Frame doesn't exist yet but is being marked evictable
-            let mut info = FrameAccessInfo::new();
-            info.is_evictable = true;
-            frame_info.insert(frame_id, info);
-            *num_evictable += 1;
+        let slot = &mut frames[frame_id.as_usize()];
+        if slot.is_none() && !is_evictable {
+            return;
+        }
+        // A frame that doesn't exist yet is tracked once marked evictable
+        let info = slot.get_or_insert_with(FrameAccessInfo::new);
+        if info.is_evictable == is_evictable {
+            return;
+        }
+        info.is_evictable = is_evictable;
+        let key = info.eviction_key(frame_id, self.k);
+        if is_evictable {
+            evictable.insert(key);
+        } else {
+            evictable.remove(&key);
+        }
+    }
+
+    /// Sets whether a frame is sticky: a sticky frame is evicted only when
+    /// every evictable frame is sticky. Stickiness is forgotten when the
+    /// frame is evicted or removed.
+    pub fn set_sticky(&self, frame_id: FrameId, is_sticky: bool) {
+        if frame_id.as_usize() >= self.max_frames {
+            return;
+        }
+
+        let mut state = self.state.lock();
+        let ReplacerState { frames, evictable } = &mut *state;
+
+        let info = frames[frame_id.as_usize()].get_or_insert_with(FrameAccessInfo::new);
+        if info.is_sticky == is_sticky {
+            return;
+        }
+        if info.is_evictable {
+            evictable.remove(&info.eviction_key(frame_id, self.k));
+        }
+        info.is_sticky = is_sticky;
+        if info.is_evictable {
+            evictable.insert(info.eviction_key(frame_id, self.k));
         }
     }
 
     /// Removes a frame from the replacer entirely.
     /// This should be called when a page is deleted from the BufferPoolManager.
     pub fn remove(&self, frame_id: FrameId) {
-        let mut frame_info = self.frame_info.lock();
-        let mut num_evictable = self.num_evictable.lock();
+        if frame_id.as_usize() >= self.max_frames {
+            return;
+        }
 
-        if let Some(info) = frame_info.remove(&frame_id) {
+        let mut state = self.state.lock();
+        if let Some(info) = state.frames[frame_id.as_usize()].take() {
             if info.is_evictable {
-                *num_evictable -= 1;
+                let key = info.eviction_key(frame_id, self.k);
+                state.evictable.remove(&key);
             }
         }
     }
 
     /// Returns the number of evictable frames.
     pub fn size(&self) -> usize {
-        *self.num_evictable.lock()
+        self.state.lock().evictable.len()
     }
 
     /// Returns the k value of this replacer.
     pub fn k(&self) -> usize {
         self.k
     }
+
+    /// Returns the number of frames this replacer can track.
+    pub fn max_frames(&self) -> usize {
+        self.max_frames
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::buffer::ManualClock;
 
     #[test]
     fn test_lru_k_replacer_new() {
@@ -316,6 +359,31 @@ mod tests {
         assert_eq!(replacer.evict(), Some(FrameId::new(0)));
     }
 
+    #[test]
+    fn test_lru_k_replacer_sticky() {
+        let replacer = LruKReplacer::new(2, 10);
+        for i in 0..3 {
+            replacer.record_access(FrameId::new(i));
+            replacer.set_evictable(FrameId::new(i), true);
+        }
+
+        // The coldest frame is sticky, so it goes last
+        replacer.set_sticky(FrameId::new(0), true);
+        assert_eq!(replacer.size(), 3);
+        assert_eq!(replacer.evict(), Some(FrameId::new(1)));
+        assert_eq!(replacer.evict(), Some(FrameId::new(2)));
+        assert_eq!(replacer.evict(), Some(FrameId::new(0)));
+
+        // Eviction forgets stickiness; clearing it restores the LRU-K order
+        replacer.record_access(FrameId::new(0));
+        replacer.record_access(FrameId::new(1));
+        replacer.set_sticky(FrameId::new(1), true);
+        replacer.set_sticky(FrameId::new(1), false);
+        replacer.set_evictable(FrameId::new(0), true);
+        replacer.set_evictable(FrameId::new(1), true);
+        assert_eq!(replacer.evict(), Some(FrameId::new(0)));
+    }
+
     #[test]
     fn test_lru_k_replacer_largest_k_distance() {
         let replacer = LruKReplacer::new(2, 10);
@@ -343,4 +411,108 @@ mod tests {
         // Frame 0 has largest k-distance, should be evicted
         assert_eq!(replacer.evict(), Some(FrameId::new(0)));
     }
+
+    #[test]
+    fn test_lru_k_replacer_correlated_reference_period() {
+        let build = |period| {
+            let clock = Arc::new(ManualClock::new(0));
+            let replacer = LruKReplacer::with_clock(2, 10, Arc::clone(&clock) as Arc<dyn Clock>)
+                .with_correlated_reference_period(period);
+            (clock, replacer)
+        };
+        let access_at = |clock: &ManualClock, replacer: &LruKReplacer, t, frame| {
+            clock.advance(t - clock.now());
+            replacer.record_access(FrameId::new(frame));
+            replacer.set_evictable(FrameId::new(frame), true);
+        };
+
+        // Frame 1 is referenced at 0 and 20; frame 0 in one burst at 30..=32
+        for (period, victim) in [(0, 1), (10, 0)] {
+            let (clock, replacer) = build(period);
+            access_at(&clock, &replacer, 0, 1);
+            access_at(&clock, &replacer, 20, 1);
+            for t in 30..=32 {
+                access_at(&clock, &replacer, t, 0);
+            }
+            // Uncorrelated, frame 0 has a recent kth access; correlated, the
+            // burst is a single reference and its k-distance is +inf
+            assert_eq!(replacer.evict(), Some(FrameId::new(victim)));
+        }
+
+        // The burst's length is taken out of the history: frame 0's burst
+        // starts at 30 but counts as ending at 32, after frame 2's kth access
+        let (clock, replacer) = build(10);
+        access_at(&clock, &replacer, 30, 0);
+        access_at(&clock, &replacer, 31, 0);
+        access_at(&clock, &replacer, 31, 2);
+        access_at(&clock, &replacer, 32, 0);
+        access_at(&clock, &replacer, 45, 2);
+        access_at(&clock, &replacer, 50, 0);
+        assert_eq!(replacer.evict(), Some(FrameId::new(2)));
+        assert_eq!(replacer.evict(), Some(FrameId::new(0)));
+    }
+
+    #[test]
+    fn test_lru_k_replacer_matches_full_scan() {
+        use rand::{Rng, SeedableRng};
+
+        // Reference model: full access histories, victim chosen by scanning
+        const K: usize = 3;
+        const FRAMES: usize = 64;
+        let replacer = LruKReplacer::new(K, FRAMES);
+        let mut history: Vec<Option<Vec<Timestamp>>> = vec![None; FRAMES];
+        let mut evictable = [false; FRAMES];
+        let mut now: Timestamp = 0;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+
+        for _ in 0..20_000 {
+            let frame = rng.gen_range(0..FRAMES);
+            let frame_id = FrameId::new(frame as u32);
+            match rng.gen_range(0..10) {
+                0..=4 => {
+                    replacer.record_access(frame_id);
+                    history[frame].get_or_insert_with(Vec::new).push(now);
+                    now += 1;
+                }
+                5..=7 => {
+                    let flag = rng.gen_bool(0.7);
+                    replacer.set_evictable(frame_id, flag);
+                    if history[frame].is_some() || flag {
+                        history[frame].get_or_insert_with(Vec::new);
+                        evictable[frame] = flag;
+                    }
+                }
+                8 => {
+                    replacer.remove(frame_id);
+                    history[frame] = None;
+                    evictable[frame] = false;
+                }
+                _ => {
+                    // Largest k-distance first; +inf by earliest access, with
+                    // never-accessed frames after the rest of them
+                    let expected = (0..FRAMES).filter(|&f| evictable[f]).min_by_key(|&f| {
+                        let h = history[f].as_ref().unwrap();
+                        match h.len() {
+                            0 => (0, Timestamp::MAX, f),
+                            n if n < K => (0, h[0], f),
+                            n => (1, h[n - K], f),
+                        }
+                    });
+                    let victim = replacer.evict().map(|id| id.as_usize());
+                    assert_eq!(victim.is_some(), expected.is_some());
+                    if let (Some(victim), Some(expected)) = (victim, expected) {
+                        // Never-accessed frames tie; any of them will do
+                        if history[expected].as_ref().unwrap().is_empty() {
+                            assert!(history[victim].as_ref().unwrap().is_empty());
+                        } else {
+                            assert_eq!(victim, expected);
+                        }
+                        history[victim] = None;
+                        evictable[victim] = false;
+                    }
+                }
+            }
+            assert_eq!(replacer.size(), evictable.iter().filter(|&&e| e).count());
+        }
+    }
 }
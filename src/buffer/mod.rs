@@ -1,9 +1,21 @@
 mod buffer_pool_manager;
+mod clock;
 mod frame_header;
+mod frame_slab;
 mod lru_k_replacer;
 mod page_guard;
+mod read_ahead;
+mod secondary_cache;
+#[cfg(feature = "stress-test")]
+mod stress;
 
 pub use buffer_pool_manager::*;
+pub use clock::*;
 pub use frame_header::*;
+pub use frame_slab::*;
 pub use lru_k_replacer::*;
 pub use page_guard::*;
+pub use read_ahead::*;
+pub use secondary_cache::*;
+#[cfg(feature = "stress-test")]
+pub use stress::*;
@@ -1,40 +1,71 @@
 use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{fence, Ordering};
 use std::sync::Arc;
 
-use parking_lot::{RwLockReadGuard, RwLockWriteGuard};
+use parking_lot::{ArcRwLockReadGuard, ArcRwLockWriteGuard, RawRwLock};
 
-use crate::common::{PageId, PAGE_SIZE};
+use crate::common::PageId;
 
-use super::FrameHeader;
+use super::{FrameData, FrameHeader};
 
-/// Callback type for releasing a page guard
-type ReleaseCallback = Box<dyn FnOnce(PageId, bool) + Send + Sync>;
+type DataReadGuard = ArcRwLockReadGuard<RawRwLock, FrameData>;
+type DataWriteGuard = ArcRwLockWriteGuard<RawRwLock, FrameData>;
+
+/// Whoever pinned a page for a guard, told when the guard lets go of it.
+/// Dirty state is not part of this: a write guard marks its frame dirty
+/// itself, while it still holds the latch.
+pub(crate) trait PinRelease: Send + Sync {
+    /// Drops pin `pin_id` on the page.
+    fn release_pin(&self, page_id: PageId, pin_id: u64);
+}
 
 /// Base page guard that manages the common functionality
 struct PageGuardBase {
     /// The page ID being guarded
     page_id: PageId,
     /// Reference to the frame header (kept alive for the guard's lifetime)
-    _frame: Arc<FrameHeader>,
-    /// Callback to release the guard
-    release_callback: Option<ReleaseCallback>,
-    /// Whether the page was marked dirty
-    is_dirty: bool,
+    frame: Arc<FrameHeader>,
+    /// Receives the pin back on release
+    owner: Arc<dyn PinRelease>,
+    /// Identifies the pin to the owner
+    pin_id: u64,
+    /// Set once the pin has been released or moved to another guard
+    released: bool,
 }
 
 impl PageGuardBase {
-    fn new(page_id: PageId, frame: Arc<FrameHeader>, release_callback: ReleaseCallback) -> Self {
+    fn new(
+        page_id: PageId,
+        frame: Arc<FrameHeader>,
+        owner: Arc<dyn PinRelease>,
+        pin_id: u64,
+    ) -> Self {
         Self {
             page_id,
-            _frame: frame,
-            release_callback: Some(release_callback),
-            is_dirty: false,
+            frame,
+            owner,
+            pin_id,
+            released: false,
         }
     }
 
+    /// Moves the pin into a new base, leaving this one inert so its drop does
+    /// nothing.
+    fn transfer(&mut self) -> Self {
+        let base = Self {
+            page_id: self.page_id,
+            frame: Arc::clone(&self.frame),
+            owner: Arc::clone(&self.owner),
+            pin_id: self.pin_id,
+            released: self.released,
+        };
+        self.released = true;
+        base
+    }
+
     fn drop_impl(&mut self) {
-        if let Some(callback) = self.release_callback.take() {
-            callback(self.page_id, self.is_dirty);
+        if !std::mem::replace(&mut self.released, true) {
+            self.owner.release_pin(self.page_id, self.pin_id);
         }
     }
 }
@@ -44,27 +75,22 @@ impl PageGuardBase {
 pub struct ReadPageGuard {
     base: PageGuardBase,
     /// Read lock on the page data
-    _data_guard: RwLockReadGuard<'static, Box<[u8; PAGE_SIZE]>>,
+    data_guard: Option<DataReadGuard>,
 }
 
 impl ReadPageGuard {
-    /// Creates a new ReadPageGuard.
-    /// # Safety
-    /// The caller must ensure that the frame outlives this guard.
-    pub(crate) unsafe fn new(
+    /// Latches a pinned page for reading. `owner` gets pin `pin_id` back
+    /// when the guard is released.
+    pub(crate) fn new(
         page_id: PageId,
         frame: Arc<FrameHeader>,
-        release_callback: ReleaseCallback,
+        owner: Arc<dyn PinRelease>,
+        pin_id: u64,
     ) -> Self {
-        // Acquire the read lock
-        let data_guard = frame.data.read();
-        // Transmute to static lifetime - the frame is kept alive via Arc
-        let data_guard: RwLockReadGuard<'static, Box<[u8; PAGE_SIZE]>> =
-            std::mem::transmute(data_guard);
-
+        let data_guard = frame.data.read_arc();
         Self {
-            base: PageGuardBase::new(page_id, frame, release_callback),
-            _data_guard: data_guard,
+            base: PageGuardBase::new(page_id, frame, owner, pin_id),
+            data_guard: Some(data_guard),
         }
     }
 
@@ -75,7 +101,38 @@ impl ReadPageGuard {
 
     /// Returns a reference to the page data.
     pub fn data(&self) -> &[u8] {
-        &self._data_guard[..]
+        &self.data_guard.as_ref().unwrap()[..]
+    }
+
+    /// Tries to convert this guard into a WritePageGuard without unpinning the page.
+    ///
+    /// The read latch is released before the write latch is attempted, so the
+    /// upgrade fails (returning the read guard, re-latched) if another reader
+    /// holds the page or if any writer latched it in between. On success the
+    /// caller is guaranteed the page is unchanged since it was read.
+    pub fn try_upgrade(mut self) -> std::result::Result<WritePageGuard, ReadPageGuard> {
+        let frame = Arc::clone(&self.base.frame);
+        let version = frame.version();
+        self.data_guard.take();
+
+        match frame.data.try_write_arc() {
+            Some(write_guard) if frame.version() == version => {
+                frame.bump_version();
+                Ok(WritePageGuard {
+                    base: self.base.transfer(),
+                    data_guard: Some(write_guard),
+                    is_dirty: false,
+                })
+            }
+            Some(write_guard) => {
+                self.data_guard = Some(DataWriteGuard::downgrade(write_guard));
+                Err(self)
+            }
+            None => {
+                self.data_guard = Some(frame.data.read_arc());
+                Err(self)
+            }
+        }
     }
 
     /// Drops this guard, releasing the page.
@@ -94,36 +151,40 @@ impl Deref for ReadPageGuard {
 
 impl Drop for ReadPageGuard {
     fn drop(&mut self) {
+        // Drop the data guard first to release the lock
+        self.data_guard.take();
+        // Then give the pin back
         self.base.drop_impl();
     }
 }
 
 /// RAII guard for read-write access to a page.
-/// Automatically marks the page as dirty and unpins it when dropped.
+/// Unpins the page when dropped, marking it dirty first if the guard changed
+/// it. Only `data_mut` (and `DerefMut`) count as a change; reading through a
+/// write guard leaves the page clean.
 pub struct WritePageGuard {
     base: PageGuardBase,
     /// Write lock on the page data
-    data_guard: Option<RwLockWriteGuard<'static, Box<[u8; PAGE_SIZE]>>>,
+    data_guard: Option<DataWriteGuard>,
+    /// Whether the page gets marked dirty on release
+    is_dirty: bool,
 }
 
 impl WritePageGuard {
-    /// Creates a new WritePageGuard.
-    /// # Safety
-    /// The caller must ensure that the frame outlives this guard.
-    pub(crate) unsafe fn new(
+    /// Latches a pinned page for writing. `owner` gets pin `pin_id` back
+    /// when the guard is released.
+    pub(crate) fn new(
         page_id: PageId,
         frame: Arc<FrameHeader>,
-        release_callback: ReleaseCallback,
+        owner: Arc<dyn PinRelease>,
+        pin_id: u64,
     ) -> Self {
-        // Acquire the write lock
-        let data_guard = frame.data.write();
-        // Transmute to static lifetime - the frame is kept alive via Arc
-        let data_guard: RwLockWriteGuard<'static, Box<[u8; PAGE_SIZE]>> =
-            std::mem::transmute(data_guard);
-
+        let data_guard = frame.data.write_arc();
+        frame.bump_version();
         Self {
-            base: PageGuardBase::new(page_id, frame, release_callback),
+            base: PageGuardBase::new(page_id, frame, owner, pin_id),
             data_guard: Some(data_guard),
+            is_dirty: false,
         }
     }
 
@@ -140,10 +201,48 @@ impl WritePageGuard {
     /// Returns a mutable reference to the page data.
     /// Automatically marks the page as dirty.
     pub fn data_mut(&mut self) -> &mut [u8] {
-        self.base.is_dirty = true;
+        self.is_dirty = true;
         &mut self.data_guard.as_mut().unwrap()[..]
     }
 
+    /// Returns true if the page will be marked dirty on release.
+    pub fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    /// Marks the page dirty on release, for changes made without `data_mut`.
+    pub fn mark_dirty(&mut self) {
+        self.is_dirty = true;
+    }
+
+    /// Forgets that the guard changed the page, e.g. after `data_mut` was
+    /// only used to inspect it or after the changes were written through.
+    /// A page that was already dirty stays dirty.
+    pub fn mark_clean(&mut self) {
+        self.is_dirty = false;
+    }
+
+    /// Marks the frame dirty if the guard changed it. Called while the latch
+    /// is still held, so a flush can't copy the page in between.
+    fn publish_dirty(&self) {
+        if self.is_dirty {
+            self.base.frame.set_dirty(true);
+        }
+    }
+
+    /// Atomically converts this guard into a ReadPageGuard without unpinning the
+    /// page or letting another writer in. Changes made so far mark the page
+    /// dirty.
+    pub fn downgrade(mut self) -> ReadPageGuard {
+        self.publish_dirty();
+        let write_guard = self.data_guard.take().unwrap();
+        let read_guard = DataWriteGuard::downgrade(write_guard);
+        ReadPageGuard {
+            base: self.base.transfer(),
+            data_guard: Some(read_guard),
+        }
+    }
+
     /// Drops this guard, releasing the page.
     pub fn drop_guard(self) {
         drop(self);
@@ -166,19 +265,88 @@ impl DerefMut for WritePageGuard {
 
 impl Drop for WritePageGuard {
     fn drop(&mut self) {
+        if self.data_guard.is_some() {
+            self.publish_dirty();
+        }
         // Drop the data guard first to release the lock
         self.data_guard.take();
-        // Then call the release callback
+        // Then give the pin back
         self.base.drop_impl();
     }
 }
 
+/// Latch-free, unpinned view of a page for optimistic reads. Copy the bytes
+/// you need with `copy_bytes`, then trust them only if `validate` returns
+/// true; otherwise retry or fall back to a `ReadPageGuard`. Any write latch
+/// taken on the page, or the page leaving its frame, fails validation.
+pub struct OptimisticReadGuard {
+    page_id: PageId,
+    frame: Arc<FrameHeader>,
+    /// Frame version when the read started
+    version: u64,
+}
+
+impl OptimisticReadGuard {
+    /// Starts an optimistic read of `page_id` in `frame`. `version` must have
+    /// been read while the page was known to be in the frame. Returns None if
+    /// a writer holds the page.
+    pub(crate) fn new(page_id: PageId, frame: Arc<FrameHeader>, version: u64) -> Option<Self> {
+        if frame.is_write_latched() {
+            return None;
+        }
+        Some(Self {
+            page_id,
+            frame,
+            version,
+        })
+    }
+
+    /// Returns the page ID.
+    pub fn page_id(&self) -> PageId {
+        self.page_id
+    }
+
+    /// Copies `buf.len()` bytes starting at `offset` out of the page. The
+    /// copy may be torn by a concurrent writer until `validate` vouches for it.
+    pub fn copy_bytes(&self, offset: usize, buf: &mut [u8]) {
+        self.frame.copy_unlatched(offset, buf);
+    }
+
+    /// Returns true if nothing has written to the page since the read
+    /// started, so every copy made so far is consistent.
+    pub fn validate(&self) -> bool {
+        // Keep the copies from being reordered after the checks
+        fence(Ordering::Acquire);
+        !self.frame.is_write_latched() && self.frame.version() == self.version
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::common::FrameId;
+    use crate::common::{FrameId, PAGE_SIZE};
     use std::sync::atomic::{AtomicBool, Ordering};
 
+    /// Records whether a guard has been released.
+    #[derive(Default)]
+    struct Releases {
+        released: AtomicBool,
+    }
+
+    impl PinRelease for Releases {
+        fn release_pin(&self, _page_id: PageId, _pin_id: u64) {
+            self.released.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn read_guard(frame: &Arc<FrameHeader>, releases: &Arc<Releases>) -> ReadPageGuard {
+        ReadPageGuard::new(PageId::new(1), frame.clone(), releases.clone(), 0)
+    }
+
+    fn write_guard(frame: &Arc<FrameHeader>, releases: &Arc<Releases>) -> WritePageGuard {
+        WritePageGuard::new(PageId::new(1), frame.clone(), releases.clone(), 0)
+    }
+
     #[test]
     fn test_read_page_guard() {
         let frame = Arc::new(FrameHeader::new(FrameId::new(0)));
@@ -188,25 +356,15 @@ mod tests {
         data[0] = 42;
         frame.copy_from(&data);
 
-        let released = Arc::new(AtomicBool::new(false));
-        let released_clone = released.clone();
-
-        let guard = unsafe {
-            ReadPageGuard::new(
-                PageId::new(1),
-                frame.clone(),
-                Box::new(move |_, _| {
-                    released_clone.store(true, Ordering::SeqCst);
-                }),
-            )
-        };
+        let releases = Arc::new(Releases::default());
+        let guard = read_guard(&frame, &releases);
 
         assert_eq!(guard.page_id(), PageId::new(1));
         assert_eq!(guard.data()[0], 42);
-        assert!(!released.load(Ordering::SeqCst));
+        assert!(!releases.released.load(Ordering::SeqCst));
 
         drop(guard);
-        assert!(released.load(Ordering::SeqCst));
+        assert!(releases.released.load(Ordering::SeqCst));
     }
 
     #[test]
@@ -214,36 +372,151 @@ mod tests {
         let frame = Arc::new(FrameHeader::new(FrameId::new(0)));
         frame.set_page_id(PageId::new(1));
 
-        let released = Arc::new(AtomicBool::new(false));
-        let dirty = Arc::new(AtomicBool::new(false));
-        let released_clone = released.clone();
-        let dirty_clone = dirty.clone();
-
-        let mut guard = unsafe {
-            WritePageGuard::new(
-                PageId::new(1),
-                frame.clone(),
-                Box::new(move |_, is_dirty| {
-                    released_clone.store(true, Ordering::SeqCst);
-                    dirty_clone.store(is_dirty, Ordering::SeqCst);
-                }),
-            )
-        };
+        let releases = Arc::new(Releases::default());
+        let mut guard = write_guard(&frame, &releases);
 
         assert_eq!(guard.page_id(), PageId::new(1));
 
         // Write some data
         guard.data_mut()[0] = 42;
 
-        assert!(!released.load(Ordering::SeqCst));
+        assert!(!releases.released.load(Ordering::SeqCst));
 
         drop(guard);
-        assert!(released.load(Ordering::SeqCst));
-        assert!(dirty.load(Ordering::SeqCst));
+        assert!(releases.released.load(Ordering::SeqCst));
+        assert!(frame.is_dirty());
 
         // Verify data was written
         let mut read_data = [0u8; PAGE_SIZE];
         frame.copy_to(&mut read_data);
         assert_eq!(read_data[0], 42);
     }
+
+    #[test]
+    fn test_optimistic_read_validation() {
+        let frame = Arc::new(FrameHeader::new(FrameId::new(0)));
+        frame.set_page_id(PageId::new(1));
+        let mut data = [0u8; PAGE_SIZE];
+        data[..4].copy_from_slice(b"abcd");
+        frame.copy_from(&data);
+
+        let releases = Arc::new(Releases::default());
+        let optimistic = OptimisticReadGuard::new(PageId::new(1), frame.clone(), frame.version());
+        let optimistic = optimistic.unwrap();
+        let mut buf = [0u8; 4];
+        optimistic.copy_bytes(0, &mut buf);
+        assert_eq!(&buf, b"abcd");
+
+        // Readers don't interfere
+        drop(read_guard(&frame, &releases));
+        assert!(optimistic.validate());
+
+        // A writer fails validation while it holds the latch and afterwards,
+        // even if it changes nothing
+        let guard = write_guard(&frame, &releases);
+        assert!(!optimistic.validate());
+        assert!(OptimisticReadGuard::new(PageId::new(1), frame.clone(), frame.version()).is_none());
+        drop(guard);
+        assert!(!optimistic.validate());
+    }
+
+    #[test]
+    fn test_write_guard_dirty_only_on_change() {
+        let frame = Arc::new(FrameHeader::new(FrameId::new(0)));
+        let releases = Arc::new(Releases::default());
+
+        // Reading through a write guard leaves the page clean
+        let guard = write_guard(&frame, &releases);
+        assert_eq!(guard.data()[0], 0);
+        assert!(!guard.is_dirty());
+        drop(guard);
+        assert!(!frame.is_dirty());
+
+        let mut guard = write_guard(&frame, &releases);
+        let _ = guard.data_mut();
+        guard.mark_clean();
+        drop(guard);
+        assert!(!frame.is_dirty());
+
+        let mut guard = write_guard(&frame, &releases);
+        guard.mark_dirty();
+        drop(guard);
+        assert!(frame.is_dirty());
+
+        // Cleaning a guard doesn't clean a page dirtied earlier
+        let mut guard = write_guard(&frame, &releases);
+        guard.mark_clean();
+        drop(guard);
+        assert!(frame.is_dirty());
+    }
+
+    #[test]
+    fn test_guard_outlives_frame_handle() {
+        // The guard keeps the frame and its latch alive on its own
+        let releases = Arc::new(Releases::default());
+        let frame = Arc::new(FrameHeader::new(FrameId::new(0)));
+        let mut guard = write_guard(&frame, &releases);
+        let weak = Arc::downgrade(&frame);
+        drop(frame);
+
+        guard.data_mut()[0] = 9;
+        let read = guard.downgrade();
+        assert_eq!(read.data()[0], 9);
+        assert!(weak.upgrade().unwrap().is_dirty());
+        drop(read);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_write_guard_downgrade() {
+        let frame = Arc::new(FrameHeader::new(FrameId::new(0)));
+        let releases = Arc::new(Releases::default());
+
+        let mut guard = write_guard(&frame, &releases);
+        guard.data_mut()[0] = 42;
+
+        let read = guard.downgrade();
+        assert_eq!(read.data()[0], 42);
+
+        // Other readers may share the latch, writers may not
+        assert!(frame.data.try_read().is_some());
+        assert!(frame.data.try_write().is_none());
+
+        drop(read);
+        assert!(frame.is_dirty());
+        assert!(frame.data.try_write().is_some());
+    }
+
+    #[test]
+    fn test_read_guard_try_upgrade() {
+        let frame = Arc::new(FrameHeader::new(FrameId::new(0)));
+        let releases = Arc::new(Releases::default());
+
+        let guard = read_guard(&frame, &releases);
+        let mut write = guard.try_upgrade().ok().unwrap();
+        write.data_mut()[0] = 7;
+        drop(write);
+
+        assert!(frame.is_dirty());
+        let mut data = [0u8; PAGE_SIZE];
+        frame.copy_to(&mut data);
+        assert_eq!(data[0], 7);
+    }
+
+    #[test]
+    fn test_read_guard_try_upgrade_fails() {
+        let frame = Arc::new(FrameHeader::new(FrameId::new(0)));
+        let releases = Arc::new(Releases::default());
+
+        // Another reader holds the latch
+        let guard = read_guard(&frame, &releases);
+        let other = frame.data.read();
+        let guard = guard.try_upgrade().err().unwrap();
+        assert_eq!(guard.page_id(), PageId::new(1));
+        assert_eq!(guard.data()[0], 0);
+        drop(other);
+
+        // Retrying once the other reader is gone succeeds
+        assert!(guard.try_upgrade().is_ok());
+    }
 }
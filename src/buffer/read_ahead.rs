@@ -0,0 +1,393 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::thread::{self, ThreadId};
+
+use crate::common::{
+    PageId, DEFAULT_READ_AHEAD_MAX_OUTSTANDING, DEFAULT_READ_AHEAD_MAX_WINDOW,
+    DEFAULT_READ_AHEAD_WINDOW, DEFAULT_SEQUENTIAL_THRESHOLD,
+};
+
+/// How the buffer pool reads ahead of sequential page accesses.
+///
+/// Once `sequential_threshold` consecutive misses hit consecutive pages, the
+/// pool prefetches the next `window` pages. With `adaptive`, the window
+/// doubles (up to `max_window`) each time a window's worth of read-ahead
+/// pages is used, and halves each time one is evicted before use. At most
+/// `max_outstanding` read-ahead pages wait in the pool unused at any time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReadAheadPolicy {
+    /// Read ahead at all
+    pub enabled: bool,
+    /// Consecutive page accesses that make a pattern sequential
+    pub sequential_threshold: usize,
+    /// Pages read ahead at once; the starting window when adaptive
+    pub window: u32,
+    /// Largest window the adaptive policy grows to
+    pub max_window: u32,
+    /// Read-ahead pages allowed in the pool before they are used
+    pub max_outstanding: u32,
+    /// Grow and shrink the window by how read-ahead pages are used
+    pub adaptive: bool,
+}
+
+impl ReadAheadPolicy {
+    /// Returns a policy that never reads ahead.
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for ReadAheadPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sequential_threshold: DEFAULT_SEQUENTIAL_THRESHOLD,
+            window: DEFAULT_READ_AHEAD_WINDOW,
+            max_window: DEFAULT_READ_AHEAD_MAX_WINDOW,
+            max_outstanding: DEFAULT_READ_AHEAD_MAX_OUTSTANDING,
+            adaptive: true,
+        }
+    }
+}
+
+/// Counters describing how read-ahead has fared.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadAheadStats {
+    /// Pages read ahead at once right now
+    pub window: u32,
+    /// Read-ahead pages in the pool not used yet
+    pub outstanding: u32,
+    /// Read-ahead pages used before eviction
+    pub hits: u64,
+    /// Read-ahead pages evicted without being used
+    pub wasted: u64,
+}
+
+/// Threads whose access patterns are tracked at once; past this the
+/// patterns of all threads are forgotten, as threads that exited are never
+/// removed otherwise.
+const MAX_TRACKED_THREADS: usize = 64;
+
+/// The recent page accesses of one scan, used to tell whether it reads
+/// pages sequentially.
+///
+/// Reads made through `BufferPoolManager::checked_read_page` are tracked per
+/// thread, so scans on different threads each trigger their own read-ahead.
+/// A thread that interleaves several scans, such as a join, gives each its
+/// own context and reads with `checked_read_page_in`.
+#[derive(Debug, Default)]
+pub struct ScanContext {
+    recent_accesses: VecDeque<PageId>,
+}
+
+impl ScanContext {
+    /// Creates a context that has seen no accesses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an access and returns true if the last `threshold` accesses
+    /// (or more) were to consecutive pages.
+    fn record(&mut self, page_id: PageId, threshold: usize) -> bool {
+        if self.recent_accesses.len() > threshold {
+            self.recent_accesses.pop_front();
+        }
+        self.recent_accesses.push_back(page_id);
+        self.is_sequential(threshold)
+    }
+
+    fn is_sequential(&self, threshold: usize) -> bool {
+        if self.recent_accesses.len() < threshold {
+            return false;
+        }
+
+        let accesses: Vec<_> = self.recent_accesses.iter().collect();
+        for i in 1..accesses.len() {
+            if accesses[i].as_u32() != accesses[i - 1].as_u32() + 1 {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Whose pattern a page access extends.
+pub(super) enum Access<'a> {
+    /// None; the access never triggers read-ahead
+    Untracked,
+    /// The calling thread's
+    Thread,
+    /// A scan's own
+    Scan(&'a mut ScanContext),
+}
+
+/// Read-ahead window state shared by every scan of a pool.
+pub(super) struct ReadAhead {
+    policy: ReadAheadPolicy,
+    /// Access patterns of threads reading without a scan context
+    threads: HashMap<ThreadId, ScanContext>,
+    window: u32,
+    /// Read-ahead pages installed and not used or evicted yet
+    outstanding: HashSet<PageId>,
+    /// Hits since the window last changed
+    streak: u32,
+    hits: u64,
+    wasted: u64,
+}
+
+impl ReadAhead {
+    pub(super) fn new(policy: ReadAheadPolicy) -> Self {
+        Self {
+            policy,
+            threads: HashMap::new(),
+            window: policy.window.clamp(1, policy.max_window.max(1)),
+            outstanding: HashSet::new(),
+            streak: 0,
+            hits: 0,
+            wasted: 0,
+        }
+    }
+
+    pub(super) fn policy(&self) -> ReadAheadPolicy {
+        self.policy
+    }
+
+    /// Replaces the policy, keeping the pages already read ahead but
+    /// forgetting the per-thread access patterns.
+    pub(super) fn set_policy(&mut self, policy: ReadAheadPolicy) {
+        let outstanding = std::mem::take(&mut self.outstanding);
+        let (hits, wasted) = (self.hits, self.wasted);
+        *self = Self::new(policy);
+        self.outstanding = outstanding;
+        self.hits = hits;
+        self.wasted = wasted;
+    }
+
+    pub(super) fn stats(&self) -> ReadAheadStats {
+        ReadAheadStats {
+            window: self.window,
+            outstanding: self.outstanding.len() as u32,
+            hits: self.hits,
+            wasted: self.wasted,
+        }
+    }
+
+    /// Records an access that missed the pool and returns how many pages to
+    /// read ahead of it, if any.
+    pub(super) fn record_miss(&mut self, page_id: PageId, access: &mut Access) -> u32 {
+        let threshold = self.policy.sequential_threshold;
+        let sequential = match self.pattern(access) {
+            Some(pattern) => pattern.record(page_id, threshold),
+            None => false,
+        };
+        if sequential {
+            self.budget()
+        } else {
+            0
+        }
+    }
+
+    /// Records an access to a resident page. If it was read ahead, counts
+    /// the hit and returns how many pages to read ahead of it to keep the
+    /// stream going; untracked accesses count the hit but return 0.
+    pub(super) fn record_hit(&mut self, page_id: PageId, access: &mut Access) -> u32 {
+        if !self.outstanding.remove(&page_id) {
+            return 0;
+        }
+        self.hits += 1;
+        self.streak += 1;
+        if self.policy.adaptive && self.streak >= self.window {
+            self.window = (self.window * 2).min(self.policy.max_window.max(1));
+            self.streak = 0;
+        }
+        let threshold = self.policy.sequential_threshold;
+        match self.pattern(access) {
+            Some(pattern) => {
+                pattern.record(page_id, threshold);
+                self.budget()
+            }
+            None => 0,
+        }
+    }
+
+    /// Records pages just read ahead into the pool.
+    pub(super) fn installed(&mut self, page_id: PageId) {
+        self.outstanding.insert(page_id);
+    }
+
+    /// Records that a page left the pool. A read-ahead page evicted unused
+    /// shrinks the window; one deleted or discarded is just forgotten.
+    pub(super) fn evicted(&mut self, page_id: PageId, wasted: bool) {
+        if !self.outstanding.remove(&page_id) || !wasted {
+            return;
+        }
+        self.wasted += 1;
+        self.streak = 0;
+        if self.policy.adaptive {
+            self.window = (self.window / 2).max(1);
+        }
+    }
+
+    /// Returns the access pattern an access extends, or None if it can't
+    /// trigger read-ahead.
+    fn pattern<'a>(&'a mut self, access: &'a mut Access) -> Option<&'a mut ScanContext> {
+        if !self.policy.enabled {
+            return None;
+        }
+        match access {
+            Access::Untracked => None,
+            Access::Scan(scan) => Some(scan),
+            Access::Thread => {
+                let thread_id = thread::current().id();
+                if self.threads.len() >= MAX_TRACKED_THREADS
+                    && !self.threads.contains_key(&thread_id)
+                {
+                    self.threads.clear();
+                }
+                Some(self.threads.entry(thread_id).or_default())
+            }
+        }
+    }
+
+    /// Pages that can be read ahead now without passing `max_outstanding`.
+    fn budget(&self) -> u32 {
+        let room = self
+            .policy
+            .max_outstanding
+            .saturating_sub(self.outstanding.len() as u32);
+        self.window.min(room)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequential_misses(read_ahead: &mut ReadAhead, pages: std::ops::Range<u32>) -> u32 {
+        pages
+            .map(|page| read_ahead.record_miss(PageId::new(page), &mut Access::Thread))
+            .last()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_read_ahead_detects_sequential_misses() {
+        let mut read_ahead = ReadAhead::new(ReadAheadPolicy::default());
+        let mut miss = |page| read_ahead.record_miss(PageId::new(page), &mut Access::Thread);
+        assert_eq!(miss(10), 0);
+        assert_eq!(miss(11), 0);
+        assert_eq!(miss(12), 4);
+        assert_eq!(miss(40), 0);
+
+        let mut read_ahead = ReadAhead::new(ReadAheadPolicy::disabled());
+        assert_eq!(sequential_misses(&mut read_ahead, 0..10), 0);
+    }
+
+    #[test]
+    fn test_read_ahead_window_adapts() {
+        let policy = ReadAheadPolicy {
+            window: 2,
+            max_window: 8,
+            ..ReadAheadPolicy::default()
+        };
+        let mut read_ahead = ReadAhead::new(policy);
+        assert_eq!(sequential_misses(&mut read_ahead, 0..3), 2);
+
+        // Using a whole window doubles it, up to the cap
+        let mut next = 3;
+        for _ in 0..10 {
+            let window = read_ahead.stats().window;
+            for page in next..next + window {
+                read_ahead.installed(PageId::new(page));
+            }
+            for page in next..next + window {
+                read_ahead.record_hit(PageId::new(page), &mut Access::Thread);
+            }
+            next += window;
+        }
+        assert_eq!(read_ahead.stats().window, 8);
+
+        // Evicting read-ahead pages unused halves it
+        for page in next..next + 8 {
+            read_ahead.installed(PageId::new(page));
+        }
+        read_ahead.evicted(PageId::new(next), true);
+        read_ahead.evicted(PageId::new(next + 1), true);
+        read_ahead.evicted(PageId::new(next + 2), false);
+        let stats = read_ahead.stats();
+        assert_eq!(stats.window, 2);
+        assert_eq!(stats.wasted, 2);
+        assert_eq!(stats.outstanding, 5);
+    }
+
+    #[test]
+    fn test_read_ahead_caps_outstanding_pages() {
+        let policy = ReadAheadPolicy {
+            window: 8,
+            max_outstanding: 10,
+            adaptive: false,
+            ..ReadAheadPolicy::default()
+        };
+        let mut read_ahead = ReadAhead::new(policy);
+        for page in 100..106 {
+            read_ahead.installed(PageId::new(page));
+        }
+        assert_eq!(sequential_misses(&mut read_ahead, 0..3), 4);
+        assert_eq!(
+            read_ahead.record_hit(PageId::new(100), &mut Access::Thread),
+            5
+        );
+        // Not a read-ahead page
+        assert_eq!(
+            read_ahead.record_hit(PageId::new(7), &mut Access::Thread),
+            0
+        );
+        assert_eq!(read_ahead.stats().window, 8);
+    }
+
+    #[test]
+    fn test_read_ahead_tracks_scans_separately() {
+        let mut read_ahead = ReadAhead::new(ReadAheadPolicy::default());
+        let (mut left, mut right) = (ScanContext::new(), ScanContext::new());
+
+        // Interleaved scans look random to a single tracker
+        let mut last = (0, 0);
+        for i in 0..3 {
+            assert_eq!(
+                read_ahead.record_miss(PageId::new(10 + i), &mut Access::Thread),
+                0
+            );
+            assert_eq!(
+                read_ahead.record_miss(PageId::new(50 + i), &mut Access::Thread),
+                0
+            );
+        }
+        for i in 0..3 {
+            last.0 = read_ahead.record_miss(PageId::new(10 + i), &mut Access::Scan(&mut left));
+            last.1 = read_ahead.record_miss(PageId::new(50 + i), &mut Access::Scan(&mut right));
+        }
+        assert_eq!(last, (4, 4));
+
+        // Untracked accesses never trigger read-ahead
+        for i in 0..3 {
+            assert_eq!(
+                read_ahead.record_miss(PageId::new(i), &mut Access::Untracked),
+                0
+            );
+        }
+
+        // Other threads keep their own patterns
+        let mut other = 0;
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..3 {
+                    other = read_ahead.record_miss(PageId::new(90 + i), &mut Access::Thread);
+                }
+            });
+        });
+        assert_eq!(other, 4);
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+
+use crate::common::{CrioError, PageId, Result, PAGE_SIZE};
+
+/// Where the secondary cache lives and how many pages it holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SecondaryCacheConfig {
+    /// Path of the cache file, ideally on fast local storage
+    pub path: PathBuf,
+    /// Pages the cache file holds
+    pub pages: u32,
+}
+
+/// Counters describing how the secondary cache has fared.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SecondaryCacheStats {
+    /// Pages cached right now
+    pub cached: u32,
+    /// Fetches served from the cache
+    pub hits: u64,
+    /// Fetches that went to the primary backend
+    pub misses: u64,
+    /// Pages written to the cache on eviction from the pool
+    pub insertions: u64,
+    /// Cached pages replaced to make room
+    pub evictions: u64,
+}
+
+/// A cache slot in use.
+struct Slot {
+    page_id: PageId,
+    /// Read since the clock hand last passed
+    referenced: bool,
+}
+
+struct SecondaryCacheState {
+    /// The cache file
+    file: File,
+    /// Slot of each cached page
+    slots: HashMap<PageId, u32>,
+    /// Page held by each slot, if any
+    entries: Vec<Option<Slot>>,
+    /// Next slot the clock hand looks at
+    hand: usize,
+    stats: SecondaryCacheStats,
+}
+
+/// SecondaryCache is a second tier below the buffer pool: a fixed number of
+/// page slots in a file of their own, meant for a fast local SSD in front of
+/// slower (possibly remote) primary storage.
+///
+/// The buffer pool copies every page it evicts into the cache, and looks a
+/// page up here before reading it from the backend. Dirty pages are still
+/// written to the backend, so the cache never holds the only copy of a page
+/// and its file can be thrown away at any time; it is emptied when opened
+/// and deleted when dropped.
+///
+/// When full, the cache replaces pages with the CLOCK policy: a page read
+/// since the hand last passed gets a second chance, independent of the
+/// pool's LRU-K replacer.
+pub struct SecondaryCache {
+    /// Path of the cache file
+    path: PathBuf,
+    state: Mutex<SecondaryCacheState>,
+}
+
+impl SecondaryCache {
+    /// Creates an empty cache of `pages` slots in the file at `path`,
+    /// truncating anything already there.
+    pub fn new<P: AsRef<Path>>(path: P, pages: u32) -> Result<Self> {
+        if pages == 0 {
+            return Err(CrioError::InvalidConfig(
+                "secondary cache must hold at least 1 page".to_string(),
+            ));
+        }
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+
+        Ok(Self {
+            path,
+            state: Mutex::new(SecondaryCacheState {
+                file,
+                slots: HashMap::new(),
+                entries: (0..pages).map(|_| None).collect(),
+                hand: 0,
+                stats: SecondaryCacheStats::default(),
+            }),
+        })
+    }
+
+    /// Opens the cache described by `config`.
+    pub fn from_config(config: &SecondaryCacheConfig) -> Result<Self> {
+        Self::new(&config.path, config.pages)
+    }
+
+    /// Copies `page_id` into the cache, replacing its cached copy or, if it
+    /// isn't cached and the cache is full, the page the clock hand picks.
+    pub fn insert(&self, page_id: PageId, data: &[u8]) -> Result<()> {
+        assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+
+        let mut state = self.state.lock();
+        let slot = match state.slots.get(&page_id) {
+            Some(&slot) => slot,
+            None => {
+                let slot = Self::claim_slot(&mut state);
+                state.entries[slot as usize] = Some(Slot {
+                    page_id,
+                    referenced: false,
+                });
+                state.slots.insert(page_id, slot);
+                slot
+            }
+        };
+
+        let written = Self::seek_slot(&mut state.file, slot)
+            .and_then(|file| file.write_all(data).map_err(CrioError::from));
+        if let Err(e) = written {
+            // A partly written slot must not be read back
+            Self::remove(&mut state, page_id);
+            return Err(e);
+        }
+        state.stats.insertions += 1;
+        Ok(())
+    }
+
+    /// Reads `page_id` into `data` if it is cached. Returns false on a miss,
+    /// in which case `data` is untouched.
+    pub fn read(&self, page_id: PageId, data: &mut [u8]) -> Result<bool> {
+        assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+
+        let mut state = self.state.lock();
+        let Some(&slot) = state.slots.get(&page_id) else {
+            state.stats.misses += 1;
+            return Ok(false);
+        };
+
+        let read = Self::seek_slot(&mut state.file, slot)
+            .and_then(|file| file.read_exact(data).map_err(CrioError::from));
+        if let Err(e) = read {
+            Self::remove(&mut state, page_id);
+            state.stats.misses += 1;
+            return Err(e);
+        }
+        if let Some(entry) = &mut state.entries[slot as usize] {
+            entry.referenced = true;
+        }
+        state.stats.hits += 1;
+        Ok(true)
+    }
+
+    /// Drops the cached copy of `page_id`, e.g. once the page changes on the
+    /// primary backend behind the pool's back. Returns true if it was cached.
+    pub fn invalidate(&self, page_id: PageId) -> bool {
+        Self::remove(&mut self.state.lock(), page_id)
+    }
+
+    /// Returns true if `page_id` is cached.
+    pub fn contains(&self, page_id: PageId) -> bool {
+        self.state.lock().slots.contains_key(&page_id)
+    }
+
+    /// Returns the number of page slots.
+    pub fn capacity(&self) -> u32 {
+        self.state.lock().entries.len() as u32
+    }
+
+    /// Returns the cache's counters.
+    pub fn stats(&self) -> SecondaryCacheStats {
+        let state = self.state.lock();
+        SecondaryCacheStats {
+            cached: state.slots.len() as u32,
+            ..state.stats
+        }
+    }
+
+    /// Returns the path of the cache file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns a free slot, evicting the first unreferenced page the clock
+    /// hand reaches if there is none.
+    fn claim_slot(state: &mut SecondaryCacheState) -> u32 {
+        if let Some(free) = state.entries.iter().position(Option::is_none) {
+            return free as u32;
+        }
+        loop {
+            let slot = state.hand;
+            state.hand = (state.hand + 1) % state.entries.len();
+            let entry = state.entries[slot]
+                .as_mut()
+                .expect("full cache has an empty slot");
+            if entry.referenced {
+                entry.referenced = false;
+                continue;
+            }
+            let victim = entry.page_id;
+            state.slots.remove(&victim);
+            state.entries[slot] = None;
+            state.stats.evictions += 1;
+            return slot as u32;
+        }
+    }
+
+    fn remove(state: &mut SecondaryCacheState, page_id: PageId) -> bool {
+        match state.slots.remove(&page_id) {
+            Some(slot) => {
+                state.entries[slot as usize] = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn seek_slot(file: &mut File, slot: u32) -> Result<&mut File> {
+        file.seek(SeekFrom::Start(slot as u64 * PAGE_SIZE as u64))?;
+        Ok(file)
+    }
+}
+
+impl Drop for SecondaryCache {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(byte: u8) -> Vec<u8> {
+        vec![byte; PAGE_SIZE]
+    }
+
+    #[test]
+    fn test_secondary_cache_read_write() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cache = SecondaryCache::new(temp_dir.path().join("cache"), 4).unwrap();
+        let mut data = page(0);
+
+        assert!(!cache.read(PageId::new(1), &mut data).unwrap());
+        cache.insert(PageId::new(1), &page(1)).unwrap();
+        assert!(cache.read(PageId::new(1), &mut data).unwrap());
+        assert_eq!(data, page(1));
+
+        // A newer copy replaces the old one in place
+        cache.insert(PageId::new(1), &page(2)).unwrap();
+        assert!(cache.read(PageId::new(1), &mut data).unwrap());
+        assert_eq!(data, page(2));
+
+        assert!(cache.invalidate(PageId::new(1)));
+        assert!(!cache.invalidate(PageId::new(1)));
+        assert!(!cache.read(PageId::new(1), &mut data).unwrap());
+
+        let stats = cache.stats();
+        assert_eq!(stats.cached, 0);
+        assert_eq!((stats.hits, stats.misses), (2, 2));
+        assert_eq!(stats.insertions, 2);
+    }
+
+    #[test]
+    fn test_secondary_cache_clock_replacement() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cache = SecondaryCache::new(temp_dir.path().join("cache"), 3).unwrap();
+        let mut data = page(0);
+        for i in 0..3 {
+            cache.insert(PageId::new(i), &page(i as u8)).unwrap();
+        }
+
+        // Page 0 was read, so it gets a second chance and page 1 goes
+        assert!(cache.read(PageId::new(0), &mut data).unwrap());
+        cache.insert(PageId::new(3), &page(3)).unwrap();
+        assert!(cache.contains(PageId::new(0)));
+        assert!(!cache.contains(PageId::new(1)));
+        assert!(cache.read(PageId::new(3), &mut data).unwrap());
+        assert_eq!(data, page(3));
+
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.stats().cached, 3);
+        assert!(SecondaryCache::new(temp_dir.path().join("empty"), 0).is_err());
+    }
+
+    #[test]
+    fn test_secondary_cache_deleted_on_drop() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("cache");
+        let cache = SecondaryCache::new(&path, 1).unwrap();
+        assert!(path.exists());
+        drop(cache);
+        assert!(!path.exists());
+    }
+}
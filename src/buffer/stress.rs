@@ -0,0 +1,436 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::common::{CrioError, PageId, Result, PAGE_SIZE};
+use crate::workload::FastRng;
+
+use super::BufferPoolManager;
+
+/// Every stamped page starts with its page ID and a write counter; the rest
+/// of the page is filled with a byte derived from both, so a torn or
+/// misdirected write shows up as a mismatch anywhere in the page.
+const STAMP_PAGE_ID: usize = 0;
+const STAMP_VERSION: usize = 4;
+const STAMP_BODY: usize = 12;
+
+/// Number of consecutive cold pages an evictor reads per operation; long
+/// enough to trigger sequential prefetch.
+const COLD_RUN: u32 = 4;
+
+/// Violations recorded beyond this are only counted.
+const MAX_VIOLATIONS: usize = 64;
+
+/// Shape of a buffer pool stress run.
+#[derive(Debug, Clone)]
+pub struct StressConfig {
+    /// Threads that read stamped pages and verify them
+    pub readers: usize,
+    /// Threads that rewrite stamped pages
+    pub writers: usize,
+    /// Threads that stream through cold pages to force evictions, flush hot
+    /// pages and check pool invariants
+    pub evictors: usize,
+    /// Number of stamped pages readers and writers contend on
+    pub hot_pages: usize,
+    /// Number of pages evictors stream through
+    pub cold_pages: usize,
+    /// How long the threads run
+    pub duration: Duration,
+    /// A run in which no thread completes an operation for this long is
+    /// reported as stalled (usually a latch deadlock) and abandoned
+    pub stall_timeout: Duration,
+    /// Seed for the per-thread RNGs
+    pub seed: u64,
+}
+
+impl Default for StressConfig {
+    fn default() -> Self {
+        Self {
+            readers: 4,
+            writers: 2,
+            evictors: 1,
+            hot_pages: 16,
+            cold_pages: 64,
+            duration: Duration::from_secs(1),
+            stall_timeout: Duration::from_secs(5),
+            seed: 0,
+        }
+    }
+}
+
+/// Outcome of a stress run.
+#[derive(Debug, Default)]
+pub struct StressReport {
+    /// Page reads verified by readers and writers
+    pub reads: u64,
+    /// Page writes by writers
+    pub writes: u64,
+    /// Cold pages read by evictors
+    pub cold_reads: u64,
+    /// Fetches refused with `BufferPoolFull`; expected with tiny pools
+    pub pool_full: u64,
+    /// Times the pool invariants were checked
+    pub invariant_checks: u64,
+    /// Total number of violations, including ones not recorded below
+    pub violation_count: u64,
+    /// Descriptions of the first violations found
+    pub violations: Vec<String>,
+}
+
+impl StressReport {
+    /// Returns true if no violations were found.
+    pub fn is_ok(&self) -> bool {
+        self.violation_count == 0
+    }
+}
+
+impl fmt::Display for StressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} reads, {} writes, {} cold reads, {} pool-full retries, {} invariant checks",
+            self.reads, self.writes, self.cold_reads, self.pool_full, self.invariant_checks
+        )?;
+        if self.is_ok() {
+            return write!(f, "no violations");
+        }
+        writeln!(f, "{} violations:", self.violation_count)?;
+        for violation in &self.violations {
+            writeln!(f, "  {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Spawns reader, writer and evictor threads against a buffer pool and
+/// checks, while they run, that:
+///
+/// - every page read holds exactly the last write to it, with no torn or
+///   misdirected bytes,
+/// - a guarded page is pinned and no frame is unpinned more often than pinned,
+/// - no two frames hold the same page and the page table matches the frames
+///   (`BufferPoolManager::check_invariants`).
+///
+/// Give it a pool smaller than `hot_pages + cold_pages` so pages are evicted
+/// and read back while other threads hold them.
+pub struct BufferPoolStress {
+    bpm: Arc<BufferPoolManager>,
+    config: StressConfig,
+}
+
+/// State shared by the threads of one run.
+struct Run {
+    bpm: Arc<BufferPoolManager>,
+    hot: Vec<PageId>,
+    /// Version of the last write to each hot page
+    versions: Vec<AtomicU64>,
+    cold: Vec<PageId>,
+    stop: AtomicBool,
+    /// Operations completed by all threads, for stall detection
+    progress: AtomicU64,
+    report: Mutex<StressReport>,
+}
+
+#[derive(Default)]
+struct Counts {
+    reads: u64,
+    writes: u64,
+    cold_reads: u64,
+    pool_full: u64,
+    invariant_checks: u64,
+}
+
+impl BufferPoolStress {
+    pub fn new(bpm: Arc<BufferPoolManager>, config: StressConfig) -> Self {
+        Self { bpm, config }
+    }
+
+    /// Allocates and stamps the pages, runs the threads for the configured
+    /// duration and returns what they found. Errors only if setup fails.
+    ///
+    /// A stalled run is reported as a violation; its stuck threads are left
+    /// behind, still holding the buffer pool.
+    pub fn run(&self) -> Result<StressReport> {
+        let hot = (0..self.config.hot_pages)
+            .map(|_| self.bpm.new_page())
+            .collect::<Result<Vec<_>>>()?;
+        for &page_id in &hot {
+            let mut guard = self
+                .bpm
+                .checked_write_page(page_id)?
+                .ok_or(CrioError::InvalidPageId(page_id))?;
+            stamp(guard.data_mut(), page_id, 0);
+        }
+        let cold = (0..self.config.cold_pages)
+            .map(|_| self.bpm.new_page())
+            .collect::<Result<Vec<_>>>()?;
+
+        let run = Arc::new(Run {
+            bpm: Arc::clone(&self.bpm),
+            versions: hot.iter().map(|_| AtomicU64::new(0)).collect(),
+            hot,
+            cold,
+            stop: AtomicBool::new(false),
+            progress: AtomicU64::new(0),
+            report: Mutex::new(StressReport::default()),
+        });
+
+        let roles = std::iter::repeat_n(Run::read_hot as Op, self.config.readers)
+            .chain(std::iter::repeat_n(
+                Run::write_hot as Op,
+                self.config.writers,
+            ))
+            .chain(std::iter::repeat_n(Run::evict as Op, self.config.evictors));
+        let handles: Vec<_> = roles
+            .enumerate()
+            .map(|(i, op)| {
+                let run = Arc::clone(&run);
+                let mut rng = FastRng::new(self.config.seed.wrapping_add(i as u64));
+                std::thread::Builder::new()
+                    .name(format!("stress-{}", i))
+                    .spawn(move || run.worker(op, &mut rng))
+                    .expect("failed to spawn stress thread")
+            })
+            .collect();
+
+        // Keep watching for stalls until every thread has seen the stop flag
+        // and finished, since a deadlock can also strike while stopping
+        let deadline = Instant::now() + self.config.duration;
+        let mut last_progress = (0, Instant::now());
+        while !handles.iter().all(|h| h.is_finished()) {
+            std::thread::sleep(Duration::from_millis(5));
+            if Instant::now() >= deadline {
+                run.stop.store(true, Ordering::Relaxed);
+            }
+            let progress = run.progress.load(Ordering::Relaxed);
+            if progress != last_progress.0 {
+                last_progress = (progress, Instant::now());
+            } else if last_progress.1.elapsed() >= self.config.stall_timeout {
+                run.stop.store(true, Ordering::Relaxed);
+                run.violation(format!(
+                    "no operation completed for {:?}; threads are likely deadlocked",
+                    self.config.stall_timeout
+                ));
+                // The stuck threads may hold pool locks, so don't touch the pool
+                return Ok(std::mem::take(&mut *run.report.lock()));
+            }
+        }
+        for handle in handles {
+            if handle.join().is_err() {
+                run.violation("a stress thread panicked".to_string());
+            }
+        }
+
+        // With every guard dropped, nothing may stay pinned and every page
+        // must still hold its last write
+        for info in self.bpm.dump_pins(Duration::ZERO) {
+            run.violation(format!(
+                "{} still pinned {} times after all guards were dropped",
+                info.page_id, info.pin_count
+            ));
+        }
+        for violation in self.bpm.check_invariants() {
+            run.violation(violation);
+        }
+        let mut counts = Counts::default();
+        for index in 0..run.hot.len() {
+            run.verify_hot(index, &mut counts);
+        }
+
+        let mut report = std::mem::take(&mut *run.report.lock());
+        report.reads += counts.reads;
+        Ok(report)
+    }
+}
+
+type Op = fn(&Run, &mut FastRng, &mut Counts);
+
+impl Run {
+    fn violation(&self, message: String) {
+        let mut report = self.report.lock();
+        report.violation_count += 1;
+        if report.violations.len() < MAX_VIOLATIONS {
+            report.violations.push(message);
+        }
+    }
+
+    /// Runs `op` until the run stops, then adds the thread's counts to the report.
+    fn worker(&self, op: Op, rng: &mut FastRng) {
+        let mut counts = Counts::default();
+        while !self.stop.load(Ordering::Relaxed) {
+            op(self, rng, &mut counts);
+            self.progress.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut report = self.report.lock();
+        report.reads += counts.reads;
+        report.writes += counts.writes;
+        report.cold_reads += counts.cold_reads;
+        report.pool_full += counts.pool_full;
+        report.invariant_checks += counts.invariant_checks;
+    }
+
+    fn read_hot(&self, rng: &mut FastRng, counts: &mut Counts) {
+        let index = rng.below(self.hot.len() as u64) as usize;
+        self.verify_hot(index, counts);
+    }
+
+    /// Reads a hot page and checks it against its last recorded write.
+    fn verify_hot(&self, index: usize, counts: &mut Counts) {
+        let page_id = self.hot[index];
+        let guard = match self.bpm.checked_read_page(page_id) {
+            Ok(Some(guard)) => guard,
+            Ok(None) => return self.violation(format!("{} could not be fetched", page_id)),
+            Err(e) => return self.fetch_error(page_id, e, counts),
+        };
+        counts.reads += 1;
+        self.check_pinned(page_id);
+
+        // Writers bump the version while holding the write latch, so under a
+        // read latch the page and the version agree
+        let expected = self.versions[index].load(Ordering::Acquire);
+        if let Err(message) = verify_stamp(guard.data(), page_id, expected) {
+            self.violation(message);
+        }
+    }
+
+    fn write_hot(&self, rng: &mut FastRng, counts: &mut Counts) {
+        let index = rng.below(self.hot.len() as u64) as usize;
+        let page_id = self.hot[index];
+        let mut guard = match self.bpm.checked_write_page(page_id) {
+            Ok(Some(guard)) => guard,
+            Ok(None) => return self.violation(format!("{} could not be fetched", page_id)),
+            Err(e) => return self.fetch_error(page_id, e, counts),
+        };
+        counts.reads += 1;
+        self.check_pinned(page_id);
+
+        let version = self.versions[index].load(Ordering::Acquire);
+        if let Err(message) = verify_stamp(guard.data(), page_id, version) {
+            self.violation(message);
+        }
+        stamp(guard.data_mut(), page_id, version + 1);
+        self.versions[index].store(version + 1, Ordering::Release);
+        counts.writes += 1;
+    }
+
+    fn evict(&self, rng: &mut FastRng, counts: &mut Counts) {
+        if !self.cold.is_empty() {
+            let run = (COLD_RUN as usize).min(self.cold.len());
+            let start = rng.below((self.cold.len() - run + 1) as u64) as usize;
+            for &page_id in &self.cold[start..start + run] {
+                match self.bpm.checked_read_page(page_id) {
+                    Ok(Some(_)) => counts.cold_reads += 1,
+                    Ok(None) => self.violation(format!("{} could not be fetched", page_id)),
+                    Err(e) => self.fetch_error(page_id, e, counts),
+                }
+            }
+        }
+
+        if !self.hot.is_empty() {
+            let page_id = self.hot[rng.below(self.hot.len() as u64) as usize];
+            if let Err(e) = self.bpm.flush_page(page_id) {
+                self.violation(format!("flushing {} failed: {}", page_id, e));
+            }
+        }
+
+        counts.invariant_checks += 1;
+        for violation in self.bpm.check_invariants() {
+            self.violation(violation);
+        }
+    }
+
+    fn check_pinned(&self, page_id: PageId) {
+        match self.bpm.get_pin_count(page_id) {
+            Some(n) if n > 0 => {}
+            pins => self.violation(format!(
+                "{} is guarded but has pin count {:?}",
+                page_id, pins
+            )),
+        }
+    }
+
+    fn fetch_error(&self, page_id: PageId, error: CrioError, counts: &mut Counts) {
+        if matches!(error, CrioError::BufferPoolFull) {
+            counts.pool_full += 1;
+        } else {
+            self.violation(format!("fetching {} failed: {}", page_id, error));
+        }
+    }
+}
+
+fn fill_byte(page_id: PageId, version: u64) -> u8 {
+    (page_id.as_u32() as u8) ^ (version as u8).wrapping_mul(31)
+}
+
+fn stamp(data: &mut [u8], page_id: PageId, version: u64) {
+    data[STAMP_PAGE_ID..STAMP_PAGE_ID + 4].copy_from_slice(&page_id.as_u32().to_le_bytes());
+    data[STAMP_VERSION..STAMP_VERSION + 8].copy_from_slice(&version.to_le_bytes());
+    data[STAMP_BODY..PAGE_SIZE].fill(fill_byte(page_id, version));
+}
+
+fn verify_stamp(data: &[u8], page_id: PageId, expected: u64) -> std::result::Result<(), String> {
+    let stamped = PageId::new(u32::from_le_bytes(
+        data[STAMP_PAGE_ID..STAMP_PAGE_ID + 4].try_into().unwrap(),
+    ));
+    if stamped != page_id {
+        return Err(format!("{} holds the contents of {}", page_id, stamped));
+    }
+    let version = u64::from_le_bytes(data[STAMP_VERSION..STAMP_VERSION + 8].try_into().unwrap());
+    if version != expected {
+        return Err(format!(
+            "{} holds write {} but the last write was {}",
+            page_id, version, expected
+        ));
+    }
+    let fill = fill_byte(page_id, version);
+    if let Some(offset) = data[STAMP_BODY..].iter().position(|&b| b != fill) {
+        return Err(format!(
+            "{} is torn at offset {}: write {} expected {:#04x}",
+            page_id,
+            STAMP_BODY + offset,
+            version,
+            fill
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::disk::MemDiskManager;
+
+    #[test]
+    fn test_stress_small_pool() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            16,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ));
+        let config = StressConfig {
+            duration: Duration::from_millis(300),
+            ..StressConfig::default()
+        };
+        let report = BufferPoolStress::new(bpm, config).run().unwrap();
+        assert!(report.is_ok(), "{}", report);
+        assert!(report.writes > 0 && report.cold_reads > 0);
+    }
+
+    #[test]
+    fn test_stamp_detects_torn_page() {
+        let page_id = PageId::new(3);
+        let mut data = [0u8; PAGE_SIZE];
+        stamp(&mut data, page_id, 7);
+        assert!(verify_stamp(&data, page_id, 7).is_ok());
+        assert!(verify_stamp(&data, page_id, 6).is_err());
+        assert!(verify_stamp(&data, PageId::new(4), 7).is_err());
+
+        data[PAGE_SIZE - 1] ^= 1;
+        let message = verify_stamp(&data, page_id, 7).unwrap_err();
+        assert!(message.contains("torn"), "{}", message);
+    }
+}
@@ -0,0 +1,1842 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::{Mutex, RwLock};
+
+use super::{
+    PartitionScheme, PartitionedTableInfo, Privilege, Privileges, RoleInfo, ZoneMapInfo,
+    SYSTEM_TABLE_PREFIX,
+};
+use crate::buffer::BufferPoolManager;
+use crate::common::{CrioError, JobProgress, PageId, RecordId, Result};
+use crate::index::{BTreeIndex, ExtentBloomFilters, InvertedIndex, TextQuery};
+use crate::storage::table::TableHeap;
+use crate::tuple::{Collation, DataType, JsonPath, Schema, Tuple, Value};
+
+/// Table ID reserved for the catalog's own heap. User tables start at 1.
+pub const CATALOG_TABLE_ID: u32 = 0;
+
+const TABLE_RECORD: u8 = 0;
+const INDEX_RECORD: u8 = 1;
+const PARTITIONED_RECORD: u8 = 2;
+const TEXT_INDEX_RECORD: u8 = 3;
+const BLOOM_FILTER_RECORD: u8 = 4;
+const ZONE_MAP_RECORD: u8 = 5;
+const ROLE_RECORD: u8 = 6;
+const GRANT_RECORD: u8 = 7;
+
+const RANGE_PARTITIONS: u8 = 0;
+const HASH_PARTITIONS: u8 = 1;
+
+/// Metadata and storage for one table.
+pub struct TableInfo {
+    /// Table name
+    pub name: String,
+    /// Table ID stamped on the table's pages
+    pub table_id: u32,
+    /// Row layout
+    pub schema: Arc<Schema>,
+    /// Tuple storage
+    pub heap: TableHeap,
+    /// Rows deleted or updated since the table was last vacuumed, or since
+    /// the catalog was loaded
+    dead_tuples: AtomicU64,
+    /// Location of this table's catalog record
+    record_id: RecordId,
+}
+
+impl TableInfo {
+    /// Returns the number of rows deleted or updated since the table was
+    /// last vacuumed. The count is kept in memory only and starts at zero
+    /// when the catalog is loaded.
+    pub fn dead_tuples(&self) -> u64 {
+        self.dead_tuples.load(Ordering::Relaxed)
+    }
+
+    /// Counts `count` rows deleted or updated.
+    pub(crate) fn add_dead_tuples(&self, count: u64) {
+        self.dead_tuples.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Forgets `count` dead rows a vacuum has dealt with, keeping any counted
+    /// while it ran.
+    pub(crate) fn remove_dead_tuples(&self, count: u64) {
+        self.dead_tuples.fetch_sub(count, Ordering::Relaxed);
+    }
+}
+
+/// Metadata and storage for one single-column B+Tree index.
+pub struct IndexInfo {
+    /// Index name
+    pub name: String,
+    /// Table the index belongs to
+    pub table_id: u32,
+    /// Position of the key column in the table schema
+    pub key_column: usize,
+    /// For an index on a JSON column, the path of the indexed node
+    pub key_path: Option<JsonPath>,
+    /// The B+Tree, shareable between threads
+    pub index: Arc<BTreeIndex>,
+    /// Location of this index's catalog record, rewritten when the root moves
+    record_id: RecordId,
+    /// Root page as last written to the catalog record
+    persisted_root: Mutex<PageId>,
+}
+
+impl IndexInfo {
+    /// Returns the value a row is indexed under: its key column, or the
+    /// node at `key_path` within it.
+    pub fn key_value(&self, values: &[Value]) -> Value {
+        key_value(values, self.key_column, self.key_path.as_ref())
+    }
+
+    /// Returns the index key of a row, or None if the row is not indexed.
+    pub fn key_of(&self, values: &[Value]) -> Option<u32> {
+        index_key(&self.key_value(values))
+    }
+
+    /// Looks up the record ID stored under `key`.
+    pub fn search(&self, key: &Value) -> Result<Option<RecordId>> {
+        match index_key(key) {
+            Some(key) => self.index.search(key),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Metadata and storage for one full-text index on a VarChar column.
+pub struct TextIndexInfo {
+    /// Index name
+    pub name: String,
+    /// Table the index belongs to
+    pub table_id: u32,
+    /// Position of the indexed column in the table schema
+    pub key_column: usize,
+    /// The inverted index, shareable between threads
+    pub index: Arc<InvertedIndex>,
+    /// Location of this index's catalog record
+    record_id: RecordId,
+}
+
+impl TextIndexInfo {
+    /// Returns the text a row is indexed under, or None for NULL.
+    pub fn text_of<'a>(&self, values: &'a [Value]) -> Option<&'a str> {
+        match values.get(self.key_column) {
+            Some(Value::String(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the row's indexed text matches `query`.
+    pub fn matches(&self, values: &[Value], query: &TextQuery) -> bool {
+        self.text_of(values).is_some_and(|text| query.matches(text))
+    }
+}
+
+/// Per-extent bloom filters over one column of a table.
+///
+/// The filters live in memory only: they are built from the table's rows
+/// the first time they are needed after the catalog is loaded, and kept up
+/// to date by inserts and updates from then on. Only the filtered column is
+/// persisted.
+pub struct BloomFilterInfo {
+    /// Table the filters belong to
+    pub table_id: u32,
+    /// Position of the filtered column in the table schema
+    pub key_column: usize,
+    /// Collation of the filtered column, which string keys are folded by
+    collation: Collation,
+    /// The filters, or None until they are first built
+    filters: Mutex<Option<ExtentBloomFilters>>,
+    /// Location of this filter's catalog record
+    record_id: RecordId,
+}
+
+impl BloomFilterInfo {
+    /// Adds a row stored at `record_id`. Does nothing before the filters are
+    /// built, as building them will read the row from the table.
+    pub fn insert(&self, values: &[Value], record_id: RecordId) {
+        if let Some(filters) = self.filters.lock().as_mut() {
+            if let Some(key) = values
+                .get(self.key_column)
+                .and_then(|value| bloom_key(value, self.collation))
+            {
+                filters.insert(record_id, &key);
+            }
+        }
+    }
+
+    /// Returns the pages of `table` that may hold a row whose filtered column
+    /// equals `value`, building the filters first if needed. Returns None if
+    /// `value` can't be looked up in the filters.
+    pub fn candidate_pages(&self, table: &TableInfo, value: &Value) -> Result<Option<Vec<PageId>>> {
+        let Some(key) = bloom_key(value, self.collation) else {
+            return Ok(None);
+        };
+
+        let mut filters = self.filters.lock();
+        if filters.is_none() {
+            *filters = Some(self.build(table)?);
+        }
+        Ok(filters
+            .as_ref()
+            .map(|filters| filters.candidate_pages(&key)))
+    }
+
+    /// Rebuilds the filters from the rows of `table`, dropping the keys of
+    /// rows deleted or updated since they were built.
+    pub fn rebuild(&self, table: &TableInfo) -> Result<()> {
+        let mut filters = self.filters.lock();
+        *filters = Some(self.build(table)?);
+        Ok(())
+    }
+
+    /// Builds filters from the rows of `table`. The caller holds `filters`,
+    /// so rows inserted during the scan wait in `insert` and are added after.
+    fn build(&self, table: &TableInfo) -> Result<ExtentBloomFilters> {
+        let mut filters = ExtentBloomFilters::new();
+        for row in table.heap.iter() {
+            let (record_id, data) = row?;
+            let tuple = Tuple::from_bytes(Arc::clone(&table.schema), &data)
+                .ok_or(CrioError::SchemaMismatch)?;
+            if let Some(key) = tuple
+                .values()
+                .get(self.key_column)
+                .and_then(|value| bloom_key(value, self.collation))
+            {
+                filters.insert(record_id, &key);
+            }
+        }
+        Ok(filters)
+    }
+}
+
+struct CatalogState {
+    tables: HashMap<String, Arc<TableInfo>>,
+    indexes: HashMap<String, Arc<IndexInfo>>,
+    text_indexes: HashMap<String, Arc<TextIndexInfo>>,
+    /// Bloom filters by table ID
+    bloom_filters: HashMap<u32, Arc<BloomFilterInfo>>,
+    /// Zone maps by table ID
+    zone_maps: HashMap<u32, Arc<ZoneMapInfo>>,
+    partitioned: HashMap<String, Arc<PartitionedTableInfo>>,
+    roles: HashMap<String, Arc<RoleInfo>>,
+    /// Privileges granted by role and table ID, with their record
+    grants: HashMap<(String, u32), (Privileges, RecordId)>,
+    next_table_id: u32,
+    /// Number of schema changes since the catalog was loaded
+    schema_version: u64,
+    /// Schema version of the last change to each table, by table name
+    table_versions: HashMap<String, u64>,
+}
+
+impl CatalogState {
+    fn new() -> Self {
+        Self {
+            tables: HashMap::new(),
+            indexes: HashMap::new(),
+            text_indexes: HashMap::new(),
+            bloom_filters: HashMap::new(),
+            zone_maps: HashMap::new(),
+            partitioned: HashMap::new(),
+            roles: HashMap::new(),
+            grants: HashMap::new(),
+            next_table_id: CATALOG_TABLE_ID + 1,
+            schema_version: 0,
+            table_versions: HashMap::new(),
+        }
+    }
+
+    /// Records a change to the definition, indexes or grants of `table`.
+    fn touch(&mut self, table: &str) {
+        self.schema_version += 1;
+        self.table_versions
+            .insert(table.to_string(), self.schema_version);
+    }
+
+    /// Records a change to the table with ID `table_id`.
+    fn touch_id(&mut self, table_id: u32) {
+        let name = self
+            .tables
+            .values()
+            .find(|table| table.table_id == table_id)
+            .map(|table| table.name.clone());
+        if let Some(name) = name {
+            self.touch(&name);
+        }
+    }
+
+    /// Returns true if an index of either kind is called `name`.
+    fn has_index(&self, name: &str) -> bool {
+        self.indexes.contains_key(name) || self.text_indexes.contains_key(name)
+    }
+
+    /// Returns the partitioned table that `table` is a partition of.
+    fn parent_of(&self, table: &TableInfo) -> Option<&Arc<PartitionedTableInfo>> {
+        self.partitioned.values().find(|parent| {
+            parent
+                .partitions
+                .iter()
+                .any(|partition| partition.table_id == table.table_id)
+        })
+    }
+}
+
+/// Catalog tracks tables and indexes by name and persists their definitions in
+/// a TableHeap of its own (table ID `CATALOG_TABLE_ID`).
+///
+/// The catalog heap's pages and every index root are sticky in the buffer
+/// pool, so scans of large tables don't push them out.
+///
+/// Record formats (little endian):
+///
+/// ```text
+/// table: 0u8 | table_id u32 | first_page u32 | name_len u16 | name | schema
+/// index: 1u8 | table_id u32 | root_page u32 | key_column u32 | name_len u16 | name
+///        [| path_len u16 | key_path]
+/// partitioned: 2u8 | key_column u32 | name_len u16 | name | kind u8 | count u32
+///              | range bounds i64 * (count - 1) | partition table_id u32 * count
+/// text index: 3u8 | table_id u32 | dictionary_table_id u32 | dictionary_page u32
+///             | key_column u32 | name_len u16 | name
+/// bloom filter: 4u8 | table_id u32 | key_column u32
+/// zone map: 5u8 | table_id u32 | count u32 | column u32 * count
+/// role: 6u8 | superuser u8 | name_len u16 | name
+/// grant: 7u8 | table_id u32 | privileges u8 | role_len u16 | role
+/// ```
+///
+/// The partitions of a partitioned table are ordinary tables named
+/// `<name>_p<i>`; the partitioned table's record lists them by table ID.
+/// A text index's term dictionary is a heap with a table ID of its own, so
+/// its pages are told apart from the table's.
+pub struct Catalog {
+    /// Buffer pool shared with every table and index
+    bpm: Arc<BufferPoolManager>,
+    /// Heap holding catalog records
+    heap: TableHeap,
+    /// In-memory view of the catalog records
+    state: RwLock<CatalogState>,
+}
+
+impl Catalog {
+    /// Creates an empty catalog with a fresh catalog heap.
+    pub fn create(bpm: Arc<BufferPoolManager>) -> Result<Self> {
+        let heap = TableHeap::create(Arc::clone(&bpm), CATALOG_TABLE_ID)?;
+        bpm.set_sticky(heap.first_page_id());
+        Ok(Self {
+            bpm,
+            heap,
+            state: RwLock::new(CatalogState::new()),
+        })
+    }
+
+    /// Loads a catalog whose heap starts at `root_page_id`.
+    pub fn open(bpm: Arc<BufferPoolManager>, root_page_id: PageId) -> Result<Self> {
+        let heap = TableHeap::open(Arc::clone(&bpm), CATALOG_TABLE_ID, root_page_id)?;
+        for page_id in heap.page_ids()? {
+            bpm.set_sticky(page_id);
+        }
+        let mut state = CatalogState::new();
+        // Partitioned tables are resolved once every partition is loaded,
+        // and bloom filters and zone maps once their table is
+        let mut partitioned = Vec::new();
+        let mut bloom_filters = Vec::new();
+        let mut zone_maps = Vec::new();
+
+        for (record_id, data) in heap.scan()? {
+            let mut reader = RecordReader::new(&data);
+            match reader.u8()? {
+                TABLE_RECORD => {
+                    let table_id = reader.u32()?;
+                    let first_page_id = PageId::new(reader.u32()?);
+                    let name = reader.string()?;
+                    let schema = Schema::deserialize(reader.rest()).ok_or_else(|| {
+                        CrioError::CatalogCorrupted(format!("bad schema for table '{}'", name))
+                    })?;
+
+                    let heap = TableHeap::open(Arc::clone(&bpm), table_id, first_page_id)?;
+                    state.next_table_id = state.next_table_id.max(table_id + 1);
+                    state.tables.insert(
+                        name.clone(),
+                        Arc::new(TableInfo {
+                            name,
+                            table_id,
+                            schema: Arc::new(schema),
+                            heap,
+                            dead_tuples: AtomicU64::new(0),
+                            record_id,
+                        }),
+                    );
+                }
+                INDEX_RECORD => {
+                    let table_id = reader.u32()?;
+                    let root_page_id = PageId::new(reader.u32()?);
+                    let key_column = reader.u32()? as usize;
+                    let name = reader.string()?;
+                    // Records written before path indexes end at the name
+                    let key_path = if reader.rest().is_empty() {
+                        None
+                    } else {
+                        let path = reader.string()?;
+                        Some(JsonPath::parse(&path).map_err(|_| {
+                            CrioError::CatalogCorrupted(format!(
+                                "bad key path for index '{}'",
+                                name
+                            ))
+                        })?)
+                    };
+
+                    let index = BTreeIndex::open(root_page_id, Arc::clone(&bpm))?;
+                    bpm.set_sticky(root_page_id);
+                    state.indexes.insert(
+                        name.clone(),
+                        Arc::new(IndexInfo {
+                            name,
+                            table_id,
+                            key_column,
+                            key_path,
+                            index: Arc::new(index),
+                            record_id,
+                            persisted_root: Mutex::new(root_page_id),
+                        }),
+                    );
+                }
+                TEXT_INDEX_RECORD => {
+                    let table_id = reader.u32()?;
+                    let dictionary_table_id = reader.u32()?;
+                    let dictionary_page_id = PageId::new(reader.u32()?);
+                    let key_column = reader.u32()? as usize;
+                    let name = reader.string()?;
+
+                    let index = InvertedIndex::open(
+                        Arc::clone(&bpm),
+                        dictionary_table_id,
+                        dictionary_page_id,
+                    )?;
+                    state.next_table_id = state.next_table_id.max(dictionary_table_id + 1);
+                    state.text_indexes.insert(
+                        name.clone(),
+                        Arc::new(TextIndexInfo {
+                            name,
+                            table_id,
+                            key_column,
+                            index: Arc::new(index),
+                            record_id,
+                        }),
+                    );
+                }
+                BLOOM_FILTER_RECORD => {
+                    let table_id = reader.u32()?;
+                    let key_column = reader.u32()? as usize;
+                    bloom_filters.push((record_id, table_id, key_column));
+                }
+                ZONE_MAP_RECORD => {
+                    let table_id = reader.u32()?;
+                    let count = reader.u32()? as usize;
+                    let columns: Vec<usize> = (0..count)
+                        .map(|_| Ok(reader.u32()? as usize))
+                        .collect::<Result<_>>()?;
+                    zone_maps.push((record_id, table_id, columns));
+                }
+                ROLE_RECORD => {
+                    let superuser = reader.u8()? != 0;
+                    let name = reader.string()?;
+                    state.roles.insert(
+                        name.clone(),
+                        Arc::new(RoleInfo {
+                            name,
+                            superuser,
+                            record_id,
+                        }),
+                    );
+                }
+                GRANT_RECORD => {
+                    let table_id = reader.u32()?;
+                    let privileges = Privileges::from_bits(reader.u8()?);
+                    let role = reader.string()?;
+                    state
+                        .grants
+                        .insert((role, table_id), (privileges, record_id));
+                }
+                PARTITIONED_RECORD => {
+                    let key_column = reader.u32()? as usize;
+                    let name = reader.string()?;
+                    let kind = reader.u8()?;
+                    let count = reader.u32()? as usize;
+                    let scheme = match kind {
+                        RANGE_PARTITIONS => PartitionScheme::Range(
+                            (1..count).map(|_| reader.i64()).collect::<Result<_>>()?,
+                        ),
+                        HASH_PARTITIONS => PartitionScheme::Hash(count as u32),
+                        kind => {
+                            return Err(CrioError::CatalogCorrupted(format!(
+                                "unknown partitioning {} for table '{}'",
+                                kind, name
+                            )))
+                        }
+                    };
+                    let table_ids: Vec<u32> =
+                        (0..count).map(|_| reader.u32()).collect::<Result<_>>()?;
+                    partitioned.push((record_id, key_column, name, scheme, table_ids));
+                }
+                kind => {
+                    return Err(CrioError::CatalogCorrupted(format!(
+                        "unknown record kind {}",
+                        kind
+                    )))
+                }
+            }
+        }
+
+        let collation_of = |state: &CatalogState, table_id: u32, column: usize| {
+            state
+                .tables
+                .values()
+                .find(|table| table.table_id == table_id)
+                .and_then(|table| table.schema.column(column))
+                .map(|column| column.collation())
+                .ok_or_else(|| {
+                    CrioError::CatalogCorrupted(format!(
+                        "missing column {} of table {}",
+                        column, table_id
+                    ))
+                })
+        };
+        for (record_id, table_id, key_column) in bloom_filters {
+            let collation = collation_of(&state, table_id, key_column)?;
+            state.bloom_filters.insert(
+                table_id,
+                Arc::new(BloomFilterInfo {
+                    table_id,
+                    key_column,
+                    collation,
+                    filters: Mutex::new(None),
+                    record_id,
+                }),
+            );
+        }
+
+        for (record_id, table_id, columns) in zone_maps {
+            let collations = columns
+                .iter()
+                .map(|&column| collation_of(&state, table_id, column))
+                .collect::<Result<_>>()?;
+            state.zone_maps.insert(
+                table_id,
+                Arc::new(ZoneMapInfo::new(table_id, columns, collations, record_id)),
+            );
+        }
+
+        for (record_id, key_column, name, scheme, table_ids) in partitioned {
+            let partitions = table_ids
+                .iter()
+                .map(|&table_id| {
+                    state
+                        .tables
+                        .values()
+                        .find(|table| table.table_id == table_id)
+                        .cloned()
+                        .ok_or_else(|| {
+                            CrioError::CatalogCorrupted(format!(
+                                "missing partition {} of table '{}'",
+                                table_id, name
+                            ))
+                        })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            state.partitioned.insert(
+                name.clone(),
+                Arc::new(PartitionedTableInfo {
+                    name,
+                    schema: Arc::clone(&partitions[0].schema),
+                    key_column,
+                    scheme,
+                    partitions,
+                    record_id,
+                }),
+            );
+        }
+
+        Ok(Self {
+            bpm,
+            heap,
+            state: RwLock::new(state),
+        })
+    }
+
+    /// Returns the first page of the catalog heap; pass it to `open` to reload.
+    pub fn root_page_id(&self) -> PageId {
+        self.heap.first_page_id()
+    }
+
+    /// Returns the number of schema changes since the catalog was loaded:
+    /// tables, indexes, bloom filters, zone maps and grants created,
+    /// dropped or changed.
+    pub fn schema_version(&self) -> u64 {
+        self.state.read().schema_version
+    }
+
+    /// Returns the schema version of the last change to the table called
+    /// `name` (including its creation or drop), or 0 if it hasn't changed
+    /// since the catalog was loaded. Plans made at a table's current
+    /// version stay valid until it moves on.
+    pub fn table_version(&self, name: &str) -> u64 {
+        self.state
+            .read()
+            .table_versions
+            .get(name)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Returns the buffer pool shared with every table and index.
+    pub(crate) fn buffer_pool(&self) -> &Arc<BufferPoolManager> {
+        &self.bpm
+    }
+
+    /// Creates a table with an empty heap. Names starting with
+    /// `SYSTEM_TABLE_PREFIX` are reserved, and every column default must fit
+    /// its column.
+    pub fn create_table(&self, name: &str, schema: Schema) -> Result<Arc<TableInfo>> {
+        check_new_table(name, &schema)?;
+        let mut state = self.state.write();
+        if state.tables.contains_key(name) || state.partitioned.contains_key(name) {
+            return Err(CrioError::DuplicateTableName(name.to_string()));
+        }
+        self.add_table(&mut state, name, schema)
+    }
+
+    /// Creates a partitioned table: one child table per partition of
+    /// `scheme`, all with `schema`, and rows routed between them by the
+    /// integer column `key_column`.
+    pub fn create_partitioned_table(
+        &self,
+        name: &str,
+        schema: Schema,
+        key_column: &str,
+        scheme: PartitionScheme,
+    ) -> Result<Arc<PartitionedTableInfo>> {
+        check_new_table(name, &schema)?;
+        scheme.validate()?;
+        let key_index = schema
+            .column_index(key_column)
+            .ok_or_else(|| CrioError::UnknownColumn(key_column.to_string()))?;
+        let data_type = schema.column(key_index).map(|c| c.data_type());
+        if !matches!(
+            data_type,
+            Some(DataType::TinyInt | DataType::SmallInt | DataType::Integer | DataType::BigInt)
+        ) {
+            return Err(CrioError::InvalidPartitionScheme(format!(
+                "key column '{}' is not an integer column",
+                key_column
+            )));
+        }
+
+        let mut state = self.state.write();
+        let names: Vec<_> = (0..scheme.partition_count())
+            .map(|i| PartitionedTableInfo::partition_name(name, i))
+            .collect();
+        if let Some(taken) = std::iter::once(name)
+            .chain(names.iter().map(String::as_str))
+            .find(|n| state.tables.contains_key(*n) || state.partitioned.contains_key(*n))
+        {
+            return Err(CrioError::DuplicateTableName(taken.to_string()));
+        }
+
+        let partitions = names
+            .iter()
+            .map(|partition| self.add_table(&mut state, partition, schema.clone()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut record = vec![PARTITIONED_RECORD];
+        record.extend_from_slice(&(key_index as u32).to_le_bytes());
+        push_string(&mut record, name);
+        match &scheme {
+            PartitionScheme::Range(bounds) => {
+                record.push(RANGE_PARTITIONS);
+                record.extend_from_slice(&(partitions.len() as u32).to_le_bytes());
+                for bound in bounds {
+                    record.extend_from_slice(&bound.to_le_bytes());
+                }
+            }
+            PartitionScheme::Hash(count) => {
+                record.push(HASH_PARTITIONS);
+                record.extend_from_slice(&count.to_le_bytes());
+            }
+        }
+        for partition in &partitions {
+            record.extend_from_slice(&partition.table_id.to_le_bytes());
+        }
+        let record_id = self.heap.insert_tuple(&record)?;
+
+        let info = Arc::new(PartitionedTableInfo {
+            name: name.to_string(),
+            schema: Arc::clone(&partitions[0].schema),
+            key_column: key_index,
+            scheme,
+            partitions,
+            record_id,
+        });
+        state
+            .partitioned
+            .insert(name.to_string(), Arc::clone(&info));
+        state.touch(name);
+        Ok(info)
+    }
+
+    /// Creates a table's heap and catalog record.
+    fn add_table(
+        &self,
+        state: &mut CatalogState,
+        name: &str,
+        schema: Schema,
+    ) -> Result<Arc<TableInfo>> {
+        let table_id = state.next_table_id;
+        let heap = TableHeap::create(Arc::clone(&self.bpm), table_id)?;
+
+        let mut record = vec![TABLE_RECORD];
+        record.extend_from_slice(&table_id.to_le_bytes());
+        record.extend_from_slice(&heap.first_page_id().as_u32().to_le_bytes());
+        push_string(&mut record, name);
+        record.extend(schema.serialize());
+        let record_id = self.heap.insert_tuple(&record)?;
+
+        let info = Arc::new(TableInfo {
+            name: name.to_string(),
+            table_id,
+            schema: Arc::new(schema),
+            heap,
+            dead_tuples: AtomicU64::new(0),
+            record_id,
+        });
+        state.next_table_id += 1;
+        state.tables.insert(name.to_string(), Arc::clone(&info));
+        state.touch(name);
+        Ok(info)
+    }
+
+    /// Drops the table called `name` along with its indexes: removes their
+    /// catalog records, then deletes their pages, throwing away any changes
+    /// still in the buffer pool. Fails with `ObjectInUse` while anything
+    /// outside the catalog holds the table or one of its indexes.
+    ///
+    /// Dropping a partitioned table drops all of its partitions. A single
+    /// partition can't be dropped on its own.
+    pub fn drop_table(&self, name: &str) -> Result<()> {
+        let mut state = self.state.write();
+        let page_ids = if state.partitioned.contains_key(name) {
+            self.drop_partitioned_table(&mut state, name)?
+        } else {
+            self.drop_plain_table(&mut state, name)?
+        };
+        drop(state);
+
+        self.release_pages(&page_ids)
+    }
+
+    /// Removes a partitioned table and its partitions from the catalog and
+    /// returns the pages to delete.
+    fn drop_partitioned_table(&self, state: &mut CatalogState, name: &str) -> Result<Vec<PageId>> {
+        let table = &state.partitioned[name];
+        if Arc::strong_count(table) > 1 {
+            return Err(CrioError::ObjectInUse(name.to_string()));
+        }
+        // Each partition is also held by the partitioned table
+        if let Some(partition) = table.partitions.iter().find(|p| Arc::strong_count(p) > 2) {
+            return Err(CrioError::ObjectInUse(partition.name.clone()));
+        }
+        let partitions: Vec<_> = table.partitions.iter().map(|p| p.name.clone()).collect();
+        if let Some(index) = state.indexes.values().find(|index| {
+            Arc::strong_count(index) > 1
+                && table
+                    .partitions
+                    .iter()
+                    .any(|p| p.table_id == index.table_id)
+        }) {
+            return Err(CrioError::ObjectInUse(index.name.clone()));
+        }
+        if let Some(index) = state.text_indexes.values().find(|index| {
+            Arc::strong_count(index) > 1
+                && table
+                    .partitions
+                    .iter()
+                    .any(|p| p.table_id == index.table_id)
+        }) {
+            return Err(CrioError::ObjectInUse(index.name.clone()));
+        }
+
+        self.heap.delete_tuple(table.record_id)?;
+        state.partitioned.remove(name);
+        state.touch(name);
+
+        let mut page_ids = Vec::new();
+        for partition in partitions {
+            page_ids.extend(self.drop_plain_table(state, &partition)?);
+        }
+        Ok(page_ids)
+    }
+
+    /// Removes a table and its indexes from the catalog and returns the pages
+    /// to delete.
+    fn drop_plain_table(&self, state: &mut CatalogState, name: &str) -> Result<Vec<PageId>> {
+        let table = state
+            .tables
+            .get(name)
+            .ok_or_else(|| CrioError::UnknownTable(name.to_string()))?;
+        if let Some(parent) = state.parent_of(table) {
+            return Err(CrioError::ObjectInUse(parent.name.clone()));
+        }
+        if Arc::strong_count(table) > 1 {
+            return Err(CrioError::ObjectInUse(name.to_string()));
+        }
+        let (table_id, table_record) = (table.table_id, table.record_id);
+        let index_names: Vec<_> = state
+            .indexes
+            .values()
+            .filter(|info| info.table_id == table_id)
+            .map(|info| info.name.clone())
+            .collect();
+        if let Some(index) = index_names
+            .iter()
+            .find(|n| Arc::strong_count(&state.indexes[*n]) > 1)
+        {
+            return Err(CrioError::ObjectInUse(index.clone()));
+        }
+        let text_index_names: Vec<_> = state
+            .text_indexes
+            .values()
+            .filter(|info| info.table_id == table_id)
+            .map(|info| info.name.clone())
+            .collect();
+        if let Some(index) = text_index_names
+            .iter()
+            .find(|n| Arc::strong_count(&state.text_indexes[*n]) > 1)
+        {
+            return Err(CrioError::ObjectInUse(index.clone()));
+        }
+
+        // Find every page before changing anything, so a failed read leaves
+        // the table intact
+        let mut page_ids = table.heap.page_ids()?;
+        for index in &index_names {
+            page_ids.extend(state.indexes[index].index.page_ids()?);
+        }
+        for index in &text_index_names {
+            page_ids.extend(state.text_indexes[index].index.page_ids()?);
+        }
+
+        // Indexes go first: a table whose drop stops halfway loses its indexes
+        // rather than leaving indexes behind without a table
+        for index in &index_names {
+            self.heap.delete_tuple(state.indexes[index].record_id)?;
+            state.indexes.remove(index);
+        }
+        for index in &text_index_names {
+            self.heap
+                .delete_tuple(state.text_indexes[index].record_id)?;
+            state.text_indexes.remove(index);
+        }
+        if let Some(filter) = state.bloom_filters.remove(&table_id) {
+            self.heap.delete_tuple(filter.record_id)?;
+        }
+        if let Some(zone_map) = state.zone_maps.remove(&table_id) {
+            self.heap.delete_tuple(zone_map.record_id)?;
+        }
+        let grants: Vec<_> = state
+            .grants
+            .keys()
+            .filter(|(_, id)| *id == table_id)
+            .cloned()
+            .collect();
+        for grant in grants {
+            if let Some((_, record_id)) = state.grants.remove(&grant) {
+                self.heap.delete_tuple(record_id)?;
+            }
+        }
+        self.heap.delete_tuple(table_record)?;
+        state.tables.remove(name);
+        state.touch(name);
+        Ok(page_ids)
+    }
+
+    /// Drops the index called `name`: removes its catalog record, then deletes
+    /// its pages. Fails with `ObjectInUse` while anything outside the catalog
+    /// holds the index.
+    pub fn drop_index(&self, name: &str) -> Result<()> {
+        let mut state = self.state.write();
+        if let Some(index) = state.text_indexes.get(name) {
+            if Arc::strong_count(index) > 1 {
+                return Err(CrioError::ObjectInUse(name.to_string()));
+            }
+            let (page_ids, table_id) = (index.index.page_ids()?, index.table_id);
+            self.heap.delete_tuple(index.record_id)?;
+            state.text_indexes.remove(name);
+            state.touch_id(table_id);
+            drop(state);
+            return self.release_pages(&page_ids);
+        }
+
+        let index = state
+            .indexes
+            .get(name)
+            .ok_or_else(|| CrioError::UnknownIndex(name.to_string()))?;
+        if Arc::strong_count(index) > 1 {
+            return Err(CrioError::ObjectInUse(name.to_string()));
+        }
+
+        let (page_ids, table_id) = (index.index.page_ids()?, index.table_id);
+        self.heap.delete_tuple(index.record_id)?;
+        state.indexes.remove(name);
+        state.touch_id(table_id);
+        drop(state);
+
+        self.release_pages(&page_ids)
+    }
+
+    /// Deletes the pages of a dropped object. The object is already gone from
+    /// the catalog, so a failure here leaks pages but nothing else.
+    fn release_pages(&self, page_ids: &[PageId]) -> Result<()> {
+        // Purge the buffered pages in one go, so a stray pin fails the drop
+        // before anything is deallocated
+        self.bpm.discard_pages(page_ids.iter().copied())?;
+        for &page_id in page_ids {
+            self.bpm.delete_page(page_id)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the table called `name`.
+    pub fn table(&self, name: &str) -> Option<Arc<TableInfo>> {
+        self.state.read().tables.get(name).cloned()
+    }
+
+    /// Returns the names of all tables, including partitions but not the
+    /// partitioned tables made of them.
+    pub fn table_names(&self) -> Vec<String> {
+        self.state.read().tables.keys().cloned().collect()
+    }
+
+    /// Returns the partitioned table called `name`.
+    pub fn partitioned_table(&self, name: &str) -> Option<Arc<PartitionedTableInfo>> {
+        self.state.read().partitioned.get(name).cloned()
+    }
+
+    /// Returns the names of all partitioned tables.
+    pub fn partitioned_table_names(&self) -> Vec<String> {
+        self.state.read().partitioned.keys().cloned().collect()
+    }
+
+    /// Creates a B+Tree index on an integer column of `table_name` and fills it
+    /// from the table's existing rows. Keys must be unique; NULLs are not
+    /// indexed.
+    pub fn create_index(
+        &self,
+        name: &str,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<Arc<IndexInfo>> {
+        self.create_index_with_progress(name, table_name, column_name, &JobProgress::new())
+    }
+
+    /// Does the work of `create_index`, counting each table page read in
+    /// `progress`. A cancel is noticed before each page and fails with
+    /// `Cancelled` before anything is written.
+    pub fn create_index_with_progress(
+        &self,
+        name: &str,
+        table_name: &str,
+        column_name: &str,
+        progress: &JobProgress,
+    ) -> Result<Arc<IndexInfo>> {
+        self.build_index(name, table_name, column_name, None, progress)
+    }
+
+    /// Creates a B+Tree index on the node at `path` within a JSON column, as
+    /// `json_get(path)` extracts it, and fills it from the table's existing
+    /// rows. Like `create_index`, keys must be unique integers in the i32
+    /// range; rows where the node is missing, NULL or not such an integer
+    /// are not indexed.
+    pub fn create_path_index(
+        &self,
+        name: &str,
+        table_name: &str,
+        column_name: &str,
+        path: &str,
+    ) -> Result<Arc<IndexInfo>> {
+        let path = JsonPath::parse(path)?;
+        self.build_index(
+            name,
+            table_name,
+            column_name,
+            Some(path),
+            &JobProgress::new(),
+        )
+    }
+
+    fn build_index(
+        &self,
+        name: &str,
+        table_name: &str,
+        column_name: &str,
+        key_path: Option<JsonPath>,
+        progress: &JobProgress,
+    ) -> Result<Arc<IndexInfo>> {
+        let mut state = self.state.write();
+        if state.has_index(name) {
+            return Err(CrioError::DuplicateIndexName(name.to_string()));
+        }
+
+        let table = state
+            .tables
+            .get(table_name)
+            .cloned()
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?;
+        let key_column = table
+            .schema
+            .column_index(column_name)
+            .ok_or_else(|| CrioError::UnknownColumn(column_name.to_string()))?;
+        match (
+            table.schema.column(key_column).map(|c| c.data_type()),
+            &key_path,
+        ) {
+            (Some(DataType::TinyInt | DataType::SmallInt | DataType::Integer), None) => {}
+            (Some(DataType::Json), Some(_)) => {}
+            _ => return Err(CrioError::UnindexableColumn(column_name.to_string())),
+        }
+
+        // Collect and check keys before filling the tree
+        let mut entries = Vec::new();
+        let page_ids = table.heap.page_ids()?;
+        progress.set_total_pages(page_ids.len() as u64);
+        for page_id in page_ids {
+            progress.checkpoint()?;
+            for row in table.heap.iter_pages(vec![page_id]) {
+                let (record_id, data) = row?;
+                let tuple = Tuple::from_bytes(Arc::clone(&table.schema), &data)
+                    .ok_or(CrioError::SchemaMismatch)?;
+                let key = key_value(tuple.values(), key_column, key_path.as_ref());
+                if let Some(key) = index_key(&key) {
+                    entries.push((key, record_id));
+                }
+            }
+            progress.page_done();
+        }
+        entries.sort_by_key(|&(key, _)| key);
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(CrioError::DuplicateKey(pair[0].0));
+        }
+
+        let index = BTreeIndex::new(Arc::clone(&self.bpm))?;
+        for (key, record_id) in entries {
+            index.insert(key, record_id)?;
+        }
+
+        let mut record = vec![INDEX_RECORD];
+        record.extend_from_slice(&table.table_id.to_le_bytes());
+        let root_page_id = index.root_page_id();
+        self.bpm.set_sticky(root_page_id);
+        record.extend_from_slice(&root_page_id.as_u32().to_le_bytes());
+        record.extend_from_slice(&(key_column as u32).to_le_bytes());
+        push_string(&mut record, name);
+        if let Some(path) = &key_path {
+            push_string(&mut record, &path.to_string());
+        }
+        let record_id = self.heap.insert_tuple(&record)?;
+
+        let info = Arc::new(IndexInfo {
+            name: name.to_string(),
+            table_id: table.table_id,
+            key_column,
+            key_path,
+            index: Arc::new(index),
+            record_id,
+            persisted_root: Mutex::new(root_page_id),
+        });
+        state.indexes.insert(name.to_string(), Arc::clone(&info));
+        state.touch(&table.name);
+        Ok(info)
+    }
+
+    /// Returns the index called `name`.
+    pub fn index(&self, name: &str) -> Option<Arc<IndexInfo>> {
+        self.state.read().indexes.get(name).cloned()
+    }
+
+    /// Returns every index on the table with ID `table_id`.
+    pub fn table_indexes(&self, table_id: u32) -> Vec<Arc<IndexInfo>> {
+        self.state
+            .read()
+            .indexes
+            .values()
+            .filter(|info| info.table_id == table_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Creates a full-text index on a VarChar column of `table_name` and
+    /// fills it from the table's existing rows. NULLs are not indexed.
+    pub fn create_text_index(
+        &self,
+        name: &str,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<Arc<TextIndexInfo>> {
+        let mut state = self.state.write();
+        if state.has_index(name) {
+            return Err(CrioError::DuplicateIndexName(name.to_string()));
+        }
+
+        let table = state
+            .tables
+            .get(table_name)
+            .cloned()
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?;
+        let key_column = table
+            .schema
+            .column_index(column_name)
+            .ok_or_else(|| CrioError::UnknownColumn(column_name.to_string()))?;
+        if !matches!(
+            table.schema.column(key_column).map(|c| c.data_type()),
+            Some(DataType::VarChar(_))
+        ) {
+            return Err(CrioError::UnindexableColumn(column_name.to_string()));
+        }
+
+        let dictionary_table_id = state.next_table_id;
+        let index = InvertedIndex::create(Arc::clone(&self.bpm), dictionary_table_id)?;
+        state.next_table_id += 1;
+        for (record_id, data) in table.heap.scan()? {
+            let tuple = Tuple::from_bytes(Arc::clone(&table.schema), &data)
+                .ok_or(CrioError::SchemaMismatch)?;
+            if let Some(Value::String(text)) = tuple.values().get(key_column) {
+                index.insert(text, record_id)?;
+            }
+        }
+
+        let mut record = vec![TEXT_INDEX_RECORD];
+        record.extend_from_slice(&table.table_id.to_le_bytes());
+        record.extend_from_slice(&dictionary_table_id.to_le_bytes());
+        record.extend_from_slice(&index.dictionary_page_id().as_u32().to_le_bytes());
+        record.extend_from_slice(&(key_column as u32).to_le_bytes());
+        push_string(&mut record, name);
+        let record_id = self.heap.insert_tuple(&record)?;
+
+        let info = Arc::new(TextIndexInfo {
+            name: name.to_string(),
+            table_id: table.table_id,
+            key_column,
+            index: Arc::new(index),
+            record_id,
+        });
+        state
+            .text_indexes
+            .insert(name.to_string(), Arc::clone(&info));
+        state.touch(&table.name);
+        Ok(info)
+    }
+
+    /// Returns the full-text index called `name`.
+    pub fn text_index(&self, name: &str) -> Option<Arc<TextIndexInfo>> {
+        self.state.read().text_indexes.get(name).cloned()
+    }
+
+    /// Returns every full-text index on the table with ID `table_id`.
+    pub fn table_text_indexes(&self, table_id: u32) -> Vec<Arc<TextIndexInfo>> {
+        self.state
+            .read()
+            .text_indexes
+            .values()
+            .filter(|info| info.table_id == table_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Keeps per-extent bloom filters over a column of `table_name`, so scans
+    /// for one value of the column read only the extents that may hold it.
+    /// Integer, Char and VarChar columns can be filtered; a table has at most
+    /// one filtered column.
+    pub fn create_bloom_filter(
+        &self,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<Arc<BloomFilterInfo>> {
+        let mut state = self.state.write();
+        let table = state
+            .tables
+            .get(table_name)
+            .cloned()
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?;
+        if state.bloom_filters.contains_key(&table.table_id) {
+            return Err(CrioError::DuplicateBloomFilter(table_name.to_string()));
+        }
+        let key_column = table
+            .schema
+            .column_index(column_name)
+            .ok_or_else(|| CrioError::UnknownColumn(column_name.to_string()))?;
+        let column = table.schema.column(key_column);
+        match column.map(|c| c.data_type()) {
+            Some(
+                DataType::TinyInt
+                | DataType::SmallInt
+                | DataType::Integer
+                | DataType::BigInt
+                | DataType::Char(_)
+                | DataType::VarChar(_),
+            ) => {}
+            _ => return Err(CrioError::UnindexableColumn(column_name.to_string())),
+        }
+
+        let mut record = vec![BLOOM_FILTER_RECORD];
+        record.extend_from_slice(&table.table_id.to_le_bytes());
+        record.extend_from_slice(&(key_column as u32).to_le_bytes());
+        let record_id = self.heap.insert_tuple(&record)?;
+
+        let info = Arc::new(BloomFilterInfo {
+            table_id: table.table_id,
+            key_column,
+            collation: column.map_or(Collation::Binary, |c| c.collation()),
+            filters: Mutex::new(None),
+            record_id,
+        });
+        state
+            .bloom_filters
+            .insert(table.table_id, Arc::clone(&info));
+        state.touch(table_name);
+        Ok(info)
+    }
+
+    /// Stops keeping bloom filters for `table_name`.
+    pub fn drop_bloom_filter(&self, table_name: &str) -> Result<()> {
+        let mut state = self.state.write();
+        let table_id = state
+            .tables
+            .get(table_name)
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?
+            .table_id;
+        let filter = state
+            .bloom_filters
+            .get(&table_id)
+            .ok_or_else(|| CrioError::UnknownIndex(format!("bloom filter on {}", table_name)))?;
+        self.heap.delete_tuple(filter.record_id)?;
+        state.bloom_filters.remove(&table_id);
+        state.touch(table_name);
+        Ok(())
+    }
+
+    /// Returns the bloom filters of the table with ID `table_id`.
+    pub fn bloom_filter(&self, table_id: u32) -> Option<Arc<BloomFilterInfo>> {
+        self.state.read().bloom_filters.get(&table_id).cloned()
+    }
+
+    /// Keeps per-page min/max values of some columns of `table_name`, so range
+    /// scans on those columns read only the pages that can hold matching
+    /// rows. Any column but a JSON one can be covered; a table has at most
+    /// one zone map.
+    pub fn create_zone_map(
+        &self,
+        table_name: &str,
+        column_names: &[&str],
+    ) -> Result<Arc<ZoneMapInfo>> {
+        let mut state = self.state.write();
+        let table = state
+            .tables
+            .get(table_name)
+            .cloned()
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?;
+        if state.zone_maps.contains_key(&table.table_id) {
+            return Err(CrioError::DuplicateZoneMap(table_name.to_string()));
+        }
+        let columns = column_names
+            .iter()
+            .map(|&name| {
+                let column = table
+                    .schema
+                    .column_index(name)
+                    .ok_or_else(|| CrioError::UnknownColumn(name.to_string()))?;
+                match table.schema.column(column).map(|c| c.data_type()) {
+                    Some(DataType::Json) | None => {
+                        Err(CrioError::UnindexableColumn(name.to_string()))
+                    }
+                    Some(_) => Ok(column),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut record = vec![ZONE_MAP_RECORD];
+        record.extend_from_slice(&table.table_id.to_le_bytes());
+        record.extend_from_slice(&(columns.len() as u32).to_le_bytes());
+        for &column in &columns {
+            record.extend_from_slice(&(column as u32).to_le_bytes());
+        }
+        let record_id = self.heap.insert_tuple(&record)?;
+
+        let collations = columns
+            .iter()
+            .filter_map(|&column| table.schema.column(column))
+            .map(|column| column.collation())
+            .collect();
+        let info = Arc::new(ZoneMapInfo::new(
+            table.table_id,
+            columns,
+            collations,
+            record_id,
+        ));
+        state.zone_maps.insert(table.table_id, Arc::clone(&info));
+        state.touch(table_name);
+        Ok(info)
+    }
+
+    /// Stops keeping zone maps for `table_name`.
+    pub fn drop_zone_map(&self, table_name: &str) -> Result<()> {
+        let mut state = self.state.write();
+        let table_id = state
+            .tables
+            .get(table_name)
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?
+            .table_id;
+        let zone_map = state
+            .zone_maps
+            .get(&table_id)
+            .ok_or_else(|| CrioError::UnknownIndex(format!("zone map on {}", table_name)))?;
+        self.heap.delete_tuple(zone_map.record_id)?;
+        state.zone_maps.remove(&table_id);
+        state.touch(table_name);
+        Ok(())
+    }
+
+    /// Returns the zone maps of the table with ID `table_id`.
+    pub fn zone_map(&self, table_id: u32) -> Option<Arc<ZoneMapInfo>> {
+        self.state.read().zone_maps.get(&table_id).cloned()
+    }
+
+    /// Creates a role. A superuser role holds every privilege on every table.
+    pub fn create_role(&self, name: &str, superuser: bool) -> Result<Arc<RoleInfo>> {
+        let mut state = self.state.write();
+        if state.roles.contains_key(name) {
+            return Err(CrioError::DuplicateRole(name.to_string()));
+        }
+        let mut record = vec![ROLE_RECORD, u8::from(superuser)];
+        push_string(&mut record, name);
+        let record_id = self.heap.insert_tuple(&record)?;
+
+        let info = Arc::new(RoleInfo {
+            name: name.to_string(),
+            superuser,
+            record_id,
+        });
+        state.roles.insert(name.to_string(), Arc::clone(&info));
+        Ok(info)
+    }
+
+    /// Drops a role and everything granted to it.
+    pub fn drop_role(&self, name: &str) -> Result<()> {
+        let mut state = self.state.write();
+        let role_record = state
+            .roles
+            .get(name)
+            .ok_or_else(|| CrioError::UnknownRole(name.to_string()))?
+            .record_id;
+        let grants: Vec<_> = state
+            .grants
+            .keys()
+            .filter(|(role, _)| role == name)
+            .cloned()
+            .collect();
+        for grant in grants {
+            if let Some((_, record_id)) = state.grants.remove(&grant) {
+                self.heap.delete_tuple(record_id)?;
+                state.touch_id(grant.1);
+            }
+        }
+        self.heap.delete_tuple(role_record)?;
+        state.roles.remove(name);
+        Ok(())
+    }
+
+    /// Returns the role called `name`.
+    pub fn role(&self, name: &str) -> Option<Arc<RoleInfo>> {
+        self.state.read().roles.get(name).cloned()
+    }
+
+    /// Returns the names of all roles, in order.
+    pub fn role_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.state.read().roles.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Returns every grant as `(role, table, privileges)`, in order.
+    pub fn grants(&self) -> Vec<(String, String, Privileges)> {
+        let state = self.state.read();
+        let mut grants: Vec<_> = state
+            .grants
+            .iter()
+            .filter_map(|((role, table_id), (privileges, _))| {
+                let table = state.tables.values().find(|t| t.table_id == *table_id)?;
+                Some((role.clone(), table.name.clone(), *privileges))
+            })
+            .collect();
+        grants.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        grants
+    }
+
+    /// Grants `privileges` on `table_name` to `role`, on top of what it
+    /// holds already.
+    pub fn grant(&self, role: &str, table_name: &str, privileges: Privileges) -> Result<()> {
+        let mut state = self.state.write();
+        let (role, table_id) = Self::grant_key(&state, role, table_name)?;
+        let held = state.grants.get(&(role.clone(), table_id)).map(|g| g.0);
+        self.set_grant(
+            &mut state,
+            role,
+            table_id,
+            held.unwrap_or_default() | privileges,
+        )
+    }
+
+    /// Takes `privileges` on `table_name` away from `role`.
+    pub fn revoke(&self, role: &str, table_name: &str, privileges: Privileges) -> Result<()> {
+        let mut state = self.state.write();
+        let (role, table_id) = Self::grant_key(&state, role, table_name)?;
+        let held = state.grants.get(&(role.clone(), table_id)).map(|g| g.0);
+        self.set_grant(
+            &mut state,
+            role,
+            table_id,
+            held.unwrap_or_default().without(privileges),
+        )
+    }
+
+    /// Returns the privileges `role` holds on `table_name`: every one for a
+    /// superuser, those granted otherwise.
+    pub fn table_privileges(&self, role: &str, table_name: &str) -> Result<Privileges> {
+        let state = self.state.read();
+        let (role, table_id) = Self::grant_key(&state, role, table_name)?;
+        if state.roles[&role].superuser {
+            return Ok(Privileges::ALL);
+        }
+        Ok(state
+            .grants
+            .get(&(role, table_id))
+            .map(|g| g.0)
+            .unwrap_or_default())
+    }
+
+    /// Fails with `PermissionDenied` unless `role` holds `privilege` on
+    /// `table_name`.
+    pub fn check_privilege(
+        &self,
+        role: &str,
+        table_name: &str,
+        privilege: Privilege,
+    ) -> Result<()> {
+        if self.table_privileges(role, table_name)?.contains(privilege) {
+            return Ok(());
+        }
+        Err(CrioError::PermissionDenied {
+            role: role.to_string(),
+            privilege: privilege.to_string(),
+            table: table_name.to_string(),
+        })
+    }
+
+    /// Checks that `role` and `table_name` exist and returns the key of
+    /// their grant.
+    fn grant_key(state: &CatalogState, role: &str, table_name: &str) -> Result<(String, u32)> {
+        if !state.roles.contains_key(role) {
+            return Err(CrioError::UnknownRole(role.to_string()));
+        }
+        let table = state
+            .tables
+            .get(table_name)
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?;
+        Ok((role.to_string(), table.table_id))
+    }
+
+    /// Replaces the grant record of `role` on `table_id` with one for
+    /// `privileges`, or removes it if there are none.
+    fn set_grant(
+        &self,
+        state: &mut CatalogState,
+        role: String,
+        table_id: u32,
+        privileges: Privileges,
+    ) -> Result<()> {
+        state.touch_id(table_id);
+        if let Some((_, record_id)) = state.grants.remove(&(role.clone(), table_id)) {
+            self.heap.delete_tuple(record_id)?;
+        }
+        if privileges.is_empty() {
+            return Ok(());
+        }
+        let mut record = vec![GRANT_RECORD];
+        record.extend_from_slice(&table_id.to_le_bytes());
+        record.push(privileges.bits());
+        push_string(&mut record, &role);
+        let record_id = self.heap.insert_tuple(&record)?;
+        state
+            .grants
+            .insert((role, table_id), (privileges, record_id));
+        Ok(())
+    }
+
+    /// Inserts `key -> record_id` into an index, persisting the new root in the
+    /// catalog if the root has moved.
+    pub fn insert_index_entry(
+        &self,
+        info: &IndexInfo,
+        key: u32,
+        record_id: RecordId,
+    ) -> Result<()> {
+        info.index.insert(key, record_id)?;
+
+        // Read the root under the lock so the last writer records the latest
+        let mut persisted_root = info.persisted_root.lock();
+        let root = info.index.root_page_id();
+        if root != *persisted_root {
+            let mut record = self.heap.get_tuple(info.record_id)?;
+            record[5..9].copy_from_slice(&root.as_u32().to_le_bytes());
+            self.heap.update_tuple(info.record_id, &record)?;
+            self.bpm.clear_sticky(*persisted_root);
+            self.bpm.set_sticky(root);
+            *persisted_root = root;
+        }
+        Ok(())
+    }
+}
+
+/// Maps an integer value to an order-preserving u32 index key. Returns None for
+/// NULLs, non-integer values and BigInts outside the i32 range (integers
+/// extracted from JSON are BigInts).
+pub fn index_key(value: &Value) -> Option<u32> {
+    let v = match *value {
+        Value::TinyInt(v) => v as i32,
+        Value::SmallInt(v) => v as i32,
+        Value::Integer(v) => v,
+        Value::BigInt(v) => i32::try_from(v).ok()?,
+        _ => return None,
+    };
+    // Flip the sign bit so negative values sort before positive ones
+    Some((v as u32) ^ 0x8000_0000)
+}
+
+/// Maps a value to the bytes bloom filters hash. Integers of every width map
+/// alike, so a key matches whatever integer type it was given as, and strings
+/// map to their sort key under the column's `collation`, so strings it treats
+/// as equal hash alike. Returns None for NULLs and values of other types.
+fn bloom_key(value: &Value, collation: Collation) -> Option<Vec<u8>> {
+    let v = match *value {
+        Value::TinyInt(v) => v as i64,
+        Value::SmallInt(v) => v as i64,
+        Value::Integer(v) => v as i64,
+        Value::BigInt(v) => v,
+        Value::String(ref s) => return Some(collation.sort_key(s).into_owned()),
+        _ => return None,
+    };
+    Some(v.to_le_bytes().to_vec())
+}
+
+/// Returns the value a row is indexed under by an index on `key_column`
+/// and, for a JSON column, `key_path`.
+fn key_value(values: &[Value], key_column: usize, key_path: Option<&JsonPath>) -> Value {
+    let value = values.get(key_column).unwrap_or(&Value::Null);
+    match (key_path, value) {
+        (None, value) => value.clone(),
+        (Some(path), Value::Json(doc)) => doc.get(path).map_or(Value::Null, |node| node.to_value()),
+        (Some(_), _) => Value::Null,
+    }
+}
+
+/// Checks the parts of a new table definition that don't depend on the
+/// catalog's contents.
+fn check_new_table(name: &str, schema: &Schema) -> Result<()> {
+    if name.starts_with(SYSTEM_TABLE_PREFIX) {
+        return Err(CrioError::ReservedTableName(name.to_string()));
+    }
+    if let Some(column) = schema.invalid_default() {
+        return Err(CrioError::InvalidDefault(column.name().to_string()));
+    }
+    Ok(())
+}
+
+fn push_string(record: &mut Vec<u8>, s: &str) {
+    record.extend_from_slice(&(s.len() as u16).to_le_bytes());
+    record.extend_from_slice(s.as_bytes());
+}
+
+/// Cursor over a catalog record.
+struct RecordReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> RecordReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| CrioError::CatalogCorrupted("truncated record".to_string()))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = u16::from_le_bytes(self.take(2)?.try_into().unwrap()) as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| CrioError::CatalogCorrupted("invalid name".to_string()))
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.data[self.offset..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::disk::MemDiskManager;
+
+    fn create_bpm() -> Arc<BufferPoolManager> {
+        Arc::new(BufferPoolManager::new(
+            16,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ))
+    }
+
+    fn schema() -> Schema {
+        Schema::builder()
+            .column("id", DataType::Integer)
+            .column("name", DataType::VarChar(32))
+            .build()
+    }
+
+    #[test]
+    fn test_catalog_reload() {
+        let bpm = create_bpm();
+        let catalog = Catalog::create(Arc::clone(&bpm)).unwrap();
+
+        let users = catalog.create_table("users", schema()).unwrap();
+        assert!(matches!(
+            catalog.create_table("users", schema()),
+            Err(CrioError::DuplicateTableName(_))
+        ));
+
+        let row = Tuple::new(
+            Arc::clone(&users.schema),
+            vec![Value::Integer(-5), Value::String("eve".into())],
+        );
+        let rid = users.heap.insert_tuple(&row.to_bytes().unwrap()).unwrap();
+
+        let index = catalog.create_index("users_id", "users", "id").unwrap();
+        assert_eq!(index.search(&Value::Integer(-5)).unwrap(), Some(rid));
+        assert!(matches!(
+            catalog.create_index("users_name", "users", "name"),
+            Err(CrioError::UnindexableColumn(_))
+        ));
+
+        // Grow the index past a root split so the stored root must be rewritten
+        for i in 0..300 {
+            catalog
+                .insert_index_entry(&index, index_key(&Value::Integer(i)).unwrap(), rid)
+                .unwrap();
+        }
+        let root = index.index.root_page_id();
+
+        let reloaded = Catalog::open(Arc::clone(&bpm), catalog.root_page_id()).unwrap();
+        let users2 = reloaded.table("users").unwrap();
+        assert_eq!(users2.table_id, users.table_id);
+        assert_eq!(*users2.schema, schema());
+        assert_eq!(users2.heap.scan().unwrap().len(), 1);
+
+        let index2 = reloaded.index("users_id").unwrap();
+        assert_eq!(index2.index.root_page_id(), root);
+        assert_eq!(index2.search(&Value::Integer(-5)).unwrap(), Some(rid));
+        assert_eq!(reloaded.table_indexes(users.table_id).len(), 1);
+
+        // Table IDs keep increasing after a reload
+        let orders = reloaded.create_table("orders", schema()).unwrap();
+        assert_eq!(orders.table_id, users.table_id + 1);
+    }
+
+    #[test]
+    fn test_catalog_drop_frees_pages() {
+        let disk = Arc::new(MemDiskManager::new());
+        let bpm = Arc::new(BufferPoolManager::new(16, 2, Arc::clone(&disk) as _));
+        let catalog = Catalog::create(Arc::clone(&bpm)).unwrap();
+        let baseline = disk.get_num_pages();
+
+        let users = catalog.create_table("users", schema()).unwrap();
+        for i in 0..200 {
+            let row = Tuple::new(
+                Arc::clone(&users.schema),
+                vec![Value::Integer(i), Value::String("x".repeat(32))],
+            );
+            users.heap.insert_tuple(&row.to_bytes().unwrap()).unwrap();
+        }
+        let table_id = users.table_id;
+        catalog.create_index("users_id", "users", "id").unwrap();
+        catalog.create_index("users_id2", "users", "id").unwrap();
+        assert!(disk.get_num_pages() > baseline + 3);
+
+        // Outstanding references block the drop
+        assert!(matches!(
+            catalog.drop_table("users"),
+            Err(CrioError::ObjectInUse(_))
+        ));
+        drop(users);
+
+        catalog.drop_index("users_id2").unwrap();
+        assert!(matches!(
+            catalog.drop_index("users_id2"),
+            Err(CrioError::UnknownIndex(_))
+        ));
+        catalog.drop_table("users").unwrap();
+        assert!(catalog.table("users").is_none());
+        assert!(catalog.index("users_id").is_none());
+        assert!(catalog.table_indexes(table_id).is_empty());
+        assert_eq!(disk.get_num_pages(), baseline);
+        assert!(bpm.check_invariants().is_empty());
+
+        let reloaded = Catalog::open(Arc::clone(&bpm), catalog.root_page_id()).unwrap();
+        assert!(reloaded.table_names().is_empty());
+        assert!(reloaded.index("users_id").is_none());
+        reloaded.create_table("users", schema()).unwrap();
+    }
+
+    #[test]
+    fn test_catalog_partitioned_tables() {
+        let disk = Arc::new(MemDiskManager::new());
+        let bpm = Arc::new(BufferPoolManager::new(16, 2, Arc::clone(&disk) as _));
+        let catalog = Catalog::create(Arc::clone(&bpm)).unwrap();
+        let baseline = disk.get_num_pages();
+
+        let scheme = PartitionScheme::Range(vec![-10, 10]);
+        let events = catalog
+            .create_partitioned_table("events", schema(), "id", scheme.clone())
+            .unwrap();
+        assert_eq!(events.partitions[1].name, "events_p1");
+        assert!(matches!(
+            catalog.create_table("events_p0", schema()),
+            Err(CrioError::DuplicateTableName(_))
+        ));
+        assert!(matches!(
+            catalog.create_partitioned_table("bad", schema(), "name", PartitionScheme::Hash(2)),
+            Err(CrioError::InvalidPartitionScheme(_))
+        ));
+        catalog
+            .create_partitioned_table("buckets", schema(), "id", PartitionScheme::Hash(3))
+            .unwrap();
+        drop(events);
+
+        let reloaded = Catalog::open(Arc::clone(&bpm), catalog.root_page_id()).unwrap();
+        let events = reloaded.partitioned_table("events").unwrap();
+        assert_eq!(events.scheme, scheme);
+        assert_eq!(events.key_column, 0);
+        let names: Vec<_> = events.partitions.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["events_p0", "events_p1", "events_p2"]);
+        assert_eq!(
+            reloaded.partitioned_table("buckets").unwrap().scheme,
+            PartitionScheme::Hash(3)
+        );
+        drop(events);
+
+        // Partitions go with their table
+        assert!(matches!(
+            catalog.drop_table("events_p1"),
+            Err(CrioError::ObjectInUse(name)) if name == "events"
+        ));
+        catalog.drop_table("events").unwrap();
+        catalog.drop_table("buckets").unwrap();
+        assert!(catalog.partitioned_table_names().is_empty());
+        assert!(catalog.table_names().is_empty());
+        assert_eq!(disk.get_num_pages(), baseline);
+    }
+
+    #[test]
+    fn test_catalog_roles_and_grants() {
+        let disk = Arc::new(MemDiskManager::new());
+        let bpm = Arc::new(BufferPoolManager::new(16, 2, Arc::clone(&disk) as _));
+        let catalog = Catalog::create(Arc::clone(&bpm)).unwrap();
+        catalog.create_table("users", schema()).unwrap();
+        catalog.create_table("orders", schema()).unwrap();
+        catalog.create_role("admin", true).unwrap();
+        catalog.create_role("clerk", false).unwrap();
+        assert!(matches!(
+            catalog.create_role("clerk", false),
+            Err(CrioError::DuplicateRole(_))
+        ));
+
+        catalog
+            .grant("clerk", "users", Privilege::Select | Privilege::Insert)
+            .unwrap();
+        catalog
+            .grant("clerk", "users", Privilege::Update.into())
+            .unwrap();
+        catalog
+            .revoke("clerk", "users", Privilege::Insert.into())
+            .unwrap();
+        catalog.grant("clerk", "orders", Privileges::ALL).unwrap();
+        assert!(matches!(
+            catalog.grant("nobody", "users", Privileges::ALL),
+            Err(CrioError::UnknownRole(_))
+        ));
+        assert!(catalog
+            .check_privilege("clerk", "users", Privilege::Update)
+            .is_ok());
+        assert!(matches!(
+            catalog.check_privilege("clerk", "users", Privilege::Delete),
+            Err(CrioError::PermissionDenied { .. })
+        ));
+        assert_eq!(
+            catalog.table_privileges("admin", "users").unwrap(),
+            Privileges::ALL
+        );
+
+        catalog.drop_table("orders").unwrap();
+        let reloaded = Catalog::open(Arc::clone(&bpm), catalog.root_page_id()).unwrap();
+        assert_eq!(reloaded.role_names(), vec!["admin", "clerk"]);
+        assert!(reloaded.role("admin").unwrap().superuser);
+        assert_eq!(
+            reloaded.grants(),
+            vec![(
+                "clerk".to_string(),
+                "users".to_string(),
+                Privilege::Select | Privilege::Update
+            )]
+        );
+
+        reloaded.drop_role("clerk").unwrap();
+        assert!(reloaded.grants().is_empty());
+        let reloaded = Catalog::open(Arc::clone(&bpm), reloaded.root_page_id()).unwrap();
+        assert_eq!(reloaded.role_names(), vec!["admin"]);
+    }
+
+    #[test]
+    fn test_index_key_order() {
+        let keys: Vec<u32> = [i32::MIN, -1, 0, 1, i32::MAX]
+            .iter()
+            .map(|&v| index_key(&Value::Integer(v)).unwrap())
+            .collect();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(index_key(&Value::Null), None);
+        assert_eq!(index_key(&Value::BigInt(-1)), Some(keys[1]));
+        assert_eq!(index_key(&Value::BigInt(i32::MAX as i64 + 1)), None);
+    }
+}
@@ -1,2 +1,14 @@
-// Placeholder for catalog (metadata management)
-// TODO: Implement catalog components
+//! System catalog: table and index metadata, persisted in the database itself.
+
+#[allow(clippy::module_inception)]
+mod catalog;
+mod partition;
+mod privilege;
+mod system_table;
+mod zone_map;
+
+pub use catalog::*;
+pub use partition::*;
+pub use privilege::*;
+pub use system_table::*;
+pub use zone_map::*;
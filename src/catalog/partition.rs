@@ -0,0 +1,204 @@
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+use super::TableInfo;
+use crate::common::{CrioError, RecordId, Result};
+use crate::tuple::{Schema, Value};
+
+/// How a partitioned table spreads its rows over its partitions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartitionScheme {
+    /// Split at ascending bounds: with bounds `[b0, b1]`, partition 0 holds
+    /// keys below `b0`, partition 1 keys in `b0..b1` and partition 2 the rest.
+    Range(Vec<i64>),
+    /// Spread keys over this many partitions by a hash of the key.
+    Hash(u32),
+}
+
+impl PartitionScheme {
+    /// Returns the number of partitions.
+    pub fn partition_count(&self) -> usize {
+        match self {
+            PartitionScheme::Range(bounds) => bounds.len() + 1,
+            PartitionScheme::Hash(count) => *count as usize,
+        }
+    }
+
+    /// Checks that range bounds strictly ascend and that there is at least
+    /// one hash partition.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            PartitionScheme::Range(bounds) if bounds.windows(2).any(|w| w[0] >= w[1]) => Err(
+                CrioError::InvalidPartitionScheme("range bounds must ascend".to_string()),
+            ),
+            PartitionScheme::Hash(0) => Err(CrioError::InvalidPartitionScheme(
+                "hash partitioning needs at least one partition".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the partition holding `key`. NULL keys go to partition 0.
+    pub fn partition_of(&self, key: Option<i64>) -> usize {
+        let Some(key) = key else {
+            return 0;
+        };
+        match self {
+            PartitionScheme::Range(bounds) => bounds.partition_point(|&bound| bound <= key),
+            PartitionScheme::Hash(count) => (hash_key(key) % *count as u64) as usize,
+        }
+    }
+
+    /// Returns, in ascending order, the partitions that may hold keys in
+    /// `range`. Hash partitioning can only narrow a single-key range.
+    pub fn prune<R: RangeBounds<i64>>(&self, range: &R) -> Vec<usize> {
+        let Some((low, high)) = key_span(range) else {
+            return Vec::new();
+        };
+        match self {
+            PartitionScheme::Range(_) => {
+                (self.partition_of(Some(low))..=self.partition_of(Some(high))).collect()
+            }
+            PartitionScheme::Hash(_) if low == high => vec![self.partition_of(Some(low))],
+            PartitionScheme::Hash(count) => (0..*count as usize).collect(),
+        }
+    }
+}
+
+/// Maps an integer value to a partition key. Returns None for NULLs and
+/// non-integer values.
+pub fn partition_key(value: &Value) -> Option<i64> {
+    match *value {
+        Value::TinyInt(v) => Some(v as i64),
+        Value::SmallInt(v) => Some(v as i64),
+        Value::Integer(v) => Some(v as i64),
+        Value::BigInt(v) => Some(v),
+        _ => None,
+    }
+}
+
+/// Returns true if a row with partition key `key` falls in `range`. NULL keys
+/// only fall in the unbounded range.
+pub fn key_in_range<R: RangeBounds<i64>>(key: Option<i64>, range: &R) -> bool {
+    match key {
+        Some(key) => range.contains(&key),
+        None => matches!(
+            (range.start_bound(), range.end_bound()),
+            (Bound::Unbounded, Bound::Unbounded)
+        ),
+    }
+}
+
+/// Returns the smallest and largest key in `range`, or None if it is empty.
+fn key_span<R: RangeBounds<i64>>(range: &R) -> Option<(i64, i64)> {
+    let low = match range.start_bound() {
+        Bound::Included(&v) => v,
+        Bound::Excluded(&v) => v.checked_add(1)?,
+        Bound::Unbounded => i64::MIN,
+    };
+    let high = match range.end_bound() {
+        Bound::Included(&v) => v,
+        Bound::Excluded(&v) => v.checked_sub(1)?,
+        Bound::Unbounded => i64::MAX,
+    };
+    (low <= high).then_some((low, high))
+}
+
+/// Fixed 64-bit mix (the SplitMix64 finalizer). Hash partitions are stored
+/// on disk, so this must never change.
+fn hash_key(key: i64) -> u64 {
+    let mut x = key as u64;
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// A logical table whose rows are stored in child tables, one per partition,
+/// chosen by the value of an integer key column.
+pub struct PartitionedTableInfo {
+    /// Table name
+    pub name: String,
+    /// Row layout, shared by every partition
+    pub schema: Arc<Schema>,
+    /// Position of the partition key column in the schema
+    pub key_column: usize,
+    /// How keys map to partitions
+    pub scheme: PartitionScheme,
+    /// Child tables, in partition order
+    pub partitions: Vec<Arc<TableInfo>>,
+    /// Location of this table's catalog record
+    pub(super) record_id: RecordId,
+}
+
+impl PartitionedTableInfo {
+    /// Returns the partition key of a row.
+    pub fn key_of(&self, values: &[Value]) -> Option<i64> {
+        values.get(self.key_column).and_then(partition_key)
+    }
+
+    /// Returns the index of the partition a row belongs in.
+    pub fn route(&self, values: &[Value]) -> usize {
+        self.scheme.partition_of(self.key_of(values))
+    }
+
+    /// Returns the partitions that may hold keys in `range`.
+    pub fn prune<R: RangeBounds<i64>>(&self, range: &R) -> Vec<&Arc<TableInfo>> {
+        self.scheme
+            .prune(range)
+            .into_iter()
+            .map(|i| &self.partitions[i])
+            .collect()
+    }
+
+    /// Returns the name of partition `index` of the table called `name`.
+    pub fn partition_name(name: &str, index: usize) -> String {
+        format!("{}_p{}", name, index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_partitioning() {
+        let scheme = PartitionScheme::Range(vec![0, 100]);
+        assert_eq!(scheme.partition_count(), 3);
+        assert_eq!(scheme.partition_of(Some(-5)), 0);
+        assert_eq!(scheme.partition_of(Some(0)), 1);
+        assert_eq!(scheme.partition_of(Some(99)), 1);
+        assert_eq!(scheme.partition_of(Some(100)), 2);
+        assert_eq!(scheme.partition_of(None), 0);
+
+        assert_eq!(scheme.prune(&(10..50)), vec![1]);
+        assert_eq!(scheme.prune(&(-10..=0)), vec![0, 1]);
+        assert_eq!(scheme.prune(&(100..)), vec![2]);
+        assert_eq!(scheme.prune(&(..)), vec![0, 1, 2]);
+        assert!(scheme.prune(&(5..5)).is_empty());
+
+        assert!(PartitionScheme::Range(vec![5, 5]).validate().is_err());
+        assert!(PartitionScheme::Range(Vec::new()).validate().is_ok());
+    }
+
+    #[test]
+    fn test_hash_partitioning() {
+        let scheme = PartitionScheme::Hash(4);
+        let mut counts = [0; 4];
+        for key in 0..4000 {
+            counts[scheme.partition_of(Some(key))] += 1;
+        }
+        assert!(counts.iter().all(|&n| n > 800), "{:?}", counts);
+
+        assert_eq!(scheme.prune(&(7..=7)), vec![scheme.partition_of(Some(7))]);
+        assert_eq!(scheme.prune(&(7..9)).len(), 4);
+        assert!(PartitionScheme::Hash(0).validate().is_err());
+    }
+
+    #[test]
+    fn test_key_in_range() {
+        assert!(key_in_range(Some(3), &(0..5)));
+        assert!(!key_in_range(Some(5), &(0..5)));
+        assert!(!key_in_range(None, &(0..5)));
+        assert!(key_in_range(None, &(..)));
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::ops::BitOr;
+
+use crate::common::RecordId;
+
+/// One kind of access to a table that can be granted to a role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Privilege {
+    /// Reading rows: scans, lookups and text searches
+    Select,
+    /// Adding rows
+    Insert,
+    /// Changing rows, including upserts that update
+    Update,
+    /// Removing rows
+    Delete,
+}
+
+impl Privilege {
+    /// Every privilege, in bit order.
+    pub const ALL: [Privilege; 4] = [
+        Privilege::Select,
+        Privilege::Insert,
+        Privilege::Update,
+        Privilege::Delete,
+    ];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Privilege {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Privilege::Select => "SELECT",
+            Privilege::Insert => "INSERT",
+            Privilege::Update => "UPDATE",
+            Privilege::Delete => "DELETE",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A set of privileges, persisted as a bitmask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Privileges(u8);
+
+impl Privileges {
+    /// No privileges.
+    pub const NONE: Privileges = Privileges(0);
+    /// Every privilege (`GRANT ALL`).
+    pub const ALL: Privileges = Privileges(0b1111);
+
+    /// Returns the set with the privileges of `bits`, ignoring unknown ones.
+    pub fn from_bits(bits: u8) -> Self {
+        Privileges(bits & Self::ALL.0)
+    }
+
+    /// Returns the bitmask of the set.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns true if `privilege` is in the set.
+    pub fn contains(self, privilege: Privilege) -> bool {
+        self.0 & privilege.bit() != 0
+    }
+
+    /// Returns the privileges of the set not in `other`.
+    pub fn without(self, other: Privileges) -> Self {
+        Privileges(self.0 & !other.0)
+    }
+
+    /// Returns true if the set is empty.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the privileges in the set, in bit order.
+    pub fn iter(self) -> impl Iterator<Item = Privilege> {
+        Privilege::ALL
+            .into_iter()
+            .filter(move |p| self.contains(*p))
+    }
+}
+
+impl From<Privilege> for Privileges {
+    fn from(privilege: Privilege) -> Self {
+        Privileges(privilege.bit())
+    }
+}
+
+impl<T: Into<Privileges>> BitOr<T> for Privileges {
+    type Output = Privileges;
+
+    fn bitor(self, other: T) -> Privileges {
+        Privileges(self.0 | other.into().0)
+    }
+}
+
+impl BitOr for Privilege {
+    type Output = Privileges;
+
+    fn bitor(self, other: Privilege) -> Privileges {
+        Privileges::from(self) | other
+    }
+}
+
+/// A role that sessions act as. A superuser role holds every privilege on
+/// every table; any other holds only what was granted to it.
+#[derive(Debug)]
+pub struct RoleInfo {
+    pub name: String,
+    pub superuser: bool,
+    /// Location of this role's catalog record
+    pub(super) record_id: RecordId,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_privileges() {
+        let set = Privilege::Select | Privilege::Update;
+        assert!(set.contains(Privilege::Select));
+        assert!(!set.contains(Privilege::Insert));
+        assert_eq!(Privileges::from_bits(set.bits()), set);
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            vec![Privilege::Select, Privilege::Update]
+        );
+        assert_eq!(
+            set.without(Privilege::Select.into()),
+            Privilege::Update.into()
+        );
+        assert!(Privileges::ALL.without(Privileges::ALL).is_empty());
+        assert_eq!(Privilege::Delete.to_string(), "DELETE");
+    }
+}
@@ -0,0 +1,248 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use super::Catalog;
+use crate::common::Result;
+use crate::tuple::{DataType, Schema, Tuple, Value};
+
+/// Table names starting with this prefix are reserved for system tables.
+pub const SYSTEM_TABLE_PREFIX: &str = "crio_";
+
+const NAME_TYPE: DataType = DataType::VarChar(255);
+
+/// Read-only virtual tables describing the catalog. Their rows are generated
+/// from the catalog's current state on every scan; nothing is stored on disk.
+///
+/// ```text
+/// crio_tables:  table_id | table_name | first_page | num_pages | num_indexes
+/// crio_columns: table_name | column_name | ordinal | data_type | nullable | default_value
+///               | collation
+/// crio_indexes: index_name | table_name | column_name | root_page | num_pages
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemTable {
+    /// `crio_tables`: one row per user table
+    Tables,
+    /// `crio_columns`: one row per column of every user table
+    Columns,
+    /// `crio_indexes`: one row per index; for a full-text index, `root_page`
+    /// is the first page of its term dictionary
+    Indexes,
+}
+
+impl SystemTable {
+    /// Every system table.
+    pub const ALL: [SystemTable; 3] = [
+        SystemTable::Tables,
+        SystemTable::Columns,
+        SystemTable::Indexes,
+    ];
+
+    /// Returns the system table called `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|table| table.name() == name)
+    }
+
+    /// Returns the table name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SystemTable::Tables => "crio_tables",
+            SystemTable::Columns => "crio_columns",
+            SystemTable::Indexes => "crio_indexes",
+        }
+    }
+
+    /// Returns the row layout.
+    pub fn schema(&self) -> Schema {
+        let builder = Schema::builder();
+        match self {
+            SystemTable::Tables => builder
+                .column("table_id", DataType::Integer)
+                .column("table_name", NAME_TYPE)
+                .column("first_page", DataType::BigInt)
+                .column("num_pages", DataType::Integer)
+                .column("num_indexes", DataType::Integer),
+            SystemTable::Columns => builder
+                .column("table_name", NAME_TYPE)
+                .column("column_name", NAME_TYPE)
+                .column("ordinal", DataType::Integer)
+                .column("data_type", DataType::VarChar(32))
+                .column("nullable", DataType::Boolean)
+                .nullable_column("default_value", NAME_TYPE)
+                .column("collation", DataType::VarChar(32)),
+            SystemTable::Indexes => builder
+                .column("index_name", NAME_TYPE)
+                .column("table_name", NAME_TYPE)
+                .column("column_name", NAME_TYPE)
+                .column("root_page", DataType::BigInt)
+                .column("num_pages", DataType::Integer),
+        }
+        .build()
+    }
+}
+
+impl Catalog {
+    /// Returns the current rows of a system table, ordered by table name (and
+    /// then by column ordinal or index name).
+    pub fn scan_system_table(&self, table: SystemTable) -> Result<Vec<Tuple>> {
+        let schema = Arc::new(table.schema());
+        let mut names = self.table_names();
+        names.sort();
+
+        let mut rows = Vec::new();
+        for info in names.iter().filter_map(|name| self.table(name)) {
+            let mut indexes = self.table_indexes(info.table_id);
+            indexes.sort_by(|a, b| a.name.cmp(&b.name));
+            let text_indexes = self.table_text_indexes(info.table_id);
+
+            match table {
+                SystemTable::Tables => rows.push(vec![
+                    Value::Integer(info.table_id as i32),
+                    Value::String(info.name.clone()),
+                    Value::BigInt(info.heap.first_page_id().as_u32() as i64),
+                    Value::Integer(info.heap.page_ids()?.len() as i32),
+                    Value::Integer((indexes.len() + text_indexes.len()) as i32),
+                ]),
+                SystemTable::Columns => {
+                    for column in info.schema.columns() {
+                        rows.push(vec![
+                            Value::String(info.name.clone()),
+                            Value::String(column.name().to_string()),
+                            Value::Integer(column.ordinal() as i32),
+                            Value::String(column.data_type().to_string()),
+                            Value::Boolean(column.is_nullable()),
+                            column
+                                .default()
+                                .map_or(Value::Null, |d| Value::String(d.to_string())),
+                            Value::String(column.collation().to_string()),
+                        ]);
+                    }
+                }
+                SystemTable::Indexes => {
+                    let mut index_rows = Vec::new();
+                    for index in indexes {
+                        let mut column = info
+                            .schema
+                            .column(index.key_column)
+                            .map_or("", |c| c.name())
+                            .to_string();
+                        if let Some(path) = &index.key_path {
+                            column = format!("{}->'{}'", column, path);
+                        }
+                        let tree = &index.index;
+                        index_rows.push(vec![
+                            Value::String(index.name.clone()),
+                            Value::String(info.name.clone()),
+                            Value::String(column),
+                            Value::BigInt(tree.root_page_id().as_u32() as i64),
+                            Value::Integer(tree.page_ids()?.len() as i32),
+                        ]);
+                    }
+                    for index in text_indexes {
+                        let column = info
+                            .schema
+                            .column(index.key_column)
+                            .map_or("", |c| c.name());
+                        index_rows.push(vec![
+                            Value::String(index.name.clone()),
+                            Value::String(info.name.clone()),
+                            Value::String(column.to_string()),
+                            Value::BigInt(index.index.dictionary_page_id().as_u32() as i64),
+                            Value::Integer(index.index.page_ids()?.len() as i32),
+                        ]);
+                    }
+                    index_rows.sort_by(|a, b| a[0].compare(&b[0]).unwrap_or(Ordering::Equal));
+                    rows.extend(index_rows);
+                }
+            }
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|values| Tuple::new(Arc::clone(&schema), values))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::BufferPoolManager;
+    use crate::common::CrioError;
+    use crate::storage::disk::MemDiskManager;
+
+    #[test]
+    fn test_system_tables() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            16,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ));
+        let catalog = Catalog::create(bpm).unwrap();
+        let schema = Schema::builder()
+            .column("id", DataType::Integer)
+            .nullable_column("name", DataType::VarChar(32))
+            .build();
+        catalog.create_table("users", schema).unwrap();
+        catalog.create_index("users_id", "users", "id").unwrap();
+        assert!(matches!(
+            catalog.create_table("crio_tables", Schema::builder().build()),
+            Err(CrioError::ReservedTableName(_))
+        ));
+
+        let tables = catalog.scan_system_table(SystemTable::Tables).unwrap();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(
+            tables[0].value_by_name("table_name"),
+            Some(&Value::String("users".into()))
+        );
+        assert_eq!(
+            tables[0].value_by_name("num_pages"),
+            Some(&Value::Integer(1))
+        );
+        assert_eq!(
+            tables[0].value_by_name("num_indexes"),
+            Some(&Value::Integer(1))
+        );
+
+        let columns = catalog.scan_system_table(SystemTable::Columns).unwrap();
+        let described: Vec<_> = columns
+            .iter()
+            .map(|row| {
+                (
+                    row.value(1).cloned(),
+                    row.value(3).cloned(),
+                    row.value(4).cloned(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            described,
+            vec![
+                (
+                    Some(Value::String("id".into())),
+                    Some(Value::String("INTEGER".into())),
+                    Some(Value::Boolean(false))
+                ),
+                (
+                    Some(Value::String("name".into())),
+                    Some(Value::String("VARCHAR(32)".into())),
+                    Some(Value::Boolean(true))
+                ),
+            ]
+        );
+
+        let indexes = catalog.scan_system_table(SystemTable::Indexes).unwrap();
+        assert_eq!(indexes.len(), 1);
+        assert_eq!(
+            indexes[0].value_by_name("column_name"),
+            Some(&Value::String("id".into()))
+        );
+
+        assert_eq!(
+            SystemTable::from_name("crio_columns"),
+            Some(SystemTable::Columns)
+        );
+        assert_eq!(SystemTable::from_name("users"), None);
+    }
+}
@@ -0,0 +1,222 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use super::TableInfo;
+use crate::common::{CrioError, PageId, RecordId, Result};
+use crate::tuple::{Collation, Tuple, Value};
+
+/// Smallest and largest non-NULL value of each covered column on one page,
+/// in `ZoneMapInfo::columns` order; None for a column with no such value yet.
+type PageZones = Vec<Option<(Value, Value)>>;
+
+/// Per-page min/max values of some columns of a table, so range scans read
+/// only the pages whose range can overlap the one asked for.
+///
+/// Like bloom filters, the zone maps live in memory: they are built from the
+/// table's rows the first time they are needed after the catalog is loaded,
+/// and widened by inserts and updates from then on. A page's range never
+/// shrinks until the maps are rebuilt. Only the covered columns are
+/// persisted. Strings are ordered by their column's collation.
+pub struct ZoneMapInfo {
+    /// Table the zone maps belong to
+    pub table_id: u32,
+    /// Positions of the covered columns in the table schema
+    pub columns: Vec<usize>,
+    /// Collations of the covered columns, in `columns` order
+    collations: Vec<Collation>,
+    /// Zones by home page, or None until they are first built
+    zones: Mutex<Option<HashMap<PageId, PageZones>>>,
+    /// Location of this zone map's catalog record
+    pub(super) record_id: RecordId,
+}
+
+impl ZoneMapInfo {
+    pub(super) fn new(
+        table_id: u32,
+        columns: Vec<usize>,
+        collations: Vec<Collation>,
+        record_id: RecordId,
+    ) -> Self {
+        Self {
+            table_id,
+            columns,
+            collations,
+            zones: Mutex::new(None),
+            record_id,
+        }
+    }
+
+    /// Widens the zones of the page holding `record_id` to cover a row.
+    /// Does nothing before the zone maps are built, as building them will
+    /// read the row from the table.
+    pub fn insert(&self, values: &[Value], record_id: RecordId) {
+        if let Some(zones) = self.zones.lock().as_mut() {
+            self.widen(zones, values, record_id);
+        }
+    }
+
+    /// Returns the pages of `table` whose zone for `column` overlaps
+    /// `range`, in page ID order, building the zone maps first if needed.
+    /// Returns None if `column` is not covered.
+    pub fn candidate_pages(
+        &self,
+        table: &TableInfo,
+        column: usize,
+        range: (Bound<&Value>, Bound<&Value>),
+    ) -> Result<Option<Vec<PageId>>> {
+        let Some(position) = self.columns.iter().position(|&c| c == column) else {
+            return Ok(None);
+        };
+
+        let mut zones = self.zones.lock();
+        if zones.is_none() {
+            *zones = Some(self.build(table)?);
+        }
+        let collation = self.collations[position];
+        let mut pages: Vec<_> = zones
+            .iter()
+            .flatten()
+            .filter(|(_, page)| match &page[position] {
+                Some((min, max)) => overlaps(min, max, range, collation),
+                None => false,
+            })
+            .map(|(&page_id, _)| page_id)
+            .collect();
+        pages.sort();
+        Ok(Some(pages))
+    }
+
+    /// Rebuilds the zone maps from the rows of `table`, shrinking ranges
+    /// widened by rows deleted or updated since they were built.
+    pub fn rebuild(&self, table: &TableInfo) -> Result<()> {
+        let mut zones = self.zones.lock();
+        *zones = Some(self.build(table)?);
+        Ok(())
+    }
+
+    /// Builds zone maps from the rows of `table`. The caller holds `zones`,
+    /// so rows inserted during the scan wait in `insert` and are added after.
+    fn build(&self, table: &TableInfo) -> Result<HashMap<PageId, PageZones>> {
+        let mut zones = HashMap::new();
+        for row in table.heap.iter() {
+            let (record_id, data) = row?;
+            let tuple = Tuple::from_bytes(Arc::clone(&table.schema), &data)
+                .ok_or(CrioError::SchemaMismatch)?;
+            self.widen(&mut zones, tuple.values(), record_id);
+        }
+        Ok(zones)
+    }
+
+    fn widen(&self, zones: &mut HashMap<PageId, PageZones>, values: &[Value], record_id: RecordId) {
+        let page = zones
+            .entry(record_id.page_id)
+            .or_insert_with(|| vec![None; self.columns.len()]);
+        for ((zone, &column), &collation) in
+            page.iter_mut().zip(&self.columns).zip(&self.collations)
+        {
+            let Some(value) = values.get(column).filter(|v| !v.is_null()) else {
+                continue;
+            };
+            match zone {
+                None => *zone = Some((value.clone(), value.clone())),
+                Some((min, max)) => {
+                    if value.compare_collated(min, collation) == Some(Ordering::Less) {
+                        *min = value.clone();
+                    }
+                    if value.compare_collated(max, collation) == Some(Ordering::Greater) {
+                        *max = value.clone();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if `[min, max]` may overlap `range` under `collation`.
+/// Values that can't be compared are assumed to overlap.
+fn overlaps(
+    min: &Value,
+    max: &Value,
+    (start, end): (Bound<&Value>, Bound<&Value>),
+    collation: Collation,
+) -> bool {
+    let below_start = match start {
+        Bound::Included(start) => max.compare_collated(start, collation) == Some(Ordering::Less),
+        Bound::Excluded(start) => matches!(
+            max.compare_collated(start, collation),
+            Some(Ordering::Less | Ordering::Equal)
+        ),
+        Bound::Unbounded => false,
+    };
+    let above_end = match end {
+        Bound::Included(end) => min.compare_collated(end, collation) == Some(Ordering::Greater),
+        Bound::Excluded(end) => matches!(
+            min.compare_collated(end, collation),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        Bound::Unbounded => false,
+    };
+    !below_start && !above_end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlaps(min: &Value, max: &Value, range: (Bound<&Value>, Bound<&Value>)) -> bool {
+        super::overlaps(min, max, range, Collation::Binary)
+    }
+
+    #[test]
+    fn test_range_overlap() {
+        use Bound::{Excluded, Included, Unbounded};
+        let (ten, twenty) = (Value::Integer(10), Value::Integer(20));
+
+        assert!(overlaps(&ten, &twenty, (Unbounded, Unbounded)));
+        assert!(overlaps(
+            &ten,
+            &twenty,
+            (Included(&Value::BigInt(20)), Unbounded)
+        ));
+        assert!(!overlaps(&ten, &twenty, (Excluded(&twenty), Unbounded)));
+        assert!(!overlaps(&ten, &twenty, (Unbounded, Excluded(&ten))));
+        assert!(overlaps(&ten, &twenty, (Unbounded, Included(&ten))));
+        assert!(!overlaps(
+            &ten,
+            &twenty,
+            (Included(&Value::Integer(21)), Unbounded)
+        ));
+        // Incomparable values never rule a page out
+        let text = Value::String("a".into());
+        assert!(overlaps(&ten, &twenty, (Included(&text), Included(&text))));
+    }
+
+    #[test]
+    fn test_range_overlap_collated() {
+        use Bound::Included;
+        let (min, max) = (
+            Value::String("apple".into()),
+            Value::String("cherry".into()),
+        );
+        let banana = Value::String("BANANA".into());
+
+        // Binary order puts "BANANA" before "apple"; case-insensitive order
+        // puts it between the two
+        assert!(!super::overlaps(
+            &min,
+            &max,
+            (Included(&banana), Included(&banana)),
+            Collation::Binary
+        ));
+        assert!(super::overlaps(
+            &min,
+            &max,
+            (Included(&banana), Included(&banana)),
+            Collation::CaseInsensitive
+        ));
+    }
+}
@@ -0,0 +1,475 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::buffer::{BufferPoolManager, ReadPageGuard};
+use crate::catalog::CATALOG_TABLE_ID;
+use crate::common::{PageId, PAGE_SIZE};
+use crate::db::Database;
+use crate::index::{BTreeNodeRef, InvertedIndex};
+use crate::storage::disk::{DiskManager, DIRECTORY_PAGE_ID};
+use crate::storage::page::{DirectoryPageRef, TablePageRef};
+
+/// Deeper trees than this are reported instead of walked; a valid tree with
+/// 4 KiB pages never gets close.
+const MAX_BTREE_DEPTH: usize = 32;
+
+/// One inconsistency found by the `IntegrityChecker`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Structure being checked, e.g. `directory`, `table users` or `index users_id`
+    pub structure: String,
+    /// Page the violation was found on, if any
+    pub page_id: Option<PageId>,
+    /// What is wrong
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.page_id {
+            Some(page_id) => write!(f, "{}: page {}: {}", self.structure, page_id, self.message),
+            None => write!(f, "{}: {}", self.structure, self.message),
+        }
+    }
+}
+
+/// Result of an integrity check.
+#[derive(Debug, Clone, Default)]
+pub struct IntegrityReport {
+    /// Every violation found, in the order the checker found them
+    pub violations: Vec<Violation>,
+    /// Number of distinct pages visited
+    pub pages_checked: usize,
+}
+
+impl IntegrityReport {
+    /// Returns true if no violations were found.
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for IntegrityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for violation in &self.violations {
+            writeln!(f, "{}", violation)?;
+        }
+        write!(
+            f,
+            "{} pages checked, {} violations",
+            self.pages_checked,
+            self.violations.len()
+        )
+    }
+}
+
+/// IntegrityChecker walks on-disk structures through the buffer pool and
+/// records every violation it finds instead of stopping at the first one.
+///
+/// Each page may belong to exactly one structure; a page reached twice is
+/// reported and not walked again, which also stops pointer cycles. When a
+/// `DiskManager` is attached, page references are also checked against the
+/// allocated page space and the extent bitmaps.
+///
+/// The checker takes no locks beyond page latches, so run it on a quiescent
+/// database.
+pub struct IntegrityChecker {
+    bpm: Arc<BufferPoolManager>,
+    disk_manager: Option<Arc<DiskManager>>,
+    /// Structure that claimed each visited page
+    owners: HashMap<PageId, String>,
+    report: IntegrityReport,
+}
+
+impl IntegrityChecker {
+    /// Creates a checker that reads pages through `bpm`.
+    pub fn new(bpm: Arc<BufferPoolManager>) -> Self {
+        Self {
+            bpm,
+            disk_manager: None,
+            owners: HashMap::new(),
+            report: IntegrityReport::default(),
+        }
+    }
+
+    /// Attaches the disk manager backing the buffer pool, enabling the
+    /// directory, allocation and extent bitmap checks.
+    pub fn with_disk_manager(mut self, disk_manager: Arc<DiskManager>) -> Self {
+        self.disk_manager = Some(disk_manager);
+        self
+    }
+
+    /// Checks the directory page and every table chain registered in it.
+    /// Does nothing without a disk manager.
+    pub fn check_directory(&mut self) {
+        let Some(disk_manager) = self.disk_manager.clone() else {
+            return;
+        };
+        let structure = "directory";
+
+        let mut data = [0u8; PAGE_SIZE];
+        if let Err(e) = disk_manager.read_directory_page(&mut data) {
+            self.violation(
+                structure,
+                Some(DIRECTORY_PAGE_ID),
+                format!("unreadable: {}", e),
+            );
+            return;
+        }
+        if !self.claim(structure, DIRECTORY_PAGE_ID) {
+            return;
+        }
+
+        let directory = DirectoryPageRef::new(&data);
+        if !directory.is_valid() {
+            self.violation(structure, Some(DIRECTORY_PAGE_ID), "bad magic number");
+            return;
+        }
+
+        let entries = directory.table_entries();
+        if entries.len() != directory.table_count() as usize {
+            self.violation(
+                structure,
+                Some(DIRECTORY_PAGE_ID),
+                format!(
+                    "table count {} exceeds the {} entries a page can hold",
+                    directory.table_count(),
+                    entries.len()
+                ),
+            );
+        }
+
+        let mut seen = HashSet::new();
+        for entry in entries {
+            if !seen.insert(entry.table_id) {
+                self.violation(
+                    structure,
+                    Some(DIRECTORY_PAGE_ID),
+                    format!("table {} is registered twice", entry.table_id),
+                );
+                continue;
+            }
+            let name = if entry.table_id == CATALOG_TABLE_ID {
+                "catalog".to_string()
+            } else {
+                format!("table {}", entry.table_id)
+            };
+            self.check_table_heap(&name, entry.table_id, entry.first_page_id);
+        }
+    }
+
+    /// Checks the extent bitmaps. Does nothing without a disk manager.
+    pub fn check_extents(&mut self) {
+        let Some(disk_manager) = self.disk_manager.clone() else {
+            return;
+        };
+        for message in disk_manager.check_extents() {
+            self.violation("extents", None, message);
+        }
+    }
+
+    /// Checks a table's page chain: page ownership, back links and the
+    /// slotted layout of every page.
+    pub fn check_table_heap(&mut self, structure: &str, table_id: u32, first_page_id: PageId) {
+        let mut prev = None;
+        let mut next = Some(first_page_id);
+
+        while let Some(page_id) = next {
+            if !self.claim(structure, page_id) {
+                return;
+            }
+            let Some(guard) = self.fetch(structure, page_id) else {
+                return;
+            };
+            let page = TablePageRef::new(guard.data());
+
+            if page.page_id() != page_id {
+                self.violation(
+                    structure,
+                    Some(page_id),
+                    format!("header names page {}", page.page_id()),
+                );
+            }
+            if page.table_id() != table_id {
+                self.violation(
+                    structure,
+                    Some(page_id),
+                    format!("owned by table {}, expected {}", page.table_id(), table_id),
+                );
+            }
+            if page.prev_page_id() != prev {
+                self.violation(
+                    structure,
+                    Some(page_id),
+                    format!("prev link {:?}, expected {:?}", page.prev_page_id(), prev),
+                );
+            }
+            for message in page.check_layout() {
+                self.violation(structure, Some(page_id), message);
+            }
+
+            prev = Some(page_id);
+            next = page.next_page_id();
+        }
+    }
+
+    /// Checks a B+Tree: node layout and key order, separator bounds, parent
+    /// pointers, uniform leaf depth and the leaf sibling chain.
+    pub fn check_btree(&mut self, structure: &str, root_page_id: PageId) {
+        let mut leaves = Vec::new();
+        let mut leaf_depth = None;
+        self.check_btree_node(
+            structure,
+            root_page_id,
+            None,
+            (None, None),
+            0,
+            &mut leaf_depth,
+            &mut leaves,
+        );
+
+        for (i, &(page_id, prev, next)) in leaves.iter().enumerate() {
+            let expected_prev = i.checked_sub(1).map(|j| leaves[j].0);
+            let expected_next = leaves.get(i + 1).map(|leaf| leaf.0);
+            if prev != expected_prev {
+                self.violation(
+                    structure,
+                    Some(page_id),
+                    format!("prev sibling {:?}, expected {:?}", prev, expected_prev),
+                );
+            }
+            if next != expected_next {
+                self.violation(
+                    structure,
+                    Some(page_id),
+                    format!("next sibling {:?}, expected {:?}", next, expected_next),
+                );
+            }
+        }
+    }
+
+    /// Checks a full-text index: its term dictionary heap and the page chain
+    /// of every posting list.
+    pub fn check_text_index(&mut self, structure: &str, index: &InvertedIndex) {
+        self.check_table_heap(
+            structure,
+            index.dictionary_table_id(),
+            index.dictionary_page_id(),
+        );
+        for head in index.posting_heads() {
+            let mut next = Some(head);
+            while let Some(page_id) = next {
+                if !self.claim(structure, page_id) {
+                    break;
+                }
+                next = match index.next_posting_page(page_id) {
+                    Ok(next) => next,
+                    Err(e) => {
+                        self.violation(structure, Some(page_id), format!("unreadable: {}", e));
+                        None
+                    }
+                };
+            }
+        }
+    }
+
+    /// Checks one node and recurses into its children. Keys must lie in
+    /// `[lower, upper)`; leaves are collected as `(page, prev, next)`.
+    #[allow(clippy::too_many_arguments)]
+    fn check_btree_node(
+        &mut self,
+        structure: &str,
+        page_id: PageId,
+        parent: Option<PageId>,
+        (lower, upper): (Option<u32>, Option<u32>),
+        depth: usize,
+        leaf_depth: &mut Option<usize>,
+        leaves: &mut Vec<(PageId, Option<PageId>, Option<PageId>)>,
+    ) {
+        if depth > MAX_BTREE_DEPTH {
+            self.violation(
+                structure,
+                Some(page_id),
+                "tree is deeper than any valid tree",
+            );
+            return;
+        }
+        if !self.claim(structure, page_id) {
+            return;
+        }
+        let Some(guard) = self.fetch(structure, page_id) else {
+            return;
+        };
+        let node = BTreeNodeRef::new(guard.data());
+
+        if node.page_id() != page_id {
+            self.violation(
+                structure,
+                Some(page_id),
+                format!("header names page {}", node.page_id()),
+            );
+        }
+        if node.parent_page_id() != parent {
+            self.violation(
+                structure,
+                Some(page_id),
+                format!("parent {:?}, expected {:?}", node.parent_page_id(), parent),
+            );
+        }
+        for message in node.check_layout() {
+            self.violation(structure, Some(page_id), message);
+        }
+        if !node.fits_page() {
+            return;
+        }
+
+        let num_keys = node.num_keys() as usize;
+        for i in 0..num_keys {
+            let key = node.get_key(i);
+            if lower.is_some_and(|lower| key < lower) || upper.is_some_and(|upper| key >= upper) {
+                self.violation(
+                    structure,
+                    Some(page_id),
+                    format!("key {} is outside its parent's range", key),
+                );
+            }
+        }
+
+        if node.is_leaf() {
+            match *leaf_depth {
+                None => *leaf_depth = Some(depth),
+                Some(expected) if expected != depth => self.violation(
+                    structure,
+                    Some(page_id),
+                    format!("leaf at depth {}, expected {}", depth, expected),
+                ),
+                Some(_) => {}
+            }
+            leaves.push((page_id, node.prev_page_id(), node.next_page_id()));
+            return;
+        }
+
+        if num_keys == 0 {
+            self.violation(structure, Some(page_id), "internal node has no keys");
+            return;
+        }
+        let children: Vec<_> = (0..=num_keys)
+            .map(|i| {
+                let child_lower = if i == 0 {
+                    lower
+                } else {
+                    Some(node.get_key(i - 1))
+                };
+                let child_upper = if i == num_keys {
+                    upper
+                } else {
+                    Some(node.get_key(i))
+                };
+                (node.get_child(i), child_lower, child_upper)
+            })
+            .collect();
+        drop(guard);
+
+        for (child, child_lower, child_upper) in children {
+            self.check_btree_node(
+                structure,
+                child,
+                Some(page_id),
+                (child_lower, child_upper),
+                depth + 1,
+                leaf_depth,
+                leaves,
+            );
+        }
+    }
+
+    /// Returns the report.
+    pub fn finish(mut self) -> IntegrityReport {
+        self.report.pages_checked = self.owners.len();
+        self.report
+    }
+
+    /// Records that `structure` owns `page_id`. Returns false, after reporting
+    /// why, if the page is unallocated or already owned.
+    fn claim(&mut self, structure: &str, page_id: PageId) -> bool {
+        if let Some(disk_manager) = &self.disk_manager {
+            if !disk_manager.is_page_allocated(page_id) {
+                self.violation(structure, Some(page_id), "page is not allocated");
+                return false;
+            }
+        }
+        if let Some(owner) = self.owners.get(&page_id) {
+            let message = format!("page is also claimed by {}", owner);
+            self.violation(structure, Some(page_id), message);
+            return false;
+        }
+        self.owners.insert(page_id, structure.to_string());
+        true
+    }
+
+    fn fetch(&mut self, structure: &str, page_id: PageId) -> Option<ReadPageGuard> {
+        match self.bpm.checked_read_page(page_id) {
+            Ok(Some(guard)) => Some(guard),
+            Ok(None) => {
+                self.violation(structure, Some(page_id), "page could not be pinned");
+                None
+            }
+            Err(e) => {
+                self.violation(structure, Some(page_id), format!("unreadable: {}", e));
+                None
+            }
+        }
+    }
+
+    fn violation(&mut self, structure: &str, page_id: Option<PageId>, message: impl Into<String>) {
+        self.report.violations.push(Violation {
+            structure: structure.to_string(),
+            page_id,
+            message: message.into(),
+        });
+    }
+}
+
+/// Checks every structure of a database: the directory and the chains it
+/// registers (including the catalog heap), every table heap, B+Tree and
+/// full-text index in the catalog, and the extent bitmaps.
+///
+/// In shadow paging mode the disk manager's page IDs are not the buffer
+/// pool's, so the directory, allocation and extent checks are skipped and
+/// only the catalog heap is checked in the directory's place.
+pub fn check_database(db: &Database) -> IntegrityReport {
+    let mut checker = IntegrityChecker::new(Arc::clone(db.buffer_pool()));
+    if db.shadow_paging() {
+        let root_page_id = db.catalog().root_page_id();
+        checker.check_table_heap("catalog", CATALOG_TABLE_ID, root_page_id);
+    } else {
+        checker = checker.with_disk_manager(Arc::clone(db.disk_manager()));
+        checker.check_directory();
+    }
+
+    let catalog = db.catalog();
+    let mut names = catalog.table_names();
+    names.sort();
+    for name in names {
+        let Some(table) = catalog.table(&name) else {
+            continue;
+        };
+        checker.check_table_heap(
+            &format!("table {}", name),
+            table.table_id,
+            table.heap.first_page_id(),
+        );
+
+        for index in catalog.table_indexes(table.table_id) {
+            let root_page_id = index.index.root_page_id();
+            checker.check_btree(&format!("index {}", index.name), root_page_id);
+        }
+        for index in catalog.table_text_indexes(table.table_id) {
+            checker.check_text_index(&format!("index {}", index.name), &index.index);
+        }
+    }
+
+    checker.check_extents();
+    checker.finish()
+}
@@ -0,0 +1,5 @@
+//! Offline consistency checks for on-disk structures.
+
+mod integrity_checker;
+
+pub use integrity_checker::*;
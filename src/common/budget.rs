@@ -0,0 +1,320 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::{CrioError, Result};
+
+/// Most a single query may use of each resource. None means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResourceLimits {
+    /// Table and index pages read
+    pub max_pages_read: Option<u64>,
+    /// Bytes of rows materialized
+    pub max_bytes_allocated: Option<u64>,
+    /// Scratch pages held at once
+    pub max_temp_pages: Option<u64>,
+    /// Bytes of executor memory (hash tables, sort buffers) held at once
+    pub max_memory_bytes: Option<u64>,
+    /// Bytes of executor memory past which executors that can spill to
+    /// scratch pages do so instead of growing
+    pub spill_memory_bytes: Option<u64>,
+}
+
+/// What a query has used so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Table and index pages read
+    pub pages_read: u64,
+    /// Bytes of rows materialized
+    pub bytes_allocated: u64,
+    /// Scratch pages held now
+    pub temp_pages: u64,
+    /// Most scratch pages held at once
+    pub peak_temp_pages: u64,
+    /// Bytes of executor memory held now
+    pub memory_bytes: u64,
+    /// Most executor memory held at once
+    pub peak_memory_bytes: u64,
+}
+
+impl fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pages read={} bytes allocated={} temp pages={} (peak {}) memory={} (peak {})",
+            self.pages_read,
+            self.bytes_allocated,
+            self.temp_pages,
+            self.peak_temp_pages,
+            self.memory_bytes,
+            self.peak_memory_bytes
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    pages_read: AtomicU64,
+    bytes_allocated: AtomicU64,
+    temp_pages: AtomicU64,
+    peak_temp_pages: AtomicU64,
+    memory_bytes: AtomicU64,
+    peak_memory_bytes: AtomicU64,
+}
+
+/// Counts the pages, bytes and scratch space one query uses and fails it
+/// with `BudgetExceeded` once it goes over a limit.
+///
+/// Executors charge each page they read and the bytes of each row they
+/// materialize; `TempFileManager::allocate_page_for` charges scratch pages
+/// and `free_page_for` gives them back. Executors holding rows in memory
+/// (hash tables, sort buffers) reserve it through a `MemoryReservation`, so
+/// the query's memory is one pool with a limit rather than whatever each
+/// executor's vectors grow to. Clones share the counters, so every executor
+/// of a query charges the same budget.
+#[derive(Debug, Clone, Default)]
+pub struct QueryBudget {
+    limits: ResourceLimits,
+    counters: Arc<Counters>,
+}
+
+impl QueryBudget {
+    /// Creates a budget with nothing used yet.
+    pub fn new(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            counters: Arc::default(),
+        }
+    }
+
+    /// Returns the limits.
+    pub fn limits(&self) -> ResourceLimits {
+        self.limits
+    }
+
+    /// Returns what has been used so far.
+    pub fn usage(&self) -> ResourceUsage {
+        ResourceUsage {
+            pages_read: self.counters.pages_read.load(Ordering::Relaxed),
+            bytes_allocated: self.counters.bytes_allocated.load(Ordering::Relaxed),
+            temp_pages: self.counters.temp_pages.load(Ordering::Relaxed),
+            peak_temp_pages: self.counters.peak_temp_pages.load(Ordering::Relaxed),
+            memory_bytes: self.counters.memory_bytes.load(Ordering::Relaxed),
+            peak_memory_bytes: self.counters.peak_memory_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Charges `count` pages read.
+    pub fn charge_pages(&self, count: u64) -> Result<()> {
+        let used = self.counters.pages_read.fetch_add(count, Ordering::Relaxed) + count;
+        check_limit("pages read", used, self.limits.max_pages_read)
+    }
+
+    /// Charges `bytes` bytes allocated.
+    pub fn charge_bytes(&self, bytes: u64) -> Result<()> {
+        let used = self
+            .counters
+            .bytes_allocated
+            .fetch_add(bytes, Ordering::Relaxed)
+            + bytes;
+        check_limit("bytes allocated", used, self.limits.max_bytes_allocated)
+    }
+
+    /// Charges one scratch page. Nothing is charged if that would go over
+    /// the limit.
+    pub fn charge_temp_page(&self) -> Result<()> {
+        let held = self.counters.temp_pages.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Err(e) = check_limit("temp pages", held, self.limits.max_temp_pages) {
+            self.counters.temp_pages.fetch_sub(1, Ordering::Relaxed);
+            return Err(e);
+        }
+        self.counters
+            .peak_temp_pages
+            .fetch_max(held, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Gives back a scratch page charged with `charge_temp_page`.
+    pub fn release_temp_page(&self) {
+        let held = &self.counters.temp_pages;
+        let _ = held.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+
+    /// Returns an empty reservation of executor memory, given back when it
+    /// is dropped.
+    pub fn reserve_memory(&self) -> MemoryReservation {
+        MemoryReservation {
+            budget: self.clone(),
+            bytes: 0,
+        }
+    }
+
+    /// Charges `bytes` of executor memory, or nothing if that would go over
+    /// `limit`.
+    fn charge_memory(&self, bytes: u64, resource: &'static str, limit: Option<u64>) -> Result<()> {
+        let held = self
+            .counters
+            .memory_bytes
+            .fetch_add(bytes, Ordering::Relaxed)
+            + bytes;
+        if let Err(e) = check_limit(resource, held, limit) {
+            self.counters
+                .memory_bytes
+                .fetch_sub(bytes, Ordering::Relaxed);
+            return Err(e);
+        }
+        self.counters
+            .peak_memory_bytes
+            .fetch_max(held, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Executor memory held out of a `QueryBudget`, sized as rows are added to
+/// and dropped from the structure it accounts for.
+///
+/// `grow` is for executors that have no choice but to keep the rows; it
+/// fails with `BudgetExceeded` past `max_memory_bytes`. `try_grow` is for
+/// executors that can spill: it returns false instead of growing past
+/// `spill_memory_bytes`, and the executor writes rows out and shrinks the
+/// reservation before trying again. The memory goes back to the budget when
+/// the reservation is dropped.
+#[derive(Debug)]
+pub struct MemoryReservation {
+    budget: QueryBudget,
+    bytes: u64,
+}
+
+impl MemoryReservation {
+    /// Returns the bytes reserved.
+    pub fn size(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Reserves `bytes` more, failing with `BudgetExceeded` if the query
+    /// would hold more than `max_memory_bytes`.
+    pub fn grow(&mut self, bytes: u64) -> Result<()> {
+        let limit = self.budget.limits.max_memory_bytes;
+        self.budget.charge_memory(bytes, "memory", limit)?;
+        self.bytes += bytes;
+        Ok(())
+    }
+
+    /// Reserves `bytes` more unless the query would then hold more than
+    /// `spill_memory_bytes` (or `max_memory_bytes`, whichever is lower),
+    /// returning whether it did. Nothing is reserved when it returns false.
+    pub fn try_grow(&mut self, bytes: u64) -> bool {
+        let limits = self.budget.limits;
+        let limit = match (limits.spill_memory_bytes, limits.max_memory_bytes) {
+            (Some(spill), Some(max)) => Some(spill.min(max)),
+            (spill, max) => spill.or(max),
+        };
+        if self.budget.charge_memory(bytes, "memory", limit).is_err() {
+            return false;
+        }
+        self.bytes += bytes;
+        true
+    }
+
+    /// Gives back up to `bytes` of the reservation.
+    pub fn shrink(&mut self, bytes: u64) {
+        let bytes = bytes.min(self.bytes);
+        self.budget
+            .counters
+            .memory_bytes
+            .fetch_sub(bytes, Ordering::Relaxed);
+        self.bytes -= bytes;
+    }
+
+    /// Gives back the whole reservation.
+    pub fn free(&mut self) {
+        self.shrink(self.bytes);
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.free();
+    }
+}
+
+fn check_limit(resource: &'static str, used: u64, limit: Option<u64>) -> Result<()> {
+    match limit {
+        Some(limit) if used > limit => Err(CrioError::BudgetExceeded { resource, limit }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_budget() {
+        let budget = QueryBudget::new(ResourceLimits {
+            max_pages_read: Some(3),
+            max_bytes_allocated: None,
+            max_temp_pages: Some(1),
+            ..Default::default()
+        });
+        let clone = budget.clone();
+
+        budget.charge_pages(2).unwrap();
+        clone.charge_pages(1).unwrap();
+        assert!(matches!(
+            budget.charge_pages(1),
+            Err(CrioError::BudgetExceeded {
+                resource: "pages read",
+                limit: 3
+            })
+        ));
+        budget.charge_bytes(1 << 40).unwrap();
+
+        budget.charge_temp_page().unwrap();
+        assert!(budget.charge_temp_page().is_err());
+        budget.release_temp_page();
+        budget.charge_temp_page().unwrap();
+        budget.release_temp_page();
+        budget.release_temp_page();
+
+        let usage = clone.usage();
+        assert_eq!(usage.pages_read, 4);
+        assert_eq!(usage.bytes_allocated, 1 << 40);
+        assert_eq!(usage.temp_pages, 0);
+        assert_eq!(usage.peak_temp_pages, 1);
+    }
+
+    #[test]
+    fn test_memory_reservation() {
+        let budget = QueryBudget::new(ResourceLimits {
+            max_memory_bytes: Some(100),
+            spill_memory_bytes: Some(60),
+            ..Default::default()
+        });
+
+        let mut hash_table = budget.reserve_memory();
+        let mut sort_buffer = budget.reserve_memory();
+        hash_table.grow(40).unwrap();
+        assert!(sort_buffer.try_grow(20));
+        // Spilling executors stop at the soft limit...
+        assert!(!sort_buffer.try_grow(1));
+        assert_eq!(sort_buffer.size(), 20);
+        // ...the others at the hard one
+        hash_table.grow(40).unwrap();
+        assert!(matches!(
+            hash_table.grow(1),
+            Err(CrioError::BudgetExceeded {
+                resource: "memory",
+                limit: 100
+            })
+        ));
+        assert_eq!(budget.usage().memory_bytes, 100);
+
+        sort_buffer.shrink(50);
+        assert_eq!(sort_buffer.size(), 0);
+        drop(hash_table);
+        let usage = budget.usage();
+        assert_eq!((usage.memory_bytes, usage.peak_memory_bytes), (0, 100));
+    }
+}
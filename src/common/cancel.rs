@@ -0,0 +1,89 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::{CrioError, Result};
+
+/// Rows a row-at-a-time scan reads between checks of its cancellation token.
+pub const CANCEL_CHECK_ROWS: usize = 64;
+
+/// Lets a running query be stopped from another thread, and optionally by a
+/// deadline.
+///
+/// Executors call `check` before each page, leaf or batch of rows they read,
+/// so a cancelled query fails with `QueryCancelled` within one unit of work.
+/// Clones share the cancel flag: cancelling any clone cancels them all.
+/// `with_timeout` makes a clone that also gives up at a deadline, which is
+/// how a per-statement timeout is applied without touching the original.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    /// Creates a token that is never cancelled unless `cancel` is called.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a clone that is also cancelled once `timeout` has passed,
+    /// keeping any earlier deadline this token has.
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        let deadline = Instant::now() + timeout;
+        Self {
+            cancelled: Arc::clone(&self.cancelled),
+            deadline: Some(self.deadline.map_or(deadline, |d| d.min(deadline))),
+        }
+    }
+
+    /// Cancels every query using this token or one of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the token was cancelled or its deadline has passed.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    /// Returns the deadline, if the token has one.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Fails with `QueryCancelled` if the token is cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(CrioError::QueryCancelled);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        assert!(token.check().is_ok());
+        assert!(token.deadline().is_none());
+
+        // A timed clone expires on its own but shares the cancel flag
+        let timed = token.with_timeout(Duration::ZERO);
+        assert!(matches!(timed.check(), Err(CrioError::QueryCancelled)));
+        assert!(!token.is_cancelled());
+        let later = token.with_timeout(Duration::from_secs(60));
+        assert!(!later.is_cancelled());
+        assert_eq!(
+            later.with_timeout(Duration::from_secs(600)).deadline(),
+            later.deadline()
+        );
+
+        later.cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(token.check(), Err(CrioError::QueryCancelled)));
+    }
+}
@@ -13,6 +13,34 @@ pub const DEFAULT_LRUK_K: usize = 2;
 /// Default buffer pool size (number of frames)
 pub const DEFAULT_BUFFER_POOL_SIZE: usize = 10;
 
+/// Default maximum number of pages per segment file (the full 24-bit offset range).
+/// Must be a multiple of the extent size so extents never straddle files.
+pub const DEFAULT_SEGMENT_PAGES: u32 = PageId::PAGE_OFFSET_MASK + 1;
+
+/// Smallest chunk of disk space reserved at once by `GrowthPolicy::chunked` (8 MB)
+pub const DEFAULT_GROWTH_MIN_PAGES: u32 = 2048;
+
+/// Largest chunk of disk space reserved at once by `GrowthPolicy::chunked` (64 MB)
+pub const DEFAULT_GROWTH_MAX_PAGES: u32 = 16384;
+
+/// Consecutive page accesses that count as a sequential pattern
+pub const DEFAULT_SEQUENTIAL_THRESHOLD: usize = 3;
+
+/// Pages read ahead of a sequential pattern at first
+pub const DEFAULT_READ_AHEAD_WINDOW: u32 = 4;
+
+/// Largest read-ahead window the adaptive policy grows to
+pub const DEFAULT_READ_AHEAD_MAX_WINDOW: u32 = 32;
+
+/// Read-ahead pages allowed in the buffer pool before they are used
+pub const DEFAULT_READ_AHEAD_MAX_OUTSTANDING: u32 = 64;
+
+/// Disk requests the DiskScheduler queues before `schedule` blocks
+pub const DEFAULT_SCHEDULER_QUEUE_DEPTH: usize = 128;
+
+/// Worker threads the DiskScheduler issues requests from
+pub const DEFAULT_SCHEDULER_WORKERS: usize = 1;
+
 /// Default B+ tree order (max keys per node)
 pub const DEFAULT_BTREE_ORDER: usize = 128;
 
@@ -22,4 +50,16 @@ pub const DEFAULT_BTREE_MIN_KEYS: usize = 64;
 /// Key prefix size for fast comparisons
 pub const KEY_PREFIX_SIZE: usize = 16;
 
+/// Bits in each extent's bloom filter
+pub const BLOOM_FILTER_BITS: usize = 8192;
+
+/// Hash functions per bloom filter probe
+pub const BLOOM_FILTER_HASHES: u32 = 4;
+
+/// Dead rows that make a table due for autovacuum
+pub const DEFAULT_AUTOVACUUM_THRESHOLD: u64 = 1000;
+
+/// Milliseconds between autovacuum checks
+pub const DEFAULT_AUTOVACUUM_INTERVAL_MS: u64 = 1000;
+
 use super::types::{FrameId, PageId};
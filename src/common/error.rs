@@ -1,3 +1,5 @@
+use std::io;
+
 use thiserror::Error;
 
 use super::types::{FrameId, PageId};
@@ -26,12 +28,56 @@ pub enum CrioError {
     #[error("Page {0} is still pinned")]
     PageStillPinned(PageId),
 
+    #[error("Page {0} was deleted")]
+    PageDeleted(PageId),
+
+    #[error("Page {0} appears more than once in batch")]
+    DuplicatePageInBatch(PageId),
+
     #[error("Failed to evict page")]
     EvictionFailed,
 
     #[error("Disk scheduler error: {0}")]
     DiskScheduler(String),
 
+    #[error("Failed to read {page_id} (file {file_id}, byte offset {offset}): {source}")]
+    PageRead {
+        page_id: PageId,
+        file_id: u8,
+        offset: u64,
+        kind: io::ErrorKind,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Failed to write {page_id} (file {file_id}, byte offset {offset}): {source}")]
+    PageWrite {
+        page_id: PageId,
+        file_id: u8,
+        offset: u64,
+        kind: io::ErrorKind,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("I/O error on segment file {file_id}: {source}")]
+    SegmentIo {
+        file_id: u8,
+        kind: io::ErrorKind,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Sequential I/O crosses file boundary: file={file_id}, start={start_offset}, count={num_pages}")]
+    FileBoundaryCrossed {
+        file_id: u8,
+        start_offset: u32,
+        num_pages: u32,
+    },
+
+    #[error("Maximum number of segment files reached")]
+    MaxFilesReached,
+
     #[error("Page overflow: tuple size {tuple_size} exceeds available space {available}")]
     PageOverflow { tuple_size: usize, available: usize },
 
@@ -41,12 +87,45 @@ pub enum CrioError {
     #[error("Slot {0} is empty")]
     EmptySlot(u16),
 
+    #[error("Tuple in slot {0} is deleted")]
+    TupleDeleted(u16),
+
+    #[error("Tuple in slot {0} has moved to another page")]
+    TupleForwarded(u16),
+
+    #[error("Forwarding stub in slot {slot} of page {page_id} does not lead back to it")]
+    DanglingForward { page_id: PageId, slot: u16 },
+
+    #[error("Tuple in slot {slot} of page {page_id} changed since it was read")]
+    UpdateConflict { page_id: PageId, slot: u16 },
+
     #[error("Page is full")]
     PageFull,
 
     #[error("Lock poisoned")]
     LockPoisoned,
 
+    #[error("Timed out waiting for lock on table {0}")]
+    LockTimeout(u32),
+
+    #[error("Table {0} is locked shared by this session and can't be locked exclusively")]
+    LockUpgrade(u32),
+
+    #[error("Timed out waiting for key-range lock on index '{0}'")]
+    KeyLockTimeout(String),
+
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    #[error("Query cancelled or timed out")]
+    QueryCancelled,
+
+    #[error("Query exceeded its {resource} budget of {limit}")]
+    BudgetExceeded { resource: &'static str, limit: u64 },
+
+    #[error("Database is open read-only")]
+    ReadOnly,
+
     #[error("Channel error: {0}")]
     Channel(String),
 
@@ -62,6 +141,30 @@ pub enum CrioError {
     #[error("Invalid database file")]
     InvalidDatabaseFile,
 
+    #[error("Invalid segment size: {0} pages")]
+    InvalidSegmentSize(u32),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Object store error: {0}")]
+    ObjectStore(String),
+
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(String),
+
+    #[error("Invalid JSON path: '{0}'")]
+    InvalidJsonPath(String),
+
+    #[error("Invalid fill factor: {0} (must be in (0, 1])")]
+    InvalidFillFactor(f32),
+
+    #[error("Temp space quota of {0} pages exceeded")]
+    TempQuotaExceeded(u32),
+
+    #[error("Shadow page table of {0} pages does not fit in a root page")]
+    ShadowPageTableFull(usize),
+
     #[error("Duplicate key: {0}")]
     DuplicateKey(u32),
 
@@ -73,6 +176,148 @@ pub enum CrioError {
 
     #[error("Index corrupted: {0}")]
     IndexCorrupted(String),
+
+    #[error("Table '{0}' not found")]
+    UnknownTable(String),
+
+    #[error("Table '{0}' already exists")]
+    DuplicateTableName(String),
+
+    #[error("Table name '{0}' is reserved for system tables")]
+    ReservedTableName(String),
+
+    #[error("Index '{0}' not found")]
+    UnknownIndex(String),
+
+    #[error("Index '{0}' already exists")]
+    DuplicateIndexName(String),
+
+    #[error("Table '{0}' already has a bloom filter")]
+    DuplicateBloomFilter(String),
+
+    #[error("Table '{0}' already has a zone map")]
+    DuplicateZoneMap(String),
+
+    #[error("'{0}' is still in use")]
+    ObjectInUse(String),
+
+    #[error("Database '{0}' not found")]
+    UnknownDatabase(String),
+
+    #[error("Invalid database name '{0}'")]
+    InvalidDatabaseName(String),
+
+    #[error("A database is already attached as '{0}'")]
+    DuplicateAlias(String),
+
+    #[error("Column '{0}' not found")]
+    UnknownColumn(String),
+
+    #[error("Column '{0}' cannot be indexed: its type is not supported by this kind of index")]
+    UnindexableColumn(String),
+
+    #[error("Invalid partitioning: {0}")]
+    InvalidPartitionScheme(String),
+
+    #[error("Default for column '{0}' does not match its type")]
+    InvalidDefault(String),
+
+    #[error("No value for column '{0}', which has no default")]
+    MissingValue(String),
+
+    #[error("Scalar subquery returned more than one row")]
+    SubqueryCardinality,
+
+    #[error("{function} can't be computed over column '{column}' of its type")]
+    InvalidAggregate {
+        function: &'static str,
+        column: String,
+    },
+
+    #[error("{0} overflowed")]
+    NumericOverflow(&'static str),
+
+    #[error("Invalid arguments to {0}")]
+    InvalidArguments(&'static str),
+
+    #[error("A transaction is already in progress")]
+    TransactionInProgress,
+
+    #[error("No transaction is in progress")]
+    NoTransaction,
+
+    #[error("A statement is already prepared as '{0}'")]
+    DuplicateStatementName(String),
+
+    #[error("Prepared statement '{0}' not found")]
+    UnknownStatement(String),
+
+    #[error("Statement takes {expected} parameters, {given} given")]
+    ParameterCount { expected: usize, given: usize },
+
+    #[error("Role '{0}' not found")]
+    UnknownRole(String),
+
+    #[error("Role '{0}' already exists")]
+    DuplicateRole(String),
+
+    #[error("Role '{0}' cannot change the session's role")]
+    RoleChangeDenied(String),
+
+    #[error("Role '{role}' lacks {privilege} on '{table}'")]
+    PermissionDenied {
+        role: String,
+        privilege: String,
+        table: String,
+    },
+
+    #[error("Tuple does not match the table schema")]
+    SchemaMismatch,
+
+    #[error("Catalog corrupted: {0}")]
+    CatalogCorrupted(String),
+}
+
+impl CrioError {
+    /// Returns the underlying `io::ErrorKind` for I/O-related errors, so callers
+    /// can tell e.g. a full disk (`StorageFull`) apart from other failures.
+    pub fn io_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            CrioError::Io(e) => Some(e.kind()),
+            CrioError::PageRead { kind, .. }
+            | CrioError::PageWrite { kind, .. }
+            | CrioError::SegmentIo { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn page_read(page_id: PageId, offset: u64, source: io::Error) -> Self {
+        CrioError::PageRead {
+            page_id,
+            file_id: page_id.file_id(),
+            offset,
+            kind: source.kind(),
+            source,
+        }
+    }
+
+    pub(crate) fn page_write(page_id: PageId, offset: u64, source: io::Error) -> Self {
+        CrioError::PageWrite {
+            page_id,
+            file_id: page_id.file_id(),
+            offset,
+            kind: source.kind(),
+            source,
+        }
+    }
+
+    pub(crate) fn segment_io(file_id: u8, source: io::Error) -> Self {
+        CrioError::SegmentIo {
+            file_id,
+            kind: source.kind(),
+            source,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, CrioError>;
@@ -0,0 +1,173 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle as ThreadHandle};
+
+use super::{CrioError, Result};
+
+/// Progress of a long-running operation, shared between the operation and
+/// whoever watches or cancels it.
+///
+/// Operations count the pages they process and call `checkpoint` at page
+/// boundaries, so a cancelled job stops within a page of being cancelled.
+/// The total is an estimate for operations that can't know it up front.
+#[derive(Debug, Default)]
+pub struct JobProgress {
+    total_pages: AtomicU64,
+    pages_processed: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl JobProgress {
+    /// Creates the progress of a job that hasn't started.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of pages the job expects to process.
+    pub fn set_total_pages(&self, pages: u64) {
+        self.total_pages.store(pages, Ordering::Relaxed);
+    }
+
+    /// Counts one more page processed.
+    pub fn page_done(&self) {
+        self.pages_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Fails with `Cancelled` if the job has been cancelled.
+    pub fn checkpoint(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(CrioError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Asks the job to stop at its next page boundary.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the job has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of pages the job expects to process, or 0 if it
+    /// hasn't said yet.
+    pub fn total_pages(&self) -> u64 {
+        self.total_pages.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of pages processed so far.
+    pub fn pages_processed(&self) -> u64 {
+        self.pages_processed.load(Ordering::Relaxed)
+    }
+
+    /// Returns how far along the job is, from 0 to 100. Never exceeds 100,
+    /// even when the total was underestimated.
+    pub fn percent(&self) -> f64 {
+        match self.total_pages() {
+            0 => 0.0,
+            total => (self.pages_processed() as f64 * 100.0 / total as f64).min(100.0),
+        }
+    }
+}
+
+/// A long-running operation running on a thread of its own, returned by
+/// operations such as `TableHandle::bulk_load` and `Database::vacuum_job`.
+///
+/// The handle reports the job's progress, can cancel it, and yields its
+/// result with `wait`. A cancelled job returns `CrioError::Cancelled` and
+/// undoes what it can; each operation documents what a cancel leaves behind.
+/// Dropping the handle lets the job run to completion unobserved.
+pub struct JobHandle<T> {
+    progress: Arc<JobProgress>,
+    worker: ThreadHandle<Result<T>>,
+}
+
+impl<T: Send + 'static> JobHandle<T> {
+    /// Runs `job` on a new thread, passing it the progress to report to.
+    pub(crate) fn spawn<F>(job: F) -> Self
+    where
+        F: FnOnce(&JobProgress) -> Result<T> + Send + 'static,
+    {
+        let progress = Arc::new(JobProgress::new());
+        let worker_progress = Arc::clone(&progress);
+        let worker = thread::spawn(move || job(&worker_progress));
+        Self { progress, worker }
+    }
+
+    /// Returns the job's progress.
+    pub fn progress(&self) -> &Arc<JobProgress> {
+        &self.progress
+    }
+
+    /// Returns how far along the job is, from 0 to 100.
+    pub fn percent(&self) -> f64 {
+        self.progress.percent()
+    }
+
+    /// Returns the number of pages processed so far.
+    pub fn pages_processed(&self) -> u64 {
+        self.progress.pages_processed()
+    }
+
+    /// Asks the job to stop at its next page boundary.
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+
+    /// Returns true once the job has returned.
+    pub fn is_finished(&self) -> bool {
+        self.worker.is_finished()
+    }
+
+    /// Waits for the job to return and returns its result. A panic in the
+    /// job is resumed on the caller's thread.
+    pub fn wait(self) -> Result<T> {
+        match self.worker.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_job_progress_and_cancel() {
+        let (step, steps) = mpsc::channel::<()>();
+        let job = JobHandle::spawn(move |progress| {
+            progress.set_total_pages(4);
+            for _ in 0..4 {
+                steps.recv().unwrap();
+                progress.checkpoint()?;
+                progress.page_done();
+            }
+            Ok(progress.pages_processed())
+        });
+
+        step.send(()).unwrap();
+        step.send(()).unwrap();
+        while job.pages_processed() < 2 {
+            thread::yield_now();
+        }
+        assert_eq!(job.percent(), 50.0);
+
+        job.cancel();
+        step.send(()).unwrap();
+        assert!(matches!(job.wait(), Err(CrioError::Cancelled)));
+    }
+
+    #[test]
+    fn test_percent_is_capped() {
+        let progress = JobProgress::new();
+        assert_eq!(progress.percent(), 0.0);
+        progress.set_total_pages(1);
+        progress.page_done();
+        progress.page_done();
+        assert_eq!(progress.percent(), 100.0);
+    }
+}
@@ -1,7 +1,13 @@
+mod budget;
+mod cancel;
 mod config;
 mod error;
+mod job;
 mod types;
 
+pub use budget::*;
+pub use cancel::*;
 pub use config::*;
 pub use error::*;
+pub use job::*;
 pub use types::*;
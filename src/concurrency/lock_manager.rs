@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::common::{CrioError, Result};
+
+/// Table lock modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Compatible with other shared locks
+    Shared,
+    /// Incompatible with every other lock
+    Exclusive,
+}
+
+/// Lock state for a single table.
+#[derive(Debug, Default)]
+struct TableLockState {
+    /// Number of shared holders
+    shared: usize,
+    /// Whether an exclusive holder exists
+    exclusive: bool,
+    /// Number of threads waiting for an exclusive lock
+    exclusive_waiters: usize,
+}
+
+impl TableLockState {
+    fn can_grant(&self, mode: LockMode) -> bool {
+        match mode {
+            // Queue behind waiting writers so they are not starved
+            LockMode::Shared => !self.exclusive && self.exclusive_waiters == 0,
+            LockMode::Exclusive => !self.exclusive && self.shared == 0,
+        }
+    }
+
+    fn grant(&mut self, mode: LockMode) {
+        match mode {
+            LockMode::Shared => self.shared += 1,
+            LockMode::Exclusive => self.exclusive = true,
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.shared == 0 && !self.exclusive && self.exclusive_waiters == 0
+    }
+}
+
+/// A key-range lock granted on one index.
+#[derive(Debug)]
+struct KeyRangeGrant {
+    id: u64,
+    start: u32,
+    end: u32,
+    mode: LockMode,
+}
+
+/// Key-range locks granted on every index.
+#[derive(Debug, Default)]
+struct KeyRangeTable {
+    /// Map of index name -> granted ranges
+    grants: HashMap<String, Vec<KeyRangeGrant>>,
+    /// ID of the next grant
+    next_id: u64,
+}
+
+impl KeyRangeTable {
+    /// Returns true if no lock on `index` conflicts with `start..=end` in
+    /// `mode`: shared ranges only conflict with overlapping exclusive ones.
+    fn can_grant(&self, index: &str, start: u32, end: u32, mode: LockMode) -> bool {
+        self.grants.get(index).is_none_or(|grants| {
+            grants.iter().all(|grant| {
+                let overlaps = grant.start <= end && start <= grant.end;
+                !overlaps || (mode == LockMode::Shared && grant.mode == LockMode::Shared)
+            })
+        })
+    }
+
+    fn grant(&mut self, index: &str, start: u32, end: u32, mode: LockMode) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.grants
+            .entry(index.to_string())
+            .or_default()
+            .push(KeyRangeGrant {
+                id,
+                start,
+                end,
+                mode,
+            });
+        id
+    }
+}
+
+/// LockManager grants shared/exclusive locks at table granularity, and
+/// key-range locks within an index.
+/// Locks are handed out as `TableLock` and `KeyRangeLock` RAII guards and
+/// released on drop.
+pub struct LockManager {
+    /// Map of table ID -> lock state
+    tables: Mutex<HashMap<u32, TableLockState>>,
+    /// Signalled whenever a lock is released
+    released: Condvar,
+    /// Key-range locks
+    key_ranges: Mutex<KeyRangeTable>,
+    /// Signalled whenever a key-range lock is released
+    key_range_released: Condvar,
+}
+
+impl LockManager {
+    /// Creates a new LockManager.
+    pub fn new() -> Self {
+        Self {
+            tables: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+            key_ranges: Mutex::new(KeyRangeTable::default()),
+            key_range_released: Condvar::new(),
+        }
+    }
+
+    /// Acquires a table lock, blocking until it can be granted.
+    pub fn lock_table(self: &Arc<Self>, table_id: u32, mode: LockMode) -> TableLock {
+        self.acquire(table_id, mode, None)
+            .expect("lock without deadline cannot time out")
+    }
+
+    /// Acquires a table lock, giving up with `LockTimeout` after `timeout`.
+    pub fn lock_table_timeout(
+        self: &Arc<Self>,
+        table_id: u32,
+        mode: LockMode,
+        timeout: Duration,
+    ) -> Result<TableLock> {
+        self.acquire(table_id, mode, Some(Instant::now() + timeout))
+    }
+
+    /// Acquires a table lock only if it can be granted immediately.
+    pub fn try_lock_table(self: &Arc<Self>, table_id: u32, mode: LockMode) -> Option<TableLock> {
+        let mut tables = self.tables.lock();
+        let state = tables.entry(table_id).or_default();
+        if !state.can_grant(mode) {
+            return None;
+        }
+        state.grant(mode);
+        Some(TableLock::new(Arc::clone(self), table_id, mode))
+    }
+
+    /// Returns the lock mode currently held on a table, if any.
+    pub fn lock_mode(&self, table_id: u32) -> Option<LockMode> {
+        let tables = self.tables.lock();
+        tables.get(&table_id).and_then(|state| {
+            if state.exclusive {
+                Some(LockMode::Exclusive)
+            } else if state.shared > 0 {
+                Some(LockMode::Shared)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn acquire(
+        self: &Arc<Self>,
+        table_id: u32,
+        mode: LockMode,
+        deadline: Option<Instant>,
+    ) -> Result<TableLock> {
+        let mut tables = self.tables.lock();
+
+        if mode == LockMode::Exclusive {
+            tables.entry(table_id).or_default().exclusive_waiters += 1;
+        }
+
+        loop {
+            let state = tables.entry(table_id).or_default();
+            if state.can_grant(mode) {
+                if mode == LockMode::Exclusive {
+                    state.exclusive_waiters -= 1;
+                }
+                state.grant(mode);
+                return Ok(TableLock::new(Arc::clone(self), table_id, mode));
+            }
+
+            match deadline {
+                Some(deadline) => {
+                    if self.released.wait_until(&mut tables, deadline).timed_out() {
+                        let state = tables.entry(table_id).or_default();
+                        if state.can_grant(mode) {
+                            continue;
+                        }
+                        if mode == LockMode::Exclusive {
+                            state.exclusive_waiters -= 1;
+                            // Shared waiters may have been queued behind us
+                            self.released.notify_all();
+                        }
+                        return Err(CrioError::LockTimeout(table_id));
+                    }
+                }
+                None => self.released.wait(&mut tables),
+            }
+        }
+    }
+
+    /// Locks the keys `range` of the index named `index`, blocking until the
+    /// lock can be granted.
+    ///
+    /// Shared locks on a range keep every key in it, present or not, from
+    /// being inserted or removed by writers, which lock each key they write
+    /// exclusively: a scan holding one sees no phantoms. Locks have no
+    /// owner, so a thread holding a shared range that writes a key in it
+    /// waits for itself.
+    pub fn lock_key_range(
+        self: &Arc<Self>,
+        index: &str,
+        range: RangeInclusive<u32>,
+        mode: LockMode,
+    ) -> KeyRangeLock {
+        self.acquire_key_range(index, range, mode, None)
+            .expect("lock without deadline cannot time out")
+    }
+
+    /// Locks a key range as `lock_key_range` does, giving up with
+    /// `KeyLockTimeout` after `timeout`.
+    pub fn lock_key_range_timeout(
+        self: &Arc<Self>,
+        index: &str,
+        range: RangeInclusive<u32>,
+        mode: LockMode,
+        timeout: Duration,
+    ) -> Result<KeyRangeLock> {
+        self.acquire_key_range(index, range, mode, Some(Instant::now() + timeout))
+    }
+
+    /// Locks a key range only if it can be granted immediately.
+    pub fn try_lock_key_range(
+        self: &Arc<Self>,
+        index: &str,
+        range: RangeInclusive<u32>,
+        mode: LockMode,
+    ) -> Option<KeyRangeLock> {
+        let (start, end) = range.into_inner();
+        let mut key_ranges = self.key_ranges.lock();
+        if !key_ranges.can_grant(index, start, end, mode) {
+            return None;
+        }
+        let id = key_ranges.grant(index, start, end, mode);
+        Some(KeyRangeLock::new(
+            Arc::clone(self),
+            index,
+            start..=end,
+            mode,
+            id,
+        ))
+    }
+
+    fn acquire_key_range(
+        self: &Arc<Self>,
+        index: &str,
+        range: RangeInclusive<u32>,
+        mode: LockMode,
+        deadline: Option<Instant>,
+    ) -> Result<KeyRangeLock> {
+        let (start, end) = range.into_inner();
+        let mut key_ranges = self.key_ranges.lock();
+        while !key_ranges.can_grant(index, start, end, mode) {
+            match deadline {
+                Some(deadline) => {
+                    if self
+                        .key_range_released
+                        .wait_until(&mut key_ranges, deadline)
+                        .timed_out()
+                        && !key_ranges.can_grant(index, start, end, mode)
+                    {
+                        return Err(CrioError::KeyLockTimeout(index.to_string()));
+                    }
+                }
+                None => self.key_range_released.wait(&mut key_ranges),
+            }
+        }
+        let id = key_ranges.grant(index, start, end, mode);
+        Ok(KeyRangeLock::new(
+            Arc::clone(self),
+            index,
+            start..=end,
+            mode,
+            id,
+        ))
+    }
+
+    fn release_key_range(&self, index: &str, id: u64) {
+        let mut key_ranges = self.key_ranges.lock();
+        if let Some(grants) = key_ranges.grants.get_mut(index) {
+            grants.retain(|grant| grant.id != id);
+            if grants.is_empty() {
+                key_ranges.grants.remove(index);
+            }
+        }
+        self.key_range_released.notify_all();
+    }
+
+    fn release(&self, table_id: u32, mode: LockMode) {
+        let mut tables = self.tables.lock();
+        if let Some(state) = tables.get_mut(&table_id) {
+            match mode {
+                LockMode::Shared => state.shared -= 1,
+                LockMode::Exclusive => state.exclusive = false,
+            }
+            if state.is_idle() {
+                tables.remove(&table_id);
+            }
+        }
+        self.released.notify_all();
+    }
+}
+
+impl Default for LockManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// RAII guard for a table lock. The lock is released when the guard is dropped.
+pub struct TableLock {
+    manager: Arc<LockManager>,
+    table_id: u32,
+    mode: LockMode,
+}
+
+impl TableLock {
+    fn new(manager: Arc<LockManager>, table_id: u32, mode: LockMode) -> Self {
+        Self {
+            manager,
+            table_id,
+            mode,
+        }
+    }
+
+    /// Returns the locked table's ID.
+    pub fn table_id(&self) -> u32 {
+        self.table_id
+    }
+
+    /// Returns the lock mode.
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+
+    /// Returns the lock manager the lock was taken from.
+    pub(crate) fn manager(&self) -> &Arc<LockManager> {
+        &self.manager
+    }
+}
+
+impl Drop for TableLock {
+    fn drop(&mut self) {
+        self.manager.release(self.table_id, self.mode);
+    }
+}
+
+/// RAII guard for a key-range lock. The lock is released when the guard is
+/// dropped.
+pub struct KeyRangeLock {
+    manager: Arc<LockManager>,
+    index: String,
+    range: RangeInclusive<u32>,
+    mode: LockMode,
+    id: u64,
+}
+
+impl KeyRangeLock {
+    fn new(
+        manager: Arc<LockManager>,
+        index: &str,
+        range: RangeInclusive<u32>,
+        mode: LockMode,
+        id: u64,
+    ) -> Self {
+        Self {
+            manager,
+            index: index.to_string(),
+            range,
+            mode,
+            id,
+        }
+    }
+
+    /// Returns the name of the locked index.
+    pub fn index(&self) -> &str {
+        &self.index
+    }
+
+    /// Returns the locked keys.
+    pub fn range(&self) -> &RangeInclusive<u32> {
+        &self.range
+    }
+
+    /// Returns true if this lock spans every key of `range` on `index`.
+    pub fn covers(&self, index: &str, range: &RangeInclusive<u32>) -> bool {
+        self.index == index
+            && self.range.contains(range.start())
+            && self.range.contains(range.end())
+    }
+
+    /// Returns the lock mode.
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+}
+
+impl Drop for KeyRangeLock {
+    fn drop(&mut self) {
+        self.manager.release_key_range(&self.index, self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    #[test]
+    fn test_shared_locks_are_compatible() {
+        let lm = Arc::new(LockManager::new());
+
+        let s1 = lm.lock_table(1, LockMode::Shared);
+        let s2 = lm.try_lock_table(1, LockMode::Shared);
+        assert!(s2.is_some());
+        assert!(lm.try_lock_table(1, LockMode::Exclusive).is_none());
+        assert_eq!(lm.lock_mode(1), Some(LockMode::Shared));
+
+        // Other tables are independent
+        assert!(lm.try_lock_table(2, LockMode::Exclusive).is_some());
+
+        drop(s1);
+        drop(s2);
+        assert_eq!(lm.lock_mode(1), None);
+        assert!(lm.try_lock_table(1, LockMode::Exclusive).is_some());
+    }
+
+    #[test]
+    fn test_exclusive_lock_blocks_others() {
+        let lm = Arc::new(LockManager::new());
+
+        let x = lm.lock_table(1, LockMode::Exclusive);
+        assert_eq!(x.mode(), LockMode::Exclusive);
+        assert!(lm.try_lock_table(1, LockMode::Shared).is_none());
+        assert!(matches!(
+            lm.lock_table_timeout(1, LockMode::Shared, Duration::from_millis(10)),
+            Err(CrioError::LockTimeout(1))
+        ));
+
+        let acquired = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let lm = Arc::clone(&lm);
+            let acquired = Arc::clone(&acquired);
+            thread::spawn(move || {
+                let _s = lm.lock_table(1, LockMode::Shared);
+                acquired.store(true, Ordering::SeqCst);
+            })
+        };
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(!acquired.load(Ordering::SeqCst));
+
+        drop(x);
+        handle.join().unwrap();
+        assert!(acquired.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_waiting_writer_blocks_new_readers() {
+        let lm = Arc::new(LockManager::new());
+
+        let s = lm.lock_table(1, LockMode::Shared);
+        let handle = {
+            let lm = Arc::clone(&lm);
+            thread::spawn(move || {
+                let _x = lm.lock_table(1, LockMode::Exclusive);
+            })
+        };
+
+        // Wait until the writer is queued
+        while lm.try_lock_table(1, LockMode::Shared).is_some() {
+            thread::yield_now();
+        }
+
+        drop(s);
+        handle.join().unwrap();
+        assert!(lm.try_lock_table(1, LockMode::Shared).is_some());
+    }
+
+    #[test]
+    fn test_key_range_locks() {
+        let lm = Arc::new(LockManager::new());
+
+        let scan = lm.lock_key_range("idx", 10..=20, LockMode::Shared);
+        assert_eq!(scan.range(), &(10..=20));
+        // Overlapping readers share; writers wait inside the range only
+        assert!(lm
+            .try_lock_key_range("idx", 15..=30, LockMode::Shared)
+            .is_some());
+        assert!(lm
+            .try_lock_key_range("idx", 20..=20, LockMode::Exclusive)
+            .is_none());
+        assert!(lm
+            .try_lock_key_range("idx", 21..=21, LockMode::Exclusive)
+            .is_some());
+        assert!(lm
+            .try_lock_key_range("other", 15..=15, LockMode::Exclusive)
+            .is_some());
+        assert!(matches!(
+            lm.lock_key_range_timeout(
+                "idx",
+                12..=12,
+                LockMode::Exclusive,
+                Duration::from_millis(10)
+            ),
+            Err(CrioError::KeyLockTimeout(_))
+        ));
+
+        let acquired = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let lm = Arc::clone(&lm);
+            let acquired = Arc::clone(&acquired);
+            thread::spawn(move || {
+                let _x = lm.lock_key_range("idx", 12..=12, LockMode::Exclusive);
+                acquired.store(true, Ordering::SeqCst);
+            })
+        };
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(!acquired.load(Ordering::SeqCst));
+
+        drop(scan);
+        handle.join().unwrap();
+        assert!(acquired.load(Ordering::SeqCst));
+        assert!(lm.key_ranges.lock().grants.is_empty());
+    }
+}
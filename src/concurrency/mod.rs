@@ -0,0 +1,26 @@
+//! Concurrency control.
+//!
+//! Crio uses three levels of synchronization with a fixed acquisition order:
+//!
+//! 1. **Key-range locks** (`KeyRangeLock`, via `LockManager`): S/X locks on a
+//!    range of keys of one index. Writers lock each B+Tree key they add or
+//!    remove exclusively; a scan holding a shared range sees no phantoms
+//!    until it lets go. They are taken before any table lock, and a writer
+//!    never waits for one while holding other locks.
+//!
+//! 2. **Table locks** (`TableLock`, via `LockManager`): logical S/X locks held
+//!    for the duration of an operation on a whole table. Scans take `Shared`;
+//!    inserts, updates and deletes take `Shared` as well and rely on page
+//!    latches for physical consistency; full-table operations that must not
+//!    race with writers (vacuum, alter, drop) take `Exclusive`. When several
+//!    tables are needed, lock them in ascending table ID order.
+//!
+//! 3. **Page latches** (`ReadPageGuard` / `WritePageGuard`): short physical
+//!    latches on a single buffered page, acquired only while a table lock is
+//!    held. Hold them for as little time as possible and never block on a table
+//!    lock while holding a page latch. When latching several pages at once,
+//!    acquire them in page ID order (as `BufferPoolManager::with_write_batch` does).
+
+mod lock_manager;
+
+pub use lock_manager::*;
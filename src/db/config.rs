@@ -0,0 +1,396 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::buffer::{ReadAheadPolicy, SecondaryCacheConfig};
+use crate::common::{
+    CrioError, ResourceLimits, Result, DEFAULT_BUFFER_POOL_SIZE, DEFAULT_LRUK_K,
+    DEFAULT_SCHEDULER_QUEUE_DEPTH, DEFAULT_SCHEDULER_WORKERS, DEFAULT_SEGMENT_PAGES,
+};
+use crate::storage::disk::{DurabilityLevel, GrowthPolicy, IoBudget, IoClass, EXTENT_SIZE};
+
+use super::AutoVacuumPolicy;
+
+/// Every tuning knob of a crio instance in one place.
+///
+/// `Database::open` takes a whole config, while `BufferPoolManager::from_config`
+/// and `DiskScheduler::from_config` each read the settings of their own
+/// layer, so a pool built by hand is tuned the same way as a database. Build
+/// one with `CrioConfig::builder()`, which checks the settings, or start from
+/// `CrioConfig::default()` and change fields directly. With the `serde`
+/// feature a config can be loaded from a file; call `validate` on it before
+/// use.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrioConfig {
+    /// Number of buffer pool frames
+    pub pool_size: usize,
+    /// K for the LRU-K replacer
+    pub lru_k: usize,
+    /// Maximum pages per segment file; must match the value the database was
+    /// created with
+    pub segment_pages: u32,
+    /// Durability level for page writes
+    pub durability: DurabilityLevel,
+    /// How segment files reserve disk space as they grow
+    pub growth_policy: GrowthPolicy,
+    /// Ask the kernel to back the buffer pool with huge pages
+    pub huge_pages: bool,
+    /// How the buffer pool reads ahead of sequential accesses
+    pub read_ahead: ReadAheadPolicy,
+    /// Create the database in shadow paging mode, where changes become
+    /// durable atomically at `Database::commit`. Ignored for an existing
+    /// database, which keeps the mode it was created with
+    pub shadow_paging: bool,
+    /// Stage page writes in a double-write file (`path.dblwr`) before writing
+    /// them in place, so a crash mid-write can't leave a torn page. Ignored
+    /// in shadow paging mode, which never overwrites live pages
+    pub double_write: bool,
+    /// Disk requests the scheduler queues before `schedule` blocks
+    pub scheduler_queue_depth: usize,
+    /// Threads the disk scheduler issues requests from. More than one lets
+    /// requests overlap on devices that serve several at once, at the cost
+    /// of submission order within a class
+    pub scheduler_workers: usize,
+    /// I/O budgets the scheduler starts with, by class
+    pub io_budgets: Vec<(IoClass, IoBudget)>,
+    /// When the autovacuum daemon vacuums tables
+    pub autovacuum: AutoVacuumPolicy,
+    /// How long a scan through a table handle may run before it fails with
+    /// `QueryCancelled`, or None for no limit
+    pub statement_timeout: Option<Duration>,
+    /// What each scan through a table handle may use before it fails with
+    /// `BudgetExceeded`
+    pub query_limits: ResourceLimits,
+    /// Cache file on fast local storage that pages evicted from the buffer
+    /// pool are kept in, if any
+    pub secondary_cache: Option<SecondaryCacheConfig>,
+    /// Open an existing database without ever writing to it; every change
+    /// fails with `ReadOnly`. Double writes and autovacuum are ignored
+    pub read_only: bool,
+}
+
+impl Default for CrioConfig {
+    fn default() -> Self {
+        Self {
+            pool_size: DEFAULT_BUFFER_POOL_SIZE,
+            lru_k: DEFAULT_LRUK_K,
+            segment_pages: DEFAULT_SEGMENT_PAGES,
+            durability: DurabilityLevel::default(),
+            growth_policy: GrowthPolicy::default(),
+            huge_pages: false,
+            read_ahead: ReadAheadPolicy::default(),
+            shadow_paging: false,
+            double_write: false,
+            scheduler_queue_depth: DEFAULT_SCHEDULER_QUEUE_DEPTH,
+            scheduler_workers: DEFAULT_SCHEDULER_WORKERS,
+            io_budgets: Vec::new(),
+            autovacuum: AutoVacuumPolicy::default(),
+            statement_timeout: None,
+            query_limits: ResourceLimits::default(),
+            secondary_cache: None,
+            read_only: false,
+        }
+    }
+}
+
+impl CrioConfig {
+    /// Returns a builder starting from the default settings.
+    pub fn builder() -> CrioConfigBuilder {
+        CrioConfigBuilder::default()
+    }
+
+    /// Checks that the settings can be used together.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| Err(CrioError::InvalidConfig(reason.to_string()));
+        if self.pool_size == 0 {
+            return invalid("pool_size must be at least 1");
+        }
+        if self.lru_k == 0 {
+            return invalid("lru_k must be at least 1");
+        }
+        if self.segment_pages == 0
+            || !self.segment_pages.is_multiple_of(EXTENT_SIZE)
+            || self.segment_pages > DEFAULT_SEGMENT_PAGES
+        {
+            return Err(CrioError::InvalidSegmentSize(self.segment_pages));
+        }
+        if self.scheduler_queue_depth == 0 {
+            return invalid("scheduler_queue_depth must be at least 1");
+        }
+        if self.scheduler_workers == 0 {
+            return invalid("scheduler_workers must be at least 1");
+        }
+        let read_ahead = &self.read_ahead;
+        if read_ahead.enabled && (read_ahead.window == 0 || read_ahead.sequential_threshold == 0) {
+            return invalid("read-ahead window and sequential_threshold must be at least 1");
+        }
+        if read_ahead.enabled && read_ahead.adaptive && read_ahead.max_window < read_ahead.window {
+            return invalid("read-ahead max_window is smaller than window");
+        }
+        for (i, (class, budget)) in self.io_budgets.iter().enumerate() {
+            if self.io_budgets[..i].iter().any(|(other, _)| other == class) {
+                return Err(CrioError::InvalidConfig(format!(
+                    "more than one I/O budget for {:?}",
+                    class
+                )));
+            }
+            // Written this way round so NaN is rejected too
+            if !(budget.pages_per_sec > 0.0 && budget.burst >= 1.0) {
+                return Err(CrioError::InvalidConfig(format!(
+                    "I/O budget for {:?} needs a positive rate and a burst of at least one page",
+                    class
+                )));
+            }
+        }
+        let autovacuum = &self.autovacuum;
+        if autovacuum.enabled
+            && (autovacuum.dead_tuple_threshold == 0 || autovacuum.interval.is_zero())
+        {
+            return invalid("autovacuum dead_tuple_threshold and interval must be nonzero");
+        }
+        if self
+            .secondary_cache
+            .as_ref()
+            .is_some_and(|cache| cache.pages == 0)
+        {
+            return invalid("secondary cache must hold at least 1 page");
+        }
+        Ok(())
+    }
+}
+
+/// Builds a `CrioConfig`, checking it in `build`.
+#[derive(Debug, Clone, Default)]
+pub struct CrioConfigBuilder {
+    config: CrioConfig,
+}
+
+impl CrioConfigBuilder {
+    /// Sets the number of buffer pool frames.
+    pub fn pool_size(mut self, pool_size: usize) -> Self {
+        self.config.pool_size = pool_size;
+        self
+    }
+
+    /// Sets K for the LRU-K replacer.
+    pub fn lru_k(mut self, lru_k: usize) -> Self {
+        self.config.lru_k = lru_k;
+        self
+    }
+
+    /// Sets the maximum pages per segment file.
+    pub fn segment_pages(mut self, segment_pages: u32) -> Self {
+        self.config.segment_pages = segment_pages;
+        self
+    }
+
+    /// Sets the durability level for page writes.
+    pub fn durability(mut self, durability: DurabilityLevel) -> Self {
+        self.config.durability = durability;
+        self
+    }
+
+    /// Sets how segment files reserve disk space.
+    pub fn growth_policy(mut self, growth_policy: GrowthPolicy) -> Self {
+        self.config.growth_policy = growth_policy;
+        self
+    }
+
+    /// Backs the buffer pool with huge pages.
+    pub fn huge_pages(mut self, huge_pages: bool) -> Self {
+        self.config.huge_pages = huge_pages;
+        self
+    }
+
+    /// Sets the read-ahead policy.
+    pub fn read_ahead(mut self, read_ahead: ReadAheadPolicy) -> Self {
+        self.config.read_ahead = read_ahead;
+        self
+    }
+
+    /// Creates new databases in shadow paging mode.
+    pub fn shadow_paging(mut self, shadow_paging: bool) -> Self {
+        self.config.shadow_paging = shadow_paging;
+        self
+    }
+
+    /// Stages page writes in a double-write file.
+    pub fn double_write(mut self, double_write: bool) -> Self {
+        self.config.double_write = double_write;
+        self
+    }
+
+    /// Sets how many disk requests the scheduler queues.
+    pub fn scheduler_queue_depth(mut self, depth: usize) -> Self {
+        self.config.scheduler_queue_depth = depth;
+        self
+    }
+
+    /// Sets how many threads the disk scheduler issues requests from.
+    pub fn scheduler_workers(mut self, workers: usize) -> Self {
+        self.config.scheduler_workers = workers;
+        self
+    }
+
+    /// Throttles `class` to `budget`.
+    pub fn io_budget(mut self, class: IoClass, budget: IoBudget) -> Self {
+        self.config.io_budgets.push((class, budget));
+        self
+    }
+
+    /// Sets when the autovacuum daemon vacuums tables.
+    pub fn autovacuum(mut self, autovacuum: AutoVacuumPolicy) -> Self {
+        self.config.autovacuum = autovacuum;
+        self
+    }
+
+    /// Limits how long each scan may run.
+    pub fn statement_timeout(mut self, timeout: Duration) -> Self {
+        self.config.statement_timeout = Some(timeout);
+        self
+    }
+
+    /// Keeps pages evicted from the buffer pool in a cache file of `pages`
+    /// pages at `path`.
+    pub fn secondary_cache(mut self, path: impl Into<PathBuf>, pages: u32) -> Self {
+        self.config.secondary_cache = Some(SecondaryCacheConfig {
+            path: path.into(),
+            pages,
+        });
+        self
+    }
+
+    /// Limits the pages, bytes and scratch space each scan may use.
+    pub fn query_limits(mut self, limits: ResourceLimits) -> Self {
+        self.config.query_limits = limits;
+        self
+    }
+
+    /// Opens the database read-only.
+    pub fn read_only(mut self) -> Self {
+        self.config.read_only = true;
+        self
+    }
+
+    /// Returns the config, or an error if its settings are invalid.
+    pub fn build(self) -> Result<CrioConfig> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_defaults() {
+        assert_eq!(
+            CrioConfig::builder().build().unwrap(),
+            CrioConfig::default()
+        );
+
+        let config = CrioConfig::builder()
+            .pool_size(64)
+            .lru_k(3)
+            .durability(DurabilityLevel::None)
+            .read_ahead(ReadAheadPolicy::disabled())
+            .io_budget(IoClass::Background, IoBudget::new(100.0, 8.0))
+            .build()
+            .unwrap();
+        assert_eq!(config.pool_size, 64);
+        assert_eq!(config.lru_k, 3);
+        assert_eq!(config.durability, DurabilityLevel::None);
+        assert!(!config.read_ahead.enabled);
+        assert_eq!(config.io_budgets.len(), 1);
+        assert_eq!(config.segment_pages, DEFAULT_SEGMENT_PAGES);
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_settings() {
+        assert!(matches!(
+            CrioConfig::builder().pool_size(0).build(),
+            Err(CrioError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            CrioConfig::builder().segment_pages(EXTENT_SIZE + 1).build(),
+            Err(CrioError::InvalidSegmentSize(_))
+        ));
+        assert!(matches!(
+            CrioConfig::builder().scheduler_queue_depth(0).build(),
+            Err(CrioError::InvalidConfig(_))
+        ));
+
+        let read_ahead = ReadAheadPolicy {
+            max_window: 2,
+            window: 4,
+            ..ReadAheadPolicy::default()
+        };
+        assert!(CrioConfig::builder()
+            .read_ahead(read_ahead)
+            .build()
+            .is_err());
+        assert!(CrioConfig::builder()
+            .read_ahead(ReadAheadPolicy {
+                adaptive: false,
+                ..read_ahead
+            })
+            .build()
+            .is_ok());
+
+        assert!(matches!(
+            CrioConfig::builder().scheduler_workers(0).build(),
+            Err(CrioError::InvalidConfig(_))
+        ));
+
+        let autovacuum = AutoVacuumPolicy {
+            dead_tuple_threshold: 0,
+            ..AutoVacuumPolicy::enabled()
+        };
+        assert!(CrioConfig::builder()
+            .autovacuum(autovacuum)
+            .build()
+            .is_err());
+
+        let budget = IoBudget::new(10.0, 1.0);
+        assert!(CrioConfig::builder()
+            .io_budget(IoClass::Prefetch, budget)
+            .io_budget(IoClass::Prefetch, budget)
+            .build()
+            .is_err());
+        // The fields are public, so budgets that IoBudget::new would refuse
+        // can still reach a config
+        for (pages_per_sec, burst) in [(0.0, 1.0), (10.0, 0.0), (f64::NAN, 1.0)] {
+            let budget = IoBudget {
+                pages_per_sec,
+                burst,
+            };
+            assert!(matches!(
+                CrioConfig::builder()
+                    .io_budget(IoClass::Background, budget)
+                    .build(),
+                Err(CrioError::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_config_serde_round_trip() {
+        let config = CrioConfig::builder()
+            .pool_size(64)
+            .durability(DurabilityLevel::FsyncOnCommit)
+            .growth_policy(GrowthPolicy::Chunked {
+                min_pages: 8,
+                max_pages: 64,
+            })
+            .scheduler_workers(2)
+            .io_budget(IoClass::Background, IoBudget::new(100.0, 8.0))
+            .build()
+            .unwrap();
+        let json = serde_json::to_string(&config).unwrap();
+        let decoded: CrioConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, config);
+        decoded.validate().unwrap();
+    }
+}
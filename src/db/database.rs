@@ -0,0 +1,646 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::RwLock;
+
+use crate::buffer::{BufferPoolManager, SecondaryCache};
+use crate::catalog::{
+    BloomFilterInfo, Catalog, IndexInfo, PartitionScheme, Privileges, RoleInfo, SystemTable,
+    TextIndexInfo, ZoneMapInfo, CATALOG_TABLE_ID,
+};
+use crate::common::{CrioError, JobHandle, JobProgress, ResourceLimits, Result, PAGE_SIZE};
+use crate::concurrency::{LockManager, LockMode};
+use crate::execution::PlanCache;
+use crate::storage::disk::{DiskManager, DoubleWriteStorage, ShadowStorage, StorageBackend};
+use crate::storage::page::{DirectoryPage, DirectoryPageRef};
+use crate::storage::table::TempTables;
+use crate::tuple::{Schema, Tuple};
+
+use super::manager::check_name;
+use super::{
+    export_snapshot, vacuum_table, AutoVacuum, CrioConfig, PartitionedTableHandle, SnapshotStats,
+    StatementPlan, TableHandle, VacuumStats,
+};
+
+/// Directory entries recording the two shadow paging root pages
+const SHADOW_ROOT_IDS: [u32; 2] = [u32::MAX - 1, u32::MAX];
+
+/// Settings used when opening a Database; the name `Database::open` took
+/// before `CrioConfig` covered every layer.
+pub type DatabaseOptions = CrioConfig;
+
+/// Database ties the disk manager, buffer pool, catalog and lock manager
+/// together behind one entry point.
+///
+/// The catalog heap is registered in the directory page under
+/// `CATALOG_TABLE_ID`, so reopening a database finds every table and index
+/// created before.
+///
+/// In shadow paging mode the buffer pool sits on a `ShadowStorage` over the
+/// disk manager, and the directory also records its two root pages. Page
+/// writes then only become durable at `commit` (or a clean close), all
+/// together; after a crash the database reopens as of the last commit.
+///
+/// With `CrioConfig::autovacuum` enabled, an `AutoVacuum` daemon reclaims the
+/// space of deleted rows in the background until the database is closed.
+///
+/// With `CrioConfig::read_only` an existing database is opened without ever
+/// being written to, e.g. a snapshot made by `export_snapshot`; DDL and row
+/// changes fail with `ReadOnly`.
+pub struct Database {
+    /// Background vacuum thread, if enabled; stopped before the buffer pool
+    autovacuum: Option<AutoVacuum>,
+    /// File-backed page storage
+    disk_manager: Arc<DiskManager>,
+    /// Page table between the buffer pool and the disk manager, in shadow
+    /// paging mode
+    shadow: Option<Arc<ShadowStorage>>,
+    /// Shared buffer pool
+    bpm: Arc<BufferPoolManager>,
+    /// Table and index metadata
+    catalog: Arc<Catalog>,
+    /// Table locks taken by table handles
+    lock_manager: Arc<LockManager>,
+    /// Statement timeout handles start with
+    statement_timeout: Option<Duration>,
+    /// Resource limits handles start with
+    query_limits: ResourceLimits,
+    /// Databases attached with `attach`, by alias
+    attached: RwLock<BTreeMap<String, Arc<Database>>>,
+    /// Plans of statements prepared in sessions, shared between them
+    plan_cache: PlanCache<StatementPlan>,
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P, options: CrioConfig) -> Result<Self> {
+        options.validate()?;
+        let disk_manager = Arc::new(if options.read_only {
+            DiskManager::open_read_only(path.as_ref(), options.segment_pages)?
+        } else {
+            DiskManager::with_segment_pages(path.as_ref(), options.segment_pages)?
+        });
+        disk_manager.set_durability(options.durability);
+        disk_manager.set_growth_policy(options.growth_policy);
+
+        // Restore pages torn by a crash before anything reads them, even if
+        // double writes are now off
+        let double_write_path = double_write_path(path.as_ref());
+        if options.read_only && double_write_path.exists() {
+            // Recovery has to write; open the database writable once first
+            return Err(CrioError::ReadOnly);
+        }
+        if !options.double_write && double_write_path.exists() {
+            DoubleWriteStorage::recover(&*disk_manager, &double_write_path)?;
+            std::fs::remove_file(&double_write_path)?;
+        }
+
+        let mut directory = [0u8; PAGE_SIZE];
+        disk_manager.read_directory_page(&mut directory)?;
+        let existing = DirectoryPageRef::new(&directory).find_table(CATALOG_TABLE_ID);
+        if options.read_only && existing.is_none() {
+            return Err(CrioError::InvalidDatabaseFile);
+        }
+
+        let roots = SHADOW_ROOT_IDS
+            .map(|id| DirectoryPageRef::new(&directory).find_table(id))
+            .map(|entry| entry.map(|entry| entry.first_page_id));
+        let shadow = match roots {
+            [Some(first), Some(second)] => Some(ShadowStorage::open(
+                Arc::clone(&disk_manager) as _,
+                [first, second],
+            )?),
+            _ if existing.is_none() && options.shadow_paging => {
+                let shadow = ShadowStorage::create(Arc::clone(&disk_manager) as _)?;
+                let mut page = DirectoryPage::new(&mut directory);
+                for (id, root) in SHADOW_ROOT_IDS.into_iter().zip(shadow.root_page_ids()) {
+                    page.register_table(id, root)?;
+                }
+                disk_manager.write_directory_page(&directory)?;
+                Some(shadow)
+            }
+            _ => None,
+        }
+        .map(Arc::new);
+
+        let backend: Arc<dyn StorageBackend> = match &shadow {
+            Some(shadow) => Arc::clone(shadow) as _,
+            None if options.double_write && !options.read_only => Arc::new(
+                DoubleWriteStorage::open(Arc::clone(&disk_manager) as _, &double_write_path)?,
+            ),
+            None => Arc::clone(&disk_manager) as _,
+        };
+        let mut bpm = BufferPoolManager::from_config(&options, backend);
+        if let Some(cache) = &options.secondary_cache {
+            bpm = bpm.with_secondary_cache(SecondaryCache::from_config(cache)?);
+        }
+        let bpm = Arc::new(bpm);
+
+        let catalog = match existing {
+            Some(entry) => Catalog::open(Arc::clone(&bpm), entry.first_page_id)?,
+            None => {
+                let catalog = Catalog::create(Arc::clone(&bpm))?;
+                if let Some(shadow) = &shadow {
+                    // The catalog must be committed before the directory
+                    // points at it
+                    bpm.flush_all_pages()?;
+                    shadow.commit()?;
+                }
+                DirectoryPage::new(&mut directory)
+                    .register_table(CATALOG_TABLE_ID, catalog.root_page_id())?;
+                disk_manager.write_directory_page(&directory)?;
+                catalog
+            }
+        };
+
+        let catalog = Arc::new(catalog);
+        let lock_manager = Arc::new(LockManager::new());
+        let autovacuum = (options.autovacuum.enabled && !options.read_only).then(|| {
+            AutoVacuum::start(
+                options.autovacuum,
+                Arc::clone(&catalog),
+                Arc::clone(&lock_manager),
+                Arc::clone(&bpm),
+            )
+        });
+
+        Ok(Self {
+            autovacuum,
+            disk_manager,
+            shadow,
+            bpm,
+            plan_cache: PlanCache::new(Arc::clone(&catalog)),
+            catalog,
+            lock_manager,
+            statement_timeout: options.statement_timeout,
+            query_limits: options.query_limits,
+            attached: RwLock::new(BTreeMap::new()),
+        })
+    }
+
+    /// Creates a table and returns a handle to it.
+    pub fn create_table(&self, name: &str, schema: Schema) -> Result<TableHandle> {
+        self.check_writable()?;
+        let info = self.catalog.create_table(name, schema)?;
+        Ok(TableHandle::new(
+            info,
+            Arc::clone(&self.catalog),
+            Arc::clone(&self.lock_manager),
+            self.statement_timeout,
+            self.query_limits,
+        ))
+    }
+
+    /// Returns a handle to the table called `name`. A name of the form
+    /// `alias.table` that isn't a table of this database names a table of
+    /// the database attached as `alias`.
+    pub fn table(&self, name: &str) -> Result<TableHandle> {
+        let Some(info) = self.catalog.table(name) else {
+            return match self.resolve_attached(name) {
+                Some((db, table)) => db.table(table),
+                None => Err(CrioError::UnknownTable(name.to_string())),
+            };
+        };
+        Ok(TableHandle::new(
+            info,
+            Arc::clone(&self.catalog),
+            Arc::clone(&self.lock_manager),
+            self.statement_timeout,
+            self.query_limits,
+        ))
+    }
+
+    /// Creates a table split into partitions by the integer column
+    /// `key_column` and returns a handle to it.
+    pub fn create_partitioned_table(
+        &self,
+        name: &str,
+        schema: Schema,
+        key_column: &str,
+        scheme: PartitionScheme,
+    ) -> Result<PartitionedTableHandle> {
+        self.check_writable()?;
+        let info = self
+            .catalog
+            .create_partitioned_table(name, schema, key_column, scheme)?;
+        Ok(PartitionedTableHandle::new(
+            info,
+            Arc::clone(&self.catalog),
+            Arc::clone(&self.lock_manager),
+            self.statement_timeout,
+            self.query_limits,
+        ))
+    }
+
+    /// Returns a handle to the partitioned table called `name`, which may
+    /// be qualified with an alias as in `table`.
+    pub fn partitioned_table(&self, name: &str) -> Result<PartitionedTableHandle> {
+        let Some(info) = self.catalog.partitioned_table(name) else {
+            return match self.resolve_attached(name) {
+                Some((db, table)) => db.partitioned_table(table),
+                None => Err(CrioError::UnknownTable(name.to_string())),
+            };
+        };
+        Ok(PartitionedTableHandle::new(
+            info,
+            Arc::clone(&self.catalog),
+            Arc::clone(&self.lock_manager),
+            self.statement_timeout,
+            self.query_limits,
+        ))
+    }
+
+    /// Opens the database at `path` with `config` and attaches it under
+    /// `alias`, so its tables can be reached as `alias.table` through
+    /// `table` and `partitioned_table` and their rows fed into the same
+    /// executors (e.g. a `HashJoinExecutor`) as this database's. The
+    /// attached database keeps its own buffer pool, catalog and locks.
+    /// Aliases follow the rules of `DatabaseManager` names.
+    pub fn attach<P: AsRef<Path>>(
+        &self,
+        alias: &str,
+        path: P,
+        config: CrioConfig,
+    ) -> Result<Arc<Database>> {
+        check_name(alias)?;
+        let mut attached = self.attached.write();
+        if attached.contains_key(alias) {
+            return Err(CrioError::DuplicateAlias(alias.to_string()));
+        }
+        let db = Arc::new(Database::open(path, config)?);
+        attached.insert(alias.to_string(), Arc::clone(&db));
+        Ok(db)
+    }
+
+    /// Detaches and closes the database attached as `alias`. Fails with
+    /// `ObjectInUse` while anyone else still holds it.
+    pub fn detach(&self, alias: &str) -> Result<()> {
+        let mut attached = self.attached.write();
+        let db = attached
+            .remove(alias)
+            .ok_or_else(|| CrioError::UnknownDatabase(alias.to_string()))?;
+        match Arc::try_unwrap(db) {
+            Ok(db) => db.close(),
+            Err(db) => {
+                attached.insert(alias.to_string(), db);
+                Err(CrioError::ObjectInUse(alias.to_string()))
+            }
+        }
+    }
+
+    /// Returns the database attached as `alias`.
+    pub fn attached(&self, alias: &str) -> Option<Arc<Database>> {
+        self.attached.read().get(alias).cloned()
+    }
+
+    /// Returns the aliases of the attached databases, in order.
+    pub fn attached_aliases(&self) -> Vec<String> {
+        self.attached.read().keys().cloned().collect()
+    }
+
+    /// Splits `alias.table` into the database attached as `alias` and the
+    /// table name.
+    fn resolve_attached<'a>(&self, name: &'a str) -> Option<(Arc<Database>, &'a str)> {
+        let (alias, table) = name.split_once('.')?;
+        Some((self.attached(alias)?, table))
+    }
+
+    /// Returns the rows of the system table called `name` (e.g. `crio_tables`),
+    /// generated from the current catalog.
+    pub fn scan_system_table(&self, name: &str) -> Result<Vec<Tuple>> {
+        let table = SystemTable::from_name(name)
+            .ok_or_else(|| CrioError::UnknownTable(name.to_string()))?;
+        self.catalog.scan_system_table(table)
+    }
+
+    /// Creates a unique B+Tree index on an integer column, filled from the
+    /// table's existing rows.
+    pub fn create_index(
+        &self,
+        index_name: &str,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<Arc<IndexInfo>> {
+        self.check_writable()?;
+        // Keep writers out while the existing rows are indexed
+        let table_id = self
+            .catalog
+            .table(table_name)
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?
+            .table_id;
+        let _lock = self.lock_manager.lock_table(table_id, LockMode::Exclusive);
+
+        self.catalog
+            .create_index(index_name, table_name, column_name)
+    }
+
+    /// Starts creating an index as `create_index` does, on a thread of its
+    /// own. The job counts the table pages read; cancelling it leaves no
+    /// index behind. Writers to the table wait until the job is done.
+    pub fn create_index_job(
+        &self,
+        index_name: &str,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<JobHandle<Arc<IndexInfo>>> {
+        self.check_writable()?;
+        let table_id = self
+            .catalog
+            .table(table_name)
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?
+            .table_id;
+        let catalog = Arc::clone(&self.catalog);
+        let lock_manager = Arc::clone(&self.lock_manager);
+        let (index_name, table_name, column_name) = (
+            index_name.to_string(),
+            table_name.to_string(),
+            column_name.to_string(),
+        );
+
+        Ok(JobHandle::spawn(move |progress| {
+            let _lock = lock_manager.lock_table(table_id, LockMode::Exclusive);
+            catalog.create_index_with_progress(&index_name, &table_name, &column_name, progress)
+        }))
+    }
+
+    /// Creates a unique B+Tree index on the integer at `path` within a JSON
+    /// column (see `Catalog::create_path_index`), filled from the table's
+    /// existing rows.
+    pub fn create_path_index(
+        &self,
+        index_name: &str,
+        table_name: &str,
+        column_name: &str,
+        path: &str,
+    ) -> Result<Arc<IndexInfo>> {
+        self.check_writable()?;
+        let table_id = self
+            .catalog
+            .table(table_name)
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?
+            .table_id;
+        let _lock = self.lock_manager.lock_table(table_id, LockMode::Exclusive);
+
+        self.catalog
+            .create_path_index(index_name, table_name, column_name, path)
+    }
+
+    /// Creates a full-text index on a VarChar column, filled from the table's
+    /// existing rows. Query it with `TableHandle::search_text`.
+    pub fn create_text_index(
+        &self,
+        index_name: &str,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<Arc<TextIndexInfo>> {
+        self.check_writable()?;
+        let table_id = self
+            .catalog
+            .table(table_name)
+            .ok_or_else(|| CrioError::UnknownTable(table_name.to_string()))?
+            .table_id;
+        let _lock = self.lock_manager.lock_table(table_id, LockMode::Exclusive);
+
+        self.catalog
+            .create_text_index(index_name, table_name, column_name)
+    }
+
+    /// Keeps per-extent bloom filters over a column of a table, which
+    /// `TableHandle::scan_eq` uses to skip extents.
+    pub fn create_bloom_filter(
+        &self,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<Arc<BloomFilterInfo>> {
+        self.check_writable()?;
+        self.catalog.create_bloom_filter(table_name, column_name)
+    }
+
+    /// Stops keeping bloom filters for a table.
+    pub fn drop_bloom_filter(&self, table_name: &str) -> Result<()> {
+        self.check_writable()?;
+        self.catalog.drop_bloom_filter(table_name)
+    }
+
+    /// Keeps per-page min/max values of some columns of a table, which
+    /// `TableHandle::scan_range` and the runtime filters of
+    /// `TableHandle::hash_join` use to skip pages.
+    pub fn create_zone_map(
+        &self,
+        table_name: &str,
+        column_names: &[&str],
+    ) -> Result<Arc<ZoneMapInfo>> {
+        self.check_writable()?;
+        self.catalog.create_zone_map(table_name, column_names)
+    }
+
+    /// Stops keeping zone maps for a table.
+    pub fn drop_zone_map(&self, table_name: &str) -> Result<()> {
+        self.check_writable()?;
+        self.catalog.drop_zone_map(table_name)
+    }
+
+    /// Creates a role for sessions to act as (see `Session::set_role`).
+    pub fn create_role(&self, name: &str, superuser: bool) -> Result<Arc<RoleInfo>> {
+        self.check_writable()?;
+        self.catalog.create_role(name, superuser)
+    }
+
+    /// Drops a role and everything granted to it.
+    pub fn drop_role(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        self.catalog.drop_role(name)
+    }
+
+    /// Grants `privileges` on a table to a role (`GRANT ... ON table TO role`).
+    pub fn grant(&self, role: &str, table_name: &str, privileges: Privileges) -> Result<()> {
+        self.check_writable()?;
+        self.catalog.grant(role, table_name, privileges)
+    }
+
+    /// Takes `privileges` on a table away from a role.
+    pub fn revoke(&self, role: &str, table_name: &str, privileges: Privileges) -> Result<()> {
+        self.check_writable()?;
+        self.catalog.revoke(role, table_name, privileges)
+    }
+
+    /// Drops a table and its indexes, freeing their pages. Dropping a
+    /// partitioned table drops every partition. Fails with `ObjectInUse`
+    /// while a handle to the table is alive.
+    pub fn drop_table(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        self.catalog.drop_table(name)
+    }
+
+    /// Drops an index, freeing its pages.
+    pub fn drop_index(&self, name: &str) -> Result<()> {
+        self.check_writable()?;
+        // Keep writers, which look up the table's indexes, out during the drop
+        let table_id = match self.catalog.index(name) {
+            Some(index) => index.table_id,
+            None => {
+                self.catalog
+                    .text_index(name)
+                    .ok_or_else(|| CrioError::UnknownIndex(name.to_string()))?
+                    .table_id
+            }
+        };
+        let _lock = self.lock_manager.lock_table(table_id, LockMode::Exclusive);
+
+        self.catalog.drop_index(name)
+    }
+
+    /// Reclaims the space of the deleted rows of the table called `name` now;
+    /// see `AutoVacuum` for doing so in the background. The pages it rewrites
+    /// are charged to `IoClass::Background`.
+    pub fn vacuum(&self, name: &str) -> Result<VacuumStats> {
+        self.check_writable()?;
+        let info = self
+            .catalog
+            .table(name)
+            .ok_or_else(|| CrioError::UnknownTable(name.to_string()))?;
+        vacuum_table(
+            &info,
+            &self.catalog,
+            &self.lock_manager,
+            &self.bpm,
+            &JobProgress::new(),
+        )
+    }
+
+    /// Starts vacuuming the table called `name` as `vacuum` does, on a
+    /// thread of its own. The job counts the heap pages examined; cancelling
+    /// it keeps the space reclaimed so far.
+    pub fn vacuum_job(&self, name: &str) -> Result<JobHandle<VacuumStats>> {
+        self.check_writable()?;
+        let info = self
+            .catalog
+            .table(name)
+            .ok_or_else(|| CrioError::UnknownTable(name.to_string()))?;
+        let catalog = Arc::clone(&self.catalog);
+        let lock_manager = Arc::clone(&self.lock_manager);
+        let bpm = Arc::clone(&self.bpm);
+
+        Ok(JobHandle::spawn(move |progress| {
+            vacuum_table(&info, &catalog, &lock_manager, &bpm, progress)
+        }))
+    }
+
+    /// Writes a compacted, single-file copy of the database to `path` (see
+    /// `export_snapshot`), for shipping a dataset elsewhere and opening it
+    /// there with `CrioConfig::read_only`. Writers wait until it is done.
+    pub fn export_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<SnapshotStats> {
+        export_snapshot(&self.catalog, &self.lock_manager, path.as_ref())
+    }
+
+    /// Returns the autovacuum daemon, if `CrioConfig::autovacuum` enabled it.
+    pub fn autovacuum(&self) -> Option<&AutoVacuum> {
+        self.autovacuum.as_ref()
+    }
+
+    /// Writes every dirty page back and makes it durable. In shadow paging
+    /// mode the pages are written but only become durable at the next
+    /// `commit`.
+    pub fn flush(&self) -> Result<()> {
+        self.bpm.flush_all_pages()?;
+        self.disk_manager.sync()
+    }
+
+    /// Writes every dirty page back and, in shadow paging mode, makes all
+    /// changes since the last commit durable as one atomic step. Changes made
+    /// by operations still running may or may not be included. Without
+    /// shadow paging this is `flush`.
+    pub fn commit(&self) -> Result<()> {
+        match &self.shadow {
+            Some(shadow) => {
+                self.bpm.flush_all_pages()?;
+                shadow.commit()
+            }
+            None => self.flush(),
+        }
+    }
+
+    /// Returns true if the database is in shadow paging mode.
+    pub fn shadow_paging(&self) -> bool {
+        self.shadow.is_some()
+    }
+
+    /// Closes the database: flushes every dirty page, stops the disk scheduler,
+    /// syncs all segment files and marks the shutdown clean. Dropping a
+    /// Database does the same but cannot report errors.
+    pub fn close(self) -> Result<()> {
+        if let Some(autovacuum) = &self.autovacuum {
+            autovacuum.stop();
+        }
+        // Attached databases still held elsewhere close when dropped
+        let attached = std::mem::take(&mut *self.attached.write());
+        for db in attached.into_values() {
+            if let Ok(db) = Arc::try_unwrap(db) {
+                db.close()?;
+            }
+        }
+        self.bpm.shutdown()
+    }
+
+    /// Returns true if the database was opened with `CrioConfig::read_only`.
+    pub fn read_only(&self) -> bool {
+        self.bpm.read_only()
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only() {
+            return Err(CrioError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Returns true if the database was not closed cleanly last time, so its
+    /// contents should be checked before being trusted.
+    pub fn unclean_shutdown(&self) -> bool {
+        self.disk_manager.unclean_shutdown()
+    }
+
+    /// Returns the catalog.
+    pub fn catalog(&self) -> &Arc<Catalog> {
+        &self.catalog
+    }
+
+    /// Returns the cache of the plans sessions run prepared statements with.
+    pub fn plan_cache(&self) -> &PlanCache<StatementPlan> {
+        &self.plan_cache
+    }
+
+    /// Returns the buffer pool.
+    pub fn buffer_pool(&self) -> &Arc<BufferPoolManager> {
+        &self.bpm
+    }
+
+    /// Returns the disk manager. In shadow paging mode its page IDs are
+    /// physical and differ from the buffer pool's.
+    pub fn disk_manager(&self) -> &Arc<DiskManager> {
+        &self.disk_manager
+    }
+
+    /// Returns the lock manager used by table handles.
+    pub fn lock_manager(&self) -> &Arc<LockManager> {
+        &self.lock_manager
+    }
+
+    /// Creates an empty set of temp tables for one session, stored in a
+    /// scratch file of their own next to the database that may hold up to
+    /// `quota_pages` pages. Temp tables work on read-only databases too.
+    pub fn temp_tables(&self, quota_pages: u32) -> Result<TempTables> {
+        Ok(TempTables::new(
+            self.disk_manager.create_temp_file_manager(quota_pages)?,
+        ))
+    }
+}
+
+/// Returns the path of the double-write file of the database at `path`.
+pub(super) fn double_write_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".dblwr");
+    PathBuf::from(path)
+}
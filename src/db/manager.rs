@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::common::{CrioError, Result};
+
+use super::database::double_write_path;
+use super::{CrioConfig, Database};
+
+/// DatabaseManager keeps a registry of named databases in one directory, so
+/// one process can serve many independent databases, e.g. one per tenant.
+///
+/// The database called `name` lives in `dir/name.0`, `dir/name.1` and so on,
+/// with a directory page, catalog and buffer pool of its own; nothing is
+/// shared between databases but the process. Every database is opened with
+/// the manager's config unless `open_with` gives it another, and an optional
+/// frame budget caps the buffer pool frames of all open databases together.
+pub struct DatabaseManager {
+    /// Directory holding the database files
+    dir: PathBuf,
+    /// Config databases are opened with by default
+    config: CrioConfig,
+    /// Most buffer pool frames all open databases may have together
+    frame_budget: Option<usize>,
+    /// Open databases by name
+    databases: Mutex<BTreeMap<String, Arc<Database>>>,
+}
+
+impl DatabaseManager {
+    /// Creates a manager for the databases in `dir`, creating the directory
+    /// if needed. Databases are opened with `config`.
+    pub fn new<P: AsRef<Path>>(dir: P, config: CrioConfig) -> Result<Self> {
+        config.validate()?;
+        std::fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            config,
+            frame_budget: None,
+            databases: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Caps the buffer pool frames of all open databases together at
+    /// `frames`; opening a database that would go over fails with
+    /// `InvalidConfig`.
+    pub fn with_frame_budget(mut self, frames: usize) -> Self {
+        self.frame_budget = Some(frames);
+        self
+    }
+
+    /// Returns the directory holding the database files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the database called `name`, opening it (and creating it if
+    /// it does not exist) with the manager's config if it isn't open yet.
+    pub fn open(&self, name: &str) -> Result<Arc<Database>> {
+        self.open_with(name, self.config.clone())
+    }
+
+    /// Returns the database called `name`, opening it with `config` if it
+    /// isn't open yet. A database already open keeps its config.
+    pub fn open_with(&self, name: &str, config: CrioConfig) -> Result<Arc<Database>> {
+        check_name(name)?;
+        let mut databases = self.databases.lock();
+        if let Some(db) = databases.get(name) {
+            return Ok(Arc::clone(db));
+        }
+
+        if let Some(budget) = self.frame_budget {
+            let in_use: usize = databases
+                .values()
+                .map(|db| db.buffer_pool().pool_size())
+                .sum();
+            if in_use + config.pool_size > budget {
+                return Err(CrioError::InvalidConfig(format!(
+                    "opening '{}' would use {} buffer pool frames, over the budget of {}",
+                    name,
+                    in_use + config.pool_size,
+                    budget
+                )));
+            }
+        }
+
+        let db = Arc::new(Database::open(self.dir.join(name), config)?);
+        databases.insert(name.to_string(), Arc::clone(&db));
+        Ok(db)
+    }
+
+    /// Returns the database called `name` if it is open.
+    pub fn get(&self, name: &str) -> Option<Arc<Database>> {
+        self.databases.lock().get(name).cloned()
+    }
+
+    /// Returns the names of the open databases, in order.
+    pub fn open_names(&self) -> Vec<String> {
+        self.databases.lock().keys().cloned().collect()
+    }
+
+    /// Returns the names of every database in the directory, open or not,
+    /// in order.
+    pub fn names(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let file_name = entry?.file_name();
+            let Some(name) = file_name.to_str().and_then(|f| f.strip_suffix(".0")) else {
+                continue;
+            };
+            if check_name(name).is_ok() {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns true if the database called `name` exists in the directory.
+    pub fn exists(&self, name: &str) -> bool {
+        check_name(name).is_ok() && segment_path(&self.dir.join(name), 0).exists()
+    }
+
+    /// Returns the buffer pool frames of all open databases together.
+    pub fn frames_in_use(&self) -> usize {
+        self.databases
+            .lock()
+            .values()
+            .map(|db| db.buffer_pool().pool_size())
+            .sum()
+    }
+
+    /// Closes the database called `name`, flushing it and marking the
+    /// shutdown clean. Fails with `ObjectInUse` while anyone else still
+    /// holds it, and with `UnknownDatabase` if it isn't open.
+    pub fn close(&self, name: &str) -> Result<()> {
+        let mut databases = self.databases.lock();
+        let db = databases
+            .remove(name)
+            .ok_or_else(|| CrioError::UnknownDatabase(name.to_string()))?;
+        match Arc::try_unwrap(db) {
+            Ok(db) => db.close(),
+            Err(db) => {
+                databases.insert(name.to_string(), db);
+                Err(CrioError::ObjectInUse(name.to_string()))
+            }
+        }
+    }
+
+    /// Closes every open database, stopping at the first error.
+    pub fn close_all(&self) -> Result<()> {
+        for name in self.open_names() {
+            self.close(&name)?;
+        }
+        Ok(())
+    }
+
+    /// Closes the database called `name` if it is open and deletes its
+    /// files.
+    pub fn drop_database(&self, name: &str) -> Result<()> {
+        check_name(name)?;
+        if !self.exists(name) {
+            return Err(CrioError::UnknownDatabase(name.to_string()));
+        }
+        if self.get(name).is_some() {
+            self.close(name)?;
+        }
+
+        let path = self.dir.join(name);
+        for file_id in 0.. {
+            let segment = segment_path(&path, file_id);
+            if !segment.exists() {
+                break;
+            }
+            std::fs::remove_file(segment)?;
+        }
+        match std::fs::remove_file(double_write_path(&path)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Database names are file names, so they are limited to ASCII letters,
+/// digits, `_` and `-`.
+pub(super) fn check_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+    if !valid {
+        return Err(CrioError::InvalidDatabaseName(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Returns the path of segment `file_id` of the database at `path`.
+pub(super) fn segment_path(path: &Path, file_id: u8) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{}", file_id));
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_name() {
+        for name in ["tenant_1", "a-b", "X"] {
+            assert!(check_name(name).is_ok());
+        }
+        for name in ["", "../up", "a.b", "a b", "ü"] {
+            assert!(matches!(
+                check_name(name),
+                Err(CrioError::InvalidDatabaseName(_))
+            ));
+        }
+    }
+}
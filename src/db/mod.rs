@@ -0,0 +1,20 @@
+//! Top-level `Database` facade over the storage, buffer, catalog and
+//! concurrency layers.
+
+mod config;
+mod database;
+mod manager;
+mod partitioned_table_handle;
+mod session;
+mod snapshot;
+mod table_handle;
+mod vacuum;
+
+pub use config::*;
+pub use database::*;
+pub use manager::*;
+pub use partitioned_table_handle::*;
+pub use session::*;
+pub use snapshot::*;
+pub use table_handle::*;
+pub use vacuum::*;
@@ -0,0 +1,156 @@
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::catalog::{Catalog, PartitionedTableInfo};
+use crate::common::{CancellationToken, CrioError, QueryBudget, RecordId, ResourceLimits, Result};
+use crate::concurrency::{LockManager, LockMode};
+use crate::execution::PartitionScanExecutor;
+use crate::tuple::{Schema, Tuple, Value};
+
+use super::TableHandle;
+
+/// PartitionedTableHandle is a cheap, cloneable handle for a partitioned
+/// table. Inserts are routed to the partition owning the row's key; each
+/// partition is an ordinary table reachable through `partition`.
+///
+/// Indexes live on partitions, so a unique index only enforces uniqueness
+/// within its partition.
+///
+/// Scans are cancelled, time out and are held to budgets as `TableHandle`
+/// scans are, and partition handles share this handle's token, timeout and
+/// budget settings.
+#[derive(Clone)]
+pub struct PartitionedTableHandle {
+    info: Arc<PartitionedTableInfo>,
+    catalog: Arc<Catalog>,
+    lock_manager: Arc<LockManager>,
+    /// Cancels the scans of this handle and its clones
+    cancellation: CancellationToken,
+    /// How long each scan may run
+    statement_timeout: Option<Duration>,
+    /// Budget every scan charges, instead of one of its own
+    budget: Option<QueryBudget>,
+    /// Limits of each scan's own budget
+    query_limits: ResourceLimits,
+}
+
+impl PartitionedTableHandle {
+    pub(crate) fn new(
+        info: Arc<PartitionedTableInfo>,
+        catalog: Arc<Catalog>,
+        lock_manager: Arc<LockManager>,
+        statement_timeout: Option<Duration>,
+        query_limits: ResourceLimits,
+    ) -> Self {
+        Self {
+            info,
+            catalog,
+            lock_manager,
+            cancellation: CancellationToken::new(),
+            statement_timeout,
+            budget: None,
+            query_limits,
+        }
+    }
+
+    /// Returns a handle whose scans stop once `cancellation` is cancelled.
+    pub fn with_cancellation(&self, cancellation: CancellationToken) -> Self {
+        Self {
+            cancellation,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle whose scans each stop after `timeout`, or never
+    /// time out if it is None.
+    pub fn with_statement_timeout(&self, timeout: Option<Duration>) -> Self {
+        Self {
+            statement_timeout: timeout,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle whose scans all charge `budget`.
+    pub fn with_budget(&self, budget: QueryBudget) -> Self {
+        Self {
+            budget: Some(budget),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle whose scans each get a budget with `limits`.
+    pub fn with_query_limits(&self, limits: ResourceLimits) -> Self {
+        Self {
+            budget: None,
+            query_limits: limits,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the table name.
+    pub fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    /// Returns the table schema.
+    pub fn schema(&self) -> &Arc<Schema> {
+        &self.info.schema
+    }
+
+    /// Returns the number of partitions.
+    pub fn partition_count(&self) -> usize {
+        self.info.partitions.len()
+    }
+
+    /// Returns a handle to partition `index`.
+    pub fn partition(&self, index: usize) -> Option<TableHandle> {
+        let info = self.info.partitions.get(index)?;
+        let handle = TableHandle::new(
+            Arc::clone(info),
+            Arc::clone(&self.catalog),
+            Arc::clone(&self.lock_manager),
+            self.statement_timeout,
+            self.query_limits,
+        );
+        let handle = handle.with_cancellation(self.cancellation.clone());
+        Some(match &self.budget {
+            Some(budget) => handle.with_budget(budget.clone()),
+            None => handle,
+        })
+    }
+
+    /// Inserts a row into the partition owning its key.
+    pub fn insert(&self, values: Vec<Value>) -> Result<RecordId> {
+        if values.len() != self.info.schema.column_count() {
+            return Err(CrioError::SchemaMismatch);
+        }
+        let index = self.info.route(&values);
+        self.partition(index)
+            .expect("routed to a missing partition")
+            .insert(values)
+    }
+
+    /// Returns every row whose partition key lies in `range`, reading only
+    /// the partitions that can hold such keys.
+    pub fn scan<R: RangeBounds<i64>>(&self, range: R) -> Result<Vec<(RecordId, Tuple)>> {
+        let cancellation = match self.statement_timeout {
+            Some(timeout) => self.cancellation.with_timeout(timeout),
+            None => self.cancellation.clone(),
+        };
+        let budget = self
+            .budget
+            .clone()
+            .unwrap_or_else(|| QueryBudget::new(self.query_limits));
+        let scan = PartitionScanExecutor::new(Arc::clone(&self.info), range)
+            .with_cancellation(cancellation)
+            .with_budget(budget);
+        // Table IDs ascend with partition order, so locks are taken in order
+        let _locks: Vec<_> = scan
+            .remaining_partitions()
+            .into_iter()
+            .map(|table_id| self.lock_manager.lock_table(table_id, LockMode::Shared))
+            .collect();
+        scan.collect()
+    }
+}
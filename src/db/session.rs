@@ -0,0 +1,473 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::common::{CrioError, RecordId, Result};
+use crate::concurrency::{LockManager, LockMode, TableLock};
+use crate::execution::Cte;
+use crate::storage::table::{TempTableScope, TempTables};
+use crate::tuple::{Schema, Tuple, Value};
+
+use super::{Database, TableHandle};
+
+/// Name under which the session's own database appears in a search path
+pub const MAIN_SCHEMA: &str = "main";
+
+/// Scratch pages a session's temp tables may use by default
+pub const DEFAULT_TEMP_QUOTA_PAGES: u32 = 16 * 1024;
+
+/// ID of the next session
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Isolation level of a session's transactions. Tables are isolated by the
+/// locks a transaction takes, so the level records what the client asked
+/// for rather than changing how rows are read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IsolationLevel {
+    /// Each statement sees the rows committed before it started
+    #[default]
+    ReadCommitted,
+    /// Tables locked by the transaction stay as they were until it ends
+    RepeatableRead,
+    /// The transaction behaves as if it ran alone
+    Serializable,
+}
+
+/// Settings of one session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSettings {
+    /// Schemas unqualified table names are looked up in, in order:
+    /// `MAIN_SCHEMA` or the alias of an attached database
+    pub search_path: Vec<String>,
+    /// Isolation level of the transactions the session begins
+    pub isolation_level: IsolationLevel,
+    /// Statement timeout of table handles; None keeps the database's
+    pub statement_timeout: Option<Duration>,
+    /// Scratch pages the session's temp tables may use together
+    pub temp_quota_pages: u32,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            search_path: vec![MAIN_SCHEMA.to_string()],
+            isolation_level: IsolationLevel::default(),
+            statement_timeout: None,
+            temp_quota_pages: DEFAULT_TEMP_QUOTA_PAGES,
+        }
+    }
+}
+
+/// A statement prepared in a session under a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreparedStatement {
+    /// Name the statement was prepared under
+    pub name: String,
+    /// Statement text, with `$1`, `$2`, ... for parameters
+    pub text: String,
+    /// Number of parameters: the highest `$N` in the text
+    pub param_count: usize,
+}
+
+impl PreparedStatement {
+    fn new(name: &str, text: &str) -> Self {
+        let param_count = text
+            .split('$')
+            .skip(1)
+            .filter_map(|rest| {
+                let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                rest[..digits].parse::<usize>().ok()
+            })
+            .max()
+            .unwrap_or(0);
+        Self {
+            name: name.to_string(),
+            text: text.to_string(),
+            param_count,
+        }
+    }
+}
+
+/// A compiled prepared statement, run in a session with the values of its
+/// parameters. Plans are made by the embedding application, which compiles
+/// statement text into them (see `Session::execute`).
+pub type StatementPlan =
+    Box<dyn Fn(&Session, &[Value]) -> Result<Vec<(RecordId, Tuple)>> + Send + Sync>;
+
+/// The transaction a session is in.
+struct Transaction {
+    isolation_level: IsolationLevel,
+    started: Instant,
+}
+
+/// Table locks a session's transaction holds, shared with the table handles
+/// of the session so they use them instead of locking the tables again.
+#[derive(Default)]
+pub(crate) struct SessionLocks {
+    locks: Mutex<Vec<TableLock>>,
+}
+
+impl SessionLocks {
+    /// Returns the mode table `table_id` of the database `manager` locks is
+    /// held in, if it is.
+    pub(crate) fn mode(&self, manager: &Arc<LockManager>, table_id: u32) -> Option<LockMode> {
+        self.locks
+            .lock()
+            .iter()
+            .find(|lock| lock.table_id() == table_id && Arc::ptr_eq(lock.manager(), manager))
+            .map(TableLock::mode)
+    }
+}
+
+/// Session is the execution context of one client connection: its
+/// settings, its transaction, its temp tables and its prepared statements.
+///
+/// Sessions share the database but nothing else, and everything a session
+/// holds (locks, temp tables and their scratch file) is released when it is
+/// dropped. Writes are applied as they are made, since the engine has no
+/// undo log, so a transaction groups the table locks taken with
+/// `lock_table` and the temp tables scoped to it, all released by `commit`.
+pub struct Session {
+    id: u64,
+    db: Arc<Database>,
+    settings: SessionSettings,
+    transaction: Option<Transaction>,
+    /// Table locks held until the transaction ends
+    locks: Arc<SessionLocks>,
+    /// Temp tables, created with the scratch file on first use
+    temp_tables: Option<TempTables>,
+    prepared: BTreeMap<String, PreparedStatement>,
+    /// Role the session acts as, or None for unrestricted access
+    role: Option<String>,
+}
+
+impl Session {
+    /// Opens a session on `db` with default settings.
+    pub fn new(db: Arc<Database>) -> Self {
+        Self::with_settings(db, SessionSettings::default())
+    }
+
+    /// Opens a session on `db` with `settings`.
+    pub fn with_settings(db: Arc<Database>, settings: SessionSettings) -> Self {
+        Self {
+            id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+            db,
+            settings,
+            transaction: None,
+            locks: Arc::default(),
+            temp_tables: None,
+            prepared: BTreeMap::new(),
+            role: None,
+        }
+    }
+
+    /// Returns the session ID, unique within the process.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the database.
+    pub fn database(&self) -> &Arc<Database> {
+        &self.db
+    }
+
+    /// Returns the settings.
+    pub fn settings(&self) -> &SessionSettings {
+        &self.settings
+    }
+
+    /// Sets the search path for unqualified table names.
+    pub fn set_search_path<S: AsRef<str>>(&mut self, schemas: &[S]) {
+        self.settings.search_path = schemas.iter().map(|s| s.as_ref().to_string()).collect();
+    }
+
+    /// Sets the isolation level of later transactions; the current one
+    /// keeps its own.
+    pub fn set_isolation_level(&mut self, level: IsolationLevel) {
+        self.settings.isolation_level = level;
+    }
+
+    /// Sets the statement timeout of table handles, or keeps the
+    /// database's if None.
+    pub fn set_statement_timeout(&mut self, timeout: Option<Duration>) {
+        self.settings.statement_timeout = timeout;
+    }
+
+    /// Makes the session act as `role`, or with unrestricted access if
+    /// None. Fails with `UnknownRole` if the database has no such role, and
+    /// with `RoleChangeDenied` if the session already acts as a role that
+    /// isn't a superuser, which can't leave its privileges behind.
+    pub fn set_role(&mut self, role: Option<&str>) -> Result<()> {
+        if let Some(current) = &self.role {
+            let superuser = self
+                .db
+                .catalog()
+                .role(current)
+                .is_some_and(|info| info.superuser);
+            if !superuser {
+                return Err(CrioError::RoleChangeDenied(current.clone()));
+            }
+        }
+        if let Some(role) = role {
+            if self.db.catalog().role(role).is_none() {
+                return Err(CrioError::UnknownRole(role.to_string()));
+            }
+        }
+        self.role = role.map(str::to_string);
+        Ok(())
+    }
+
+    /// Returns the role the session acts as.
+    pub fn role(&self) -> Option<&str> {
+        self.role.as_deref()
+    }
+
+    /// Returns a handle to the table called `name`. A name with a schema
+    /// (`alias.table`) is looked up there; any other is looked up in each
+    /// schema of the search path in turn, temp tables aside. The handle uses
+    /// the table locks the session holds (see `lock_table`).
+    ///
+    /// A session acting as a role gets a handle limited to the role's
+    /// privileges on the table (see `TableHandle::with_role`), failing with
+    /// `PermissionDenied` right away if it holds none. Roles belong to a
+    /// database, so the role must exist in an attached one to use its
+    /// tables.
+    pub fn table(&self, name: &str) -> Result<TableHandle> {
+        let (db, table) = self.resolve(name)?;
+        let mut handle = db
+            .table(&table)?
+            .with_session_locks(Arc::clone(&self.locks));
+        if let Some(role) = &self.role {
+            handle = handle.with_role(role)?;
+            if handle.privileges().is_empty() {
+                return Err(CrioError::PermissionDenied {
+                    role: role.clone(),
+                    privilege: "any privilege".to_string(),
+                    table: name.to_string(),
+                });
+            }
+        }
+        Ok(match self.settings.statement_timeout {
+            Some(timeout) => handle.with_statement_timeout(Some(timeout)),
+            None => handle,
+        })
+    }
+
+    /// Returns the database holding the table called `name` and the table's
+    /// name there.
+    fn resolve(&self, name: &str) -> Result<(Arc<Database>, String)> {
+        let schema_db = |schema: &str| match schema {
+            MAIN_SCHEMA => Some(Arc::clone(&self.db)),
+            alias => self.db.attached(alias),
+        };
+        if let Some((schema, table)) = name.split_once('.') {
+            let db = schema_db(schema).ok_or_else(|| CrioError::UnknownTable(name.to_string()))?;
+            return Ok((db, table.to_string()));
+        }
+        for schema in &self.settings.search_path {
+            if let Some(db) = schema_db(schema) {
+                if db.catalog().table(name).is_some() {
+                    return Ok((db, name.to_string()));
+                }
+            }
+        }
+        Err(CrioError::UnknownTable(name.to_string()))
+    }
+
+    /// Begins a transaction at the session's isolation level. Fails with
+    /// `TransactionInProgress` if one has begun already.
+    pub fn begin(&mut self) -> Result<()> {
+        if self.transaction.is_some() {
+            return Err(CrioError::TransactionInProgress);
+        }
+        self.transaction = Some(Transaction {
+            isolation_level: self.settings.isolation_level,
+            started: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Ends the transaction, releasing its table locks and dropping the
+    /// temp tables scoped to it. Fails with `NoTransaction` outside one.
+    pub fn commit(&mut self) -> Result<()> {
+        if self.transaction.take().is_none() {
+            return Err(CrioError::NoTransaction);
+        }
+        self.locks.locks.lock().clear();
+        if let Some(temp_tables) = &mut self.temp_tables {
+            temp_tables.end_transaction();
+        }
+        Ok(())
+    }
+
+    /// Returns true if the session is in a transaction.
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Returns the isolation level of the current transaction.
+    pub fn transaction_isolation(&self) -> Option<IsolationLevel> {
+        self.transaction.as_ref().map(|t| t.isolation_level)
+    }
+
+    /// Returns how long the current transaction has been running.
+    pub fn transaction_age(&self) -> Option<Duration> {
+        self.transaction.as_ref().map(|t| t.started.elapsed())
+    }
+
+    /// Locks the table called `name` in `mode` until the transaction ends
+    /// (`LOCK TABLE`). Fails with `NoTransaction` outside a transaction, and
+    /// with `PermissionDenied` if the session's role may not take the lock
+    /// (see `TableHandle::authorize_lock`).
+    ///
+    /// Handles from `table` use the lock rather than locking the table for
+    /// each operation, since waiting for a lock the session holds itself
+    /// would never end. For the same reason a table locked shared can't be
+    /// locked exclusively: doing so here, or writing it through a handle
+    /// where the write needs an exclusive lock, fails with `LockUpgrade`.
+    /// Locking a table again in a mode already covered does nothing.
+    pub fn lock_table(&mut self, name: &str, mode: LockMode) -> Result<()> {
+        if self.transaction.is_none() {
+            return Err(CrioError::NoTransaction);
+        }
+        let handle = self.table(name)?;
+        handle.authorize_lock(mode)?;
+        let (db, _) = self.resolve(name)?;
+        match (self.locks.mode(db.lock_manager(), handle.table_id()), mode) {
+            (None, mode) => {
+                let lock = db.lock_manager().lock_table(handle.table_id(), mode);
+                self.locks.locks.lock().push(lock);
+                Ok(())
+            }
+            (Some(LockMode::Shared), LockMode::Exclusive) => {
+                Err(CrioError::LockUpgrade(handle.table_id()))
+            }
+            (Some(_), _) => Ok(()),
+        }
+    }
+
+    /// Returns the session's temp tables, creating their scratch file on
+    /// first use.
+    pub fn temp_tables(&mut self) -> Result<&mut TempTables> {
+        if self.temp_tables.is_none() {
+            self.temp_tables = Some(self.db.temp_tables(self.settings.temp_quota_pages)?);
+        }
+        Ok(self.temp_tables.as_mut().expect("temp tables just created"))
+    }
+
+    /// Returns the CTE `name` of a statement run in this session, with rows
+    /// of `schema` from `run`, which the statement reads `references` times
+    /// (see `Cte`). A CTE read more than once is materialized in the
+    /// session's scratch file, counting against its temp quota until the
+    /// CTE is dropped.
+    pub fn cte<F, I>(
+        &mut self,
+        name: &str,
+        schema: Schema,
+        references: usize,
+        run: F,
+    ) -> Result<Cte<F>>
+    where
+        F: FnMut() -> Result<I>,
+        I: Iterator<Item = Result<(RecordId, Tuple)>>,
+    {
+        let temp = Arc::clone(self.temp_tables()?.temp_file());
+        Ok(Cte::new(name, schema, references, temp, run))
+    }
+
+    /// Returns the scope a temp table created now should have: the
+    /// transaction's if the session is in one and `on_commit_drop` is set,
+    /// the session's otherwise.
+    pub fn temp_table_scope(&self, on_commit_drop: bool) -> TempTableScope {
+        if on_commit_drop && self.in_transaction() {
+            TempTableScope::Transaction
+        } else {
+            TempTableScope::Session
+        }
+    }
+
+    /// Prepares `text` under `name`. Fails with `DuplicateStatementName` if
+    /// a statement has been prepared under that name already.
+    pub fn prepare(&mut self, name: &str, text: &str) -> Result<&PreparedStatement> {
+        if self.prepared.contains_key(name) {
+            return Err(CrioError::DuplicateStatementName(name.to_string()));
+        }
+        let statement = PreparedStatement::new(name, text);
+        Ok(self.prepared.entry(name.to_string()).or_insert(statement))
+    }
+
+    /// Returns the statement prepared under `name`.
+    pub fn prepared(&self, name: &str) -> Result<&PreparedStatement> {
+        self.prepared
+            .get(name)
+            .ok_or_else(|| CrioError::UnknownStatement(name.to_string()))
+    }
+
+    /// Forgets the statement prepared under `name` (`DEALLOCATE`).
+    pub fn deallocate(&mut self, name: &str) -> Result<()> {
+        self.prepared
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| CrioError::UnknownStatement(name.to_string()))
+    }
+
+    /// Returns the names of the prepared statements, in order.
+    pub fn prepared_names(&self) -> Vec<String> {
+        self.prepared.keys().cloned().collect()
+    }
+
+    /// Runs the statement prepared under `name` (`EXECUTE`) with `params`
+    /// as the values of `$1`, `$2`, ... and returns its rows.
+    ///
+    /// The plan comes from the database's `PlanCache`, shared by every
+    /// session: `planner` is called with the normalized statement text, and
+    /// returns the plan and the names of the tables it reads, only if no
+    /// plan of the statement is cached or a table it reads has changed since
+    /// it was made. Fails with `UnknownStatement` if nothing is prepared
+    /// under `name` and `ParameterCount` if `params` has the wrong length.
+    pub fn execute<F>(
+        &self,
+        name: &str,
+        params: &[Value],
+        planner: F,
+    ) -> Result<Vec<(RecordId, Tuple)>>
+    where
+        F: FnOnce(&str) -> Result<(StatementPlan, Vec<String>)>,
+    {
+        let statement = self.prepared(name)?;
+        if params.len() != statement.param_count {
+            return Err(CrioError::ParameterCount {
+                expected: statement.param_count,
+                given: params.len(),
+            });
+        }
+        let plan = self.db.plan_cache().get_or_plan(&statement.text, planner)?;
+        plan(self, params)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // Handles may outlive the session; its locks must not
+        self.locks.locks.lock().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepared_param_count() {
+        let count = |text| PreparedStatement::new("s", text).param_count;
+        assert_eq!(count("SELECT 1"), 0);
+        assert_eq!(
+            count("SELECT * FROM t WHERE a = $2 AND b = $10 OR c = $1"),
+            10
+        );
+        assert_eq!(count("SELECT '$' || $x"), 0);
+    }
+}
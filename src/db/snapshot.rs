@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::catalog::{Catalog, TableInfo};
+use crate::common::{CrioError, Result};
+use crate::concurrency::{LockManager, LockMode};
+
+use super::manager::segment_path;
+use super::{CrioConfig, Database};
+
+/// What `Database::export_snapshot` wrote.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotStats {
+    /// Tables copied, counting each partition of a partitioned table
+    pub tables: usize,
+    /// Rows copied
+    pub rows: u64,
+    /// B+Tree and full-text indexes rebuilt
+    pub indexes: usize,
+}
+
+/// Writes a compacted copy of the database behind `catalog` to a new
+/// database at `path`, which must not exist yet.
+///
+/// Every table is locked exclusively for the whole export, so the copy is
+/// consistent across tables: single-row writes to a table without indexes
+/// take only a shared lock, so shared locks would not keep them out. Readers
+/// wait for the export too. Rows are packed into full pages with
+/// `TableHeap::bulk_insert`, leaving out deleted rows and forwarding stubs;
+/// indexes, bloom filters and zone maps are then created afresh on the copy,
+/// and roles and grants copied.
+/// The snapshot is an ordinary database in a single segment file (as long as
+/// it fits one), closed cleanly and meant to be opened with
+/// `CrioConfig::read_only`. If the export fails, the segment files it
+/// created are removed.
+pub fn export_snapshot(
+    catalog: &Catalog,
+    lock_manager: &Arc<LockManager>,
+    path: &Path,
+) -> Result<SnapshotStats> {
+    let first_segment = segment_path(path, 0);
+    if first_segment.exists() {
+        return Err(CrioError::Io(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", first_segment.display()),
+        )));
+    }
+
+    let mut tables: Vec<Arc<TableInfo>> = catalog
+        .table_names()
+        .iter()
+        .filter_map(|name| catalog.table(name))
+        .collect();
+    // Lock in table ID order so concurrent exports cannot deadlock
+    tables.sort_by_key(|table| table.table_id);
+    let _locks: Vec<_> = tables
+        .iter()
+        .map(|table| lock_manager.lock_table(table.table_id, LockMode::Exclusive))
+        .collect();
+
+    let result = write_snapshot(catalog, &tables, path);
+    if result.is_err() {
+        remove_segments(path);
+    }
+    result
+}
+
+/// Creates the snapshot database at `path` and copies `tables` into it.
+fn write_snapshot(
+    catalog: &Catalog,
+    tables: &[Arc<TableInfo>],
+    path: &Path,
+) -> Result<SnapshotStats> {
+    let snapshot = Database::open(path, CrioConfig::default())?;
+    let mut stats = SnapshotStats::default();
+
+    let mut partitions = HashSet::new();
+    for name in catalog.partitioned_table_names() {
+        let Some(table) = catalog.partitioned_table(&name) else {
+            continue;
+        };
+        let key_column = table
+            .schema
+            .column(table.key_column)
+            .map(|column| column.name().to_string())
+            .ok_or(CrioError::SchemaMismatch)?;
+        snapshot.catalog().create_partitioned_table(
+            &name,
+            (*table.schema).clone(),
+            &key_column,
+            table.scheme.clone(),
+        )?;
+        partitions.extend(table.partitions.iter().map(|p| p.name.clone()));
+    }
+    for table in tables {
+        if !partitions.contains(&table.name) {
+            snapshot
+                .catalog()
+                .create_table(&table.name, (*table.schema).clone())?;
+        }
+    }
+
+    for table in tables {
+        let target = snapshot
+            .catalog()
+            .table(&table.name)
+            .ok_or_else(|| CrioError::UnknownTable(table.name.clone()))?;
+        stats.rows += copy_rows(table, &target)?;
+        stats.tables += 1;
+    }
+
+    for table in tables {
+        stats.indexes += rebuild_indexes(catalog, snapshot.catalog(), table)?;
+    }
+
+    for role in catalog.role_names() {
+        if let Some(role) = catalog.role(&role) {
+            snapshot.catalog().create_role(&role.name, role.superuser)?;
+        }
+    }
+    for (role, table, privileges) in catalog.grants() {
+        snapshot.catalog().grant(&role, &table, privileges)?;
+    }
+
+    snapshot.close()?;
+    Ok(stats)
+}
+
+/// Copies the live rows of `source` into the empty heap of `target` in one
+/// bulk load, so only the last page is partly filled.
+fn copy_rows(source: &TableInfo, target: &TableInfo) -> Result<u64> {
+    let mut error = None;
+    let rows = source.heap.iter().map_while(|row| match row {
+        Ok((_, data)) => Some(data),
+        Err(e) => {
+            error = Some(e);
+            None
+        }
+    });
+    let copied = target.heap.bulk_insert(rows, 1.0)?.len() as u64;
+    match error {
+        Some(e) => Err(e),
+        None => Ok(copied),
+    }
+}
+
+/// Creates the indexes, bloom filter and zone map of `table` on its copy in
+/// `snapshot`, filled from the copied rows. Returns the number of indexes.
+fn rebuild_indexes(catalog: &Catalog, snapshot: &Catalog, table: &TableInfo) -> Result<usize> {
+    let column_name = |column: usize| {
+        table
+            .schema
+            .column(column)
+            .map(|column| column.name().to_string())
+            .ok_or(CrioError::SchemaMismatch)
+    };
+
+    let mut indexes = 0;
+    for index in catalog.table_indexes(table.table_id) {
+        let column = column_name(index.key_column)?;
+        match &index.key_path {
+            Some(path) => {
+                snapshot.create_path_index(&index.name, &table.name, &column, &path.to_string())?
+            }
+            None => snapshot.create_index(&index.name, &table.name, &column)?,
+        };
+        indexes += 1;
+    }
+    for index in catalog.table_text_indexes(table.table_id) {
+        let column = column_name(index.key_column)?;
+        snapshot.create_text_index(&index.name, &table.name, &column)?;
+        indexes += 1;
+    }
+
+    if let Some(filter) = catalog.bloom_filter(table.table_id) {
+        snapshot.create_bloom_filter(&table.name, &column_name(filter.key_column)?)?;
+    }
+    if let Some(zone_map) = catalog.zone_map(table.table_id) {
+        let columns = zone_map
+            .columns
+            .iter()
+            .map(|&column| column_name(column))
+            .collect::<Result<Vec<_>>>()?;
+        let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+        snapshot.create_zone_map(&table.name, &columns)?;
+    }
+    Ok(indexes)
+}
+
+/// Removes the segment files of a partly written snapshot at `path`. Errors
+/// are ignored: the export has already failed.
+fn remove_segments(path: &Path) {
+    for file_id in 0..=u8::MAX {
+        if std::fs::remove_file(segment_path(path, file_id)).is_err() {
+            break;
+        }
+    }
+}
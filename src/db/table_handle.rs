@@ -0,0 +1,1103 @@
+use std::collections::HashSet;
+use std::ops::{RangeBounds, RangeInclusive};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::catalog::{
+    index_key, Catalog, IndexInfo, Privilege, Privileges, TableInfo, TextIndexInfo,
+};
+use crate::common::{
+    CancellationToken, CrioError, JobHandle, JobProgress, QueryBudget, RecordId, ResourceLimits,
+    Result, CANCEL_CHECK_ROWS, PAGE_SIZE,
+};
+use crate::concurrency::{KeyRangeLock, LockManager, LockMode, TableLock};
+use crate::execution::{
+    Batch, BatchExecutor, BatchPredicate, ExplainAnalyze, FullTextScanExecutor, HashJoinExecutor,
+    IndexScanExecutor, JoinType, KeyPredicate, OnConflict, RuntimeFilterSlot, RuntimeFilterStats,
+    SeqScanExecutor, SubqueryCache, SubqueryExecutor, SubqueryKind,
+};
+use crate::index::TextQuery;
+use crate::storage::table::DEFAULT_FILL_FACTOR;
+use crate::tuple::{Schema, Tuple, Value};
+
+use super::SessionLocks;
+
+/// TableHandle is a cheap, cloneable handle for reading and writing one table.
+///
+/// Every operation takes a table lock: `Shared` for reads and for writes to
+/// tables without indexes, `Exclusive` for writes to indexed tables, bulk
+/// loads and upserts so the uniqueness check and the writes happen atomically.
+/// Handles from `Session::table` use the table locks the session holds
+/// instead (see `Session::lock_table`).
+///
+/// Every write keeps the table's indexes in step: inserts add the row's keys,
+/// deletes remove them and updates move a row whose key changed from its old
+/// key to its new one. Full-text indexes keep the postings of deleted rows
+/// and of replaced text, which searches recheck and skip.
+///
+/// Writes also lock every B+Tree key they add or remove exclusively, so a
+/// shared key-range lock from `lock_key_range` keeps a range of an index
+/// free of phantoms between scans. A write that finds a key locked waits
+/// for it with no other lock held, then starts over.
+///
+/// Scans stop with `QueryCancelled` when the handle's cancellation token is
+/// cancelled or when they run past the statement timeout, which starts
+/// anew with each scan. Each scan also gets a fresh `QueryBudget` with the
+/// handle's resource limits and fails with `BudgetExceeded` past them,
+/// unless the handle was given one budget to share with `with_budget`.
+#[derive(Clone)]
+pub struct TableHandle {
+    info: Arc<TableInfo>,
+    catalog: Arc<Catalog>,
+    lock_manager: Arc<LockManager>,
+    /// Cancels the scans of this handle and its clones
+    cancellation: CancellationToken,
+    /// How long each scan may run
+    statement_timeout: Option<Duration>,
+    /// Budget every scan charges, instead of one of its own
+    budget: Option<QueryBudget>,
+    /// Limits of each scan's own budget
+    query_limits: ResourceLimits,
+    /// Locks of the session the handle came from, used instead of its own
+    session_locks: Option<Arc<SessionLocks>>,
+    /// Role the handle acts as and the privileges it holds on the table,
+    /// or None for unrestricted access
+    role: Option<(Arc<str>, Privileges)>,
+    /// Collects what the runtime filters of joins did, under
+    /// `explain_analyze`
+    runtime_filters: Option<Arc<Mutex<Vec<RuntimeFilterStats>>>>,
+}
+
+impl TableHandle {
+    pub(crate) fn new(
+        info: Arc<TableInfo>,
+        catalog: Arc<Catalog>,
+        lock_manager: Arc<LockManager>,
+        statement_timeout: Option<Duration>,
+        query_limits: ResourceLimits,
+    ) -> Self {
+        Self {
+            info,
+            catalog,
+            lock_manager,
+            cancellation: CancellationToken::new(),
+            statement_timeout,
+            budget: None,
+            query_limits,
+            session_locks: None,
+            role: None,
+            runtime_filters: None,
+        }
+    }
+
+    /// Returns a handle that uses the table locks `locks` holds rather than
+    /// locking the table again.
+    pub(crate) fn with_session_locks(&self, locks: Arc<SessionLocks>) -> Self {
+        Self {
+            session_locks: Some(locks),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle whose scans stop once `cancellation` is cancelled,
+    /// e.g. from another thread.
+    pub fn with_cancellation(&self, cancellation: CancellationToken) -> Self {
+        Self {
+            cancellation,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle whose scans each stop after `timeout`, or never
+    /// time out if it is None. Handles start with
+    /// `CrioConfig::statement_timeout`.
+    pub fn with_statement_timeout(&self, timeout: Option<Duration>) -> Self {
+        Self {
+            statement_timeout: timeout,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle whose scans all charge `budget`, so a query made of
+    /// several scans is held to one set of limits.
+    pub fn with_budget(&self, budget: QueryBudget) -> Self {
+        Self {
+            budget: Some(budget),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle whose scans each get a budget with `limits`. Handles
+    /// start with `CrioConfig::query_limits`.
+    pub fn with_query_limits(&self, limits: ResourceLimits) -> Self {
+        Self {
+            budget: None,
+            query_limits: limits,
+            ..self.clone()
+        }
+    }
+
+    /// Returns a handle acting as `role`, whose operations each fail with
+    /// `PermissionDenied` unless the role holds the privilege they need:
+    /// `SELECT` for reads, `INSERT`, `UPDATE` or `DELETE` for writes (an
+    /// upsert that may update needs both of the first two). Privileges are
+    /// read once, when the handle is made, like a plan's; later grants and
+    /// revokes apply to handles made after them.
+    pub fn with_role(&self, role: &str) -> Result<Self> {
+        let privileges = self.catalog.table_privileges(role, &self.info.name)?;
+        Ok(Self {
+            role: Some((Arc::from(role), privileges)),
+            ..self.clone()
+        })
+    }
+
+    /// Returns the privileges the handle holds: every one without a role.
+    pub fn privileges(&self) -> Privileges {
+        self.role
+            .as_ref()
+            .map_or(Privileges::ALL, |(_, privileges)| *privileges)
+    }
+
+    /// Fails with `PermissionDenied` if the handle's role lacks `privilege`.
+    pub fn authorize(&self, privilege: Privilege) -> Result<()> {
+        match &self.role {
+            Some((role, privileges)) if !privileges.contains(privilege) => {
+                Err(CrioError::PermissionDenied {
+                    role: role.to_string(),
+                    privilege: privilege.to_string(),
+                    table: self.info.name.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error unless the handle may lock the table in `mode`: a
+    /// shared lock needs SELECT, an exclusive one any privilege to write
+    /// rows.
+    pub fn authorize_lock(&self, mode: LockMode) -> Result<()> {
+        match (mode, &self.role) {
+            (LockMode::Exclusive, Some((_, privileges)))
+                if [Privilege::Insert, Privilege::Update, Privilege::Delete]
+                    .into_iter()
+                    .any(|privilege| privileges.contains(privilege)) =>
+            {
+                Ok(())
+            }
+            (LockMode::Exclusive, _) => self.authorize(Privilege::Update),
+            (LockMode::Shared, _) => self.authorize(Privilege::Select),
+        }
+    }
+
+    /// Runs `statement` against this handle and reports the rows it
+    /// returned, how long it took and the resources its scans used, which
+    /// all charge one fresh budget with the handle's limits, along with what
+    /// the runtime filters of its `hash_join`s did.
+    pub fn explain_analyze<F>(
+        &self,
+        statement: F,
+    ) -> Result<(Vec<(RecordId, Tuple)>, ExplainAnalyze)>
+    where
+        F: FnOnce(&TableHandle) -> Result<Vec<(RecordId, Tuple)>>,
+    {
+        let limits = self
+            .budget
+            .as_ref()
+            .map_or(self.query_limits, QueryBudget::limits);
+        let budget = QueryBudget::new(limits);
+        let runtime_filters = Arc::new(Mutex::new(Vec::new()));
+        let handle = Self {
+            runtime_filters: Some(Arc::clone(&runtime_filters)),
+            ..self.with_budget(budget.clone())
+        };
+        let start = Instant::now();
+        let rows = statement(&handle)?;
+        let report = ExplainAnalyze {
+            rows: rows.len(),
+            elapsed: start.elapsed(),
+            usage: budget.usage(),
+            runtime_filters: std::mem::take(&mut *runtime_filters.lock()),
+        };
+        Ok((rows, report))
+    }
+
+    /// Returns the budget of a statement starting now.
+    fn statement_budget(&self) -> QueryBudget {
+        self.budget
+            .clone()
+            .unwrap_or_else(|| QueryBudget::new(self.query_limits))
+    }
+
+    /// Returns the cancellation token of a statement starting now.
+    fn statement_cancellation(&self) -> CancellationToken {
+        match self.statement_timeout {
+            Some(timeout) => self.cancellation.with_timeout(timeout),
+            None => self.cancellation.clone(),
+        }
+    }
+
+    /// Returns the table name.
+    pub fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    /// Returns the table ID.
+    pub fn table_id(&self) -> u32 {
+        self.info.table_id
+    }
+
+    /// Returns the table schema.
+    pub fn schema(&self) -> &Arc<Schema> {
+        &self.info.schema
+    }
+
+    /// Returns the number of live rows without scanning the table; see
+    /// `TableHeap::approx_row_count`.
+    pub fn approx_row_count(&self) -> u64 {
+        self.info.heap.approx_row_count()
+    }
+
+    /// Returns the space taken by the table's pages without scanning it.
+    pub fn approx_size_bytes(&self) -> u64 {
+        self.info.heap.approx_size_bytes()
+    }
+
+    /// Inserts a row and adds it to every index on the table.
+    pub fn insert(&self, values: Vec<Value>) -> Result<RecordId> {
+        self.authorize(Privilege::Insert)?;
+        if values.len() != self.info.schema.column_count() {
+            return Err(CrioError::SchemaMismatch);
+        }
+        self.insert_tuple(Tuple::new(Arc::clone(&self.info.schema), values))
+    }
+
+    /// Inserts a row given as `(column, value)` pairs. Omitted columns get
+    /// their default, or NULL if they are nullable and have none.
+    pub fn insert_columns(&self, columns: Vec<(&str, Value)>) -> Result<RecordId> {
+        self.authorize(Privilege::Insert)?;
+        let schema = &self.info.schema;
+        let mut values: Vec<Option<Value>> = vec![None; schema.column_count()];
+        for (name, value) in columns {
+            let index = schema
+                .column_index(name)
+                .ok_or_else(|| CrioError::UnknownColumn(name.to_string()))?;
+            values[index] = Some(value);
+        }
+
+        let values = schema
+            .columns()
+            .zip(values)
+            .map(|(column, value)| match value {
+                Some(value) => Ok(value),
+                None if column.default().is_some() || column.is_nullable() => {
+                    Ok(column.default_value())
+                }
+                None => Err(CrioError::MissingValue(column.name().to_string())),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.insert_tuple(Tuple::new(Arc::clone(schema), values))
+    }
+
+    fn insert_tuple(&self, tuple: Tuple) -> Result<RecordId> {
+        let data = tuple.to_bytes().ok_or(CrioError::SchemaMismatch)?;
+
+        let indexes = self.catalog.table_indexes(self.info.table_id);
+        let text_indexes = self.catalog.table_text_indexes(self.info.table_id);
+        let mut key_locks = Vec::new();
+        let (_lock, ()) =
+            self.lock_for_keys(write_mode(&indexes, &text_indexes), &mut key_locks, || {
+                Ok(((), row_keys(&indexes, tuple.values())))
+            })?;
+        self.insert_locked(&tuple, &data, &indexes, &text_indexes)
+    }
+
+    /// Locks the table in `mode` for one operation. If the handle's session
+    /// holds a lock on the table already, that lock is used instead and None
+    /// is returned, or, if it is shared and `mode` exclusive, the call fails
+    /// with `LockUpgrade` rather than wait for the session itself.
+    fn lock_table(&self, mode: LockMode) -> Result<Option<TableLock>> {
+        let held = self
+            .session_locks
+            .as_ref()
+            .and_then(|locks| locks.mode(&self.lock_manager, self.info.table_id));
+        match (held, mode) {
+            (None, mode) => Ok(Some(self.lock_manager.lock_table(self.info.table_id, mode))),
+            (Some(LockMode::Shared), LockMode::Exclusive) => {
+                Err(CrioError::LockUpgrade(self.info.table_id))
+            }
+            (Some(_), _) => Ok(None),
+        }
+    }
+
+    /// Takes the table lock in `mode` for a write, once `key_locks` holds an
+    /// exclusive lock on every index key range the write adds or removes.
+    ///
+    /// `plan` is called under the table lock and returns what the write will
+    /// do along with those ranges. If one is locked by someone else, the
+    /// table lock and every key lock are released while it is waited for, so
+    /// a range holder can finish and writers never wait on each other, and
+    /// `plan` is called again.
+    fn lock_for_keys<'a, T>(
+        &self,
+        mode: LockMode,
+        key_locks: &mut Vec<KeyRangeLock>,
+        mut plan: impl FnMut() -> Result<(T, Vec<(&'a str, RangeInclusive<u32>)>)>,
+    ) -> Result<(Option<TableLock>, T)> {
+        loop {
+            let lock = self.lock_table(mode)?;
+            let (planned, ranges) = plan()?;
+            let mut blocked = None;
+            for (index, range) in ranges {
+                if key_locks.iter().any(|held| held.covers(index, &range)) {
+                    continue;
+                }
+                match self.lock_manager.try_lock_key_range(
+                    index,
+                    range.clone(),
+                    LockMode::Exclusive,
+                ) {
+                    Some(held) => key_locks.push(held),
+                    None => {
+                        blocked = Some((index, range));
+                        break;
+                    }
+                }
+            }
+            let Some((index, range)) = blocked else {
+                return Ok((lock, planned));
+            };
+
+            drop(lock);
+            key_locks.clear();
+            key_locks.push(
+                self.lock_manager
+                    .lock_key_range(index, range, LockMode::Exclusive),
+            );
+        }
+    }
+
+    /// Does the work of `insert_tuple`; the caller holds the table lock.
+    fn insert_locked(
+        &self,
+        tuple: &Tuple,
+        data: &[u8],
+        indexes: &[Arc<IndexInfo>],
+        text_indexes: &[Arc<TextIndexInfo>],
+    ) -> Result<RecordId> {
+        let mut entries = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            if let Some(key) = index.key_of(tuple.values()) {
+                if index.index.search(key)?.is_some() {
+                    return Err(CrioError::DuplicateKey(key));
+                }
+                entries.push((index, key));
+            }
+        }
+
+        let record_id = self.info.heap.insert_tuple(data)?;
+        for (index, key) in entries {
+            self.catalog.insert_index_entry(index, key, record_id)?;
+        }
+        for index in text_indexes {
+            if let Some(text) = index.text_of(tuple.values()) {
+                index.index.insert(text, record_id)?;
+            }
+        }
+        self.note_row(tuple, record_id);
+        Ok(record_id)
+    }
+
+    /// Starts loading many rows at once on a thread of its own, and returns
+    /// their record IDs in input order once done. Rows are written with
+    /// `TableHeap::bulk_insert` and then added to every index on the table.
+    ///
+    /// The job counts the heap pages filled against an estimate made from
+    /// the rows' size. The table is locked exclusively for the whole load,
+    /// and every key is checked before anything is written, so a duplicate
+    /// key or a cancel leaves the table unchanged.
+    pub fn bulk_load(&self, rows: Vec<Vec<Value>>) -> JobHandle<Vec<RecordId>> {
+        let handle = self.clone();
+        JobHandle::spawn(move |progress| handle.bulk_load_with_progress(rows, progress))
+    }
+
+    fn bulk_load_with_progress(
+        &self,
+        rows: Vec<Vec<Value>>,
+        progress: &JobProgress,
+    ) -> Result<Vec<RecordId>> {
+        self.authorize(Privilege::Insert)?;
+        let mut tuples = Vec::with_capacity(rows.len());
+        let mut data = Vec::with_capacity(rows.len());
+        for values in rows {
+            if values.len() != self.info.schema.column_count() {
+                return Err(CrioError::SchemaMismatch);
+            }
+            let tuple = Tuple::new(Arc::clone(&self.info.schema), values);
+            data.push(tuple.to_bytes().ok_or(CrioError::SchemaMismatch)?);
+            tuples.push(tuple);
+        }
+        let bytes: usize = data.iter().map(Vec::len).sum();
+        let page_bytes = (PAGE_SIZE as f32 * DEFAULT_FILL_FACTOR) as usize;
+        progress.set_total_pages(bytes.div_ceil(page_bytes) as u64);
+
+        let indexes = self.catalog.table_indexes(self.info.table_id);
+        let text_indexes = self.catalog.table_text_indexes(self.info.table_id);
+        // Each index's keys are locked as the one range they span
+        let spans = indexes
+            .iter()
+            .filter_map(|index| {
+                let keys = tuples.iter().filter_map(|t| index.key_of(t.values()));
+                let start = keys.clone().min()?;
+                Some((index.name.as_str(), start..=keys.max()?))
+            })
+            .collect();
+        let mut key_locks = Vec::new();
+        let (_lock, ()) = self.lock_for_keys(LockMode::Exclusive, &mut key_locks, || {
+            Ok(((), Vec::clone(&spans)))
+        })?;
+        for index in &indexes {
+            let mut keys = HashSet::new();
+            for tuple in &tuples {
+                if let Some(key) = index.key_of(tuple.values()) {
+                    if !keys.insert(key) || index.index.search(key)?.is_some() {
+                        return Err(CrioError::DuplicateKey(key));
+                    }
+                }
+            }
+        }
+
+        let record_ids =
+            self.info
+                .heap
+                .bulk_insert_with_progress(&data, DEFAULT_FILL_FACTOR, progress)?;
+        for (tuple, &record_id) in tuples.iter().zip(&record_ids) {
+            for index in &indexes {
+                if let Some(key) = index.key_of(tuple.values()) {
+                    self.catalog.insert_index_entry(index, key, record_id)?;
+                }
+            }
+            for index in &text_indexes {
+                if let Some(text) = index.text_of(tuple.values()) {
+                    index.index.insert(text, record_id)?;
+                }
+            }
+            self.note_row(tuple, record_id);
+        }
+        Ok(record_ids)
+    }
+
+    /// Adds a row written at `record_id` to the table's bloom filters and
+    /// zone maps.
+    fn note_row(&self, tuple: &Tuple, record_id: RecordId) {
+        if let Some(bloom) = self.catalog.bloom_filter(self.info.table_id) {
+            bloom.insert(tuple.values(), record_id);
+        }
+        if let Some(zone_map) = self.catalog.zone_map(self.info.table_id) {
+            zone_map.insert(tuple.values(), record_id);
+        }
+    }
+
+    /// Inserts a row unless it conflicts with a live row on the unique index
+    /// `index_name`, in which case `on_conflict` decides what happens.
+    /// Returns the row as inserted or updated, or None if nothing was
+    /// written.
+    ///
+    /// The table is locked exclusively from the index probe to the write, so
+    /// concurrent upserts of the same key are serialized. A row whose NULL
+    /// key can't conflict is always inserted. An update may change indexed
+    /// columns as `update` does, failing with `DuplicateKey` if a new key
+    /// belongs to another row. The update function is called again if the
+    /// upsert has to wait for a key lock.
+    pub fn upsert(
+        &self,
+        index_name: &str,
+        values: Vec<Value>,
+        on_conflict: &OnConflict,
+    ) -> Result<Option<(RecordId, Tuple)>> {
+        self.authorize(Privilege::Insert)?;
+        if matches!(on_conflict, OnConflict::DoUpdate(_)) {
+            self.authorize(Privilege::Update)?;
+        }
+        let index = self.index(index_name)?;
+        if values.len() != self.info.schema.column_count() {
+            return Err(CrioError::SchemaMismatch);
+        }
+        let proposed = Tuple::new(Arc::clone(&self.info.schema), values);
+
+        let indexes = self.catalog.table_indexes(self.info.table_id);
+        let text_indexes = self.catalog.table_text_indexes(self.info.table_id);
+        let key = index.key_value(proposed.values());
+        let mut key_locks = Vec::new();
+        let (_lock, conflict) = self.lock_for_keys(LockMode::Exclusive, &mut key_locks, || {
+            let Some(record_id) = index.search(&key)? else {
+                return Ok((None, row_keys(&indexes, proposed.values())));
+            };
+            let OnConflict::DoUpdate(update) = on_conflict else {
+                return Ok((Some((record_id, None)), Vec::new()));
+            };
+            let existing = match self.read_tuple(record_id) {
+                Ok(tuple) => tuple,
+                Err(CrioError::TupleDeleted(_)) => {
+                    return Err(CrioError::DuplicateKey(index_key(&key).unwrap_or_default()))
+                }
+                Err(e) => return Err(e),
+            };
+
+            let values = update(&existing, &proposed);
+            if values.len() != self.info.schema.column_count() {
+                return Err(CrioError::SchemaMismatch);
+            }
+            let updated = Tuple::new(Arc::clone(&self.info.schema), values);
+            let mut keys = row_keys(&indexes, existing.values());
+            keys.extend(row_keys(&indexes, updated.values()));
+            Ok((Some((record_id, Some((existing, updated)))), keys))
+        })?;
+
+        match conflict {
+            None => {
+                let data = proposed.to_bytes().ok_or(CrioError::SchemaMismatch)?;
+                let record_id = self.insert_locked(&proposed, &data, &indexes, &text_indexes)?;
+                Ok(Some((record_id, proposed)))
+            }
+            Some((_, None)) => Ok(None),
+            Some((record_id, Some((existing, updated)))) => {
+                self.update_locked(record_id, &existing, &updated, &indexes, &text_indexes)?;
+                Ok(Some((record_id, updated)))
+            }
+        }
+    }
+
+    /// Replaces the row at `record_id` with `values`, keeping its record ID.
+    ///
+    /// Indexes whose key changed have the old key removed and the new one
+    /// added. Every new key is checked before anything is written, so an
+    /// update to a key held by another row fails with `DuplicateKey` and
+    /// leaves the table unchanged.
+    pub fn update(&self, record_id: RecordId, values: Vec<Value>) -> Result<()> {
+        self.authorize(Privilege::Update)?;
+        if values.len() != self.info.schema.column_count() {
+            return Err(CrioError::SchemaMismatch);
+        }
+        let updated = Tuple::new(Arc::clone(&self.info.schema), values);
+
+        let indexes = self.catalog.table_indexes(self.info.table_id);
+        let text_indexes = self.catalog.table_text_indexes(self.info.table_id);
+        let mut key_locks = Vec::new();
+        let (_lock, existing) =
+            self.lock_for_keys(write_mode(&indexes, &text_indexes), &mut key_locks, || {
+                let existing = self.read_tuple(record_id)?;
+                let mut keys = row_keys(&indexes, existing.values());
+                keys.extend(row_keys(&indexes, updated.values()));
+                Ok((existing, keys))
+            })?;
+        self.update_locked(record_id, &existing, &updated, &indexes, &text_indexes)
+    }
+
+    /// Does the work of `update`; the caller holds the table lock and has
+    /// read `existing` under it.
+    fn update_locked(
+        &self,
+        record_id: RecordId,
+        existing: &Tuple,
+        updated: &Tuple,
+        indexes: &[Arc<IndexInfo>],
+        text_indexes: &[Arc<TextIndexInfo>],
+    ) -> Result<()> {
+        let mut moves = Vec::new();
+        for index in indexes {
+            let old_key = index.key_of(existing.values());
+            let new_key = index.key_of(updated.values());
+            if old_key == new_key {
+                continue;
+            }
+            if let Some(key) = new_key {
+                if index.index.search(key)?.is_some() {
+                    return Err(CrioError::DuplicateKey(key));
+                }
+            }
+            moves.push((index, old_key, new_key));
+        }
+
+        let data = updated.to_bytes().ok_or(CrioError::SchemaMismatch)?;
+        self.info.heap.update_tuple(record_id, &data)?;
+        for (index, old_key, new_key) in moves {
+            if let Some(key) = old_key {
+                index.index.remove(key, record_id)?;
+            }
+            if let Some(key) = new_key {
+                self.catalog.insert_index_entry(index, key, record_id)?;
+            }
+        }
+        for index in text_indexes {
+            let text = index.text_of(updated.values());
+            if text != index.text_of(existing.values()) {
+                if let Some(text) = text {
+                    index.index.insert(text, record_id)?;
+                }
+            }
+        }
+        self.info.add_dead_tuples(1);
+        self.note_row(updated, record_id);
+        Ok(())
+    }
+
+    /// Returns the row at `record_id`.
+    pub fn get(&self, record_id: RecordId) -> Result<Tuple> {
+        self.authorize(Privilege::Select)?;
+        let _lock = self.lock_table(LockMode::Shared)?;
+        self.read_tuple(record_id)
+    }
+
+    /// Returns the rows at `record_ids`, in the same order, with None for
+    /// deleted ones. Rows sharing a page are read with one page fetch.
+    pub fn get_many(&self, record_ids: &[RecordId]) -> Result<Vec<Option<Tuple>>> {
+        self.authorize(Privilege::Select)?;
+        let _lock = self.lock_table(LockMode::Shared)?;
+        self.info
+            .heap
+            .get_tuples(record_ids)?
+            .into_iter()
+            .map(|data| {
+                data.map(|data| {
+                    Tuple::from_bytes(Arc::clone(&self.info.schema), &data)
+                        .ok_or(CrioError::SchemaMismatch)
+                })
+                .transpose()
+            })
+            .collect()
+    }
+
+    /// Deletes the row at `record_id` and its B+Tree index entries, counting
+    /// it towards the table's next vacuum.
+    pub fn delete(&self, record_id: RecordId) -> Result<()> {
+        self.authorize(Privilege::Delete)?;
+        let indexes = self.catalog.table_indexes(self.info.table_id);
+        let text_indexes = self.catalog.table_text_indexes(self.info.table_id);
+        let mut key_locks = Vec::new();
+        let (_lock, entries) =
+            self.lock_for_keys(write_mode(&indexes, &text_indexes), &mut key_locks, || {
+                if indexes.is_empty() {
+                    return Ok((Vec::new(), Vec::new()));
+                }
+                let existing = self.read_tuple(record_id)?;
+                let entries: Vec<_> = indexes
+                    .iter()
+                    .filter_map(|index| Some((index, index.key_of(existing.values())?)))
+                    .collect();
+                Ok((entries, row_keys(&indexes, existing.values())))
+            })?;
+        for (index, key) in entries {
+            index.index.remove(key, record_id)?;
+        }
+        self.info.heap.delete_tuple(record_id)?;
+        self.info.add_dead_tuples(1);
+        Ok(())
+    }
+
+    /// Returns every live row.
+    pub fn scan(&self) -> Result<Vec<(RecordId, Tuple)>> {
+        self.authorize(Privilege::Select)?;
+        let cancellation = self.statement_cancellation();
+        let budget = self.statement_budget();
+        let _lock = self.lock_table(LockMode::Shared)?;
+        let mut last_page = None;
+        self.info
+            .heap
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                if i % CANCEL_CHECK_ROWS == 0 {
+                    cancellation.check()?;
+                }
+                let (record_id, data) = row?;
+                if last_page.replace(record_id.page_id) != Some(record_id.page_id) {
+                    budget.charge_pages(1)?;
+                }
+                budget.charge_bytes(data.len() as u64)?;
+                let tuple = Tuple::from_bytes(Arc::clone(&self.info.schema), &data)
+                    .ok_or(CrioError::SchemaMismatch)?;
+                Ok((record_id, tuple))
+            })
+            .collect()
+    }
+
+    /// Returns every live row whose column `column_name` equals `value`. If
+    /// the table keeps bloom filters on that column, only the extents that
+    /// may hold `value` are read.
+    pub fn scan_eq(&self, column_name: &str, value: &Value) -> Result<Vec<(RecordId, Tuple)>> {
+        self.authorize(Privilege::Select)?;
+        let column = self
+            .info
+            .schema
+            .column_index(column_name)
+            .ok_or_else(|| CrioError::UnknownColumn(column_name.to_string()))?;
+        let bloom = self.catalog.bloom_filter(self.info.table_id);
+        let cancellation = self.statement_cancellation();
+
+        let _lock = self.lock_table(LockMode::Shared)?;
+        SeqScanExecutor::with_equality(
+            Arc::clone(&self.info),
+            bloom.as_deref(),
+            column,
+            value.clone(),
+        )?
+        .with_cancellation(cancellation)
+        .with_budget(self.statement_budget())
+        .collect()
+    }
+
+    /// Returns every live row whose column `column_name` lies in `range`. If
+    /// the table keeps zone maps on that column, only the pages whose range
+    /// overlaps `range` are read.
+    pub fn scan_range<R: RangeBounds<Value>>(
+        &self,
+        column_name: &str,
+        range: R,
+    ) -> Result<Vec<(RecordId, Tuple)>> {
+        self.authorize(Privilege::Select)?;
+        let column = self
+            .info
+            .schema
+            .column_index(column_name)
+            .ok_or_else(|| CrioError::UnknownColumn(column_name.to_string()))?;
+        let zone_map = self.catalog.zone_map(self.info.table_id);
+        let cancellation = self.statement_cancellation();
+
+        let _lock = self.lock_table(LockMode::Shared)?;
+        SeqScanExecutor::with_range(Arc::clone(&self.info), zone_map.as_deref(), column, range)?
+            .with_cancellation(cancellation)
+            .with_budget(self.statement_budget())
+            .collect()
+    }
+
+    /// Evaluates the subquery of `cache` for each row of this table,
+    /// correlated on the row's values of the columns `params`, and returns
+    /// the rows `kind` asks for (see `SubqueryExecutor`).
+    ///
+    /// The table is read, and its lock released, before the subquery first
+    /// runs, so the subquery may read any table through its own handles,
+    /// this one included. Outer rows are checked against this handle's
+    /// cancellation.
+    pub fn scan_subquery<F, I>(
+        &self,
+        params: &[&str],
+        kind: SubqueryKind,
+        cache: SubqueryCache<F>,
+    ) -> Result<Vec<(RecordId, Tuple)>>
+    where
+        F: FnMut(&[Value]) -> Result<I>,
+        I: IntoIterator<Item = Result<(RecordId, Tuple)>>,
+    {
+        let params = params
+            .iter()
+            .map(|name| {
+                self.info
+                    .schema
+                    .column_index(name)
+                    .ok_or_else(|| CrioError::UnknownColumn(name.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let cancellation = self.statement_cancellation();
+        let outer = self.scan()?;
+        SubqueryExecutor::new(outer.into_iter().map(Ok), &params, kind, cache)
+            .with_cancellation(cancellation)
+            .collect()
+    }
+
+    /// Returns the live rows `filter` holds for, in batches (see `Batch`),
+    /// with only the columns named in `columns` if given. The filter is
+    /// evaluated a batch at a time, for analytical scans over many rows.
+    pub fn scan_batches(
+        &self,
+        filter: Option<BatchPredicate>,
+        columns: Option<&[&str]>,
+    ) -> Result<Vec<Batch>> {
+        self.authorize(Privilege::Select)?;
+        let indices = columns
+            .map(|names| {
+                names
+                    .iter()
+                    .map(|name| {
+                        self.info
+                            .schema
+                            .column_index(name)
+                            .ok_or_else(|| CrioError::UnknownColumn(name.to_string()))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .transpose()?;
+        let cancellation = self.statement_cancellation();
+
+        let _lock = self.lock_table(LockMode::Shared)?;
+        let scan = SeqScanExecutor::new(Arc::clone(&self.info))?
+            .with_cancellation(cancellation)
+            .with_budget(self.statement_budget());
+        let mut batches: Box<dyn BatchExecutor> = Box::new(scan);
+        if let Some(filter) = filter {
+            batches = Box::new(batches.filter(filter));
+        }
+        if let Some(indices) = indices {
+            batches = Box::new(batches.project(indices));
+        }
+        std::iter::from_fn(|| batches.next_batch()).collect()
+    }
+
+    /// Joins the rows of this table with those of `build` whose column
+    /// `build_column` equals their column `column`, returning the rows
+    /// `join_type` asks for (see `HashJoinExecutor`). Both tables are read
+    /// with this handle's cancellation and budget.
+    ///
+    /// `build` is hashed first. If it has few enough distinct keys, the
+    /// join pushes a runtime filter of them into the scan of this table
+    /// (see `RuntimeFilterSlot`), so rows and zone-mapped pages that can't
+    /// match are skipped before reaching the join.
+    pub fn hash_join(
+        &self,
+        column: &str,
+        build: &TableHandle,
+        build_column: &str,
+        join_type: JoinType,
+    ) -> Result<Vec<(RecordId, Tuple)>> {
+        self.authorize(Privilege::Select)?;
+        build.authorize(Privilege::Select)?;
+        let column_index = |handle: &TableHandle, name: &str| {
+            handle
+                .info
+                .schema
+                .column_index(name)
+                .ok_or_else(|| CrioError::UnknownColumn(name.to_string()))
+        };
+        let probe_column = column_index(self, column)?;
+        let build_column = column_index(build, build_column)?;
+        let cancellation = self.statement_cancellation();
+        let budget = self.statement_budget();
+
+        // Table locks aren't reentrant, so a self-join locks once, and two
+        // tables of one database lock in table ID order
+        let same_db = Arc::ptr_eq(&self.lock_manager, &build.lock_manager);
+        let (first, second) = if same_db && build.info.table_id < self.info.table_id {
+            (build, self)
+        } else {
+            (self, build)
+        };
+        let _first_lock = first.lock_table(LockMode::Shared)?;
+        let _second_lock = if !same_db || second.info.table_id != first.info.table_id {
+            second.lock_table(LockMode::Shared)?
+        } else {
+            None
+        };
+
+        let slot = RuntimeFilterSlot::new();
+        let probe = SeqScanExecutor::new(Arc::clone(&self.info))?
+            .with_cancellation(cancellation.clone())
+            .with_budget(budget.clone())
+            .with_runtime_filter(
+                probe_column,
+                slot.clone(),
+                self.catalog.zone_map(self.info.table_id),
+            );
+        let build_scan = SeqScanExecutor::new(Arc::clone(&build.info))?
+            .with_cancellation(cancellation.clone())
+            .with_budget(budget.clone());
+        let rows = HashJoinExecutor::with_budget(
+            probe,
+            probe_column,
+            build_scan,
+            build_column,
+            join_type,
+            cancellation,
+            &budget,
+        )?
+        .with_runtime_filter(&slot)
+        .collect::<Result<Vec<_>>>()?;
+        if let Some(runtime_filters) = &self.runtime_filters {
+            runtime_filters.lock().push(slot.stats());
+        }
+        Ok(rows)
+    }
+
+    /// Rebuilds the table's bloom filters from its live rows, dropping the
+    /// keys of deleted and updated rows. Does nothing if the table has none.
+    pub fn rebuild_bloom_filter(&self) -> Result<()> {
+        let Some(bloom) = self.catalog.bloom_filter(self.info.table_id) else {
+            return Ok(());
+        };
+        let _lock = self.lock_table(LockMode::Shared)?;
+        bloom.rebuild(&self.info)
+    }
+
+    /// Rebuilds the table's zone maps from its live rows, shrinking ranges
+    /// widened by deleted and updated rows. Does nothing if the table has
+    /// none.
+    pub fn rebuild_zone_map(&self) -> Result<()> {
+        let Some(zone_map) = self.catalog.zone_map(self.info.table_id) else {
+            return Ok(());
+        };
+        let _lock = self.lock_table(LockMode::Shared)?;
+        zone_map.rebuild(&self.info)
+    }
+
+    /// Finds the live row whose indexed column equals `key`.
+    pub fn lookup(&self, index_name: &str, key: &Value) -> Result<Option<(RecordId, Tuple)>> {
+        self.authorize(Privilege::Select)?;
+        let index = self.index(index_name)?;
+
+        let _lock = self.lock_table(LockMode::Shared)?;
+        let Some(record_id) = index.search(key)? else {
+            return Ok(None);
+        };
+
+        match self.read_tuple(record_id) {
+            Ok(tuple) => Ok(Some((record_id, tuple))),
+            Err(CrioError::TupleDeleted(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the live rows whose key on the index `index_name` lies
+    /// between `start` and `end` inclusive, in key order. Hold a shared
+    /// `lock_key_range` over the same keys to see the same rows on every
+    /// scan.
+    pub fn scan_index(
+        &self,
+        index_name: &str,
+        start: &Value,
+        end: &Value,
+    ) -> Result<Vec<(RecordId, Tuple)>> {
+        self.authorize(Privilege::Select)?;
+        let index = self.index(index_name)?;
+        let (start, end) = (key_bound(start)?, key_bound(end)?);
+        let cancellation = self.statement_cancellation();
+
+        let _lock = self.lock_table(LockMode::Shared)?;
+        IndexScanExecutor::new(
+            Arc::clone(self.catalog.buffer_pool()),
+            Arc::clone(&self.info),
+            &index.index,
+            start,
+            end,
+        )?
+        .with_cancellation(cancellation)
+        .with_budget(self.statement_budget())
+        .collect()
+    }
+
+    /// Returns the live rows whose key on the index `index_name` meets
+    /// `predicate`, in key order. The predicate is turned into merged key
+    /// ranges (see `KeyPredicate::key_ranges`) read by one index scan, so
+    /// `IN` lists and `OR`s of ranges fetch each row once.
+    pub fn scan_index_where(
+        &self,
+        index_name: &str,
+        predicate: &KeyPredicate,
+    ) -> Result<Vec<(RecordId, Tuple)>> {
+        self.authorize(Privilege::Select)?;
+        let index = self.index(index_name)?;
+        let ranges = predicate.key_ranges()?;
+        let cancellation = self.statement_cancellation();
+
+        let _lock = self.lock_table(LockMode::Shared)?;
+        IndexScanExecutor::with_ranges(
+            Arc::clone(self.catalog.buffer_pool()),
+            Arc::clone(&self.info),
+            &index.index,
+            &ranges,
+        )?
+        .with_cancellation(cancellation)
+        .with_budget(self.statement_budget())
+        .collect()
+    }
+
+    /// Locks the keys between `start` and `end` inclusive of the index
+    /// `index_name` until the returned guard is dropped. A shared lock keeps
+    /// writers from inserting, deleting or updating rows whose key lies in
+    /// the range, so repeated scans of it return the same rows; an
+    /// exclusive one also keeps other lockers out.
+    ///
+    /// Key-range locks are taken before table locks. The holder must not
+    /// write keys in a range it holds, as the write would wait for the
+    /// holder's own lock. Fails with `PermissionDenied` as
+    /// `authorize_lock` does.
+    pub fn lock_key_range(
+        &self,
+        index_name: &str,
+        start: &Value,
+        end: &Value,
+        mode: LockMode,
+    ) -> Result<KeyRangeLock> {
+        self.authorize_lock(mode)?;
+        let index = self.index(index_name)?;
+        let range = key_bound(start)?..=key_bound(end)?;
+        Ok(self.lock_manager.lock_key_range(&index.name, range, mode))
+    }
+
+    /// Returns the live rows matching `query` on the full-text index
+    /// `index_name`, in posting order.
+    pub fn search_text(
+        &self,
+        index_name: &str,
+        query: TextQuery,
+    ) -> Result<Vec<(RecordId, Tuple)>> {
+        self.authorize(Privilege::Select)?;
+        let index = self.text_index(index_name)?;
+        let cancellation = self.statement_cancellation();
+
+        let _lock = self.lock_table(LockMode::Shared)?;
+        FullTextScanExecutor::new(Arc::clone(&self.info), index, query)?
+            .with_cancellation(cancellation)
+            .with_budget(self.statement_budget())
+            .collect()
+    }
+
+    /// Returns the full-text index named `index_name` if it is on this table.
+    fn text_index(&self, index_name: &str) -> Result<Arc<TextIndexInfo>> {
+        self.catalog
+            .text_index(index_name)
+            .filter(|index| index.table_id == self.info.table_id)
+            .ok_or_else(|| CrioError::UnknownIndex(index_name.to_string()))
+    }
+
+    /// Returns the index named `index_name` if it is on this table.
+    fn index(&self, index_name: &str) -> Result<Arc<IndexInfo>> {
+        self.catalog
+            .index(index_name)
+            .filter(|index| index.table_id == self.info.table_id)
+            .ok_or_else(|| CrioError::UnknownIndex(index_name.to_string()))
+    }
+
+    fn read_tuple(&self, record_id: RecordId) -> Result<Tuple> {
+        let data = self.info.heap.get_tuple(record_id)?;
+        Tuple::from_bytes(Arc::clone(&self.info.schema), &data).ok_or(CrioError::SchemaMismatch)
+    }
+}
+
+/// Returns the table lock mode of a single-row write: exclusive if the table
+/// has indexes to keep in step, shared otherwise.
+fn write_mode(indexes: &[Arc<IndexInfo>], text_indexes: &[Arc<TextIndexInfo>]) -> LockMode {
+    if indexes.is_empty() && text_indexes.is_empty() {
+        LockMode::Shared
+    } else {
+        LockMode::Exclusive
+    }
+}
+
+/// Returns the B+Tree keys of a row as key-range locks name them.
+fn row_keys<'a>(
+    indexes: &'a [Arc<IndexInfo>],
+    values: &[Value],
+) -> Vec<(&'a str, RangeInclusive<u32>)> {
+    indexes
+        .iter()
+        .filter_map(|index| {
+            let key = index.key_of(values)?;
+            Some((index.name.as_str(), key..=key))
+        })
+        .collect()
+}
+
+/// Maps a scan or lock bound to an index key.
+fn key_bound(value: &Value) -> Result<u32> {
+    index_key(value).ok_or(CrioError::SchemaMismatch)
+}
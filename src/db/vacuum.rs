@@ -0,0 +1,282 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
+use parking_lot::Mutex;
+
+use crate::buffer::BufferPoolManager;
+use crate::catalog::{Catalog, TableInfo};
+use crate::common::{
+    CrioError, JobProgress, Result, DEFAULT_AUTOVACUUM_INTERVAL_MS, DEFAULT_AUTOVACUUM_THRESHOLD,
+};
+use crate::concurrency::{LockManager, LockMode};
+
+/// When the autovacuum daemon runs and which tables it vacuums.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AutoVacuumPolicy {
+    /// Run the daemon at all
+    pub enabled: bool,
+    /// Rows deleted or updated since the last vacuum that make a table due
+    pub dead_tuple_threshold: u64,
+    /// Time between two checks of the tables' dead-row counts
+    pub interval: Duration,
+}
+
+impl AutoVacuumPolicy {
+    /// Returns the default policy with the daemon turned on.
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for AutoVacuumPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dead_tuple_threshold: DEFAULT_AUTOVACUUM_THRESHOLD,
+            interval: Duration::from_millis(DEFAULT_AUTOVACUUM_INTERVAL_MS),
+        }
+    }
+}
+
+/// What one vacuum of a table did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VacuumStats {
+    /// Heap pages examined
+    pub pages: usize,
+    /// Tombstoned tuples whose space was reclaimed
+    pub tuples_reclaimed: usize,
+    /// Forwarded tuples moved back into their home slots
+    pub forwards_collapsed: usize,
+}
+
+/// Reclaims the space of the dead rows of `table`.
+///
+/// Each page's tombstones are freed and the page compacted under a shared
+/// table lock, one page at a time, so readers and writers only wait for the
+/// page being vacuumed. Every page changed is flushed as `IoClass::Background`
+/// I/O, so a background I/O budget paces the vacuum. Forwarded tuples are then
+/// moved home where they fit, and the table's bloom filters and zone maps are
+/// rebuilt without the dead rows.
+///
+/// Each page is counted in `progress`, and a cancel is noticed before each
+/// page. A cancelled vacuum keeps the space it has reclaimed so far but
+/// leaves the table's dead-row count alone.
+pub(crate) fn vacuum_table(
+    table: &TableInfo,
+    catalog: &Catalog,
+    lock_manager: &Arc<LockManager>,
+    bpm: &BufferPoolManager,
+    progress: &JobProgress,
+) -> Result<VacuumStats> {
+    let dead_tuples = table.dead_tuples();
+    let mut stats = VacuumStats::default();
+
+    let page_ids = table.heap.page_ids()?;
+    progress.set_total_pages(page_ids.len() as u64);
+    for page_id in page_ids {
+        progress.checkpoint()?;
+        let reclaimed = {
+            let _lock = lock_manager.lock_table(table.table_id, LockMode::Shared);
+            table.heap.reclaim_page(page_id)?
+        };
+        stats.pages += 1;
+        if reclaimed > 0 {
+            stats.tuples_reclaimed += reclaimed;
+            bpm.flush_page(page_id)?;
+        }
+        progress.page_done();
+    }
+
+    let _lock = lock_manager.lock_table(table.table_id, LockMode::Shared);
+    stats.forwards_collapsed = table.heap.collapse_forwards()?;
+    if let Some(bloom) = catalog.bloom_filter(table.table_id) {
+        bloom.rebuild(table)?;
+    }
+    if let Some(zone_map) = catalog.zone_map(table.table_id) {
+        zone_map.rebuild(table)?;
+    }
+    table.remove_dead_tuples(dead_tuples);
+    Ok(stats)
+}
+
+/// Counts of what the daemon has done.
+#[derive(Default)]
+struct AutoVacuumCounters {
+    vacuums: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// Background thread that vacuums every table whose dead-row count has
+/// reached `AutoVacuumPolicy::dead_tuple_threshold`, checking every
+/// `interval`. Tables are vacuumed one at a time with `vacuum_table`, so the
+/// daemon's writes are paced by the background I/O budget.
+///
+/// A vacuum that fails is counted and retried at the next check. The thread
+/// stops when the daemon is stopped or dropped, cancelling a vacuum in
+/// progress at its next page.
+pub struct AutoVacuum {
+    policy: AutoVacuumPolicy,
+    counters: Arc<AutoVacuumCounters>,
+    /// Progress of the daemon's vacuums, cancelled to stop one in progress
+    progress: Arc<JobProgress>,
+    /// Dropped to wake the thread and stop it
+    stop_signal: Mutex<Option<Sender<()>>>,
+    worker_handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl AutoVacuum {
+    /// Starts the daemon over the tables of `catalog`.
+    pub(crate) fn start(
+        policy: AutoVacuumPolicy,
+        catalog: Arc<Catalog>,
+        lock_manager: Arc<LockManager>,
+        bpm: Arc<BufferPoolManager>,
+    ) -> Self {
+        let (stop_signal, stop_receiver) = bounded::<()>(0);
+        let counters = Arc::new(AutoVacuumCounters::default());
+        let worker_counters = Arc::clone(&counters);
+        let progress = Arc::new(JobProgress::new());
+        let worker_progress = Arc::clone(&progress);
+
+        let worker_handle = thread::spawn(move || loop {
+            match stop_receiver.recv_timeout(policy.interval) {
+                Err(RecvTimeoutError::Timeout) => Self::vacuum_due(
+                    &policy,
+                    &catalog,
+                    &lock_manager,
+                    &bpm,
+                    &worker_counters,
+                    &worker_progress,
+                ),
+                _ => return,
+            }
+        });
+
+        Self {
+            policy,
+            counters,
+            progress,
+            stop_signal: Mutex::new(Some(stop_signal)),
+            worker_handle: Mutex::new(Some(worker_handle)),
+        }
+    }
+
+    /// Vacuums the tables due for it.
+    fn vacuum_due(
+        policy: &AutoVacuumPolicy,
+        catalog: &Catalog,
+        lock_manager: &Arc<LockManager>,
+        bpm: &BufferPoolManager,
+        counters: &AutoVacuumCounters,
+        progress: &JobProgress,
+    ) {
+        for name in catalog.table_names() {
+            let Some(table) = catalog.table(&name) else {
+                continue;
+            };
+            if table.dead_tuples() < policy.dead_tuple_threshold {
+                continue;
+            }
+            let counter = match vacuum_table(&table, catalog, lock_manager, bpm, progress) {
+                Ok(_) => &counters.vacuums,
+                Err(CrioError::Cancelled) => return,
+                Err(_) => &counters.errors,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the policy the daemon runs with.
+    pub fn policy(&self) -> &AutoVacuumPolicy {
+        &self.policy
+    }
+
+    /// Returns the number of table vacuums the daemon has completed.
+    pub fn vacuum_count(&self) -> u64 {
+        self.counters.vacuums.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of table vacuums that failed.
+    pub fn error_count(&self) -> u64 {
+        self.counters.errors.load(Ordering::Relaxed)
+    }
+
+    /// Stops the daemon, cancelling a vacuum in progress and waiting for it
+    /// to notice. Does nothing if it is already stopped.
+    pub fn stop(&self) {
+        self.progress.cancel();
+        drop(self.stop_signal.lock().take());
+        if let Some(handle) = self.worker_handle.lock().take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for AutoVacuum {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::disk::MemDiskManager;
+    use crate::tuple::{DataType, Schema, Tuple, Value};
+
+    #[test]
+    fn test_vacuum_reclaims_deleted_rows() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            64,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ));
+        let catalog = Catalog::create(Arc::clone(&bpm)).unwrap();
+        let lock_manager = Arc::new(LockManager::new());
+        let schema = Schema::builder()
+            .column("id", DataType::Integer)
+            .column("pad", DataType::VarChar(200))
+            .build();
+        let table = catalog.create_table("t", schema).unwrap();
+        let record_ids: Vec<_> = (0..200)
+            .map(|i| {
+                let tuple = Tuple::new(
+                    Arc::clone(&table.schema),
+                    vec![Value::Integer(i), Value::String("x".repeat(100))],
+                );
+                table.heap.insert_tuple(&tuple.to_bytes().unwrap()).unwrap()
+            })
+            .collect();
+        for &record_id in record_ids.iter().step_by(2) {
+            table.heap.delete_tuple(record_id).unwrap();
+        }
+        table.add_dead_tuples(100);
+
+        let stats =
+            vacuum_table(&table, &catalog, &lock_manager, &bpm, &JobProgress::new()).unwrap();
+        assert_eq!(stats.tuples_reclaimed, 100);
+        assert_eq!(stats.pages, table.heap.page_ids().unwrap().len());
+        assert_eq!(table.dead_tuples(), 0);
+        assert_eq!(table.heap.iter().count(), 100);
+
+        // Nothing is left to reclaim
+        let progress = JobProgress::new();
+        let stats = vacuum_table(&table, &catalog, &lock_manager, &bpm, &progress).unwrap();
+        assert_eq!(stats.tuples_reclaimed, 0);
+        assert_eq!(progress.percent(), 100.0);
+
+        // A cancelled vacuum stops before the first page
+        progress.cancel();
+        assert!(matches!(
+            vacuum_table(&table, &catalog, &lock_manager, &bpm, &progress),
+            Err(CrioError::Cancelled)
+        ));
+    }
+}
@@ -0,0 +1,5 @@
+//! Debugging aids for inspecting raw pages.
+
+mod page_dump;
+
+pub use page_dump::*;
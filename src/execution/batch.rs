@@ -0,0 +1,810 @@
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::common::{CrioError, RecordId, Result};
+use crate::tuple::{Collation, Column, DataType, Schema, Tuple, Value};
+
+use super::{LikePattern, StringFunction};
+
+/// Rows a batch executor returns at once by default
+pub const DEFAULT_BATCH_SIZE: usize = 1024;
+
+/// Batch holds up to a batch size of rows column by column: one vector of
+/// values per column, plus the rows' record IDs.
+///
+/// Filters and projections work on whole columns of a batch at a time, so
+/// an analytical scan pays for one call per batch and operator rather than
+/// one per row.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    schema: Arc<Schema>,
+    record_ids: Vec<RecordId>,
+    columns: Vec<Vec<Value>>,
+}
+
+impl Batch {
+    /// Creates an empty batch of rows of `schema`.
+    pub fn new(schema: Arc<Schema>) -> Self {
+        let columns = vec![Vec::new(); schema.column_count()];
+        Self {
+            schema,
+            record_ids: Vec::new(),
+            columns,
+        }
+    }
+
+    /// Creates a batch of `rows`, which must all have `schema`.
+    pub fn from_rows<I>(schema: Arc<Schema>, rows: I) -> Self
+    where
+        I: IntoIterator<Item = (RecordId, Tuple)>,
+    {
+        let mut batch = Self::new(schema);
+        for (record_id, tuple) in rows {
+            batch.push(record_id, tuple);
+        }
+        batch
+    }
+
+    /// Appends a row.
+    ///
+    /// # Panics
+    /// Panics if the row doesn't have as many values as the schema has
+    /// columns.
+    pub fn push(&mut self, record_id: RecordId, tuple: Tuple) {
+        assert_eq!(
+            tuple.len(),
+            self.columns.len(),
+            "Value count must match schema column count"
+        );
+        self.record_ids.push(record_id);
+        for (column, value) in self.columns.iter_mut().zip(tuple.into_values()) {
+            column.push(value);
+        }
+    }
+
+    /// Returns the schema of the rows.
+    pub fn schema(&self) -> &Arc<Schema> {
+        &self.schema
+    }
+
+    /// Returns the number of rows.
+    pub fn len(&self) -> usize {
+        self.record_ids.len()
+    }
+
+    /// Returns true if the batch has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.record_ids.is_empty()
+    }
+
+    /// Returns the record IDs of the rows, in order.
+    pub fn record_ids(&self) -> &[RecordId] {
+        &self.record_ids
+    }
+
+    /// Returns the values of column `index`, one per row.
+    pub fn column(&self, index: usize) -> Option<&[Value]> {
+        self.columns.get(index).map(Vec::as_slice)
+    }
+
+    /// Keeps the rows whose entry in `mask` is true.
+    ///
+    /// # Panics
+    /// Panics if `mask` doesn't have one entry per row.
+    pub fn retain(&mut self, mask: &[bool]) {
+        assert_eq!(mask.len(), self.len(), "mask must have one entry per row");
+        if mask.iter().all(|keep| *keep) {
+            return;
+        }
+        retain_by(&mut self.record_ids, mask);
+        for column in &mut self.columns {
+            retain_by(column, mask);
+        }
+    }
+
+    /// Returns the batch with only the columns at `indices`, in that order,
+    /// or fails with `SchemaMismatch` if one is out of range.
+    pub fn project(mut self, indices: &[usize]) -> Result<Batch> {
+        let schema = self
+            .schema
+            .project(indices)
+            .ok_or(CrioError::SchemaMismatch)?;
+        Ok(self.project_into(Arc::new(schema), indices))
+    }
+
+    /// Moves the columns at `indices` into a batch of `schema`, cloning
+    /// only columns picked more than once.
+    fn project_into(&mut self, schema: Arc<Schema>, indices: &[usize]) -> Batch {
+        let mut columns = Vec::with_capacity(indices.len());
+        for (i, &index) in indices.iter().enumerate() {
+            if indices[i + 1..].contains(&index) {
+                columns.push(self.columns[index].clone());
+            } else {
+                columns.push(std::mem::take(&mut self.columns[index]));
+            }
+        }
+        Batch {
+            schema,
+            record_ids: std::mem::take(&mut self.record_ids),
+            columns,
+        }
+    }
+
+    /// Returns the batch with `columns` appended, in a batch of `schema`.
+    fn append(mut self, schema: Arc<Schema>, columns: Vec<Vec<Value>>) -> Batch {
+        self.schema = schema;
+        self.columns.extend(columns);
+        self
+    }
+
+    /// Returns the rows of the batch as tuples, in order.
+    pub fn into_rows(self) -> impl Iterator<Item = (RecordId, Tuple)> {
+        let schema = self.schema;
+        let mut columns: Vec<_> = self.columns.into_iter().map(Vec::into_iter).collect();
+        self.record_ids.into_iter().map(move |record_id| {
+            let values = columns
+                .iter_mut()
+                .map(|column| column.next().expect("a value per row"))
+                .collect();
+            (record_id, Tuple::new(Arc::clone(&schema), values))
+        })
+    }
+}
+
+/// Keeps the items of `items` whose entry in `mask` is true.
+fn retain_by<T>(items: &mut Vec<T>, mask: &[bool]) {
+    let mut keep = mask.iter();
+    items.retain(|_| *keep.next().expect("a mask entry per item"));
+}
+
+/// BatchExecutor is the batch-at-a-time side of the executor interface:
+/// executors that implement it return rows a batch at a time as well as
+/// one at a time through `Iterator`.
+///
+/// Any row executor can be read in batches with `Batched`, and batches
+/// turned back into rows with `rows`, so batch operators slot in between
+/// row executors.
+pub trait BatchExecutor {
+    /// Returns the next batch of rows, or None at the end. Batches are
+    /// never empty, and nothing is returned after an error.
+    fn next_batch(&mut self) -> Option<Result<Batch>>;
+
+    /// Keeps the rows of each batch that `predicate` holds for.
+    fn filter(self, predicate: BatchPredicate) -> FilterBatches<Self>
+    where
+        Self: Sized,
+    {
+        FilterBatches {
+            input: self,
+            predicate,
+        }
+    }
+
+    /// Keeps the columns at `indices` of each batch, in that order.
+    fn project(self, indices: Vec<usize>) -> ProjectBatches<Self>
+    where
+        Self: Sized,
+    {
+        ProjectBatches {
+            input: self,
+            indices,
+            schema: None,
+        }
+    }
+
+    /// Appends to each batch a column per expression of `columns`, named
+    /// as paired with it.
+    fn compute(self, columns: Vec<(String, BatchExpression)>) -> ComputeBatches<Self>
+    where
+        Self: Sized,
+    {
+        ComputeBatches {
+            input: self,
+            columns,
+            schema: None,
+        }
+    }
+
+    /// Returns the rows of the batches one at a time.
+    fn rows(self) -> BatchRows<Self>
+    where
+        Self: Sized,
+    {
+        BatchRows {
+            input: self,
+            rows: None,
+        }
+    }
+}
+
+impl<B: BatchExecutor + ?Sized> BatchExecutor for Box<B> {
+    fn next_batch(&mut self) -> Option<Result<Batch>> {
+        (**self).next_batch()
+    }
+}
+
+/// Comparison of a `BatchPredicate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+impl CompareOp {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::NotEq => ordering != Ordering::Equal,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::LtEq => ordering != Ordering::Greater,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::GtEq => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// A `WHERE` condition evaluated a column at a time over a `Batch`.
+///
+/// Conditions follow SQL's three-valued logic: a comparison with NULL is
+/// unknown, `NOT` of unknown is unknown, and only rows for which the whole
+/// condition is true are kept. Values are compared with their column's
+/// collation; values that don't compare (different types) are unknown too.
+/// `LIKE` also follows the collation, so it ignores case on a
+/// case-insensitive column, and `ILIKE` always does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchPredicate {
+    /// `column op value`
+    Compare(usize, CompareOp, Value),
+    /// `column BETWEEN low AND high`, both ends included
+    Between(usize, Value, Value),
+    /// `column IN (values)`
+    In(usize, Vec<Value>),
+    /// `column IS NULL`
+    IsNull(usize),
+    /// `column IS NOT NULL`
+    IsNotNull(usize),
+    /// `column LIKE pattern` (see `LikePattern`)
+    Like(usize, String),
+    /// `column ILIKE pattern`, ignoring case
+    ILike(usize, String),
+    /// All of the conditions
+    And(Vec<BatchPredicate>),
+    /// Any of the conditions
+    Or(Vec<BatchPredicate>),
+    /// The opposite of the condition
+    Not(Box<BatchPredicate>),
+}
+
+impl BatchPredicate {
+    /// Returns which rows of `batch` the predicate holds for. Fails with
+    /// `SchemaMismatch` if it names a column the batch doesn't have.
+    pub fn evaluate(&self, batch: &Batch) -> Result<Vec<bool>> {
+        Ok(self
+            .truth(batch)?
+            .into_iter()
+            .map(|t| t == Some(true))
+            .collect())
+    }
+
+    /// Returns the truth of the predicate for each row: None if unknown.
+    fn truth(&self, batch: &Batch) -> Result<Vec<Option<bool>>> {
+        let column = |index: usize| {
+            let values = batch.column(index).ok_or(CrioError::SchemaMismatch)?;
+            let column = batch
+                .schema
+                .column(index)
+                .ok_or(CrioError::SchemaMismatch)?;
+            let compare = move |a: &Value, b: &Value| match (a, b) {
+                (Value::Null, _) | (_, Value::Null) => None,
+                (a, b) => column.compare(a, b),
+            };
+            Ok::<_, CrioError>((values, compare))
+        };
+        Ok(match self {
+            BatchPredicate::Compare(index, op, value) => {
+                let (values, compare) = column(*index)?;
+                values
+                    .iter()
+                    .map(|v| compare(v, value).map(|o| op.holds(o)))
+                    .collect()
+            }
+            BatchPredicate::Between(index, low, high) => {
+                let (values, compare) = column(*index)?;
+                values
+                    .iter()
+                    .map(|v| {
+                        let above = compare(v, low).map(|o| o != Ordering::Less);
+                        let below = compare(v, high).map(|o| o != Ordering::Greater);
+                        and(above, below)
+                    })
+                    .collect()
+            }
+            BatchPredicate::In(index, list) => {
+                let (values, compare) = column(*index)?;
+                values
+                    .iter()
+                    .map(|v| {
+                        list.iter()
+                            .map(|item| compare(v, item).map(|o| o == Ordering::Equal))
+                            .fold(Some(false), or)
+                    })
+                    .collect()
+            }
+            BatchPredicate::IsNull(index) => {
+                let (values, _) = column(*index)?;
+                values.iter().map(|v| Some(v.is_null())).collect()
+            }
+            BatchPredicate::IsNotNull(index) => {
+                let (values, _) = column(*index)?;
+                values.iter().map(|v| Some(!v.is_null())).collect()
+            }
+            BatchPredicate::Like(index, pattern) | BatchPredicate::ILike(index, pattern) => {
+                let (values, _) = column(*index)?;
+                let fold = matches!(self, BatchPredicate::ILike(..))
+                    || batch.schema.column(*index).map(Column::collation)
+                        == Some(Collation::CaseInsensitive);
+                let pattern = if fold {
+                    LikePattern::new(&pattern.to_lowercase())
+                } else {
+                    LikePattern::new(pattern)
+                };
+                values
+                    .iter()
+                    .map(|v| match v {
+                        Value::String(s) if fold => Some(pattern.matches(&s.to_lowercase())),
+                        Value::String(s) => Some(pattern.matches(s)),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            BatchPredicate::And(predicates) => {
+                let mut truth = vec![Some(true); batch.len()];
+                for predicate in predicates {
+                    let other = predicate.truth(batch)?;
+                    for (t, o) in truth.iter_mut().zip(other) {
+                        *t = and(*t, o);
+                    }
+                }
+                truth
+            }
+            BatchPredicate::Or(predicates) => {
+                let mut truth = vec![Some(false); batch.len()];
+                for predicate in predicates {
+                    let other = predicate.truth(batch)?;
+                    for (t, o) in truth.iter_mut().zip(other) {
+                        *t = or(*t, o);
+                    }
+                }
+                truth
+            }
+            BatchPredicate::Not(predicate) => predicate
+                .truth(batch)?
+                .into_iter()
+                .map(|t| t.map(|t| !t))
+                .collect(),
+        })
+    }
+}
+
+/// A value computed for each row of a `Batch`, a column at a time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchExpression {
+    /// The value of a column
+    Column(usize),
+    /// The same value for every row
+    Literal(Value),
+    /// A string function of the values of other expressions
+    Function(StringFunction, Vec<BatchExpression>),
+}
+
+impl BatchExpression {
+    /// Returns the expression's value for each row of `batch`. Fails with
+    /// `SchemaMismatch` if it names a column the batch doesn't have.
+    pub fn evaluate(&self, batch: &Batch) -> Result<Vec<Value>> {
+        match self {
+            BatchExpression::Column(index) => batch
+                .column(*index)
+                .map(<[Value]>::to_vec)
+                .ok_or(CrioError::SchemaMismatch),
+            BatchExpression::Literal(value) => Ok(vec![value.clone(); batch.len()]),
+            BatchExpression::Function(function, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(batch))
+                    .collect::<Result<Vec<_>>>()?;
+                let mut row = Vec::with_capacity(args.len());
+                (0..batch.len())
+                    .map(|i| {
+                        row.clear();
+                        row.extend(args.iter().map(|arg| arg[i].clone()));
+                        function.apply(&row)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns the column named `name` the expression's values are
+    /// returned in for batches of `schema`.
+    fn output_column(&self, name: &str, schema: &Schema) -> Result<Column> {
+        Ok(match self {
+            BatchExpression::Column(index) => {
+                let column = schema.column(*index).ok_or(CrioError::SchemaMismatch)?;
+                Column::new(name, column.data_type().clone(), column.is_nullable())
+                    .with_collation(column.collation())
+            }
+            BatchExpression::Literal(value) => {
+                let data_type = value.infer_type().unwrap_or(DataType::Integer);
+                Column::new(name, data_type, value.is_null())
+            }
+            BatchExpression::Function(function, _) => Column::new(name, function.data_type(), true),
+        })
+    }
+}
+
+/// Three-valued AND: false beats unknown, which beats true.
+fn and(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+/// Three-valued OR: true beats unknown, which beats false.
+fn or(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    }
+}
+
+/// Batched reads a row executor a batch at a time.
+pub struct Batched<I> {
+    input: I,
+    batch_size: usize,
+    /// Whether the input has ended or failed
+    done: bool,
+}
+
+impl<I> Batched<I>
+where
+    I: Iterator<Item = Result<(RecordId, Tuple)>>,
+{
+    /// Reads `input` in batches of up to `batch_size` rows, at least one.
+    pub fn new(input: I, batch_size: usize) -> Self {
+        Self {
+            input,
+            batch_size: batch_size.max(1),
+            done: false,
+        }
+    }
+}
+
+impl<I> BatchExecutor for Batched<I>
+where
+    I: Iterator<Item = Result<(RecordId, Tuple)>>,
+{
+    fn next_batch(&mut self) -> Option<Result<Batch>> {
+        if self.done {
+            return None;
+        }
+        let mut batch: Option<Batch> = None;
+        while batch.as_ref().map_or(0, Batch::len) < self.batch_size {
+            match self.input.next() {
+                Some(Ok((record_id, tuple))) => batch
+                    .get_or_insert_with(|| Batch::new(Arc::clone(tuple.schema())))
+                    .push(record_id, tuple),
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                None => {
+                    self.done = true;
+                    break;
+                }
+            }
+        }
+        batch.map(Ok)
+    }
+}
+
+/// Batches of an input with only the rows a `BatchPredicate` holds for.
+/// Batches left empty are skipped.
+pub struct FilterBatches<B> {
+    input: B,
+    predicate: BatchPredicate,
+}
+
+impl<B: BatchExecutor> BatchExecutor for FilterBatches<B> {
+    fn next_batch(&mut self) -> Option<Result<Batch>> {
+        loop {
+            let mut batch = match self.input.next_batch()? {
+                Ok(batch) => batch,
+                Err(e) => return Some(Err(e)),
+            };
+            match self.predicate.evaluate(&batch) {
+                Ok(mask) => batch.retain(&mask),
+                Err(e) => return Some(Err(e)),
+            }
+            if !batch.is_empty() {
+                return Some(Ok(batch));
+            }
+        }
+    }
+}
+
+/// Batches of an input with only some of its columns.
+pub struct ProjectBatches<B> {
+    input: B,
+    indices: Vec<usize>,
+    /// Schema of projected batches, once the first one is made
+    schema: Option<Arc<Schema>>,
+}
+
+impl<B: BatchExecutor> BatchExecutor for ProjectBatches<B> {
+    fn next_batch(&mut self) -> Option<Result<Batch>> {
+        let mut batch = match self.input.next_batch()? {
+            Ok(batch) => batch,
+            Err(e) => return Some(Err(e)),
+        };
+        if self.schema.is_none() {
+            match batch.schema.project(&self.indices) {
+                Some(schema) => self.schema = Some(Arc::new(schema)),
+                None => return Some(Err(CrioError::SchemaMismatch)),
+            }
+        }
+        let schema = Arc::clone(self.schema.as_ref().expect("schema just made"));
+        Some(Ok(batch.project_into(schema, &self.indices)))
+    }
+}
+
+/// Batches of an input with computed columns appended.
+pub struct ComputeBatches<B> {
+    input: B,
+    columns: Vec<(String, BatchExpression)>,
+    /// Schema of the output batches, once the first one is made
+    schema: Option<Arc<Schema>>,
+}
+
+impl<B: BatchExecutor> ComputeBatches<B> {
+    fn compute(&mut self, batch: Batch) -> Result<Batch> {
+        if self.schema.is_none() {
+            let mut columns: Vec<_> = batch.schema.columns().cloned().collect();
+            for (name, expression) in &self.columns {
+                columns.push(expression.output_column(name, &batch.schema)?);
+            }
+            self.schema = Some(Arc::new(Schema::new(columns)));
+        }
+        let computed = self
+            .columns
+            .iter()
+            .map(|(_, expression)| expression.evaluate(&batch))
+            .collect::<Result<Vec<_>>>()?;
+        let schema = Arc::clone(self.schema.as_ref().expect("schema just made"));
+        Ok(batch.append(schema, computed))
+    }
+}
+
+impl<B: BatchExecutor> BatchExecutor for ComputeBatches<B> {
+    fn next_batch(&mut self) -> Option<Result<Batch>> {
+        let batch = match self.input.next_batch()? {
+            Ok(batch) => batch,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(self.compute(batch))
+    }
+}
+
+/// The rows of a batch executor, one at a time.
+pub struct BatchRows<B> {
+    input: B,
+    /// Rows of the current batch not returned yet
+    rows: Option<Box<dyn Iterator<Item = (RecordId, Tuple)>>>,
+}
+
+impl<B: BatchExecutor> Iterator for BatchRows<B> {
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.rows.as_mut().and_then(Iterator::next) {
+                return Some(Ok(row));
+            }
+            match self.input.next_batch()? {
+                Ok(batch) => self.rows = Some(Box::new(batch.into_rows())),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::test_util::int_string_rows as rows;
+
+    fn slots(batch: &Batch) -> Vec<u16> {
+        batch
+            .record_ids()
+            .iter()
+            .map(|rid| rid.slot_id.as_u16())
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_predicates() {
+        let input = rows(&[(Some(1), "a"), (None, "b"), (Some(5), "c"), (Some(9), "d")]);
+        let batch = Batched::new(input.into_iter(), 10)
+            .next_batch()
+            .unwrap()
+            .unwrap();
+        assert_eq!(batch.len(), 4);
+        let matching = |predicate: BatchPredicate| -> Vec<usize> {
+            let mask = predicate.evaluate(&batch).unwrap();
+            (0..mask.len()).filter(|&i| mask[i]).collect()
+        };
+
+        let n = |op, v| BatchPredicate::Compare(0, op, Value::BigInt(v));
+        assert_eq!(matching(n(CompareOp::GtEq, 5)), vec![2, 3]);
+        assert_eq!(matching(n(CompareOp::NotEq, 5)), vec![0, 3]);
+        assert_eq!(
+            matching(BatchPredicate::Between(
+                0,
+                Value::Integer(2),
+                Value::Integer(9)
+            )),
+            vec![2, 3]
+        );
+        assert_eq!(
+            matching(BatchPredicate::In(
+                0,
+                vec![Value::Integer(9), Value::Integer(1)]
+            )),
+            vec![0, 3]
+        );
+        assert_eq!(matching(BatchPredicate::IsNull(0)), vec![1]);
+
+        // NOT of an unknown comparison stays unknown; OR with a true one
+        // is true
+        let not_small = BatchPredicate::Not(Box::new(n(CompareOp::Lt, 5)));
+        assert_eq!(matching(not_small.clone()), vec![2, 3]);
+        let either = BatchPredicate::Or(vec![
+            not_small,
+            BatchPredicate::Compare(1, CompareOp::Eq, Value::String("b".into())),
+        ]);
+        assert_eq!(matching(either), vec![1, 2, 3]);
+        // x NOT IN (1, NULL) is never true
+        let not_in = BatchPredicate::Not(Box::new(BatchPredicate::In(
+            0,
+            vec![Value::Integer(1), Value::Null],
+        )));
+        assert!(matching(not_in).is_empty());
+        assert_eq!(
+            matching(BatchPredicate::And(vec![
+                BatchPredicate::IsNotNull(0),
+                n(CompareOp::Lt, 9)
+            ])),
+            vec![0, 2]
+        );
+
+        assert!(matches!(
+            BatchPredicate::IsNull(7).evaluate(&batch),
+            Err(CrioError::SchemaMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_batch_string_functions() {
+        let input = rows(&[(Some(1), " Ann "), (Some(2), "bob"), (None, "ANNA")]);
+        let s = |s: &str| Value::String(s.into());
+        let trimmed =
+            BatchExpression::Function(StringFunction::Trim, vec![BatchExpression::Column(1)]);
+        let mut batches = Batched::new(input.into_iter(), 10)
+            .filter(BatchPredicate::ILike(1, "%an%".into()))
+            .compute(vec![
+                (
+                    "upper".into(),
+                    BatchExpression::Function(StringFunction::Upper, vec![trimmed.clone()]),
+                ),
+                (
+                    "label".into(),
+                    BatchExpression::Function(
+                        StringFunction::Concat,
+                        vec![
+                            BatchExpression::Function(
+                                StringFunction::Substr,
+                                vec![
+                                    trimmed,
+                                    BatchExpression::Literal(Value::Integer(1)),
+                                    BatchExpression::Literal(Value::Integer(2)),
+                                ],
+                            ),
+                            BatchExpression::Literal(s("-")),
+                            BatchExpression::Column(0),
+                        ],
+                    ),
+                ),
+                (
+                    "length".into(),
+                    BatchExpression::Function(
+                        StringFunction::Length,
+                        vec![BatchExpression::Column(1)],
+                    ),
+                ),
+            ]);
+
+        let batch = batches.next_batch().unwrap().unwrap();
+        assert_eq!(slots(&batch), vec![0, 2]);
+        assert_eq!(batch.schema().column(2).unwrap().name(), "upper");
+        assert_eq!(batch.column(2).unwrap(), &[s("ANN"), s("ANNA")]);
+        assert_eq!(batch.column(3).unwrap(), &[s("An-1"), s("AN-")]);
+        assert_eq!(
+            batch.column(4).unwrap(),
+            &[Value::Integer(5), Value::Integer(4)]
+        );
+        assert!(batches.next_batch().is_none());
+
+        // LIKE is case-sensitive on a binary column and unknown for NULLs
+        let batch = Batched::new(rows(&[(Some(1), "Ann"), (Some(2), "ann")]).into_iter(), 10)
+            .next_batch()
+            .unwrap()
+            .unwrap();
+        let like = BatchPredicate::Like(1, "A_n".into());
+        assert_eq!(like.evaluate(&batch).unwrap(), vec![true, false]);
+        let not_like = BatchPredicate::Not(Box::new(BatchPredicate::Like(0, "1".into())));
+        assert_eq!(not_like.evaluate(&batch).unwrap(), vec![false, false]);
+
+        let bad =
+            BatchExpression::Function(StringFunction::Upper, vec![BatchExpression::Column(0)]);
+        assert!(matches!(
+            bad.evaluate(&batch),
+            Err(CrioError::InvalidArguments("UPPER"))
+        ));
+    }
+
+    #[test]
+    fn test_batch_pipeline() {
+        let values: Vec<_> = (0..25).map(|i| (Some(i), "x")).collect();
+        let mut batches = Batched::new(rows(&values).into_iter(), 10)
+            .filter(BatchPredicate::Compare(
+                0,
+                CompareOp::Lt,
+                Value::Integer(12),
+            ))
+            .project(vec![1, 0, 0]);
+
+        let first = batches.next_batch().unwrap().unwrap();
+        assert_eq!(first.len(), 10);
+        assert_eq!(first.schema().column_count(), 3);
+        assert_eq!(first.column(1), first.column(2));
+        let second = batches.next_batch().unwrap().unwrap();
+        assert_eq!(slots(&second), vec![10, 11]);
+        // The third input batch has no matching rows
+        assert!(batches.next_batch().is_none());
+
+        let out: Vec<_> = Batched::new(rows(&values).into_iter(), 4)
+            .filter(BatchPredicate::Compare(
+                0,
+                CompareOp::Gt,
+                Value::Integer(20),
+            ))
+            .rows()
+            .map(|row| row.unwrap())
+            .collect();
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[0].0.slot_id.as_u16(), 21);
+        assert_eq!(out[0].1.value(0), Some(&Value::Integer(21)));
+
+        let mut bad = Batched::new(rows(&values).into_iter(), 4).project(vec![5]);
+        assert!(matches!(
+            bad.next_batch(),
+            Some(Err(CrioError::SchemaMismatch))
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use crate::common::{RecordId, Result};
+use crate::storage::disk::TempFileManager;
+use crate::storage::table::{TempTable, TempTableScan};
+use crate::tuple::{Schema, Tuple};
+
+/// Cte is a common table expression (a `WITH` query) shared by the parts of
+/// a statement that read it.
+///
+/// A CTE read at most once is inlined: each scan runs its query afresh. One
+/// read more often is materialized into a `TempTable` by the first scan,
+/// and every scan after that reads the temp table instead of running the
+/// query again.
+pub struct Cte<F> {
+    name: String,
+    schema: Schema,
+    /// Starts the CTE's query
+    run: F,
+    /// Number of times the statement reads the CTE
+    references: usize,
+    /// Scratch file a materialized CTE is stored in
+    temp: Arc<TempFileManager>,
+    /// Rows of a materialized CTE, once the first scan ran its query
+    table: Option<TempTable>,
+}
+
+impl<F, I> Cte<F>
+where
+    F: FnMut() -> Result<I>,
+    I: Iterator<Item = Result<(RecordId, Tuple)>>,
+{
+    /// Creates the CTE `name` with rows of `schema` from `run`, which the
+    /// statement reads `references` times.
+    pub fn new(
+        name: impl Into<String>,
+        schema: Schema,
+        references: usize,
+        temp: Arc<TempFileManager>,
+        run: F,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+            run,
+            references,
+            temp,
+            table: None,
+        }
+    }
+
+    /// Returns the CTE name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns true if the CTE is stored in a temp table rather than inlined.
+    pub fn is_materialized(&self) -> bool {
+        self.references > 1
+    }
+
+    /// Returns a scan of the CTE's rows, running its query if it is inlined
+    /// or not materialized yet.
+    pub fn scan(&mut self) -> Result<CteScan<'_, I>> {
+        if !self.is_materialized() {
+            return Ok(CteScan::Inline((self.run)()?));
+        }
+        if self.table.is_none() {
+            let mut table = TempTable::new(
+                self.name.clone(),
+                self.schema.clone(),
+                Arc::clone(&self.temp),
+            );
+            table.insert_all((self.run)()?)?;
+            self.table = Some(table);
+        }
+        let table = self.table.as_ref().expect("CTE just materialized");
+        Ok(CteScan::Materialized(table.scan()))
+    }
+}
+
+/// Scan over the rows of a `Cte`.
+pub enum CteScan<'a, I> {
+    /// Rows straight from the CTE's query
+    Inline(I),
+    /// Rows read back from the CTE's temp table
+    Materialized(TempTableScan<'a>),
+}
+
+impl<I> Iterator for CteScan<'_, I>
+where
+    I: Iterator<Item = Result<(RecordId, Tuple)>>,
+{
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            CteScan::Inline(rows) => rows.next(),
+            CteScan::Materialized(rows) => rows.next(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{PageId, SlotId};
+    use crate::tuple::{DataType, Value};
+
+    #[test]
+    fn test_cte_materialization() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = Arc::new(TempFileManager::new(dir.path().join("cte.tmp"), 16).unwrap());
+        let schema = Schema::builder().column("n", DataType::Integer).build();
+        let rows = {
+            let schema = Arc::new(schema.clone());
+            move || -> Vec<Result<(RecordId, Tuple)>> {
+                (0..10)
+                    .map(|i| {
+                        let record_id = RecordId::new(PageId::new(1), SlotId::new(i as u16));
+                        let tuple = Tuple::new(Arc::clone(&schema), vec![Value::Integer(i)]);
+                        Ok((record_id, tuple))
+                    })
+                    .collect()
+            }
+        };
+
+        for references in [1, 3] {
+            let mut runs = 0;
+            let mut cte = Cte::new(
+                "nums",
+                schema.clone(),
+                references,
+                Arc::clone(&temp),
+                || {
+                    runs += 1;
+                    Ok(rows().into_iter())
+                },
+            );
+            assert_eq!(cte.name(), "nums");
+            for _ in 0..references {
+                let values: Vec<_> = cte
+                    .scan()
+                    .unwrap()
+                    .map(|row| row.unwrap().1.value(0).cloned().unwrap())
+                    .collect();
+                assert_eq!(values, (0..10).map(Value::Integer).collect::<Vec<_>>());
+            }
+            assert_eq!(cte.is_materialized(), references > 1);
+            assert_eq!(temp.allocated_pages(), u32::from(references > 1));
+            drop(cte);
+            // Materialized or not, the query ran once for all the scans
+            assert_eq!(runs, 1);
+        }
+        assert_eq!(temp.allocated_pages(), 0);
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::common::ResourceUsage;
+
+use super::RuntimeFilterStats;
+
+/// ExplainAnalyze reports what running a statement took, like the output of
+/// `EXPLAIN ANALYZE`: the rows it returned, how long it ran, what it used
+/// of its `QueryBudget` and what the runtime filters of its joins did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplainAnalyze {
+    /// Rows returned
+    pub rows: usize,
+    /// Wall-clock time the statement ran
+    pub elapsed: Duration,
+    /// Pages, bytes, scratch space and executor memory used
+    pub usage: ResourceUsage,
+    /// Runtime filters of the statement's hash joins, in join order
+    pub runtime_filters: Vec<RuntimeFilterStats>,
+}
+
+impl fmt::Display for ExplainAnalyze {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rows={} time={:.3}ms {}",
+            self.rows,
+            self.elapsed.as_secs_f64() * 1000.0,
+            self.usage
+        )?;
+        for filter in &self.runtime_filters {
+            write!(f, " {}", filter)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_analyze_display() {
+        let report = ExplainAnalyze {
+            rows: 3,
+            elapsed: Duration::from_micros(1500),
+            usage: ResourceUsage {
+                pages_read: 2,
+                bytes_allocated: 96,
+                temp_pages: 0,
+                peak_temp_pages: 1,
+                memory_bytes: 0,
+                peak_memory_bytes: 512,
+            },
+            runtime_filters: Vec::new(),
+        };
+        assert_eq!(
+            report.to_string(),
+            "rows=3 time=1.500ms pages read=2 bytes allocated=96 temp pages=0 (peak 1) memory=0 (peak 512)"
+        );
+
+        let report = ExplainAnalyze {
+            runtime_filters: vec![RuntimeFilterStats::default()],
+            ..report
+        };
+        assert!(report.to_string().ends_with(" runtime filter=none"));
+    }
+}
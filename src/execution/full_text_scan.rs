@@ -0,0 +1,195 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use crate::catalog::{TableInfo, TextIndexInfo};
+use crate::common::{CancellationToken, CrioError, QueryBudget, RecordId, Result};
+use crate::index::TextQuery;
+use crate::tuple::Tuple;
+
+/// Rows resolved with one `TableHeap::get_tuples` call.
+const BATCH_SIZE: usize = 64;
+
+/// FullTextScanExecutor returns the rows of a table that match a full-text
+/// query on one of its text indexes, in posting order.
+///
+/// The matching record IDs are read from the index up front and resolved in
+/// batches. Postings outlive their rows, so deleted rows are skipped and
+/// every row fetched is checked against the query again: a reused record ID
+/// may hold a row the posting wasn't written for. The cancellation token is
+/// checked before each batch is resolved, and the budget is charged for the
+/// heap pages the batch lives on.
+pub struct FullTextScanExecutor {
+    table: Arc<TableInfo>,
+    index: Arc<TextIndexInfo>,
+    query: TextQuery,
+    /// Record IDs not resolved yet
+    record_ids: VecDeque<RecordId>,
+    /// Rows of the current batch not returned yet
+    pending: VecDeque<(RecordId, Tuple)>,
+    /// Stops the scan when cancelled
+    cancellation: CancellationToken,
+    /// Charged for the pages and rows read
+    budget: QueryBudget,
+}
+
+impl FullTextScanExecutor {
+    /// Starts a scan of `table` for the rows matching `query` through
+    /// `index`, which must be a text index on it.
+    pub fn new(table: Arc<TableInfo>, index: Arc<TextIndexInfo>, query: TextQuery) -> Result<Self> {
+        let record_ids = index.index.search(&query)?.into();
+        Ok(Self {
+            table,
+            index,
+            query,
+            record_ids,
+            pending: VecDeque::new(),
+            cancellation: CancellationToken::new(),
+            budget: QueryBudget::default(),
+        })
+    }
+
+    /// Stops the scan with `QueryCancelled` once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Charges the pages and row bytes the scan reads to `budget`, failing
+    /// with `BudgetExceeded` once it runs out.
+    pub fn with_budget(mut self, budget: QueryBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Scans for the rows whose indexed text contains `term`.
+    pub fn contains_term(
+        table: Arc<TableInfo>,
+        index: Arc<TextIndexInfo>,
+        term: &str,
+    ) -> Result<Self> {
+        Self::new(table, index, TextQuery::Term(term.to_string()))
+    }
+
+    /// Scans for the rows whose indexed text contains the terms of `phrase`
+    /// next to each other, in order.
+    pub fn phrase(table: Arc<TableInfo>, index: Arc<TextIndexInfo>, phrase: &str) -> Result<Self> {
+        Self::new(table, index, TextQuery::Phrase(phrase.to_string()))
+    }
+
+    /// Resolves the next batch of record IDs and queues the matching rows.
+    fn read_batch(&mut self) -> Result<()> {
+        let count = self.record_ids.len().min(BATCH_SIZE);
+        let record_ids: Vec<_> = self.record_ids.drain(..count).collect();
+
+        let heap_pages: HashSet<_> = record_ids.iter().map(|rid| rid.page_id).collect();
+        self.budget.charge_pages(heap_pages.len() as u64)?;
+        let tuples = self.table.heap.get_tuples(&record_ids)?;
+        for (record_id, data) in record_ids.into_iter().zip(tuples) {
+            let Some(data) = data else {
+                continue;
+            };
+            self.budget.charge_bytes(data.len() as u64)?;
+            let tuple = Tuple::from_bytes(Arc::clone(&self.table.schema), &data)
+                .ok_or(CrioError::SchemaMismatch)?;
+            if self.index.matches(tuple.values(), &self.query) {
+                self.pending.push_back((record_id, tuple));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the next row, or None once every match is returned.
+    fn advance(&mut self) -> Result<Option<(RecordId, Tuple)>> {
+        loop {
+            if let Some(row) = self.pending.pop_front() {
+                return Ok(Some(row));
+            }
+            if self.record_ids.is_empty() {
+                return Ok(None);
+            }
+            self.cancellation.check()?;
+            self.read_batch()?;
+        }
+    }
+}
+
+impl Iterator for FullTextScanExecutor {
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(row) => row.map(Ok),
+            Err(e) => {
+                // Stop after the first error
+                self.record_ids.clear();
+                self.pending.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::BufferPoolManager;
+    use crate::catalog::Catalog;
+    use crate::storage::disk::MemDiskManager;
+    use crate::tuple::{DataType, Schema, Value};
+
+    #[test]
+    fn test_full_text_scan() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            32,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ));
+        let catalog = Catalog::create(bpm).unwrap();
+        let schema = Schema::builder()
+            .column("id", DataType::Integer)
+            .column("body", DataType::VarChar(200))
+            .build();
+        let table = catalog.create_table("docs", schema).unwrap();
+
+        let texts = [
+            "Rust makes systems programming safe",
+            "Buffer pools cache pages in memory",
+            "Systems programming with buffer pools",
+        ];
+        let mut record_ids = Vec::new();
+        for (i, text) in texts.iter().enumerate() {
+            let tuple = Tuple::new(
+                Arc::clone(&table.schema),
+                vec![Value::Integer(i as i32), Value::String(text.to_string())],
+            );
+            record_ids.push(table.heap.insert_tuple(&tuple.to_bytes().unwrap()).unwrap());
+        }
+        let index = catalog
+            .create_text_index("docs_body", "docs", "body")
+            .unwrap();
+
+        let ids = |scan: FullTextScanExecutor| -> Vec<Value> {
+            scan.map(|row| row.unwrap().1.values()[0].clone()).collect()
+        };
+        let term =
+            |t| FullTextScanExecutor::contains_term(Arc::clone(&table), Arc::clone(&index), t);
+        let phrase = |p| FullTextScanExecutor::phrase(Arc::clone(&table), Arc::clone(&index), p);
+
+        assert_eq!(
+            ids(term("systems").unwrap()),
+            vec![Value::Integer(0), Value::Integer(2)]
+        );
+        assert_eq!(
+            ids(phrase("buffer pools").unwrap()),
+            vec![Value::Integer(1), Value::Integer(2)]
+        );
+        assert!(ids(phrase("pools buffer").unwrap()).is_empty());
+
+        // Deleted rows are skipped
+        table.heap.delete_tuple(record_ids[2]).unwrap();
+        assert_eq!(
+            ids(phrase("buffer pools").unwrap()),
+            vec![Value::Integer(1)]
+        );
+    }
+}
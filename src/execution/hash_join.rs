@@ -0,0 +1,530 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use crate::common::{
+    CancellationToken, CrioError, MemoryReservation, QueryBudget, RecordId, Result,
+    CANCEL_CHECK_ROWS,
+};
+use crate::tuple::{Collation, Schema, Tuple, Value};
+
+use super::{RuntimeFilter, RuntimeFilterSlot};
+
+/// Join key with one representation per class of values
+/// `Value::compare_collated` considers equal, so an Integer key finds a
+/// BigInt one and, under a case-insensitive collation, "Ann" finds "ANN".
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) enum JoinKey {
+    Boolean(bool),
+    Integer(i64),
+    /// Bits of the value as an f64, with -0.0 folded into 0.0
+    Float(u64),
+    /// Sort key of the string under the join's collation
+    String(Vec<u8>),
+    Timestamp(i64),
+}
+
+impl JoinKey {
+    /// Returns the key of `value` under `collation`, or None if it can never
+    /// match: NULLs, NaNs and values of types without equality joins.
+    pub(super) fn of(value: &Value, collation: Collation) -> Option<Self> {
+        let float = |f: f64| (!f.is_nan()).then(|| JoinKey::Float((f + 0.0).to_bits()));
+        match value {
+            Value::Boolean(b) => Some(JoinKey::Boolean(*b)),
+            Value::TinyInt(i) => Some(JoinKey::Integer(*i as i64)),
+            Value::SmallInt(i) => Some(JoinKey::Integer(*i as i64)),
+            Value::Integer(i) => Some(JoinKey::Integer(*i as i64)),
+            Value::BigInt(i) => Some(JoinKey::Integer(*i)),
+            Value::Float(f) => float(*f as f64),
+            Value::Double(f) => float(*f),
+            Value::String(s) => Some(JoinKey::String(collation.sort_key(s).into_owned())),
+            Value::Timestamp(t) => Some(JoinKey::Timestamp(*t)),
+            _ => None,
+        }
+    }
+
+    /// Returns about how many bytes the key takes in a hash table.
+    fn memory_size(&self) -> usize {
+        let heap = match self {
+            JoinKey::String(s) => s.capacity(),
+            _ => 0,
+        };
+        std::mem::size_of::<Self>() + std::mem::size_of::<Vec<Tuple>>() + heap
+    }
+}
+
+/// Which probe rows a `HashJoinExecutor` returns, and what it returns for
+/// them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JoinType {
+    /// Each probe row once per build row with an equal key, as the probe
+    /// row's values followed by the build row's
+    #[default]
+    Inner,
+    /// Each probe row with at least one equal build key, once and as it is:
+    /// `EXISTS` and `IN` subqueries
+    Semi,
+    /// Each probe row without an equal build key, as it is: `NOT EXISTS`
+    Anti,
+    /// Like `Anti`, but with the NULL rules of `NOT IN`: a probe row with a
+    /// NULL key is left out unless the build side is empty, and nothing is
+    /// returned at all if the build side has a NULL key
+    NullAwareAnti,
+}
+
+/// HashJoinExecutor joins the rows of two executors on equal column values.
+///
+/// The build side is read in full when the join is created and hashed on
+/// its key column; the probe side is then streamed, and each probe row is
+/// returned once per build row with an equal key, as the probe row's values
+/// followed by the build row's. A joined row carries the record ID of its
+/// probe row, so joins stack like any other executor. NULL keys never match,
+/// and strings match under the build key column's collation.
+///
+/// Semi- and anti-joins (see `JoinType`) return probe rows unchanged and
+/// only keep the build side's keys, so `EXISTS`, `IN` and their negations
+/// take one pass over each input instead of running the subquery once per
+/// outer row.
+///
+/// The inputs may come from different databases, e.g. a table of the
+/// primary database and one of a database attached with `Database::attach`.
+/// The joined schema repeats both schemas' columns in order; where names
+/// repeat, look columns up by position. The cancellation token is checked
+/// every `CANCEL_CHECK_ROWS` rows read from either side.
+///
+/// The hash table is held in a `MemoryReservation` of the query's budget,
+/// given back when the join is dropped. The join can't spill, so a build
+/// side larger than the budget's `max_memory_bytes` fails it with
+/// `BudgetExceeded`.
+pub struct HashJoinExecutor<P> {
+    probe: P,
+    /// Position of the key column in probe rows
+    probe_column: usize,
+    /// Rows returned for each probe row
+    join_type: JoinType,
+    /// Build rows by key; semi- and anti-joins keep the keys only
+    table: HashMap<JoinKey, Vec<Tuple>>,
+    /// Collation keys are made under, the build key column's
+    collation: Collation,
+    /// Memory the hash table holds
+    memory: MemoryReservation,
+    /// Build rows hashed
+    build_rows: usize,
+    /// Whether a build row had a NULL key
+    build_has_null: bool,
+    /// Schema of joined rows, once the first one is made
+    schema: Option<Arc<Schema>>,
+    /// Joined rows not returned yet
+    pending: VecDeque<(RecordId, Tuple)>,
+    /// Probe rows read
+    probed: usize,
+    /// Stops the join when cancelled
+    cancellation: CancellationToken,
+}
+
+impl<P> HashJoinExecutor<P>
+where
+    P: Iterator<Item = Result<(RecordId, Tuple)>>,
+{
+    /// Reads `build` into a hash table on `build_column` and starts probing
+    /// it with the rows of `probe`, keyed on `probe_column`.
+    pub fn new<B>(probe: P, probe_column: usize, build: B, build_column: usize) -> Result<Self>
+    where
+        B: IntoIterator<Item = Result<(RecordId, Tuple)>>,
+    {
+        Self::with_cancellation(
+            probe,
+            probe_column,
+            build,
+            build_column,
+            CancellationToken::new(),
+        )
+    }
+
+    /// Creates a join like `new` that stops with `QueryCancelled` once
+    /// `cancellation` is cancelled, including while the build side is read.
+    pub fn with_cancellation<B>(
+        probe: P,
+        probe_column: usize,
+        build: B,
+        build_column: usize,
+        cancellation: CancellationToken,
+    ) -> Result<Self>
+    where
+        B: IntoIterator<Item = Result<(RecordId, Tuple)>>,
+    {
+        Self::with_join_type(
+            probe,
+            probe_column,
+            build,
+            build_column,
+            JoinType::Inner,
+            cancellation,
+        )
+    }
+
+    /// Creates a join like `with_cancellation` returning the rows
+    /// `join_type` asks for.
+    pub fn with_join_type<B>(
+        probe: P,
+        probe_column: usize,
+        build: B,
+        build_column: usize,
+        join_type: JoinType,
+        cancellation: CancellationToken,
+    ) -> Result<Self>
+    where
+        B: IntoIterator<Item = Result<(RecordId, Tuple)>>,
+    {
+        Self::with_budget(
+            probe,
+            probe_column,
+            build,
+            build_column,
+            join_type,
+            cancellation,
+            &QueryBudget::default(),
+        )
+    }
+
+    /// Creates a join like `with_join_type` whose hash table is reserved
+    /// from `budget`, failing with `BudgetExceeded` if the build side takes
+    /// more than its `max_memory_bytes`.
+    pub fn with_budget<B>(
+        probe: P,
+        probe_column: usize,
+        build: B,
+        build_column: usize,
+        join_type: JoinType,
+        cancellation: CancellationToken,
+        budget: &QueryBudget,
+    ) -> Result<Self>
+    where
+        B: IntoIterator<Item = Result<(RecordId, Tuple)>>,
+    {
+        let mut memory = budget.reserve_memory();
+        let mut table: HashMap<JoinKey, Vec<Tuple>> = HashMap::new();
+        let mut build_rows: usize = 0;
+        let mut collation = None;
+        let mut build_has_null = false;
+        for row in build {
+            if build_rows.is_multiple_of(CANCEL_CHECK_ROWS) && cancellation.is_cancelled() {
+                return Err(CrioError::QueryCancelled);
+            }
+            let (_, tuple) = row?;
+            build_rows += 1;
+            let collation = *collation.get_or_insert_with(|| {
+                tuple
+                    .schema()
+                    .column(build_column)
+                    .map_or(Collation::Binary, |column| column.collation())
+            });
+            let value = tuple.value(build_column);
+            build_has_null |= matches!(value, None | Some(Value::Null));
+            if let Some(key) = value.and_then(|value| JoinKey::of(value, collation)) {
+                if !table.contains_key(&key) {
+                    memory.grow(key.memory_size() as u64)?;
+                }
+                let matches = table.entry(key).or_default();
+                if join_type == JoinType::Inner {
+                    memory.grow(tuple.memory_size() as u64)?;
+                    matches.push(tuple);
+                }
+            }
+        }
+
+        Ok(Self {
+            probe,
+            probe_column,
+            join_type,
+            table,
+            collation: collation.unwrap_or(Collation::Binary),
+            memory,
+            build_rows,
+            build_has_null,
+            schema: None,
+            pending: VecDeque::new(),
+            probed: 0,
+            cancellation,
+        })
+    }
+
+    /// Returns the number of build rows read.
+    pub fn build_rows(&self) -> usize {
+        self.build_rows
+    }
+
+    /// Returns the join type.
+    pub fn join_type(&self) -> JoinType {
+        self.join_type
+    }
+
+    /// Publishes a `RuntimeFilter` of the build keys to `slot`, for the scan
+    /// feeding the probe side to skip rows that can't match. Only inner and
+    /// semi-joins make one, as anti-joins return the probe rows without a
+    /// match, and only for build sides of at most `RUNTIME_FILTER_MAX_KEYS`
+    /// distinct keys.
+    pub fn with_runtime_filter(self, slot: &RuntimeFilterSlot) -> Self {
+        if matches!(self.join_type, JoinType::Inner | JoinType::Semi) {
+            if let Some(filter) = RuntimeFilter::from_keys(self.table.keys(), self.collation) {
+                slot.publish(filter);
+            }
+        }
+        self
+    }
+
+    /// Returns the bytes of memory the hash table is charged for.
+    pub fn memory_bytes(&self) -> u64 {
+        self.memory.size()
+    }
+}
+
+impl<P> Iterator for HashJoinExecutor<P>
+where
+    P: Iterator<Item = Result<(RecordId, Tuple)>>,
+{
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.join_type == JoinType::NullAwareAnti && self.build_has_null {
+            // x NOT IN (..., NULL, ...) is never true
+            return None;
+        }
+        loop {
+            if let Some(row) = self.pending.pop_front() {
+                return Some(Ok(row));
+            }
+            if self.probed.is_multiple_of(CANCEL_CHECK_ROWS) && self.cancellation.is_cancelled() {
+                return Some(Err(CrioError::QueryCancelled));
+            }
+            let (record_id, tuple) = match self.probe.next()? {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e)),
+            };
+            self.probed += 1;
+            let value = tuple.value(self.probe_column);
+            let key = value.and_then(|value| JoinKey::of(value, self.collation));
+            let matched = key.as_ref().is_some_and(|key| self.table.contains_key(key));
+            let keep = match self.join_type {
+                JoinType::Inner => {
+                    if let Some(matches) = key.and_then(|key| self.table.get(&key)) {
+                        join_rows(
+                            &mut self.schema,
+                            &mut self.pending,
+                            record_id,
+                            &tuple,
+                            matches,
+                        );
+                    }
+                    false
+                }
+                JoinType::Semi => matched,
+                JoinType::Anti => !matched,
+                JoinType::NullAwareAnti => {
+                    let null = matches!(value, None | Some(Value::Null));
+                    !matched && (!null || self.build_rows == 0)
+                }
+            };
+            if keep {
+                return Some(Ok((record_id, tuple)));
+            }
+        }
+    }
+}
+
+/// Queues the rows joining `probe` with each of `matches`, making the
+/// joined schema first if there is none yet.
+fn join_rows(
+    schema: &mut Option<Arc<Schema>>,
+    pending: &mut VecDeque<(RecordId, Tuple)>,
+    record_id: RecordId,
+    probe: &Tuple,
+    matches: &[Tuple],
+) {
+    let schema = schema.get_or_insert_with(|| {
+        let columns = probe
+            .schema()
+            .columns()
+            .chain(matches[0].schema().columns())
+            .cloned()
+            .collect();
+        Arc::new(Schema::new(columns))
+    });
+    for build in matches {
+        let values = probe
+            .values()
+            .iter()
+            .chain(build.values())
+            .cloned()
+            .collect();
+        pending.push_back((record_id, Tuple::new(Arc::clone(schema), values)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::test_util::rows;
+    use crate::tuple::DataType;
+
+    #[test]
+    fn test_hash_join() {
+        let users = Arc::new(
+            Schema::builder()
+                .column("id", DataType::Integer)
+                .column("name", DataType::VarChar(16))
+                .build(),
+        );
+        let orders = Arc::new(
+            Schema::builder()
+                .nullable_column("user_id", DataType::BigInt)
+                .column("total", DataType::Integer)
+                .build(),
+        );
+        let build = rows(
+            &users,
+            vec![
+                vec![Value::Integer(1), Value::String("ann".into())],
+                vec![Value::Integer(2), Value::String("bob".into())],
+            ],
+        );
+        let probe = rows(
+            &orders,
+            vec![
+                vec![Value::BigInt(2), Value::Integer(10)],
+                vec![Value::Null, Value::Integer(20)],
+                vec![Value::BigInt(3), Value::Integer(30)],
+                vec![Value::BigInt(1), Value::Integer(40)],
+                vec![Value::BigInt(2), Value::Integer(50)],
+            ],
+        );
+
+        let join = HashJoinExecutor::new(probe.into_iter(), 0, build, 0).unwrap();
+        assert_eq!(join.build_rows(), 2);
+        let joined: Vec<_> = join.map(|row| row.unwrap()).collect();
+        let summary: Vec<_> = joined
+            .iter()
+            .map(|(rid, t)| {
+                (
+                    rid.slot_id.as_u16(),
+                    t.value(1).cloned(),
+                    t.value(3).cloned(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    0,
+                    Some(Value::Integer(10)),
+                    Some(Value::String("bob".into()))
+                ),
+                (
+                    3,
+                    Some(Value::Integer(40)),
+                    Some(Value::String("ann".into()))
+                ),
+                (
+                    4,
+                    Some(Value::Integer(50)),
+                    Some(Value::String("bob".into()))
+                ),
+            ]
+        );
+        assert_eq!(joined[0].1.schema().column_count(), 4);
+    }
+
+    #[test]
+    fn test_semi_and_anti_join() {
+        let schema = Arc::new(
+            Schema::builder()
+                .nullable_column("id", DataType::Integer)
+                .build(),
+        );
+        let ids = |values: &[Option<i32>]| {
+            let values = values
+                .iter()
+                .map(|v| vec![v.map_or(Value::Null, Value::Integer)])
+                .collect();
+            rows(&schema, values)
+        };
+        let probe = || ids(&[Some(1), None, Some(2), Some(3), Some(2)]).into_iter();
+        let slots = |join_type: JoinType, build: &[Option<i32>]| -> Vec<u16> {
+            let join = HashJoinExecutor::with_join_type(
+                probe(),
+                0,
+                ids(build),
+                0,
+                join_type,
+                CancellationToken::new(),
+            )
+            .unwrap();
+            assert_eq!(join.join_type(), join_type);
+            join.map(|row| row.unwrap().0.slot_id.as_u16()).collect()
+        };
+
+        let build = [Some(2), Some(2), Some(3), Some(7)];
+        // Each matching probe row once, even with two equal build keys
+        assert_eq!(slots(JoinType::Semi, &build), vec![2, 3, 4]);
+        assert_eq!(slots(JoinType::Anti, &build), vec![0, 1]);
+        assert_eq!(slots(JoinType::NullAwareAnti, &build), vec![0]);
+        assert_eq!(slots(JoinType::NullAwareAnti, &[]), vec![0, 1, 2, 3, 4]);
+
+        // A NULL on the build side only matters to NOT IN
+        let build = [Some(3), None];
+        assert_eq!(slots(JoinType::Semi, &build), vec![3]);
+        assert_eq!(slots(JoinType::Anti, &build), vec![0, 1, 2, 4]);
+        assert!(slots(JoinType::NullAwareAnti, &build).is_empty());
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let join =
+            HashJoinExecutor::with_join_type(probe(), 0, ids(&[Some(1)]), 0, JoinType::Semi, token);
+        assert!(matches!(join, Err(CrioError::QueryCancelled)));
+    }
+
+    #[test]
+    fn test_hash_join_memory_budget() {
+        use crate::common::ResourceLimits;
+
+        let schema = Arc::new(Schema::builder().column("id", DataType::Integer).build());
+        let ids = |n: i32| rows(&schema, (0..n).map(|i| vec![Value::Integer(i)]).collect());
+        let budget = QueryBudget::new(ResourceLimits {
+            max_memory_bytes: Some(64 * 1024),
+            ..Default::default()
+        });
+
+        let join = HashJoinExecutor::with_budget(
+            ids(10).into_iter(),
+            0,
+            ids(100),
+            0,
+            JoinType::Inner,
+            CancellationToken::new(),
+            &budget,
+        )
+        .unwrap();
+        assert!(join.memory_bytes() > 0);
+        assert_eq!(budget.usage().memory_bytes, join.memory_bytes());
+        assert_eq!(join.count(), 10);
+        assert_eq!(budget.usage().memory_bytes, 0);
+
+        let join = HashJoinExecutor::with_budget(
+            ids(10).into_iter(),
+            0,
+            ids(10_000),
+            0,
+            JoinType::Inner,
+            CancellationToken::new(),
+            &budget,
+        );
+        assert!(matches!(
+            join,
+            Err(CrioError::BudgetExceeded {
+                resource: "memory",
+                ..
+            })
+        ));
+        assert_eq!(budget.usage().memory_bytes, 0);
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use crate::buffer::BufferPoolManager;
+use crate::catalog::TableInfo;
+use crate::common::{CancellationToken, CrioError, PageId, QueryBudget, RecordId, Result};
+use crate::index::{BTreeIndex, BTreeNodeRef};
+use crate::tuple::Tuple;
+
+use super::key_predicate::merge_ranges;
+
+/// Leaves prefetched ahead of the one being read.
+const PREFETCH_LEAVES: usize = 4;
+
+/// IndexScanExecutor returns the rows of a table whose index key lies in
+/// `start_key..=end_key`, or in any of several key ranges, in key order.
+///
+/// It walks the B+Tree leaves through their `next_page_id` links, one leaf at
+/// a time. Upcoming leaves are the current leaf's later siblings in its
+/// parent, so whenever it runs out of prefetched leaves it reads the parent
+/// and prefetches the next `PREFETCH_LEAVES` of them. The record IDs of each
+/// leaf are resolved together with `TableHeap::get_tuples`, which latches
+/// every heap page once per leaf rather than once per row.
+///
+/// A scan of several ranges, e.g. from `KeyPredicate::key_ranges`, merges
+/// the ranges that overlap, descends the tree once per range and reads the
+/// ranges in key order. Its record IDs are deduplicated before the heap is
+/// read, so no row is fetched or returned twice.
+///
+/// Index entries whose row has been deleted are skipped. Keys inserted while
+/// the scan runs may or may not be returned. The cancellation token is
+/// checked before each leaf is read, and the budget is charged for each leaf
+/// and the heap pages its rows live on.
+pub struct IndexScanExecutor {
+    bpm: Arc<BufferPoolManager>,
+    table: Arc<TableInfo>,
+    start_key: u32,
+    end_key: u32,
+    /// Next leaf to read, or None once the range is exhausted
+    next_leaf: Option<PageId>,
+    /// Ranges after the current one, with the leaf each starts in
+    ranges: VecDeque<(u32, u32, PageId)>,
+    /// Record IDs read so far, for scans of several ranges
+    seen: Option<HashSet<RecordId>>,
+    /// Leaves prefetched and not read yet, in chain order
+    prefetched: VecDeque<PageId>,
+    /// Rows of the current leaf not returned yet
+    pending: VecDeque<(RecordId, Tuple)>,
+    /// Stops the scan when cancelled
+    cancellation: CancellationToken,
+    /// Charged for the pages and rows read
+    budget: QueryBudget,
+}
+
+impl IndexScanExecutor {
+    /// Starts a scan of `table` through `index`, which must be an index on
+    /// it.
+    pub fn new(
+        bpm: Arc<BufferPoolManager>,
+        table: Arc<TableInfo>,
+        index: &BTreeIndex,
+        start_key: u32,
+        end_key: u32,
+    ) -> Result<Self> {
+        let next_leaf = if start_key <= end_key {
+            Some(index.leaf_page_id(start_key)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            bpm,
+            table,
+            start_key,
+            end_key,
+            next_leaf,
+            ranges: VecDeque::new(),
+            seen: None,
+            prefetched: VecDeque::new(),
+            pending: VecDeque::new(),
+            cancellation: CancellationToken::new(),
+            budget: QueryBudget::default(),
+        })
+    }
+
+    /// Starts a scan of `table` through `index` of the keys in any of the
+    /// inclusive `ranges`, which may overlap and come in any order.
+    pub fn with_ranges(
+        bpm: Arc<BufferPoolManager>,
+        table: Arc<TableInfo>,
+        index: &BTreeIndex,
+        ranges: &[(u32, u32)],
+    ) -> Result<Self> {
+        let mut merged = merge_ranges(ranges.to_vec())
+            .into_iter()
+            .map(|(start, end)| Ok((start, end, index.leaf_page_id(start)?)))
+            .collect::<Result<VecDeque<_>>>()?;
+
+        let Some((start_key, end_key, leaf)) = merged.pop_front() else {
+            return Self::new(bpm, table, index, 1, 0);
+        };
+        let mut scan = Self::new(bpm, table, index, start_key, end_key)?;
+        scan.next_leaf = Some(leaf);
+        scan.ranges = merged;
+        scan.seen = Some(HashSet::new());
+        Ok(scan)
+    }
+
+    /// Stops the scan with `QueryCancelled` once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Charges the pages and row bytes the scan reads to `budget`, failing
+    /// with `BudgetExceeded` once it runs out.
+    pub fn with_budget(mut self, budget: QueryBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Reads a leaf and queues its rows.
+    fn read_leaf(&mut self, leaf: PageId) -> Result<()> {
+        if self.prefetched.front() == Some(&leaf) {
+            self.prefetched.pop_front();
+        } else {
+            // The chain changed under us; the prefetched leaves are stale
+            self.prefetched.clear();
+        }
+
+        self.budget.charge_pages(1)?;
+        let (record_ids, next, parent) = {
+            let guard = self
+                .bpm
+                .checked_read_page(leaf)?
+                .ok_or(CrioError::PageNotFound(leaf))?;
+            let node = BTreeNodeRef::new(guard.data());
+
+            let mut record_ids = Vec::new();
+            let mut past_end = false;
+            for i in 0..node.num_keys() as usize {
+                let key = node.get_key(i);
+                if key > self.end_key {
+                    past_end = true;
+                    break;
+                }
+                if key >= self.start_key {
+                    record_ids.push(node.get_value(i));
+                }
+            }
+
+            let next = if past_end { None } else { node.next_page_id() };
+            (record_ids, next, node.parent_page_id())
+        };
+
+        self.next_leaf = next;
+        if let (Some(next), true) = (next, self.prefetched.is_empty()) {
+            self.prefetch_after(leaf, next, parent);
+        }
+
+        let mut record_ids = record_ids;
+        if let Some(seen) = &mut self.seen {
+            record_ids.retain(|&rid| seen.insert(rid));
+        }
+        let heap_pages: HashSet<_> = record_ids.iter().map(|rid| rid.page_id).collect();
+        self.budget.charge_pages(heap_pages.len() as u64)?;
+        let tuples = self.table.heap.get_tuples(&record_ids)?;
+        for (record_id, data) in record_ids.into_iter().zip(tuples) {
+            let Some(data) = data else {
+                continue;
+            };
+            self.budget.charge_bytes(data.len() as u64)?;
+            let tuple = Tuple::from_bytes(Arc::clone(&self.table.schema), &data)
+                .ok_or(CrioError::SchemaMismatch)?;
+            self.pending.push_back((record_id, tuple));
+        }
+        Ok(())
+    }
+
+    /// Prefetches the leaves that follow `leaf`, whose successor is `next`.
+    fn prefetch_after(&mut self, leaf: PageId, next: PageId, parent: Option<PageId>) {
+        let mut leaves = Vec::new();
+        if let Some(parent) = parent {
+            if let Ok(Some(guard)) = self.bpm.checked_read_page(parent) {
+                let node = BTreeNodeRef::new(guard.data());
+                let children = (0..=node.num_keys() as usize).map(|i| node.get_child(i));
+                leaves.extend(
+                    children
+                        .skip_while(|&child| child != leaf)
+                        .skip(1)
+                        .take(PREFETCH_LEAVES),
+                );
+            }
+        }
+        // The last child of its parent, or a parent that changed since the
+        // leaf was read: fall back to the one leaf we know comes next
+        if leaves.first() != Some(&next) {
+            leaves = vec![next];
+        }
+
+        // Prefetching is only a hint; reading the leaves reports real errors.
+        // Adjacent leaves are fetched with one read.
+        let mut run_start = 0;
+        for i in 1..=leaves.len() {
+            let contiguous =
+                i < leaves.len() && leaves[i].as_u32() == leaves[i - 1].as_u32().wrapping_add(1);
+            if !contiguous {
+                let _ = self
+                    .bpm
+                    .prefetch_pages(leaves[run_start], (i - run_start) as u32);
+                run_start = i;
+            }
+        }
+        self.prefetched.extend(leaves);
+    }
+
+    /// Returns the next row, or None at the end of the range.
+    fn advance(&mut self) -> Result<Option<(RecordId, Tuple)>> {
+        loop {
+            if let Some(row) = self.pending.pop_front() {
+                return Ok(Some(row));
+            }
+            self.cancellation.check()?;
+            if self.next_leaf.is_none() {
+                // On to the next range, from the leaf it starts in
+                let Some((start_key, end_key, leaf)) = self.ranges.pop_front() else {
+                    return Ok(None);
+                };
+                self.start_key = start_key;
+                self.end_key = end_key;
+                self.next_leaf = Some(leaf);
+            }
+            if let Some(leaf) = self.next_leaf {
+                self.read_leaf(leaf)?;
+            }
+        }
+    }
+}
+
+impl Iterator for IndexScanExecutor {
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(row) => row.map(Ok),
+            Err(e) => {
+                // Stop after the first error
+                self.next_leaf = None;
+                self.ranges.clear();
+                self.pending.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{index_key, Catalog};
+    use crate::storage::disk::MemDiskManager;
+    use crate::tuple::{DataType, Schema, Value};
+
+    #[test]
+    fn test_index_scan_executor() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            64,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ));
+        let catalog = Catalog::create(Arc::clone(&bpm)).unwrap();
+        let schema = Schema::builder()
+            .column("id", DataType::Integer)
+            .column("name", DataType::VarChar(16))
+            .build();
+        let table = catalog.create_table("users", schema).unwrap();
+        let index = catalog.create_index("users_id", "users", "id").unwrap();
+
+        let mut rids = Vec::new();
+        for id in 0..2000 {
+            let row = Tuple::new(
+                Arc::clone(&table.schema),
+                vec![Value::Integer(id), Value::String(format!("u{}", id))],
+            );
+            let rid = table.heap.insert_tuple(&row.to_bytes().unwrap()).unwrap();
+            let key = index_key(&Value::Integer(id)).unwrap();
+            catalog.insert_index_entry(&index, key, rid).unwrap();
+            rids.push(rid);
+        }
+        table.heap.delete_tuple(rids[150]).unwrap();
+
+        let key = |id: i32| index_key(&Value::Integer(id)).unwrap();
+        let mut scan = IndexScanExecutor::new(
+            Arc::clone(&bpm),
+            Arc::clone(&table),
+            &index.index,
+            key(100),
+            key(1899),
+        )
+        .unwrap();
+
+        // The first leaf's successors are prefetched and resident
+        let first = scan.next().unwrap().unwrap();
+        assert_eq!(first.0, rids[100]);
+        assert!(!scan.prefetched.is_empty());
+        assert!(scan
+            .prefetched
+            .iter()
+            .all(|&leaf| bpm.get_pin_count(leaf).is_some()));
+
+        let ids: Vec<i32> = std::iter::once(Ok(first))
+            .chain(scan)
+            .map(|row| match row.unwrap().1.value(0) {
+                Some(&Value::Integer(id)) => id,
+                other => panic!("unexpected id {:?}", other),
+            })
+            .collect();
+        let expected: Vec<i32> = (100..1900).filter(|&id| id != 150).collect();
+        assert_eq!(ids, expected);
+
+        let empty = IndexScanExecutor::new(bpm, table, &index.index, key(5), key(4)).unwrap();
+        assert_eq!(empty.count(), 0);
+    }
+
+    #[test]
+    fn test_index_scan_executor_ranges() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            64,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ));
+        let catalog = Catalog::create(Arc::clone(&bpm)).unwrap();
+        let schema = Schema::builder().column("id", DataType::Integer).build();
+        let table = catalog.create_table("t", schema).unwrap();
+        let index = catalog.create_index("t_id", "t", "id").unwrap();
+        for id in 0..1000 {
+            let row = Tuple::new(Arc::clone(&table.schema), vec![Value::Integer(id)]);
+            let rid = table.heap.insert_tuple(&row.to_bytes().unwrap()).unwrap();
+            let key = index_key(&Value::Integer(id)).unwrap();
+            catalog.insert_index_entry(&index, key, rid).unwrap();
+        }
+
+        // Unsorted, overlapping and empty ranges; each row comes back once
+        let key = |id: i32| index_key(&Value::Integer(id)).unwrap();
+        let ranges = [
+            (key(900), key(905)),
+            (key(10), key(12)),
+            (key(500), key(400)),
+            (key(11), key(13)),
+            (key(903), key(903)),
+        ];
+        let scan = IndexScanExecutor::with_ranges(
+            Arc::clone(&bpm),
+            Arc::clone(&table),
+            &index.index,
+            &ranges,
+        )
+        .unwrap();
+        let ids: Vec<i32> = scan
+            .map(|row| match row.unwrap().1.value(0) {
+                Some(&Value::Integer(id)) => id,
+                other => panic!("unexpected id {:?}", other),
+            })
+            .collect();
+        assert_eq!(ids, vec![10, 11, 12, 13, 900, 901, 902, 903, 904, 905]);
+
+        let none = IndexScanExecutor::with_ranges(bpm, table, &index.index, &[]).unwrap();
+        assert_eq!(none.count(), 0);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::catalog::index_key;
+use crate::common::{CrioError, Result};
+use crate::tuple::Value;
+
+/// A condition on an integer column that an index on the column can answer
+/// with range and point scans instead of a full scan.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyPredicate {
+    /// `col = value`
+    Eq(Value),
+    /// `col BETWEEN low AND high`, both ends included
+    Between(Value, Value),
+    /// `col IN (values)`
+    In(Vec<Value>),
+    /// Rows meeting any of the predicates
+    Or(Vec<KeyPredicate>),
+}
+
+impl KeyPredicate {
+    /// Translates the predicate into the inclusive B+Tree key ranges holding
+    /// every matching row: ascending, disjoint and not adjacent, with
+    /// overlapping and touching ranges merged, so each key is read once.
+    ///
+    /// NULLs never match, and neither do BigInt values outside the range
+    /// indexes can hold; BETWEEN is clipped to that range. Values that are
+    /// not integers fail with `SchemaMismatch`.
+    pub fn key_ranges(&self) -> Result<Vec<(u32, u32)>> {
+        let mut ranges = Vec::new();
+        self.collect_ranges(&mut ranges)?;
+        Ok(merge_ranges(ranges))
+    }
+
+    fn collect_ranges(&self, ranges: &mut Vec<(u32, u32)>) -> Result<()> {
+        match self {
+            KeyPredicate::Eq(value) => {
+                if let Some(key) = integer(value)?.and_then(|v| clip(v, v)) {
+                    ranges.push(key);
+                }
+            }
+            KeyPredicate::Between(low, high) => {
+                if let (Some(low), Some(high)) = (integer(low)?, integer(high)?) {
+                    ranges.extend(clip(low, high));
+                }
+            }
+            KeyPredicate::In(values) => {
+                for value in values {
+                    KeyPredicate::Eq(value.clone()).collect_ranges(ranges)?;
+                }
+            }
+            KeyPredicate::Or(predicates) => {
+                for predicate in predicates {
+                    predicate.collect_ranges(ranges)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the integer in `value`, None for NULL.
+fn integer(value: &Value) -> Result<Option<i64>> {
+    match *value {
+        Value::Null => Ok(None),
+        Value::TinyInt(v) => Ok(Some(v as i64)),
+        Value::SmallInt(v) => Ok(Some(v as i64)),
+        Value::Integer(v) => Ok(Some(v as i64)),
+        Value::BigInt(v) => Ok(Some(v)),
+        _ => Err(CrioError::SchemaMismatch),
+    }
+}
+
+/// Returns the keys of `low..=high` that an index can hold, or None if
+/// there are none.
+fn clip(low: i64, high: i64) -> Option<(u32, u32)> {
+    let low = Value::BigInt(low.max(i32::MIN as i64));
+    let high = Value::BigInt(high.min(i32::MAX as i64));
+    index_key(&low)
+        .zip(index_key(&high))
+        .filter(|(low, high)| low <= high)
+}
+
+/// Sorts inclusive `ranges`, drops empty ones and merges those that
+/// overlap or touch.
+pub(super) fn merge_ranges(mut ranges: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges.into_iter().filter(|(start, end)| start <= end) {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(v: i32) -> u32 {
+        index_key(&Value::Integer(v)).unwrap()
+    }
+
+    #[test]
+    fn test_key_ranges() {
+        let predicate = KeyPredicate::Or(vec![
+            KeyPredicate::In(vec![
+                Value::Integer(7),
+                Value::Null,
+                Value::SmallInt(-3),
+                Value::BigInt(1 << 40),
+                Value::Integer(7),
+            ]),
+            KeyPredicate::Between(Value::Integer(10), Value::Integer(20)),
+            KeyPredicate::Between(Value::Integer(15), Value::Integer(30)),
+            KeyPredicate::Eq(Value::Integer(31)),
+            KeyPredicate::Between(Value::Integer(50), Value::Integer(40)),
+        ]);
+        assert_eq!(
+            predicate.key_ranges().unwrap(),
+            vec![(key(-3), key(-3)), (key(7), key(7)), (key(10), key(31))]
+        );
+
+        let clipped = KeyPredicate::Between(Value::BigInt(i64::MIN), Value::Integer(0));
+        assert_eq!(clipped.key_ranges().unwrap(), vec![(0, key(0))]);
+        assert!(matches!(
+            KeyPredicate::Eq(Value::String("x".into())).key_ranges(),
+            Err(CrioError::SchemaMismatch)
+        ));
+    }
+}
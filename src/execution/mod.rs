@@ -1,2 +1,37 @@
-// Placeholder for query execution engine
-// TODO: Implement query execution components
+//! Query execution: executors that produce rows from tables and indexes.
+
+mod batch;
+mod cte;
+mod explain;
+mod full_text_scan;
+mod hash_join;
+mod index_scan;
+mod key_predicate;
+mod partition_scan;
+mod plan_cache;
+mod runtime_filter;
+mod seq_scan;
+mod sort;
+mod string_function;
+mod subquery;
+#[cfg(test)]
+mod test_util;
+mod upsert;
+mod window;
+
+pub use batch::*;
+pub use cte::*;
+pub use explain::*;
+pub use full_text_scan::*;
+pub use hash_join::*;
+pub use index_scan::*;
+pub use key_predicate::*;
+pub use partition_scan::*;
+pub use plan_cache::*;
+pub use runtime_filter::*;
+pub use seq_scan::*;
+pub use sort::*;
+pub use string_function::*;
+pub use subquery::*;
+pub use upsert::*;
+pub use window::*;
@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+use crate::catalog::{key_in_range, PartitionedTableInfo, TableInfo};
+use crate::common::{
+    CancellationToken, CrioError, QueryBudget, RecordId, Result, CANCEL_CHECK_ROWS,
+};
+use crate::tuple::Tuple;
+
+/// PartitionScanExecutor returns the rows of a partitioned table whose
+/// partition key lies in a range.
+///
+/// Partitions that can't hold a key in the range are pruned up front and
+/// never read. The others are read one at a time, in partition order, and
+/// their rows filtered by key; within a partition rows come in heap order.
+/// NULL keys only match the unbounded range `..`. The cancellation token is
+/// checked before each partition and every `CANCEL_CHECK_ROWS` rows read.
+/// The budget is charged for each heap page as its first row is read.
+pub struct PartitionScanExecutor {
+    table: Arc<PartitionedTableInfo>,
+    range: (Bound<i64>, Bound<i64>),
+    /// Partitions still to read
+    partitions: VecDeque<Arc<TableInfo>>,
+    /// Matching rows of the current partition not returned yet
+    pending: VecDeque<(RecordId, Tuple)>,
+    /// Stops the scan when cancelled
+    cancellation: CancellationToken,
+    /// Charged for the pages and rows read
+    budget: QueryBudget,
+}
+
+impl PartitionScanExecutor {
+    /// Starts a scan of the rows of `table` with keys in `range`.
+    pub fn new<R: RangeBounds<i64>>(table: Arc<PartitionedTableInfo>, range: R) -> Self {
+        let partitions = table.prune(&range).into_iter().cloned().collect();
+        Self {
+            range: (range.start_bound().cloned(), range.end_bound().cloned()),
+            table,
+            partitions,
+            pending: VecDeque::new(),
+            cancellation: CancellationToken::new(),
+            budget: QueryBudget::default(),
+        }
+    }
+
+    /// Stops the scan with `QueryCancelled` once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Charges the pages and row bytes the scan reads to `budget`, failing
+    /// with `BudgetExceeded` once it runs out.
+    pub fn with_budget(mut self, budget: QueryBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Returns the table IDs of the partitions left to read.
+    pub fn remaining_partitions(&self) -> Vec<u32> {
+        self.partitions.iter().map(|p| p.table_id).collect()
+    }
+
+    /// Reads a partition and queues its matching rows.
+    fn read_partition(&mut self, partition: &TableInfo) -> Result<()> {
+        let mut last_page = None;
+        for (i, row) in partition.heap.iter().enumerate() {
+            if i % CANCEL_CHECK_ROWS == 0 {
+                self.cancellation.check()?;
+            }
+            let (record_id, data) = row?;
+            if last_page.replace(record_id.page_id) != Some(record_id.page_id) {
+                self.budget.charge_pages(1)?;
+            }
+            self.budget.charge_bytes(data.len() as u64)?;
+            let tuple = Tuple::from_bytes(Arc::clone(&partition.schema), &data)
+                .ok_or(CrioError::SchemaMismatch)?;
+            if key_in_range(self.table.key_of(tuple.values()), &self.range) {
+                self.pending.push_back((record_id, tuple));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the next row, or None once every partition has been read.
+    fn advance(&mut self) -> Result<Option<(RecordId, Tuple)>> {
+        loop {
+            if let Some(row) = self.pending.pop_front() {
+                return Ok(Some(row));
+            }
+            match self.partitions.pop_front() {
+                Some(partition) => self.read_partition(&partition)?,
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl Iterator for PartitionScanExecutor {
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(row) => row.map(Ok),
+            Err(e) => {
+                // Stop after the first error
+                self.partitions.clear();
+                self.pending.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::BufferPoolManager;
+    use crate::catalog::{Catalog, PartitionScheme};
+    use crate::storage::disk::MemDiskManager;
+    use crate::tuple::{DataType, Schema, Value};
+
+    #[test]
+    fn test_partition_scan_executor() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            32,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ));
+        let catalog = Catalog::create(bpm).unwrap();
+        let schema = Schema::builder()
+            .nullable_column("id", DataType::BigInt)
+            .column("name", DataType::VarChar(16))
+            .build();
+        let table = catalog
+            .create_partitioned_table(
+                "events",
+                schema,
+                "id",
+                PartitionScheme::Range(vec![100, 200]),
+            )
+            .unwrap();
+
+        for id in (0..300).map(Value::BigInt).chain([Value::Null]) {
+            let row = Tuple::new(
+                Arc::clone(&table.schema),
+                vec![id, Value::String("e".into())],
+            );
+            let partition = &table.partitions[table.route(row.values())];
+            partition
+                .heap
+                .insert_tuple(&row.to_bytes().unwrap())
+                .unwrap();
+        }
+        assert_eq!(table.partitions[0].heap.scan().unwrap().len(), 101);
+
+        let ids = |scan: PartitionScanExecutor| -> Vec<Value> {
+            scan.map(|row| row.unwrap().1.value(0).cloned().unwrap())
+                .collect()
+        };
+
+        // Only the middle partition is read
+        let scan = PartitionScanExecutor::new(Arc::clone(&table), 150..160);
+        assert_eq!(
+            scan.remaining_partitions(),
+            vec![table.partitions[1].table_id]
+        );
+        assert_eq!(ids(scan), (150..160).map(Value::BigInt).collect::<Vec<_>>());
+
+        let scan = PartitionScanExecutor::new(Arc::clone(&table), 195..=205);
+        assert_eq!(scan.remaining_partitions().len(), 2);
+        assert_eq!(ids(scan).len(), 11);
+
+        // The full scan includes the NULL key
+        assert_eq!(ids(PartitionScanExecutor::new(table, ..)).len(), 301);
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::catalog::Catalog;
+use crate::common::Result;
+
+/// Plans a `PlanCache` keeps by default
+pub const DEFAULT_PLAN_CACHE_ENTRIES: usize = 256;
+
+/// Counters of a `PlanCache`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlanCacheStats {
+    /// Lookups answered with a cached plan
+    pub hits: u64,
+    /// Lookups that had to plan the statement
+    pub misses: u64,
+    /// Plans thrown away because a table they read changed
+    pub invalidations: u64,
+    /// Plans thrown away to make room
+    pub evictions: u64,
+    /// Plans cached now
+    pub entries: usize,
+}
+
+/// A cached plan with the versions of the tables it was made at.
+struct CachedPlan<P> {
+    plan: Arc<P>,
+    /// Tables the plan reads, with their `Catalog::table_version`
+    tables: Vec<(String, u64)>,
+    /// Tick of the last lookup, for evicting the least recently used
+    last_used: u64,
+}
+
+/// PlanCache keeps compiled plans by normalized statement text, so a
+/// statement run over and over in an OLTP loop is planned once.
+///
+/// Each plan records the tables it reads at their `Catalog::table_version`.
+/// A lookup finding that any of them has changed since (a table dropped or
+/// recreated, an index, bloom filter or zone map added or removed, a grant
+/// changed) throws the plan away and plans again. The least recently used
+/// plan makes room once `capacity` plans are cached.
+pub struct PlanCache<P> {
+    catalog: Arc<Catalog>,
+    capacity: usize,
+    state: Mutex<PlanCacheState<P>>,
+}
+
+struct PlanCacheState<P> {
+    plans: HashMap<String, CachedPlan<P>>,
+    /// Lookups so far
+    tick: u64,
+    stats: PlanCacheStats,
+}
+
+impl<P> PlanCache<P> {
+    /// Creates an empty cache for plans over the tables of `catalog`,
+    /// keeping up to `DEFAULT_PLAN_CACHE_ENTRIES` plans.
+    pub fn new(catalog: Arc<Catalog>) -> Self {
+        Self {
+            catalog,
+            capacity: DEFAULT_PLAN_CACHE_ENTRIES,
+            state: Mutex::new(PlanCacheState {
+                plans: HashMap::new(),
+                tick: 0,
+                stats: PlanCacheStats::default(),
+            }),
+        }
+    }
+
+    /// Keeps up to `entries` plans, at least one.
+    pub fn with_capacity(mut self, entries: usize) -> Self {
+        self.capacity = entries.max(1);
+        self
+    }
+
+    /// Returns the plan of `statement`, calling `plan` with the normalized
+    /// text to make it if there is no valid one cached. `plan` returns the
+    /// plan and the names of the tables it reads.
+    ///
+    /// The cache isn't locked while planning, so two threads missing on the
+    /// same statement may both plan it; the last plan made is kept.
+    pub fn get_or_plan<F>(&self, statement: &str, plan: F) -> Result<Arc<P>>
+    where
+        F: FnOnce(&str) -> Result<(P, Vec<String>)>,
+    {
+        let key = normalize_statement(statement);
+        if let Some(plan) = self.lookup(&key) {
+            return Ok(plan);
+        }
+
+        let planned_at = self.catalog.schema_version();
+        let (plan, tables) = plan(&key)?;
+        let plan = Arc::new(plan);
+        let tables: Vec<_> = tables
+            .into_iter()
+            .map(|table| {
+                let version = self.catalog.table_version(&table);
+                (table, version)
+            })
+            .collect();
+        // A table changed while the statement was planned may have been
+        // planned at its old definition, so the plan is used once only
+        if tables.iter().any(|(_, version)| *version > planned_at) {
+            return Ok(plan);
+        }
+
+        let mut state = self.state.lock();
+        if !state.plans.contains_key(&key) && state.plans.len() >= self.capacity {
+            let oldest = state
+                .plans
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                state.plans.remove(&oldest);
+                state.stats.evictions += 1;
+            }
+        }
+        let last_used = state.tick;
+        state.plans.insert(
+            key,
+            CachedPlan {
+                plan: Arc::clone(&plan),
+                tables,
+                last_used,
+            },
+        );
+        Ok(plan)
+    }
+
+    /// Returns the cached plan for the normalized text `key` if it is still
+    /// valid, counting the hit or miss.
+    fn lookup(&self, key: &str) -> Option<Arc<P>> {
+        let mut state = self.state.lock();
+        state.tick += 1;
+        let tick = state.tick;
+        let valid = state.plans.get(key).map(|cached| {
+            cached
+                .tables
+                .iter()
+                .all(|(table, version)| self.catalog.table_version(table) == *version)
+        });
+        match valid {
+            Some(true) => {
+                state.stats.hits += 1;
+                let cached = state.plans.get_mut(key).expect("plan just found");
+                cached.last_used = tick;
+                Some(Arc::clone(&cached.plan))
+            }
+            Some(false) => {
+                state.plans.remove(key);
+                state.stats.invalidations += 1;
+                state.stats.misses += 1;
+                None
+            }
+            None => {
+                state.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Throws away every plan reading the table called `table`.
+    pub fn invalidate_table(&self, table: &str) {
+        let mut state = self.state.lock();
+        let before = state.plans.len();
+        state
+            .plans
+            .retain(|_, cached| cached.tables.iter().all(|(name, _)| name != table));
+        state.stats.invalidations += (before - state.plans.len()) as u64;
+    }
+
+    /// Throws away every plan.
+    pub fn clear(&self) {
+        self.state.lock().plans.clear();
+    }
+
+    /// Returns the cache's counters.
+    pub fn stats(&self) -> PlanCacheStats {
+        let state = self.state.lock();
+        PlanCacheStats {
+            entries: state.plans.len(),
+            ..state.stats
+        }
+    }
+}
+
+/// Returns `statement` in the form plans are cached under: outside quotes,
+/// runs of whitespace become one space and letters are lowercased, and
+/// leading and trailing whitespace and semicolons are dropped. Text in
+/// single quotes (strings) and double quotes (identifiers) is kept as is.
+pub fn normalize_statement(statement: &str) -> String {
+    let mut normalized = String::with_capacity(statement.len());
+    let mut quote = None;
+    let mut space = false;
+    for c in statement.trim().trim_end_matches(';').trim_end().chars() {
+        match quote {
+            Some(q) => {
+                normalized.push(c);
+                if c == q {
+                    quote = None;
+                }
+            }
+            None if c.is_whitespace() => space = true,
+            None => {
+                if space {
+                    normalized.push(' ');
+                    space = false;
+                }
+                if c == '\'' || c == '"' {
+                    quote = Some(c);
+                }
+                normalized.extend(c.to_lowercase());
+            }
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::BufferPoolManager;
+    use crate::common::CrioError;
+    use crate::storage::disk::MemDiskManager;
+    use crate::tuple::{DataType, Schema};
+
+    #[test]
+    fn test_normalize_statement() {
+        assert_eq!(
+            normalize_statement("  SELECT *\n\tFROM  Users WHERE name = 'Ann  B' ;"),
+            "select * from users where name = 'Ann  B'"
+        );
+        assert_eq!(
+            normalize_statement("select \"Mixed  Case\" from t"),
+            "select \"Mixed  Case\" from t"
+        );
+    }
+
+    #[test]
+    fn test_plan_cache() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            16,
+            2,
+            Arc::new(MemDiskManager::new()) as _,
+        ));
+        let catalog = Arc::new(Catalog::create(bpm).unwrap());
+        let schema = || Schema::builder().column("id", DataType::Integer).build();
+        catalog.create_table("users", schema()).unwrap();
+        catalog.create_table("orders", schema()).unwrap();
+
+        let cache: PlanCache<String> = PlanCache::new(Arc::clone(&catalog)).with_capacity(2);
+        let plan = |text: &str, table: &str| {
+            let table = table.to_string();
+            cache.get_or_plan(text, |key| Ok((key.to_uppercase(), vec![table])))
+        };
+
+        let first = plan("SELECT * FROM users", "users").unwrap();
+        let again = plan("select *  from users;", "users").unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+        plan("SELECT * FROM orders", "orders").unwrap();
+        assert_eq!(
+            cache.stats(),
+            PlanCacheStats {
+                hits: 1,
+                misses: 2,
+                invalidations: 0,
+                evictions: 0,
+                entries: 2,
+            }
+        );
+
+        // An index on users invalidates its plan but not the orders one
+        catalog.create_index("users_id", "users", "id").unwrap();
+        let replanned = plan("SELECT * FROM users", "users").unwrap();
+        assert!(!Arc::ptr_eq(&first, &replanned));
+        plan("SELECT * FROM orders", "orders").unwrap();
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.invalidations), (2, 1));
+
+        // A third statement evicts the least recently used one
+        plan("SELECT 1 FROM users", "users").unwrap();
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.stats().entries, 2);
+
+        cache.invalidate_table("users");
+        assert_eq!(cache.stats().entries, 1);
+
+        let failed: Result<Arc<String>> =
+            cache.get_or_plan("bad", |_| Err(CrioError::UnknownTable("x".into())));
+        assert!(failed.is_err());
+        cache.clear();
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
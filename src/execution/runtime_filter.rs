@@ -0,0 +1,356 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use crate::index::BloomFilter;
+use crate::tuple::{Collation, Value};
+
+use super::hash_join::JoinKey;
+
+/// Most build keys a runtime filter checks as an exact list; a bloom filter
+/// is used past that
+pub const RUNTIME_FILTER_IN_LIST_KEYS: usize = 32;
+
+/// Most build keys a join makes a runtime filter for. Larger build sides
+/// would fill the bloom filter and let most rows through anyway.
+pub const RUNTIME_FILTER_MAX_KEYS: usize = 1024;
+
+/// How a `RuntimeFilter` checks values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeFilterKind {
+    /// Exactly, against the list of build keys
+    InList,
+    /// Against a bloom filter of the build keys, letting a few
+    /// non-matching values through
+    Bloom,
+}
+
+impl fmt::Display for RuntimeFilterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RuntimeFilterKind::InList => "in-list",
+            RuntimeFilterKind::Bloom => "bloom",
+        })
+    }
+}
+
+enum Keys {
+    InList(HashSet<JoinKey>),
+    Bloom(BloomFilter),
+}
+
+/// The keys of a hash join's build side, in a form a probe-side scan can
+/// check its rows against before they reach the join.
+///
+/// A value the filter rejects can't match any build row. A value it
+/// accepts may still not match: bloom filters let some through, and the
+/// join checks every row it gets anyway. Strings are checked under the
+/// join's collation.
+pub struct RuntimeFilter {
+    keys: Keys,
+    /// Collation the join's string keys were made under
+    collation: Collation,
+    /// Number of distinct build keys
+    key_count: usize,
+    /// Smallest and largest build key, if the keys are all comparable
+    range: Option<(Value, Value)>,
+}
+
+impl RuntimeFilter {
+    /// Makes a filter of the distinct build keys `keys`, made under
+    /// `collation`, or returns None if there are more than
+    /// `RUNTIME_FILTER_MAX_KEYS`.
+    pub(super) fn from_keys<'a, I>(keys: I, collation: Collation) -> Option<Self>
+    where
+        I: ExactSizeIterator<Item = &'a JoinKey>,
+    {
+        let key_count = keys.len();
+        if key_count > RUNTIME_FILTER_MAX_KEYS {
+            return None;
+        }
+        let mut range: Option<(Value, Value)> = None;
+        let mut comparable = true;
+        let mut list = HashSet::new();
+        let mut bloom = BloomFilter::new();
+        for key in keys {
+            let value = key_value(key);
+            if let Some((min, max)) = &mut range {
+                match (
+                    value.compare_collated(min, collation),
+                    value.compare_collated(max, collation),
+                ) {
+                    (Some(CmpOrdering::Less), _) => *min = value,
+                    (_, Some(CmpOrdering::Greater)) => *max = value,
+                    (Some(_), Some(_)) => {}
+                    _ => comparable = false,
+                }
+            } else {
+                range = Some((value.clone(), value));
+            }
+            if key_count <= RUNTIME_FILTER_IN_LIST_KEYS {
+                list.insert(key.clone());
+            } else {
+                bloom.insert(&key_bytes(key));
+            }
+        }
+        let keys = if key_count <= RUNTIME_FILTER_IN_LIST_KEYS {
+            Keys::InList(list)
+        } else {
+            Keys::Bloom(bloom)
+        };
+        Some(Self {
+            keys,
+            collation,
+            key_count,
+            range: range.filter(|_| comparable),
+        })
+    }
+
+    /// Returns how the filter checks values.
+    pub fn kind(&self) -> RuntimeFilterKind {
+        match self.keys {
+            Keys::InList(_) => RuntimeFilterKind::InList,
+            Keys::Bloom(_) => RuntimeFilterKind::Bloom,
+        }
+    }
+
+    /// Returns the collation strings are checked under.
+    pub fn collation(&self) -> Collation {
+        self.collation
+    }
+
+    /// Returns the number of distinct build keys.
+    pub fn key_count(&self) -> usize {
+        self.key_count
+    }
+
+    /// Returns the smallest and largest build key under the filter's
+    /// collation, if they are known.
+    pub fn range(&self) -> Option<(&Value, &Value)> {
+        self.range.as_ref().map(|(min, max)| (min, max))
+    }
+
+    /// Returns false if no build row has a key equal to `value`; true if
+    /// one may. NULLs never match.
+    pub fn may_match(&self, value: &Value) -> bool {
+        let Some(key) = JoinKey::of(value, self.collation) else {
+            return false;
+        };
+        if let Some((min, max)) = &self.range {
+            let below = value.compare_collated(min, self.collation) == Some(CmpOrdering::Less);
+            let above = value.compare_collated(max, self.collation) == Some(CmpOrdering::Greater);
+            if below || above {
+                return false;
+            }
+        }
+        match &self.keys {
+            Keys::InList(keys) => keys.contains(&key),
+            Keys::Bloom(bloom) => bloom.may_contain(&key_bytes(&key)),
+        }
+    }
+}
+
+/// Returns a value with the key `key`. A string comes back as its sort
+/// key, which is itself a string equal to the original under the collation
+/// the key was made under.
+fn key_value(key: &JoinKey) -> Value {
+    match key {
+        JoinKey::Boolean(b) => Value::Boolean(*b),
+        JoinKey::Integer(i) => Value::BigInt(*i),
+        JoinKey::Float(bits) => Value::Double(f64::from_bits(*bits)),
+        JoinKey::String(s) => Value::String(String::from_utf8_lossy(s).into_owned()),
+        JoinKey::Timestamp(t) => Value::Timestamp(*t),
+    }
+}
+
+/// Returns the bytes `key` is hashed as in a bloom filter: a tag byte for
+/// its class, then its value.
+fn key_bytes(key: &JoinKey) -> Vec<u8> {
+    let (tag, payload) = match key {
+        JoinKey::Boolean(b) => (0, vec![*b as u8]),
+        JoinKey::Integer(i) => (1, i.to_le_bytes().to_vec()),
+        JoinKey::Float(bits) => (2, bits.to_le_bytes().to_vec()),
+        JoinKey::String(s) => (3, s.clone()),
+        JoinKey::Timestamp(t) => (4, t.to_le_bytes().to_vec()),
+    };
+    let mut bytes = Vec::with_capacity(1 + payload.len());
+    bytes.push(tag);
+    bytes.extend(payload);
+    bytes
+}
+
+/// What a runtime filter did, for `EXPLAIN ANALYZE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RuntimeFilterStats {
+    /// How the filter checked values, or None if the join made none
+    pub kind: Option<RuntimeFilterKind>,
+    /// Distinct build keys in the filter
+    pub keys: usize,
+    /// Probe rows checked against the filter
+    pub rows_checked: u64,
+    /// Probe rows the filter rejected
+    pub rows_filtered: u64,
+    /// Probe pages skipped without being read
+    pub pages_skipped: u64,
+}
+
+impl fmt::Display for RuntimeFilterStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            Some(kind) => write!(
+                f,
+                "runtime filter={}({} keys) rows filtered={}/{} pages skipped={}",
+                kind, self.keys, self.rows_filtered, self.rows_checked, self.pages_skipped
+            ),
+            None => f.write_str("runtime filter=none"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SlotState {
+    filter: OnceLock<RuntimeFilter>,
+    rows_checked: AtomicU64,
+    rows_filtered: AtomicU64,
+    pages_skipped: AtomicU64,
+}
+
+/// RuntimeFilterSlot carries a runtime filter from a hash join to the scan
+/// feeding its probe side, and counts what the filter did.
+///
+/// A plan gives clones of one slot to the probe scan
+/// (`SeqScanExecutor::with_runtime_filter`) and to the join
+/// (`HashJoinExecutor::with_runtime_filter`). The join fills the slot once
+/// its build side is hashed, before the probe side is read; until then, or
+/// if the join makes no filter, the scan returns every row.
+#[derive(Clone, Default)]
+pub struct RuntimeFilterSlot {
+    state: Arc<SlotState>,
+}
+
+impl RuntimeFilterSlot {
+    /// Creates an empty slot.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fills the slot with `filter`. A slot already filled keeps its filter.
+    pub fn publish(&self, filter: RuntimeFilter) {
+        let _ = self.state.filter.set(filter);
+    }
+
+    /// Returns the filter, once the join has made one.
+    pub fn filter(&self) -> Option<&RuntimeFilter> {
+        self.state.filter.get()
+    }
+
+    /// Counts `checked` probe rows checked, of which `filtered` were
+    /// rejected.
+    pub(super) fn record_rows(&self, checked: u64, filtered: u64) {
+        self.state
+            .rows_checked
+            .fetch_add(checked, Ordering::Relaxed);
+        self.state
+            .rows_filtered
+            .fetch_add(filtered, Ordering::Relaxed);
+    }
+
+    /// Counts `pages` probe pages skipped.
+    pub(super) fn record_pages_skipped(&self, pages: u64) {
+        self.state.pages_skipped.fetch_add(pages, Ordering::Relaxed);
+    }
+
+    /// Returns what the filter has done so far.
+    pub fn stats(&self) -> RuntimeFilterStats {
+        let filter = self.filter();
+        RuntimeFilterStats {
+            kind: filter.map(RuntimeFilter::kind),
+            keys: filter.map_or(0, RuntimeFilter::key_count),
+            rows_checked: self.state.rows_checked.load(Ordering::Relaxed),
+            rows_filtered: self.state.rows_filtered.load(Ordering::Relaxed),
+            pages_skipped: self.state.pages_skipped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collated_filter(values: &[Value], collation: Collation) -> Option<RuntimeFilter> {
+        let keys: HashSet<_> = values
+            .iter()
+            .filter_map(|value| JoinKey::of(value, collation))
+            .collect();
+        RuntimeFilter::from_keys(keys.iter(), collation)
+    }
+
+    fn filter(values: &[Value]) -> Option<RuntimeFilter> {
+        collated_filter(values, Collation::Binary)
+    }
+
+    #[test]
+    fn test_runtime_filter() {
+        let small = filter(&[Value::Integer(3), Value::BigInt(7), Value::Null]).unwrap();
+        assert_eq!(small.kind(), RuntimeFilterKind::InList);
+        assert_eq!(small.key_count(), 2);
+        assert!(small.may_match(&Value::SmallInt(7)));
+        assert!(!small.may_match(&Value::Integer(5)));
+        assert!(!small.may_match(&Value::Integer(100)));
+        assert!(!small.may_match(&Value::Null));
+
+        let values: Vec<_> = (0..500).map(|i| Value::Integer(i * 2)).collect();
+        let bloom = filter(&values).unwrap();
+        assert_eq!(bloom.kind(), RuntimeFilterKind::Bloom);
+        assert_eq!(
+            bloom.range(),
+            Some((&Value::BigInt(0), &Value::BigInt(998)))
+        );
+        assert!(values.iter().all(|v| bloom.may_match(v)));
+        assert!(!bloom.may_match(&Value::Integer(1000)));
+        let false_positives = (0..500)
+            .filter(|i| bloom.may_match(&Value::Integer(i * 2 + 1)))
+            .count();
+        assert!(false_positives < 50);
+
+        let values: Vec<_> = (0..=RUNTIME_FILTER_MAX_KEYS as i32)
+            .map(Value::Integer)
+            .collect();
+        assert!(filter(&values).is_none());
+
+        // Keys of different classes have no common range
+        let mixed = filter(&[Value::Integer(1), Value::String("a".into())]).unwrap();
+        assert!(mixed.range().is_none());
+        assert!(mixed.may_match(&Value::String("a".into())));
+
+        // Strings match, and fall in the key range, under the collation
+        let names = ["Bob", "alice"].map(|name| Value::String(name.into()));
+        let binary = filter(&names).unwrap();
+        assert!(!binary.may_match(&Value::String("ALICE".into())));
+        let folded = collated_filter(&names, Collation::CaseInsensitive).unwrap();
+        assert!(folded.may_match(&Value::String("ALICE".into())));
+        assert!(folded.may_match(&Value::String("bob".into())));
+        assert!(!folded.may_match(&Value::String("Carol".into())));
+        assert_eq!(
+            folded.range(),
+            Some((&Value::String("alice".into()), &Value::String("bob".into())))
+        );
+    }
+
+    #[test]
+    fn test_runtime_filter_slot() {
+        let slot = RuntimeFilterSlot::new();
+        assert_eq!(slot.stats().to_string(), "runtime filter=none");
+        let other = slot.clone();
+        other.publish(filter(&[Value::Integer(1)]).unwrap());
+        other.publish(filter(&[Value::Integer(1), Value::Integer(2)]).unwrap());
+        slot.record_rows(10, 9);
+        slot.record_pages_skipped(2);
+        assert_eq!(
+            other.stats().to_string(),
+            "runtime filter=in-list(1 keys) rows filtered=9/10 pages skipped=2"
+        );
+    }
+}
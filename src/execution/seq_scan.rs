@@ -0,0 +1,460 @@
+use std::cmp::Ordering;
+use std::collections::{HashSet, VecDeque};
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+use crate::catalog::{BloomFilterInfo, TableInfo, ZoneMapInfo};
+use crate::common::{CancellationToken, CrioError, PageId, QueryBudget, RecordId, Result};
+use crate::tuple::{Collation, Tuple, Value};
+
+use super::{Batch, BatchExecutor, RuntimeFilterSlot, DEFAULT_BATCH_SIZE};
+
+/// Condition a row must meet to be returned.
+enum Predicate {
+    /// The column equals the value
+    Equal(usize, Value),
+    /// The column lies in the range
+    Range(usize, Bound<Value>, Bound<Value>),
+}
+
+impl Predicate {
+    fn matches(&self, tuple: &Tuple) -> bool {
+        let (column, start, end) = match self {
+            Predicate::Equal(column, value) => {
+                (column, Bound::Included(value), Bound::Included(value))
+            }
+            Predicate::Range(column, start, end) => (column, start.as_ref(), end.as_ref()),
+        };
+        let Some(value) = tuple.value(*column).filter(|v| !v.is_null()) else {
+            return false;
+        };
+        // Strings compare under the column's collation, as the bloom filters
+        // and zone maps do
+        let collation = tuple
+            .schema()
+            .column(*column)
+            .map_or(Collation::Binary, |c| c.collation());
+        let compare = |bound: &Value| value.compare_collated(bound, collation);
+        let after_start = match start {
+            Bound::Included(start) => {
+                matches!(compare(start), Some(Ordering::Greater | Ordering::Equal))
+            }
+            Bound::Excluded(start) => compare(start) == Some(Ordering::Greater),
+            Bound::Unbounded => true,
+        };
+        let before_end = match end {
+            Bound::Included(end) => matches!(compare(end), Some(Ordering::Less | Ordering::Equal)),
+            Bound::Excluded(end) => compare(end) == Some(Ordering::Less),
+            Bound::Unbounded => true,
+        };
+        after_start && before_end
+    }
+}
+
+/// Runtime filter from a hash join the scan feeds.
+struct ScanRuntimeFilter {
+    /// Position of the join key column in the scanned rows
+    column: usize,
+    slot: RuntimeFilterSlot,
+    /// Zone maps of the table, to skip pages outside the filter's key range
+    zone_map: Option<Arc<ZoneMapInfo>>,
+    /// Whether pages were skipped for the filter yet
+    pruned: bool,
+}
+
+/// SeqScanExecutor returns the live rows of a table, optionally only those
+/// whose column equals a value or lies in a range, reading one page at a
+/// time.
+///
+/// A full scan follows the page chain. An equality scan on the column the
+/// table's bloom filters cover reads only the pages of extents whose filter
+/// may hold the value, and a range scan on a column the table's zone maps
+/// cover reads only the pages whose min/max range overlaps it; both read in
+/// page ID order. Any other scan reads every page and filters the rows.
+///
+/// A scan feeding the probe side of a hash join can be given the join's
+/// runtime filter (`with_runtime_filter`): once the join has published it,
+/// rows whose key can't match are dropped as pages are read, and pages
+/// whose zone map range lies outside the build keys' range are not read.
+///
+/// The scan returns rows one at a time as an `Iterator`, or a `Batch` of
+/// whole pages' rows at a time as a `BatchExecutor`. The cancellation token
+/// is checked and the budget charged before each page is read.
+pub struct SeqScanExecutor {
+    table: Arc<TableInfo>,
+    /// Condition rows must meet, if any
+    predicate: Option<Predicate>,
+    /// Pages not read yet
+    pages: VecDeque<PageId>,
+    /// Pages the scan reads in all
+    page_count: usize,
+    /// Rows of the current page not returned yet
+    pending: VecDeque<(RecordId, Tuple)>,
+    /// Stops the scan when cancelled
+    cancellation: CancellationToken,
+    /// Charged for the pages and rows read
+    budget: QueryBudget,
+    /// Most rows returned per batch
+    batch_size: usize,
+    /// Filter pushed down from a hash join, if any
+    runtime_filter: Option<ScanRuntimeFilter>,
+}
+
+impl SeqScanExecutor {
+    /// Starts a scan of every row of `table`.
+    pub fn new(table: Arc<TableInfo>) -> Result<Self> {
+        let pages = table.heap.page_ids()?;
+        Ok(Self::with_pages(table, None, pages))
+    }
+
+    /// Starts a scan of the rows of `table` whose column `column` equals
+    /// `value`, skipping extents through `bloom` if it covers that column.
+    pub fn with_equality(
+        table: Arc<TableInfo>,
+        bloom: Option<&BloomFilterInfo>,
+        column: usize,
+        value: Value,
+    ) -> Result<Self> {
+        let candidates = match bloom {
+            Some(bloom) if bloom.key_column == column => bloom.candidate_pages(&table, &value)?,
+            _ => None,
+        };
+        let pages = match candidates {
+            Some(pages) => pages,
+            None => table.heap.page_ids()?,
+        };
+        Ok(Self::with_pages(
+            table,
+            Some(Predicate::Equal(column, value)),
+            pages,
+        ))
+    }
+
+    /// Starts a scan of the rows of `table` whose column `column` lies in
+    /// `range`, skipping pages through `zone_map` if it covers that column.
+    pub fn with_range<R: RangeBounds<Value>>(
+        table: Arc<TableInfo>,
+        zone_map: Option<&ZoneMapInfo>,
+        column: usize,
+        range: R,
+    ) -> Result<Self> {
+        let bounds = (range.start_bound(), range.end_bound());
+        let candidates = match zone_map {
+            Some(zone_map) => zone_map.candidate_pages(&table, column, bounds)?,
+            None => None,
+        };
+        let pages = match candidates {
+            Some(pages) => pages,
+            None => table.heap.page_ids()?,
+        };
+        let predicate = Predicate::Range(column, bounds.0.cloned(), bounds.1.cloned());
+        Ok(Self::with_pages(table, Some(predicate), pages))
+    }
+
+    fn with_pages(table: Arc<TableInfo>, predicate: Option<Predicate>, pages: Vec<PageId>) -> Self {
+        Self {
+            table,
+            predicate,
+            page_count: pages.len(),
+            pages: pages.into(),
+            pending: VecDeque::new(),
+            cancellation: CancellationToken::new(),
+            budget: QueryBudget::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            runtime_filter: None,
+        }
+    }
+
+    /// Stops the scan with `QueryCancelled` once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Charges the pages and row bytes the scan reads to `budget`, failing
+    /// with `BudgetExceeded` once it runs out.
+    pub fn with_budget(mut self, budget: QueryBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Returns up to `batch_size` rows per batch, at least one.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Drops rows whose column `column` can't match the hash join filter
+    /// published to `slot`, skipping pages through `zone_map` if it covers
+    /// that column.
+    pub fn with_runtime_filter(
+        mut self,
+        column: usize,
+        slot: RuntimeFilterSlot,
+        zone_map: Option<Arc<ZoneMapInfo>>,
+    ) -> Self {
+        self.runtime_filter = Some(ScanRuntimeFilter {
+            column,
+            slot,
+            zone_map,
+            pruned: false,
+        });
+        self
+    }
+
+    /// Returns how many pages the scan reads.
+    pub fn page_count(&self) -> usize {
+        self.page_count
+    }
+
+    /// Reads the next page and queues its matching rows.
+    fn read_page(&mut self, page_id: PageId) -> Result<()> {
+        self.budget.charge_pages(1)?;
+        let (mut checked, mut filtered) = (0, 0);
+        for row in self.table.heap.iter_pages(vec![page_id]) {
+            let (record_id, data) = row?;
+            self.budget.charge_bytes(data.len() as u64)?;
+            let tuple = Tuple::from_bytes(Arc::clone(&self.table.schema), &data)
+                .ok_or(CrioError::SchemaMismatch)?;
+            if !self.predicate.as_ref().is_none_or(|p| p.matches(&tuple)) {
+                continue;
+            }
+            if let Some((column, filter)) = self
+                .runtime_filter
+                .as_ref()
+                .and_then(|rf| rf.slot.filter().map(|filter| (rf.column, filter)))
+            {
+                checked += 1;
+                if !filter.may_match(tuple.value(column).unwrap_or(&Value::Null)) {
+                    filtered += 1;
+                    continue;
+                }
+            }
+            self.pending.push_back((record_id, tuple));
+        }
+        if let Some(rf) = &self.runtime_filter {
+            rf.slot.record_rows(checked, filtered);
+        }
+        Ok(())
+    }
+
+    /// Returns the next page to read, first dropping the pages a runtime
+    /// filter published since the last page rules out.
+    fn next_page(&mut self) -> Result<Option<PageId>> {
+        if let Some(rf) = &mut self.runtime_filter {
+            if let (false, Some(filter)) = (rf.pruned, rf.slot.filter()) {
+                rf.pruned = true;
+                let before = self.pages.len();
+                if filter.key_count() == 0 {
+                    // An empty build side matches nothing
+                    self.pages.clear();
+                } else if let (Some(zone_map), Some((min, max))) = (&rf.zone_map, filter.range()) {
+                    // The zone maps order strings by the column's collation,
+                    // so the key range only bounds them under the same one
+                    let collation = self
+                        .table
+                        .schema
+                        .column(rf.column)
+                        .map_or(Collation::Binary, |c| c.collation());
+                    let range = (Bound::Included(min), Bound::Included(max));
+                    let candidates = if collation == filter.collation() {
+                        zone_map.candidate_pages(&self.table, rf.column, range)?
+                    } else {
+                        None
+                    };
+                    if let Some(candidates) = candidates {
+                        let candidates: HashSet<_> = candidates.into_iter().collect();
+                        self.pages.retain(|page_id| candidates.contains(page_id));
+                    }
+                }
+                rf.slot
+                    .record_pages_skipped((before - self.pages.len()) as u64);
+            }
+        }
+        Ok(self.pages.pop_front())
+    }
+
+    /// Returns the next row, or None at the end of the scan.
+    fn advance(&mut self) -> Result<Option<(RecordId, Tuple)>> {
+        loop {
+            if let Some(row) = self.pending.pop_front() {
+                return Ok(Some(row));
+            }
+            self.cancellation.check()?;
+            match self.next_page()? {
+                Some(page_id) => self.read_page(page_id)?,
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl SeqScanExecutor {
+    /// Returns the next batch of rows, or None at the end of the scan.
+    fn advance_batch(&mut self) -> Result<Option<Batch>> {
+        while self.pending.len() < self.batch_size {
+            self.cancellation.check()?;
+            match self.next_page()? {
+                Some(page_id) => self.read_page(page_id)?,
+                None => break,
+            }
+        }
+        if self.pending.is_empty() {
+            return Ok(None);
+        }
+        let count = self.pending.len().min(self.batch_size);
+        let rows = self.pending.drain(..count);
+        Ok(Some(Batch::from_rows(Arc::clone(&self.table.schema), rows)))
+    }
+}
+
+impl BatchExecutor for SeqScanExecutor {
+    fn next_batch(&mut self) -> Option<Result<Batch>> {
+        match self.advance_batch() {
+            Ok(batch) => batch.map(Ok),
+            Err(e) => {
+                self.pages.clear();
+                self.pending.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl Iterator for SeqScanExecutor {
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(row) => row.map(Ok),
+            Err(e) => {
+                // Stop after the first error
+                self.pages.clear();
+                self.pending.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::BufferPoolManager;
+    use crate::catalog::Catalog;
+    use crate::storage::disk::MemDiskManager;
+    use crate::tuple::{DataType, Schema};
+
+    #[test]
+    fn test_seq_scan_skips_extents() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            64,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ));
+        let catalog = Catalog::create(bpm).unwrap();
+        let schema = Schema::builder()
+            .column("id", DataType::Integer)
+            .column("pad", DataType::VarChar(200))
+            .build();
+        let table = catalog.create_table("t", schema).unwrap();
+        for i in 0..2000 {
+            let tuple = Tuple::new(
+                Arc::clone(&table.schema),
+                vec![Value::Integer(i), Value::String("x".repeat(100))],
+            );
+            table.heap.insert_tuple(&tuple.to_bytes().unwrap()).unwrap();
+        }
+        let bloom = catalog.create_bloom_filter("t", "id").unwrap();
+        let total_pages = table.heap.page_ids().unwrap().len();
+
+        let full = SeqScanExecutor::new(Arc::clone(&table)).unwrap();
+        assert_eq!(full.page_count(), total_pages);
+        assert_eq!(full.count(), 2000);
+
+        let scan = SeqScanExecutor::with_equality(
+            Arc::clone(&table),
+            Some(&bloom),
+            0,
+            Value::BigInt(1234),
+        )
+        .unwrap();
+        assert!(scan.page_count() < total_pages / 4);
+        let rows: Vec<_> = scan.map(|row| row.unwrap().1).collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].value(0), Some(&Value::Integer(1234)));
+
+        // Without a filter on the column every page is read
+        let scan = SeqScanExecutor::with_equality(Arc::clone(&table), Some(&bloom), 1, Value::Null)
+            .unwrap();
+        assert_eq!(scan.page_count(), total_pages);
+        assert_eq!(scan.count(), 0);
+
+        // Rows inserted after the filters were built are found
+        let tuple = Tuple::new(
+            Arc::clone(&table.schema),
+            vec![Value::Integer(5000), Value::String(String::new())],
+        );
+        let record_id = table.heap.insert_tuple(&tuple.to_bytes().unwrap()).unwrap();
+        bloom.insert(tuple.values(), record_id);
+        let scan = SeqScanExecutor::with_equality(
+            Arc::clone(&table),
+            Some(&bloom),
+            0,
+            Value::Integer(5000),
+        )
+        .unwrap();
+        assert_eq!(scan.count(), 1);
+    }
+
+    #[test]
+    fn test_seq_scan_skips_pages_by_zone() {
+        let bpm = Arc::new(BufferPoolManager::new(
+            64,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ));
+        let catalog = Catalog::create(bpm).unwrap();
+        let schema = Schema::builder()
+            .column("id", DataType::Integer)
+            .column("pad", DataType::VarChar(200))
+            .build();
+        let table = catalog.create_table("t", schema).unwrap();
+        let zone_map = catalog.create_zone_map("t", &["id"]).unwrap();
+        let insert = |id: i32| {
+            let tuple = Tuple::new(
+                Arc::clone(&table.schema),
+                vec![Value::Integer(id), Value::String("x".repeat(100))],
+            );
+            let record_id = table.heap.insert_tuple(&tuple.to_bytes().unwrap()).unwrap();
+            zone_map.insert(tuple.values(), record_id);
+        };
+        for id in 0..1000 {
+            insert(id);
+        }
+        let total_pages = table.heap.page_ids().unwrap().len();
+
+        let range = Value::Integer(100)..Value::Integer(150);
+        let scan =
+            SeqScanExecutor::with_range(Arc::clone(&table), Some(&zone_map), 0, range).unwrap();
+        assert!(scan.page_count() <= 3, "{} pages", scan.page_count());
+        let ids: Vec<_> = scan.map(|row| row.unwrap().1.values()[0].clone()).collect();
+        assert_eq!(ids, (100..150).map(Value::Integer).collect::<Vec<_>>());
+
+        // Rows added after the maps are built widen them
+        insert(-5);
+        let scan = SeqScanExecutor::with_range(
+            Arc::clone(&table),
+            Some(&zone_map),
+            0,
+            ..=Value::Integer(0),
+        )
+        .unwrap();
+        assert_eq!(scan.count(), 2);
+
+        // Columns without a zone map read every page
+        let scan = SeqScanExecutor::with_range(Arc::clone(&table), Some(&zone_map), 1, ..).unwrap();
+        assert_eq!(scan.page_count(), total_pages);
+        assert_eq!(scan.count(), 1001);
+    }
+}
@@ -0,0 +1,416 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::common::{
+    CancellationToken, CrioError, MemoryReservation, PageId, QueryBudget, RecordId, Result, SlotId,
+    CANCEL_CHECK_ROWS,
+};
+use crate::storage::disk::TempFileManager;
+use crate::storage::table::TempTable;
+use crate::tuple::{Column, DataType, Schema, Tuple, Value};
+
+/// One `ORDER BY` key of a `SortExecutor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    /// Position of the key column in input rows
+    pub column: usize,
+    /// Whether larger values come first
+    pub descending: bool,
+}
+
+impl SortKey {
+    /// Sorts on `column`, smallest first and NULLs last.
+    pub fn asc(column: usize) -> Self {
+        Self {
+            column,
+            descending: false,
+        }
+    }
+
+    /// Sorts on `column`, largest first and NULLs first.
+    pub fn desc(column: usize) -> Self {
+        Self {
+            column,
+            descending: true,
+        }
+    }
+}
+
+/// SortExecutor returns the rows of an executor ordered on its `SortKey`s,
+/// comparing values with each key column's collation. Rows with equal keys
+/// keep their input order, and each keeps its input record ID.
+///
+/// The input is read in full on the first call to `next`, into a buffer
+/// held in a `MemoryReservation` of the query's budget. Once the buffer
+/// would pass the budget's `spill_memory_bytes`, and a scratch file was
+/// given with `with_spill`, the buffer is sorted and written out as a run
+/// in scratch pages; the runs are merged as rows are returned. Without a
+/// scratch file the sort stays in memory and fails with `BudgetExceeded`
+/// past `max_memory_bytes`.
+pub struct SortExecutor<I> {
+    /// Input rows, until they are sorted
+    input: Option<I>,
+    keys: Vec<SortKey>,
+    /// Scratch file runs are spilled to
+    temp: Option<Arc<TempFileManager>>,
+    budget: QueryBudget,
+    /// Stops the sort when cancelled
+    cancellation: CancellationToken,
+    /// Sorted rows, once the input is read
+    output: Option<SortOutput>,
+    /// Runs written to scratch pages
+    spilled_runs: usize,
+    /// Rows read, then rows returned
+    rows: usize,
+}
+
+/// Where a `SortExecutor` returns rows from.
+enum SortOutput {
+    /// The input fit in memory
+    Memory {
+        rows: std::vec::IntoIter<(RecordId, Tuple)>,
+        /// Held until the sort is dropped
+        _memory: MemoryReservation,
+    },
+    /// Merging spilled runs
+    Merge(Merge),
+}
+
+impl<I> SortExecutor<I>
+where
+    I: Iterator<Item = Result<(RecordId, Tuple)>>,
+{
+    /// Creates a sort of the rows of `input` on `keys`, the first key
+    /// deciding first.
+    pub fn new(input: I, keys: Vec<SortKey>) -> Self {
+        Self {
+            input: Some(input),
+            keys,
+            temp: None,
+            budget: QueryBudget::default(),
+            cancellation: CancellationToken::new(),
+            output: None,
+            spilled_runs: 0,
+            rows: 0,
+        }
+    }
+
+    /// Stops the sort with `QueryCancelled` once `cancellation` is
+    /// cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Reserves the sort buffer from `budget`.
+    pub fn with_budget(mut self, budget: QueryBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Spills sorted runs to the scratch file of `temp` once the buffer
+    /// passes the budget's `spill_memory_bytes`.
+    pub fn with_spill(mut self, temp: Arc<TempFileManager>) -> Self {
+        self.temp = Some(temp);
+        self
+    }
+
+    /// Returns the number of runs written to scratch pages so far.
+    pub fn spilled_runs(&self) -> usize {
+        self.spilled_runs
+    }
+
+    /// Reads and sorts the input.
+    fn sort(&mut self, mut input: I) -> Result<SortOutput> {
+        let mut memory = self.budget.reserve_memory();
+        let mut buffer = Vec::new();
+        let mut runs = Vec::new();
+        for row in &mut input {
+            if self.rows.is_multiple_of(CANCEL_CHECK_ROWS) && self.cancellation.is_cancelled() {
+                return Err(CrioError::QueryCancelled);
+            }
+            let row = row?;
+            self.rows += 1;
+            let size = row.1.memory_size() as u64;
+            match &self.temp {
+                Some(_) if !memory.try_grow(size) => {
+                    if !buffer.is_empty() {
+                        runs.push(self.spill(&mut buffer)?);
+                        memory.free();
+                    }
+                    memory.grow(size)?;
+                }
+                Some(_) => {}
+                None => memory.grow(size)?,
+            }
+            buffer.push(row);
+        }
+        self.rows = 0;
+
+        if runs.is_empty() {
+            sort_rows(&self.keys, &mut buffer);
+            return Ok(SortOutput::Memory {
+                rows: buffer.into_iter(),
+                _memory: memory,
+            });
+        }
+        if !buffer.is_empty() {
+            runs.push(self.spill(&mut buffer)?);
+        }
+        Ok(SortOutput::Merge(Merge::new(self.keys.clone(), runs)?))
+    }
+
+    /// Sorts the rows of `buffer` and moves them into a new run.
+    fn spill(&mut self, buffer: &mut Vec<(RecordId, Tuple)>) -> Result<Run> {
+        let temp = self
+            .temp
+            .as_ref()
+            .expect("runs are only spilled with a temp file");
+        sort_rows(&self.keys, buffer);
+        let input = Arc::clone(buffer[0].1.schema());
+        let columns = input
+            .columns()
+            .cloned()
+            .chain([
+                Column::new("__page", DataType::BigInt, false),
+                Column::new("__slot", DataType::Integer, false),
+            ])
+            .collect();
+        let name = format!("sort_run_{}", self.spilled_runs);
+        let mut table = TempTable::new(name, Schema::new(columns), Arc::clone(temp));
+        for (record_id, tuple) in buffer.drain(..) {
+            let mut values = tuple.values().to_vec();
+            values.push(Value::BigInt(record_id.page_id.as_u32() as i64));
+            values.push(Value::Integer(record_id.slot_id.as_u16() as i32));
+            table.insert(values)?;
+        }
+        self.spilled_runs += 1;
+        Ok(Run {
+            table,
+            schema: input,
+            next_page: 0,
+            rows: VecDeque::new(),
+        })
+    }
+}
+
+impl<I> Iterator for SortExecutor<I>
+where
+    I: Iterator<Item = Result<(RecordId, Tuple)>>,
+{
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(input) = self.input.take() {
+            match self.sort(input) {
+                Ok(output) => self.output = Some(output),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        if self.rows.is_multiple_of(CANCEL_CHECK_ROWS) && self.cancellation.is_cancelled() {
+            self.output = None;
+            return Some(Err(CrioError::QueryCancelled));
+        }
+        let row = match self.output.as_mut()? {
+            SortOutput::Memory { rows, .. } => rows.next().map(Ok),
+            SortOutput::Merge(merge) => merge.next().transpose(),
+        };
+        if matches!(row, Some(Err(_))) {
+            self.output = None;
+        }
+        self.rows += 1;
+        row
+    }
+}
+
+/// Sorts `rows` on `keys`, keeping rows with equal keys in order.
+fn sort_rows(keys: &[SortKey], rows: &mut [(RecordId, Tuple)]) {
+    rows.sort_by(|(_, a), (_, b)| compare_rows(keys, a, b));
+}
+
+/// Compares two rows on `keys`. NULLs sort after every value, before them
+/// for descending keys, and values that don't compare are taken as equal.
+pub(super) fn compare_rows(keys: &[SortKey], a: &Tuple, b: &Tuple) -> Ordering {
+    for key in keys {
+        let (x, y) = (a.value(key.column), b.value(key.column));
+        let null = |v: Option<&Value>| matches!(v, None | Some(Value::Null));
+        let ordering = match (null(x), null(y)) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => a
+                .schema()
+                .column(key.column)
+                .zip(x.zip(y))
+                .and_then(|(column, (x, y))| column.compare(x, y))
+                .unwrap_or(Ordering::Equal),
+        };
+        let ordering = if key.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// A sorted run of rows in scratch pages, each row followed by the page and
+/// slot of its record ID.
+struct Run {
+    table: TempTable,
+    /// Schema of the rows as they were sorted
+    schema: Arc<Schema>,
+    /// Index of the next page to read
+    next_page: usize,
+    /// Rows of the current page not returned yet
+    rows: VecDeque<(RecordId, Tuple)>,
+}
+
+impl Run {
+    /// Returns the run's next row, with its input record ID.
+    fn next(&mut self) -> Result<Option<(RecordId, Tuple)>> {
+        while self.rows.is_empty() {
+            if self.next_page >= self.table.page_count() {
+                return Ok(None);
+            }
+            self.rows.extend(self.table.read_page(self.next_page)?);
+            self.next_page += 1;
+        }
+        let (_, tuple) = self.rows.pop_front().expect("page rows just read");
+        let mut values = tuple.values().to_vec();
+        let (Some(Value::Integer(slot)), Some(Value::BigInt(page))) = (values.pop(), values.pop())
+        else {
+            return Err(CrioError::SchemaMismatch);
+        };
+        let record_id = RecordId::new(PageId::new(page as u32), SlotId::new(slot as u16));
+        Ok(Some((
+            record_id,
+            Tuple::new(Arc::clone(&self.schema), values),
+        )))
+    }
+}
+
+/// Merge of sorted runs, taking the smallest head row each time. Runs are
+/// in input order and ties go to the earlier run, so the sort stays stable.
+struct Merge {
+    keys: Vec<SortKey>,
+    runs: Vec<Run>,
+    /// Next row of each run
+    heads: Vec<Option<(RecordId, Tuple)>>,
+}
+
+impl Merge {
+    fn new(keys: Vec<SortKey>, mut runs: Vec<Run>) -> Result<Self> {
+        let heads = runs.iter_mut().map(Run::next).collect::<Result<_>>()?;
+        Ok(Self { keys, runs, heads })
+    }
+
+    fn next(&mut self) -> Result<Option<(RecordId, Tuple)>> {
+        let mut smallest: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let Some((_, tuple)) = head else { continue };
+            let smaller = match smallest.and_then(|s| self.heads[s].as_ref()) {
+                Some((_, best)) => compare_rows(&self.keys, tuple, best) == Ordering::Less,
+                None => true,
+            };
+            if smaller {
+                smallest = Some(i);
+            }
+        }
+        let Some(i) = smallest else {
+            return Ok(None);
+        };
+        let next = self.runs[i].next()?;
+        Ok(std::mem::replace(&mut self.heads[i], next))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::ResourceLimits;
+    use crate::execution::test_util::int_string_rows as rows;
+
+    fn slots(sort: SortExecutor<impl Iterator<Item = Result<(RecordId, Tuple)>>>) -> Vec<u16> {
+        sort.map(|row| row.unwrap().0.slot_id.as_u16()).collect()
+    }
+
+    #[test]
+    fn test_sort() {
+        let input = || {
+            rows(&[
+                (Some(3), "c"),
+                (None, "x"),
+                (Some(1), "b"),
+                (Some(3), "a"),
+                (Some(1), "a"),
+            ])
+            .into_iter()
+        };
+
+        let sort = SortExecutor::new(input(), vec![SortKey::asc(0)]);
+        assert_eq!(slots(sort), vec![2, 4, 0, 3, 1]);
+        let sort = SortExecutor::new(input(), vec![SortKey::desc(0), SortKey::asc(1)]);
+        assert_eq!(slots(sort), vec![1, 3, 0, 4, 2]);
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let mut sort = SortExecutor::new(input(), vec![SortKey::asc(0)]).with_cancellation(token);
+        assert!(matches!(sort.next(), Some(Err(CrioError::QueryCancelled))));
+    }
+
+    #[test]
+    fn test_sort_spills_past_memory_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = Arc::new(TempFileManager::new(dir.path().join("sort.tmp"), 64).unwrap());
+        let values: Vec<_> = (0..2000)
+            .map(|i| (Some((i * 7919) % 2000), "row"))
+            .collect();
+        let budget = || {
+            QueryBudget::new(ResourceLimits {
+                max_memory_bytes: Some(64 * 1024),
+                spill_memory_bytes: Some(16 * 1024),
+                ..Default::default()
+            })
+        };
+
+        // Without a scratch file the sort runs out of memory
+        let mut sort = SortExecutor::new(rows(&values).into_iter(), vec![SortKey::asc(0)])
+            .with_budget(budget());
+        assert!(matches!(
+            sort.next(),
+            Some(Err(CrioError::BudgetExceeded {
+                resource: "memory",
+                ..
+            }))
+        ));
+        assert!(sort.next().is_none());
+
+        let budget = budget();
+        let mut sort = SortExecutor::new(rows(&values).into_iter(), vec![SortKey::asc(0)])
+            .with_budget(budget.clone())
+            .with_spill(Arc::clone(&temp));
+        let first = sort.next().unwrap().unwrap();
+        assert!(sort.spilled_runs() > 1);
+        assert!(budget.usage().peak_memory_bytes <= 16 * 1024);
+        let sorted: Vec<_> = std::iter::once(first)
+            .chain(sort.by_ref().map(|row| row.unwrap()))
+            .map(|(record_id, tuple)| (tuple.value(0).cloned(), record_id.slot_id.as_u16()))
+            .collect();
+        let expected: Vec<_> = (0..2000)
+            .map(|n| {
+                let slot = (0..2000).find(|i| (i * 7919) % 2000 == n).unwrap();
+                (Some(Value::Integer(n)), slot as u16)
+            })
+            .collect();
+        assert_eq!(sorted, expected);
+
+        drop(sort);
+        assert_eq!(temp.allocated_pages(), 0);
+        assert_eq!(budget.usage().memory_bytes, 0);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::common::{CrioError, Result};
+use crate::tuple::{DataType, Value};
+
+/// A SQL string function, applied a batch at a time by `BatchExpression`.
+///
+/// Functions return NULL if any argument is NULL, except `CONCAT`, which
+/// skips NULLs. Positions and lengths count characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringFunction {
+    /// `UPPER(s)`
+    Upper,
+    /// `LOWER(s)`
+    Lower,
+    /// `LENGTH(s)`, as an Integer
+    Length,
+    /// `SUBSTR(s, start [, count])`: the characters of `s` from the 1-based
+    /// position `start` on, or only the `count` positions from there
+    Substr,
+    /// `CONCAT(values...)`: the values as text, one after another
+    Concat,
+    /// `TRIM(s)`: `s` without its leading and trailing spaces
+    Trim,
+}
+
+impl StringFunction {
+    /// Returns the SQL name of the function.
+    pub fn name(&self) -> &'static str {
+        match self {
+            StringFunction::Upper => "UPPER",
+            StringFunction::Lower => "LOWER",
+            StringFunction::Length => "LENGTH",
+            StringFunction::Substr => "SUBSTR",
+            StringFunction::Concat => "CONCAT",
+            StringFunction::Trim => "TRIM",
+        }
+    }
+
+    /// Returns the type of the function's values.
+    pub fn data_type(&self) -> DataType {
+        match self {
+            StringFunction::Length => DataType::Integer,
+            _ => DataType::VarChar(u16::MAX),
+        }
+    }
+
+    /// Applies the function to one row's arguments. Fails with
+    /// `InvalidArguments` if there are too few or too many of them, or one
+    /// has a type the function doesn't take.
+    pub fn apply(&self, args: &[Value]) -> Result<Value> {
+        let invalid = || CrioError::InvalidArguments(self.name());
+        let arity = match self {
+            StringFunction::Substr => (2..=3).contains(&args.len()),
+            StringFunction::Concat => true,
+            _ => args.len() == 1,
+        };
+        if !arity {
+            return Err(invalid());
+        }
+        if *self != StringFunction::Concat && args.iter().any(Value::is_null) {
+            return Ok(Value::Null);
+        }
+
+        let text = |value: &Value| match value {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(invalid()),
+        };
+        Ok(Value::String(match self {
+            StringFunction::Upper => text(&args[0])?.to_uppercase(),
+            StringFunction::Lower => text(&args[0])?.to_lowercase(),
+            StringFunction::Length => {
+                return Ok(Value::Integer(text(&args[0])?.chars().count() as i32))
+            }
+            StringFunction::Trim => text(&args[0])?.trim_matches(' ').to_string(),
+            StringFunction::Substr => {
+                let integer = |value: &Value| match value {
+                    Value::TinyInt(n) => Ok(*n as i64),
+                    Value::SmallInt(n) => Ok(*n as i64),
+                    Value::Integer(n) => Ok(*n as i64),
+                    Value::BigInt(n) => Ok(*n),
+                    _ => Err(invalid()),
+                };
+                let start = integer(&args[1])?;
+                let count = args.get(2).map(integer).transpose()?;
+                if count.is_some_and(|count| count < 0) {
+                    return Err(invalid());
+                }
+                // Positions before the first still use up the count
+                let end = count.map(|count| start.saturating_add(count));
+                let start = start.max(1);
+                let take = end.map_or(usize::MAX, |end| (end - start).max(0) as usize);
+                text(&args[0])?
+                    .chars()
+                    .skip(start as usize - 1)
+                    .take(take)
+                    .collect()
+            }
+            StringFunction::Concat => {
+                let mut out = String::new();
+                for arg in args {
+                    match arg {
+                        Value::Null => {}
+                        Value::String(s) => out.push_str(s),
+                        Value::Boolean(_)
+                        | Value::TinyInt(_)
+                        | Value::SmallInt(_)
+                        | Value::Integer(_)
+                        | Value::BigInt(_)
+                        | Value::Float(_)
+                        | Value::Double(_) => out.push_str(&arg.to_string()),
+                        _ => return Err(invalid()),
+                    }
+                }
+                out
+            }
+        }))
+    }
+}
+
+/// One element of a `LikePattern`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LikeToken {
+    /// `%`: any run of characters, including none
+    Any,
+    /// `_`: any one character
+    One,
+    /// Exactly this character
+    Char(char),
+}
+
+/// A compiled SQL `LIKE` pattern: `%` matches any run of characters, `_`
+/// any one character, and `\` makes the character after it literal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LikePattern {
+    tokens: Vec<LikeToken>,
+}
+
+impl LikePattern {
+    /// Compiles `pattern`. A trailing `\` matches itself.
+    pub fn new(pattern: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '%' if tokens.last() == Some(&LikeToken::Any) => continue,
+                '%' => LikeToken::Any,
+                '_' => LikeToken::One,
+                '\\' => LikeToken::Char(chars.next().unwrap_or('\\')),
+                c => LikeToken::Char(c),
+            });
+        }
+        Self { tokens }
+    }
+
+    /// Returns true if the whole of `text` matches the pattern.
+    pub fn matches(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        let (mut t, mut p) = (0, 0);
+        // Pattern position after the last `%` and the text position it was
+        // tried at, to backtrack to when a later token fails
+        let mut backtrack: Option<(usize, usize)> = None;
+        while t < text.len() {
+            match self.tokens.get(p) {
+                Some(LikeToken::Any) => {
+                    p += 1;
+                    backtrack = Some((p, t));
+                }
+                Some(LikeToken::One) => {
+                    p += 1;
+                    t += 1;
+                }
+                Some(LikeToken::Char(c)) if *c == text[t] => {
+                    p += 1;
+                    t += 1;
+                }
+                _ => match backtrack {
+                    // Let the `%` take one more character
+                    Some((after_any, tried)) => {
+                        p = after_any;
+                        t = tried + 1;
+                        backtrack = Some((after_any, t));
+                    }
+                    None => return false,
+                },
+            }
+        }
+        self.tokens[p..]
+            .iter()
+            .all(|token| *token == LikeToken::Any)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_like_pattern() {
+        let like = |text: &str, pattern: &str| LikePattern::new(pattern).matches(text);
+        assert!(like("hello", "hello"));
+        assert!(!like("hello", "hell"));
+        assert!(like("hello", "h%o"));
+        assert!(like("hello", "%"));
+        assert!(like("", "%%"));
+        assert!(!like("", "_"));
+        assert!(like("hello", "_e%l_"));
+        assert!(like("abcabcabd", "%abd"));
+        assert!(!like("abcabcabc", "%abd"));
+        assert!(like("naïve", "na_ve"));
+        assert!(like("100%", "100\\%"));
+        assert!(!like("1000", "100\\%"));
+        assert!(like("a_b", "a\\_b"));
+        assert!(!like("axb", "a\\_b"));
+    }
+
+    #[test]
+    fn test_string_functions() {
+        let s = |s: &str| Value::String(s.into());
+        let apply = |f: StringFunction, args: &[Value]| f.apply(args).unwrap();
+
+        assert_eq!(apply(StringFunction::Upper, &[s("Ab")]), s("AB"));
+        assert_eq!(apply(StringFunction::Lower, &[s("Ab")]), s("ab"));
+        assert_eq!(
+            apply(StringFunction::Length, &[s("naïve")]),
+            Value::Integer(5)
+        );
+        assert_eq!(apply(StringFunction::Trim, &[s("  a b ")]), s("a b"));
+        assert_eq!(
+            apply(
+                StringFunction::Concat,
+                &[s("a"), Value::Null, Value::Integer(1)]
+            ),
+            s("a1")
+        );
+        assert_eq!(apply(StringFunction::Upper, &[Value::Null]), Value::Null);
+
+        let substr = |start: i32, count: Option<i32>| {
+            let mut args = vec![s("hello"), Value::Integer(start)];
+            args.extend(count.map(Value::Integer));
+            apply(StringFunction::Substr, &args)
+        };
+        assert_eq!(substr(2, None), s("ello"));
+        assert_eq!(substr(2, Some(3)), s("ell"));
+        assert_eq!(substr(0, Some(3)), s("he"));
+        assert_eq!(substr(-5, Some(3)), s(""));
+        assert_eq!(substr(9, None), s(""));
+
+        for (function, args) in [
+            (StringFunction::Upper, vec![]),
+            (StringFunction::Length, vec![Value::Integer(1)]),
+            (StringFunction::Substr, vec![s("a"), s("b")]),
+            (
+                StringFunction::Substr,
+                vec![s("a"), Value::Integer(1), Value::Integer(-1)],
+            ),
+        ] {
+            assert!(matches!(
+                function.apply(&args),
+                Err(CrioError::InvalidArguments(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::common::{CancellationToken, CrioError, RecordId, Result, CANCEL_CHECK_ROWS};
+use crate::tuple::{Collation, Column, Schema, Tuple, Value};
+
+use super::hash_join::JoinKey;
+
+/// Distinct correlation values a `SubqueryCache` keeps results for by default
+pub const DEFAULT_SUBQUERY_CACHE_ENTRIES: usize = 1024;
+
+/// SubqueryCache runs a subquery and remembers its rows by the correlation
+/// values it was run with, so a correlated subquery runs once per distinct
+/// outer value instead of once per outer row.
+///
+/// `run` is given the correlation values and returns the subquery's rows,
+/// usually by starting an executor over them. An uncorrelated subquery is
+/// run with no values, so it runs once in all. Values equal under
+/// `Value::compare` share an entry, as do NULLs; runs with a value that
+/// cannot be hashed (NaN, JSON) are not cached. The cache is cleared when it
+/// holds `capacity` entries and a new one is added.
+pub struct SubqueryCache<F> {
+    run: F,
+    /// Rows by correlation values, None standing for NULL
+    entries: HashMap<Vec<Option<JoinKey>>, Arc<Vec<Tuple>>>,
+    /// Most entries kept
+    capacity: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl<F, I> SubqueryCache<F>
+where
+    F: FnMut(&[Value]) -> Result<I>,
+    I: IntoIterator<Item = Result<(RecordId, Tuple)>>,
+{
+    /// Creates a cache for the subquery `run`, keeping up to
+    /// `DEFAULT_SUBQUERY_CACHE_ENTRIES` results.
+    pub fn new(run: F) -> Self {
+        Self {
+            run,
+            entries: HashMap::new(),
+            capacity: DEFAULT_SUBQUERY_CACHE_ENTRIES,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Keeps up to `entries` results, at least one.
+    pub fn with_capacity(mut self, entries: usize) -> Self {
+        self.capacity = entries.max(1);
+        self
+    }
+
+    /// Returns the rows of the subquery for the correlation values `params`,
+    /// running it only if they aren't cached.
+    pub fn rows(&mut self, params: &[Value]) -> Result<Arc<Vec<Tuple>>> {
+        let key: Option<Vec<Option<JoinKey>>> = params
+            .iter()
+            .map(|value| match value {
+                Value::Null => Some(None),
+                // Binary keys: the subquery may tell apart strings that a
+                // case-insensitive column would not
+                value => JoinKey::of(value, Collation::Binary).map(Some),
+            })
+            .collect();
+        if let Some(rows) = key.as_ref().and_then(|key| self.entries.get(key)) {
+            self.hits += 1;
+            return Ok(Arc::clone(rows));
+        }
+
+        self.misses += 1;
+        let rows = (self.run)(params)?
+            .into_iter()
+            .map(|row| row.map(|(_, tuple)| tuple))
+            .collect::<Result<Vec<_>>>()?;
+        let rows = Arc::new(rows);
+        if let Some(key) = key {
+            if self.entries.len() >= self.capacity {
+                self.entries.clear();
+            }
+            self.entries.insert(key, Arc::clone(&rows));
+        }
+        Ok(rows)
+    }
+
+    /// Returns the value of the scalar subquery for `params` (see
+    /// `scalar_value`).
+    pub fn scalar(&mut self, params: &[Value]) -> Result<Value> {
+        scalar_value(&self.rows(params)?)
+    }
+
+    /// Returns true if the subquery has rows for `params`.
+    pub fn exists(&mut self, params: &[Value]) -> Result<bool> {
+        Ok(!self.rows(params)?.is_empty())
+    }
+
+    /// Returns the number of lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of times the subquery ran.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Returns the number of results cached.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no results are cached.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Returns the value of a scalar subquery with result `rows`: the first
+/// column of its only row, or NULL if it has none. More than one row fails
+/// with `SubqueryCardinality`.
+pub fn scalar_value(rows: &[Tuple]) -> Result<Value> {
+    match rows {
+        [] => Ok(Value::Null),
+        [row] => Ok(row.value(0).cloned().unwrap_or(Value::Null)),
+        _ => Err(CrioError::SubqueryCardinality),
+    }
+}
+
+/// How a `SubqueryExecutor` uses its subquery.
+#[derive(Debug, Clone)]
+pub enum SubqueryKind {
+    /// Appends the subquery's scalar value to each outer row as `Column`
+    Scalar(Column),
+    /// Returns the outer rows the subquery has rows for
+    Exists,
+    /// Returns the outer rows the subquery has no rows for
+    NotExists,
+}
+
+/// SubqueryExecutor evaluates a correlated subquery for each row of an
+/// outer executor, passing it the values of the outer row's `params`
+/// columns through a `SubqueryCache`.
+///
+/// Uncorrelated `EXISTS` and `IN` subqueries, and correlated ones whose
+/// correlation is an equality, run faster as semi- or anti-joins (see
+/// `JoinType`); this executor covers the rest, such as scalar subqueries in
+/// the select list or correlations on ranges. The cancellation token is
+/// checked every `CANCEL_CHECK_ROWS` outer rows.
+pub struct SubqueryExecutor<P, F> {
+    outer: P,
+    /// Outer columns passed to the subquery
+    params: Vec<usize>,
+    kind: SubqueryKind,
+    cache: SubqueryCache<F>,
+    /// Schema of rows with a scalar appended, once the first one is made
+    schema: Option<Arc<Schema>>,
+    /// Outer rows read
+    rows_read: usize,
+    /// Stops the executor when cancelled
+    cancellation: CancellationToken,
+}
+
+impl<P, F, I> SubqueryExecutor<P, F>
+where
+    P: Iterator<Item = Result<(RecordId, Tuple)>>,
+    F: FnMut(&[Value]) -> Result<I>,
+    I: IntoIterator<Item = Result<(RecordId, Tuple)>>,
+{
+    /// Evaluates the subquery of `cache` as `kind` for each row of `outer`,
+    /// correlated on the outer columns `params`.
+    pub fn new(outer: P, params: &[usize], kind: SubqueryKind, cache: SubqueryCache<F>) -> Self {
+        Self {
+            outer,
+            params: params.to_vec(),
+            kind,
+            cache,
+            schema: None,
+            rows_read: 0,
+            cancellation: CancellationToken::new(),
+        }
+    }
+
+    /// Stops the executor with `QueryCancelled` once `token` is cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Returns the subquery cache, e.g. for its hit count.
+    pub fn cache(&self) -> &SubqueryCache<F> {
+        &self.cache
+    }
+
+    fn evaluate(&mut self, tuple: Tuple) -> Result<Option<Tuple>> {
+        let params: Vec<Value> = self
+            .params
+            .iter()
+            .map(|&column| tuple.value(column).cloned().unwrap_or(Value::Null))
+            .collect();
+        match &self.kind {
+            SubqueryKind::Scalar(column) => {
+                let value = self.cache.scalar(&params)?;
+                let schema = self.schema.get_or_insert_with(|| {
+                    let columns = tuple
+                        .schema()
+                        .columns()
+                        .cloned()
+                        .chain(std::iter::once(column.clone()))
+                        .collect();
+                    Arc::new(Schema::new(columns))
+                });
+                let mut values = tuple.values().to_vec();
+                values.push(value);
+                Ok(Some(Tuple::new(Arc::clone(schema), values)))
+            }
+            SubqueryKind::Exists => Ok(self.cache.exists(&params)?.then_some(tuple)),
+            SubqueryKind::NotExists => Ok((!self.cache.exists(&params)?).then_some(tuple)),
+        }
+    }
+}
+
+impl<P, F, I> Iterator for SubqueryExecutor<P, F>
+where
+    P: Iterator<Item = Result<(RecordId, Tuple)>>,
+    F: FnMut(&[Value]) -> Result<I>,
+    I: IntoIterator<Item = Result<(RecordId, Tuple)>>,
+{
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.rows_read.is_multiple_of(CANCEL_CHECK_ROWS) && self.cancellation.is_cancelled()
+            {
+                return Some(Err(CrioError::QueryCancelled));
+            }
+            let (record_id, tuple) = match self.outer.next()? {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e)),
+            };
+            self.rows_read += 1;
+            match self.evaluate(tuple) {
+                Ok(Some(tuple)) => return Some(Ok((record_id, tuple))),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::test_util::rows;
+    use crate::tuple::DataType;
+
+    #[test]
+    fn test_correlated_subquery() {
+        let users = Arc::new(
+            Schema::builder()
+                .nullable_column("id", DataType::Integer)
+                .build(),
+        );
+        let totals = Arc::new(Schema::builder().column("total", DataType::Integer).build());
+        let orders = [(1, 10), (2, 20), (2, 30)];
+
+        // (SELECT total FROM orders WHERE user_id = users.id)
+        let subquery = |params: &[Value]| -> Result<Vec<Result<(RecordId, Tuple)>>> {
+            let matching = orders
+                .iter()
+                .filter(|(user_id, _)| {
+                    params[0].compare(&Value::Integer(*user_id)) == Some(std::cmp::Ordering::Equal)
+                })
+                .map(|&(_, total)| vec![Value::Integer(total)])
+                .collect();
+            Ok(rows(&totals, matching))
+        };
+        let outer = rows(
+            &users,
+            vec![
+                vec![Value::Integer(1)],
+                vec![Value::Integer(3)],
+                vec![Value::Integer(1)],
+                vec![Value::Null],
+            ],
+        );
+        let column = Column::new("total", DataType::Integer, true);
+        let mut scalars = SubqueryExecutor::new(
+            outer.into_iter(),
+            &[0],
+            SubqueryKind::Scalar(column),
+            SubqueryCache::new(subquery),
+        );
+        let values: Vec<_> = scalars
+            .by_ref()
+            .map(|row| row.unwrap().1.values().to_vec())
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                vec![Value::Integer(1), Value::Integer(10)],
+                vec![Value::Integer(3), Value::Null],
+                vec![Value::Integer(1), Value::Integer(10)],
+                vec![Value::Null, Value::Null],
+            ]
+        );
+        // The repeated outer value came from the cache
+        assert_eq!(scalars.cache().misses(), 3);
+        assert_eq!(scalars.cache().hits(), 1);
+
+        // User 2 has two orders, too many for a scalar
+        let outer = rows(&users, vec![vec![Value::Integer(2)]]);
+        let mut scalars = SubqueryExecutor::new(
+            outer.into_iter(),
+            &[0],
+            SubqueryKind::Scalar(Column::new("total", DataType::Integer, true)),
+            SubqueryCache::new(subquery),
+        );
+        assert!(matches!(
+            scalars.next(),
+            Some(Err(CrioError::SubqueryCardinality))
+        ));
+
+        let ids = |kind: SubqueryKind| -> Vec<u16> {
+            let outer = rows(
+                &users,
+                vec![
+                    vec![Value::Integer(2)],
+                    vec![Value::Integer(3)],
+                    vec![Value::BigInt(2)],
+                ],
+            );
+            let cache = SubqueryCache::new(subquery);
+            let executor = SubqueryExecutor::new(outer.into_iter(), &[0], kind, cache);
+            executor
+                .map(|row| row.unwrap().0.slot_id.as_u16())
+                .collect()
+        };
+        assert_eq!(ids(SubqueryKind::Exists), vec![0, 2]);
+        assert_eq!(ids(SubqueryKind::NotExists), vec![1]);
+    }
+
+    #[test]
+    fn test_subquery_cache() {
+        let runs = std::cell::Cell::new(0);
+        let mut cache = SubqueryCache::new(|_: &[Value]| {
+            runs.set(runs.get() + 1);
+            Ok(Vec::new())
+        })
+        .with_capacity(2);
+
+        assert!(!cache.exists(&[]).unwrap());
+        assert_eq!(cache.scalar(&[]).unwrap(), Value::Null);
+        assert_eq!(runs.get(), 1);
+
+        // Integer and BigInt keys are equal; NaN is never cached
+        cache.rows(&[Value::Integer(5)]).unwrap();
+        cache.rows(&[Value::BigInt(5)]).unwrap();
+        cache.rows(&[Value::Double(f64::NAN)]).unwrap();
+        cache.rows(&[Value::Double(f64::NAN)]).unwrap();
+        assert_eq!(runs.get(), 4);
+        assert_eq!(cache.len(), 2);
+
+        // A third entry clears the cache
+        cache.rows(&[Value::Integer(6)]).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!((cache.hits(), cache.misses()), (2, 5));
+    }
+}
@@ -0,0 +1,41 @@
+//! Row builders shared by the executor tests.
+
+use std::sync::Arc;
+
+use crate::common::{PageId, RecordId, Result, SlotId};
+use crate::tuple::{DataType, Schema, Tuple, Value};
+
+/// Returns `values` as rows of `schema`, on page 1 with slot IDs in order.
+pub(super) fn rows(
+    schema: &Arc<Schema>,
+    values: Vec<Vec<Value>>,
+) -> Vec<Result<(RecordId, Tuple)>> {
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, values)| {
+            let record_id = RecordId::new(PageId::new(1), SlotId::new(i as u16));
+            Ok((record_id, Tuple::new(Arc::clone(schema), values)))
+        })
+        .collect()
+}
+
+/// Returns rows of a nullable integer column `n` and a string column `s`.
+pub(super) fn int_string_rows(values: &[(Option<i32>, &str)]) -> Vec<Result<(RecordId, Tuple)>> {
+    let schema = Arc::new(
+        Schema::builder()
+            .nullable_column("n", DataType::Integer)
+            .column("s", DataType::VarChar(16))
+            .build(),
+    );
+    let values = values
+        .iter()
+        .map(|(n, s)| {
+            vec![
+                n.map_or(Value::Null, Value::Integer),
+                Value::String(s.to_string()),
+            ]
+        })
+        .collect();
+    rows(&schema, values)
+}
@@ -0,0 +1,100 @@
+use std::collections::VecDeque;
+
+use crate::common::{CancellationToken, RecordId, Result};
+use crate::db::TableHandle;
+use crate::tuple::{Tuple, Value};
+
+/// Computes the values of an updated row from the existing row and the row
+/// that conflicted with it.
+pub type UpdateFn = dyn Fn(&Tuple, &Tuple) -> Vec<Value> + Send + Sync;
+
+/// What an upsert does when its row conflicts with a live row on the unique
+/// index, like `INSERT ... ON CONFLICT`.
+pub enum OnConflict {
+    /// Leave the existing row alone
+    DoNothing,
+    /// Replace the existing row with the values returned for
+    /// `(existing, proposed)`
+    DoUpdate(Box<UpdateFn>),
+}
+
+impl OnConflict {
+    /// Returns `DoUpdate` with `update` computing the new values.
+    pub fn update<F>(update: F) -> Self
+    where
+        F: Fn(&Tuple, &Tuple) -> Vec<Value> + Send + Sync + 'static,
+    {
+        OnConflict::DoUpdate(Box::new(update))
+    }
+}
+
+/// UpsertExecutor inserts rows into a table, resolving conflicts on a unique
+/// index with `OnConflict`. It returns each row as inserted or updated;
+/// rows skipped by `DoNothing` are not returned.
+///
+/// Rows are written one at a time as the executor is pulled, each under its
+/// own exclusive table lock (see `TableHandle::upsert`). The cancellation
+/// token is checked before each row, so a cancel keeps the rows already
+/// written.
+pub struct UpsertExecutor {
+    table: TableHandle,
+    index_name: String,
+    on_conflict: OnConflict,
+    /// Rows not written yet
+    rows: VecDeque<Vec<Value>>,
+    /// Stops the upserts when cancelled
+    cancellation: CancellationToken,
+}
+
+impl UpsertExecutor {
+    /// Upserts `rows` into `table`, detecting conflicts with `index_name`.
+    pub fn new(
+        table: TableHandle,
+        index_name: impl Into<String>,
+        rows: impl IntoIterator<Item = Vec<Value>>,
+        on_conflict: OnConflict,
+    ) -> Self {
+        Self {
+            table,
+            index_name: index_name.into(),
+            on_conflict,
+            rows: rows.into_iter().collect(),
+            cancellation: CancellationToken::new(),
+        }
+    }
+
+    /// Stops with `QueryCancelled` once `cancellation` is cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Writes rows until one is inserted or updated, or none are left.
+    fn advance(&mut self) -> Result<Option<(RecordId, Tuple)>> {
+        while let Some(values) = self.rows.pop_front() {
+            self.cancellation.check()?;
+            let written = self
+                .table
+                .upsert(&self.index_name, values, &self.on_conflict)?;
+            if written.is_some() {
+                return Ok(written);
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Iterator for UpsertExecutor {
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(row) => row.map(Ok),
+            Err(e) => {
+                // Stop after the first error
+                self.rows.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::common::{CancellationToken, CrioError, QueryBudget, RecordId, Result};
+use crate::storage::disk::TempFileManager;
+use crate::tuple::{Column, DataType, Schema, Tuple, Value};
+
+use super::sort::compare_rows;
+use super::{SortExecutor, SortKey};
+
+/// A function a `WindowExecutor` computes for each row, over the rows of its
+/// partition up to and including its peers (the rows with equal order
+/// keys), as SQL's default window frame does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowFunction {
+    /// `ROW_NUMBER()`: the row's position in its partition, from 1
+    RowNumber,
+    /// `RANK()`: one more than the number of rows before the row's peers,
+    /// so peers share a rank and leave a gap after them
+    Rank,
+    /// `DENSE_RANK()`: the number of distinct order keys up to the row's
+    DenseRank,
+    /// `COUNT(column)`: the number of non-NULL values of the column
+    Count(usize),
+    /// `SUM(column)`: a BigInt for integer columns and a Double for
+    /// floating point ones
+    Sum(usize),
+    /// `AVG(column)` of a numeric column, as a Double
+    Avg(usize),
+    /// `MIN(column)`, under the column's collation
+    Min(usize),
+    /// `MAX(column)`, under the column's collation
+    Max(usize),
+}
+
+impl WindowFunction {
+    /// Returns the SQL name of the function.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindowFunction::RowNumber => "ROW_NUMBER",
+            WindowFunction::Rank => "RANK",
+            WindowFunction::DenseRank => "DENSE_RANK",
+            WindowFunction::Count(_) => "COUNT",
+            WindowFunction::Sum(_) => "SUM",
+            WindowFunction::Avg(_) => "AVG",
+            WindowFunction::Min(_) => "MIN",
+            WindowFunction::Max(_) => "MAX",
+        }
+    }
+
+    /// Returns the input column the function aggregates, if any.
+    fn input_column(&self) -> Option<usize> {
+        match self {
+            WindowFunction::RowNumber | WindowFunction::Rank | WindowFunction::DenseRank => None,
+            WindowFunction::Count(column)
+            | WindowFunction::Sum(column)
+            | WindowFunction::Avg(column)
+            | WindowFunction::Min(column)
+            | WindowFunction::Max(column) => Some(*column),
+        }
+    }
+
+    /// Returns the column named `name` the function's values are returned
+    /// in for rows of `input`, or fails if it can't be computed over them.
+    fn output_column(&self, name: &str, input: &Schema) -> Result<Column> {
+        let Some(index) = self.input_column() else {
+            return Ok(Column::new(name, DataType::BigInt, false));
+        };
+        let column = input.column(index).ok_or(CrioError::SchemaMismatch)?;
+        let integer = matches!(
+            column.data_type(),
+            DataType::TinyInt | DataType::SmallInt | DataType::Integer | DataType::BigInt
+        );
+        let floating = matches!(column.data_type(), DataType::Float | DataType::Double);
+        Ok(match self {
+            WindowFunction::Count(_) => Column::new(name, DataType::BigInt, false),
+            WindowFunction::Min(_) | WindowFunction::Max(_) => {
+                Column::new(name, column.data_type().clone(), true)
+                    .with_collation(column.collation())
+            }
+            WindowFunction::Sum(_) if integer => Column::new(name, DataType::BigInt, true),
+            WindowFunction::Sum(_) | WindowFunction::Avg(_) if integer || floating => {
+                Column::new(name, DataType::Double, true)
+            }
+            _ => {
+                return Err(CrioError::InvalidAggregate {
+                    function: self.name(),
+                    column: column.name().to_string(),
+                })
+            }
+        })
+    }
+}
+
+/// Running state of one `WindowFunction` over the current partition.
+#[derive(Debug, Default)]
+struct Running {
+    /// Non-NULL values added
+    count: i64,
+    /// Sum of the integer values added
+    integer_sum: i64,
+    /// Sum of all values added, as floating point
+    float_sum: f64,
+    /// Smallest or largest value added, for MIN and MAX
+    extreme: Option<Value>,
+}
+
+impl Running {
+    /// Adds the value of one row of the partition.
+    fn add(&mut self, function: WindowFunction, value: &Value, column: &Column) -> Result<()> {
+        if value.is_null() {
+            return Ok(());
+        }
+        self.count += 1;
+        let wanted = match function {
+            WindowFunction::Min(_) => Ordering::Less,
+            WindowFunction::Max(_) => Ordering::Greater,
+            _ => {
+                if let Some(n) = integer(value) {
+                    self.integer_sum = self
+                        .integer_sum
+                        .checked_add(n)
+                        .ok_or(CrioError::NumericOverflow(function.name()))?;
+                    self.float_sum += n as f64;
+                } else if let Value::Float(f) = value {
+                    self.float_sum += *f as f64;
+                } else if let Value::Double(f) = value {
+                    self.float_sum += f;
+                }
+                return Ok(());
+            }
+        };
+        let replace = match &self.extreme {
+            Some(extreme) => column.compare(value, extreme) == Some(wanted),
+            None => true,
+        };
+        if replace {
+            self.extreme = Some(value.clone());
+        }
+        Ok(())
+    }
+
+    /// Returns the function's value so far, returned in `output`.
+    fn value(&self, function: WindowFunction, output: &Column) -> Value {
+        match function {
+            WindowFunction::Count(_) => Value::BigInt(self.count),
+            _ if self.count == 0 => Value::Null,
+            WindowFunction::Sum(_) if *output.data_type() == DataType::BigInt => {
+                Value::BigInt(self.integer_sum)
+            }
+            WindowFunction::Sum(_) => Value::Double(self.float_sum),
+            WindowFunction::Avg(_) => Value::Double(self.float_sum / self.count as f64),
+            _ => self.extreme.clone().unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Returns an integer value as an i64.
+fn integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::TinyInt(n) => Some(*n as i64),
+        Value::SmallInt(n) => Some(*n as i64),
+        Value::Integer(n) => Some(*n as i64),
+        Value::BigInt(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// WindowExecutor computes window functions (`ROW_NUMBER`, `RANK` and
+/// running aggregates) over the rows of an executor, returning each row
+/// with one more value per function.
+///
+/// The rows are sorted by a `SortExecutor` on the partition columns, then
+/// the order keys, so the sort's budget, spilling and cancellation apply.
+/// They are then read one peer group at a time: each function's running
+/// state is reset at the start of each partition and takes in the whole
+/// group before its rows are returned, so peers get the same aggregates.
+/// Without order keys a partition is a single group. Rows keep their input
+/// record IDs.
+pub struct WindowExecutor<I> {
+    input: SortExecutor<I>,
+    /// Keys of the partition columns, then the order keys
+    keys: Vec<SortKey>,
+    /// Number of partition keys at the start of `keys`
+    partition_keys: usize,
+    /// Output column name and function of each computed value
+    functions: Vec<(String, WindowFunction)>,
+    /// Schemas of the input and output rows, once the first row is read
+    schemas: Option<(Arc<Schema>, Arc<Schema>)>,
+    /// First row of the next peer group, read while finding the end of the
+    /// current one
+    lookahead: Option<(RecordId, Tuple)>,
+    /// A row of the current peer group, to tell where its partition ends
+    group_row: Option<Tuple>,
+    /// Rows of the current partition before the current peer group
+    rows_before: i64,
+    /// Peer groups of the current partition so far
+    groups: i64,
+    /// State of each function over the current partition
+    running: Vec<Running>,
+    /// Rows of the current peer group not returned yet
+    pending: VecDeque<(RecordId, Tuple)>,
+    /// Whether the input has ended or failed
+    done: bool,
+}
+
+impl<I> WindowExecutor<I>
+where
+    I: Iterator<Item = Result<(RecordId, Tuple)>>,
+{
+    /// Creates an executor computing `functions` over the rows of `input`,
+    /// partitioned on the columns `partition_by` and ordered within each
+    /// partition on `order_by`. Each function's values are returned in a
+    /// column of the name paired with it.
+    pub fn new(
+        input: I,
+        partition_by: &[usize],
+        order_by: Vec<SortKey>,
+        functions: Vec<(String, WindowFunction)>,
+    ) -> Self {
+        let keys: Vec<_> = partition_by
+            .iter()
+            .map(|&column| SortKey::asc(column))
+            .chain(order_by)
+            .collect();
+        let running = functions.iter().map(|_| Running::default()).collect();
+        Self {
+            input: SortExecutor::new(input, keys.clone()),
+            keys,
+            partition_keys: partition_by.len(),
+            functions,
+            schemas: None,
+            lookahead: None,
+            group_row: None,
+            rows_before: 0,
+            groups: 0,
+            running,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Stops the executor with `QueryCancelled` once `cancellation` is
+    /// cancelled.
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.input = self.input.with_cancellation(cancellation);
+        self
+    }
+
+    /// Reserves the sort buffer from `budget`.
+    pub fn with_budget(mut self, budget: QueryBudget) -> Self {
+        self.input = self.input.with_budget(budget);
+        self
+    }
+
+    /// Lets the sort spill to the scratch file of `temp` (see
+    /// `SortExecutor::with_spill`).
+    pub fn with_spill(mut self, temp: Arc<TempFileManager>) -> Self {
+        self.input = self.input.with_spill(temp);
+        self
+    }
+
+    /// Returns the next sorted input row, or None at the end.
+    fn next_input(&mut self) -> Result<Option<(RecordId, Tuple)>> {
+        if let Some(row) = self.lookahead.take() {
+            return Ok(Some(row));
+        }
+        self.input.next().transpose()
+    }
+
+    /// Returns the input and output schemas for rows of `input`, making the
+    /// output one on first use.
+    fn schemas(&mut self, input: &Arc<Schema>) -> Result<(Arc<Schema>, Arc<Schema>)> {
+        if let Some(schemas) = &self.schemas {
+            return Ok(schemas.clone());
+        }
+        let mut columns: Vec<_> = input.columns().cloned().collect();
+        for (name, function) in &self.functions {
+            columns.push(function.output_column(name, input)?);
+        }
+        let schemas = (Arc::clone(input), Arc::new(Schema::new(columns)));
+        self.schemas = Some(schemas.clone());
+        Ok(schemas)
+    }
+
+    /// Reads the next peer group and queues its rows with their computed
+    /// values. Returns false at the end of the input.
+    fn read_group(&mut self) -> Result<bool> {
+        let Some(first) = self.next_input()? else {
+            return Ok(false);
+        };
+        let (input, output) = self.schemas(first.1.schema())?;
+        let new_partition = self.group_row.as_ref().is_none_or(|row| {
+            compare_rows(&self.keys[..self.partition_keys], row, &first.1) != Ordering::Equal
+        });
+        if new_partition {
+            self.rows_before = 0;
+            self.groups = 0;
+            self.running
+                .iter_mut()
+                .for_each(|r| *r = Running::default());
+        }
+        self.group_row = Some(first.1.clone());
+
+        let mut group = vec![first];
+        while let Some(row) = self.next_input()? {
+            if compare_rows(&self.keys, &group[0].1, &row.1) == Ordering::Equal {
+                group.push(row);
+            } else {
+                self.lookahead = Some(row);
+                break;
+            }
+        }
+
+        for (_, tuple) in &group {
+            for ((_, function), running) in self.functions.iter().zip(&mut self.running) {
+                if let Some(index) = function.input_column() {
+                    let column = input.column(index).ok_or(CrioError::SchemaMismatch)?;
+                    let value = tuple.value(index).unwrap_or(&Value::Null);
+                    running.add(*function, value, column)?;
+                }
+            }
+        }
+        self.groups += 1;
+        let rank = self.rows_before + 1;
+        for (i, (record_id, tuple)) in group.into_iter().enumerate() {
+            let mut values = tuple.into_values();
+            for (position, ((_, function), running)) in
+                self.functions.iter().zip(&self.running).enumerate()
+            {
+                let column = output
+                    .column(input.column_count() + position)
+                    .expect("a column per function");
+                values.push(match function {
+                    WindowFunction::RowNumber => Value::BigInt(rank + i as i64),
+                    WindowFunction::Rank => Value::BigInt(rank),
+                    WindowFunction::DenseRank => Value::BigInt(self.groups),
+                    _ => running.value(*function, column),
+                });
+            }
+            self.pending
+                .push_back((record_id, Tuple::new(Arc::clone(&output), values)));
+        }
+        self.rows_before += self.pending.len() as i64;
+        Ok(true)
+    }
+}
+
+impl<I> Iterator for WindowExecutor<I>
+where
+    I: Iterator<Item = Result<(RecordId, Tuple)>>,
+{
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.pending.pop_front() {
+                return Some(Ok(row));
+            }
+            if self.done {
+                return None;
+            }
+            match self.read_group() {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(e) => {
+                    // Stop after the first error
+                    self.done = true;
+                    self.pending.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::test_util::int_string_rows as rows;
+
+    fn input() -> std::vec::IntoIter<Result<(RecordId, Tuple)>> {
+        rows(&[
+            (Some(3), "a"),
+            (Some(1), "b"),
+            (Some(1), "a"),
+            (None, "a"),
+            (Some(3), "a"),
+            (Some(2), "b"),
+        ])
+        .into_iter()
+    }
+
+    #[test]
+    fn test_window_functions() {
+        let functions = [
+            WindowFunction::RowNumber,
+            WindowFunction::Rank,
+            WindowFunction::DenseRank,
+            WindowFunction::Sum(0),
+            WindowFunction::Count(0),
+            WindowFunction::Max(0),
+        ]
+        .map(|f| (f.name().to_lowercase(), f));
+        let window = WindowExecutor::new(input(), &[1], vec![SortKey::asc(0)], functions.into());
+        let rows: Vec<_> = window.map(|row| row.unwrap()).collect();
+
+        let schema = rows[0].1.schema();
+        assert_eq!(schema.column_count(), 8);
+        assert_eq!(schema.column(2).unwrap().name(), "row_number");
+        assert_eq!(*schema.column(5).unwrap().data_type(), DataType::BigInt);
+        assert_eq!(*schema.column(7).unwrap().data_type(), DataType::Integer);
+
+        // Partition "a" is 1, 3, 3, NULL and partition "b" is 1, 2; peers
+        // share the rank and the running aggregates
+        let computed: Vec<_> = rows
+            .iter()
+            .map(|(record_id, tuple)| {
+                let values = tuple.values()[2..].to_vec();
+                (record_id.slot_id.as_u16(), values)
+            })
+            .collect();
+        let row = |slot, n: [i64; 5], max: Value| {
+            let mut values: Vec<_> = n.into_iter().map(Value::BigInt).collect();
+            values.push(max);
+            (slot, values)
+        };
+        assert_eq!(
+            computed,
+            vec![
+                row(2, [1, 1, 1, 1, 1], Value::Integer(1)),
+                row(0, [2, 2, 2, 7, 3], Value::Integer(3)),
+                row(4, [3, 2, 2, 7, 3], Value::Integer(3)),
+                row(3, [4, 4, 3, 7, 3], Value::Integer(3)),
+                row(1, [1, 1, 1, 1, 1], Value::Integer(1)),
+                row(5, [2, 2, 2, 3, 2], Value::Integer(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_window_without_order_keys() {
+        let functions = vec![
+            ("total".to_string(), WindowFunction::Sum(0)),
+            ("mean".to_string(), WindowFunction::Avg(0)),
+            ("first".to_string(), WindowFunction::Min(1)),
+        ];
+        let window = WindowExecutor::new(input(), &[1], Vec::new(), functions);
+        let computed: Vec<_> = window
+            .map(|row| row.unwrap().1.values()[2..].to_vec())
+            .collect();
+        let a = vec![
+            Value::BigInt(7),
+            Value::Double(7.0 / 3.0),
+            Value::String("a".into()),
+        ];
+        let b = vec![
+            Value::BigInt(3),
+            Value::Double(1.5),
+            Value::String("b".into()),
+        ];
+        assert_eq!(
+            computed,
+            vec![a.clone(), a.clone(), a.clone(), a, b.clone(), b]
+        );
+
+        // Sums need a numeric column
+        let functions = vec![("total".to_string(), WindowFunction::Sum(1))];
+        let mut window = WindowExecutor::new(input(), &[], Vec::new(), functions);
+        assert!(matches!(
+            window.next(),
+            Some(Err(CrioError::InvalidAggregate {
+                function: "SUM",
+                ..
+            }))
+        ));
+        assert!(window.next().is_none());
+    }
+}
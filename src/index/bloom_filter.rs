@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use crate::common::{PageId, RecordId, BLOOM_FILTER_BITS, BLOOM_FILTER_HASHES};
+use crate::storage::disk::EXTENT_SIZE;
+
+/// A fixed-size bloom filter over byte keys, probed with
+/// `BLOOM_FILTER_HASHES` hashes derived from one 64-bit FNV-1a hash by
+/// double hashing.
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Box<[u64]>,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter of `BLOOM_FILTER_BITS` bits.
+    pub fn new() -> Self {
+        Self {
+            bits: vec![0; BLOOM_FILTER_BITS / 64].into_boxed_slice(),
+        }
+    }
+
+    /// Adds a key.
+    pub fn insert(&mut self, key: &[u8]) {
+        for bit in bit_positions(key) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Returns false if `key` was never added; true if it may have been.
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        bit_positions(key).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
+impl Default for BloomFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bloom filter of one extent and the extent's pages that hold rows.
+#[derive(Debug, Clone, Default)]
+struct ExtentFilter {
+    filter: BloomFilter,
+    /// Bit i set if page `start + i` of the extent holds a row
+    pages: u8,
+}
+
+/// One bloom filter per extent over the keys of the rows whose home page
+/// lies in it, so a point lookup reads only the pages of extents that may
+/// hold the key.
+///
+/// Keys are never removed: a deleted or updated row's key stays in its
+/// extent's filter until the filters are rebuilt from scratch.
+#[derive(Debug, Clone, Default)]
+pub struct ExtentBloomFilters {
+    extents: HashMap<u32, ExtentFilter>,
+}
+
+impl ExtentBloomFilters {
+    /// Creates filters with no keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the key of the row at `record_id`.
+    pub fn insert(&mut self, record_id: RecordId, key: &[u8]) {
+        let page = record_id.page_id.as_u32();
+        let extent = self.extents.entry(page / EXTENT_SIZE).or_default();
+        extent.filter.insert(key);
+        extent.pages |= 1 << (page % EXTENT_SIZE);
+    }
+
+    /// Returns the pages that may hold a row with `key`, in page ID order.
+    pub fn candidate_pages(&self, key: &[u8]) -> Vec<PageId> {
+        let mut pages: Vec<_> = self
+            .extents
+            .iter()
+            .filter(|(_, extent)| extent.filter.may_contain(key))
+            .flat_map(|(&extent, filter)| {
+                (0..EXTENT_SIZE)
+                    .filter(move |i| filter.pages & (1 << i) != 0)
+                    .map(move |i| PageId::new(extent * EXTENT_SIZE + i))
+            })
+            .collect();
+        pages.sort();
+        pages
+    }
+
+    /// Returns the number of extents with a filter.
+    pub fn extent_count(&self) -> usize {
+        self.extents.len()
+    }
+}
+
+/// Returns the bits `key` maps to.
+fn bit_positions(key: &[u8]) -> impl Iterator<Item = usize> {
+    let hash = fnv1a(key);
+    let (h1, h2) = (hash as u32, (hash >> 32) as u32 | 1);
+    (0..BLOOM_FILTER_HASHES)
+        .map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) as usize % BLOOM_FILTER_BITS)
+}
+
+/// 64-bit FNV-1a, stable across runs and platforms.
+fn fnv1a(key: &[u8]) -> u64 {
+    key.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::SlotId;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::new();
+        for i in 0..1000u32 {
+            filter.insert(&i.to_le_bytes());
+        }
+        assert!((0..1000u32).all(|i| filter.may_contain(&i.to_le_bytes())));
+
+        let false_positives = (1000..11_000u32)
+            .filter(|i| filter.may_contain(&i.to_le_bytes()))
+            .count();
+        assert!(false_positives < 500, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn test_extent_candidate_pages() {
+        let rid = |page: u32| RecordId::new(PageId::new(page), SlotId::new(0));
+        let mut filters = ExtentBloomFilters::new();
+        filters.insert(rid(3), b"apple");
+        filters.insert(rid(5), b"pear");
+        filters.insert(rid(17), b"apple");
+
+        assert_eq!(filters.extent_count(), 2);
+        assert_eq!(
+            filters.candidate_pages(b"apple"),
+            vec![PageId::new(3), PageId::new(5), PageId::new(17)]
+        );
+        assert_eq!(
+            filters.candidate_pages(b"pear"),
+            vec![PageId::new(3), PageId::new(5)]
+        );
+        assert!(filters.candidate_pages(b"plum").is_empty());
+    }
+}
@@ -1,12 +1,27 @@
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 
+use parking_lot::RwLock;
+
 use crate::buffer::BufferPoolManager;
-use crate::common::{CrioError, PageId, RecordId, Result, DEFAULT_BTREE_ORDER};
+use crate::common::{CrioError, PageId, RecordId, Result, DEFAULT_BTREE_ORDER, PAGE_SIZE};
+
+use super::btree_page::{BTreeNode, BTreeNodeRef, HEADER_SIZE};
 
-use super::btree_page::{BTreeNode, BTreeNodeRef};
+/// Optimistic reads of a node a descent tries before latching it.
+const OPTIMISTIC_ATTEMPTS: usize = 3;
 
+/// A B+Tree of unique u32 keys. All methods take `&self`, so one index can be
+/// shared between threads through an `Arc`.
+///
+/// Lookups and inserts that fit in their leaf hold `structure_latch` shared
+/// and rely on page latches; an insert that splits a node takes it
+/// exclusively, so nobody descends through a half-split tree.
 pub struct BTreeIndex {
-    root_page_id: PageId,
+    /// Current root; moves when the root splits
+    root_page_id: AtomicU32,
+    /// Held exclusively while nodes split
+    structure_latch: RwLock<()>,
     bpm: Arc<BufferPoolManager>,
     order: usize,
 }
@@ -24,7 +39,8 @@ impl BTreeIndex {
         }
 
         Ok(Self {
-            root_page_id,
+            root_page_id: AtomicU32::new(root_page_id.as_u32()),
+            structure_latch: RwLock::new(()),
             bpm,
             order: DEFAULT_BTREE_ORDER,
         })
@@ -32,17 +48,23 @@ impl BTreeIndex {
 
     pub fn open(root_page_id: PageId, bpm: Arc<BufferPoolManager>) -> Result<Self> {
         Ok(Self {
-            root_page_id,
+            root_page_id: AtomicU32::new(root_page_id.as_u32()),
+            structure_latch: RwLock::new(()),
             bpm,
             order: DEFAULT_BTREE_ORDER,
         })
     }
 
     pub fn root_page_id(&self) -> PageId {
-        self.root_page_id
+        PageId::new(self.root_page_id.load(Ordering::Acquire))
+    }
+
+    fn set_root_page_id(&self, page_id: PageId) {
+        self.root_page_id.store(page_id.as_u32(), Ordering::Release);
     }
 
     pub fn search(&self, key: u32) -> Result<Option<RecordId>> {
+        let _structure = self.structure_latch.read();
         let leaf_page_id = self.find_leaf(key)?;
 
         let guard = self
@@ -59,41 +81,62 @@ impl BTreeIndex {
         }
     }
 
-    fn find_leaf(&self, key: u32) -> Result<PageId> {
-        let mut current_page_id = self.root_page_id;
-
-        loop {
-            let next_page_id = {
-                let guard = self
-                    .bpm
-                    .checked_read_page(current_page_id)?
-                    .ok_or(CrioError::PageNotFound(current_page_id))?;
-                let node = BTreeNodeRef::new(guard.data());
-
-                if node.is_leaf() {
-                    return Ok(current_page_id);
-                }
-
-                let pos = node.search_key(key);
-                let num_keys = node.num_keys() as usize;
+    /// Returns the leaf that holds `key`, or would hold it. Scans start here
+    /// and follow `next_page_id` from leaf to leaf.
+    pub fn leaf_page_id(&self, key: u32) -> Result<PageId> {
+        let _structure = self.structure_latch.read();
+        self.find_leaf(key)
+    }
 
-                // For internal nodes: child[i] has keys < keys[i], child[i+1] has keys >= keys[i]
-                // search_key returns first index where keys[pos] >= key
-                // If key == keys[pos], we need child[pos+1] (keys >= keys[pos])
-                let child_index = if pos < num_keys && node.get_key(pos) == key {
-                    pos + 1
-                } else {
-                    pos
-                };
+    fn find_leaf(&self, key: u32) -> Result<PageId> {
+        let mut current_page_id = self.root_page_id();
+        while let Some(child_page_id) = self.next_node(current_page_id, key)? {
+            current_page_id = child_page_id;
+        }
+        Ok(current_page_id)
+    }
 
-                node.get_child(child_index)
+    /// Returns the child of `page_id` to follow for `key`, or None if it is a
+    /// leaf. The node is read optimistically so descents don't latch the hot
+    /// upper levels; it is latched only if it is not resident or keeps
+    /// changing underneath.
+    fn next_node(&self, page_id: PageId, key: u32) -> Result<Option<PageId>> {
+        for _ in 0..OPTIMISTIC_ATTEMPTS {
+            let Some(guard) = self.bpm.optimistic_read_page(page_id) else {
+                break;
             };
-
-            current_page_id = next_page_id;
+            let mut data = [0u8; PAGE_SIZE];
+            guard.copy_bytes(0, &mut data[..HEADER_SIZE]);
+            let len = BTreeNodeRef::new(&data).search_len();
+            guard.copy_bytes(HEADER_SIZE, &mut data[HEADER_SIZE..len]);
+            if guard.validate() {
+                return Ok(child_for(&BTreeNodeRef::new(&data), key));
+            }
         }
+
+        let guard = self
+            .bpm
+            .checked_read_page(page_id)?
+            .ok_or(CrioError::PageNotFound(page_id))?;
+        Ok(child_for(&BTreeNodeRef::new(guard.data()), key))
     }
 
-    pub fn insert(&mut self, key: u32, value: RecordId) -> Result<()> {
+    pub fn insert(&self, key: u32, value: RecordId) -> Result<()> {
+        // Fast path: the leaf has room, so only the leaf changes
+        {
+            let _structure = self.structure_latch.read();
+            let leaf_page_id = self.find_leaf(key)?;
+            let mut guard = self
+                .bpm
+                .checked_write_page(leaf_page_id)?
+                .ok_or(CrioError::PageNotFound(leaf_page_id))?;
+            if BTreeNodeRef::new(guard.data()).num_keys() < self.order as u16 {
+                let mut node = BTreeNode::new(guard.data_mut());
+                return node.insert_key_value(key, value);
+            }
+        }
+
+        let _structure = self.structure_latch.write();
         let leaf_page_id = self.find_leaf(key)?;
 
         let needs_split = {
@@ -119,12 +162,7 @@ impl BTreeIndex {
         Ok(())
     }
 
-    fn split_and_insert_leaf(
-        &mut self,
-        leaf_page_id: PageId,
-        key: u32,
-        value: RecordId,
-    ) -> Result<()> {
+    fn split_and_insert_leaf(&self, leaf_page_id: PageId, key: u32, value: RecordId) -> Result<()> {
         let (separator_key, right_pairs, next_page_id, parent_page_id) = {
             let mut guard = self
                 .bpm
@@ -207,18 +245,13 @@ impl BTreeIndex {
                 node.set_parent_page_id(Some(new_root_id));
             }
 
-            self.root_page_id = new_root_id;
+            self.set_root_page_id(new_root_id);
         }
 
         Ok(())
     }
 
-    fn insert_into_parent(
-        &mut self,
-        parent_id: PageId,
-        key: u32,
-        new_child_id: PageId,
-    ) -> Result<()> {
+    fn insert_into_parent(&self, parent_id: PageId, key: u32, new_child_id: PageId) -> Result<()> {
         let needs_split = {
             let guard = self
                 .bpm
@@ -243,7 +276,7 @@ impl BTreeIndex {
     }
 
     fn split_and_insert_internal(
-        &mut self,
+        &self,
         internal_id: PageId,
         key: u32,
         new_child_id: PageId,
@@ -318,13 +351,60 @@ impl BTreeIndex {
                 node.set_parent_page_id(Some(new_root_id));
             }
 
-            self.root_page_id = new_root_id;
+            self.set_root_page_id(new_root_id);
         }
 
         Ok(())
     }
 
+    /// Removes `key` if it maps to `value`, and returns whether it did.
+    ///
+    /// Only the leaf changes: nodes are never merged, so a leaf emptied by
+    /// removes stays in the tree and is refilled by later inserts of keys in
+    /// its range.
+    pub fn remove(&self, key: u32, value: RecordId) -> Result<bool> {
+        let _structure = self.structure_latch.read();
+        let leaf_page_id = self.find_leaf(key)?;
+        let mut guard = self
+            .bpm
+            .checked_write_page(leaf_page_id)?
+            .ok_or(CrioError::PageNotFound(leaf_page_id))?;
+
+        let node = BTreeNodeRef::new(guard.data());
+        let pos = node.search_key(key);
+        if pos >= node.num_keys() as usize
+            || node.get_key(pos) != key
+            || node.get_value(pos) != value
+        {
+            // Only looked: leave the leaf clean
+            guard.mark_clean();
+            return Ok(false);
+        }
+        BTreeNode::new(guard.data_mut()).remove_key_value(pos);
+        Ok(true)
+    }
+
+    /// Returns the IDs of every node in the tree, root first.
+    pub fn page_ids(&self) -> Result<Vec<PageId>> {
+        let _structure = self.structure_latch.read();
+        let mut page_ids = Vec::new();
+        let mut pending = vec![self.root_page_id()];
+        while let Some(page_id) = pending.pop() {
+            let guard = self
+                .bpm
+                .checked_read_page(page_id)?
+                .ok_or(CrioError::PageNotFound(page_id))?;
+            let node = BTreeNodeRef::new(guard.data());
+            if !node.is_leaf() {
+                pending.extend((0..=node.num_keys() as usize).map(|i| node.get_child(i)));
+            }
+            page_ids.push(page_id);
+        }
+        Ok(page_ids)
+    }
+
     pub fn range_scan(&self, start_key: u32, end_key: u32) -> Result<Vec<(u32, RecordId)>> {
+        let _structure = self.structure_latch.read();
         let mut results = Vec::new();
         let leaf_page_id = self.find_leaf(start_key)?;
 
@@ -366,6 +446,27 @@ impl BTreeIndex {
     }
 }
 
+/// Picks the child of an internal node to follow for `key`; None for a leaf.
+fn child_for(node: &BTreeNodeRef, key: u32) -> Option<PageId> {
+    if node.is_leaf() {
+        return None;
+    }
+
+    let pos = node.search_key(key);
+    let num_keys = node.num_keys() as usize;
+
+    // For internal nodes: child[i] has keys < keys[i], child[i+1] has keys >= keys[i]
+    // search_key returns first index where keys[pos] >= key
+    // If key == keys[pos], we need child[pos+1] (keys >= keys[pos])
+    let child_index = if pos < num_keys && node.get_key(pos) == key {
+        pos + 1
+    } else {
+        pos
+    };
+
+    Some(node.get_child(child_index))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -379,7 +480,7 @@ mod tests {
         let disk_manager = Arc::new(DiskManager::new(temp_file.path()).unwrap());
         let bpm = Arc::new(BufferPoolManager::new(10, 2, disk_manager));
 
-        let mut index = BTreeIndex::new(bpm.clone()).unwrap();
+        let index = BTreeIndex::new(bpm.clone()).unwrap();
         let record1 = RecordId::new(PageId::new(100), SlotId::new(0));
         let record2 = RecordId::new(PageId::new(100), SlotId::new(1));
         let record3 = RecordId::new(PageId::new(101), SlotId::new(0));
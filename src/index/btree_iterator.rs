@@ -24,6 +24,7 @@ impl BTreeIterator {
         }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Result<Option<(u32, RecordId)>> {
         if self.done {
             return Ok(None);
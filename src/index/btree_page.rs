@@ -1,6 +1,6 @@
 use crate::common::{PageId, RecordId, Result, SlotId, PAGE_SIZE};
 
-const HEADER_SIZE: usize = 20;
+pub(super) const HEADER_SIZE: usize = 20;
 
 const PAGE_ID_OFFSET: usize = 0;
 const IS_LEAF_OFFSET: usize = 4;
@@ -189,11 +189,11 @@ impl<'a> BTreeNode<'a> {
         self.set_key(pos, key);
 
         // Write all values to their new positions (all shift due to key array growing)
-        for i in 0..num_keys {
+        for (i, value) in values.into_iter().enumerate() {
             if i >= pos {
-                self.set_value_at(i + 1, values[i], num_keys + 1);
+                self.set_value_at(i + 1, value, num_keys + 1);
             } else {
-                self.set_value_at(i, values[i], num_keys + 1);
+                self.set_value_at(i, value, num_keys + 1);
             }
         }
 
@@ -203,6 +203,28 @@ impl<'a> BTreeNode<'a> {
         Ok(())
     }
 
+    /// Removes the key at `index` of a leaf along with its value.
+    pub fn remove_key_value(&mut self, index: usize) {
+        let num_keys = self.num_keys() as usize;
+
+        // Read all remaining values before modifying anything
+        let values: Vec<RecordId> = (0..num_keys)
+            .filter(|&i| i != index)
+            .map(|i| self.get_value_at(i, num_keys))
+            .collect();
+
+        for i in index + 1..num_keys {
+            let k = self.get_key(i);
+            self.set_key(i - 1, k);
+        }
+        self.set_num_keys((num_keys - 1) as u16);
+
+        // Values move up as the key array shrinks
+        for (i, value) in values.into_iter().enumerate() {
+            self.set_value_at(i, value, num_keys - 1);
+        }
+    }
+
     fn get_value_at(&self, index: usize, num_keys: usize) -> RecordId {
         let offset = HEADER_SIZE + num_keys * KEY_SIZE + index * VALUE_SIZE;
 
@@ -249,11 +271,11 @@ impl<'a> BTreeNode<'a> {
         self.set_key(pos, key);
 
         // Write all children to their new positions (all shift due to key array growing)
-        for i in 0..=num_keys {
+        for (i, child_id) in children.into_iter().enumerate() {
             if i > pos {
-                self.set_child_at(i + 1, children[i], num_keys + 1);
+                self.set_child_at(i + 1, child_id, num_keys + 1);
             } else {
-                self.set_child_at(i, children[i], num_keys + 1);
+                self.set_child_at(i, child_id, num_keys + 1);
             }
         }
 
@@ -299,8 +321,8 @@ impl<'a> BTreeNode<'a> {
         self.set_num_keys(mid as u16);
 
         // Rewrite left values at their new positions (based on new num_keys)
-        for i in 0..mid {
-            self.set_value_at(i, left_values[i], mid);
+        for (i, value) in left_values.into_iter().enumerate() {
+            self.set_value_at(i, value, mid);
         }
 
         (separator_key, right_pairs)
@@ -333,8 +355,8 @@ impl<'a> BTreeNode<'a> {
         self.set_num_keys(mid as u16);
 
         // Rewrite left children at their new positions (based on new num_keys)
-        for i in 0..=mid {
-            self.set_child_at(i, left_children[i], mid);
+        for (i, child_id) in left_children.into_iter().enumerate() {
+            self.set_child_at(i, child_id, mid);
         }
 
         (separator_key, right_keys, right_children)
@@ -403,6 +425,64 @@ impl<'a> BTreeNodeRef<'a> {
         }
     }
 
+    pub fn prev_page_id(&self) -> Option<PageId> {
+        let bytes: [u8; 4] = self.data[PREV_PAGE_OFFSET..PREV_PAGE_OFFSET + 4]
+            .try_into()
+            .unwrap();
+        let value = u32::from_le_bytes(bytes);
+        if value == INVALID_PAGE {
+            None
+        } else {
+            Some(PageId::new(value))
+        }
+    }
+
+    pub fn parent_page_id(&self) -> Option<PageId> {
+        let bytes: [u8; 4] = self.data[PARENT_PAGE_OFFSET..PARENT_PAGE_OFFSET + 4]
+            .try_into()
+            .unwrap();
+        let value = u32::from_le_bytes(bytes);
+        if value == INVALID_PAGE {
+            None
+        } else {
+            Some(PageId::new(value))
+        }
+    }
+
+    /// Returns true if the node's keys, values and children fit in the page.
+    pub fn fits_page(&self) -> bool {
+        let num_keys = self.num_keys() as usize;
+        let used = if self.is_leaf() {
+            HEADER_SIZE + num_keys * (KEY_SIZE + VALUE_SIZE)
+        } else {
+            HEADER_SIZE + num_keys * KEY_SIZE + (num_keys + 1) * CHILD_SIZE
+        };
+        used <= PAGE_SIZE
+    }
+
+    /// Checks that the node fits in the page and that keys are strictly
+    /// increasing. Returns a description of every violation.
+    pub fn check_layout(&self) -> Vec<String> {
+        let num_keys = self.num_keys() as usize;
+        if !self.fits_page() {
+            return vec![format!("{} keys do not fit in the page", num_keys)];
+        }
+
+        let mut violations = Vec::new();
+        for i in 1..num_keys {
+            if self.get_key(i - 1) >= self.get_key(i) {
+                violations.push(format!(
+                    "key {} ({}) is not less than key {} ({})",
+                    i - 1,
+                    self.get_key(i - 1),
+                    i,
+                    self.get_key(i)
+                ));
+            }
+        }
+        violations
+    }
+
     pub fn get_key(&self, index: usize) -> u32 {
         let offset = HEADER_SIZE + index * KEY_SIZE;
         let bytes: [u8; 4] = self.data[offset..offset + 4].try_into().unwrap();
@@ -448,6 +528,16 @@ impl<'a> BTreeNodeRef<'a> {
 
         left
     }
+
+    /// Returns how many leading bytes of the node a descent reads: the header,
+    /// plus the keys and children of an internal node.
+    pub fn search_len(&self) -> usize {
+        if self.is_leaf() {
+            return HEADER_SIZE;
+        }
+        let num_keys = self.num_keys() as usize;
+        (HEADER_SIZE + num_keys * KEY_SIZE + (num_keys + 1) * CHILD_SIZE).min(PAGE_SIZE)
+    }
 }
 
 #[cfg(test)]
@@ -475,6 +565,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_btree_node_remove() {
+        let mut data = [0u8; PAGE_SIZE];
+        let mut node = BTreeNode::new(&mut data);
+        node.init(PageId::new(1), true);
+        let record = |i: u16| RecordId::new(PageId::new(9), SlotId::new(i));
+        for key in [1, 2, 3] {
+            node.insert_key_value(key, record(key as u16)).unwrap();
+        }
+
+        node.remove_key_value(1);
+        assert_eq!(node.num_keys(), 2);
+        assert_eq!((node.get_key(0), node.get_value(0)), (1, record(1)));
+        assert_eq!((node.get_key(1), node.get_value(1)), (3, record(3)));
+        assert!(BTreeNodeRef::new(&data).check_layout().is_empty());
+    }
+
+    #[test]
+    fn test_btree_node_check_layout() {
+        let mut data = [0u8; PAGE_SIZE];
+        let mut node = BTreeNode::new(&mut data);
+        node.init(PageId::new(1), true);
+        for key in [5, 1, 3] {
+            node.insert_key_value(key, RecordId::new(PageId::new(9), SlotId::new(0)))
+                .unwrap();
+        }
+        assert!(BTreeNodeRef::new(&data).check_layout().is_empty());
+
+        let mut node = BTreeNode::new(&mut data);
+        node.set_key(2, 2);
+        assert_eq!(BTreeNodeRef::new(&data).check_layout().len(), 1);
+
+        let mut node = BTreeNode::new(&mut data);
+        node.set_num_keys(1000);
+        let node = BTreeNodeRef::new(&data);
+        assert!(!node.fits_page());
+        assert_eq!(node.check_layout().len(), 1);
+    }
+
     #[test]
     fn test_btree_node_offset_calculation() {
         let mut data = [0u8; PAGE_SIZE];
@@ -483,7 +612,7 @@ mod tests {
 
         // Manually write a value at the calculated offset
         node.set_num_keys(1);
-        let expected_offset = HEADER_SIZE + 1 * KEY_SIZE + 0 * VALUE_SIZE;
+        let expected_offset = HEADER_SIZE + KEY_SIZE;
         println!(
             "Expected value offset for index 0 with num_keys=1: {}",
             expected_offset
@@ -494,7 +623,7 @@ mod tests {
         node.data[expected_offset..expected_offset + 4].copy_from_slice(&page_id_bytes);
 
         // Read it back
-        let retrieved_offset = HEADER_SIZE + node.num_keys() as usize * KEY_SIZE + 0 * VALUE_SIZE;
+        let retrieved_offset = HEADER_SIZE + node.num_keys() as usize * KEY_SIZE;
         println!("Read offset: {}", retrieved_offset);
 
         let read_bytes: [u8; 4] = node.data[retrieved_offset..retrieved_offset + 4]
@@ -0,0 +1,453 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::buffer::BufferPoolManager;
+use crate::common::{CrioError, PageId, RecordId, Result, SlotId, PAGE_SIZE};
+use crate::storage::table::TableHeap;
+
+/// Longest term indexed, in bytes; longer tokens are cut to this length.
+pub const MAX_TERM_LEN: usize = 64;
+
+/// Posting page header: next_page u32 | used u16
+const POSTING_HEADER_SIZE: usize = 6;
+/// Posting entry header: page_id u32 | slot u16 | count u16
+const ENTRY_HEADER_SIZE: usize = 8;
+/// Most positions one entry holds; a row with more is split across entries
+const MAX_ENTRY_POSITIONS: usize = (PAGE_SIZE - POSTING_HEADER_SIZE - ENTRY_HEADER_SIZE) / 2;
+/// `next_page` of the last page of a posting list
+const NO_NEXT_PAGE: u32 = u32::MAX;
+
+/// Splits text into terms: runs of alphanumeric characters, lowercased and
+/// cut to `MAX_TERM_LEN` bytes.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| {
+            let mut term = token.to_lowercase();
+            if term.len() > MAX_TERM_LEN {
+                let mut end = MAX_TERM_LEN;
+                while !term.is_char_boundary(end) {
+                    end -= 1;
+                }
+                term.truncate(end);
+            }
+            term
+        })
+        .collect()
+}
+
+/// A full-text query against an `InvertedIndex`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextQuery {
+    /// Rows containing the term
+    Term(String),
+    /// Rows containing the phrase's terms next to each other, in order
+    Phrase(String),
+}
+
+impl TextQuery {
+    /// Returns the query's terms, in order.
+    fn terms(&self) -> Vec<String> {
+        match self {
+            // A term that tokenizes into several terms can't match anything
+            TextQuery::Term(term) => match tokenize(term).as_slice() {
+                [term] => vec![term.clone()],
+                _ => Vec::new(),
+            },
+            TextQuery::Phrase(phrase) => tokenize(phrase),
+        }
+    }
+
+    /// Returns true if `text` matches the query.
+    pub fn matches(&self, text: &str) -> bool {
+        let terms = self.terms();
+        if terms.is_empty() {
+            return false;
+        }
+        tokenize(text)
+            .windows(terms.len())
+            .any(|window| window == terms.as_slice())
+    }
+}
+
+/// Where a term's posting list lives.
+#[derive(Debug, Clone, Copy)]
+struct TermEntry {
+    head: PageId,
+    tail: PageId,
+    /// The term's record in the dictionary
+    record_id: RecordId,
+}
+
+/// An inverted index over text: for every term, the rows that contain it and
+/// the term's positions in each.
+///
+/// Each term's postings are a chain of posting pages, appended to at the
+/// tail. The term dictionary is a TableHeap of
+/// `head u32 | tail u32 | term` records, loaded into memory on open. Posting
+/// pages hold `next_page u32 | used u16` followed by entries of
+/// `page_id u32 | slot u16 | count u16 | positions u16 * count`.
+///
+/// Postings are never removed: deleted rows are left for the reader to skip,
+/// and a reused record ID can match a stale posting, so callers recheck the
+/// rows they fetch with `TextQuery::matches`.
+pub struct InvertedIndex {
+    bpm: Arc<BufferPoolManager>,
+    dictionary: TableHeap,
+    /// In-memory dictionary; held for the whole of an insert
+    terms: Mutex<HashMap<String, TermEntry>>,
+}
+
+impl InvertedIndex {
+    /// Creates an empty index whose dictionary heap pages are stamped with
+    /// `table_id`.
+    pub fn create(bpm: Arc<BufferPoolManager>, table_id: u32) -> Result<Self> {
+        let dictionary = TableHeap::create(Arc::clone(&bpm), table_id)?;
+        Ok(Self {
+            bpm,
+            dictionary,
+            terms: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Loads an index whose dictionary heap starts at `first_page_id`.
+    pub fn open(bpm: Arc<BufferPoolManager>, table_id: u32, first_page_id: PageId) -> Result<Self> {
+        let dictionary = TableHeap::open(Arc::clone(&bpm), table_id, first_page_id)?;
+        let mut terms = HashMap::new();
+        for (record_id, data) in dictionary.scan()? {
+            if data.len() < 8 {
+                return Err(CrioError::CatalogCorrupted(
+                    "truncated term dictionary record".to_string(),
+                ));
+            }
+            let term = String::from_utf8(data[8..].to_vec()).map_err(|_| {
+                CrioError::CatalogCorrupted("invalid term in dictionary".to_string())
+            })?;
+            let entry = TermEntry {
+                head: PageId::new(u32::from_le_bytes(data[0..4].try_into().unwrap())),
+                tail: PageId::new(u32::from_le_bytes(data[4..8].try_into().unwrap())),
+                record_id,
+            };
+            terms.insert(term, entry);
+        }
+        Ok(Self {
+            bpm,
+            dictionary,
+            terms: Mutex::new(terms),
+        })
+    }
+
+    /// Returns the first page of the term dictionary; pass it to `open` to
+    /// reload.
+    pub fn dictionary_page_id(&self) -> PageId {
+        self.dictionary.first_page_id()
+    }
+
+    /// Returns the table ID stamped on the dictionary's pages.
+    pub fn dictionary_table_id(&self) -> u32 {
+        self.dictionary.table_id()
+    }
+
+    /// Returns the number of distinct terms.
+    pub fn term_count(&self) -> usize {
+        self.terms.lock().len()
+    }
+
+    /// Adds the terms of `text` to the index for the row at `record_id`.
+    pub fn insert(&self, text: &str, record_id: RecordId) -> Result<()> {
+        let mut positions: HashMap<String, Vec<u16>> = HashMap::new();
+        // VarChar values are under 64 KiB, so positions fit in a u16
+        for (position, term) in tokenize(text).into_iter().enumerate() {
+            positions.entry(term).or_default().push(position as u16);
+        }
+
+        let mut terms = self.terms.lock();
+        for (term, positions) in positions {
+            let entry = match terms.get(&term) {
+                Some(&entry) => entry,
+                None => {
+                    let page_id = self.new_posting_page()?;
+                    let record = dictionary_record(page_id, page_id, &term);
+                    let record_id = self.dictionary.insert_tuple(&record)?;
+                    let entry = TermEntry {
+                        head: page_id,
+                        tail: page_id,
+                        record_id,
+                    };
+                    terms.insert(term.clone(), entry);
+                    entry
+                }
+            };
+
+            let mut tail = entry.tail;
+            for chunk in positions.chunks(MAX_ENTRY_POSITIONS) {
+                let mut encoded = Vec::with_capacity(ENTRY_HEADER_SIZE + chunk.len() * 2);
+                encoded.extend_from_slice(&record_id.page_id.as_u32().to_le_bytes());
+                encoded.extend_from_slice(&record_id.slot_id.as_u16().to_le_bytes());
+                encoded.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+                for position in chunk {
+                    encoded.extend_from_slice(&position.to_le_bytes());
+                }
+                tail = self.append(tail, &encoded)?;
+            }
+
+            if tail != entry.tail {
+                let record = dictionary_record(entry.head, tail, &term);
+                self.dictionary.update_tuple(entry.record_id, &record)?;
+                terms.insert(term, TermEntry { tail, ..entry });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the record IDs of the rows matching `query`, in posting order,
+    /// without duplicates. Deleted rows are included.
+    pub fn search(&self, query: &TextQuery) -> Result<Vec<RecordId>> {
+        let terms = query.terms();
+        let Some((first, rest)) = terms.split_first() else {
+            return Ok(Vec::new());
+        };
+
+        let rest = rest
+            .iter()
+            .map(|term| Ok(self.postings(term)?.into_iter().collect::<HashMap<_, _>>()))
+            .collect::<Result<Vec<_>>>()?;
+        let mut seen = HashSet::new();
+        let mut record_ids = Vec::new();
+        for (record_id, positions) in self.postings(first)? {
+            let matched = positions.iter().any(|&start| {
+                rest.iter().enumerate().all(|(i, postings)| {
+                    let position = start as usize + i + 1;
+                    postings
+                        .get(&record_id)
+                        .is_some_and(|p| p.iter().any(|&p| p as usize == position))
+                })
+            });
+            if matched && seen.insert(record_id) {
+                record_ids.push(record_id);
+            }
+        }
+        Ok(record_ids)
+    }
+
+    /// Returns the posting list of `term`: each row containing it with the
+    /// term's positions in the row.
+    pub fn postings(&self, term: &str) -> Result<Vec<(RecordId, Vec<u16>)>> {
+        let Some(entry) = self.terms.lock().get(term).copied() else {
+            return Ok(Vec::new());
+        };
+
+        let mut postings: Vec<(RecordId, Vec<u16>)> = Vec::new();
+        let mut next = Some(entry.head);
+        while let Some(page_id) = next {
+            let guard = self
+                .bpm
+                .checked_read_page(page_id)?
+                .ok_or(CrioError::PageNotFound(page_id))?;
+            let data = guard.data();
+            let (next_page, used) = posting_header(data);
+
+            let mut offset = POSTING_HEADER_SIZE;
+            while offset < POSTING_HEADER_SIZE + used {
+                let record_id = RecordId::new(
+                    PageId::new(u32::from_le_bytes(
+                        data[offset..offset + 4].try_into().unwrap(),
+                    )),
+                    SlotId::new(u16::from_le_bytes(
+                        data[offset + 4..offset + 6].try_into().unwrap(),
+                    )),
+                );
+                let count =
+                    u16::from_le_bytes(data[offset + 6..offset + 8].try_into().unwrap()) as usize;
+                offset += ENTRY_HEADER_SIZE;
+                let positions = data[offset..offset + count * 2]
+                    .chunks_exact(2)
+                    .map(|b| u16::from_le_bytes([b[0], b[1]]));
+                offset += count * 2;
+
+                // A row split across entries has them next to each other
+                match postings.last_mut() {
+                    Some((last, existing)) if *last == record_id => existing.extend(positions),
+                    _ => postings.push((record_id, positions.collect())),
+                }
+            }
+            next = next_page;
+        }
+        Ok(postings)
+    }
+
+    /// Returns the first page of every posting list.
+    pub fn posting_heads(&self) -> Vec<PageId> {
+        self.terms.lock().values().map(|entry| entry.head).collect()
+    }
+
+    /// Returns the page after `page_id` in its posting list.
+    pub fn next_posting_page(&self, page_id: PageId) -> Result<Option<PageId>> {
+        let guard = self
+            .bpm
+            .checked_read_page(page_id)?
+            .ok_or(CrioError::PageNotFound(page_id))?;
+        Ok(posting_header(guard.data()).0)
+    }
+
+    /// Returns every page of the index: the dictionary heap and all posting
+    /// pages.
+    pub fn page_ids(&self) -> Result<Vec<PageId>> {
+        let mut page_ids = self.dictionary.page_ids()?;
+        for head in self.posting_heads() {
+            let mut next = Some(head);
+            while let Some(page_id) = next {
+                page_ids.push(page_id);
+                next = self.next_posting_page(page_id)?;
+            }
+        }
+        Ok(page_ids)
+    }
+
+    /// Appends an encoded entry to the posting list ending at `tail`, linking
+    /// a new page if it doesn't fit. Returns the list's tail afterwards.
+    fn append(&self, tail: PageId, entry: &[u8]) -> Result<PageId> {
+        {
+            let mut guard = self
+                .bpm
+                .checked_write_page(tail)?
+                .ok_or(CrioError::PageNotFound(tail))?;
+            let data = guard.data_mut();
+            let used = posting_header(data).1;
+            let offset = POSTING_HEADER_SIZE + used;
+            if offset + entry.len() <= PAGE_SIZE {
+                data[offset..offset + entry.len()].copy_from_slice(entry);
+                data[4..6].copy_from_slice(&((used + entry.len()) as u16).to_le_bytes());
+                return Ok(tail);
+            }
+        }
+
+        let page_id = self.new_posting_page()?;
+        {
+            let mut guard = self
+                .bpm
+                .checked_write_page(tail)?
+                .ok_or(CrioError::PageNotFound(tail))?;
+            guard.data_mut()[0..4].copy_from_slice(&page_id.as_u32().to_le_bytes());
+        }
+        self.append(page_id, entry)
+    }
+
+    /// Allocates an empty posting page.
+    fn new_posting_page(&self) -> Result<PageId> {
+        let page_id = self.bpm.new_page()?;
+        let mut guard = self
+            .bpm
+            .checked_write_page(page_id)?
+            .ok_or(CrioError::PageNotFound(page_id))?;
+        let data = guard.data_mut();
+        data[0..4].copy_from_slice(&NO_NEXT_PAGE.to_le_bytes());
+        data[4..6].copy_from_slice(&0u16.to_le_bytes());
+        Ok(page_id)
+    }
+}
+
+/// Returns a posting page's next page and the bytes used by its entries.
+fn posting_header(data: &[u8]) -> (Option<PageId>, usize) {
+    let next = u32::from_le_bytes(data[0..4].try_into().unwrap());
+    let used = u16::from_le_bytes(data[4..6].try_into().unwrap()) as usize;
+    let next = (next != NO_NEXT_PAGE).then(|| PageId::new(next));
+    (next, used.min(PAGE_SIZE - POSTING_HEADER_SIZE))
+}
+
+fn dictionary_record(head: PageId, tail: PageId, term: &str) -> Vec<u8> {
+    let mut record = Vec::with_capacity(8 + term.len());
+    record.extend_from_slice(&head.as_u32().to_le_bytes());
+    record.extend_from_slice(&tail.as_u32().to_le_bytes());
+    record.extend_from_slice(term.as_bytes());
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::disk::MemDiskManager;
+
+    fn create_bpm() -> Arc<BufferPoolManager> {
+        Arc::new(BufferPoolManager::new(
+            32,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ))
+    }
+
+    fn rid(page: u32, slot: u16) -> RecordId {
+        RecordId::new(PageId::new(page), SlotId::new(slot))
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("The quick, brown FOX -- jumps!"),
+            vec!["the", "quick", "brown", "fox", "jumps"]
+        );
+        assert!(tokenize(" ,.; ").is_empty());
+        assert_eq!(tokenize(&"é".repeat(40))[0].len(), MAX_TERM_LEN);
+    }
+
+    #[test]
+    fn test_term_and_phrase_search() {
+        let index = InvertedIndex::create(create_bpm(), 1).unwrap();
+        index.insert("the quick brown fox", rid(1, 0)).unwrap();
+        index.insert("brown bread, quick", rid(1, 1)).unwrap();
+        index.insert("a fox that is quick", rid(2, 0)).unwrap();
+
+        let term = |t: &str| index.search(&TextQuery::Term(t.to_string())).unwrap();
+        let phrase = |p: &str| index.search(&TextQuery::Phrase(p.to_string())).unwrap();
+        assert_eq!(term("QUICK"), vec![rid(1, 0), rid(1, 1), rid(2, 0)]);
+        assert_eq!(term("fox"), vec![rid(1, 0), rid(2, 0)]);
+        assert!(term("wolf").is_empty());
+        assert!(term("quick brown").is_empty());
+
+        assert_eq!(phrase("quick brown"), vec![rid(1, 0)]);
+        assert_eq!(phrase("brown fox"), vec![rid(1, 0)]);
+        assert!(phrase("brown quick").is_empty());
+        assert_eq!(phrase("bread"), vec![rid(1, 1)]);
+        assert!(phrase("").is_empty());
+
+        assert!(TextQuery::Phrase("Quick Brown".to_string()).matches("the quick brown fox"));
+        assert!(!TextQuery::Phrase("quick fox".to_string()).matches("the quick brown fox"));
+    }
+
+    #[test]
+    fn test_posting_lists_span_pages_and_reload() {
+        let bpm = create_bpm();
+        let index = InvertedIndex::create(Arc::clone(&bpm), 1).unwrap();
+        for i in 0..2000u32 {
+            index
+                .insert(&format!("common term{}", i % 10), rid(i, 0))
+                .unwrap();
+        }
+        let long_text = "word ".repeat(MAX_ENTRY_POSITIONS + 10);
+        index.insert(&long_text, rid(5000, 1)).unwrap();
+
+        let postings = index.postings("common").unwrap();
+        assert_eq!(postings.len(), 2000);
+        let word = index.postings("word").unwrap();
+        assert_eq!(word.len(), 1);
+        assert_eq!(word[0].1.len(), MAX_ENTRY_POSITIONS + 10);
+
+        let page_ids = index.page_ids().unwrap();
+        assert!(page_ids.len() > index.term_count() + 1);
+
+        let reopened =
+            InvertedIndex::open(Arc::clone(&bpm), 1, index.dictionary_page_id()).unwrap();
+        assert_eq!(reopened.term_count(), index.term_count());
+        assert_eq!(
+            reopened
+                .search(&TextQuery::Phrase("common term3".to_string()))
+                .unwrap()
+                .len(),
+            200
+        );
+        reopened.insert("common", rid(6000, 0)).unwrap();
+        assert_eq!(reopened.postings("common").unwrap().len(), 2001);
+    }
+}
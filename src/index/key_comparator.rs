@@ -1,5 +1,7 @@
 use std::cmp::Ordering;
 
+use crate::tuple::Collation;
+
 pub trait KeyComparator: Send + Sync {
     fn compare(&self, a: &[u8], b: &[u8]) -> Ordering;
 }
@@ -26,3 +28,16 @@ impl KeyComparator for BytewiseComparator {
         a.cmp(b)
     }
 }
+
+/// Compares UTF-8 string keys under a collation. Keys that are not valid
+/// UTF-8 fall back to byte order.
+pub struct CollatedComparator(pub Collation);
+
+impl KeyComparator for CollatedComparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        match (std::str::from_utf8(a), std::str::from_utf8(b)) {
+            (Ok(a), Ok(b)) => self.0.compare(a, b),
+            _ => a.cmp(b),
+        }
+    }
+}
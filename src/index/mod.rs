@@ -1,9 +1,15 @@
+pub mod bloom_filter;
 pub mod btree_page;
 pub mod btree_index;
 pub mod btree_iterator;
+pub mod inverted_index;
 pub mod key_comparator;
 
+pub use bloom_filter::{BloomFilter, ExtentBloomFilters};
 pub use btree_index::BTreeIndex;
 pub use btree_iterator::BTreeIterator;
 pub use btree_page::{BTreeNode, BTreeNodeRef, KeyValuePair};
-pub use key_comparator::{BytewiseComparator, IntegerComparator, KeyComparator};
+pub use inverted_index::{tokenize, InvertedIndex, TextQuery};
+pub use key_comparator::{
+    BytewiseComparator, CollatedComparator, IntegerComparator, KeyComparator,
+};
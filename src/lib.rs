@@ -9,28 +9,124 @@
 //! The system is organized into several layers:
 //!
 //! - **Storage Layer** (`storage`): Handles disk I/O and page organization
-//!   - `DiskManager`: Reads and writes pages to/from disk
-//!   - `DiskScheduler`: Asynchronous disk I/O scheduling
+//!   - `StorageBackend`: Pluggable page storage interface
+//!   - `DiskManager`: File-backed `StorageBackend` that reads and writes pages to/from disk
+//!   - `MemDiskManager`: In-memory `StorageBackend` for tests and ephemeral databases
+//!   - `ShadowStorage`: `StorageBackend` wrapper giving atomic commits by shadow paging
+//!   - `DoubleWriteStorage`: `StorageBackend` wrapper staging writes so torn pages can be restored
+//!   - `ObjectStoreBackend`: `StorageBackend` over an S3-compatible `ObjectStore`, one object per
+//!     extent, with range GETs and a local write buffer written back an extent per PUT
+//!   - `DiskScheduler`: Asynchronous disk I/O scheduling, with priority lanes and per-class I/O budgets
 //!   - `SlottedPage`: Variable-length tuple storage within pages
 //!   - `TablePage`: Table-specific page format with linked list structure
+//!   - `TableHeap`: A table's tuples stored in a chain of table pages
+//!   - `TempTable`: Append-only rows in scratch pages for temp tables and materialized CTEs;
+//!     `TempTables` holds a session's temp tables, some dropped when the transaction ends
 //!
 //! - **Buffer Pool** (`buffer`): Memory management for database pages
 //!   - `BufferPoolManager`: Fetches pages from disk and caches them in memory
 //!   - `LruKReplacer`: LRU-K page replacement policy
 //!   - `FrameHeader`: Per-frame metadata and data storage
 //!   - `ReadPageGuard`/`WritePageGuard`: RAII guards for thread-safe page access
+//!   - `ReadAheadPolicy`: Sequential read-ahead window, adapted to how read-ahead pages are used
+//!   - `SecondaryCache`: Second tier of evicted pages in a cache file on fast local storage,
+//!     replaced by CLOCK and read before the storage backend
+//!   - `BufferPoolStress`: Concurrent stress test that checks pool invariants
+//!     (`stress-test` feature)
 //!
 //! - **Tuple** (`tuple`): Typed tuple representation and serialization
-//!   - `DataType`: Column type definitions (Integer, VarChar, etc.)
+//!   - `DataType`: Column type definitions (Integer, VarChar, Json, etc.)
 //!   - `Value`: Typed values for storage and computation
+//!   - `JsonValue`: JSON documents, stored in a compact binary encoding, with `Value::json_get` path extraction
 //!   - `Schema`: Table structure with column definitions
 //!   - `Tuple`: Row representation with serialization/deserialization
-//!
-//! - **Catalog** (`catalog`): System catalog and metadata management (TODO)
-//!
-//! - **Execution** (`execution`): Query execution engine (TODO)
+//!   - `Schema`, `Column`, `DataType`, `Value` and `CrioConfig` implement serde traits
+//!     (`serde` feature)
+//!
+//! - **Concurrency** (`concurrency`): Table-level S/X locks, key-range locks and the latching
+//!   contract
+//!   - `LockManager`: Grants table and key-range locks
+//!   - `TableLock`: RAII guard held for the duration of a table operation
+//!   - `KeyRangeLock`: RAII guard over a range of index keys, keeping phantoms out of scans
+//!
+//! - **Catalog** (`catalog`): System catalog and metadata management
+//!   - `Catalog`: Table and index definitions, persisted in a heap of its own
+//!   - `PartitionScheme`: Range or hash partitioning of a table on an integer column
+//!   - `ZoneMapInfo`: Per-page min/max values of selected columns for scan pruning
+//!
+//! - **Check** (`check`): Consistency checks for on-disk structures
+//!   - `IntegrityChecker`: Walks the directory, table chains, extents, B+Trees and full-text indexes
+//!   - `check_database`: Checks every structure of a `Database` (`crio check`)
+//!
+//! - **Debug** (`debug`): Page inspection for forensics (`crio dump`)
+//!   - `dump_page`: Detects a page's format and decodes it with an annotated hexdump
+//!
+//! - **Database** (`db`): Ergonomic entry point tying the layers together
+//!   - `Database`: Opens a database file and creates tables and indexes
+//!   - `Database::attach`: Opens another database file under an alias whose tables are reached
+//!     as `alias.table` and feed the same executors as the primary's
+//!   - `DatabaseManager`: Registry of named databases in one directory, each with its own
+//!     catalog and buffer pool, for serving many tenants from one process
+//!   - `Session`: Per-connection context with settings (search path, isolation level,
+//!     statement timeout), a transaction holding table locks, temp tables and prepared statements
+//!   - `Privileges`: Roles and table grants kept in the catalog; handles made for a role with
+//!     `TableHandle::with_role` check each operation's privilege
+//!   - `CrioConfig`: Every tuning knob, built with `CrioConfig::builder()` and read by each layer
+//!   - `TableHandle`: Inserts, updates, upserts, deletes, reads, scans, index lookups and text
+//!     searches on one table, keeping its indexes in step with every write
+//!   - `PartitionedTableHandle`: Routes inserts to partitions and scans with pruning
+//!   - `JobHandle`: Progress and cancellation of bulk loads, index builds and vacuums run in the
+//!     background
+//!   - `AutoVacuum`: Background thread reclaiming deleted rows' space once a table's dead-row
+//!     count passes a threshold, paced by the background I/O budget
+//!   - `export_snapshot`: Compacted single-file copy of a database with its indexes rebuilt,
+//!     opened elsewhere with `CrioConfig::read_only`
+//!
+//! - **Workload** (`workload`): Standard workloads for performance tracking
+//!   - `WorkloadRunner`: Runs YCSB-style and TPC-B-like workloads and reports
+//!     throughput and latency percentiles (see `benches/workloads.rs`)
+//!
+//! - **Execution** (`execution`): Query execution engine
+//!   - `IndexScanExecutor`: Key-range scans along B+Tree leaves with prefetch; several ranges
+//!     are merged and their record IDs deduplicated
+//!   - `KeyPredicate`: `=`, `BETWEEN`, `IN` and `OR` conditions translated into index key ranges
+//!   - `PartitionScanExecutor`: Key-range scans over the partitions that can match
+//!   - `UpsertExecutor`: Inserts that update or skip rows conflicting on a unique index
+//!   - `FullTextScanExecutor`: Term and phrase queries through a full-text index
+//!   - `SeqScanExecutor`: Page-at-a-time table scans; equality scans skip extents by bloom
+//!     filter and range scans skip pages by zone map
+//!   - `BatchExecutor`: Batch-at-a-time output of columnar `Batch`es, with `BatchPredicate`
+//!     filters and projections evaluated a column at a time; `SeqScanExecutor` returns
+//!     batches natively and `Batched` adapts any row executor
+//!   - `StringFunction`: `UPPER`, `LOWER`, `LENGTH`, `SUBSTR`, `CONCAT` and `TRIM`, computed
+//!     into batch columns by `BatchExpression`; `BatchPredicate` matches `LIKE` and `ILIKE`
+//!   - `HashJoinExecutor`: Equi-joins of two executors' rows, hashing the build side in memory
+//!   - `SortExecutor`: `ORDER BY` on several keys, spilling sorted runs to scratch pages and
+//!     merging them once its buffer passes the budget's spill threshold
+//!   - `WindowExecutor`: `ROW_NUMBER`, `RANK`, `DENSE_RANK` and running `COUNT`, `SUM`, `AVG`,
+//!     `MIN` and `MAX` over partitions, on top of `SortExecutor`
+//!   - `RuntimeFilter`: In-list or bloom filter of a small hash join build side, pushed into the
+//!     probe-side scan through a `RuntimeFilterSlot` to skip rows and zone-mapped pages early;
+//!     `TableHandle::hash_join` plans it and `ExplainAnalyze` reports what it skipped
+//!   - `JoinType`: Semi- and anti-joins for `EXISTS`, `IN`, `NOT EXISTS` and `NOT IN`
+//!   - `Cte`: `WITH` queries, inlined when read once and materialized into a `TempTable`
+//!     when read more often; `Session::cte` keeps them in the session's scratch file
+//!   - `SubqueryExecutor`: Scalar and correlated `EXISTS` subqueries per outer row, with a
+//!     `SubqueryCache` running the subquery once per distinct correlation value;
+//!     `TableHandle::scan_subquery` runs one over a table's rows
+//!   - `PlanCache`: Plans by normalized statement text, dropped once a table they read changes
+//!     (`Catalog::table_version`), with hit, miss and invalidation counts; each database
+//!     keeps one that `Session::execute` runs prepared statements through
+//!   - Every executor stops with `QueryCancelled` once its `CancellationToken` is cancelled
+//!     or its statement timeout passes
+//!   - Scans charge the pages, row bytes and scratch pages they use to a `QueryBudget` and
+//!     fail with `BudgetExceeded` past its limits; `ExplainAnalyze` reports the usage
+//!   - `MemoryReservation`: Executor memory (hash tables, sort buffers) held out of the query's
+//!     `QueryBudget`, with a hard limit and a spill threshold
 //!
 //! - **Index** (`index`): B+Tree index structures
+//!   - `InvertedIndex`: Full-text index of VarChar columns, with positional posting lists
+//!   - `ExtentBloomFilters`: Per-extent bloom filters over a table column
 //!
 //! # Example
 //!
@@ -64,11 +160,16 @@
 
 pub mod buffer;
 pub mod catalog;
+pub mod check;
 pub mod common;
+pub mod concurrency;
+pub mod db;
+pub mod debug;
 pub mod execution;
 pub mod index;
 pub mod storage;
 pub mod tuple;
+pub mod workload;
 
 // Re-export commonly used types at the crate root
 pub use common::{CrioError, PageId, RecordId, Result, SlotId};
@@ -1,22 +1,91 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{self, IoSlice, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
 
 use parking_lot::{Mutex, RwLock};
 
-use crate::common::{CrioError, PageId, Result, PAGE_SIZE};
+use crate::common::{
+    CrioError, PageId, Result, DEFAULT_GROWTH_MAX_PAGES, DEFAULT_GROWTH_MIN_PAGES,
+    DEFAULT_SEGMENT_PAGES, PAGE_SIZE,
+};
 use crate::storage::page::{DirectoryPage, DirectoryPageRef};
 
-use super::extent_allocator::ExtentAllocator;
+use super::extent_allocator::{ExtentAllocator, EXTENT_SIZE};
+use super::storage_backend::StorageBackend;
+use super::temp_file_manager::TempFileManager;
 
 pub const DIRECTORY_PAGE_ID: PageId = PageId::new_const(0);
 
+/// How far a page write is pushed towards stable storage before it returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum DurabilityLevel {
+    /// Hand the data to the OS and never fsync implicitly, not even when the
+    /// DiskManager is dropped. Meant for scratch and test databases.
+    None = 0,
+    /// Flush after every write so the data is in the OS page cache, and fsync
+    /// on drop. Survives a process crash, not a power loss. This is the default.
+    #[default]
+    Flush = 1,
+    /// `fdatasync` the segment after every write. Every completed write
+    /// survives a power loss, at the cost of one sync per page write.
+    FsyncPerWrite = 2,
+    /// Flush after every write and `fdatasync` every segment written since the
+    /// last commit when `sync()` is called. Writes are durable once `sync()`
+    /// returns; anything after the last `sync()` may be lost.
+    FsyncOnCommit = 3,
+}
+
+impl DurabilityLevel {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => DurabilityLevel::None,
+            1 => DurabilityLevel::Flush,
+            2 => DurabilityLevel::FsyncPerWrite,
+            _ => DurabilityLevel::FsyncOnCommit,
+        }
+    }
+}
+
+/// How a segment file's disk space grows as pages are allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GrowthPolicy {
+    /// Let each write past the end of the file extend it. This is the default.
+    #[default]
+    OnWrite,
+    /// Reserve disk space ahead of allocation to limit fragmentation. When a
+    /// page is allocated past the reserved end of its segment, the reservation
+    /// grows by as many pages as are already reserved, clamped to
+    /// `min_pages..=max_pages` and to the segment size. Space is reserved
+    /// without changing the file's length, so reopening never mistakes it for
+    /// allocated pages. Only Linux reserves anything; elsewhere this behaves
+    /// like `OnWrite`.
+    Chunked { min_pages: u32, max_pages: u32 },
+}
+
+impl GrowthPolicy {
+    /// Chunks of 8 MB, doubling up to 64 MB.
+    pub const fn chunked() -> Self {
+        GrowthPolicy::Chunked {
+            min_pages: DEFAULT_GROWTH_MIN_PAGES,
+            max_pages: DEFAULT_GROWTH_MAX_PAGES,
+        }
+    }
+}
+
 /// DiskManager is responsible for reading and writing pages to/from disk.
 /// It manages multiple database files (segments) and tracks the number of pages allocated.
 /// Supports both single-page and sequential multi-page I/O for performance.
 /// Uses extent-based allocation to keep pages for the same table contiguous.
+///
+/// Allocation works in a linear "virtual" page space that is split into segments
+/// of `segment_pages` pages each: virtual page `v` lives in file `v / segment_pages`
+/// at offset `v % segment_pages`. When allocation crosses the end of a segment the
+/// next file is created transparently.
 pub struct DiskManager {
     /// Map of FileID -> File Handle.
     /// Outer RwLock allows concurrent reads/writes to different files.
@@ -24,14 +93,37 @@ pub struct DiskManager {
     files: RwLock<HashMap<u8, Mutex<File>>>,
     /// Base path for database files
     db_path: PathBuf,
-    /// Total number of pages allocated across all files (approximate)
+    /// Maximum number of pages per segment file
+    segment_pages: u32,
+    /// Total number of pages allocated across all files (virtual page high-water mark)
     num_pages: AtomicU32,
     /// Number of disk reads performed
     num_reads: AtomicU32,
     /// Number of disk writes performed
     num_writes: AtomicU32,
+    /// Number of file syncs (fsync/fdatasync) performed
+    num_syncs: AtomicU32,
+    /// Durability level applied to page writes (a `DurabilityLevel` discriminant)
+    durability: AtomicU8,
+    /// Files written since the last `sync()`, for `FsyncOnCommit`
+    unsynced_files: Mutex<HashSet<u8>>,
+    /// How segment files grow
+    growth_policy: Mutex<GrowthPolicy>,
+    /// Pages from the start of each segment known to have disk space, either
+    /// written or reserved by the growth policy
+    reserved_pages: Mutex<HashMap<u8, u32>>,
+    /// Whether freed extents are punched out of their segment file. Only set
+    /// with the `punch-holes` feature, and cleared once the filesystem
+    /// reports it can't punch holes
+    hole_punching: AtomicBool,
     /// Extent allocator for tracking free space
     extent_allocator: ExtentAllocator,
+    /// True if the previous session did not close cleanly
+    unclean_shutdown: bool,
+    /// True if opened with `open_read_only`; every write fails
+    read_only: bool,
+    /// Counter used to name temp files
+    next_temp_id: AtomicU32,
 }
 
 impl DiskManager {
@@ -39,8 +131,37 @@ impl DiskManager {
     /// Scans for files named `db_path.0`, `db_path.1`, etc.
     /// If no files exist, creates `db_path.0` and initializes the directory page.
     pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
-        let db_path = db_path.as_ref().to_path_buf();
+        Self::with_segment_pages(db_path, DEFAULT_SEGMENT_PAGES)
+    }
+
+    /// Creates a new DiskManager whose segment files hold at most `segment_pages` pages.
+    /// `segment_pages` must be a non-zero multiple of `EXTENT_SIZE` no larger than
+    /// the 24-bit page offset range, and must match the value used when the
+    /// database was created, which the directory page records; opening with
+    /// another fails with `InvalidSegmentSize`.
+    pub fn with_segment_pages<P: AsRef<Path>>(db_path: P, segment_pages: u32) -> Result<Self> {
+        Self::open(db_path.as_ref(), segment_pages, false)
+    }
+
+    /// Opens an existing database without ever writing to it: segment files
+    /// are opened read-only, the clean-shutdown flag is left alone, and page
+    /// writes, allocation and deallocation fail with `ReadOnly`. Suits
+    /// snapshots on read-only media or shared between processes.
+    pub fn open_read_only<P: AsRef<Path>>(db_path: P, segment_pages: u32) -> Result<Self> {
+        Self::open(db_path.as_ref(), segment_pages, true)
+    }
+
+    fn open(db_path: &Path, segment_pages: u32, read_only: bool) -> Result<Self> {
+        if segment_pages == 0
+            || !segment_pages.is_multiple_of(EXTENT_SIZE)
+            || segment_pages > DEFAULT_SEGMENT_PAGES
+        {
+            return Err(CrioError::InvalidSegmentSize(segment_pages));
+        }
+
+        let db_path = db_path.to_path_buf();
         let mut files = HashMap::new();
+        let mut reserved_pages = HashMap::new();
         let mut total_pages = 0;
         let mut max_file_id = 0;
 
@@ -51,17 +172,30 @@ impl DiskManager {
                 break;
             }
 
-            let file = OpenOptions::new().read(true).write(true).open(&file_path)?;
+            let file = OpenOptions::new()
+                .read(true)
+                .write(!read_only)
+                .open(&file_path)?;
 
             let metadata = file.metadata()?;
             let file_size = metadata.len();
             let pages_in_file = (file_size / PAGE_SIZE as u64) as u32;
+            if pages_in_file > segment_pages {
+                return Err(CrioError::InvalidSegmentSize(segment_pages));
+            }
 
             files.insert(max_file_id, Mutex::new(file));
-            total_pages += pages_in_file;
+            reserved_pages.insert(max_file_id, pages_in_file);
+            // Earlier segments are treated as full; only the last one may be partial
+            total_pages = max_file_id as u32 * segment_pages + pages_in_file;
             max_file_id += 1;
         }
 
+        // A read-only database must already exist
+        if read_only && total_pages == 0 {
+            return Err(CrioError::InvalidDatabaseFile);
+        }
+
         // If no files found, create the first one (File 0)
         if files.is_empty() {
             let file_path = Self::get_segment_path(&db_path, 0);
@@ -75,19 +209,27 @@ impl DiskManager {
             files.insert(0, Mutex::new(file));
         }
 
-        let extent_allocator = if total_pages > 0 {
-            ExtentAllocator::from_existing(total_pages)
-        } else {
-            ExtentAllocator::new()
-        };
+        // The directory page always occupies virtual page 0, so the allocator must
+        // never hand it out to a table.
+        let extent_allocator = ExtentAllocator::from_existing(total_pages.max(1));
 
-        let dm = Self {
+        let mut dm = Self {
             files: RwLock::new(files),
             db_path,
+            segment_pages,
             num_pages: AtomicU32::new(total_pages),
             num_reads: AtomicU32::new(0),
             num_writes: AtomicU32::new(0),
+            num_syncs: AtomicU32::new(0),
+            durability: AtomicU8::new(DurabilityLevel::Flush as u8),
+            unsynced_files: Mutex::new(HashSet::new()),
+            growth_policy: Mutex::new(GrowthPolicy::default()),
+            reserved_pages: Mutex::new(reserved_pages),
+            hole_punching: AtomicBool::new(cfg!(feature = "punch-holes")),
             extent_allocator,
+            unclean_shutdown: false,
+            read_only,
+            next_temp_id: AtomicU32::new(0),
         };
 
         // Initialize or validate directory page if we just created File 0 or it's empty
@@ -98,6 +240,13 @@ impl DiskManager {
             if total_pages > 0 {
                 dm.validate_directory_page()?;
             }
+            if read_only {
+                dm.unclean_shutdown = !dm.read_clean_shutdown_flag()?;
+                return Ok(dm);
+            }
+            // Clear the flag up front so a crash during this session is
+            // detected on the next open
+            dm.unclean_shutdown = !dm.swap_clean_shutdown_flag(false)?;
         }
 
         Ok(dm)
@@ -117,6 +266,7 @@ impl DiskManager {
         {
             let mut dir_page = DirectoryPage::new(&mut data);
             dir_page.init();
+            dir_page.set_segment_pages(self.segment_pages);
         }
 
         self.num_pages.store(1, Ordering::SeqCst);
@@ -128,6 +278,7 @@ impl DiskManager {
         file.seek(SeekFrom::Start(0))?;
         file.write_all(&data)?;
         file.flush()?;
+        self.unsynced_files.lock().insert(0);
 
         self.num_writes.fetch_add(1, Ordering::Relaxed);
         Ok(())
@@ -142,11 +293,17 @@ impl DiskManager {
                 let mut file = mutex.lock();
                 file.seek(SeekFrom::Start(0))?;
                 // Handle case where file exists but is empty
-                if let Ok(_) = file.read_exact(&mut data) {
+                if file.read_exact(&mut data).is_ok() {
                     let dir_page = DirectoryPageRef::new(&data);
                     if !dir_page.is_valid() {
                         return Err(CrioError::InvalidDatabaseFile);
                     }
+                    // Databases that didn't record their segment size can't
+                    // be checked
+                    let segment_pages = dir_page.segment_pages();
+                    if segment_pages != 0 && segment_pages != self.segment_pages {
+                        return Err(CrioError::InvalidSegmentSize(self.segment_pages));
+                    }
                 }
             }
         }
@@ -154,6 +311,165 @@ impl DiskManager {
         Ok(())
     }
 
+    /// Returns the directory page's clean-shutdown flag.
+    fn read_clean_shutdown_flag(&self) -> Result<bool> {
+        let mut data = [0u8; PAGE_SIZE];
+        let files = self.files.read();
+        let mut file = files.get(&0).unwrap().lock();
+
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut data)?;
+        Ok(DirectoryPageRef::new(&data).clean_shutdown())
+    }
+
+    /// Sets the directory page's clean-shutdown flag, syncs file 0 and returns
+    /// the previous value.
+    fn swap_clean_shutdown_flag(&self, clean: bool) -> Result<bool> {
+        let mut data = [0u8; PAGE_SIZE];
+        let files = self.files.read();
+        let mut file = files.get(&0).unwrap().lock();
+
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut data)?;
+        let mut dir_page = DirectoryPage::new(&mut data);
+        let previous = dir_page.clean_shutdown();
+        dir_page.set_clean_shutdown(clean);
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        self.num_syncs.fetch_add(1, Ordering::Relaxed);
+        Ok(previous)
+    }
+
+    /// Returns true if the previous session ended without `mark_clean_shutdown`,
+    /// e.g. after a crash, so callers know to run recovery or an integrity
+    /// check. Databases written before the flag existed also report true.
+    pub fn unclean_shutdown(&self) -> bool {
+        self.unclean_shutdown
+    }
+
+    /// Returns true if the database was opened with `open_read_only`.
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(CrioError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Syncs every segment and then records a clean shutdown in the directory
+    /// page. Must be the last write of the session; the flag is cleared again
+    /// on the next open.
+    pub fn mark_clean_shutdown(&self) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        self.sync()?;
+        self.swap_clean_shutdown_flag(true)?;
+        Ok(())
+    }
+
+    /// Maps a virtual page number to its segment file and offset.
+    fn virtual_to_physical(&self, virtual_page: u32) -> Result<PageId> {
+        let file_id = virtual_page / self.segment_pages;
+        if file_id >= u8::MAX as u32 {
+            return Err(CrioError::MaxFilesReached);
+        }
+        Ok(PageId::from_parts(
+            file_id as u8,
+            virtual_page % self.segment_pages,
+        ))
+    }
+
+    /// Maps a physical page ID back to its virtual page number.
+    fn physical_to_virtual(&self, page_id: PageId) -> u32 {
+        page_id.file_id() as u32 * self.segment_pages + page_id.page_offset()
+    }
+
+    /// Creates segment files up to and including `file_id` if they don't exist yet.
+    fn ensure_segment(&self, file_id: u8) -> Result<()> {
+        while self.files.read().len() <= file_id as usize {
+            self.add_file()?;
+        }
+        Ok(())
+    }
+
+    /// Maps a freshly allocated virtual page to a physical page, rolling over to
+    /// a new segment file if needed.
+    fn place_page(&self, virtual_page: u32) -> Result<PageId> {
+        let page_id = self.virtual_to_physical(virtual_page)?;
+        self.ensure_segment(page_id.file_id())?;
+        self.grow_segment(page_id);
+
+        let required_pages = virtual_page + 1;
+        self.num_pages.fetch_max(required_pages, Ordering::SeqCst);
+
+        Ok(page_id)
+    }
+
+    /// Reserves disk space past `page_id` if the growth policy asks for it
+    /// and the page lies beyond the segment's reserved end. Reservation is
+    /// best effort: if the filesystem can't do it, writes extend the file as
+    /// usual.
+    fn grow_segment(&self, page_id: PageId) {
+        let GrowthPolicy::Chunked {
+            min_pages,
+            max_pages,
+        } = self.growth_policy()
+        else {
+            return;
+        };
+
+        let file_id = page_id.file_id();
+        let mut reserved_pages = self.reserved_pages.lock();
+        let reserved = reserved_pages.entry(file_id).or_insert(0);
+        if page_id.page_offset() < *reserved {
+            return;
+        }
+
+        let chunk = (*reserved).max(min_pages).min(max_pages).max(1);
+        let end = (*reserved + chunk)
+            .max(page_id.page_offset() + 1)
+            .min(self.segment_pages);
+        if let Some(file) = self.files.read().get(&file_id) {
+            let offset = *reserved as u64 * PAGE_SIZE as u64;
+            let len = (end - *reserved) as u64 * PAGE_SIZE as u64;
+            let _ = reserve_space(&file.lock(), offset, len);
+        }
+        // Even on failure, so an unsupported filesystem isn't retried per page
+        *reserved = end;
+    }
+
+    /// Sets how segment files grow from now on.
+    pub fn set_growth_policy(&self, policy: GrowthPolicy) {
+        *self.growth_policy.lock() = policy;
+    }
+
+    /// Returns how segment files grow.
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        *self.growth_policy.lock()
+    }
+
+    /// Returns the disk space taken by the segment files in bytes, including
+    /// space reserved by the growth policy. Where block counts are not
+    /// available this is the files' total length.
+    pub fn physical_size(&self) -> Result<u64> {
+        let files = self.files.read();
+        let mut size = 0;
+        for (&file_id, file) in files.iter() {
+            let metadata = file
+                .lock()
+                .metadata()
+                .map_err(|e| CrioError::segment_io(file_id, e))?;
+            size += disk_usage(&metadata);
+        }
+        Ok(size)
+    }
+
     pub fn read_directory_page(&self, data: &mut [u8]) -> Result<()> {
         self.read_page(DIRECTORY_PAGE_ID, data)
     }
@@ -165,13 +481,14 @@ impl DiskManager {
     /// Adds a new file segment to the database.
     /// Returns the new File ID.
     pub fn add_file(&self) -> Result<u8> {
+        self.check_writable()?;
         let mut files = self.files.write();
-        let next_file_id = files.len() as u8;
 
         // Hard limit check (since we use u8 for FileID)
-        if next_file_id == u8::MAX {
-            return Err(CrioError::DiskScheduler("Max files reached".to_string()));
+        if files.len() >= u8::MAX as usize {
+            return Err(CrioError::MaxFilesReached);
         }
+        let next_file_id = files.len() as u8;
 
         let file_path = Self::get_segment_path(&self.db_path, next_file_id);
         let file = OpenOptions::new()
@@ -179,7 +496,8 @@ impl DiskManager {
             .write(true)
             .create(true)
             .truncate(false)
-            .open(&file_path)?;
+            .open(&file_path)
+            .map_err(|e| CrioError::segment_io(next_file_id, e))?;
 
         files.insert(next_file_id, Mutex::new(file));
         Ok(next_file_id)
@@ -199,9 +517,10 @@ impl DiskManager {
             .ok_or(CrioError::InvalidPageId(page_id))?;
 
         let mut file = file_mutex.lock();
-        file.seek(SeekFrom::Start(byte_offset))?;
-
-        let bytes_read = file.read(data)?;
+        let bytes_read = file
+            .seek(SeekFrom::Start(byte_offset))
+            .and_then(|_| file.read(data))
+            .map_err(|e| CrioError::page_read(page_id, byte_offset, e))?;
         if bytes_read < PAGE_SIZE {
             data[bytes_read..].fill(0);
         }
@@ -213,6 +532,7 @@ impl DiskManager {
     /// Writes a page to disk from the provided buffer.
     pub fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()> {
         assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+        self.check_writable()?;
 
         let file_id = page_id.file_id();
         let page_offset = page_id.page_offset();
@@ -224,9 +544,10 @@ impl DiskManager {
             .ok_or(CrioError::InvalidPageId(page_id))?;
 
         let mut file = file_mutex.lock();
-        file.seek(SeekFrom::Start(byte_offset))?;
-        file.write_all(data)?;
-        file.flush()?;
+        file.seek(SeekFrom::Start(byte_offset))
+            .and_then(|_| file.write_all(data))
+            .and_then(|_| self.apply_durability(file_id, &mut file))
+            .map_err(|e| CrioError::page_write(page_id, byte_offset, e))?;
 
         self.num_writes.fetch_add(1, Ordering::Relaxed);
         Ok(())
@@ -241,15 +562,15 @@ impl DiskManager {
         let file_id = start_page_id.file_id();
         let start_offset = start_page_id.page_offset();
 
-        let end_offset = start_offset
+        if start_offset
             .checked_add(num_pages)
-            .ok_or_else(|| CrioError::DiskScheduler("Page range overflow".to_string()))?;
-
-        if end_offset > PageId::PAGE_OFFSET_MASK + 1 {
-            return Err(CrioError::DiskScheduler(format!(
-                "Sequential read crosses file boundary: start={}, count={}",
-                start_offset, num_pages
-            )));
+            .is_none_or(|end_offset| end_offset > self.segment_pages)
+        {
+            return Err(CrioError::FileBoundaryCrossed {
+                file_id,
+                start_offset,
+                num_pages,
+            });
         }
 
         let byte_offset = (start_offset as u64) * (PAGE_SIZE as u64);
@@ -260,9 +581,10 @@ impl DiskManager {
             .ok_or(CrioError::InvalidPageId(start_page_id))?;
 
         let mut file = file_mutex.lock();
-        file.seek(SeekFrom::Start(byte_offset))?;
-
-        let bytes_read = file.read(data)?;
+        let bytes_read = file
+            .seek(SeekFrom::Start(byte_offset))
+            .and_then(|_| file.read(data))
+            .map_err(|e| CrioError::page_read(start_page_id, byte_offset, e))?;
         if bytes_read < expected_size {
             data[bytes_read..].fill(0);
         }
@@ -275,19 +597,20 @@ impl DiskManager {
     pub fn write_pages(&self, start_page_id: PageId, num_pages: u32, data: &[u8]) -> Result<()> {
         let expected_size = (num_pages as usize) * PAGE_SIZE;
         assert_eq!(data.len(), expected_size);
+        self.check_writable()?;
 
         let file_id = start_page_id.file_id();
         let start_offset = start_page_id.page_offset();
 
-        let end_offset = start_offset
+        if start_offset
             .checked_add(num_pages)
-            .ok_or_else(|| CrioError::DiskScheduler("Page range overflow".to_string()))?;
-
-        if end_offset > PageId::PAGE_OFFSET_MASK + 1 {
-            return Err(CrioError::DiskScheduler(format!(
-                "Sequential write crosses file boundary: start={}, count={}",
-                start_offset, num_pages
-            )));
+            .is_none_or(|end_offset| end_offset > self.segment_pages)
+        {
+            return Err(CrioError::FileBoundaryCrossed {
+                file_id,
+                start_offset,
+                num_pages,
+            });
         }
 
         let byte_offset = (start_offset as u64) * (PAGE_SIZE as u64);
@@ -298,28 +621,73 @@ impl DiskManager {
             .ok_or(CrioError::InvalidPageId(start_page_id))?;
 
         let mut file = file_mutex.lock();
-        file.seek(SeekFrom::Start(byte_offset))?;
-        file.write_all(data)?;
-        file.flush()?;
+        file.seek(SeekFrom::Start(byte_offset))
+            .and_then(|_| file.write_all(data))
+            .and_then(|_| self.apply_durability(file_id, &mut file))
+            .map_err(|e| CrioError::page_write(start_page_id, byte_offset, e))?;
 
         self.num_writes.fetch_add(1, Ordering::Relaxed);
         Ok(())
     }
 
-    /// Allocates a new page on disk and returns its page ID.
-    /// Currently defaults to allocating in File 0.
-    /// TODO: Implement logic to choose file based on free space or create new files.
-    pub fn allocate_page(&self) -> Result<PageId> {
-        // Simple allocation strategy: Linear growth in File 0
-        // In a real system, we'd check free maps or file capacity.
-        let raw_count = self.num_pages.fetch_add(1, Ordering::SeqCst);
+    /// Writes a batch of possibly scattered pages. Each segment file is locked
+    /// once and the durability level is applied once per file rather than once
+    /// per page. Only contiguous runs are batched: each run of adjacent pages
+    /// costs one seek and one `writev`, so pages with gaps between them still
+    /// take a write each. Pages should be sorted by page ID.
+    pub fn write_pages_vectored(&self, pages: &[(PageId, &[u8])]) -> Result<()> {
+        self.check_writable()?;
+        let files = self.files.read();
 
-        // For now, map everything to File 0.
-        // Note: This relies on num_pages being consistent with File 0's size.
-        // If we have multiple files, this simple counter logic breaks.
-        // FIX: Use PageId::from_parts(0, raw_count) assuming raw_count tracks File 0 usage.
+        let mut start = 0;
+        while start < pages.len() {
+            let file_id = pages[start].0.file_id();
+            let file_mutex = files
+                .get(&file_id)
+                .ok_or(CrioError::InvalidPageId(pages[start].0))?;
+            let mut file = file_mutex.lock();
+
+            while start < pages.len() && pages[start].0.file_id() == file_id {
+                let mut end = start + 1;
+                while end < pages.len()
+                    && pages[end].0.file_id() == file_id
+                    && pages[end].0.page_offset() == pages[end - 1].0.page_offset() + 1
+                {
+                    end += 1;
+                }
 
-        let page_id = PageId::from_parts(0, raw_count);
+                let run_start = pages[start].0;
+                let byte_offset = (run_start.page_offset() as u64) * (PAGE_SIZE as u64);
+                let mut slices: Vec<IoSlice<'_>> = pages[start..end]
+                    .iter()
+                    .map(|&(_, data)| {
+                        assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+                        IoSlice::new(data)
+                    })
+                    .collect();
+
+                file.seek(SeekFrom::Start(byte_offset))
+                    .and_then(|_| write_all_vectored(&mut file, &mut slices))
+                    .map_err(|e| CrioError::page_write(run_start, byte_offset, e))?;
+
+                self.num_writes.fetch_add(1, Ordering::Relaxed);
+                start = end;
+            }
+
+            self.apply_durability(file_id, &mut file)
+                .map_err(|e| CrioError::segment_io(file_id, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Allocates a new page on disk and returns its page ID.
+    /// Pages grow linearly through the virtual page space, rolling over into a
+    /// new segment file once the current one is full.
+    pub fn allocate_page(&self) -> Result<PageId> {
+        self.check_writable()?;
+        let virtual_page = self.extent_allocator.allocate_linear_page(&self.num_pages);
+        let page_id = self.place_page(virtual_page.as_u32())?;
 
         let zeros = [0u8; PAGE_SIZE];
         self.write_page(page_id, &zeros)?;
@@ -329,23 +697,9 @@ impl DiskManager {
 
     /// Allocates a new page for a specific table.
     pub fn allocate_page_for_table(&self, table_id: u32) -> Result<PageId> {
+        self.check_writable()?;
         let virtual_page_id = self.extent_allocator.allocate_page_for_table(table_id)?;
-
-        let page_offset = virtual_page_id.as_u32();
-        if page_offset > PageId::PAGE_OFFSET_MASK {
-            return Err(CrioError::DiskScheduler(format!(
-                "Page offset {} exceeds 24-bit limit",
-                page_offset
-            )));
-        }
-
-        let page_id = PageId::from_parts(0, page_offset);
-
-        let required_pages = (page_offset + 1) as u32;
-        let current_pages = self.num_pages.load(Ordering::Relaxed);
-        if required_pages > current_pages {
-            self.num_pages.store(required_pages, Ordering::SeqCst);
-        }
+        let page_id = self.place_page(virtual_page_id.as_u32())?;
 
         let zeros = [0u8; PAGE_SIZE];
         self.write_page(page_id, &zeros)?;
@@ -354,36 +708,19 @@ impl DiskManager {
     }
 
     pub fn allocate_extent_for_table(&self, table_id: u32) -> Result<Vec<PageId>> {
+        self.check_writable()?;
         let virtual_pages = self.extent_allocator.allocate_extent_for_table(table_id)?;
 
+        // Extents are aligned and segment sizes are a multiple of EXTENT_SIZE,
+        // so a whole extent always lands in a single file.
         let mut real_pages = Vec::with_capacity(virtual_pages.len());
-
         for vp in &virtual_pages {
-            let page_offset = vp.as_u32();
-            if page_offset > PageId::PAGE_OFFSET_MASK {
-                return Err(CrioError::DiskScheduler(format!(
-                    "Page offset {} exceeds 24-bit limit",
-                    page_offset
-                )));
-            }
-        }
-
-        if let Some(last_page) = virtual_pages.last() {
-            let required_pages = last_page.as_u32() + 1;
-            let current_pages = self.num_pages.load(Ordering::Relaxed);
-            if required_pages > current_pages {
-                self.num_pages.store(required_pages, Ordering::SeqCst);
-            }
-        }
-
-        if let Some(first_virtual) = virtual_pages.first() {
-            let start_page = PageId::from_parts(0, first_virtual.as_u32());
-            let zeros = vec![0u8; PAGE_SIZE * virtual_pages.len()];
-            self.write_pages(start_page, virtual_pages.len() as u32, &zeros)?;
+            real_pages.push(self.place_page(vp.as_u32())?);
         }
 
-        for vp in virtual_pages {
-            real_pages.push(PageId::from_parts(0, vp.as_u32()));
+        if let Some(&start_page) = real_pages.first() {
+            let zeros = vec![0u8; PAGE_SIZE * real_pages.len()];
+            self.write_pages(start_page, real_pages.len() as u32, &zeros)?;
         }
 
         Ok(real_pages)
@@ -393,20 +730,112 @@ impl DiskManager {
         self.extent_allocator
             .get_contiguous_pages(table_id)
             .into_iter()
-            .map(|(pid, count)| (PageId::from_parts(0, pid.as_u32()), count))
+            .filter_map(|(pid, count)| {
+                self.virtual_to_physical(pid.as_u32())
+                    .ok()
+                    .map(|page_id| (page_id, count))
+            })
             .collect()
     }
 
     pub fn deallocate_page(&self, page_id: PageId) -> Result<()> {
+        self.check_writable()?;
         // Map back to linear space for allocator
-        // Only supports File 0 deallocation currently
-        if page_id.file_id() == 0 {
-            let virtual_pid = PageId::new(page_id.page_offset());
-            self.extent_allocator.deallocate_page(virtual_pid);
+        let virtual_pid = PageId::new(self.physical_to_virtual(page_id));
+        if self.extent_allocator.deallocate_page(virtual_pid) {
+            self.release_extent(page_id);
         }
         Ok(())
     }
 
+    /// Returns disk space of the now free extent holding `page_id` to the OS
+    /// if hole punching is enabled. Punched pages read back as zeros and get
+    /// new blocks when written again.
+    fn release_extent(&self, page_id: PageId) {
+        if !self.hole_punching() {
+            return;
+        }
+
+        let file_id = page_id.file_id();
+        let start = page_id.page_offset() / EXTENT_SIZE * EXTENT_SIZE;
+        if let Some(file) = self.files.read().get(&file_id) {
+            let offset = start as u64 * PAGE_SIZE as u64;
+            let len = EXTENT_SIZE as u64 * PAGE_SIZE as u64;
+            if let Err(e) = punch_hole(&file.lock(), offset, len) {
+                if e.kind() == io::ErrorKind::Unsupported {
+                    self.hole_punching.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Returns true if freed extents are punched out of their segment file.
+    pub fn hole_punching(&self) -> bool {
+        self.hole_punching.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if `page_id` lies within the allocated page space and has
+    /// not been deallocated.
+    pub fn is_page_allocated(&self, page_id: PageId) -> bool {
+        if page_id.page_offset() >= self.segment_pages {
+            return false;
+        }
+        let virtual_page = self.physical_to_virtual(page_id);
+        virtual_page < self.get_num_pages()
+            && self
+                .extent_allocator
+                .is_allocated(PageId::new(virtual_page))
+    }
+
+    /// Returns how many of the `num_pages` pages following `page_id` lie in
+    /// the same segment, within the allocated page space, and in extents of
+    /// the same table, stopping at the first page that doesn't. Pages
+    /// allocated outside table extents count as one table; so do all pages
+    /// after a reopen, as extent ownership is not persisted.
+    pub fn read_ahead_limit(&self, page_id: PageId, num_pages: u32) -> u32 {
+        let offset = page_id.page_offset();
+        if offset >= self.segment_pages {
+            return 0;
+        }
+        let virtual_page = self.physical_to_virtual(page_id);
+        let num_pages = num_pages
+            .min(self.segment_pages - offset - 1)
+            .min(self.get_num_pages().saturating_sub(virtual_page + 1));
+        self.extent_allocator
+            .same_owner_run(PageId::new(virtual_page), num_pages)
+    }
+
+    /// Checks the extent bitmaps for internal inconsistencies.
+    pub fn check_extents(&self) -> Vec<String> {
+        self.extent_allocator.check()
+    }
+
+    /// Creates a scratch file next to the database segments
+    /// (`db_path.tmp.PID.N`) for operator spilling. The file is removed when
+    /// the manager is dropped. Names left behind by a crashed process are
+    /// skipped, never reused.
+    pub fn create_temp_file_manager(&self, quota_pages: u32) -> Result<TempFileManager> {
+        loop {
+            let temp_id = self.next_temp_id.fetch_add(1, Ordering::Relaxed);
+            let mut path_str = self.db_path.to_string_lossy().to_string();
+            path_str.push_str(&format!(".tmp.{}.{}", std::process::id(), temp_id));
+            match TempFileManager::new(PathBuf::from(path_str), quota_pages) {
+                Err(CrioError::Io(e)) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                result => return result,
+            }
+        }
+    }
+
+    /// Returns the maximum number of pages per segment file.
+    pub fn segment_pages(&self) -> u32 {
+        self.segment_pages
+    }
+
+    /// Returns the number of segment files currently open.
+    pub fn num_files(&self) -> usize {
+        self.files.read().len()
+    }
+
     pub fn get_num_pages(&self) -> u32 {
         self.num_pages.load(Ordering::Relaxed)
     }
@@ -423,18 +852,205 @@ impl DiskManager {
         &self.db_path
     }
 
+    pub fn get_num_syncs(&self) -> u32 {
+        self.num_syncs.load(Ordering::Relaxed)
+    }
+
+    /// Sets the durability level applied to subsequent page writes.
+    pub fn set_durability(&self, level: DurabilityLevel) {
+        self.durability.store(level as u8, Ordering::SeqCst);
+    }
+
+    /// Returns the durability level applied to page writes.
+    pub fn durability(&self) -> DurabilityLevel {
+        DurabilityLevel::from_u8(self.durability.load(Ordering::SeqCst))
+    }
+
+    /// Makes every write issued so far durable by `fdatasync`ing each segment
+    /// file written since the previous `sync()`. This is the commit point for
+    /// `DurabilityLevel::FsyncOnCommit`.
     pub fn sync(&self) -> Result<()> {
+        let pending: Vec<u8> = self.unsynced_files.lock().drain().collect();
+
         let files = self.files.read();
-        for file_mutex in files.values() {
-            let file = file_mutex.lock();
-            file.sync_all()?;
+        for file_id in pending {
+            if let Some(file_mutex) = files.get(&file_id) {
+                let file = file_mutex.lock();
+                if let Err(e) = file.sync_data() {
+                    // Keep the file pending so a retry syncs it again
+                    self.unsynced_files.lock().insert(file_id);
+                    return Err(CrioError::segment_io(file_id, e));
+                }
+                self.num_syncs.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes a just-written file towards stable storage according to the
+    /// current durability level. Called with the file's lock held.
+    fn apply_durability(&self, file_id: u8, file: &mut File) -> std::io::Result<()> {
+        match self.durability() {
+            DurabilityLevel::None => {
+                self.unsynced_files.lock().insert(file_id);
+            }
+            DurabilityLevel::Flush | DurabilityLevel::FsyncOnCommit => {
+                file.flush()?;
+                self.unsynced_files.lock().insert(file_id);
+            }
+            DurabilityLevel::FsyncPerWrite => {
+                file.flush()?;
+                file.sync_data()?;
+                self.num_syncs.fetch_add(1, Ordering::Relaxed);
+            }
         }
         Ok(())
     }
 }
 
+/// Allocates disk blocks for `len` bytes at `offset` without changing the
+/// file's length.
+#[cfg(target_os = "linux")]
+fn reserve_space(file: &File, offset: u64, len: u64) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // Safety: plain syscall on a descriptor that stays open for the call
+    let ret = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_KEEP_SIZE,
+            offset as libc::off_t,
+            len as libc::off_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reserve_space(_file: &File, _offset: u64, _len: u64) -> io::Result<()> {
+    Ok(())
+}
+
+/// Deallocates the disk blocks backing `len` bytes at `offset`, keeping the
+/// file's length.
+#[cfg(all(feature = "punch-holes", target_os = "linux"))]
+fn punch_hole(file: &File, offset: u64, len: u64) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // Safety: plain syscall on a descriptor that stays open for the call
+    let ret = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+            offset as libc::off_t,
+            len as libc::off_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(all(feature = "punch-holes", target_os = "linux")))]
+fn punch_hole(_file: &File, _offset: u64, _len: u64) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Returns the bytes of disk a file occupies.
+#[cfg(unix)]
+fn disk_usage(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn disk_usage(metadata: &std::fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Writes every slice in `bufs`, retrying short and interrupted writes.
+fn write_all_vectored(file: &mut File, mut bufs: &mut [IoSlice<'_>]) -> io::Result<()> {
+    while !bufs.is_empty() {
+        match file.write_vectored(bufs) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
+            }
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+impl StorageBackend for DiskManager {
+    fn read_page(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
+        DiskManager::read_page(self, page_id, data)
+    }
+
+    fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()> {
+        DiskManager::write_page(self, page_id, data)
+    }
+
+    fn read_pages(&self, start_page_id: PageId, num_pages: u32, data: &mut [u8]) -> Result<()> {
+        DiskManager::read_pages(self, start_page_id, num_pages, data)
+    }
+
+    fn write_pages(&self, start_page_id: PageId, num_pages: u32, data: &[u8]) -> Result<()> {
+        DiskManager::write_pages(self, start_page_id, num_pages, data)
+    }
+
+    fn write_pages_vectored(&self, pages: &[(PageId, &[u8])]) -> Result<()> {
+        DiskManager::write_pages_vectored(self, pages)
+    }
+
+    fn allocate_page(&self) -> Result<PageId> {
+        DiskManager::allocate_page(self)
+    }
+
+    fn allocate_extent(&self, table_id: u32) -> Result<Vec<PageId>> {
+        DiskManager::allocate_extent_for_table(self, table_id)
+    }
+
+    fn deallocate_page(&self, page_id: PageId) -> Result<()> {
+        DiskManager::deallocate_page(self, page_id)
+    }
+
+    fn read_ahead_limit(&self, page_id: PageId, num_pages: u32) -> u32 {
+        DiskManager::read_ahead_limit(self, page_id, num_pages)
+    }
+
+    fn sync(&self) -> Result<()> {
+        DiskManager::sync(self)
+    }
+
+    fn close(&self) -> Result<()> {
+        DiskManager::mark_clean_shutdown(self)
+    }
+
+    fn get_num_reads(&self) -> u32 {
+        DiskManager::get_num_reads(self)
+    }
+
+    fn get_num_writes(&self) -> u32 {
+        DiskManager::get_num_writes(self)
+    }
+}
+
 impl Drop for DiskManager {
     fn drop(&mut self) {
+        if self.durability() == DurabilityLevel::None {
+            return;
+        }
         let files = self.files.get_mut();
         for file_mutex in files.values_mut() {
             let file = file_mutex.get_mut();
@@ -545,4 +1161,170 @@ mod tests {
             assert_eq!(data[0], 123);
         }
     }
+
+    #[test]
+    fn test_durability_levels() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("durability.db");
+        let dm = DiskManager::new(&db_path).unwrap();
+        assert_eq!(dm.durability(), DurabilityLevel::Flush);
+
+        let page_id = dm.allocate_page().unwrap();
+        let data = [7u8; PAGE_SIZE];
+
+        // Flush: no fsync until sync(), which only touches written files
+        dm.write_page(page_id, &data).unwrap();
+        assert_eq!(dm.get_num_syncs(), 0);
+        dm.add_file().unwrap();
+        dm.sync().unwrap();
+        assert_eq!(dm.get_num_syncs(), 1);
+        dm.sync().unwrap();
+        assert_eq!(dm.get_num_syncs(), 1);
+
+        // FsyncPerWrite: every write is synced, nothing left for sync()
+        dm.set_durability(DurabilityLevel::FsyncPerWrite);
+        dm.write_page(page_id, &data).unwrap();
+        dm.write_pages(page_id, 1, &data).unwrap();
+        assert_eq!(dm.get_num_syncs(), 3);
+        dm.sync().unwrap();
+        assert_eq!(dm.get_num_syncs(), 3);
+
+        // FsyncOnCommit: writes to two files are synced once each on commit
+        dm.set_durability(DurabilityLevel::FsyncOnCommit);
+        dm.write_page(page_id, &data).unwrap();
+        dm.write_page(page_id, &data).unwrap();
+        dm.write_page(PageId::from_parts(1, 0), &data).unwrap();
+        assert_eq!(dm.get_num_syncs(), 3);
+        dm.sync().unwrap();
+        assert_eq!(dm.get_num_syncs(), 5);
+
+        let mut read_data = [0u8; PAGE_SIZE];
+        dm.read_page(page_id, &mut read_data).unwrap();
+        assert_eq!(read_data, data);
+    }
+
+    #[test]
+    fn test_growth_policy_reserves_in_chunks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("growth.db");
+        let dm = DiskManager::with_segment_pages(&db_path, 512).unwrap();
+        assert_eq!(dm.growth_policy(), GrowthPolicy::OnWrite);
+        dm.set_growth_policy(GrowthPolicy::Chunked {
+            min_pages: 64,
+            max_pages: 256,
+        });
+
+        for _ in 0..100 {
+            dm.allocate_page().unwrap();
+        }
+        // Reserved 64 pages, then 64 more; writes extend only the length
+        let reserved = dm.reserved_pages.lock()[&0];
+        assert_eq!(reserved, 128);
+        let file_len = std::fs::metadata(temp_dir.path().join("growth.db.0"))
+            .unwrap()
+            .len();
+        assert_eq!(file_len, 101 * PAGE_SIZE as u64);
+        #[cfg(target_os = "linux")]
+        assert!(dm.physical_size().unwrap() >= 128 * PAGE_SIZE as u64);
+
+        // Chunks double up to the cap and stop at the segment's end
+        for _ in 100..600 {
+            dm.allocate_page().unwrap();
+        }
+        assert_eq!(dm.reserved_pages.lock()[&0], 512);
+        assert_eq!(dm.reserved_pages.lock()[&1], 128);
+        drop(dm);
+
+        // Reserved space is not mistaken for allocated pages
+        let dm = DiskManager::with_segment_pages(&db_path, 512).unwrap();
+        assert_eq!(dm.get_num_pages(), 601);
+    }
+
+    #[test]
+    fn test_read_ahead_limit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dm = DiskManager::with_segment_pages(temp_dir.path().join("ra.db"), 32).unwrap();
+        let linear = dm.allocate_page().unwrap();
+        let t1 = dm.allocate_extent_for_table(1).unwrap();
+        let t2 = dm.allocate_extent_for_table(2).unwrap();
+
+        // Stops at unallocated pages, another table's extent and the end
+        assert_eq!(dm.read_ahead_limit(DIRECTORY_PAGE_ID, 16), 1);
+        assert_eq!(dm.read_ahead_limit(linear, 16), 0);
+        assert_eq!(dm.read_ahead_limit(t1[0], 4), 4);
+        assert_eq!(dm.read_ahead_limit(t1[4], 16), 3);
+        assert_eq!(dm.read_ahead_limit(t2[5], 16), 2);
+
+        // ... and at the segment boundary
+        let t3 = dm.allocate_extent_for_table(3).unwrap();
+        dm.allocate_extent_for_table(3).unwrap();
+        assert_eq!(t3[7].page_offset(), 31);
+        assert_eq!(dm.read_ahead_limit(t3[7], 16), 0);
+    }
+
+    #[test]
+    fn test_open_read_only() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_path = temp_dir.path().join("ro.db");
+        assert!(DiskManager::open_read_only(&db_path, DEFAULT_SEGMENT_PAGES).is_err());
+
+        let page_id = {
+            let dm = DiskManager::new(&db_path).unwrap();
+            let page_id = dm.allocate_page().unwrap();
+            dm.write_page(page_id, &[7u8; PAGE_SIZE]).unwrap();
+            dm.mark_clean_shutdown().unwrap();
+            page_id
+        };
+
+        let dm = DiskManager::open_read_only(&db_path, DEFAULT_SEGMENT_PAGES).unwrap();
+        assert!(dm.read_only());
+        assert!(!dm.unclean_shutdown());
+        let mut data = [0u8; PAGE_SIZE];
+        dm.read_page(page_id, &mut data).unwrap();
+        assert_eq!(data, [7u8; PAGE_SIZE]);
+
+        assert!(matches!(
+            dm.write_page(page_id, &data),
+            Err(CrioError::ReadOnly)
+        ));
+        assert!(matches!(dm.allocate_page(), Err(CrioError::ReadOnly)));
+        assert!(matches!(
+            dm.deallocate_page(page_id),
+            Err(CrioError::ReadOnly)
+        ));
+        dm.mark_clean_shutdown().unwrap();
+        drop(dm);
+
+        // The flag was left alone, so the next writable open sees a clean
+        // shutdown too
+        assert!(!DiskManager::new(&db_path).unwrap().unclean_shutdown());
+    }
+
+    #[test]
+    fn test_freed_extent_punches_hole() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dm = DiskManager::new(temp_dir.path().join("punch.db")).unwrap();
+        assert_eq!(dm.hole_punching(), cfg!(feature = "punch-holes"));
+
+        let pages = dm.allocate_extent_for_table(1).unwrap();
+        let data = vec![0xABu8; PAGE_SIZE * pages.len()];
+        dm.write_pages(pages[0], pages.len() as u32, &data).unwrap();
+        dm.sync().unwrap();
+        let before = dm.physical_size().unwrap();
+
+        for &page in &pages {
+            dm.deallocate_page(page).unwrap();
+        }
+        if !dm.hole_punching() {
+            // Feature off, or the filesystem can't punch holes
+            assert_eq!(dm.physical_size().unwrap(), before);
+            return;
+        }
+
+        let extent_bytes = (PAGE_SIZE * pages.len()) as u64;
+        assert!(dm.physical_size().unwrap() <= before - extent_bytes);
+        let mut buf = [0xFFu8; PAGE_SIZE];
+        dm.read_page(pages[3], &mut buf).unwrap();
+        assert!(buf.iter().all(|&b| b == 0));
+    }
 }
@@ -1,14 +1,100 @@
+use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-use crossbeam_channel::{bounded, Receiver, Sender};
+use crossbeam_channel::{after, bounded, never, select, Receiver, Sender};
+use parking_lot::Mutex;
 
-use crate::common::{CrioError, PageId, Result, PAGE_SIZE};
+use crate::common::{
+    CrioError, PageId, Result, DEFAULT_SCHEDULER_QUEUE_DEPTH, DEFAULT_SCHEDULER_WORKERS, PAGE_SIZE,
+};
+use crate::db::CrioConfig;
 
-use super::DiskManager;
+use super::{IoBudget, IoClass, IoThrottle, StorageBackend};
 
-/// Represents a disk I/O request
+/// A request that has waited this long is served before higher-priority
+/// lanes, so background work still progresses under foreground load.
+pub const STARVATION_LIMIT: Duration = Duration::from_millis(100);
+
+/// A request waiting in its class's lane.
+struct Queued {
+    request: DiskRequest,
+    enqueued_at: Instant,
+    /// Whether it has been counted as throttled
+    throttled: bool,
+}
+
+/// Per-class queues of requests received but not yet issued.
+#[derive(Default)]
+struct Lanes {
+    queues: [VecDeque<Queued>; IoClass::ALL.len()],
+}
+
+impl Lanes {
+    fn push(&mut self, request: DiskRequest) {
+        self.queues[request.class.index()].push_back(Queued {
+            request,
+            enqueued_at: Instant::now(),
+            throttled: false,
+        });
+    }
+
+    /// Picks the next request to issue: the oldest starved lane head if any,
+    /// otherwise the head of the highest-priority lane within its budget.
+    /// If every head is over budget, returns how long until one is due.
+    fn pop_next(
+        &mut self,
+        throttle: &Mutex<IoThrottle>,
+    ) -> std::result::Result<DiskRequest, Option<Duration>> {
+        let now = Instant::now();
+        let mut order: Vec<usize> = (0..self.queues.len())
+            .filter(|&i| !self.queues[i].is_empty())
+            .collect();
+        // Starved lanes first, oldest head first; then the rest by priority
+        order.sort_by_key(|&i| {
+            let enqueued_at = self.queues[i][0].enqueued_at;
+            if now.duration_since(enqueued_at) >= STARVATION_LIMIT {
+                (false, Some(enqueued_at), i)
+            } else {
+                (true, None, i)
+            }
+        });
+
+        let mut throttle = throttle.lock();
+        let mut next_due: Option<Duration> = None;
+        for i in order {
+            let head = self.queues[i].front_mut().unwrap();
+            match throttle.try_acquire(head.request.class, head.request.num_pages) {
+                Ok(()) => return Ok(self.queues[i].pop_front().unwrap().request),
+                Err(wait) => {
+                    if !head.throttled {
+                        head.throttled = true;
+                        throttle.record_throttled(head.request.class);
+                    }
+                    next_due = Some(next_due.map_or(wait, |d| d.min(wait)));
+                }
+            }
+        }
+        Err(next_due)
+    }
+
+    /// Removes every request, highest priority first.
+    fn drain(&mut self) -> impl Iterator<Item = DiskRequest> + '_ {
+        self.queues
+            .iter_mut()
+            .flat_map(|q| q.drain(..))
+            .map(|queued| queued.request)
+    }
+}
+
+/// Outcome of a disk request: on success, the request's buffer (filled, for
+/// a read).
+pub type DiskCompletion = Result<Vec<u8>>;
+
+/// Represents a disk I/O request. The request owns its buffer, which travels
+/// to the worker and back through the callback.
 pub struct DiskRequest {
     /// Whether this is a write (true) or read (false) request
     pub is_write: bool,
@@ -16,127 +102,212 @@ pub struct DiskRequest {
     pub page_id: PageId,
     /// Number of pages to read/write (1 for single page, >1 for sequential I/O)
     pub num_pages: u32,
-    /// Pointer to the data buffer (must be PAGE_SIZE * num_pages bytes)
-    /// For reads: data will be written here
-    /// For writes: data will be read from here
-    pub data: *mut u8,
-    /// Promise to signal completion
-    pub callback: Option<std::sync::mpsc::Sender<bool>>,
+    /// The pages' data, PAGE_SIZE * num_pages bytes
+    /// For reads: filled by the worker
+    /// For writes: written to disk
+    pub data: Vec<u8>,
+    /// Page IDs of a vectored write, one per PAGE_SIZE chunk of `data`; when
+    /// non-empty, `page_id` and `num_pages` describe the first page and count
+    pub scatter: Vec<PageId>,
+    /// Who the request is for; decides its priority and which I/O budget it
+    /// is charged to
+    pub class: IoClass,
+    /// Promise to signal completion, carrying the outcome of the I/O
+    pub callback: Option<std::sync::mpsc::Sender<DiskCompletion>>,
 }
 
-// Safety: DiskRequest is only used by the disk scheduler thread
-// and the caller must ensure the data pointer remains valid
-unsafe impl Send for DiskRequest {}
-
 impl DiskRequest {
     /// Creates a new single-page read request
-    pub fn read(page_id: PageId, data: *mut u8) -> Self {
-        Self {
-            is_write: false,
-            page_id,
-            num_pages: 1,
-            data,
-            callback: None,
-        }
+    pub fn read(page_id: PageId) -> Self {
+        Self::read_sequential(page_id, 1)
     }
 
     /// Creates a new single-page write request
-    pub fn write(page_id: PageId, data: *mut u8) -> Self {
-        Self {
-            is_write: true,
-            page_id,
-            num_pages: 1,
-            data,
-            callback: None,
-        }
+    pub fn write(page_id: PageId, data: Vec<u8>) -> Self {
+        assert_eq!(data.len(), PAGE_SIZE);
+        Self::write_sequential(page_id, data)
     }
 
     /// Creates a new sequential multi-page read request
     /// Reads num_pages starting from page_id in a single I/O operation
-    pub fn read_sequential(page_id: PageId, num_pages: u32, data: *mut u8) -> Self {
+    pub fn read_sequential(page_id: PageId, num_pages: u32) -> Self {
         Self {
             is_write: false,
             page_id,
             num_pages,
-            data,
+            data: vec![0u8; num_pages as usize * PAGE_SIZE],
+            scatter: Vec::new(),
+            class: IoClass::ForegroundRead,
             callback: None,
         }
     }
 
     /// Creates a new sequential multi-page write request
-    /// Writes num_pages starting from page_id in a single I/O operation
-    pub fn write_sequential(page_id: PageId, num_pages: u32, data: *mut u8) -> Self {
+    /// Writes the pages in `data` starting from page_id in a single I/O operation
+    pub fn write_sequential(page_id: PageId, data: Vec<u8>) -> Self {
+        assert_eq!(data.len() % PAGE_SIZE, 0);
         Self {
             is_write: true,
             page_id,
-            num_pages,
+            num_pages: (data.len() / PAGE_SIZE) as u32,
             data,
+            scatter: Vec::new(),
+            class: IoClass::ForegroundWrite,
             callback: None,
         }
     }
 
+    /// Creates a vectored write request for pages that need not be adjacent.
+    /// `data` holds one PAGE_SIZE chunk per page. Pages should be sorted by
+    /// page ID.
+    pub fn write_vectored(page_ids: Vec<PageId>, data: Vec<u8>) -> Self {
+        assert_eq!(data.len(), page_ids.len() * PAGE_SIZE);
+        Self {
+            is_write: true,
+            page_id: page_ids.first().copied().unwrap_or(PageId::new(0)),
+            num_pages: page_ids.len() as u32,
+            data,
+            scatter: page_ids,
+            class: IoClass::ForegroundWrite,
+            callback: None,
+        }
+    }
+
+    /// Sets the I/O class of this request (foreground by default)
+    pub fn with_class(mut self, class: IoClass) -> Self {
+        self.class = class;
+        self
+    }
+
     /// Sets the callback for this request
-    pub fn with_callback(mut self, callback: std::sync::mpsc::Sender<bool>) -> Self {
+    pub fn with_callback(mut self, callback: std::sync::mpsc::Sender<DiskCompletion>) -> Self {
         self.callback = Some(callback);
         self
     }
 }
 
-/// DiskScheduler manages a background worker thread that processes disk I/O requests.
+/// DiskScheduler manages background worker threads that process disk I/O requests.
 /// It provides asynchronous disk access through a request queue.
+///
+/// Each request carries an `IoClass` and waits in that class's lane. A
+/// worker serves the highest-priority lane first, except that a request
+/// waiting longer than `STARVATION_LIMIT` goes ahead of the rest. A class given
+/// an `IoBudget` is throttled: its lane is skipped while over budget. With one
+/// worker, requests of one class run in submission order, but different
+/// classes may complete out of order; with several, any two requests may.
+/// Callers that need ordering wait for completion.
 pub struct DiskScheduler {
-    /// The disk manager for actual I/O operations
-    disk_manager: Arc<DiskManager>,
+    /// The storage backend for actual I/O operations
+    disk_manager: Arc<dyn StorageBackend>,
     /// Channel sender for queuing requests
     request_sender: Sender<DiskRequest>,
-    /// Flag to signal shutdown
-    shutdown: Arc<AtomicBool>,
-    /// Handle to the background worker thread
-    worker_handle: Option<JoinHandle<()>>,
+    /// Per-class I/O budgets, shared with the workers
+    throttle: Arc<Mutex<IoThrottle>>,
+    /// Set once shutdown starts; new requests are refused
+    shutdown: AtomicBool,
+    /// Dropped on shutdown to wake the workers
+    shutdown_signal: Mutex<Option<Sender<()>>>,
+    /// Handles to the background worker threads; taken on shutdown
+    worker_handles: Mutex<Vec<JoinHandle<()>>>,
 }
 
 impl DiskScheduler {
-    /// Creates a new DiskScheduler with the given DiskManager.
+    /// Creates a new DiskScheduler over the given storage backend.
     /// Spawns a background worker thread to process requests.
-    pub fn new(disk_manager: Arc<DiskManager>) -> Self {
-        let (sender, receiver) = bounded::<DiskRequest>(128);
-        let shutdown = Arc::new(AtomicBool::new(false));
+    pub fn new(disk_manager: Arc<dyn StorageBackend>) -> Self {
+        Self::with_queue_depth(disk_manager, DEFAULT_SCHEDULER_QUEUE_DEPTH)
+    }
+
+    /// Creates a DiskScheduler with the queue depth, worker count and I/O
+    /// budgets of `config`.
+    pub fn from_config(disk_manager: Arc<dyn StorageBackend>, config: &CrioConfig) -> Self {
+        let scheduler = Self::with_workers(
+            disk_manager,
+            config.scheduler_queue_depth,
+            config.scheduler_workers,
+        );
+        for &(class, budget) in &config.io_budgets {
+            scheduler.set_io_budget(class, Some(budget));
+        }
+        scheduler
+    }
 
-        let dm_clone = Arc::clone(&disk_manager);
-        let shutdown_clone = Arc::clone(&shutdown);
+    /// Creates a DiskScheduler that queues up to `queue_depth` requests
+    /// before `schedule` blocks.
+    pub fn with_queue_depth(disk_manager: Arc<dyn StorageBackend>, queue_depth: usize) -> Self {
+        Self::with_workers(disk_manager, queue_depth, DEFAULT_SCHEDULER_WORKERS)
+    }
 
-        let worker_handle = thread::spawn(move || {
-            Self::start_worker_thread(dm_clone, receiver, shutdown_clone);
-        });
+    /// Creates a DiskScheduler that queues up to `queue_depth` requests and
+    /// issues them from `workers` threads, each taking requests off the
+    /// shared queue.
+    pub fn with_workers(
+        disk_manager: Arc<dyn StorageBackend>,
+        queue_depth: usize,
+        workers: usize,
+    ) -> Self {
+        assert!(workers > 0, "DiskScheduler needs at least one worker");
+        let (sender, receiver) = bounded::<DiskRequest>(queue_depth);
+        let (shutdown_signal, shutdown_receiver) = bounded::<()>(0);
+        let throttle = Arc::new(Mutex::new(IoThrottle::default()));
+
+        let worker_handles = (0..workers)
+            .map(|_| {
+                let dm_clone = Arc::clone(&disk_manager);
+                let worker_throttle = Arc::clone(&throttle);
+                let receiver = receiver.clone();
+                let shutdown_receiver = shutdown_receiver.clone();
+                thread::spawn(move || {
+                    Self::start_worker_thread(
+                        dm_clone,
+                        worker_throttle,
+                        receiver,
+                        shutdown_receiver,
+                    );
+                })
+            })
+            .collect();
 
         Self {
             disk_manager,
             request_sender: sender,
-            shutdown,
-            worker_handle: Some(worker_handle),
+            throttle,
+            shutdown: AtomicBool::new(false),
+            shutdown_signal: Mutex::new(Some(shutdown_signal)),
+            worker_handles: Mutex::new(worker_handles),
         }
     }
 
     /// Schedules a disk request for processing by the background worker.
+    /// Fails once the scheduler has been shut down.
     pub fn schedule(&self, request: DiskRequest) -> Result<()> {
+        if self.is_shut_down() {
+            return Err(CrioError::DiskScheduler(
+                "Scheduler is shut down".to_string(),
+            ));
+        }
         self.request_sender
             .send(request)
             .map_err(|e| CrioError::DiskScheduler(format!("Failed to schedule request: {}", e)))
     }
 
-    /// Schedules a read request and waits for completion.
-    pub fn schedule_read_sync(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
-        assert_eq!(data.len(), PAGE_SIZE);
-
+    /// Schedules a request and waits for completion. Returns the request's
+    /// buffer.
+    pub fn schedule_sync(&self, request: DiskRequest) -> DiskCompletion {
         let (tx, rx) = std::sync::mpsc::channel();
-        let request = DiskRequest::read(page_id, data.as_mut_ptr()).with_callback(tx);
 
-        self.schedule(request)?;
+        self.schedule(request.with_callback(tx))?;
 
-        rx.recv().map_err(|e| {
-            CrioError::DiskScheduler(format!("Failed to receive completion: {}", e))
-        })?;
+        rx.recv()
+            .map_err(|e| CrioError::DiskScheduler(format!("Failed to receive completion: {}", e)))?
+    }
 
+    /// Schedules a read request and waits for completion.
+    pub fn schedule_read_sync(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
+        assert_eq!(data.len(), PAGE_SIZE);
+
+        let read = self.schedule_sync(DiskRequest::read(page_id))?;
+        data.copy_from_slice(&read);
         Ok(())
     }
 
@@ -144,18 +315,8 @@ impl DiskScheduler {
     pub fn schedule_write_sync(&self, page_id: PageId, data: &[u8]) -> Result<()> {
         assert_eq!(data.len(), PAGE_SIZE);
 
-        let (tx, rx) = std::sync::mpsc::channel();
-        // Safety: We're passing a const pointer but treating it as mutable in the struct
-        // The worker will only read from it for writes
-        let request = DiskRequest::write(page_id, data.as_ptr() as *mut u8).with_callback(tx);
-
-        self.schedule(request)?;
-
-        rx.recv().map_err(|e| {
-            CrioError::DiskScheduler(format!("Failed to receive completion: {}", e))
-        })?;
-
-        Ok(())
+        self.schedule_sync(DiskRequest::write(page_id, data.to_vec()))
+            .map(drop)
     }
 
     /// Schedules a sequential multi-page read request and waits for completion.
@@ -170,16 +331,8 @@ impl DiskScheduler {
         let expected_size = (num_pages as usize) * PAGE_SIZE;
         assert_eq!(data.len(), expected_size);
 
-        let (tx, rx) = std::sync::mpsc::channel();
-        let request = DiskRequest::read_sequential(start_page_id, num_pages, data.as_mut_ptr())
-            .with_callback(tx);
-
-        self.schedule(request)?;
-
-        rx.recv().map_err(|e| {
-            CrioError::DiskScheduler(format!("Failed to receive completion: {}", e))
-        })?;
-
+        let read = self.schedule_sync(DiskRequest::read_sequential(start_page_id, num_pages))?;
+        data.copy_from_slice(&read);
         Ok(())
     }
 
@@ -195,110 +348,217 @@ impl DiskScheduler {
         let expected_size = (num_pages as usize) * PAGE_SIZE;
         assert_eq!(data.len(), expected_size);
 
-        let (tx, rx) = std::sync::mpsc::channel();
-        let request =
-            DiskRequest::write_sequential(start_page_id, num_pages, data.as_ptr() as *mut u8)
-                .with_callback(tx);
+        self.schedule_sync(DiskRequest::write_sequential(start_page_id, data.to_vec()))
+            .map(drop)
+    }
+
+    /// Schedules a vectored write of scattered pages and waits for completion.
+    /// All pages are handed to the backend as one batch, so contiguous runs are
+    /// coalesced and each segment file is visited once.
+    pub fn schedule_write_vectored_sync(&self, pages: &[(PageId, &[u8])]) -> Result<()> {
+        self.schedule_write_vectored_sync_as(IoClass::ForegroundWrite, pages)
+    }
+
+    /// `schedule_write_vectored_sync` charged to the given I/O class.
+    pub fn schedule_write_vectored_sync_as(
+        &self,
+        class: IoClass,
+        pages: &[(PageId, &[u8])],
+    ) -> Result<()> {
+        if pages.is_empty() {
+            return Ok(());
+        }
 
-        self.schedule(request)?;
+        let mut page_ids = Vec::with_capacity(pages.len());
+        let mut data = Vec::with_capacity(pages.len() * PAGE_SIZE);
+        for &(page_id, page) in pages {
+            assert_eq!(page.len(), PAGE_SIZE);
+            page_ids.push(page_id);
+            data.extend_from_slice(page);
+        }
 
-        rx.recv().map_err(|e| {
-            CrioError::DiskScheduler(format!("Failed to receive completion: {}", e))
-        })?;
+        self.schedule_sync(DiskRequest::write_vectored(page_ids, data).with_class(class))
+            .map(drop)
+    }
 
-        Ok(())
+    /// Sets or clears the I/O budget of a class. Takes effect for requests
+    /// the worker has not started yet.
+    pub fn set_io_budget(&self, class: IoClass, budget: Option<IoBudget>) {
+        self.throttle.lock().set_budget(class, budget);
+    }
+
+    /// Returns the I/O budget of a class, if it has one.
+    pub fn io_budget(&self, class: IoClass) -> Option<IoBudget> {
+        self.throttle.lock().budget(class)
+    }
+
+    /// Returns how many requests of a class had to wait for their budget.
+    pub fn throttled_requests(&self, class: IoClass) -> u64 {
+        self.throttle.lock().throttled(class)
     }
 
     /// The background worker thread function.
-    /// Processes requests from the queue until shutdown is signaled.
+    /// Processes requests from the queue until shutdown is signaled, moving
+    /// them into per-class lanes and issuing them in priority order.
     fn start_worker_thread(
-        disk_manager: Arc<DiskManager>,
+        disk_manager: Arc<dyn StorageBackend>,
+        throttle: Arc<Mutex<IoThrottle>>,
         receiver: Receiver<DiskRequest>,
-        shutdown: Arc<AtomicBool>,
+        shutdown: Receiver<()>,
     ) {
+        let mut lanes = Lanes::default();
+
         loop {
-            // Check for shutdown
-            if shutdown.load(Ordering::Relaxed) {
-                // Drain remaining requests before exiting
-                while let Ok(request) = receiver.try_recv() {
-                    Self::process_request(&disk_manager, request);
-                }
-                break;
+            // Take in everything already submitted, so a read queued behind
+            // a burst of flushes is seen before they are issued
+            while let Ok(request) = receiver.try_recv() {
+                lanes.push(request);
             }
 
-            // Wait for a request with timeout
-            match receiver.recv_timeout(std::time::Duration::from_millis(100)) {
+            let next_due = match lanes.pop_next(&throttle) {
                 Ok(request) => {
-                    Self::process_request(&disk_manager, request);
-                }
-                Err(crossbeam_channel::RecvTimeoutError::Timeout) => {
-                    // Continue loop, check shutdown flag
+                    Self::process_request(disk_manager.as_ref(), request);
+                    continue;
                 }
-                Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
+                Err(next_due) => next_due,
+            };
+            let timer = next_due.map_or_else(never, after);
+
+            select! {
+                recv(receiver) -> request => match request {
+                    Ok(request) => lanes.push(request),
                     // Channel closed, exit
-                    break;
-                }
+                    Err(_) => break,
+                },
+                recv(shutdown) -> _ => break,
+                recv(timer) -> _ => {}
             }
         }
+
+        // Drain remaining requests before exiting, ignoring budgets
+        for request in lanes.drain() {
+            Self::process_request(disk_manager.as_ref(), request);
+        }
+        while let Ok(request) = receiver.try_recv() {
+            Self::process_request(disk_manager.as_ref(), request);
+        }
     }
 
     /// Processes a single disk request (supports both single-page and sequential I/O).
-    fn process_request(disk_manager: &DiskManager, request: DiskRequest) {
-        let total_size = (request.num_pages as usize) * PAGE_SIZE;
-
-        let success = if request.num_pages == 1 {
+    fn process_request(disk_manager: &dyn StorageBackend, mut request: DiskRequest) {
+        let result = if !request.scatter.is_empty() {
+            let pages: Vec<(PageId, &[u8])> = request
+                .scatter
+                .iter()
+                .copied()
+                .zip(request.data.chunks_exact(PAGE_SIZE))
+                .collect();
+            disk_manager.write_pages_vectored(&pages)
+        } else if request.num_pages == 1 {
             // Single page I/O (original behavior)
             if request.is_write {
-                let data = unsafe { std::slice::from_raw_parts(request.data, PAGE_SIZE) };
-                disk_manager.write_page(request.page_id, data).is_ok()
+                disk_manager.write_page(request.page_id, &request.data)
             } else {
-                let data = unsafe { std::slice::from_raw_parts_mut(request.data, PAGE_SIZE) };
-                disk_manager.read_page(request.page_id, data).is_ok()
+                disk_manager.read_page(request.page_id, &mut request.data)
             }
         } else {
             // Sequential multi-page I/O
             if request.is_write {
-                let data = unsafe { std::slice::from_raw_parts(request.data, total_size) };
-                disk_manager
-                    .write_pages(request.page_id, request.num_pages, data)
-                    .is_ok()
+                disk_manager.write_pages(request.page_id, request.num_pages, &request.data)
             } else {
-                let data = unsafe { std::slice::from_raw_parts_mut(request.data, total_size) };
-                disk_manager
-                    .read_pages(request.page_id, request.num_pages, data)
-                    .is_ok()
+                disk_manager.read_pages(request.page_id, request.num_pages, &mut request.data)
             }
         };
 
-        // Signal completion
+        // Signal completion, handing the buffer back
         if let Some(callback) = request.callback {
-            let _ = callback.send(success);
+            let _ = callback.send(result.map(|()| request.data));
         }
     }
 
-    /// Returns a reference to the underlying DiskManager.
-    pub fn disk_manager(&self) -> &Arc<DiskManager> {
+    /// Stops accepting requests, lets the workers finish everything already
+    /// queued, and waits for them to exit. Calling this more than once is a
+    /// no-op.
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        drop(self.shutdown_signal.lock().take());
+
+        let handles = std::mem::take(&mut *self.worker_handles.lock());
+        for handle in handles {
+            let _ = handle.join();
+        }
+    }
+
+    /// Returns true once `shutdown` has been called.
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// Returns a reference to the underlying storage backend.
+    pub fn disk_manager(&self) -> &Arc<dyn StorageBackend> {
         &self.disk_manager
     }
 }
 
 impl Drop for DiskScheduler {
     fn drop(&mut self) {
-        // Signal shutdown
-        self.shutdown.store(true, Ordering::SeqCst);
-
-        // Wait for worker thread to finish
-        if let Some(handle) = self.worker_handle.take() {
-            let _ = handle.join();
-        }
+        self.shutdown();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::disk::{DiskManager, MemDiskManager};
+    use std::sync::mpsc;
     use tempfile::NamedTempFile;
 
+    /// Records the order of page I/O and holds the first operation until
+    /// released, so later requests pile up in the lanes.
+    struct GatedBackend {
+        inner: MemDiskManager,
+        log: Mutex<Vec<PageId>>,
+        entered: Mutex<Option<mpsc::Sender<()>>>,
+        gate: Mutex<Option<mpsc::Receiver<()>>>,
+    }
+
+    impl GatedBackend {
+        fn record(&self, page_id: PageId) {
+            if let Some(entered) = self.entered.lock().take() {
+                entered.send(()).unwrap();
+                let gate = self.gate.lock().take().unwrap();
+                gate.recv().unwrap();
+            }
+            self.log.lock().push(page_id);
+        }
+    }
+
+    impl StorageBackend for GatedBackend {
+        fn read_page(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
+            self.record(page_id);
+            self.inner.read_page(page_id, data)
+        }
+        fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()> {
+            self.record(page_id);
+            self.inner.write_page(page_id, data)
+        }
+        fn allocate_page(&self) -> Result<PageId> {
+            self.inner.allocate_page()
+        }
+        fn deallocate_page(&self, page_id: PageId) -> Result<()> {
+            self.inner.deallocate_page(page_id)
+        }
+        fn sync(&self) -> Result<()> {
+            Ok(())
+        }
+        fn get_num_reads(&self) -> u32 {
+            self.inner.get_num_reads()
+        }
+        fn get_num_writes(&self) -> u32 {
+            self.inner.get_num_writes()
+        }
+    }
+
     #[test]
     fn test_disk_scheduler_read_write() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -324,6 +584,32 @@ mod tests {
         assert_eq!(read_data[100], 255);
     }
 
+    #[test]
+    fn test_from_config_applies_budgets() {
+        let config = CrioConfig::builder()
+            .scheduler_queue_depth(1)
+            .io_budget(IoClass::Prefetch, IoBudget::new(50.0, 4.0))
+            .build()
+            .unwrap();
+        let scheduler = DiskScheduler::from_config(Arc::new(MemDiskManager::new()), &config);
+        assert_eq!(
+            scheduler.io_budget(IoClass::Prefetch),
+            Some(IoBudget::new(50.0, 4.0))
+        );
+        assert_eq!(scheduler.io_budget(IoClass::Background), None);
+
+        // A queue of one still serves many requests
+        let page_id = scheduler.disk_manager().allocate_page().unwrap();
+        for i in 0..10u8 {
+            scheduler
+                .schedule_write_sync(page_id, &[i; PAGE_SIZE])
+                .unwrap();
+        }
+        let mut data = [0u8; PAGE_SIZE];
+        scheduler.schedule_read_sync(page_id, &mut data).unwrap();
+        assert_eq!(data, [9; PAGE_SIZE]);
+    }
+
     #[test]
     fn test_disk_scheduler_multiple_requests() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -351,4 +637,207 @@ mod tests {
         assert_eq!(read1[0], 1);
         assert_eq!(read2[0], 2);
     }
+
+    #[test]
+    fn test_background_budget_does_not_delay_foreground() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let dm = Arc::new(DiskManager::new(temp_file.path()).unwrap());
+        let scheduler = DiskScheduler::new(dm);
+        let page_ids: Vec<PageId> = (0..6)
+            .map(|_| scheduler.disk_manager().allocate_page().unwrap())
+            .collect();
+
+        // One background page every 50ms
+        scheduler.set_io_budget(IoClass::Background, Some(IoBudget::new(20.0, 1.0)));
+
+        let start = std::time::Instant::now();
+        let completions: Vec<_> = page_ids[..5]
+            .iter()
+            .map(|&page_id| {
+                let (tx, rx) = std::sync::mpsc::channel();
+                let request = DiskRequest::write(page_id, vec![7u8; PAGE_SIZE])
+                    .with_class(IoClass::Background)
+                    .with_callback(tx);
+                scheduler.schedule(request).unwrap();
+                rx
+            })
+            .collect();
+
+        // A foreground read overtakes the queued background writes
+        let mut read = [0u8; PAGE_SIZE];
+        scheduler
+            .schedule_read_sync(page_ids[5], &mut read)
+            .unwrap();
+        let foreground = start.elapsed();
+
+        for rx in completions {
+            rx.recv().unwrap().unwrap();
+        }
+        let background = start.elapsed();
+
+        assert!(foreground < Duration::from_millis(150), "{:?}", foreground);
+        assert!(background >= Duration::from_millis(190), "{:?}", background);
+        assert_eq!(scheduler.throttled_requests(IoClass::Background), 4);
+        assert_eq!(scheduler.throttled_requests(IoClass::ForegroundRead), 0);
+    }
+
+    #[test]
+    fn test_shutdown_drains_throttled_requests() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let dm = Arc::new(DiskManager::new(temp_file.path()).unwrap());
+        let scheduler = DiskScheduler::new(dm);
+        let page_id = scheduler.disk_manager().allocate_page().unwrap();
+
+        // So slow that only the shutdown drain can issue the second write
+        scheduler.set_io_budget(IoClass::Background, Some(IoBudget::new(0.001, 1.0)));
+        let completions: Vec<_> = [1u8, 2u8]
+            .into_iter()
+            .map(|byte| {
+                let (tx, rx) = std::sync::mpsc::channel();
+                let request = DiskRequest::write(page_id, vec![byte; PAGE_SIZE])
+                    .with_class(IoClass::Background)
+                    .with_callback(tx);
+                scheduler.schedule(request).unwrap();
+                rx
+            })
+            .collect();
+
+        scheduler.shutdown();
+        for rx in completions {
+            rx.recv().unwrap().unwrap();
+        }
+
+        // Deferred requests of one class keep their order
+        let mut read = [0u8; PAGE_SIZE];
+        scheduler
+            .disk_manager()
+            .read_page(page_id, &mut read)
+            .unwrap();
+        assert_eq!(read[0], 2);
+    }
+
+    #[test]
+    fn test_lanes_issue_in_priority_order() {
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (gate_tx, gate_rx) = mpsc::channel();
+        let backend = Arc::new(GatedBackend {
+            inner: MemDiskManager::new(),
+            log: Mutex::new(Vec::new()),
+            entered: Mutex::new(Some(entered_tx)),
+            gate: Mutex::new(Some(gate_rx)),
+        });
+        let pages: Vec<PageId> = (0..8).map(|_| backend.allocate_page().unwrap()).collect();
+        let scheduler = DiskScheduler::new(Arc::clone(&backend) as Arc<dyn StorageBackend>);
+
+        let mut completions = Vec::new();
+        let mut submit = |i: usize, is_write: bool, class| {
+            let (tx, rx) = mpsc::channel();
+            let request = if is_write {
+                DiskRequest::write(pages[i], vec![0u8; PAGE_SIZE])
+            } else {
+                DiskRequest::read(pages[i])
+            };
+            let request = request.with_class(class).with_callback(tx);
+            scheduler.schedule(request).unwrap();
+            completions.push(rx);
+        };
+
+        // Occupy the worker, then queue one request per class, lowest first
+        submit(0, true, IoClass::Background);
+        entered_rx.recv().unwrap();
+        submit(1, true, IoClass::Background);
+        submit(2, true, IoClass::Background);
+        submit(3, false, IoClass::Prefetch);
+        submit(4, true, IoClass::ForegroundWrite);
+        submit(5, false, IoClass::ForegroundRead);
+        submit(6, true, IoClass::ForegroundWrite);
+        submit(7, false, IoClass::ForegroundRead);
+
+        gate_tx.send(()).unwrap();
+        for rx in completions {
+            rx.recv().unwrap().unwrap();
+        }
+
+        let order: Vec<usize> = backend
+            .log
+            .lock()
+            .iter()
+            .map(|page_id| pages.iter().position(|p| p == page_id).unwrap())
+            .collect();
+        assert_eq!(order, vec![0, 5, 7, 4, 6, 3, 1, 2]);
+    }
+
+    #[test]
+    fn test_read_hands_buffer_back() {
+        let backend = Arc::new(MemDiskManager::new());
+        let page_id = backend.allocate_page().unwrap();
+        backend.write_page(page_id, &[9u8; PAGE_SIZE]).unwrap();
+        let scheduler = DiskScheduler::new(backend);
+
+        let (tx, rx) = mpsc::channel();
+        scheduler
+            .schedule(DiskRequest::read(page_id).with_callback(tx))
+            .unwrap();
+        let data = rx.recv().unwrap().unwrap();
+        assert_eq!(data.len(), PAGE_SIZE);
+        assert!(data.iter().all(|&b| b == 9));
+    }
+
+    #[test]
+    fn test_multiple_workers() {
+        let backend = Arc::new(MemDiskManager::new());
+        let page_ids: Vec<PageId> = (0..32).map(|_| backend.allocate_page().unwrap()).collect();
+        let scheduler = DiskScheduler::with_workers(backend, 4, 4);
+
+        let (tx, rx) = mpsc::channel();
+        for (i, &page_id) in page_ids.iter().enumerate() {
+            scheduler
+                .schedule(
+                    DiskRequest::write(page_id, vec![i as u8; PAGE_SIZE]).with_callback(tx.clone()),
+                )
+                .unwrap();
+        }
+        for _ in &page_ids {
+            rx.recv().unwrap().unwrap();
+        }
+
+        for (i, &page_id) in page_ids.iter().enumerate() {
+            let mut data = [0u8; PAGE_SIZE];
+            scheduler.schedule_read_sync(page_id, &mut data).unwrap();
+            assert!(data.iter().all(|&b| b == i as u8));
+        }
+        scheduler.shutdown();
+        assert!(scheduler.worker_handles.lock().is_empty());
+    }
+
+    #[test]
+    fn test_starved_lane_goes_first() {
+        let throttle = Mutex::new(IoThrottle::default());
+        let mut lanes = Lanes::default();
+
+        lanes.push(
+            DiskRequest::write(PageId::new(1), vec![0u8; PAGE_SIZE])
+                .with_class(IoClass::Background),
+        );
+        lanes.push(DiskRequest::read(PageId::new(2)));
+        lanes.push(DiskRequest::read(PageId::new(3)));
+
+        // Fresh requests go by priority
+        assert_eq!(
+            lanes.pop_next(&throttle).ok().unwrap().page_id,
+            PageId::new(2)
+        );
+
+        // Once the flush has waited too long it overtakes the foreground read
+        lanes.queues[IoClass::Background.index()][0].enqueued_at -= STARVATION_LIMIT;
+        assert_eq!(
+            lanes.pop_next(&throttle).ok().unwrap().page_id,
+            PageId::new(1)
+        );
+        assert_eq!(
+            lanes.pop_next(&throttle).ok().unwrap().page_id,
+            PageId::new(3)
+        );
+        assert!(matches!(lanes.pop_next(&throttle), Err(None)));
+    }
 }
@@ -0,0 +1,312 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::common::{PageId, Result, PAGE_SIZE};
+
+use super::shadow_storage::checksum;
+use super::StorageBackend;
+
+const HEADER_MAGIC: u32 = 0x4442_4c57; // "DBLW"
+
+// Header page layout: magic u32 | page_count u32 | (page ID u32, page
+// checksum u64) * page_count | ... | header checksum u64. The page images
+// follow the header in the same order.
+const PAGE_COUNT_OFFSET: usize = 4;
+const ENTRIES_OFFSET: usize = 8;
+const ENTRY_SIZE: usize = 12;
+const CHECKSUM_OFFSET: usize = PAGE_SIZE - 8;
+
+/// Pages staged in the double-write file at once; larger writes are split
+pub const DOUBLE_WRITE_PAGES: usize = 64;
+
+struct DoubleWriteState {
+    /// The double-write file
+    file: File,
+    /// In-place writes made since the inner backend was last synced. The
+    /// staged batch may only be overwritten once they are durable.
+    unsynced: bool,
+}
+
+/// DoubleWriteStorage wraps a storage backend so that a crash in the middle
+/// of a page write can't leave a torn page behind.
+///
+/// Every write is first staged, with its page ID and a checksum, in a
+/// scratch file next to the database and fsynced there; only then is it
+/// written in place. If the in-place write is torn, the staged image is
+/// intact, and `open` copies it back over the page. If the staged write is
+/// torn instead, the in-place page was never touched and the batch is
+/// ignored.
+///
+/// The scratch file holds one batch at a time, so before a batch replaces
+/// the previous one, the previous batch's in-place writes are synced. Writes
+/// made directly to the inner backend are not protected.
+pub struct DoubleWriteStorage {
+    inner: Arc<dyn StorageBackend>,
+    state: Mutex<DoubleWriteState>,
+    /// Pages restored from the double-write file when it was opened
+    recovered_pages: usize,
+}
+
+impl DoubleWriteStorage {
+    /// Wraps `inner`, staging writes in the file at `path`. If the file
+    /// holds an intact batch from a session that crashed, its pages are
+    /// first written back in place.
+    pub fn open<P: AsRef<Path>>(inner: Arc<dyn StorageBackend>, path: P) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let recovered_pages = Self::restore(&mut file, &*inner)?;
+
+        Ok(Self {
+            inner,
+            state: Mutex::new(DoubleWriteState {
+                file,
+                unsynced: false,
+            }),
+            recovered_pages,
+        })
+    }
+
+    /// Writes the intact batch staged in the double-write file at `path`, if
+    /// any, back to `backend` and empties the file. Returns the number of
+    /// pages restored. For reopening without double writes a database that
+    /// used them.
+    pub fn recover<P: AsRef<Path>>(backend: &dyn StorageBackend, path: P) -> Result<usize> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        Self::restore(&mut file, backend)
+    }
+
+    /// Returns the number of pages restored when the storage was opened.
+    pub fn recovered_pages(&self) -> usize {
+        self.recovered_pages
+    }
+
+    fn restore(file: &mut File, backend: &dyn StorageBackend) -> Result<usize> {
+        let mut staged = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut staged)?;
+        let Some(pages) = decode_batch(&staged) else {
+            return Ok(0);
+        };
+
+        for &(page_id, data) in &pages {
+            backend.write_page(page_id, data)?;
+        }
+        backend.sync()?;
+        clear(file)?;
+        Ok(pages.len())
+    }
+
+    /// Stages `pages` in the double-write file, then writes them in place.
+    fn write_batch(&self, pages: &[(PageId, &[u8])]) -> Result<()> {
+        let mut state = self.state.lock();
+        for batch in pages.chunks(DOUBLE_WRITE_PAGES) {
+            if state.unsynced {
+                self.inner.sync()?;
+                state.unsynced = false;
+            }
+
+            let staged = encode_batch(batch);
+            state.file.seek(SeekFrom::Start(0))?;
+            state.file.write_all(&staged)?;
+            state.file.sync_data()?;
+
+            state.unsynced = true;
+            self.inner.write_pages_vectored(batch)?;
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for DoubleWriteStorage {
+    fn read_page(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
+        self.inner.read_page(page_id, data)
+    }
+
+    fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()> {
+        assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+        self.write_batch(&[(page_id, data)])
+    }
+
+    fn read_pages(&self, start_page_id: PageId, num_pages: u32, data: &mut [u8]) -> Result<()> {
+        self.inner.read_pages(start_page_id, num_pages, data)
+    }
+
+    fn write_pages(&self, start_page_id: PageId, num_pages: u32, data: &[u8]) -> Result<()> {
+        assert_eq!(data.len(), num_pages as usize * PAGE_SIZE);
+        let pages: Vec<_> = data
+            .chunks_exact(PAGE_SIZE)
+            .enumerate()
+            .map(|(i, page)| (PageId::new(start_page_id.as_u32() + i as u32), page))
+            .collect();
+        self.write_batch(&pages)
+    }
+
+    fn write_pages_vectored(&self, pages: &[(PageId, &[u8])]) -> Result<()> {
+        self.write_batch(pages)
+    }
+
+    fn allocate_page(&self) -> Result<PageId> {
+        self.inner.allocate_page()
+    }
+
+    fn allocate_extent(&self, table_id: u32) -> Result<Vec<PageId>> {
+        self.inner.allocate_extent(table_id)
+    }
+
+    fn deallocate_page(&self, page_id: PageId) -> Result<()> {
+        self.inner.deallocate_page(page_id)
+    }
+
+    fn read_ahead_limit(&self, page_id: PageId, num_pages: u32) -> u32 {
+        self.inner.read_ahead_limit(page_id, num_pages)
+    }
+
+    fn sync(&self) -> Result<()> {
+        let mut state = self.state.lock();
+        self.inner.sync()?;
+        state.unsynced = false;
+        Ok(())
+    }
+
+    /// Syncs, empties the double-write file so a later session can't
+    /// restore stale images from it, then closes the inner backend.
+    fn close(&self) -> Result<()> {
+        let mut state = self.state.lock();
+        self.inner.sync()?;
+        state.unsynced = false;
+        clear(&mut state.file)?;
+        self.inner.close()
+    }
+
+    fn get_num_reads(&self) -> u32 {
+        self.inner.get_num_reads()
+    }
+
+    fn get_num_writes(&self) -> u32 {
+        self.inner.get_num_writes()
+    }
+}
+
+fn encode_batch(pages: &[(PageId, &[u8])]) -> Vec<u8> {
+    let mut staged = vec![0u8; (pages.len() + 1) * PAGE_SIZE];
+    let (header, images) = staged.split_at_mut(PAGE_SIZE);
+    header[..PAGE_COUNT_OFFSET].copy_from_slice(&HEADER_MAGIC.to_le_bytes());
+    header[PAGE_COUNT_OFFSET..ENTRIES_OFFSET].copy_from_slice(&(pages.len() as u32).to_le_bytes());
+    for (i, (&(page_id, data), image)) in pages
+        .iter()
+        .zip(images.chunks_exact_mut(PAGE_SIZE))
+        .enumerate()
+    {
+        let entry = ENTRIES_OFFSET + i * ENTRY_SIZE;
+        header[entry..entry + 4].copy_from_slice(&page_id.as_u32().to_le_bytes());
+        header[entry + 4..entry + ENTRY_SIZE].copy_from_slice(&checksum(data).to_le_bytes());
+        image.copy_from_slice(data);
+    }
+    let header_checksum = checksum(&header[..CHECKSUM_OFFSET]);
+    header[CHECKSUM_OFFSET..].copy_from_slice(&header_checksum.to_le_bytes());
+    staged
+}
+
+/// Returns the pages of the batch staged in `staged`, or None if there is
+/// none or any part of it is torn.
+fn decode_batch(staged: &[u8]) -> Option<Vec<(PageId, &[u8])>> {
+    let header = staged.get(..PAGE_SIZE)?;
+    let u32_at = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    if u32_at(0) != HEADER_MAGIC
+        || header[CHECKSUM_OFFSET..] != checksum(&header[..CHECKSUM_OFFSET]).to_le_bytes()
+    {
+        return None;
+    }
+
+    let page_count = u32_at(PAGE_COUNT_OFFSET) as usize;
+    if page_count > DOUBLE_WRITE_PAGES {
+        return None;
+    }
+    (0..page_count)
+        .map(|i| {
+            let entry = ENTRIES_OFFSET + i * ENTRY_SIZE;
+            let expected = &header[entry + 4..entry + ENTRY_SIZE];
+            let image = staged.get((i + 1) * PAGE_SIZE..(i + 2) * PAGE_SIZE)?;
+            (checksum(image).to_le_bytes() == expected).then(|| (PageId::new(u32_at(entry)), image))
+        })
+        .collect()
+}
+
+/// Empties the double-write file durably.
+fn clear(file: &mut File) -> Result<()> {
+    file.set_len(0)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::disk::{DiskManager, Fault, FaultInjectingDiskManager, FaultTrigger};
+
+    fn page(fill: u8) -> [u8; PAGE_SIZE] {
+        [fill; PAGE_SIZE]
+    }
+
+    #[test]
+    fn test_double_write_restores_torn_page() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dblwr_path = temp_dir.path().join("db.dblwr");
+        let dm = Arc::new(DiskManager::new(temp_dir.path().join("db")).unwrap());
+        let fdm = Arc::new(FaultInjectingDiskManager::new(Arc::clone(&dm) as _));
+        let storage = DoubleWriteStorage::open(Arc::clone(&fdm) as _, &dblwr_path).unwrap();
+        let a = storage.allocate_page().unwrap();
+        let b = storage.allocate_page().unwrap();
+        storage.write_page(a, &page(1)).unwrap();
+        storage.write_page(b, &page(1)).unwrap();
+
+        // The in-place write of `b` tears, then the process dies
+        fdm.inject(FaultTrigger::Page(b), Fault::TornWrite { bytes: 100 });
+        storage
+            .write_pages_vectored(&[(a, &page(2)), (b, &page(2))])
+            .unwrap();
+        let mut torn = page(0);
+        dm.read_page(b, &mut torn).unwrap();
+        assert_ne!(torn, page(2));
+        drop(storage);
+
+        let storage = DoubleWriteStorage::open(Arc::clone(&dm) as _, &dblwr_path).unwrap();
+        assert_eq!(storage.recovered_pages(), 2);
+        let mut data = page(0);
+        for page_id in [a, b] {
+            storage.read_page(page_id, &mut data).unwrap();
+            assert_eq!(data, page(2));
+        }
+
+        // A clean close leaves nothing to restore
+        storage.close().unwrap();
+        let storage = DoubleWriteStorage::open(dm, &dblwr_path).unwrap();
+        assert_eq!(storage.recovered_pages(), 0);
+    }
+
+    #[test]
+    fn test_double_write_ignores_torn_batch() {
+        let staged = encode_batch(&[(PageId::new(3), &page(7)), (PageId::new(4), &page(8))]);
+        let pages = decode_batch(&staged).unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1], (PageId::new(4), &page(8)[..]));
+
+        // A torn image or header, or a short file, invalidates the batch
+        let mut torn = staged.clone();
+        torn[2 * PAGE_SIZE + 10] ^= 1;
+        assert!(decode_batch(&torn).is_none());
+        let mut torn = staged.clone();
+        torn[PAGE_COUNT_OFFSET] = 1;
+        assert!(decode_batch(&torn).is_none());
+        assert!(decode_batch(&staged[..2 * PAGE_SIZE]).is_none());
+        assert!(decode_batch(&[]).is_none());
+    }
+}
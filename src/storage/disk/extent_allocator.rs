@@ -28,6 +28,8 @@ impl ExtentId {
 struct ExtentInfo {
     allocated_bitmap: u8,
     allocated_count: u8,
+    /// Table the extent was allocated for; None for linearly allocated pages
+    owner: Option<u32>,
 }
 
 impl ExtentInfo {
@@ -35,6 +37,7 @@ impl ExtentInfo {
         Self {
             allocated_bitmap: 0,
             allocated_count: 0,
+            owner: None,
         }
     }
 
@@ -70,7 +73,6 @@ impl ExtentInfo {
         self.allocated_count == EXTENT_SIZE as u8
     }
 
-    #[allow(dead_code)]
     fn is_empty(&self) -> bool {
         self.allocated_count == 0
     }
@@ -92,7 +94,7 @@ impl ExtentAllocator {
     }
 
     pub fn from_existing(num_pages: u32) -> Self {
-        let num_extents = (num_pages + EXTENT_SIZE - 1) / EXTENT_SIZE;
+        let num_extents = num_pages.div_ceil(EXTENT_SIZE);
 
         let mut extent_info_map = HashMap::new();
 
@@ -122,7 +124,7 @@ impl ExtentAllocator {
         let mut table_extents = self.table_extents.lock();
         let mut extent_info = self.extent_info.lock();
 
-        let extents = table_extents.entry(table_id).or_insert_with(Vec::new);
+        let extents = table_extents.entry(table_id).or_default();
 
         for &extent_id in extents.iter().rev() {
             if let Some(info) = extent_info.get_mut(&extent_id) {
@@ -137,6 +139,7 @@ impl ExtentAllocator {
 
         let extent_id = ExtentId::new(self.next_extent_id.fetch_add(1, Ordering::SeqCst));
         let mut info = ExtentInfo::new();
+        info.owner = Some(table_id);
         let offset = info.allocate_next().unwrap();
 
         extent_info.insert(extent_id, info);
@@ -152,6 +155,7 @@ impl ExtentAllocator {
 
         let extent_id = ExtentId::new(self.next_extent_id.fetch_add(1, Ordering::SeqCst));
         let mut info = ExtentInfo::new();
+        info.owner = Some(table_id);
 
         let mut pages = Vec::with_capacity(EXTENT_SIZE as usize);
         for _ in 0..EXTENT_SIZE {
@@ -163,22 +167,21 @@ impl ExtentAllocator {
         }
 
         extent_info.insert(extent_id, info);
-        table_extents
-            .entry(table_id)
-            .or_insert_with(Vec::new)
-            .push(extent_id);
+        table_extents.entry(table_id).or_default().push(extent_id);
 
         Ok(pages)
     }
 
-    pub fn deallocate_page(&self, page_id: PageId) {
+    /// Frees a page, returning true if this left its whole extent free.
+    pub fn deallocate_page(&self, page_id: PageId) -> bool {
         let extent_idx = page_id.as_u32() / EXTENT_SIZE;
         let offset = (page_id.as_u32() % EXTENT_SIZE) as u8;
         let extent_id = ExtentId::new(extent_idx);
 
         let mut extent_info = self.extent_info.lock();
-        if let Some(info) = extent_info.get_mut(&extent_id) {
-            info.deallocate(offset);
+        match extent_info.get_mut(&extent_id) {
+            Some(info) => info.deallocate(offset) && info.is_empty(),
+            None => false,
         }
     }
 
@@ -210,6 +213,141 @@ impl ExtentAllocator {
         ranges
     }
 
+    /// Allocates the next linear page: virtual page `num_pages`, or the
+    /// first page past every extent if a table's extent holds that page.
+    /// The page is marked allocated and `num_pages` raised past it under the
+    /// allocator's locks, so a concurrent extent allocation can't hand it out
+    /// too.
+    pub fn allocate_linear_page(&self, num_pages: &AtomicU32) -> PageId {
+        let table_extents = self.table_extents.lock();
+        let mut extent_info = self.extent_info.lock();
+
+        let mut page = num_pages.load(Ordering::SeqCst);
+        let extent_id = ExtentId::new(page / EXTENT_SIZE);
+        if table_extents
+            .values()
+            .any(|extents| extents.contains(&extent_id))
+        {
+            page = page.max(self.next_extent_id.load(Ordering::SeqCst) * EXTENT_SIZE);
+        }
+        self.mark_allocated_locked(&mut extent_info, PageId::new(page));
+        num_pages.fetch_max(page + 1, Ordering::SeqCst);
+        PageId::new(page)
+    }
+
+    /// Records a page handed out outside of table extents (e.g. by linear
+    /// allocation) so the bitmaps cover every allocated page.
+    pub fn mark_allocated(&self, page_id: PageId) {
+        let mut extent_info = self.extent_info.lock();
+        self.mark_allocated_locked(&mut extent_info, page_id);
+    }
+
+    fn mark_allocated_locked(
+        &self,
+        extent_info: &mut HashMap<ExtentId, ExtentInfo>,
+        page_id: PageId,
+    ) {
+        let extent_idx = page_id.as_u32() / EXTENT_SIZE;
+        let mask = 1 << (page_id.as_u32() % EXTENT_SIZE);
+
+        let info = extent_info
+            .entry(ExtentId::new(extent_idx))
+            .or_insert_with(ExtentInfo::new);
+        if info.allocated_bitmap & mask == 0 {
+            info.allocated_bitmap |= mask;
+            info.allocated_count += 1;
+        }
+        self.next_extent_id
+            .fetch_max(extent_idx + 1, Ordering::SeqCst);
+    }
+
+    /// Returns true if the page's bit is set in its extent bitmap.
+    pub fn is_allocated(&self, page_id: PageId) -> bool {
+        let extent_id = ExtentId::new(page_id.as_u32() / EXTENT_SIZE);
+        let mask = 1 << (page_id.as_u32() % EXTENT_SIZE);
+        self.extent_info
+            .lock()
+            .get(&extent_id)
+            .is_some_and(|info| info.allocated_bitmap & mask != 0)
+    }
+
+    /// Returns how many of the `max_pages` pages after `page_id` are
+    /// allocated to the same table as it, stopping at the first that isn't.
+    /// Linearly allocated pages belong to no table and run together.
+    pub fn same_owner_run(&self, page_id: PageId, max_pages: u32) -> u32 {
+        let extent_info = self.extent_info.lock();
+        let owner_of = |page: u32| {
+            let info = extent_info.get(&ExtentId::new(page / EXTENT_SIZE))?;
+            let mask = 1 << (page % EXTENT_SIZE);
+            (info.allocated_bitmap & mask != 0).then_some(info.owner)
+        };
+
+        let Some(owner) = owner_of(page_id.as_u32()) else {
+            return 0;
+        };
+        (1..=max_pages)
+            .take_while(|&i| {
+                page_id
+                    .as_u32()
+                    .checked_add(i)
+                    .is_some_and(|page| owner_of(page) == Some(owner))
+            })
+            .count() as u32
+    }
+
+    /// Checks the bitmaps against their counts and the table ownership map,
+    /// returning a description of every inconsistency.
+    pub fn check(&self) -> Vec<String> {
+        let table_extents = self.table_extents.lock();
+        let extent_info = self.extent_info.lock();
+        let next_extent_id = self.next_extent_id.load(Ordering::SeqCst);
+        let mut violations = Vec::new();
+
+        let mut extent_ids: Vec<_> = extent_info.keys().copied().collect();
+        extent_ids.sort_unstable_by_key(|id| id.as_u32());
+        for extent_id in extent_ids {
+            let info = &extent_info[&extent_id];
+            if info.allocated_bitmap.count_ones() != info.allocated_count as u32 {
+                violations.push(format!(
+                    "extent {} bitmap {:#010b} disagrees with its count {}",
+                    extent_id.as_u32(),
+                    info.allocated_bitmap,
+                    info.allocated_count
+                ));
+            }
+            if extent_id.as_u32() >= next_extent_id {
+                violations.push(format!(
+                    "extent {} is past the allocation high-water mark {}",
+                    extent_id.as_u32(),
+                    next_extent_id
+                ));
+            }
+        }
+
+        let mut owners: HashMap<ExtentId, u32> = HashMap::new();
+        for (&table_id, extents) in table_extents.iter() {
+            for &extent_id in extents {
+                if !extent_info.contains_key(&extent_id) {
+                    violations.push(format!(
+                        "table {} owns extent {} which has no bitmap",
+                        table_id,
+                        extent_id.as_u32()
+                    ));
+                }
+                if let Some(other) = owners.insert(extent_id, table_id) {
+                    violations.push(format!(
+                        "extent {} is owned by both table {} and table {}",
+                        extent_id.as_u32(),
+                        other,
+                        table_id
+                    ));
+                }
+            }
+        }
+
+        violations
+    }
+
     pub fn total_pages_allocated(&self) -> u32 {
         self.next_extent_id.load(Ordering::Relaxed) * EXTENT_SIZE
     }
@@ -278,6 +416,83 @@ mod tests {
         assert_eq!(overflow_page, PageId::new(EXTENT_SIZE));
     }
 
+    #[test]
+    fn test_linear_pages_skip_table_extents() {
+        let allocator = ExtentAllocator::from_existing(1);
+        let num_pages = AtomicU32::new(1);
+        assert_eq!(allocator.allocate_linear_page(&num_pages), PageId::new(1));
+
+        // Table extents start past linearly allocated pages
+        let pages = allocator.allocate_extent_for_table(1).unwrap();
+        assert_eq!(pages[0], PageId::new(EXTENT_SIZE));
+
+        // and a linear page that reaches a table's extent skips it
+        num_pages.store(EXTENT_SIZE, Ordering::SeqCst);
+        let page = allocator.allocate_linear_page(&num_pages);
+        assert_eq!(page, PageId::new(2 * EXTENT_SIZE));
+        assert_eq!(num_pages.load(Ordering::SeqCst), 2 * EXTENT_SIZE + 1);
+        assert_eq!(
+            allocator.allocate_extent_for_table(2).unwrap()[0],
+            PageId::new(3 * EXTENT_SIZE)
+        );
+    }
+
+    #[test]
+    fn test_mark_allocated_and_check() {
+        let allocator = ExtentAllocator::from_existing(3);
+        assert!(allocator.is_allocated(PageId::new(2)));
+        assert!(!allocator.is_allocated(PageId::new(3)));
+
+        allocator.mark_allocated(PageId::new(3));
+        allocator.mark_allocated(PageId::new(3));
+        assert!(allocator.is_allocated(PageId::new(3)));
+
+        // Table extents start past linearly allocated pages
+        let page = allocator.allocate_page_for_table(1).unwrap();
+        assert_eq!(page, PageId::new(EXTENT_SIZE));
+
+        assert!(!allocator.deallocate_page(PageId::new(2)));
+        assert!(!allocator.is_allocated(PageId::new(2)));
+        assert!(allocator.check().is_empty());
+
+        allocator
+            .extent_info
+            .lock()
+            .get_mut(&ExtentId::new(0))
+            .unwrap()
+            .allocated_count = 7;
+        assert_eq!(allocator.check().len(), 1);
+    }
+
+    #[test]
+    fn test_deallocate_reports_free_extent() {
+        let allocator = ExtentAllocator::new();
+        let pages = allocator.allocate_extent_for_table(1).unwrap();
+
+        for &page in &pages[..pages.len() - 1] {
+            assert!(!allocator.deallocate_page(page));
+        }
+        assert!(allocator.deallocate_page(pages[pages.len() - 1]));
+        // Freeing an already free page doesn't report the extent again
+        assert!(!allocator.deallocate_page(pages[0]));
+    }
+
+    #[test]
+    fn test_same_owner_run() {
+        let allocator = ExtentAllocator::from_existing(3);
+        let t1 = allocator.allocate_extent_for_table(1).unwrap();
+        let t2 = allocator.allocate_extent_for_table(2).unwrap();
+
+        // Linear pages run up to the first unallocated page
+        assert_eq!(allocator.same_owner_run(PageId::new(0), 10), 2);
+        // A table's run stops at another table's extent
+        assert_eq!(allocator.same_owner_run(t1[2], 10), 5);
+        assert_eq!(allocator.same_owner_run(t1[2], 3), 3);
+        allocator.deallocate_page(t2[4]);
+        assert_eq!(allocator.same_owner_run(t2[0], 10), 3);
+        assert_eq!(allocator.same_owner_run(t2[4], 10), 0);
+    }
+
     #[test]
     fn test_get_contiguous_pages() {
         let allocator = ExtentAllocator::new();
@@ -0,0 +1,314 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::common::{CrioError, PageId, Result, PAGE_SIZE};
+
+use super::StorageBackend;
+
+/// A failure to inject into a page write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The write is silently discarded but reported as successful
+    Drop,
+    /// The write lands on its page and is also copied onto another page
+    /// (a misdirected write)
+    Duplicate { to: PageId },
+    /// Only the first `bytes` bytes of the page reach disk; the rest keeps its
+    /// previous contents
+    TornWrite { bytes: usize },
+    /// The write fails with an I/O error and nothing reaches disk
+    IoError,
+    /// Simulated power loss: this write and every later one is dropped
+    Crash,
+}
+
+/// When an injected fault fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultTrigger {
+    /// On the n-th page write through the wrapper (1-based), once
+    NthWrite(u64),
+    /// On every write to the given page
+    Page(PageId),
+    /// On every write
+    Always,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FaultRule {
+    trigger: FaultTrigger,
+    fault: Fault,
+}
+
+/// FaultInjectingDiskManager wraps a storage backend and implements the same
+/// StorageBackend interface, but can drop, duplicate, tear or fail page writes at
+/// deterministic points. It is meant for tests that validate recovery and
+/// corruption detection.
+///
+/// Every page counts as one write, including each page of a `write_pages` call.
+pub struct FaultInjectingDiskManager {
+    /// The real backend
+    inner: Arc<dyn StorageBackend>,
+    /// Registered fault rules
+    rules: Mutex<Vec<FaultRule>>,
+    /// Number of page writes seen so far
+    write_count: AtomicU64,
+    /// Set once a `Fault::Crash` has fired
+    crashed: AtomicBool,
+}
+
+impl FaultInjectingDiskManager {
+    /// Wraps a backend with no faults registered.
+    pub fn new(inner: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            inner,
+            rules: Mutex::new(Vec::new()),
+            write_count: AtomicU64::new(0),
+            crashed: AtomicBool::new(false),
+        }
+    }
+
+    /// Registers a fault that fires when `trigger` matches a page write.
+    pub fn inject(&self, trigger: FaultTrigger, fault: Fault) {
+        self.rules.lock().push(FaultRule { trigger, fault });
+    }
+
+    /// Removes all registered faults and clears the crashed state.
+    pub fn clear_faults(&self) {
+        self.rules.lock().clear();
+        self.crashed.store(false, Ordering::SeqCst);
+    }
+
+    /// Returns the number of page writes seen so far.
+    pub fn write_count(&self) -> u64 {
+        self.write_count.load(Ordering::SeqCst)
+    }
+
+    /// Returns true once a `Fault::Crash` has fired.
+    pub fn is_crashed(&self) -> bool {
+        self.crashed.load(Ordering::SeqCst)
+    }
+
+    /// Returns the wrapped backend.
+    pub fn inner(&self) -> &Arc<dyn StorageBackend> {
+        &self.inner
+    }
+
+    /// Counts a write and returns the fault to apply to it, if any.
+    fn next_fault(&self, page_id: PageId) -> Option<Fault> {
+        let n = self.write_count.fetch_add(1, Ordering::SeqCst) + 1;
+
+        if self.is_crashed() {
+            return Some(Fault::Crash);
+        }
+
+        let mut rules = self.rules.lock();
+        let index = rules.iter().position(|rule| match rule.trigger {
+            FaultTrigger::NthWrite(target) => target == n,
+            FaultTrigger::Page(target) => target == page_id,
+            FaultTrigger::Always => true,
+        })?;
+
+        let rule = rules[index];
+        if let FaultTrigger::NthWrite(_) = rule.trigger {
+            rules.remove(index);
+        }
+        if rule.fault == Fault::Crash {
+            self.crashed.store(true, Ordering::SeqCst);
+        }
+        Some(rule.fault)
+    }
+
+    fn apply(&self, fault: Fault, page_id: PageId, data: &[u8]) -> Result<()> {
+        match fault {
+            Fault::Drop | Fault::Crash => Ok(()),
+            Fault::Duplicate { to } => {
+                self.inner.write_page(page_id, data)?;
+                self.inner.write_page(to, data)
+            }
+            Fault::TornWrite { bytes } => {
+                let bytes = bytes.min(PAGE_SIZE);
+                let mut torn = [0u8; PAGE_SIZE];
+                self.inner.read_page(page_id, &mut torn)?;
+                torn[..bytes].copy_from_slice(&data[..bytes]);
+                self.inner.write_page(page_id, &torn)
+            }
+            Fault::IoError => Err(CrioError::page_write(
+                page_id,
+                page_id.page_offset() as u64 * PAGE_SIZE as u64,
+                io::Error::other("injected write failure"),
+            )),
+        }
+    }
+}
+
+impl StorageBackend for FaultInjectingDiskManager {
+    /// Reads a page. Reads are never faulted.
+    fn read_page(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
+        self.inner.read_page(page_id, data)
+    }
+
+    /// Reads multiple contiguous pages. Reads are never faulted.
+    fn read_pages(&self, start_page_id: PageId, num_pages: u32, data: &mut [u8]) -> Result<()> {
+        self.inner.read_pages(start_page_id, num_pages, data)
+    }
+
+    /// Writes a page, applying any fault that matches.
+    fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()> {
+        assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+
+        match self.next_fault(page_id) {
+            None => self.inner.write_page(page_id, data),
+            Some(fault) => self.apply(fault, page_id, data),
+        }
+    }
+
+    /// Writes multiple contiguous pages. Falls back to page-by-page writes when
+    /// any page in the range is faulted.
+    fn write_pages(&self, start_page_id: PageId, num_pages: u32, data: &[u8]) -> Result<()> {
+        assert_eq!(data.len(), num_pages as usize * PAGE_SIZE);
+
+        let faults: Vec<Option<Fault>> = (0..num_pages)
+            .map(|i| self.next_fault(PageId::new(start_page_id.as_u32() + i)))
+            .collect();
+
+        if faults.iter().all(Option::is_none) {
+            return self.inner.write_pages(start_page_id, num_pages, data);
+        }
+
+        for (i, fault) in faults.into_iter().enumerate() {
+            let page_id = PageId::new(start_page_id.as_u32() + i as u32);
+            let page_data = &data[i * PAGE_SIZE..(i + 1) * PAGE_SIZE];
+            match fault {
+                None => self.inner.write_page(page_id, page_data)?,
+                Some(fault) => self.apply(fault, page_id, page_data)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn allocate_page(&self) -> Result<PageId> {
+        self.inner.allocate_page()
+    }
+
+    /// Frees a page. After a crash this is a no-op, as the free would have
+    /// been lost with the power.
+    fn deallocate_page(&self, page_id: PageId) -> Result<()> {
+        if self.is_crashed() {
+            return Ok(());
+        }
+        self.inner.deallocate_page(page_id)
+    }
+
+    fn read_ahead_limit(&self, page_id: PageId, num_pages: u32) -> u32 {
+        self.inner.read_ahead_limit(page_id, num_pages)
+    }
+
+    /// Syncs the wrapped backend. After a crash this is a no-op.
+    fn sync(&self) -> Result<()> {
+        if self.is_crashed() {
+            return Ok(());
+        }
+        self.inner.sync()
+    }
+
+    /// Closes the wrapped backend. After a crash this is a no-op, so the
+    /// shutdown looks unclean on the next open.
+    fn close(&self) -> Result<()> {
+        if self.is_crashed() {
+            return Ok(());
+        }
+        self.inner.close()
+    }
+
+    fn get_num_reads(&self) -> u32 {
+        self.inner.get_num_reads()
+    }
+
+    fn get_num_writes(&self) -> u32 {
+        self.inner.get_num_writes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::disk::DiskManager;
+
+    fn create_fdm() -> (FaultInjectingDiskManager, tempfile::TempDir) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dm = Arc::new(DiskManager::new(temp_dir.path().join("fault.db")).unwrap());
+        (FaultInjectingDiskManager::new(dm), temp_dir)
+    }
+
+    fn read(fdm: &FaultInjectingDiskManager, page_id: PageId) -> [u8; PAGE_SIZE] {
+        let mut data = [0u8; PAGE_SIZE];
+        fdm.read_page(page_id, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_drop_and_io_error() {
+        let (fdm, _temp) = create_fdm();
+        let page_id = fdm.allocate_page().unwrap();
+
+        fdm.inject(FaultTrigger::NthWrite(1), Fault::Drop);
+        fdm.write_page(page_id, &[1u8; PAGE_SIZE]).unwrap();
+        assert_eq!(read(&fdm, page_id)[0], 0);
+
+        // NthWrite fires once
+        fdm.write_page(page_id, &[2u8; PAGE_SIZE]).unwrap();
+        assert_eq!(read(&fdm, page_id)[0], 2);
+
+        fdm.inject(FaultTrigger::Page(page_id), Fault::IoError);
+        let err = fdm.write_page(page_id, &[3u8; PAGE_SIZE]).unwrap_err();
+        assert_eq!(err.io_kind(), Some(io::ErrorKind::Other));
+        assert_eq!(read(&fdm, page_id)[0], 2);
+        assert_eq!(fdm.write_count(), 3);
+    }
+
+    #[test]
+    fn test_torn_and_duplicate_writes() {
+        let (fdm, _temp) = create_fdm();
+        let p1 = fdm.allocate_page().unwrap();
+        let p2 = fdm.allocate_page().unwrap();
+
+        fdm.write_page(p1, &[1u8; PAGE_SIZE]).unwrap();
+        fdm.inject(FaultTrigger::Page(p1), Fault::TornWrite { bytes: 512 });
+        fdm.write_page(p1, &[9u8; PAGE_SIZE]).unwrap();
+
+        let data = read(&fdm, p1);
+        assert!(data[..512].iter().all(|&b| b == 9));
+        assert!(data[512..].iter().all(|&b| b == 1));
+
+        fdm.clear_faults();
+        fdm.inject(FaultTrigger::Always, Fault::Duplicate { to: p2 });
+        fdm.write_page(p1, &[5u8; PAGE_SIZE]).unwrap();
+        assert_eq!(read(&fdm, p1)[0], 5);
+        assert_eq!(read(&fdm, p2)[0], 5);
+    }
+
+    #[test]
+    fn test_crash_drops_later_writes() {
+        let (fdm, _temp) = create_fdm();
+        let start = fdm.allocate_page().unwrap();
+        for _ in 0..3 {
+            fdm.allocate_page().unwrap();
+        }
+
+        // Crash on the 3rd page of a 4-page sequential write
+        fdm.inject(FaultTrigger::NthWrite(3), Fault::Crash);
+        fdm.write_pages(start, 4, &[7u8; PAGE_SIZE * 4]).unwrap();
+        assert!(fdm.is_crashed());
+
+        for i in 0..4 {
+            let expected = if i < 2 { 7 } else { 0 };
+            assert_eq!(read(&fdm, PageId::new(start.as_u32() + i))[0], expected);
+        }
+
+        fdm.write_page(start, &[8u8; PAGE_SIZE]).unwrap();
+        assert_eq!(read(&fdm, start)[0], 7);
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+
+/// Who a disk request is for, in priority order: the DiskScheduler serves
+/// foreground reads first and background flushes last. Any class may also be
+/// throttled with an `IoBudget`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IoClass {
+    /// Reads a query is waiting on
+    ForegroundRead,
+    /// Writes a query is waiting on, such as evicting a dirty page
+    ForegroundWrite,
+    /// Speculative read-ahead
+    Prefetch,
+    /// Flushes and maintenance nobody is waiting on
+    Background,
+}
+
+impl IoClass {
+    /// All classes, highest priority first.
+    pub const ALL: [IoClass; 4] = [
+        IoClass::ForegroundRead,
+        IoClass::ForegroundWrite,
+        IoClass::Prefetch,
+        IoClass::Background,
+    ];
+
+    /// The foreground class for a read or a write.
+    pub fn foreground(is_write: bool) -> Self {
+        if is_write {
+            IoClass::ForegroundWrite
+        } else {
+            IoClass::ForegroundRead
+        }
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// A token bucket limit on the pages per second a class may read or write.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IoBudget {
+    /// Sustained rate, in pages per second
+    pub pages_per_sec: f64,
+    /// Pages that may be issued at once after a quiet period
+    pub burst: f64,
+}
+
+impl IoBudget {
+    pub fn new(pages_per_sec: f64, burst: f64) -> Self {
+        assert!(pages_per_sec > 0.0, "I/O budget rate must be positive");
+        assert!(burst >= 1.0, "I/O budget burst must allow one page");
+        Self {
+            pages_per_sec,
+            burst,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    budget: IoBudget,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(budget: IoBudget) -> Self {
+        Self {
+            budget,
+            tokens: budget.burst,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    /// Takes `pages` tokens, or returns how long until they are available.
+    /// A request larger than the burst goes ahead once the bucket is full
+    /// and leaves it in debt.
+    fn try_take(&mut self, pages: u32, now: Instant) -> Result<(), Duration> {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.budget.pages_per_sec).min(self.budget.burst);
+        self.refilled_at = now;
+
+        let needed = (pages as f64).min(self.budget.burst);
+        if self.tokens >= needed {
+            self.tokens -= pages as f64;
+            Ok(())
+        } else {
+            let wait = (needed - self.tokens) / self.budget.pages_per_sec;
+            Err(Duration::from_secs_f64(wait))
+        }
+    }
+}
+
+/// Per-class I/O budgets. Classes without a budget are never throttled.
+#[derive(Debug, Default)]
+pub(crate) struct IoThrottle {
+    buckets: [Option<TokenBucket>; IoClass::ALL.len()],
+    throttled: [u64; IoClass::ALL.len()],
+}
+
+impl IoThrottle {
+    pub(crate) fn set_budget(&mut self, class: IoClass, budget: Option<IoBudget>) {
+        self.buckets[class.index()] = budget.map(TokenBucket::new);
+    }
+
+    pub(crate) fn budget(&self, class: IoClass) -> Option<IoBudget> {
+        self.buckets[class.index()].as_ref().map(|b| b.budget)
+    }
+
+    /// Charges `pages` to `class`, or returns how long until it can be.
+    pub(crate) fn try_acquire(&mut self, class: IoClass, pages: u32) -> Result<(), Duration> {
+        match &mut self.buckets[class.index()] {
+            Some(bucket) => bucket.try_take(pages, Instant::now()),
+            None => Ok(()),
+        }
+    }
+
+    /// Counts a request that had to wait for its budget.
+    pub(crate) fn record_throttled(&mut self, class: IoClass) {
+        self.throttled[class.index()] += 1;
+    }
+
+    pub(crate) fn throttled(&self, class: IoClass) -> u64 {
+        self.throttled[class.index()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_refills_at_rate() {
+        let mut bucket = TokenBucket::new(IoBudget::new(100.0, 10.0));
+        let start = bucket.refilled_at;
+
+        // The burst is available immediately, then the bucket is empty
+        assert!(bucket.try_take(10, start).is_ok());
+        let wait = bucket.try_take(1, start).unwrap_err();
+        assert!(wait > Duration::from_millis(9) && wait <= Duration::from_millis(10));
+
+        // 50ms at 100 pages/s refills 5 pages
+        let later = start + Duration::from_millis(50);
+        assert!(bucket.try_take(5, later).is_ok());
+        assert!(bucket.try_take(1, later).is_err());
+
+        // Refills never exceed the burst
+        let much_later = start + Duration::from_secs(10);
+        assert!(bucket.try_take(10, much_later).is_ok());
+        assert!(bucket.try_take(1, much_later).is_err());
+    }
+
+    #[test]
+    fn test_oversized_request_goes_into_debt() {
+        let mut bucket = TokenBucket::new(IoBudget::new(10.0, 4.0));
+        let start = bucket.refilled_at;
+
+        assert!(bucket.try_take(8, start).is_ok());
+        // 4 tokens of debt plus the 1 needed: half a second at 10 pages/s
+        let wait = bucket.try_take(1, start).unwrap_err();
+        assert!(wait >= Duration::from_millis(499), "{:?}", wait);
+    }
+
+    #[test]
+    fn test_unbudgeted_class_is_never_throttled() {
+        let mut throttle = IoThrottle::default();
+        throttle.set_budget(IoClass::Background, Some(IoBudget::new(1.0, 1.0)));
+
+        for _ in 0..100 {
+            assert!(throttle.try_acquire(IoClass::ForegroundRead, 64).is_ok());
+        }
+        assert!(throttle.try_acquire(IoClass::Background, 1).is_ok());
+        assert!(throttle.try_acquire(IoClass::Background, 1).is_err());
+    }
+}
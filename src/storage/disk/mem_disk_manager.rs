@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use parking_lot::Mutex;
+
+use crate::common::{CrioError, PageId, Result, PAGE_SIZE};
+
+use super::StorageBackend;
+
+struct MemState {
+    /// Page contents keyed by page ID
+    pages: HashMap<PageId, Box<[u8; PAGE_SIZE]>>,
+    /// Next never-allocated page ID
+    next_page: u32,
+    /// Deallocated pages available for reuse
+    free_pages: Vec<PageId>,
+}
+
+/// MemDiskManager is a StorageBackend that keeps every page in RAM. Nothing
+/// touches the filesystem and all data is lost when it is dropped, which makes
+/// it suitable for unit tests and ephemeral databases.
+///
+/// Page 0 is reserved like the DiskManager directory page, so the first page
+/// allocated is page 1.
+pub struct MemDiskManager {
+    /// Page storage and allocation state
+    state: Mutex<MemState>,
+    /// Number of page reads served
+    num_reads: AtomicU32,
+    /// Number of page writes performed
+    num_writes: AtomicU32,
+}
+
+impl MemDiskManager {
+    /// Creates an empty in-memory backend.
+    pub fn new() -> Self {
+        let mut pages = HashMap::new();
+        pages.insert(PageId::new(0), Box::new([0u8; PAGE_SIZE]));
+
+        Self {
+            state: Mutex::new(MemState {
+                pages,
+                next_page: 1,
+                free_pages: Vec::new(),
+            }),
+            num_reads: AtomicU32::new(0),
+            num_writes: AtomicU32::new(0),
+        }
+    }
+
+    /// Returns the number of live pages, including the reserved page 0.
+    pub fn get_num_pages(&self) -> u32 {
+        self.state.lock().pages.len() as u32
+    }
+}
+
+impl Default for MemDiskManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StorageBackend for MemDiskManager {
+    fn read_page(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
+        assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+
+        let state = self.state.lock();
+        let page = state
+            .pages
+            .get(&page_id)
+            .ok_or(CrioError::InvalidPageId(page_id))?;
+        data.copy_from_slice(&page[..]);
+
+        self.num_reads.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()> {
+        assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+
+        let mut state = self.state.lock();
+        let page = state
+            .pages
+            .get_mut(&page_id)
+            .ok_or(CrioError::InvalidPageId(page_id))?;
+        page.copy_from_slice(data);
+
+        self.num_writes.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn allocate_page(&self) -> Result<PageId> {
+        let mut state = self.state.lock();
+
+        let page_id = match state.free_pages.pop() {
+            Some(page_id) => page_id,
+            None => {
+                let page_id = PageId::new(state.next_page);
+                state.next_page += 1;
+                page_id
+            }
+        };
+
+        state.pages.insert(page_id, Box::new([0u8; PAGE_SIZE]));
+        Ok(page_id)
+    }
+
+    fn deallocate_page(&self, page_id: PageId) -> Result<()> {
+        let mut state = self.state.lock();
+        if page_id.as_u32() == 0 || state.pages.remove(&page_id).is_none() {
+            return Err(CrioError::InvalidPageId(page_id));
+        }
+        state.free_pages.push(page_id);
+        Ok(())
+    }
+
+    /// Limits read-ahead to live pages. Tables are not tracked.
+    fn read_ahead_limit(&self, page_id: PageId, num_pages: u32) -> u32 {
+        let state = self.state.lock();
+        (1..=num_pages)
+            .take_while(|&i| {
+                page_id
+                    .as_u32()
+                    .checked_add(i)
+                    .is_some_and(|page| state.pages.contains_key(&PageId::new(page)))
+            })
+            .count() as u32
+    }
+
+    /// Memory is as durable as it gets; this is a no-op.
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_num_reads(&self) -> u32 {
+        self.num_reads.load(Ordering::Relaxed)
+    }
+
+    fn get_num_writes(&self) -> u32 {
+        self.num_writes.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mem_read_write() {
+        let mdm = MemDiskManager::new();
+        let page_id = mdm.allocate_page().unwrap();
+        assert_eq!(page_id, PageId::new(1));
+
+        let mut data = [0u8; PAGE_SIZE];
+        data[0] = 42;
+        data[PAGE_SIZE - 1] = 7;
+        mdm.write_page(page_id, &data).unwrap();
+
+        let mut read_data = [0u8; PAGE_SIZE];
+        mdm.read_page(page_id, &mut read_data).unwrap();
+        assert_eq!(read_data, data);
+        assert_eq!(mdm.get_num_reads(), 1);
+        assert_eq!(mdm.get_num_writes(), 1);
+
+        // Unallocated pages are rejected
+        assert!(mdm.read_page(PageId::new(99), &mut read_data).is_err());
+    }
+
+    #[test]
+    fn test_mem_multi_page_and_reuse() {
+        let mdm = MemDiskManager::new();
+        let start = mdm.allocate_page().unwrap();
+        for _ in 0..2 {
+            mdm.allocate_page().unwrap();
+        }
+
+        let mut data = vec![0u8; PAGE_SIZE * 3];
+        for i in 0..3 {
+            data[i * PAGE_SIZE] = i as u8 + 1;
+        }
+        mdm.write_pages(start, 3, &data).unwrap();
+
+        let mut read_data = vec![0u8; PAGE_SIZE * 3];
+        mdm.read_pages(start, 3, &mut read_data).unwrap();
+        assert_eq!(read_data, data);
+
+        mdm.deallocate_page(start).unwrap();
+        assert!(mdm.deallocate_page(start).is_err());
+        assert_eq!(mdm.get_num_pages(), 3);
+
+        // Reused pages come back zeroed
+        assert_eq!(mdm.allocate_page().unwrap(), start);
+        let mut page = [0u8; PAGE_SIZE];
+        mdm.read_page(start, &mut page).unwrap();
+        assert!(page.iter().all(|&b| b == 0));
+    }
+}
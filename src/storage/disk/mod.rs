@@ -1,7 +1,25 @@
 mod disk_manager;
 mod disk_scheduler;
+mod double_write;
 mod extent_allocator;
+mod fault_injection;
+mod io_throttle;
+mod mem_disk_manager;
+mod object_store;
+mod object_store_backend;
+mod shadow_storage;
+mod storage_backend;
+mod temp_file_manager;
 
 pub use disk_manager::*;
 pub use disk_scheduler::*;
+pub use double_write::*;
 pub use extent_allocator::*;
+pub use fault_injection::*;
+pub use io_throttle::*;
+pub use mem_disk_manager::*;
+pub use object_store::*;
+pub use object_store_backend::*;
+pub use shadow_storage::*;
+pub use storage_backend::*;
+pub use temp_file_manager::*;
@@ -0,0 +1,117 @@
+//! Object stores that `ObjectStoreBackend` keeps a database in.
+//!
+//! crio ships no S3 client of its own: one needs an HTTP and TLS stack and
+//! request signing, which this crate does not otherwise depend on, and the
+//! credentials, retry and timeout policy belong to the embedding
+//! application. Wrap the SDK client the application already uses in an
+//! `ObjectStore` instead. `MemObjectStore` covers tests.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+
+use crate::common::Result;
+
+/// A flat key-value store of immutable objects, the subset of the S3 API that
+/// `ObjectStoreBackend` needs.
+///
+/// Each method maps to one request: `get_range` to a `GetObject` with a
+/// `Range` header, `put` to `PutObject` and `delete` to `DeleteObject`. An
+/// S3-compatible client implements this trait and reports request failures
+/// as `CrioError::ObjectStore`.
+pub trait ObjectStore: Send + Sync {
+    /// Returns up to `len` bytes of object `key` starting at `offset`, or
+    /// None if there is no such object. The result is shorter than `len` if
+    /// the object ends first.
+    fn get_range(&self, key: &str, offset: u64, len: u64) -> Result<Option<Vec<u8>>>;
+
+    /// Returns the whole object `key`, or None if there is no such object.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.get_range(key, 0, u64::MAX)
+    }
+
+    /// Creates or replaces object `key`.
+    fn put(&self, key: &str, data: Vec<u8>) -> Result<()>;
+
+    /// Deletes object `key`. Deleting a missing object succeeds.
+    fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// MemObjectStore keeps objects in RAM and counts the requests made to it,
+/// for tests of code that runs against an object store.
+#[derive(Debug, Default)]
+pub struct MemObjectStore {
+    objects: Mutex<BTreeMap<String, Vec<u8>>>,
+    gets: AtomicU64,
+    puts: AtomicU64,
+}
+
+impl MemObjectStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the keys of every object, in order.
+    pub fn keys(&self) -> Vec<String> {
+        self.objects.lock().keys().cloned().collect()
+    }
+
+    /// Returns the number of `get_range` and `get` requests served.
+    pub fn get_count(&self) -> u64 {
+        self.gets.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of `put` requests served.
+    pub fn put_count(&self) -> u64 {
+        self.puts.load(Ordering::Relaxed)
+    }
+}
+
+impl ObjectStore for MemObjectStore {
+    fn get_range(&self, key: &str, offset: u64, len: u64) -> Result<Option<Vec<u8>>> {
+        self.gets.fetch_add(1, Ordering::Relaxed);
+        let objects = self.objects.lock();
+        Ok(objects.get(key).map(|object| {
+            let start = (offset as usize).min(object.len());
+            let end = start.saturating_add(len.min(usize::MAX as u64) as usize);
+            object[start..end.min(object.len())].to_vec()
+        }))
+    }
+
+    fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        self.puts.fetch_add(1, Ordering::Relaxed);
+        self.objects.lock().insert(key.to_string(), data);
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.objects.lock().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mem_object_store() {
+        let store = MemObjectStore::new();
+        assert!(store.get("a").unwrap().is_none());
+
+        store.put("a", b"hello world".to_vec()).unwrap();
+        assert_eq!(store.get("a").unwrap().unwrap(), b"hello world");
+        assert_eq!(store.get_range("a", 6, 5).unwrap().unwrap(), b"world");
+        assert_eq!(store.get_range("a", 9, 100).unwrap().unwrap(), b"ld");
+        assert!(store.get_range("a", 100, 1).unwrap().unwrap().is_empty());
+
+        store.put("b", Vec::new()).unwrap();
+        assert_eq!(store.keys(), vec!["a", "b"]);
+        store.delete("a").unwrap();
+        store.delete("a").unwrap();
+        assert_eq!(store.keys(), vec!["b"]);
+        assert_eq!((store.get_count(), store.put_count()), (5, 2));
+    }
+}
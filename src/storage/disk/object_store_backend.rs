@@ -0,0 +1,420 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::common::{CrioError, PageId, Result, PAGE_SIZE};
+
+use super::shadow_storage::checksum;
+use super::{ObjectStore, StorageBackend, EXTENT_SIZE};
+
+const META_MAGIC: u32 = 0x4f42_4a53; // "OBJS"
+
+// Meta object layout: magic u32 | next_page u32 | free_count u32 | free page
+// IDs u32 * free_count | checksum u64 of everything before it
+const META_HEADER_SIZE: usize = 12;
+
+/// Pages the local write buffer holds before it is written back
+pub const DEFAULT_WRITE_BUFFER_PAGES: usize = 1024;
+
+/// Bytes in one extent object
+const EXTENT_BYTES: usize = EXTENT_SIZE as usize * PAGE_SIZE;
+
+struct ObjectState {
+    /// Next never-allocated page ID
+    next_page: u32,
+    /// Deallocated pages available for reuse
+    free_pages: BTreeSet<u32>,
+    /// Pages written since they were last written back, by page ID
+    buffer: BTreeMap<u32, Box<[u8]>>,
+    /// Allocation changed since the meta object was last written
+    meta_dirty: bool,
+}
+
+/// ObjectStoreBackend is a StorageBackend over an S3-compatible object
+/// store, for cheap, durable storage of mostly-read databases.
+///
+/// Pages are grouped by extent, and each extent is one object,
+/// `<prefix>/extent-<n>`. Reads fetch just the pages they need with range
+/// GETs; `read_pages` fetches a contiguous run with one GET per extent.
+/// Writes land in a local write buffer and are written back a whole extent
+/// per PUT, reading the extent first if only some of its pages are
+/// buffered, once the buffer holds `write_buffer_pages` pages and on `sync`.
+/// Allocation state lives in `<prefix>/meta`, rewritten on `sync`.
+///
+/// Only synced writes are durable: a crash loses the write buffer and any
+/// allocations since the last sync. Page 0 is reserved like the DiskManager
+/// directory page, so the first page allocated is page 1.
+pub struct ObjectStoreBackend {
+    store: Arc<dyn ObjectStore>,
+    /// Key prefix of every object of this database
+    prefix: String,
+    /// Buffered pages that trigger a write-back
+    write_buffer_pages: usize,
+    state: Mutex<ObjectState>,
+    /// Number of page reads served
+    num_reads: AtomicU32,
+    /// Number of page writes performed
+    num_writes: AtomicU32,
+}
+
+impl ObjectStoreBackend {
+    /// Opens the database stored under `prefix` in `store`, creating an
+    /// empty one if there is none.
+    pub fn open(store: Arc<dyn ObjectStore>, prefix: impl Into<String>) -> Result<Self> {
+        let prefix = prefix.into();
+        let (next_page, free_pages, meta_dirty) = match store.get(&meta_key(&prefix))? {
+            Some(meta) => {
+                let (next_page, free_pages) = decode_meta(&meta)?;
+                (next_page, free_pages, false)
+            }
+            None => (1, BTreeSet::new(), true),
+        };
+
+        Ok(Self {
+            store,
+            prefix,
+            write_buffer_pages: DEFAULT_WRITE_BUFFER_PAGES,
+            state: Mutex::new(ObjectState {
+                next_page,
+                free_pages,
+                buffer: BTreeMap::new(),
+                meta_dirty,
+            }),
+            num_reads: AtomicU32::new(0),
+            num_writes: AtomicU32::new(0),
+        })
+    }
+
+    /// Sets how many pages the write buffer holds before it is written
+    /// back.
+    pub fn with_write_buffer_pages(mut self, pages: usize) -> Self {
+        self.write_buffer_pages = pages.max(1);
+        self
+    }
+
+    /// Returns the number of pages in the write buffer.
+    pub fn buffered_pages(&self) -> usize {
+        self.state.lock().buffer.len()
+    }
+
+    /// Writes every buffered page back, a whole extent per PUT.
+    fn write_back(&self, state: &mut ObjectState) -> Result<()> {
+        let extents: BTreeSet<u32> = state.buffer.keys().map(|page| page / EXTENT_SIZE).collect();
+        for extent in extents {
+            let first = extent * EXTENT_SIZE;
+            let pages: Vec<u32> = state
+                .buffer
+                .range(first..first + EXTENT_SIZE)
+                .map(|(&page, _)| page)
+                .collect();
+
+            let mut object = if pages.len() == EXTENT_SIZE as usize {
+                vec![0u8; EXTENT_BYTES]
+            } else {
+                let mut object = self
+                    .store
+                    .get(&self.extent_key(extent))?
+                    .unwrap_or_default();
+                object.resize(EXTENT_BYTES, 0);
+                object
+            };
+            for page in &pages {
+                let offset = (page - first) as usize * PAGE_SIZE;
+                object[offset..offset + PAGE_SIZE].copy_from_slice(&state.buffer[page]);
+            }
+
+            self.store.put(&self.extent_key(extent), object)?;
+            for page in pages {
+                state.buffer.remove(&page);
+            }
+        }
+        Ok(())
+    }
+
+    fn extent_key(&self, extent: u32) -> String {
+        format!("{}/extent-{:08}", self.prefix, extent)
+    }
+
+    fn check_allocated(state: &ObjectState, page_id: PageId) -> Result<()> {
+        let page = page_id.as_u32();
+        if page >= state.next_page || state.free_pages.contains(&page) {
+            return Err(CrioError::InvalidPageId(page_id));
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for ObjectStoreBackend {
+    fn read_page(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
+        self.read_pages(page_id, 1, data)
+    }
+
+    fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()> {
+        assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+
+        let mut state = self.state.lock();
+        Self::check_allocated(&state, page_id)?;
+        state.buffer.insert(page_id.as_u32(), data.into());
+        self.num_writes.fetch_add(1, Ordering::Relaxed);
+
+        if state.buffer.len() >= self.write_buffer_pages {
+            self.write_back(&mut state)?;
+        }
+        Ok(())
+    }
+
+    /// Reads buffered pages from the write buffer and the rest with one
+    /// range GET per extent. Pages never written read back as zeros.
+    fn read_pages(&self, start_page_id: PageId, num_pages: u32, data: &mut [u8]) -> Result<()> {
+        assert_eq!(data.len(), num_pages as usize * PAGE_SIZE);
+        let start = start_page_id.as_u32();
+        let end = start
+            .checked_add(num_pages)
+            .ok_or(CrioError::InvalidPageId(start_page_id))?;
+
+        let buffered: BTreeMap<u32, Box<[u8]>> = {
+            let state = self.state.lock();
+            for page in start..end {
+                Self::check_allocated(&state, PageId::new(page))?;
+            }
+            state
+                .buffer
+                .range(start..end)
+                .map(|(&page, data)| (page, data.clone()))
+                .collect()
+        };
+
+        let mut page = start;
+        while page < end {
+            let extent = page / EXTENT_SIZE;
+            let run_end = end.min((extent + 1) * EXTENT_SIZE);
+            if (page..run_end).any(|p| !buffered.contains_key(&p)) {
+                let offset = ((page % EXTENT_SIZE) as usize * PAGE_SIZE) as u64;
+                let len = (run_end - page) as usize * PAGE_SIZE;
+                let fetched = self
+                    .store
+                    .get_range(&self.extent_key(extent), offset, len as u64)?
+                    .unwrap_or_default();
+                let out = &mut data[(page - start) as usize * PAGE_SIZE..][..len];
+                let copied = fetched.len().min(len);
+                out[..copied].copy_from_slice(&fetched[..copied]);
+                out[copied..].fill(0);
+            }
+            page = run_end;
+        }
+
+        for (page, page_data) in buffered {
+            let offset = (page - start) as usize * PAGE_SIZE;
+            data[offset..offset + PAGE_SIZE].copy_from_slice(&page_data);
+        }
+        self.num_reads.fetch_add(num_pages, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn allocate_page(&self) -> Result<PageId> {
+        let mut state = self.state.lock();
+        state.meta_dirty = true;
+
+        match state.free_pages.pop_first() {
+            Some(page) => {
+                // A reused page must read back as zeros, not as its old self
+                state.buffer.insert(page, vec![0u8; PAGE_SIZE].into());
+                Ok(PageId::new(page))
+            }
+            None => {
+                let page = state.next_page;
+                state.next_page += 1;
+                Ok(PageId::new(page))
+            }
+        }
+    }
+
+    fn deallocate_page(&self, page_id: PageId) -> Result<()> {
+        let mut state = self.state.lock();
+        if page_id.as_u32() == 0 {
+            return Err(CrioError::InvalidPageId(page_id));
+        }
+        Self::check_allocated(&state, page_id)?;
+
+        state.buffer.remove(&page_id.as_u32());
+        state.free_pages.insert(page_id.as_u32());
+        state.meta_dirty = true;
+        Ok(())
+    }
+
+    /// Limits read-ahead to allocated pages. Tables are not tracked.
+    fn read_ahead_limit(&self, page_id: PageId, num_pages: u32) -> u32 {
+        let state = self.state.lock();
+        (1..=num_pages)
+            .take_while(|&i| {
+                page_id
+                    .as_u32()
+                    .checked_add(i)
+                    .is_some_and(|page| Self::check_allocated(&state, PageId::new(page)).is_ok())
+            })
+            .count() as u32
+    }
+
+    /// Writes the buffer back, then the allocation state.
+    fn sync(&self) -> Result<()> {
+        let mut state = self.state.lock();
+        self.write_back(&mut state)?;
+        if state.meta_dirty {
+            let meta = encode_meta(state.next_page, &state.free_pages);
+            self.store.put(&meta_key(&self.prefix), meta)?;
+            state.meta_dirty = false;
+        }
+        Ok(())
+    }
+
+    fn get_num_reads(&self) -> u32 {
+        self.num_reads.load(Ordering::Relaxed)
+    }
+
+    fn get_num_writes(&self) -> u32 {
+        self.num_writes.load(Ordering::Relaxed)
+    }
+}
+
+fn meta_key(prefix: &str) -> String {
+    format!("{}/meta", prefix)
+}
+
+fn encode_meta(next_page: u32, free_pages: &BTreeSet<u32>) -> Vec<u8> {
+    let mut meta = Vec::with_capacity(META_HEADER_SIZE + free_pages.len() * 4 + 8);
+    meta.extend_from_slice(&META_MAGIC.to_le_bytes());
+    meta.extend_from_slice(&next_page.to_le_bytes());
+    meta.extend_from_slice(&(free_pages.len() as u32).to_le_bytes());
+    for page in free_pages {
+        meta.extend_from_slice(&page.to_le_bytes());
+    }
+    let sum = checksum(&meta);
+    meta.extend_from_slice(&sum.to_le_bytes());
+    meta
+}
+
+fn decode_meta(meta: &[u8]) -> Result<(u32, BTreeSet<u32>)> {
+    let corrupt = || CrioError::ObjectStore("corrupt meta object".to_string());
+    let u32_at = |offset: usize| u32::from_le_bytes(meta[offset..offset + 4].try_into().unwrap());
+
+    if meta.len() < META_HEADER_SIZE + 8 || u32_at(0) != META_MAGIC {
+        return Err(corrupt());
+    }
+    let free_count = u32_at(8) as usize;
+    let body_len = META_HEADER_SIZE + free_count * 4;
+    if meta.len() != body_len + 8 {
+        return Err(corrupt());
+    }
+    let stored = u64::from_le_bytes(meta[body_len..].try_into().unwrap());
+    if checksum(&meta[..body_len]) != stored {
+        return Err(corrupt());
+    }
+
+    let free_pages = (0..free_count)
+        .map(|i| u32_at(META_HEADER_SIZE + i * 4))
+        .collect();
+    Ok((u32_at(4), free_pages))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::disk::MemObjectStore;
+
+    fn page(byte: u8) -> Vec<u8> {
+        vec![byte; PAGE_SIZE]
+    }
+
+    #[test]
+    fn test_object_store_backend_read_write() {
+        let store = Arc::new(MemObjectStore::new());
+        let backend = ObjectStoreBackend::open(store.clone(), "db")
+            .unwrap()
+            .with_write_buffer_pages(64);
+
+        let pages: Vec<_> = (0..10).map(|_| backend.allocate_page().unwrap()).collect();
+        assert_eq!(pages[0], PageId::new(1));
+        for (i, &page_id) in pages.iter().enumerate() {
+            backend.write_page(page_id, &page(i as u8 + 1)).unwrap();
+        }
+
+        // Writes stay in the local buffer until synced
+        assert_eq!(backend.buffered_pages(), 10);
+        assert_eq!(store.put_count(), 0);
+        let mut data = page(0);
+        backend.read_page(pages[3], &mut data).unwrap();
+        assert_eq!(data, page(4));
+
+        // Pages 1..=10 span extents 0 and 1, each written with one PUT;
+        // extent 0 is partly buffered, so it is read first
+        backend.sync().unwrap();
+        assert_eq!(backend.buffered_pages(), 0);
+        assert_eq!(
+            store.keys(),
+            vec!["db/extent-00000000", "db/extent-00000001", "db/meta"]
+        );
+        assert_eq!(store.put_count(), 3);
+
+        let gets = store.get_count();
+        let mut run = vec![0u8; 10 * PAGE_SIZE];
+        backend.read_pages(pages[0], 10, &mut run).unwrap();
+        for (i, chunk) in run.chunks_exact(PAGE_SIZE).enumerate() {
+            assert_eq!(chunk, &page(i as u8 + 1)[..]);
+        }
+        assert_eq!(store.get_count(), gets + 2);
+
+        // Allocated but never written pages read as zeros; others are invalid
+        let fresh = backend.allocate_page().unwrap();
+        backend.read_page(fresh, &mut data).unwrap();
+        assert_eq!(data, page(0));
+        assert!(backend.read_page(PageId::new(100), &mut data).is_err());
+    }
+
+    #[test]
+    fn test_object_store_backend_write_back_and_reopen() {
+        let store = Arc::new(MemObjectStore::new());
+        {
+            let backend = ObjectStoreBackend::open(store.clone(), "db")
+                .unwrap()
+                .with_write_buffer_pages(EXTENT_SIZE as usize);
+            let pages: Vec<_> = (0..EXTENT_SIZE * 2)
+                .map(|_| backend.allocate_page().unwrap())
+                .collect();
+            for &page_id in &pages {
+                backend
+                    .write_page(page_id, &page(page_id.as_u32() as u8))
+                    .unwrap();
+            }
+            // A full buffer is written back without waiting for a sync
+            assert!(backend.buffered_pages() < EXTENT_SIZE as usize);
+            assert!(store.put_count() >= 2);
+
+            backend.deallocate_page(pages[0]).unwrap();
+            assert!(backend.deallocate_page(pages[0]).is_err());
+            backend.sync().unwrap();
+        }
+
+        let backend = ObjectStoreBackend::open(store.clone(), "db").unwrap();
+        let mut data = page(0);
+        backend.read_page(PageId::new(5), &mut data).unwrap();
+        assert_eq!(data, page(5));
+        assert!(backend.read_page(PageId::new(1), &mut data).is_err());
+
+        // The freed page is reused, and reads back as zeros
+        assert_eq!(backend.allocate_page().unwrap(), PageId::new(1));
+        backend.read_page(PageId::new(1), &mut data).unwrap();
+        assert_eq!(data, page(0));
+        assert_eq!(
+            backend.allocate_page().unwrap(),
+            PageId::new(EXTENT_SIZE * 2 + 1)
+        );
+
+        store.put("db/meta", vec![0u8; 4]).unwrap();
+        assert!(matches!(
+            ObjectStoreBackend::open(store, "db"),
+            Err(CrioError::ObjectStore(_))
+        ));
+    }
+}
@@ -0,0 +1,465 @@
+use std::mem;
+use std::sync::Arc;
+
+use parking_lot::{Mutex, RwLock};
+
+use crate::common::{CrioError, PageId, Result, PAGE_SIZE};
+
+use super::StorageBackend;
+
+const ROOT_MAGIC: u32 = 0x5348_4457; // "SHDW"
+
+// Root page layout: magic u32 | seq u64 | page_count u32 | table_page_count u32
+// | table page IDs u32 * table_page_count | ... | checksum u64
+const SEQ_OFFSET: usize = 4;
+const PAGE_COUNT_OFFSET: usize = 12;
+const TABLE_PAGE_COUNT_OFFSET: usize = 16;
+const TABLE_PAGES_OFFSET: usize = 20;
+const CHECKSUM_OFFSET: usize = PAGE_SIZE - 8;
+
+/// Page table pages a root can list
+const MAX_TABLE_PAGES: usize = (CHECKSUM_OFFSET - TABLE_PAGES_OFFSET) / 4;
+/// Page table entry of an unallocated logical page
+const UNMAPPED: u32 = u32::MAX;
+
+struct ShadowState {
+    /// Physical page of each logical page, indexed by logical page ID
+    current: Vec<Option<PageId>>,
+    /// `current` as of the last commit
+    committed: Vec<Option<PageId>>,
+    /// Deallocated logical pages available for reuse
+    free: Vec<u32>,
+    /// Committed physical pages superseded since the last commit; freed once
+    /// the next commit lands
+    retired: Vec<PageId>,
+    /// Physical pages holding the committed page table
+    table_pages: Vec<PageId>,
+    /// Sequence number of the last commit
+    seq: u64,
+}
+
+/// ShadowStorage wraps a storage backend and gives commits crash atomicity by
+/// shadow paging instead of a log.
+///
+/// Callers see logical page IDs, which a page table maps to physical pages of
+/// the inner backend. The first write to a page after a commit goes to a
+/// newly allocated physical page, leaving the committed copy untouched;
+/// later writes before the next commit overwrite the shadow in place.
+/// `commit` writes the whole page table to fresh pages, syncs, and then
+/// writes a root pointing at it into whichever of the two root pages holds
+/// the older commit. Reopening picks the root with the highest sequence
+/// number and a valid checksum, so a crash at any point leaves either the
+/// previous commit or the new one.
+///
+/// The page table is rewritten in full on every commit and a root lists at
+/// most `MAX_TABLE_PAGES` table pages, which suits small databases. Physical
+/// pages written after the last commit are not reclaimed after a crash.
+pub struct ShadowStorage {
+    /// The real backend
+    inner: Arc<dyn StorageBackend>,
+    /// The two physical pages roots alternate between
+    roots: [PageId; 2],
+    /// Page table and commit bookkeeping
+    state: Mutex<ShadowState>,
+    /// Held shared by every other operation and exclusively by `commit`, so
+    /// a commit sees no half-done write
+    commit_lock: RwLock<()>,
+}
+
+impl ShadowStorage {
+    /// Sets up shadow paging on `inner`, which should hold no other data the
+    /// caller cares about, and returns the empty storage. The root pages it
+    /// allocates are needed to reopen it (see `root_page_ids`).
+    pub fn create(inner: Arc<dyn StorageBackend>) -> Result<Self> {
+        let roots = [inner.allocate_page()?, inner.allocate_page()?];
+        inner.write_page(roots[0], &encode_root(0, 1, &[]))?;
+        inner.write_page(roots[1], &[0u8; PAGE_SIZE])?;
+        inner.sync()?;
+        Ok(Self::from_state(inner, roots, vec![None], Vec::new(), 0))
+    }
+
+    /// Reopens shadow storage on `inner` at its last commit.
+    pub fn open(inner: Arc<dyn StorageBackend>, roots: [PageId; 2]) -> Result<Self> {
+        let mut latest: Option<(u64, usize, Vec<PageId>)> = None;
+        for &root in &roots {
+            let mut data = [0u8; PAGE_SIZE];
+            inner.read_page(root, &mut data)?;
+            if let Some((seq, page_count, table_pages)) = decode_root(&data) {
+                if latest
+                    .as_ref()
+                    .is_none_or(|&(latest_seq, ..)| seq > latest_seq)
+                {
+                    latest = Some((seq, page_count, table_pages));
+                }
+            }
+        }
+        let (seq, page_count, table_pages) = latest.ok_or(CrioError::InvalidDatabaseFile)?;
+
+        let mut current = Vec::with_capacity(page_count);
+        let mut data = [0u8; PAGE_SIZE];
+        for &page_id in &table_pages {
+            inner.read_page(page_id, &mut data)?;
+            for entry in data.chunks_exact(4) {
+                if current.len() == page_count {
+                    break;
+                }
+                let physical = u32::from_le_bytes(entry.try_into().unwrap());
+                current.push((physical != UNMAPPED).then(|| PageId::new(physical)));
+            }
+        }
+        if current.len() != page_count {
+            return Err(CrioError::InvalidDatabaseFile);
+        }
+
+        Ok(Self::from_state(inner, roots, current, table_pages, seq))
+    }
+
+    fn from_state(
+        inner: Arc<dyn StorageBackend>,
+        roots: [PageId; 2],
+        current: Vec<Option<PageId>>,
+        table_pages: Vec<PageId>,
+        seq: u64,
+    ) -> Self {
+        let free = (1..current.len() as u32)
+            .rev()
+            .filter(|&i| current[i as usize].is_none())
+            .collect();
+        Self {
+            inner,
+            roots,
+            state: Mutex::new(ShadowState {
+                committed: current.clone(),
+                current,
+                free,
+                retired: Vec::new(),
+                table_pages,
+                seq,
+            }),
+            commit_lock: RwLock::new(()),
+        }
+    }
+
+    /// Returns the physical pages holding the two roots.
+    pub fn root_page_ids(&self) -> [PageId; 2] {
+        self.roots
+    }
+
+    /// Returns the number of commits made since the storage was created.
+    pub fn commit_seq(&self) -> u64 {
+        self.state.lock().seq
+    }
+
+    /// Makes every write and (de)allocation so far durable as one atomic
+    /// step. Writes issued while a commit runs wait for it.
+    pub fn commit(&self) -> Result<()> {
+        let _commit = self.commit_lock.write();
+        let mut state = self.state.lock();
+
+        let mut entries: Vec<u8> = state
+            .current
+            .iter()
+            .flat_map(|page_id| page_id.map_or(UNMAPPED, |p| p.as_u32()).to_le_bytes())
+            .collect();
+        let table_page_count = entries.len().div_ceil(PAGE_SIZE);
+        if table_page_count > MAX_TABLE_PAGES {
+            return Err(CrioError::ShadowPageTableFull(state.current.len()));
+        }
+        entries.resize(table_page_count * PAGE_SIZE, 0xFF);
+
+        let mut table_pages = Vec::with_capacity(table_page_count);
+        let written = entries.chunks_exact(PAGE_SIZE).try_for_each(|chunk| {
+            let page_id = self.inner.allocate_page()?;
+            table_pages.push(page_id);
+            self.inner.write_page(page_id, chunk)
+        });
+        if let Err(e) = written.and_then(|()| self.inner.sync()) {
+            for page_id in table_pages {
+                let _ = self.inner.deallocate_page(page_id);
+            }
+            return Err(e);
+        }
+
+        // The root write is the commit point
+        let seq = state.seq + 1;
+        let root = encode_root(seq, state.current.len(), &table_pages);
+        self.inner.write_page(self.roots[seq as usize % 2], &root)?;
+        self.inner.sync()?;
+
+        // Nothing reachable from the new root refers to these any more
+        let old_table_pages = mem::replace(&mut state.table_pages, table_pages);
+        let retired = mem::take(&mut state.retired);
+        state.committed = state.current.clone();
+        state.seq = seq;
+        for page_id in retired.into_iter().chain(old_table_pages) {
+            self.inner.deallocate_page(page_id)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the physical page currently backing `page_id`.
+    fn physical(&self, page_id: PageId) -> Result<PageId> {
+        let state = self.state.lock();
+        state
+            .current
+            .get(page_id.as_u32() as usize)
+            .copied()
+            .flatten()
+            .ok_or(CrioError::InvalidPageId(page_id))
+    }
+
+    /// Returns the physical page a write to `page_id` goes to, moving the
+    /// page to a fresh one if it is still at its committed location.
+    fn shadow(&self, page_id: PageId) -> Result<PageId> {
+        let mut state = self.state.lock();
+        let i = page_id.as_u32() as usize;
+        let current = state
+            .current
+            .get(i)
+            .copied()
+            .flatten()
+            .ok_or(CrioError::InvalidPageId(page_id))?;
+        if state.committed.get(i).copied().flatten() != Some(current) {
+            return Ok(current);
+        }
+
+        let shadow = self.inner.allocate_page()?;
+        state.current[i] = Some(shadow);
+        state.retired.push(current);
+        Ok(shadow)
+    }
+}
+
+impl StorageBackend for ShadowStorage {
+    fn read_page(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
+        let _commit = self.commit_lock.read();
+        self.inner.read_page(self.physical(page_id)?, data)
+    }
+
+    fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()> {
+        let _commit = self.commit_lock.read();
+        self.inner.write_page(self.shadow(page_id)?, data)
+    }
+
+    fn write_pages_vectored(&self, pages: &[(PageId, &[u8])]) -> Result<()> {
+        let _commit = self.commit_lock.read();
+        let mut physical = pages
+            .iter()
+            .map(|&(page_id, data)| Ok((self.shadow(page_id)?, data)))
+            .collect::<Result<Vec<_>>>()?;
+        physical.sort_by_key(|&(page_id, _)| page_id.as_u32());
+        self.inner.write_pages_vectored(&physical)
+    }
+
+    fn allocate_page(&self) -> Result<PageId> {
+        let _commit = self.commit_lock.read();
+        let physical = self.inner.allocate_page()?;
+
+        let mut state = self.state.lock();
+        let logical = match state.free.pop() {
+            Some(logical) => logical,
+            None => {
+                state.current.push(None);
+                state.current.len() as u32 - 1
+            }
+        };
+        state.current[logical as usize] = Some(physical);
+        Ok(PageId::new(logical))
+    }
+
+    fn deallocate_page(&self, page_id: PageId) -> Result<()> {
+        let _commit = self.commit_lock.read();
+        let mut state = self.state.lock();
+        let i = page_id.as_u32() as usize;
+        let physical = state
+            .current
+            .get_mut(i)
+            .and_then(Option::take)
+            .ok_or(CrioError::InvalidPageId(page_id))?;
+        state.free.push(i as u32);
+
+        if state.committed.get(i).copied().flatten() == Some(physical) {
+            state.retired.push(physical);
+            Ok(())
+        } else {
+            drop(state);
+            self.inner.deallocate_page(physical)
+        }
+    }
+
+    /// Limits read-ahead to mapped logical pages. Logical pages are not
+    /// laid out by table, so tables are not tracked.
+    fn read_ahead_limit(&self, page_id: PageId, num_pages: u32) -> u32 {
+        let state = self.state.lock();
+        let start = page_id.as_u32() as usize;
+        (1..=num_pages as usize)
+            .take_while(|&i| state.current.get(start + i).is_some_and(Option::is_some))
+            .count() as u32
+    }
+
+    fn sync(&self) -> Result<()> {
+        self.inner.sync()
+    }
+
+    /// Commits, then closes the inner backend.
+    fn close(&self) -> Result<()> {
+        self.commit()?;
+        self.inner.close()
+    }
+
+    fn get_num_reads(&self) -> u32 {
+        self.inner.get_num_reads()
+    }
+
+    fn get_num_writes(&self) -> u32 {
+        self.inner.get_num_writes()
+    }
+}
+
+fn encode_root(seq: u64, page_count: usize, table_pages: &[PageId]) -> [u8; PAGE_SIZE] {
+    let mut data = [0u8; PAGE_SIZE];
+    data[..SEQ_OFFSET].copy_from_slice(&ROOT_MAGIC.to_le_bytes());
+    data[SEQ_OFFSET..PAGE_COUNT_OFFSET].copy_from_slice(&seq.to_le_bytes());
+    data[PAGE_COUNT_OFFSET..TABLE_PAGE_COUNT_OFFSET]
+        .copy_from_slice(&(page_count as u32).to_le_bytes());
+    data[TABLE_PAGE_COUNT_OFFSET..TABLE_PAGES_OFFSET]
+        .copy_from_slice(&(table_pages.len() as u32).to_le_bytes());
+    for (i, page_id) in table_pages.iter().enumerate() {
+        let offset = TABLE_PAGES_OFFSET + i * 4;
+        data[offset..offset + 4].copy_from_slice(&page_id.as_u32().to_le_bytes());
+    }
+    let checksum = checksum(&data[..CHECKSUM_OFFSET]);
+    data[CHECKSUM_OFFSET..].copy_from_slice(&checksum.to_le_bytes());
+    data
+}
+
+/// Returns `(seq, page_count, table_pages)` of a root, or None if the page
+/// holds no valid root, e.g. because its write was torn.
+fn decode_root(data: &[u8]) -> Option<(u64, usize, Vec<PageId>)> {
+    let u32_at = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let checksum_ok = data[CHECKSUM_OFFSET..] == checksum(&data[..CHECKSUM_OFFSET]).to_le_bytes();
+    if u32_at(0) != ROOT_MAGIC || !checksum_ok {
+        return None;
+    }
+
+    let seq = u64::from_le_bytes(data[SEQ_OFFSET..PAGE_COUNT_OFFSET].try_into().unwrap());
+    let table_page_count = u32_at(TABLE_PAGE_COUNT_OFFSET) as usize;
+    if table_page_count > MAX_TABLE_PAGES {
+        return None;
+    }
+    let table_pages = (0..table_page_count)
+        .map(|i| PageId::new(u32_at(TABLE_PAGES_OFFSET + i * 4)))
+        .collect();
+    Some((seq, u32_at(PAGE_COUNT_OFFSET) as usize, table_pages))
+}
+
+/// FNV-1a over `data`.
+pub(super) fn checksum(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::disk::{Fault, FaultInjectingDiskManager, FaultTrigger, MemDiskManager};
+
+    fn page(fill: u8) -> [u8; PAGE_SIZE] {
+        [fill; PAGE_SIZE]
+    }
+
+    fn read(storage: &ShadowStorage, page_id: PageId) -> u8 {
+        let mut data = [0u8; PAGE_SIZE];
+        storage.read_page(page_id, &mut data).unwrap();
+        data[0]
+    }
+
+    #[test]
+    fn test_shadow_storage_reopens_at_last_commit() {
+        let disk = Arc::new(MemDiskManager::new());
+        let storage = ShadowStorage::create(Arc::clone(&disk) as _).unwrap();
+        let roots = storage.root_page_ids();
+
+        let a = storage.allocate_page().unwrap();
+        let b = storage.allocate_page().unwrap();
+        storage.write_page(a, &page(1)).unwrap();
+        storage.write_page(b, &page(2)).unwrap();
+        storage.commit().unwrap();
+
+        // Uncommitted changes never touch the committed pages
+        let c = storage.allocate_page().unwrap();
+        storage
+            .write_pages_vectored(&[(a, &page(10)), (c, &page(30))])
+            .unwrap();
+        storage.write_page(a, &page(11)).unwrap();
+        storage.deallocate_page(b).unwrap();
+        assert_eq!(read(&storage, a), 11);
+        drop(storage);
+
+        let storage = ShadowStorage::open(Arc::clone(&disk) as _, roots).unwrap();
+        assert_eq!(storage.commit_seq(), 1);
+        assert_eq!((read(&storage, a), read(&storage, b)), (1, 2));
+        assert!(storage.read_page(c, &mut page(0)).is_err());
+
+        // A second commit lands in the other root
+        storage.write_page(b, &page(22)).unwrap();
+        storage.commit().unwrap();
+        let pages_after_commit = disk.get_num_pages();
+        storage.write_page(b, &page(23)).unwrap();
+        storage.commit().unwrap();
+        drop(storage);
+
+        let storage = ShadowStorage::open(disk.clone() as _, roots).unwrap();
+        assert_eq!(storage.commit_seq(), 3);
+        assert_eq!((read(&storage, a), read(&storage, b)), (1, 23));
+
+        // Superseded pages and page tables are freed
+        assert_eq!(disk.get_num_pages(), pages_after_commit);
+    }
+
+    #[test]
+    fn test_shadow_storage_crash_during_commit() {
+        let disk = Arc::new(MemDiskManager::new());
+        let faulty = Arc::new(FaultInjectingDiskManager::new(Arc::clone(&disk) as _));
+        let storage = ShadowStorage::create(Arc::clone(&faulty) as _).unwrap();
+        let roots = storage.root_page_ids();
+
+        let pages: Vec<_> = (0..8)
+            .map(|i| {
+                let page_id = storage.allocate_page().unwrap();
+                storage.write_page(page_id, &page(i)).unwrap();
+                page_id
+            })
+            .collect();
+        storage.commit().unwrap();
+
+        for (i, &page_id) in pages.iter().enumerate() {
+            storage.write_page(page_id, &page(100 + i as u8)).unwrap();
+        }
+
+        // Power loss as the new root is written: every page keeps its old
+        // contents
+        faulty.inject(FaultTrigger::Page(roots[0]), Fault::Crash);
+        storage.commit().unwrap();
+        assert!(faulty.is_crashed());
+        drop(storage);
+
+        let storage = ShadowStorage::open(disk.clone() as _, roots).unwrap();
+        for (i, &page_id) in pages.iter().enumerate() {
+            assert_eq!(read(&storage, page_id), i as u8);
+        }
+
+        // A torn root is ignored too
+        let root = encode_root(2, pages.len() + 1, &[]);
+        let mut torn = [0u8; PAGE_SIZE];
+        torn[..512].copy_from_slice(&root[..512]);
+        assert!(decode_root(&root).is_some());
+        assert!(decode_root(&torn).is_none());
+        disk.write_page(roots[0], &torn).unwrap();
+
+        let storage = ShadowStorage::open(disk as _, roots).unwrap();
+        assert_eq!(storage.commit_seq(), 1);
+        assert_eq!(read(&storage, pages[7]), 7);
+    }
+}
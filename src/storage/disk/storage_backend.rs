@@ -0,0 +1,83 @@
+use crate::common::{PageId, Result, PAGE_SIZE};
+
+use super::extent_allocator::EXTENT_SIZE;
+
+/// StorageBackend is the page-level storage interface used by the DiskScheduler
+/// and BufferPoolManager. DiskManager is the file-backed implementation; other
+/// backends (in-memory, encrypted, object store) can be plugged in by
+/// implementing this trait.
+///
+/// Implementations must be safe to call from the disk scheduler's worker thread
+/// and from any thread holding the buffer pool.
+pub trait StorageBackend: Send + Sync {
+    /// Reads a page into the provided buffer, which must be PAGE_SIZE bytes.
+    fn read_page(&self, page_id: PageId, data: &mut [u8]) -> Result<()>;
+
+    /// Writes a page from the provided buffer, which must be PAGE_SIZE bytes.
+    fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()>;
+
+    /// Reads `num_pages` contiguous pages starting at `start_page_id`.
+    /// The default implementation issues one `read_page` per page.
+    fn read_pages(&self, start_page_id: PageId, num_pages: u32, data: &mut [u8]) -> Result<()> {
+        assert_eq!(data.len(), num_pages as usize * PAGE_SIZE);
+        for (i, chunk) in data.chunks_exact_mut(PAGE_SIZE).enumerate() {
+            self.read_page(PageId::new(start_page_id.as_u32() + i as u32), chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `num_pages` contiguous pages starting at `start_page_id`.
+    /// The default implementation issues one `write_page` per page.
+    fn write_pages(&self, start_page_id: PageId, num_pages: u32, data: &[u8]) -> Result<()> {
+        assert_eq!(data.len(), num_pages as usize * PAGE_SIZE);
+        for (i, chunk) in data.chunks_exact(PAGE_SIZE).enumerate() {
+            self.write_page(PageId::new(start_page_id.as_u32() + i as u32), chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a batch of pages that need not be adjacent on disk. Pages should be
+    /// sorted by page ID so that contiguous runs can be coalesced.
+    /// The default implementation issues one `write_page` per page.
+    fn write_pages_vectored(&self, pages: &[(PageId, &[u8])]) -> Result<()> {
+        for &(page_id, data) in pages {
+            self.write_page(page_id, data)?;
+        }
+        Ok(())
+    }
+
+    /// Allocates a new page and returns its ID.
+    fn allocate_page(&self) -> Result<PageId>;
+
+    /// Allocates a whole extent of pages for `table_id` and returns their IDs
+    /// in order. The default implementation calls `allocate_page`
+    /// `EXTENT_SIZE` times, so the pages need not be contiguous.
+    fn allocate_extent(&self, _table_id: u32) -> Result<Vec<PageId>> {
+        (0..EXTENT_SIZE).map(|_| self.allocate_page()).collect()
+    }
+
+    /// Returns a page to the backend for reuse.
+    fn deallocate_page(&self, page_id: PageId) -> Result<()>;
+
+    /// Returns how many of the `num_pages` pages following `page_id` are
+    /// worth reading ahead of it: allocated pages of the same table, up to
+    /// the first that isn't. The default implementation allows all of them.
+    fn read_ahead_limit(&self, _page_id: PageId, num_pages: u32) -> u32 {
+        num_pages
+    }
+
+    /// Makes all completed writes durable.
+    fn sync(&self) -> Result<()>;
+
+    /// Makes all completed writes durable and records that the session ended
+    /// cleanly. Called once, after the last write.
+    fn close(&self) -> Result<()> {
+        self.sync()
+    }
+
+    /// Returns the number of page reads served.
+    fn get_num_reads(&self) -> u32;
+
+    /// Returns the number of page writes performed.
+    fn get_num_writes(&self) -> u32;
+}
@@ -0,0 +1,266 @@
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use parking_lot::Mutex;
+
+use crate::common::{CrioError, PageId, QueryBudget, Result, PAGE_SIZE};
+
+struct TempFileState {
+    /// Scratch file handle
+    file: File,
+    /// Number of pages ever allocated (file high-water mark)
+    next_page: u32,
+    /// Pages freed and available for reuse
+    free_pages: Vec<u32>,
+}
+
+/// TempFileManager hands out scratch pages for operator spilling (sorts, hash
+/// joins) in a file of its own. Temp pages never touch the database segments,
+/// the directory page or the extent allocator, are not logged, and the file is
+/// deleted when the manager is dropped.
+///
+/// Page IDs returned by this manager are only meaningful to the same manager.
+pub struct TempFileManager {
+    /// Path of the scratch file
+    path: PathBuf,
+    /// Maximum number of pages that may be allocated at once
+    quota_pages: u32,
+    /// File handle and allocation state
+    state: Mutex<TempFileState>,
+}
+
+impl TempFileManager {
+    /// Creates a scratch file at `path` that may hold at most `quota_pages`
+    /// live pages. Fails with an `AlreadyExists` I/O error if something is
+    /// already there, rather than truncating a file another process may own.
+    pub fn new<P: AsRef<Path>>(path: P, quota_pages: u32) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(Self {
+            path,
+            quota_pages,
+            state: Mutex::new(TempFileState {
+                file,
+                next_page: 0,
+                free_pages: Vec::new(),
+            }),
+        })
+    }
+
+    /// Allocates a scratch page, reusing freed pages first.
+    /// Fails with `TempQuotaExceeded` once `quota_pages` pages are live.
+    pub fn allocate_page(&self) -> Result<PageId> {
+        let mut state = self.state.lock();
+
+        if let Some(offset) = state.free_pages.pop() {
+            return Ok(PageId::from_parts(0, offset));
+        }
+
+        if state.next_page >= self.quota_pages || state.next_page > PageId::PAGE_OFFSET_MASK {
+            return Err(CrioError::TempQuotaExceeded(self.quota_pages));
+        }
+
+        let offset = state.next_page;
+        state.next_page += 1;
+        Ok(PageId::from_parts(0, offset))
+    }
+
+    /// Returns a scratch page to the free list.
+    pub fn free_page(&self, page_id: PageId) -> Result<()> {
+        let mut state = self.state.lock();
+        Self::check_allocated(&state, page_id)?;
+
+        state.free_pages.push(page_id.page_offset());
+        Ok(())
+    }
+
+    /// Allocates a scratch page for the query owning `budget`, charging it
+    /// first so a query over its temp limit fails with `BudgetExceeded`
+    /// before the manager's own quota is touched.
+    pub fn allocate_page_for(&self, budget: &QueryBudget) -> Result<PageId> {
+        budget.charge_temp_page()?;
+        self.allocate_page()
+            .inspect_err(|_| budget.release_temp_page())
+    }
+
+    /// Frees a scratch page allocated with `allocate_page_for`, giving it
+    /// back to `budget`.
+    pub fn free_page_for(&self, page_id: PageId, budget: &QueryBudget) -> Result<()> {
+        self.free_page(page_id)?;
+        budget.release_temp_page();
+        Ok(())
+    }
+
+    /// Reads a scratch page into the provided buffer.
+    /// Pages that were allocated but never written read back as zeros.
+    pub fn read_page(&self, page_id: PageId, data: &mut [u8]) -> Result<()> {
+        assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+
+        let mut state = self.state.lock();
+        Self::check_allocated(&state, page_id)?;
+
+        let byte_offset = (page_id.page_offset() as u64) * (PAGE_SIZE as u64);
+        state.file.seek(SeekFrom::Start(byte_offset))?;
+
+        // Reads may come back short; only EOF ends the page early
+        let mut bytes_read = 0;
+        while bytes_read < PAGE_SIZE {
+            match state.file.read(&mut data[bytes_read..]) {
+                Ok(0) => break,
+                Ok(n) => bytes_read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        data[bytes_read..].fill(0);
+
+        Ok(())
+    }
+
+    /// Writes a scratch page from the provided buffer. No fsync is issued since
+    /// temp data does not need to survive a crash.
+    pub fn write_page(&self, page_id: PageId, data: &[u8]) -> Result<()> {
+        assert_eq!(data.len(), PAGE_SIZE, "Buffer must be PAGE_SIZE bytes");
+
+        let mut state = self.state.lock();
+        Self::check_allocated(&state, page_id)?;
+
+        let byte_offset = (page_id.page_offset() as u64) * (PAGE_SIZE as u64);
+        state.file.seek(SeekFrom::Start(byte_offset))?;
+        state.file.write_all(data)?;
+
+        Ok(())
+    }
+
+    /// Returns the number of live (allocated and not freed) scratch pages.
+    pub fn allocated_pages(&self) -> u32 {
+        let state = self.state.lock();
+        state.next_page - state.free_pages.len() as u32
+    }
+
+    /// Returns the page quota.
+    pub fn quota_pages(&self) -> u32 {
+        self.quota_pages
+    }
+
+    /// Returns the path of the scratch file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn check_allocated(state: &TempFileState, page_id: PageId) -> Result<()> {
+        let offset = page_id.page_offset();
+        if page_id.file_id() != 0 || offset >= state.next_page || state.free_pages.contains(&offset)
+        {
+            return Err(CrioError::InvalidPageId(page_id));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TempFileManager {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_file_read_write() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let tfm = TempFileManager::new(temp_dir.path().join("spill.tmp"), 4).unwrap();
+
+        let page_id = tfm.allocate_page().unwrap();
+        let mut data = [0u8; PAGE_SIZE];
+        data[0] = 42;
+        tfm.write_page(page_id, &data).unwrap();
+
+        let mut read_data = [0u8; PAGE_SIZE];
+        tfm.read_page(page_id, &mut read_data).unwrap();
+        assert_eq!(read_data[0], 42);
+
+        // Never-written pages read back as zeros
+        let page_id2 = tfm.allocate_page().unwrap();
+        tfm.read_page(page_id2, &mut read_data).unwrap();
+        assert!(read_data.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_temp_file_quota_and_reuse() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let tfm = TempFileManager::new(temp_dir.path().join("quota.tmp"), 2).unwrap();
+
+        let p1 = tfm.allocate_page().unwrap();
+        let _p2 = tfm.allocate_page().unwrap();
+        assert!(matches!(
+            tfm.allocate_page(),
+            Err(CrioError::TempQuotaExceeded(2))
+        ));
+
+        tfm.free_page(p1).unwrap();
+        assert_eq!(tfm.allocated_pages(), 1);
+        assert!(tfm.free_page(p1).is_err());
+
+        let mut data = [0u8; PAGE_SIZE];
+        assert!(tfm.read_page(p1, &mut data).is_err());
+
+        assert_eq!(tfm.allocate_page().unwrap(), p1);
+    }
+
+    #[test]
+    fn test_temp_file_never_truncates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("taken.tmp");
+        std::fs::write(&path, b"not ours").unwrap();
+
+        assert!(matches!(
+            TempFileManager::new(&path, 1),
+            Err(CrioError::Io(e)) if e.kind() == ErrorKind::AlreadyExists
+        ));
+        assert_eq!(std::fs::read(&path).unwrap(), b"not ours");
+    }
+
+    #[test]
+    fn test_temp_file_query_budget() {
+        use crate::common::ResourceLimits;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let tfm = TempFileManager::new(temp_dir.path().join("budget.tmp"), 4).unwrap();
+        let budget = QueryBudget::new(ResourceLimits {
+            max_temp_pages: Some(1),
+            ..Default::default()
+        });
+
+        let p1 = tfm.allocate_page_for(&budget).unwrap();
+        assert!(matches!(
+            tfm.allocate_page_for(&budget),
+            Err(CrioError::BudgetExceeded { limit: 1, .. })
+        ));
+        assert_eq!(tfm.allocated_pages(), 1);
+
+        tfm.free_page_for(p1, &budget).unwrap();
+        assert_eq!(budget.usage().temp_pages, 0);
+        assert_eq!(budget.usage().peak_temp_pages, 1);
+        tfm.allocate_page_for(&budget).unwrap();
+    }
+
+    #[test]
+    fn test_temp_file_deleted_on_drop() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("drop.tmp");
+
+        let tfm = TempFileManager::new(&path, 1).unwrap();
+        assert!(path.exists());
+        drop(tfm);
+        assert!(!path.exists());
+    }
+}
@@ -1,2 +1,3 @@
 pub mod disk;
 pub mod page;
+pub mod table;
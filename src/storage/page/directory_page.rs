@@ -10,8 +10,16 @@ const FREE_PAGE_LIST_HEAD_OFFSET: usize = 12;
 const TABLE_COUNT_OFFSET: usize = 16;
 const TABLE_ENTRIES_OFFSET: usize = 20;
 
+// Flags live in the word before the segment size, past the end of the table
+// entries, and the segment size in the last word of the page
+const SEGMENT_PAGES_OFFSET: usize = PAGE_SIZE - 4;
+const FLAGS_OFFSET: usize = SEGMENT_PAGES_OFFSET - 4;
+
 const TABLE_ENTRY_SIZE: usize = 12; // table_id (4) + first_page (4) + page_count (4)
-const MAX_TABLES: usize = (PAGE_SIZE - TABLE_ENTRIES_OFFSET) / TABLE_ENTRY_SIZE;
+const MAX_TABLES: usize = (FLAGS_OFFSET - TABLE_ENTRIES_OFFSET) / TABLE_ENTRY_SIZE;
+
+/// Set when the database was closed cleanly, cleared while it is open
+const FLAG_CLEAN_SHUTDOWN: u32 = 1;
 
 const INVALID_PAGE: u32 = u32::MAX;
 
@@ -69,6 +77,43 @@ impl<'a> DirectoryPage<'a> {
         self.data[VERSION_OFFSET..VERSION_OFFSET + 4].copy_from_slice(&version.to_le_bytes());
     }
 
+    /// Returns the number of pages per segment file the database was
+    /// created with, or 0 if it wasn't recorded.
+    pub fn segment_pages(&self) -> u32 {
+        u32::from_le_bytes(
+            self.data[SEGMENT_PAGES_OFFSET..SEGMENT_PAGES_OFFSET + 4]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    pub fn set_segment_pages(&mut self, segment_pages: u32) {
+        self.data[SEGMENT_PAGES_OFFSET..SEGMENT_PAGES_OFFSET + 4]
+            .copy_from_slice(&segment_pages.to_le_bytes());
+    }
+
+    fn flags(&self) -> u32 {
+        u32::from_le_bytes(
+            self.data[FLAGS_OFFSET..FLAGS_OFFSET + 4]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    /// Returns true if the database was closed cleanly.
+    pub fn clean_shutdown(&self) -> bool {
+        self.flags() & FLAG_CLEAN_SHUTDOWN != 0
+    }
+
+    pub fn set_clean_shutdown(&mut self, clean: bool) {
+        let flags = if clean {
+            self.flags() | FLAG_CLEAN_SHUTDOWN
+        } else {
+            self.flags() & !FLAG_CLEAN_SHUTDOWN
+        };
+        self.data[FLAGS_OFFSET..FLAGS_OFFSET + 4].copy_from_slice(&flags.to_le_bytes());
+    }
+
     pub fn page_count(&self) -> u32 {
         u32::from_le_bytes(
             self.data[PAGE_COUNT_OFFSET..PAGE_COUNT_OFFSET + 4]
@@ -262,6 +307,19 @@ impl<'a> DirectoryPageRef<'a> {
         )
     }
 
+    pub fn free_page_list_head(&self) -> Option<PageId> {
+        let val = u32::from_le_bytes(
+            self.data[FREE_PAGE_LIST_HEAD_OFFSET..FREE_PAGE_LIST_HEAD_OFFSET + 4]
+                .try_into()
+                .unwrap(),
+        );
+        if val == INVALID_PAGE {
+            None
+        } else {
+            Some(PageId::new(val))
+        }
+    }
+
     pub fn table_count(&self) -> u32 {
         u32::from_le_bytes(
             self.data[TABLE_COUNT_OFFSET..TABLE_COUNT_OFFSET + 4]
@@ -270,6 +328,44 @@ impl<'a> DirectoryPageRef<'a> {
         )
     }
 
+    /// Returns the number of pages per segment file the database was
+    /// created with, or 0 if it wasn't recorded.
+    pub fn segment_pages(&self) -> u32 {
+        u32::from_le_bytes(
+            self.data[SEGMENT_PAGES_OFFSET..SEGMENT_PAGES_OFFSET + 4]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    /// Returns the registered table entries. A corrupt table count is clamped
+    /// to the number of entries the page can hold.
+    pub fn table_entries(&self) -> Vec<TableEntry> {
+        let count = (self.table_count() as usize).min(MAX_TABLES);
+        (0..count)
+            .map(|i| {
+                let offset = TABLE_ENTRIES_OFFSET + i * TABLE_ENTRY_SIZE;
+                let read =
+                    |at: usize| u32::from_le_bytes(self.data[at..at + 4].try_into().unwrap());
+                TableEntry {
+                    table_id: read(offset),
+                    first_page_id: PageId::new(read(offset + 4)),
+                    page_count: read(offset + 8),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns true if the database was closed cleanly.
+    pub fn clean_shutdown(&self) -> bool {
+        let flags = u32::from_le_bytes(
+            self.data[FLAGS_OFFSET..FLAGS_OFFSET + 4]
+                .try_into()
+                .unwrap(),
+        );
+        flags & FLAG_CLEAN_SHUTDOWN != 0
+    }
+
     pub fn find_table(&self, table_id: u32) -> Option<TableEntry> {
         for i in 0..self.table_count() as usize {
             let offset = TABLE_ENTRIES_OFFSET + i * TABLE_ENTRY_SIZE;
@@ -307,6 +403,30 @@ mod tests {
         assert_eq!(page.table_count(), 0);
     }
 
+    #[test]
+    fn test_directory_page_clean_shutdown_flag() {
+        let mut data = [0u8; PAGE_SIZE];
+        let mut page = DirectoryPage::new(&mut data);
+        page.init();
+        assert!(!page.clean_shutdown());
+
+        page.set_clean_shutdown(true);
+        page.set_segment_pages(4096);
+        assert!(page.clean_shutdown());
+        // The flag and segment size share no bytes with the table entries
+        for table_id in 0..MAX_TABLES as u32 {
+            page.register_table(table_id, PageId::new(u32::MAX - 1))
+                .unwrap();
+        }
+        assert!(DirectoryPageRef::new(&data).clean_shutdown());
+        assert_eq!(DirectoryPageRef::new(&data).segment_pages(), 4096);
+
+        let mut page = DirectoryPage::new(&mut data);
+        page.set_clean_shutdown(false);
+        assert!(!DirectoryPageRef::new(&data).clean_shutdown());
+        assert_eq!(DirectoryPageRef::new(&data).segment_pages(), 4096);
+    }
+
     #[test]
     fn test_directory_page_register_table() {
         let mut data = [0u8; PAGE_SIZE];
@@ -1,4 +1,6 @@
-use crate::common::{CrioError, PageId, Result, SlotId, PAGE_SIZE};
+use std::ops::Range;
+
+use crate::common::{CrioError, PageId, RecordId, Result, SlotId, PAGE_SIZE};
 
 /// Slotted page layout:
 ///
@@ -22,8 +24,14 @@ use crate::common::{CrioError, PageId, Result, SlotId, PAGE_SIZE};
 ///
 /// Each slot entry contains:
 ///   - offset: u16 (offset from start of page to tuple data)
-///   - length: u16 (length of the tuple)
+///   - length: u16 (length of the tuple, high bit = tombstone flag)
 ///   - A length of 0 indicates an empty/deleted slot
+///   - A set tombstone bit marks a logically deleted tuple whose bytes are
+///     kept (and whose slot is not reused) until it is reclaimed
+///   - A set forward bit marks a forwarding stub: the tuple grew too large for
+///     this page and moved, and the slot holds its new RecordId
+///   - A set moved bit marks a tuple moved in from another page; its bytes
+///     start with the RecordId of the stub it moved from
 const HEADER_SIZE: usize = 16;
 
 /// Size of each slot entry in bytes
@@ -41,6 +49,36 @@ const FREE_SPACE_START_OFFSET: usize = 8;
 /// Offset of free_space_end field in header
 const FREE_SPACE_END_OFFSET: usize = 12;
 
+/// High bit of the slot length marks a tombstoned (logically deleted) tuple.
+/// Tuple lengths never exceed PAGE_SIZE, so the bit is always free.
+const TOMBSTONE_FLAG: u16 = 0x8000;
+
+/// Slot length bit marking a forwarding stub.
+const FORWARD_FLAG: u16 = 0x4000;
+
+/// Slot length bit marking a tuple moved in from another page.
+const MOVED_FLAG: u16 = 0x2000;
+
+/// Bits of the slot length that hold the length itself.
+const LENGTH_MASK: u16 = 0x1FFF;
+
+/// Size of a RecordId stored in a forwarding stub or ahead of a moved tuple.
+pub const RECORD_ID_SIZE: usize = 6;
+
+fn encode_record_id(record_id: RecordId) -> [u8; RECORD_ID_SIZE] {
+    let mut bytes = [0u8; RECORD_ID_SIZE];
+    bytes[..4].copy_from_slice(&record_id.page_id.as_u32().to_le_bytes());
+    bytes[4..].copy_from_slice(&record_id.slot_id.as_u16().to_le_bytes());
+    bytes
+}
+
+fn decode_record_id(bytes: &[u8]) -> RecordId {
+    RecordId::new(
+        PageId::new(u32::from_le_bytes(bytes[..4].try_into().unwrap())),
+        SlotId::new(u16::from_le_bytes(bytes[4..6].try_into().unwrap())),
+    )
+}
+
 /// Represents a slot entry in the slot array
 #[derive(Debug, Clone, Copy)]
 pub struct SlotEntry {
@@ -48,23 +86,102 @@ pub struct SlotEntry {
     pub offset: u16,
     /// Length of the tuple (0 = empty/deleted)
     pub length: u16,
+    /// Whether the tuple is logically deleted but not yet reclaimed
+    pub tombstone: bool,
+    /// Whether the slot is a forwarding stub holding the tuple's new RecordId
+    pub forward: bool,
+    /// Whether the tuple moved here from another page
+    pub moved: bool,
 }
 
 impl SlotEntry {
     pub fn new(offset: u16, length: u16) -> Self {
-        Self { offset, length }
+        Self {
+            offset,
+            length,
+            tombstone: false,
+            forward: false,
+            moved: false,
+        }
     }
 
     pub fn empty() -> Self {
-        Self {
-            offset: 0,
-            length: 0,
-        }
+        Self::new(0, 0)
     }
 
     pub fn is_empty(&self) -> bool {
         self.length == 0
     }
+
+    pub fn is_tombstone(&self) -> bool {
+        self.tombstone
+    }
+
+    pub fn is_forward(&self) -> bool {
+        self.forward
+    }
+
+    pub fn is_moved(&self) -> bool {
+        self.moved
+    }
+
+    /// Returns true if the slot holds a visible tuple: not empty, tombstoned
+    /// or a forwarding stub.
+    pub fn is_live(&self) -> bool {
+        !self.is_empty() && !self.is_tombstone() && !self.is_forward()
+    }
+
+    /// Decodes a slot entry from its on-page representation.
+    fn decode(offset: u16, raw_length: u16) -> Self {
+        Self {
+            offset,
+            length: raw_length & LENGTH_MASK,
+            tombstone: raw_length & TOMBSTONE_FLAG != 0,
+            forward: raw_length & FORWARD_FLAG != 0,
+            moved: raw_length & MOVED_FLAG != 0,
+        }
+    }
+
+    /// Returns the on-page representation of the length field.
+    fn raw_length(&self) -> u16 {
+        let mut raw = self.length;
+        if self.tombstone {
+            raw |= TOMBSTONE_FLAG;
+        }
+        if self.forward {
+            raw |= FORWARD_FLAG;
+        }
+        if self.moved {
+            raw |= MOVED_FLAG;
+        }
+        raw
+    }
+
+    /// Returns the page range of the tuple's own bytes, past the home
+    /// RecordId of a moved tuple.
+    fn tuple_range(&self) -> Range<usize> {
+        let start = self.offset as usize;
+        let end = start + self.length as usize;
+        if self.moved {
+            (start + RECORD_ID_SIZE).min(end)..end
+        } else {
+            start..end
+        }
+    }
+
+    /// Checks that the slot holds a readable tuple.
+    fn check_live(&self, slot_id: SlotId) -> Result<()> {
+        if self.is_empty() {
+            return Err(CrioError::EmptySlot(slot_id.as_u16()));
+        }
+        if self.is_tombstone() {
+            return Err(CrioError::TupleDeleted(slot_id.as_u16()));
+        }
+        if self.is_forward() {
+            return Err(CrioError::TupleForwarded(slot_id.as_u16()));
+        }
+        Ok(())
+    }
 }
 
 /// SlottedPage provides methods to interpret and manipulate a page
@@ -179,7 +296,7 @@ impl<'a> SlottedPage<'a> {
             .try_into()
             .unwrap();
 
-        Some(SlotEntry::new(
+        Some(SlotEntry::decode(
             u16::from_le_bytes(offset_bytes),
             u16::from_le_bytes(length_bytes),
         ))
@@ -191,7 +308,7 @@ impl<'a> SlottedPage<'a> {
         let slot_offset = self.slot_array_base() + (slot_num as usize) * SLOT_SIZE;
 
         let offset_bytes = entry.offset.to_le_bytes();
-        let length_bytes = entry.length.to_le_bytes();
+        let length_bytes = entry.raw_length().to_le_bytes();
 
         self.data[slot_offset..slot_offset + 2].copy_from_slice(&offset_bytes);
         self.data[slot_offset + 2..slot_offset + 4].copy_from_slice(&length_bytes);
@@ -256,14 +373,8 @@ impl<'a> SlottedPage<'a> {
             .get_slot(slot_id)
             .ok_or(CrioError::InvalidSlotId(slot_id.as_u16()))?;
 
-        if entry.is_empty() {
-            return Err(CrioError::EmptySlot(slot_id.as_u16()));
-        }
-
-        let start = entry.offset as usize;
-        let end = start + entry.length as usize;
-
-        Ok(&self.data[start..end])
+        entry.check_live(slot_id)?;
+        Ok(&self.data[entry.tuple_range()])
     }
 
     /// Gets mutable tuple data by slot ID.
@@ -272,14 +383,8 @@ impl<'a> SlottedPage<'a> {
             .get_slot(slot_id)
             .ok_or(CrioError::InvalidSlotId(slot_id.as_u16()))?;
 
-        if entry.is_empty() {
-            return Err(CrioError::EmptySlot(slot_id.as_u16()));
-        }
-
-        let start = entry.offset as usize;
-        let end = start + entry.length as usize;
-
-        Ok(&mut self.data[start..end])
+        entry.check_live(slot_id)?;
+        Ok(&mut self.data[entry.tuple_range()])
     }
 
     /// Deletes a tuple by slot ID.
@@ -295,9 +400,12 @@ impl<'a> SlottedPage<'a> {
         Ok(())
     }
 
-    /// Updates a tuple in place. The new data must fit in the existing slot.
-    pub fn update_tuple(&mut self, slot_id: SlotId, new_data: &[u8]) -> Result<()> {
-        let entry = self
+    /// Logically deletes a tuple by setting its tombstone bit.
+    /// The tuple bytes and the slot stay reserved, so RecordIds held by indexes
+    /// never get silently reused; readers see `TupleDeleted` until the slot is
+    /// reclaimed with `reclaim_tombstones`.
+    pub fn mark_deleted(&mut self, slot_id: SlotId) -> Result<()> {
+        let mut entry = self
             .get_slot(slot_id)
             .ok_or(CrioError::InvalidSlotId(slot_id.as_u16()))?;
 
@@ -305,24 +413,234 @@ impl<'a> SlottedPage<'a> {
             return Err(CrioError::EmptySlot(slot_id.as_u16()));
         }
 
-        if new_data.len() > entry.length as usize {
+        if entry.is_tombstone() {
+            return Err(CrioError::TupleDeleted(slot_id.as_u16()));
+        }
+
+        entry.tombstone = true;
+        self.set_slot(slot_id, entry);
+
+        Ok(())
+    }
+
+    /// Returns true if the slot holds a tombstoned tuple.
+    pub fn is_tombstone(&self, slot_id: SlotId) -> bool {
+        self.get_slot(slot_id)
+            .map(|e| e.is_tombstone())
+            .unwrap_or(false)
+    }
+
+    /// Returns the number of tombstoned tuples awaiting reclamation.
+    pub fn tombstone_count(&self) -> usize {
+        (0..self.num_slots())
+            .filter(|&i| self.is_tombstone(SlotId::new(i)))
+            .count()
+    }
+
+    /// Physically removes all tombstoned tuples, freeing their slots for reuse
+    /// and compacting the page. Returns the number of tuples reclaimed.
+    ///
+    /// The caller is responsible for ensuring no reader still needs the
+    /// tombstoned versions (and that index entries pointing at them are gone).
+    pub fn reclaim_tombstones(&mut self) -> usize {
+        let mut reclaimed = 0;
+        for i in 0..self.num_slots() {
+            let slot_id = SlotId::new(i);
+            if self.is_tombstone(slot_id) {
+                self.set_slot(slot_id, SlotEntry::empty());
+                reclaimed += 1;
+            }
+        }
+
+        if reclaimed > 0 {
+            self.compact();
+        }
+
+        reclaimed
+    }
+
+    /// Updates a tuple in place. The new data must fit in the existing slot.
+    pub fn update_tuple(&mut self, slot_id: SlotId, new_data: &[u8]) -> Result<()> {
+        let entry = self
+            .get_slot(slot_id)
+            .ok_or(CrioError::InvalidSlotId(slot_id.as_u16()))?;
+        entry.check_live(slot_id)?;
+
+        let range = entry.tuple_range();
+        if new_data.len() > range.len() {
             return Err(CrioError::PageOverflow {
                 tuple_size: new_data.len(),
-                available: entry.length as usize,
+                available: range.len(),
             });
         }
 
-        let start = entry.offset as usize;
-        self.data[start..start + new_data.len()].copy_from_slice(new_data);
+        self.data[range.start..range.start + new_data.len()].copy_from_slice(new_data);
 
         // Update slot length if smaller
-        if new_data.len() < entry.length as usize {
-            self.set_slot(slot_id, SlotEntry::new(entry.offset, new_data.len() as u16));
+        if new_data.len() < range.len() {
+            let shrink = (range.len() - new_data.len()) as u16;
+            self.set_slot(
+                slot_id,
+                SlotEntry {
+                    length: entry.length - shrink,
+                    ..entry
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Overwrites a tuple, moving it within the page (compacting first if
+    /// needed) when it outgrows its slot. Fails with `PageOverflow`, leaving
+    /// the tuple unchanged, if the page cannot hold it.
+    pub fn replace_tuple(&mut self, slot_id: SlotId, new_data: &[u8]) -> Result<()> {
+        let entry = self
+            .get_slot(slot_id)
+            .ok_or(CrioError::InvalidSlotId(slot_id.as_u16()))?;
+        entry.check_live(slot_id)?;
+
+        if new_data.len() <= entry.tuple_range().len() {
+            return self.update_tuple(slot_id, new_data);
+        }
+
+        let mut bytes = Vec::with_capacity(RECORD_ID_SIZE + new_data.len());
+        if entry.is_moved() {
+            let start = entry.offset as usize;
+            bytes.extend_from_slice(&self.data[start..start + RECORD_ID_SIZE]);
+        }
+        bytes.extend_from_slice(new_data);
+        self.rewrite_slot(slot_id, &bytes, entry)
+    }
+
+    /// Inserts a tuple that moved here from `home`, recording `home` ahead of
+    /// its bytes.
+    pub fn insert_moved_tuple(&mut self, home: RecordId, tuple: &[u8]) -> Result<SlotId> {
+        let mut bytes = Vec::with_capacity(RECORD_ID_SIZE + tuple.len());
+        bytes.extend_from_slice(&encode_record_id(home));
+        bytes.extend_from_slice(tuple);
+
+        let slot_id = self.insert_tuple(&bytes)?;
+        let entry = self.get_slot(slot_id).unwrap();
+        self.set_slot(
+            slot_id,
+            SlotEntry {
+                moved: true,
+                ..entry
+            },
+        );
+        Ok(slot_id)
+    }
+
+    /// Replaces the tuple in a slot (or an existing stub) with a forwarding
+    /// stub pointing at `target`. Only a tuple's home slot can forward, so
+    /// moved tuples are refused.
+    pub fn forward_tuple(&mut self, slot_id: SlotId, target: RecordId) -> Result<()> {
+        let entry = self
+            .get_slot(slot_id)
+            .ok_or(CrioError::InvalidSlotId(slot_id.as_u16()))?;
+        if entry.is_empty() {
+            return Err(CrioError::EmptySlot(slot_id.as_u16()));
+        }
+        if entry.is_tombstone() {
+            return Err(CrioError::TupleDeleted(slot_id.as_u16()));
+        }
+        if entry.is_moved() {
+            return Err(CrioError::InvalidSlotId(slot_id.as_u16()));
+        }
+
+        let stub = SlotEntry {
+            forward: true,
+            ..SlotEntry::empty()
+        };
+        self.rewrite_slot(slot_id, &encode_record_id(target), stub)
+    }
+
+    /// Puts a tuple back into the slot of a forwarding stub, making it a
+    /// regular tuple again. Fails with `PageOverflow`, leaving the stub, if the
+    /// page cannot hold it.
+    pub fn restore_tuple(&mut self, slot_id: SlotId, tuple: &[u8]) -> Result<()> {
+        if self.forward_target(slot_id).is_none() {
+            return Err(CrioError::InvalidSlotId(slot_id.as_u16()));
+        }
+        self.rewrite_slot(slot_id, tuple, SlotEntry::empty())
+    }
+
+    /// Returns where the tuple of a forwarding stub lives now. Tombstoned
+    /// stubs and other slots return None.
+    pub fn forward_target(&self, slot_id: SlotId) -> Option<RecordId> {
+        let entry = self.get_slot(slot_id)?;
+        (entry.is_forward() && !entry.is_tombstone()).then(|| {
+            let start = entry.offset as usize;
+            decode_record_id(&self.data[start..start + RECORD_ID_SIZE])
+        })
+    }
+
+    /// Returns the home RecordId of a tuple that moved here from another page.
+    pub fn moved_from(&self, slot_id: SlotId) -> Option<RecordId> {
+        let entry = self.get_slot(slot_id)?;
+        entry.is_moved().then(|| {
+            let start = entry.offset as usize;
+            decode_record_id(&self.data[start..start + RECORD_ID_SIZE])
+        })
+    }
+
+    /// Stores `bytes` as a slot's data, with the flags of `flags`: in place if
+    /// they fit, otherwise in free space, compacting the page first if needed.
+    /// On `PageOverflow` the slot is left as it was.
+    fn rewrite_slot(&mut self, slot_id: SlotId, bytes: &[u8], flags: SlotEntry) -> Result<()> {
+        let current = self.get_slot(slot_id).unwrap();
+        if bytes.len() <= current.length as usize {
+            let start = current.offset as usize;
+            self.data[start..start + bytes.len()].copy_from_slice(bytes);
+            self.set_slot(
+                slot_id,
+                SlotEntry {
+                    offset: current.offset,
+                    length: bytes.len() as u16,
+                    ..flags
+                },
+            );
+            return Ok(());
         }
 
+        if self.free_space() < bytes.len() {
+            // Free the old bytes, along with any other dead space
+            let start = current.offset as usize;
+            let old = self.data[start..start + current.length as usize].to_vec();
+            self.set_slot(slot_id, SlotEntry::empty());
+            self.compact();
+
+            if self.free_space() < bytes.len() {
+                let available = self.free_space();
+                // Compaction freed at least the old bytes, so they fit again
+                self.place_slot(slot_id, &old, current);
+                return Err(CrioError::PageOverflow {
+                    tuple_size: bytes.len(),
+                    available,
+                });
+            }
+        }
+
+        self.place_slot(slot_id, bytes, flags);
         Ok(())
     }
 
+    /// Writes a slot's bytes at the end of free space.
+    fn place_slot(&mut self, slot_id: SlotId, bytes: &[u8], flags: SlotEntry) {
+        let offset = self.free_space_end() - bytes.len() as u16;
+        self.data[offset as usize..offset as usize + bytes.len()].copy_from_slice(bytes);
+        self.set_slot(
+            slot_id,
+            SlotEntry {
+                offset,
+                length: bytes.len() as u16,
+                ..flags
+            },
+        );
+        self.set_free_space_end(offset);
+    }
+
     /// Compacts the page, reclaiming space from deleted tuples.
     /// This is an expensive operation and should be done sparingly.
     pub fn compact(&mut self) {
@@ -331,12 +649,14 @@ impl<'a> SlottedPage<'a> {
             return;
         }
 
-        // Collect non-empty tuples with their slot IDs
-        let mut tuples: Vec<(SlotId, Vec<u8>)> = Vec::new();
+        // Collect non-empty tuples (including tombstones) with their slot entries
+        let mut tuples: Vec<(SlotId, SlotEntry, Vec<u8>)> = Vec::new();
         for i in 0..num_slots {
             let slot_id = SlotId::new(i);
-            if let Ok(tuple) = self.get_tuple(slot_id) {
-                tuples.push((slot_id, tuple.to_vec()));
+            if let Some(entry) = self.get_slot(slot_id).filter(|e| !e.is_empty()) {
+                let start = entry.offset as usize;
+                let end = start + entry.length as usize;
+                tuples.push((slot_id, entry, self.data[start..end].to_vec()));
             }
         }
 
@@ -349,30 +669,36 @@ impl<'a> SlottedPage<'a> {
         }
 
         // Reinsert all tuples in order
-        for (slot_id, tuple) in tuples {
+        for (slot_id, entry, tuple) in tuples {
             let tuple_offset = self.free_space_end() - tuple.len() as u16;
 
             self.data[tuple_offset as usize..tuple_offset as usize + tuple.len()]
                 .copy_from_slice(&tuple);
 
-            self.set_slot(slot_id, SlotEntry::new(tuple_offset, tuple.len() as u16));
+            self.set_slot(
+                slot_id,
+                SlotEntry {
+                    offset: tuple_offset,
+                    ..entry
+                },
+            );
 
             self.set_free_space_end(tuple_offset);
         }
     }
 
-    /// Returns an iterator over all non-empty slot IDs.
+    /// Returns an iterator over all live (non-empty, non-tombstoned) slot IDs.
     pub fn slot_ids(&self) -> impl Iterator<Item = SlotId> + '_ {
         let num_slots = self.num_slots();
         (0..num_slots).filter_map(move |i| {
             let slot_id = SlotId::new(i);
             self.get_slot(slot_id)
-                .filter(|e| !e.is_empty())
+                .filter(|e| e.is_live())
                 .map(|_| slot_id)
         })
     }
 
-    /// Returns the number of non-empty tuples.
+    /// Returns the number of live tuples.
     pub fn tuple_count(&self) -> usize {
         self.slot_ids().count()
     }
@@ -424,13 +750,21 @@ impl<'a> SlottedPageRef<'a> {
     }
 
     /// Returns the start of free space.
-    fn free_space_start(&self) -> u16 {
+    pub fn free_space_start(&self) -> u16 {
         let bytes: [u8; 4] = self.data[FREE_SPACE_START_OFFSET..FREE_SPACE_START_OFFSET + 4]
             .try_into()
             .unwrap();
         u32::from_le_bytes(bytes) as u16
     }
 
+    /// Returns the end of free space.
+    pub fn free_space_end(&self) -> u16 {
+        let bytes: [u8; 4] = self.data[FREE_SPACE_END_OFFSET..FREE_SPACE_END_OFFSET + 4]
+            .try_into()
+            .unwrap();
+        u32::from_le_bytes(bytes) as u16
+    }
+
     /// Computes the base offset where slot array starts.
     fn slot_array_base(&self) -> usize {
         let num_slots = self.num_slots() as usize;
@@ -450,7 +784,7 @@ impl<'a> SlottedPageRef<'a> {
             .try_into()
             .unwrap();
 
-        Some(SlotEntry::new(
+        Some(SlotEntry::decode(
             u16::from_le_bytes(offset_bytes),
             u16::from_le_bytes(length_bytes),
         ))
@@ -462,26 +796,131 @@ impl<'a> SlottedPageRef<'a> {
             .get_slot(slot_id)
             .ok_or(CrioError::InvalidSlotId(slot_id.as_u16()))?;
 
-        if entry.is_empty() {
-            return Err(CrioError::EmptySlot(slot_id.as_u16()));
+        entry.check_live(slot_id)?;
+        Ok(&self.data[entry.tuple_range()])
+    }
+
+    /// Returns true if the slot holds a tombstoned tuple.
+    pub fn is_tombstone(&self, slot_id: SlotId) -> bool {
+        self.get_slot(slot_id)
+            .map(|e| e.is_tombstone())
+            .unwrap_or(false)
+    }
+
+    /// Returns the number of tombstoned tuples awaiting reclamation.
+    pub fn tombstone_count(&self) -> usize {
+        (0..self.num_slots())
+            .filter(|&i| self.is_tombstone(SlotId::new(i)))
+            .count()
+    }
+
+    /// Returns where the tuple of a forwarding stub lives now; see
+    /// `SlottedPage::forward_target`.
+    pub fn forward_target(&self, slot_id: SlotId) -> Option<RecordId> {
+        let entry = self.get_slot(slot_id)?;
+        (entry.is_forward() && !entry.is_tombstone()).then(|| {
+            let start = entry.offset as usize;
+            decode_record_id(&self.data[start..start + RECORD_ID_SIZE])
+        })
+    }
+
+    /// Returns the home RecordId of a tuple that moved here from another page.
+    pub fn moved_from(&self, slot_id: SlotId) -> Option<RecordId> {
+        let entry = self.get_slot(slot_id)?;
+        entry.is_moved().then(|| {
+            let start = entry.offset as usize;
+            decode_record_id(&self.data[start..start + RECORD_ID_SIZE])
+        })
+    }
+
+    /// Returns the slots holding forwarding stubs that are not tombstoned.
+    pub fn forwarded_slots(&self) -> impl Iterator<Item = SlotId> + '_ {
+        (0..self.num_slots())
+            .map(SlotId::new)
+            .filter(move |&slot_id| self.forward_target(slot_id).is_some())
+    }
+
+    /// Checks the page layout and returns a description of every violation:
+    /// free space pointers out of order, a slot array that does not start right
+    /// after the header, tuples outside the data region, or overlapping tuples.
+    pub fn check_layout(&self) -> Vec<String> {
+        self.check_layout_with_header(HEADER_SIZE)
+    }
+
+    /// `check_layout` for page formats that extend the slotted page header.
+    pub(crate) fn check_layout_with_header(&self, header_size: usize) -> Vec<String> {
+        let mut violations = Vec::new();
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes(self.data[offset..offset + 4].try_into().unwrap()) as usize
+        };
+        let num_slots = read_u32(NUM_SLOTS_OFFSET);
+        let start = read_u32(FREE_SPACE_START_OFFSET);
+        let end = read_u32(FREE_SPACE_END_OFFSET);
+
+        if start > end || end > PAGE_SIZE {
+            violations.push(format!(
+                "free space [{}, {}) is out of order or past the page end",
+                start, end
+            ));
+            return violations;
+        }
+        if num_slots > u16::MAX as usize || start != header_size + num_slots * SLOT_SIZE {
+            violations.push(format!(
+                "{} slots do not fit between the {}-byte header and free space start {}",
+                num_slots, header_size, start
+            ));
+            return violations;
+        }
+
+        let mut tuples = Vec::new();
+        for i in 0..num_slots as u16 {
+            let Some(entry) = self.get_slot(SlotId::new(i)) else {
+                continue;
+            };
+            if entry.is_empty() {
+                continue;
+            }
+            let tuple_start = entry.offset as usize;
+            let tuple_end = tuple_start + entry.length as usize;
+            if tuple_start < end || tuple_end > PAGE_SIZE {
+                violations.push(format!(
+                    "slot {} tuple [{}, {}) lies outside the data region [{}, {})",
+                    i, tuple_start, tuple_end, end, PAGE_SIZE
+                ));
+            } else {
+                tuples.push((tuple_start, tuple_end, i));
+            }
         }
 
-        let start = entry.offset as usize;
-        let end = start + entry.length as usize;
+        tuples.sort_unstable();
+        for pair in tuples.windows(2) {
+            let (_, prev_end, prev_slot) = pair[0];
+            let (next_start, _, next_slot) = pair[1];
+            if prev_end > next_start {
+                violations.push(format!(
+                    "slot {} tuple overlaps slot {} tuple",
+                    prev_slot, next_slot
+                ));
+            }
+        }
 
-        Ok(&self.data[start..end])
+        violations
     }
 
-    /// Returns the number of non-empty tuples.
-    pub fn tuple_count(&self) -> usize {
+    /// Returns an iterator over slot IDs of live tuples.
+    pub fn slot_ids(&self) -> impl Iterator<Item = SlotId> + '_ {
         let num_slots = self.num_slots();
-        (0..num_slots)
-            .filter(|&i| {
-                self.get_slot(SlotId::new(i))
-                    .map(|e| !e.is_empty())
-                    .unwrap_or(false)
-            })
-            .count()
+        (0..num_slots).filter_map(move |i| {
+            let slot_id = SlotId::new(i);
+            self.get_slot(slot_id)
+                .filter(|e| e.is_live())
+                .map(|_| slot_id)
+        })
+    }
+
+    /// Returns the number of live tuples.
+    pub fn tuple_count(&self) -> usize {
+        self.slot_ids().count()
     }
 }
 
@@ -489,6 +928,29 @@ impl<'a> SlottedPageRef<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_slotted_page_check_layout() {
+        let mut data = [0u8; PAGE_SIZE];
+        let mut page = SlottedPage::new(&mut data);
+        page.init(PageId::new(1));
+        page.insert_tuple(b"hello").unwrap();
+        page.insert_tuple(b"world").unwrap();
+        assert!(SlottedPageRef::new(&data).check_layout().is_empty());
+
+        // Point slot 1 into slot 0's tuple
+        let mut page = SlottedPage::new(&mut data);
+        let slot0 = page.get_slot(SlotId::new(0)).unwrap();
+        page.set_slot(SlotId::new(1), SlotEntry::new(slot0.offset - 2, 5));
+        let violations = SlottedPageRef::new(&data).check_layout();
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].contains("overlaps"));
+
+        // A slot array that runs past free space start is reported alone
+        let mut page = SlottedPage::new(&mut data);
+        page.set_num_slots(100);
+        assert_eq!(SlottedPageRef::new(&data).check_layout().len(), 1);
+    }
+
     #[test]
     fn test_slotted_page_init() {
         let mut data = [0u8; PAGE_SIZE];
@@ -640,6 +1102,64 @@ mod tests {
         assert!(page.get_tuple(slot_id2).is_err());
     }
 
+    #[test]
+    fn test_slotted_page_tombstone() {
+        let mut data = [0u8; PAGE_SIZE];
+        let mut page = SlottedPage::new(&mut data);
+        page.init(PageId::new(1));
+
+        let slot_id1 = page.insert_tuple(b"First").unwrap();
+        let slot_id2 = page.insert_tuple(b"Second").unwrap();
+
+        page.mark_deleted(slot_id1).unwrap();
+
+        assert!(page.is_tombstone(slot_id1));
+        assert!(matches!(
+            page.get_tuple(slot_id1),
+            Err(CrioError::TupleDeleted(0))
+        ));
+        assert!(page.mark_deleted(slot_id1).is_err());
+        assert!(page.update_tuple(slot_id1, b"x").is_err());
+        assert_eq!(page.tuple_count(), 1);
+        assert_eq!(page.tombstone_count(), 1);
+        assert_eq!(page.slot_ids().collect::<Vec<_>>(), vec![slot_id2]);
+
+        // Tombstoned slot must not be reused before reclamation
+        let slot_id3 = page.insert_tuple(b"Third").unwrap();
+        assert_eq!(slot_id3, SlotId::new(2));
+
+        // Compaction keeps tombstones intact
+        page.compact();
+        assert!(page.is_tombstone(slot_id1));
+        assert_eq!(page.get_tuple(slot_id2).unwrap(), b"Second");
+    }
+
+    #[test]
+    fn test_slotted_page_reclaim_tombstones() {
+        let mut data = [0u8; PAGE_SIZE];
+        let mut page = SlottedPage::new(&mut data);
+        page.init(PageId::new(1));
+
+        let slot_id1 = page.insert_tuple(b"First").unwrap();
+        let slot_id2 = page.insert_tuple(b"Second").unwrap();
+        let free_before = page.free_space();
+
+        page.mark_deleted(slot_id1).unwrap();
+        assert_eq!(page.reclaim_tombstones(), 1);
+
+        assert!(page.free_space() > free_before);
+        assert_eq!(page.tombstone_count(), 0);
+        assert!(matches!(
+            page.get_tuple(slot_id1),
+            Err(CrioError::EmptySlot(0))
+        ));
+        assert_eq!(page.get_tuple(slot_id2).unwrap(), b"Second");
+
+        // Reclaimed slot is reusable
+        assert_eq!(page.insert_tuple(b"Again").unwrap(), slot_id1);
+        assert_eq!(page.reclaim_tombstones(), 0);
+    }
+
     #[test]
     fn test_slotted_page_ref() {
         let mut data = [0u8; PAGE_SIZE];
@@ -654,4 +1174,69 @@ mod tests {
         assert_eq!(page_ref.tuple_count(), 1);
         assert_eq!(page_ref.get_tuple(SlotId::new(0)).unwrap(), b"Test");
     }
+
+    #[test]
+    fn test_slotted_page_replace_grows() {
+        let mut data = [0u8; PAGE_SIZE];
+        let mut page = SlottedPage::new(&mut data);
+        page.init(PageId::new(1));
+
+        let slot = page.insert_tuple(b"short").unwrap();
+        let other = page.insert_tuple(&[7u8; 2000]).unwrap();
+        page.replace_tuple(slot, b"a good deal longer").unwrap();
+        assert_eq!(page.get_tuple(slot).unwrap(), b"a good deal longer");
+
+        // Growing past the page leaves the tuple as it was
+        page.delete_tuple(other).unwrap();
+        assert!(matches!(
+            page.replace_tuple(slot, &[1u8; PAGE_SIZE]),
+            Err(CrioError::PageOverflow { .. })
+        ));
+        assert_eq!(page.get_tuple(slot).unwrap(), b"a good deal longer");
+
+        // Compaction makes room taken by deleted tuples
+        page.replace_tuple(slot, &[2u8; 3000]).unwrap();
+        assert_eq!(page.get_tuple(slot).unwrap(), &[2u8; 3000][..]);
+        assert!(SlottedPageRef::new(page.data).check_layout().is_empty());
+    }
+
+    #[test]
+    fn test_slotted_page_forwarding() {
+        let mut data = [0u8; PAGE_SIZE];
+        let mut page = SlottedPage::new(&mut data);
+        page.init(PageId::new(1));
+
+        let home = RecordId::new(PageId::new(9), SlotId::new(3));
+        let target = RecordId::new(PageId::new(2), SlotId::new(5));
+
+        let slot = page.insert_tuple(b"tuple").unwrap();
+        page.forward_tuple(slot, target).unwrap();
+        assert_eq!(page.forward_target(slot), Some(target));
+        assert_eq!(page.tuple_count(), 0);
+        assert!(matches!(
+            page.get_tuple(slot),
+            Err(CrioError::TupleForwarded(0))
+        ));
+
+        page.restore_tuple(slot, b"back home").unwrap();
+        assert_eq!(page.forward_target(slot), None);
+        assert_eq!(page.get_tuple(slot).unwrap(), b"back home");
+
+        let moved = page.insert_moved_tuple(home, b"moved in").unwrap();
+        assert_eq!(page.moved_from(moved), Some(home));
+        assert_eq!(page.get_tuple(moved).unwrap(), b"moved in");
+        page.replace_tuple(moved, b"moved in, then grown").unwrap();
+        assert_eq!(page.moved_from(moved), Some(home));
+        assert_eq!(page.get_tuple(moved).unwrap(), b"moved in, then grown");
+        // Only a home slot can forward
+        assert!(page.forward_tuple(moved, target).is_err());
+
+        page.forward_tuple(slot, target).unwrap();
+        page.mark_deleted(slot).unwrap();
+        assert_eq!(page.forward_target(slot), None);
+
+        let page_ref = SlottedPageRef::new(page.data);
+        assert!(page_ref.check_layout().is_empty());
+        assert_eq!(page_ref.forwarded_slots().count(), 0);
+    }
 }
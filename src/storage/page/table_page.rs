@@ -143,11 +143,62 @@ impl<'a> TablePage<'a> {
         self.inner.delete_tuple(slot_id)
     }
 
+    /// Logically deletes a tuple, leaving a tombstone until it is reclaimed.
+    pub fn mark_deleted(&mut self, slot_id: SlotId) -> Result<()> {
+        self.inner.mark_deleted(slot_id)
+    }
+
+    /// Returns true if the slot holds a tombstoned tuple.
+    pub fn is_tombstone(&self, slot_id: SlotId) -> bool {
+        self.inner.is_tombstone(slot_id)
+    }
+
+    /// Returns the number of tombstoned tuples awaiting reclamation.
+    pub fn tombstone_count(&self) -> usize {
+        self.inner.tombstone_count()
+    }
+
+    /// Physically removes tombstoned tuples. Returns the number reclaimed.
+    pub fn reclaim_tombstones(&mut self) -> usize {
+        self.inner.reclaim_tombstones()
+    }
+
     /// Updates a tuple in place.
     pub fn update_tuple(&mut self, slot_id: SlotId, new_data: &[u8]) -> Result<()> {
         self.inner.update_tuple(slot_id, new_data)
     }
 
+    /// Overwrites a tuple, moving it within the page if it grows.
+    pub fn replace_tuple(&mut self, slot_id: SlotId, new_data: &[u8]) -> Result<()> {
+        self.inner.replace_tuple(slot_id, new_data)
+    }
+
+    /// Inserts a tuple that moved here from `home` and returns its record ID.
+    pub fn insert_moved_tuple(&mut self, home: RecordId, tuple: &[u8]) -> Result<RecordId> {
+        let slot_id = self.inner.insert_moved_tuple(home, tuple)?;
+        Ok(RecordId::new(self.page_id(), slot_id))
+    }
+
+    /// Turns a tuple's home slot into a forwarding stub pointing at `target`.
+    pub fn forward_tuple(&mut self, slot_id: SlotId, target: RecordId) -> Result<()> {
+        self.inner.forward_tuple(slot_id, target)
+    }
+
+    /// Puts a forwarded tuple back into its home slot.
+    pub fn restore_tuple(&mut self, slot_id: SlotId, tuple: &[u8]) -> Result<()> {
+        self.inner.restore_tuple(slot_id, tuple)
+    }
+
+    /// Returns where the tuple of a forwarding stub lives now.
+    pub fn forward_target(&self, slot_id: SlotId) -> Option<RecordId> {
+        self.inner.forward_target(slot_id)
+    }
+
+    /// Returns the home record ID of a tuple moved in from another page.
+    pub fn moved_from(&self, slot_id: SlotId) -> Option<RecordId> {
+        self.inner.moved_from(slot_id)
+    }
+
     /// Returns whether there's enough space to insert a tuple.
     pub fn can_insert(&self, tuple_size: usize) -> bool {
         self.inner.can_insert(tuple_size)
@@ -158,7 +209,7 @@ impl<'a> TablePage<'a> {
         self.inner.free_space()
     }
 
-    /// Returns the number of non-empty tuples.
+    /// Returns the number of live tuples.
     pub fn tuple_count(&self) -> usize {
         self.inner.tuple_count()
     }
@@ -208,6 +259,27 @@ impl<'a> TablePageRef<'a> {
         }
     }
 
+    /// Returns the previous page ID.
+    pub fn prev_page_id(&self) -> Option<PageId> {
+        let bytes: [u8; 4] = self.inner.data[PREV_PAGE_ID_OFFSET..PREV_PAGE_ID_OFFSET + 4]
+            .try_into()
+            .unwrap();
+        let value = u32::from_le_bytes(bytes);
+        if value == INVALID_PAGE {
+            None
+        } else {
+            Some(PageId::new(value))
+        }
+    }
+
+    /// Returns the LSN (Log Sequence Number).
+    pub fn lsn(&self) -> Lsn {
+        let bytes: [u8; 8] = self.inner.data[LSN_OFFSET..LSN_OFFSET + 8]
+            .try_into()
+            .unwrap();
+        u64::from_le_bytes(bytes)
+    }
+
     /// Returns the table ID.
     pub fn table_id(&self) -> u32 {
         let bytes: [u8; 4] = self.inner.data[TABLE_ID_OFFSET..TABLE_ID_OFFSET + 4]
@@ -221,10 +293,59 @@ impl<'a> TablePageRef<'a> {
         self.inner.get_tuple(slot_id)
     }
 
-    /// Returns the number of non-empty tuples.
+    /// Returns true if the slot holds a tombstoned tuple.
+    pub fn is_tombstone(&self, slot_id: SlotId) -> bool {
+        self.inner.is_tombstone(slot_id)
+    }
+
+    /// Returns the number of tombstoned tuples awaiting reclamation.
+    pub fn tombstone_count(&self) -> usize {
+        self.inner.tombstone_count()
+    }
+
+    /// Returns where the tuple of a forwarding stub lives now.
+    pub fn forward_target(&self, slot_id: SlotId) -> Option<RecordId> {
+        self.inner.forward_target(slot_id)
+    }
+
+    /// Returns the home record ID of a tuple moved in from another page.
+    pub fn moved_from(&self, slot_id: SlotId) -> Option<RecordId> {
+        self.inner.moved_from(slot_id)
+    }
+
+    /// Returns the record IDs of the forwarding stubs in this page along with
+    /// where each tuple lives now.
+    pub fn forwards(&self) -> impl Iterator<Item = (RecordId, RecordId)> + '_ {
+        let page_id = self.page_id();
+        self.inner.forwarded_slots().filter_map(move |slot_id| {
+            let target = self.inner.forward_target(slot_id)?;
+            Some((RecordId::new(page_id, slot_id), target))
+        })
+    }
+
+    /// Returns the amount of free space.
+    pub fn free_space(&self) -> usize {
+        self.inner.free_space()
+    }
+
+    /// Returns the number of live tuples.
     pub fn tuple_count(&self) -> usize {
         self.inner.tuple_count()
     }
+
+    /// Returns an iterator over the record IDs of live tuples in this page.
+    pub fn record_ids(&self) -> impl Iterator<Item = RecordId> + '_ {
+        let page_id = self.page_id();
+        self.inner
+            .slot_ids()
+            .map(move |slot_id| RecordId::new(page_id, slot_id))
+    }
+
+    /// Checks the slotted layout below the table header; see
+    /// `SlottedPageRef::check_layout`.
+    pub fn check_layout(&self) -> Vec<String> {
+        self.inner.check_layout_with_header(TABLE_HEADER_SIZE)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,7 @@
+mod table_heap;
+mod table_iterator;
+mod temp_table;
+
+pub use table_heap::*;
+pub use table_iterator::*;
+pub use temp_table::*;
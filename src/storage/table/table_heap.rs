@@ -0,0 +1,1031 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::buffer::{BufferPoolManager, ReadPageGuard, ScanContext, WritePageGuard};
+use crate::common::{CrioError, JobProgress, PageId, RecordId, Result, PAGE_SIZE};
+use crate::storage::page::{TablePage, TablePageRef, RECORD_ID_SIZE};
+
+use super::TableIterator;
+
+/// Number of stripes in the record lock table.
+const RECORD_LOCK_STRIPES: usize = 64;
+
+/// Share of each page `bulk_insert` fills by default, leaving room for
+/// updates to grow tuples in place.
+pub const DEFAULT_FILL_FACTOR: f32 = 0.9;
+
+/// TableHeap stores the tuples of one table in a doubly linked chain of
+/// TablePages. Tuples are appended to the last page; a new page is linked in
+/// when it fills up. Deletes leave tombstones (see `TablePage::mark_deleted`).
+///
+/// A tuple that grows too large for its page moves to another page and leaves
+/// a forwarding stub in its home slot, so its RecordId stays valid. Reads
+/// follow the stub; `collapse_forwards` moves tuples home once they fit.
+///
+/// The heap counts its live tuples and pages as it goes, starting from the
+/// per-page tuple counts read while `open` walks the chain, so
+/// `approx_row_count` and `approx_size_bytes` answer without a scan.
+pub struct TableHeap {
+    /// Buffer pool holding the table's pages
+    bpm: Arc<BufferPoolManager>,
+    /// Table ID stamped on every page of the chain
+    table_id: u32,
+    /// Head of the page chain
+    first_page_id: PageId,
+    /// Tail of the page chain; the lock serializes appends
+    last_page_id: Mutex<PageId>,
+    /// Live tuples, counted by inserts and deletes
+    row_count: AtomicU64,
+    /// Pages in the chain
+    page_count: AtomicU64,
+    /// Striped locks serializing updates and deletes of the same record, so a
+    /// tuple is moved by one writer at a time
+    record_locks: Box<[Mutex<()>]>,
+}
+
+impl TableHeap {
+    /// Creates an empty heap with a single page.
+    pub fn create(bpm: Arc<BufferPoolManager>, table_id: u32) -> Result<Self> {
+        let first_page_id = bpm.new_page()?;
+        {
+            let mut guard = bpm
+                .checked_write_page(first_page_id)?
+                .ok_or(CrioError::PageNotFound(first_page_id))?;
+            let mut page = TablePage::new(guard.data_mut());
+            page.init(first_page_id, table_id);
+        }
+
+        Ok(Self {
+            bpm,
+            table_id,
+            first_page_id,
+            last_page_id: Mutex::new(first_page_id),
+            row_count: AtomicU64::new(0),
+            page_count: AtomicU64::new(1),
+            record_locks: new_record_locks(),
+        })
+    }
+
+    /// Opens an existing heap whose chain starts at `first_page_id`.
+    pub fn open(bpm: Arc<BufferPoolManager>, table_id: u32, first_page_id: PageId) -> Result<Self> {
+        let mut last_page_id = first_page_id;
+        let (mut row_count, mut page_count) = (0, 0);
+        loop {
+            let guard = bpm
+                .checked_read_page(last_page_id)?
+                .ok_or(CrioError::PageNotFound(last_page_id))?;
+            let page = TablePageRef::new(guard.data());
+            if page.table_id() != table_id {
+                return Err(CrioError::InvalidPageId(last_page_id));
+            }
+            row_count += page.tuple_count() as u64;
+            page_count += 1;
+            match page.next_page_id() {
+                Some(next) => last_page_id = next,
+                None => break,
+            }
+        }
+
+        Ok(Self {
+            bpm,
+            table_id,
+            first_page_id,
+            last_page_id: Mutex::new(last_page_id),
+            row_count: AtomicU64::new(row_count),
+            page_count: AtomicU64::new(page_count),
+            record_locks: new_record_locks(),
+        })
+    }
+
+    /// Returns the table ID.
+    pub fn table_id(&self) -> u32 {
+        self.table_id
+    }
+
+    /// Returns the first page of the chain.
+    pub fn first_page_id(&self) -> PageId {
+        self.first_page_id
+    }
+
+    /// Returns the number of live tuples. Kept in memory and updated without
+    /// locking, so it may be briefly off while writers are running.
+    pub fn approx_row_count(&self) -> u64 {
+        self.row_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the space taken by the heap's pages, live or not.
+    pub fn approx_size_bytes(&self) -> u64 {
+        self.page_count.load(Ordering::Relaxed) * PAGE_SIZE as u64
+    }
+
+    /// Appends a tuple and returns its record ID.
+    pub fn insert_tuple(&self, data: &[u8]) -> Result<RecordId> {
+        let record_id = self.insert_with(data.len(), |page| page.insert_tuple(data))?;
+        self.row_count.fetch_add(1, Ordering::Relaxed);
+        Ok(record_id)
+    }
+
+    /// Appends `size` bytes to the tail page, or to a new page if the tail is
+    /// full, using `insert` to write them.
+    fn insert_with(
+        &self,
+        size: usize,
+        insert: impl Fn(&mut TablePage) -> Result<RecordId>,
+    ) -> Result<RecordId> {
+        let mut last_page_id = self.last_page_id.lock();
+
+        {
+            let mut guard = self
+                .bpm
+                .checked_write_page(*last_page_id)?
+                .ok_or(CrioError::PageNotFound(*last_page_id))?;
+            let mut page = TablePage::new(guard.data_mut());
+            if page.can_insert(size) {
+                return insert(&mut page);
+            }
+            // Only looked: a full tail is left clean
+            guard.mark_clean();
+        }
+
+        // The tail is full: insert into a fresh page before linking it in, so a
+        // tuple too large for any page leaves the chain untouched
+        let new_page_id = self.bpm.new_page()?;
+        let insert_result = {
+            let mut guard = self
+                .bpm
+                .checked_write_page(new_page_id)?
+                .ok_or(CrioError::PageNotFound(new_page_id))?;
+            let mut page = TablePage::new(guard.data_mut());
+            page.init(new_page_id, self.table_id);
+            page.set_prev_page_id(Some(*last_page_id));
+            insert(&mut page)
+        };
+        let record_id = match insert_result {
+            Ok(record_id) => record_id,
+            Err(e) => {
+                let _ = self.bpm.delete_page(new_page_id);
+                return Err(e);
+            }
+        };
+
+        {
+            let mut guard = self
+                .bpm
+                .checked_write_page(*last_page_id)?
+                .ok_or(CrioError::PageNotFound(*last_page_id))?;
+            let mut page = TablePage::new(guard.data_mut());
+            page.set_next_page_id(Some(new_page_id));
+        }
+        *last_page_id = new_page_id;
+        self.page_count.fetch_add(1, Ordering::Relaxed);
+
+        Ok(record_id)
+    }
+
+    /// Appends many tuples at once and returns their record IDs in input order.
+    ///
+    /// Pages are built in memory and filled to `fill_factor` (in (0, 1]) of
+    /// their size; a tuple that does not fit that share of an empty page gets
+    /// the page to itself. Pages come from whole extents allocated up front
+    /// and each extent is written in one vectored batch, bypassing the buffer
+    /// pool. The new pages are linked into the chain only once all of them
+    /// are on disk, so readers never see a partial load; on error they are
+    /// freed and the heap is unchanged.
+    pub fn bulk_insert<T: AsRef<[u8]>>(
+        &self,
+        tuples: impl IntoIterator<Item = T>,
+        fill_factor: f32,
+    ) -> Result<Vec<RecordId>> {
+        self.bulk_insert_with_progress(tuples, fill_factor, &JobProgress::new())
+    }
+
+    /// Does the work of `bulk_insert`, counting each page filled in
+    /// `progress`. A cancel is noticed before each new page and fails the
+    /// load with `Cancelled`, leaving the heap unchanged.
+    pub fn bulk_insert_with_progress<T: AsRef<[u8]>>(
+        &self,
+        tuples: impl IntoIterator<Item = T>,
+        fill_factor: f32,
+        progress: &JobProgress,
+    ) -> Result<Vec<RecordId>> {
+        if !(fill_factor > 0.0 && fill_factor <= 1.0) {
+            return Err(CrioError::InvalidFillFactor(fill_factor));
+        }
+
+        let mut last_page_id = self.last_page_id.lock();
+        let mut load = BulkLoad {
+            heap: self,
+            progress,
+            reserve: ((1.0 - fill_factor) * PAGE_SIZE as f32) as usize,
+            allocated: Vec::new(),
+            spare: VecDeque::new(),
+            filled: Vec::new(),
+            current: None,
+            prev_page_id: *last_page_id,
+        };
+
+        let loaded = tuples
+            .into_iter()
+            .map(|tuple| load.insert(tuple.as_ref()))
+            .collect::<Result<Vec<_>>>()
+            .and_then(|record_ids| Ok((record_ids, load.finish()?)));
+        let (record_ids, first_page_id) = match loaded {
+            Ok((record_ids, Some(first_page_id))) => (record_ids, first_page_id),
+            Ok((record_ids, None)) => return Ok(record_ids),
+            Err(e) => {
+                for &page_id in &load.allocated {
+                    let _ = self.bpm.delete_page(page_id);
+                }
+                return Err(e);
+            }
+        };
+
+        for &page_id in &load.spare {
+            self.bpm.delete_page(page_id)?;
+        }
+        {
+            let mut guard = self.write_page(*last_page_id)?;
+            let mut page = TablePage::new(guard.data_mut());
+            page.set_next_page_id(Some(first_page_id));
+        }
+        *last_page_id = load.prev_page_id;
+        let pages = load.allocated.len() - load.spare.len();
+        self.page_count.fetch_add(pages as u64, Ordering::Relaxed);
+        self.row_count
+            .fetch_add(record_ids.len() as u64, Ordering::Relaxed);
+
+        Ok(record_ids)
+    }
+
+    /// Returns a copy of the tuple at `record_id`, following its forwarding
+    /// stub if it has moved.
+    pub fn get_tuple(&self, record_id: RecordId) -> Result<Vec<u8>> {
+        let mut last_target = None;
+        loop {
+            let target = {
+                let guard = self.read_page(record_id.page_id)?;
+                let page = TablePageRef::new(guard.data());
+                match page.forward_target(record_id.slot_id) {
+                    Some(target) => target,
+                    None => return Ok(page.get_tuple(record_id.slot_id)?.to_vec()),
+                }
+            };
+
+            let guard = self.read_page(target.page_id)?;
+            let page = TablePageRef::new(guard.data());
+            if page.moved_from(target.slot_id) == Some(record_id) {
+                return Ok(page.get_tuple(target.slot_id)?.to_vec());
+            }
+
+            // The tuple moved again between the two reads; a stub that keeps
+            // pointing at the wrong tuple is broken
+            if last_target == Some(target) {
+                return Err(dangling(record_id));
+            }
+            last_target = Some(target);
+        }
+    }
+
+    /// Returns copies of the tuples at `record_ids`, in the same order, with
+    /// None for deleted ones. Each page is latched once for all of its
+    /// records, so callers resolving many record IDs should batch them here.
+    pub fn get_tuples(&self, record_ids: &[RecordId]) -> Result<Vec<Option<Vec<u8>>>> {
+        let mut order: Vec<usize> = (0..record_ids.len()).collect();
+        order.sort_by_key(|&i| record_ids[i].page_id.as_u32());
+
+        let mut tuples = vec![None; record_ids.len()];
+        let mut forwarded = Vec::new();
+        for group in order.chunk_by(|&a, &b| record_ids[a].page_id == record_ids[b].page_id) {
+            let guard = self.read_page(record_ids[group[0]].page_id)?;
+            let page = TablePageRef::new(guard.data());
+            for &i in group {
+                let slot_id = record_ids[i].slot_id;
+                if page.forward_target(slot_id).is_some() {
+                    forwarded.push(i);
+                    continue;
+                }
+                tuples[i] = deleted_as_none(page.get_tuple(slot_id).map(<[u8]>::to_vec))?;
+            }
+        }
+
+        // Moved tuples live on other pages
+        for i in forwarded {
+            tuples[i] = deleted_as_none(self.get_tuple(record_ids[i]))?;
+        }
+        Ok(tuples)
+    }
+
+    /// Overwrites the tuple at `record_id`. A tuple that no longer fits in its
+    /// page moves to another one behind a forwarding stub.
+    pub fn update_tuple(&self, record_id: RecordId, data: &[u8]) -> Result<()> {
+        let _record = self.record_lock(record_id).lock();
+        self.replace(record_id, data)
+    }
+
+    /// Overwrites the tuple at `record_id` with `data` only if it still holds
+    /// `expected`, failing with `UpdateConflict` otherwise. The check and the
+    /// write happen under the record lock, so of several writers that read
+    /// the same tuple exactly one succeeds.
+    pub fn update_if(&self, record_id: RecordId, expected: &[u8], data: &[u8]) -> Result<()> {
+        let _record = self.record_lock(record_id).lock();
+        if self.get_tuple(record_id)? != expected {
+            return Err(CrioError::UpdateConflict {
+                page_id: record_id.page_id,
+                slot: record_id.slot_id.as_u16(),
+            });
+        }
+        self.replace(record_id, data)
+    }
+
+    /// Does the work of `update_tuple`; the caller holds the record lock.
+    fn replace(&self, record_id: RecordId, data: &[u8]) -> Result<()> {
+        let target = {
+            let mut guard = self.write_page(record_id.page_id)?;
+            match TablePageRef::new(guard.data()).forward_target(record_id.slot_id) {
+                Some(target) => target,
+                None => {
+                    let mut page = TablePage::new(guard.data_mut());
+                    return match page.replace_tuple(record_id.slot_id, data) {
+                        Err(CrioError::PageOverflow { .. }) => {
+                            drop(guard);
+                            self.move_tuple(record_id, data)
+                        }
+                        result => result,
+                    };
+                }
+            }
+        };
+
+        {
+            let mut guard = self.write_page(target.page_id)?;
+            if TablePageRef::new(guard.data()).moved_from(target.slot_id) != Some(record_id) {
+                return Err(dangling(record_id));
+            }
+            match TablePage::new(guard.data_mut()).replace_tuple(target.slot_id, data) {
+                Err(CrioError::PageOverflow { .. }) => {}
+                result => return result,
+            }
+        }
+
+        // Outgrew the page it moved to: bring it home if it fits there now,
+        // otherwise move it again
+        let restored = {
+            let mut guard = self.write_page(record_id.page_id)?;
+            let mut page = TablePage::new(guard.data_mut());
+            match page.restore_tuple(record_id.slot_id, data) {
+                Ok(()) => true,
+                Err(CrioError::PageOverflow { .. }) => false,
+                Err(e) => return Err(e),
+            }
+        };
+        if !restored {
+            self.move_tuple(record_id, data)?;
+        }
+        self.remove_moved(target)
+    }
+
+    /// Tombstones the tuple at `record_id`, dropping its moved copy if it has
+    /// one.
+    pub fn delete_tuple(&self, record_id: RecordId) -> Result<()> {
+        let _record = self.record_lock(record_id).lock();
+
+        let target = {
+            let mut guard = self.write_page(record_id.page_id)?;
+            let mut page = TablePage::new(guard.data_mut());
+            let target = page.forward_target(record_id.slot_id);
+            page.mark_deleted(record_id.slot_id)?;
+            target
+        };
+        self.row_count.fetch_sub(1, Ordering::Relaxed);
+
+        match target {
+            Some(target) => self.remove_moved(target),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns an iterator over the live tuples in chain order, reading one
+    /// page at a time. See `TableIterator` for what it guarantees under
+    /// concurrent writers.
+    pub fn iter(&self) -> TableIterator<'_> {
+        let last_page_id = *self.last_page_id.lock();
+        TableIterator::new(self, self.first_page_id, last_page_id)
+    }
+
+    /// Returns an iterator over the live tuples of the given pages of this
+    /// heap, read in the order given. A page that is not part of the heap
+    /// fails with `InvalidPageId`.
+    pub fn iter_pages(&self, page_ids: Vec<PageId>) -> TableIterator<'_> {
+        TableIterator::listed(self, page_ids)
+    }
+
+    /// Returns every live tuple in chain order, collected from `iter`. Moved
+    /// tuples are reported under their home record ID, at their home slot.
+    pub fn scan(&self) -> Result<Vec<(RecordId, Vec<u8>)>> {
+        self.iter().collect()
+    }
+
+    /// Returns the IDs of every page in the chain, head first.
+    pub fn page_ids(&self) -> Result<Vec<PageId>> {
+        let mut page_ids = Vec::new();
+        let mut next = Some(self.first_page_id);
+        while let Some(page_id) = next {
+            let guard = self.read_page(page_id)?;
+            next = TablePageRef::new(guard.data()).next_page_id();
+            page_ids.push(page_id);
+        }
+        Ok(page_ids)
+    }
+
+    /// Moves forwarded tuples back into their home slots wherever they fit
+    /// again, e.g. after the home page was compacted. Returns the number of
+    /// stubs removed.
+    pub fn collapse_forwards(&self) -> Result<usize> {
+        let mut collapsed = 0;
+        let mut next = Some(self.first_page_id);
+
+        while let Some(page_id) = next {
+            let forwards: Vec<_> = {
+                let guard = self.read_page(page_id)?;
+                let page = TablePageRef::new(guard.data());
+                next = page.next_page_id();
+                page.forwards().map(|(home, _)| home).collect()
+            };
+
+            for home in forwards {
+                let _record = self.record_lock(home).lock();
+                // Re-read under the record lock: the tuple may have moved
+                let Some(target) = self.forward_target(home)? else {
+                    continue;
+                };
+                let data = {
+                    let guard = self.read_page(target.page_id)?;
+                    let page = TablePageRef::new(guard.data());
+                    if page.moved_from(target.slot_id) != Some(home) {
+                        return Err(dangling(home));
+                    }
+                    page.get_tuple(target.slot_id)?.to_vec()
+                };
+
+                let restored = {
+                    let mut guard = self.write_page(home.page_id)?;
+                    let mut page = TablePage::new(guard.data_mut());
+                    match page.restore_tuple(home.slot_id, &data) {
+                        Ok(()) => true,
+                        Err(CrioError::PageOverflow { .. }) => false,
+                        Err(e) => return Err(e),
+                    }
+                };
+                if restored {
+                    self.remove_moved(target)?;
+                    collapsed += 1;
+                }
+            }
+        }
+
+        Ok(collapsed)
+    }
+
+    /// Frees the slots of the tombstoned tuples on one page and compacts it.
+    /// Returns the number of tuples reclaimed. A reclaimed slot can be reused
+    /// by a later insert, so its RecordId must no longer be referenced.
+    pub fn reclaim_page(&self, page_id: PageId) -> Result<usize> {
+        let tombstones = {
+            let guard = self.read_page(page_id)?;
+            TablePageRef::new(guard.data()).tombstone_count()
+        };
+        if tombstones == 0 {
+            return Ok(0);
+        }
+
+        let mut guard = self.write_page(page_id)?;
+        Ok(TablePage::new(guard.data_mut()).reclaim_tombstones())
+    }
+
+    /// Copies a tuple to another page and points its home slot at the copy.
+    fn move_tuple(&self, home: RecordId, data: &[u8]) -> Result<()> {
+        let target = self.insert_with(data.len() + RECORD_ID_SIZE, |page| {
+            page.insert_moved_tuple(home, data)
+        })?;
+
+        let forwarded = {
+            let mut guard = self.write_page(home.page_id)?;
+            let mut page = TablePage::new(guard.data_mut());
+            page.forward_tuple(home.slot_id, target)
+        };
+        if let Err(e) = forwarded {
+            let _ = self.remove_moved(target);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Frees the slot of a moved tuple that no stub points at anymore.
+    fn remove_moved(&self, target: RecordId) -> Result<()> {
+        let mut guard = self.write_page(target.page_id)?;
+        let mut page = TablePage::new(guard.data_mut());
+        page.delete_tuple(target.slot_id)
+    }
+
+    fn forward_target(&self, record_id: RecordId) -> Result<Option<RecordId>> {
+        let guard = self.read_page(record_id.page_id)?;
+        Ok(TablePageRef::new(guard.data()).forward_target(record_id.slot_id))
+    }
+
+    pub(super) fn read_page(&self, page_id: PageId) -> Result<ReadPageGuard> {
+        let guard = self
+            .bpm
+            .checked_read_page(page_id)?
+            .ok_or(CrioError::PageNotFound(page_id))?;
+        self.check_owned(page_id, TablePageRef::new(guard.data()).table_id())?;
+        Ok(guard)
+    }
+
+    /// Reads a page as `read_page` does, as part of `scan`, or without
+    /// read-ahead if there is none.
+    pub(super) fn read_scan_page(
+        &self,
+        page_id: PageId,
+        scan: Option<&mut ScanContext>,
+    ) -> Result<ReadPageGuard> {
+        let guard = match scan {
+            Some(scan) => self.bpm.checked_read_page_in(page_id, scan)?,
+            None => self.bpm.checked_read_page_without_read_ahead(page_id)?,
+        }
+        .ok_or(CrioError::PageNotFound(page_id))?;
+        self.check_owned(page_id, TablePageRef::new(guard.data()).table_id())?;
+        Ok(guard)
+    }
+
+    fn write_page(&self, page_id: PageId) -> Result<WritePageGuard> {
+        let guard = self
+            .bpm
+            .checked_write_page(page_id)?
+            .ok_or(CrioError::PageNotFound(page_id))?;
+        self.check_owned(page_id, TablePageRef::new(guard.data()).table_id())?;
+        Ok(guard)
+    }
+
+    fn record_lock(&self, record_id: RecordId) -> &Mutex<()> {
+        let key = record_id.page_id.as_u32() as usize * 31 + record_id.slot_id.as_u16() as usize;
+        &self.record_locks[key % RECORD_LOCK_STRIPES]
+    }
+
+    fn check_owned(&self, page_id: PageId, table_id: u32) -> Result<()> {
+        if table_id != self.table_id {
+            return Err(CrioError::InvalidPageId(page_id));
+        }
+        Ok(())
+    }
+}
+
+/// State of one `TableHeap::bulk_insert`.
+struct BulkLoad<'a> {
+    heap: &'a TableHeap,
+    /// Where filled pages are counted and cancels noticed
+    progress: &'a JobProgress,
+    /// Bytes each page keeps free
+    reserve: usize,
+    /// Every page allocated for the load
+    allocated: Vec<PageId>,
+    /// Allocated pages not used yet
+    spare: VecDeque<PageId>,
+    /// Full pages not written yet
+    filled: Vec<(PageId, Vec<u8>)>,
+    /// Page being filled
+    current: Option<(PageId, Vec<u8>)>,
+    /// Last page of the chain built so far
+    prev_page_id: PageId,
+}
+
+impl BulkLoad<'_> {
+    fn insert(&mut self, data: &[u8]) -> Result<RecordId> {
+        if let Some((_, buf)) = &mut self.current {
+            let mut page = TablePage::new(buf);
+            if page.can_insert(data.len() + self.reserve) {
+                return page.insert_tuple(data);
+            }
+        }
+
+        self.start_page()?;
+        let (_, buf) = self.current.as_mut().expect("page just started");
+        TablePage::new(buf).insert_tuple(data)
+    }
+
+    /// Starts a new page after the current one.
+    fn start_page(&mut self) -> Result<()> {
+        self.progress.checkpoint()?;
+        if self.spare.is_empty() {
+            let extent = self.heap.bpm.allocate_extent(self.heap.table_id)?;
+            self.allocated.extend(&extent);
+            self.spare.extend(extent);
+        }
+        let page_id = self.spare.pop_front().expect("extent is not empty");
+
+        let mut buf = vec![0u8; PAGE_SIZE];
+        let mut page = TablePage::new(&mut buf);
+        page.init(page_id, self.heap.table_id);
+        page.set_prev_page_id(Some(self.prev_page_id));
+
+        if let Some((prev_id, mut prev)) = self.current.take() {
+            TablePage::new(&mut prev).set_next_page_id(Some(page_id));
+            self.filled.push((prev_id, prev));
+            self.progress.page_done();
+            if self.spare.is_empty() {
+                self.write_filled()?;
+            }
+        }
+        self.current = Some((page_id, buf));
+        self.prev_page_id = page_id;
+        Ok(())
+    }
+
+    /// Writes out the last page and returns the first page of the load, or
+    /// None if it is empty.
+    fn finish(&mut self) -> Result<Option<PageId>> {
+        let first_page_id = self.allocated.first().copied();
+        if let Some(page) = self.current.take() {
+            self.filled.push(page);
+            self.progress.page_done();
+        }
+        self.write_filled()?;
+        Ok(first_page_id)
+    }
+
+    fn write_filled(&mut self) -> Result<()> {
+        let pages: Vec<_> = self
+            .filled
+            .iter()
+            .map(|(page_id, data)| (*page_id, data.as_slice()))
+            .collect();
+        self.heap.bpm.write_pages_direct(&pages)?;
+        self.filled.clear();
+        Ok(())
+    }
+}
+
+fn deleted_as_none(tuple: Result<Vec<u8>>) -> Result<Option<Vec<u8>>> {
+    match tuple {
+        Ok(data) => Ok(Some(data)),
+        Err(CrioError::TupleDeleted(_) | CrioError::EmptySlot(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn new_record_locks() -> Box<[Mutex<()>]> {
+    (0..RECORD_LOCK_STRIPES).map(|_| Mutex::new(())).collect()
+}
+
+fn dangling(record_id: RecordId) -> CrioError {
+    CrioError::DanglingForward {
+        page_id: record_id.page_id,
+        slot: record_id.slot_id.as_u16(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::disk::MemDiskManager;
+
+    fn create_bpm(pool_size: usize) -> Arc<BufferPoolManager> {
+        Arc::new(BufferPoolManager::new(
+            pool_size,
+            2,
+            Arc::new(MemDiskManager::new()),
+        ))
+    }
+
+    #[test]
+    fn test_table_heap_insert_spans_pages() {
+        let bpm = create_bpm(4);
+        let heap = TableHeap::create(Arc::clone(&bpm), 7).unwrap();
+
+        let tuple = [0xABu8; 500];
+        let rids: Vec<_> = (0..20)
+            .map(|_| heap.insert_tuple(&tuple).unwrap())
+            .collect();
+        assert_ne!(rids[0].page_id, rids[19].page_id);
+
+        for &rid in &rids {
+            assert_eq!(heap.get_tuple(rid).unwrap(), tuple);
+        }
+
+        // Reopening walks the chain to the same tail
+        let reopened = TableHeap::open(bpm, 7, heap.first_page_id()).unwrap();
+        assert_eq!(reopened.scan().unwrap().len(), 20);
+        let rid = reopened.insert_tuple(b"tail").unwrap();
+        assert_eq!(rid.page_id, rids[19].page_id);
+    }
+
+    #[test]
+    fn test_table_heap_update_delete() {
+        let heap = TableHeap::create(create_bpm(4), 1).unwrap();
+
+        let r1 = heap.insert_tuple(b"first").unwrap();
+        let r2 = heap.insert_tuple(b"second").unwrap();
+
+        heap.update_tuple(r1, b"FIRST").unwrap();
+        assert_eq!(heap.get_tuple(r1).unwrap(), b"FIRST");
+        heap.update_tuple(r1, b"much longer").unwrap();
+        assert_eq!(heap.get_tuple(r1).unwrap(), b"much longer");
+
+        heap.delete_tuple(r2).unwrap();
+        assert!(matches!(
+            heap.get_tuple(r2),
+            Err(CrioError::TupleDeleted(_))
+        ));
+
+        let rows = heap.scan().unwrap();
+        assert_eq!(rows, vec![(r1, b"much longer".to_vec())]);
+
+        // Oversized tuples fail without growing the chain
+        assert!(heap.insert_tuple(&[0u8; 8192]).is_err());
+        assert_eq!(heap.insert_tuple(b"third").unwrap().page_id, r1.page_id);
+    }
+
+    #[test]
+    fn test_table_heap_update_if() {
+        let heap = TableHeap::create(create_bpm(4), 1).unwrap();
+        let rid = heap.insert_tuple(&0u64.to_le_bytes()).unwrap();
+
+        assert!(matches!(
+            heap.update_if(rid, &1u64.to_le_bytes(), &2u64.to_le_bytes()),
+            Err(CrioError::UpdateConflict { .. })
+        ));
+        assert_eq!(heap.get_tuple(rid).unwrap(), 0u64.to_le_bytes());
+
+        // Read-modify-write loops lose no increments
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        loop {
+                            let current = heap.get_tuple(rid).unwrap();
+                            let n = u64::from_le_bytes(current.as_slice().try_into().unwrap());
+                            match heap.update_if(rid, &current, &(n + 1).to_le_bytes()) {
+                                Ok(()) => break,
+                                Err(CrioError::UpdateConflict { .. }) => continue,
+                                Err(e) => panic!("{e}"),
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(heap.get_tuple(rid).unwrap(), 400u64.to_le_bytes());
+
+        heap.delete_tuple(rid).unwrap();
+        assert!(matches!(
+            heap.update_if(rid, &400u64.to_le_bytes(), b"x"),
+            Err(CrioError::TupleDeleted(_))
+        ));
+    }
+
+    #[test]
+    fn test_table_heap_get_tuples() {
+        let bpm = create_bpm(4);
+        let heap = TableHeap::create(Arc::clone(&bpm), 3).unwrap();
+
+        let rids: Vec<_> = (0..20u8)
+            .map(|i| heap.insert_tuple(&[i; 500]).unwrap())
+            .collect();
+        heap.delete_tuple(rids[7]).unwrap();
+
+        // Scattered across pages, repeated, and with a deleted record
+        let wanted = [rids[19], rids[0], rids[7], rids[12], rids[0], rids[1]];
+        let tuples = heap.get_tuples(&wanted).unwrap();
+        let expected: Vec<_> = [Some(19u8), Some(0), None, Some(12), Some(0), Some(1)]
+            .into_iter()
+            .map(|fill| fill.map(|i| vec![i; 500]))
+            .collect();
+        assert_eq!(tuples, expected);
+        assert!(heap.get_tuples(&[]).unwrap().is_empty());
+
+        // Nothing is left pinned
+        for rid in rids {
+            assert_eq!(bpm.get_pin_count(rid.page_id), Some(0));
+        }
+    }
+
+    #[test]
+    fn test_table_heap_forwards_grown_tuple() {
+        let heap = TableHeap::create(create_bpm(4), 1).unwrap();
+
+        // Fill the first page so the grown tuple has to move
+        let rid = heap.insert_tuple(b"small").unwrap();
+        let mut filler = Vec::new();
+        loop {
+            let r = heap.insert_tuple(&[0xEEu8; 400]).unwrap();
+            if r.page_id != rid.page_id {
+                break;
+            }
+            filler.push(r);
+        }
+
+        let grown = vec![0x42u8; 1000];
+        heap.update_tuple(rid, &grown).unwrap();
+        assert_eq!(heap.get_tuple(rid).unwrap(), grown);
+        assert!(heap.forward_target(rid).unwrap().is_some());
+
+        // Batched reads follow the stub too
+        assert_eq!(
+            heap.get_tuples(&[rid, filler[0]]).unwrap(),
+            vec![Some(grown.clone()), Some(vec![0xEEu8; 400])]
+        );
+
+        // Scans report the tuple under its original record ID
+        let rows = heap.scan().unwrap();
+        let matching: Vec<_> = rows.iter().filter(|(r, _)| *r == rid).collect();
+        assert_eq!(matching, vec![&(rid, grown.clone())]);
+
+        // Growing it again at its new page keeps the same record ID
+        heap.update_tuple(rid, &[0x43u8; 1500]).unwrap();
+        assert_eq!(heap.get_tuple(rid).unwrap(), vec![0x43u8; 1500]);
+
+        heap.delete_tuple(rid).unwrap();
+        assert!(matches!(
+            heap.get_tuple(rid),
+            Err(CrioError::TupleDeleted(_))
+        ));
+        assert!(heap.scan().unwrap().iter().all(|(r, _)| *r != rid));
+    }
+
+    #[test]
+    fn test_table_heap_iter_under_writes() {
+        let heap = TableHeap::create(create_bpm(8), 1).unwrap();
+
+        let rid = heap.insert_tuple(b"small").unwrap();
+        let mut before = vec![rid];
+        loop {
+            let r = heap.insert_tuple(&[0xEEu8; 400]).unwrap();
+            before.push(r);
+            if r.page_id != rid.page_id {
+                break;
+            }
+        }
+        let snapshot_pages = heap.page_ids().unwrap();
+
+        let mut iter = heap.iter();
+        let mut seen = vec![iter.next().unwrap().unwrap().0];
+
+        // Move a tuple the iterator has already copied onto a page it has not
+        // reached yet, and grow the chain past its tail
+        heap.update_tuple(rid, &[0x42u8; 1000]).unwrap();
+        assert!(heap.forward_target(rid).unwrap().is_some());
+        for _ in 0..20 {
+            heap.insert_tuple(&[0xDDu8; 400]).unwrap();
+        }
+
+        seen.extend(iter.map(|item| item.unwrap().0));
+        assert_eq!(seen.iter().filter(|&&r| r == rid).count(), 1);
+        assert!(before.iter().all(|r| seen.contains(r)));
+        assert!(seen.iter().all(|r| snapshot_pages.contains(&r.page_id)));
+
+        // A fresh scan sees the moved tuple once, at its home slot
+        let rows = heap.scan().unwrap();
+        assert_eq!(rows[0], (rid, vec![0x42u8; 1000]));
+        assert_eq!(rows.iter().filter(|(r, _)| *r == rid).count(), 1);
+    }
+
+    #[test]
+    fn test_table_heap_bulk_insert() {
+        let bpm = create_bpm(8);
+        let heap = TableHeap::create(Arc::clone(&bpm), 3).unwrap();
+        let existing = heap.insert_tuple(b"existing").unwrap();
+
+        let tuples: Vec<_> = (0..100u8).map(|i| vec![i; 200]).collect();
+        let rids = heap.bulk_insert(&tuples, 0.5).unwrap();
+        assert_eq!(rids.len(), 100);
+        for (rid, tuple) in rids.iter().zip(&tuples) {
+            assert_eq!(&heap.get_tuple(*rid).unwrap(), tuple);
+        }
+
+        // Half-full pages: roughly twice the pages a full packing needs
+        let pages = heap.page_ids().unwrap();
+        assert_eq!(pages[0], existing.page_id);
+        assert!(pages.len() > 1 + 2 * 100 * 200 / PAGE_SIZE);
+        for &page_id in &pages[1..] {
+            let guard = bpm.checked_read_page(page_id).unwrap().unwrap();
+            assert!(TablePageRef::new(guard.data()).free_space() >= PAGE_SIZE / 2);
+        }
+
+        let scanned: Vec<_> = heap.scan().unwrap().into_iter().map(|(r, _)| r).collect();
+        assert_eq!(scanned[0], existing);
+        assert_eq!(&scanned[1..], &rids[..]);
+
+        // The chain's tail moved to the last bulk page
+        let next = heap.insert_tuple(b"after").unwrap();
+        assert_eq!(next.page_id, *pages.last().unwrap());
+
+        // Failed loads leave the heap as it was
+        let bad = vec![vec![1u8; 100], vec![0u8; 8192]];
+        assert!(heap.bulk_insert(&bad, 1.0).is_err());
+        assert_eq!(heap.page_ids().unwrap(), pages);
+        assert!(matches!(
+            heap.bulk_insert(&tuples, 0.0),
+            Err(CrioError::InvalidFillFactor(_))
+        ));
+        let none: Vec<Vec<u8>> = Vec::new();
+        assert!(heap.bulk_insert(none, 1.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_table_heap_approx_counts() {
+        let bpm = create_bpm(8);
+        let heap = TableHeap::create(Arc::clone(&bpm), 3).unwrap();
+        assert_eq!(heap.approx_row_count(), 0);
+        assert_eq!(heap.approx_size_bytes(), PAGE_SIZE as u64);
+
+        let rids: Vec<_> = (0..40u8)
+            .map(|i| heap.insert_tuple(&[i; 300]).unwrap())
+            .collect();
+        let tuples: Vec<_> = (0..50u8).map(|i| vec![i; 200]).collect();
+        heap.bulk_insert(&tuples, 1.0).unwrap();
+        heap.delete_tuple(rids[3]).unwrap();
+        // A moved tuple is still one row
+        heap.update_tuple(rids[0], &[9u8; 3000]).unwrap();
+        assert!(heap.delete_tuple(rids[3]).is_err());
+
+        let check = |heap: &TableHeap| {
+            assert_eq!(heap.approx_row_count(), heap.scan().unwrap().len() as u64);
+            assert_eq!(
+                heap.approx_size_bytes(),
+                (heap.page_ids().unwrap().len() * PAGE_SIZE) as u64
+            );
+        };
+        check(&heap);
+        assert_eq!(heap.approx_row_count(), 89);
+
+        // Reopening recounts from the pages
+        let reopened = TableHeap::open(bpm, 3, heap.first_page_id()).unwrap();
+        check(&reopened);
+        assert_eq!(reopened.approx_row_count(), 89);
+    }
+
+    #[test]
+    fn test_table_heap_bulk_insert_progress() {
+        let bpm = create_bpm(8);
+        let heap = TableHeap::create(Arc::clone(&bpm), 3).unwrap();
+        let tuples: Vec<_> = (0..100u8).map(|i| vec![i; 200]).collect();
+
+        let progress = JobProgress::new();
+        heap.bulk_insert_with_progress(&tuples, 1.0, &progress)
+            .unwrap();
+        assert_eq!(
+            progress.pages_processed() as usize,
+            heap.page_ids().unwrap().len() - 1
+        );
+
+        // A cancelled load leaves the heap as it was
+        let pages = heap.page_ids().unwrap();
+        progress.cancel();
+        assert!(matches!(
+            heap.bulk_insert_with_progress(&tuples, 1.0, &progress),
+            Err(CrioError::Cancelled)
+        ));
+        assert_eq!(heap.page_ids().unwrap(), pages);
+    }
+
+    #[test]
+    fn test_table_heap_collapse_forwards() {
+        let bpm = create_bpm(4);
+        let heap = TableHeap::create(Arc::clone(&bpm), 1).unwrap();
+
+        let rid = heap.insert_tuple(b"small").unwrap();
+        let mut filler = Vec::new();
+        loop {
+            let r = heap.insert_tuple(&[0xEEu8; 400]).unwrap();
+            if r.page_id != rid.page_id {
+                break;
+            }
+            filler.push(r);
+        }
+        heap.update_tuple(rid, &[0x42u8; 1000]).unwrap();
+        assert_eq!(heap.collapse_forwards().unwrap(), 0);
+
+        // Free space on the home page and reclaim it
+        for &r in &filler[..4] {
+            heap.delete_tuple(r).unwrap();
+        }
+        {
+            let mut guard = bpm.checked_write_page(rid.page_id).unwrap().unwrap();
+            TablePage::new(guard.data_mut()).reclaim_tombstones();
+        }
+
+        assert_eq!(heap.collapse_forwards().unwrap(), 1);
+        assert_eq!(heap.forward_target(rid).unwrap(), None);
+        assert_eq!(heap.get_tuple(rid).unwrap(), vec![0x42u8; 1000]);
+        assert_eq!(
+            heap.scan()
+                .unwrap()
+                .iter()
+                .filter(|(r, _)| *r == rid)
+                .count(),
+            1
+        );
+    }
+}
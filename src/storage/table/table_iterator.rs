@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+
+use crate::buffer::ScanContext;
+use crate::common::{CrioError, PageId, RecordId, Result};
+use crate::storage::page::TablePageRef;
+
+use super::TableHeap;
+
+/// A tuple copied out of a page, or a forwarding stub still to be followed.
+enum Entry {
+    Tuple(RecordId, Vec<u8>),
+    Forwarded(RecordId),
+}
+
+/// Iterator over the live tuples of a TableHeap, created by `TableHeap::iter`.
+///
+/// Guarantees under concurrent writers:
+///
+/// - Each page is copied out under its read latch, so no tuple is torn.
+/// - Tuples are reported at their home slot under their home record ID; a
+///   moved tuple is read through its stub and its moved copy is skipped. A
+///   tuple that stays live for the whole scan is returned exactly once, even
+///   if it moves while the scan runs.
+/// - The scan ends at the tail page as of `iter()`: pages appended later are
+///   not visited, so the scan finishes under a steady stream of inserts.
+///   Tuples inserted, updated or deleted during the scan may or may not be
+///   seen.
+///
+/// An iterator from `TableHeap::iter_pages` reads just the pages it was
+/// given, in that order, instead of following the chain.
+pub struct TableIterator<'a> {
+    heap: &'a TableHeap,
+    /// Next page to copy, or None once the last page has been read
+    next_page_id: Option<PageId>,
+    /// Tail of the chain when the iterator was created
+    last_page_id: PageId,
+    /// Pages still to read after `next_page_id` when reading a given list of
+    /// pages rather than the chain
+    listed: Option<VecDeque<PageId>>,
+    /// Entries of the current page not returned yet
+    pending: VecDeque<Entry>,
+    /// Access pattern of this scan for buffer pool read-ahead, or None to
+    /// read without it
+    scan: Option<ScanContext>,
+}
+
+impl<'a> TableIterator<'a> {
+    pub(super) fn new(heap: &'a TableHeap, first_page_id: PageId, last_page_id: PageId) -> Self {
+        Self {
+            heap,
+            next_page_id: Some(first_page_id),
+            last_page_id,
+            listed: None,
+            pending: VecDeque::new(),
+            scan: Some(ScanContext::new()),
+        }
+    }
+
+    pub(super) fn listed(heap: &'a TableHeap, page_ids: Vec<PageId>) -> Self {
+        let mut listed: VecDeque<_> = page_ids.into();
+        Self {
+            heap,
+            next_page_id: listed.pop_front(),
+            last_page_id: heap.first_page_id(),
+            listed: Some(listed),
+            pending: VecDeque::new(),
+            scan: Some(ScanContext::new()),
+        }
+    }
+
+    /// Reads the pages without buffer pool read-ahead, e.g. for a short scan
+    /// or one that runs alongside latency-sensitive work.
+    pub fn without_read_ahead(mut self) -> Self {
+        self.scan = None;
+        self
+    }
+
+    /// Copies the next page's entries into `pending`, in slot order.
+    fn load_page(&mut self, page_id: PageId) -> Result<()> {
+        let guard = self.heap.read_scan_page(page_id, self.scan.as_mut())?;
+        let page = TablePageRef::new(guard.data());
+        if self.listed.is_some() && page.table_id() != self.heap.table_id() {
+            return Err(CrioError::InvalidPageId(page_id));
+        }
+
+        let mut entries: Vec<_> = page
+            .record_ids()
+            .filter(|record_id| page.moved_from(record_id.slot_id).is_none())
+            .map(|record_id| {
+                let data = page.get_tuple(record_id.slot_id)?;
+                Ok(Entry::Tuple(record_id, data.to_vec()))
+            })
+            .collect::<Result<_>>()?;
+        entries.extend(page.forwards().map(|(home, _)| Entry::Forwarded(home)));
+        entries.sort_by_key(|entry| match entry {
+            Entry::Tuple(record_id, _) | Entry::Forwarded(record_id) => record_id.slot_id.as_u16(),
+        });
+        self.pending.extend(entries);
+
+        self.next_page_id = match (&mut self.listed, page.next_page_id()) {
+            (Some(listed), _) => listed.pop_front(),
+            (None, Some(next)) if page_id != self.last_page_id => Some(next),
+            _ => None,
+        };
+        Ok(())
+    }
+
+    /// Returns the next live tuple, or None at the end of the scan.
+    fn advance(&mut self) -> Result<Option<(RecordId, Vec<u8>)>> {
+        loop {
+            match self.pending.pop_front() {
+                Some(Entry::Tuple(record_id, data)) => return Ok(Some((record_id, data))),
+                Some(Entry::Forwarded(home)) => match self.heap.get_tuple(home) {
+                    Ok(data) => return Ok(Some((home, data))),
+                    // Deleted since the page was copied
+                    Err(CrioError::TupleDeleted(_) | CrioError::EmptySlot(_)) => {}
+                    Err(e) => return Err(e),
+                },
+                None => match self.next_page_id {
+                    Some(page_id) => self.load_page(page_id)?,
+                    None => return Ok(None),
+                },
+            }
+        }
+    }
+}
+
+impl Iterator for TableIterator<'_> {
+    type Item = Result<(RecordId, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(item) => item.map(Ok),
+            Err(e) => {
+                // Stop after the first error
+                self.next_page_id = None;
+                self.pending.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+
+use crate::common::{CrioError, PageId, RecordId, Result, PAGE_SIZE};
+use crate::storage::disk::TempFileManager;
+use crate::storage::page::TablePage;
+use crate::tuple::{Schema, Tuple, Value};
+
+/// TempTable holds rows in scratch pages of a `TempFileManager`, for
+/// `CREATE TEMP TABLE` and materialized `WITH` queries.
+///
+/// Pages are filled in order like a bulk-loaded heap, with the last page
+/// kept in memory until it is full. Nothing is logged, indexed or visible
+/// in the catalog, and rows are only appended; the pages go back to the
+/// manager when the table is truncated or dropped.
+pub struct TempTable {
+    name: String,
+    schema: Arc<Schema>,
+    temp: Arc<TempFileManager>,
+    /// Full pages, in order
+    pages: Vec<PageId>,
+    /// Page being filled
+    tail: Option<(PageId, Vec<u8>)>,
+    /// Rows inserted
+    rows: u64,
+}
+
+impl TempTable {
+    /// Creates an empty temp table called `name` in the scratch file of
+    /// `temp`.
+    pub fn new(name: impl Into<String>, schema: Schema, temp: Arc<TempFileManager>) -> Self {
+        Self {
+            name: name.into(),
+            schema: Arc::new(schema),
+            temp,
+            pages: Vec::new(),
+            tail: None,
+            rows: 0,
+        }
+    }
+
+    /// Returns the table name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the table schema.
+    pub fn schema(&self) -> &Arc<Schema> {
+        &self.schema
+    }
+
+    /// Appends a row, failing with `SchemaMismatch` if `values` doesn't fit
+    /// the schema and with `TempQuotaExceeded` if the scratch file is full.
+    pub fn insert(&mut self, values: Vec<Value>) -> Result<RecordId> {
+        if values.len() != self.schema.column_count() {
+            return Err(CrioError::SchemaMismatch);
+        }
+        let data = Tuple::new(Arc::clone(&self.schema), values)
+            .to_bytes()
+            .ok_or(CrioError::SchemaMismatch)?;
+
+        if let Some((_, buf)) = &mut self.tail {
+            let mut page = TablePage::new(buf);
+            if page.can_insert(data.len()) {
+                let record_id = page.insert_tuple(&data)?;
+                self.rows += 1;
+                return Ok(record_id);
+            }
+        }
+
+        let page_id = self.temp.allocate_page()?;
+        let mut buf = vec![0u8; PAGE_SIZE];
+        let mut page = TablePage::new(&mut buf);
+        page.init(page_id, 0);
+        let record_id = match page.insert_tuple(&data) {
+            Ok(record_id) => record_id,
+            Err(e) => {
+                // Too large for any page
+                self.temp.free_page(page_id)?;
+                return Err(e);
+            }
+        };
+        if let Some((full, full_buf)) = self.tail.replace((page_id, buf)) {
+            self.pages.push(full);
+            self.temp.write_page(full, &full_buf)?;
+        }
+        self.rows += 1;
+        Ok(record_id)
+    }
+
+    /// Appends the rows of an executor, returning the number appended.
+    pub fn insert_all<I>(&mut self, rows: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = Result<(RecordId, Tuple)>>,
+    {
+        let mut inserted = 0;
+        for row in rows {
+            let (_, tuple) = row?;
+            self.insert(tuple.values().to_vec())?;
+            inserted += 1;
+        }
+        Ok(inserted)
+    }
+
+    /// Returns a scan of the rows in insertion order.
+    pub fn scan(&self) -> TempTableScan<'_> {
+        TempTableScan {
+            table: self,
+            next_page: 0,
+            buffered: VecDeque::new(),
+        }
+    }
+
+    /// Returns the number of rows.
+    pub fn len(&self) -> u64 {
+        self.rows
+    }
+
+    /// Returns true if the table has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    /// Returns the number of scratch pages holding the rows.
+    pub fn page_count(&self) -> usize {
+        self.pages.len() + usize::from(self.tail.is_some())
+    }
+
+    /// Removes every row, giving the pages back to the scratch file.
+    pub fn truncate(&mut self) -> Result<()> {
+        let tail = self.tail.take().map(|(page_id, _)| page_id);
+        for page_id in self.pages.drain(..).chain(tail) {
+            self.temp.free_page(page_id)?;
+        }
+        self.rows = 0;
+        Ok(())
+    }
+
+    /// Reads the rows of the `index`th page.
+    pub(crate) fn read_page(&self, index: usize) -> Result<Vec<(RecordId, Tuple)>> {
+        let mut buf = match self.pages.get(index) {
+            Some(&page_id) => {
+                let mut buf = vec![0u8; PAGE_SIZE];
+                self.temp.read_page(page_id, &mut buf)?;
+                buf
+            }
+            None => match &self.tail {
+                Some((_, buf)) => buf.clone(),
+                None => return Ok(Vec::new()),
+            },
+        };
+        let page = TablePage::new(&mut buf);
+        let mut rows = Vec::with_capacity(page.tuple_count());
+        for record_id in page.record_ids().collect::<Vec<_>>() {
+            let data = page.get_tuple(record_id.slot_id)?;
+            let tuple = Tuple::from_bytes(Arc::clone(&self.schema), data)
+                .ok_or(CrioError::SchemaMismatch)?;
+            rows.push((record_id, tuple));
+        }
+        Ok(rows)
+    }
+}
+
+impl Drop for TempTable {
+    fn drop(&mut self) {
+        let _ = self.truncate();
+    }
+}
+
+/// Scan over the rows of a `TempTable`, reading one page at a time.
+pub struct TempTableScan<'a> {
+    table: &'a TempTable,
+    /// Index of the next page to read
+    next_page: usize,
+    /// Rows of the current page not returned yet
+    buffered: VecDeque<(RecordId, Tuple)>,
+}
+
+impl Iterator for TempTableScan<'_> {
+    type Item = Result<(RecordId, Tuple)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.buffered.pop_front() {
+                return Some(Ok(row));
+            }
+            if self.next_page >= self.table.page_count() {
+                return None;
+            }
+            match self.table.read_page(self.next_page) {
+                Ok(rows) => self.buffered.extend(rows),
+                Err(e) => return Some(Err(e)),
+            }
+            self.next_page += 1;
+        }
+    }
+}
+
+/// How long a temp table lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempTableScope {
+    /// Until it is dropped or the session ends
+    Session,
+    /// Until the current transaction ends (`ON COMMIT DROP`)
+    Transaction,
+}
+
+/// TempTables holds the temp tables of one session by name, sharing one
+/// scratch file. Everything is freed when it is dropped.
+pub struct TempTables {
+    temp: Arc<TempFileManager>,
+    tables: BTreeMap<String, (TempTable, TempTableScope)>,
+}
+
+impl TempTables {
+    /// Creates an empty set of temp tables stored in the scratch file of
+    /// `temp`.
+    pub fn new(temp: TempFileManager) -> Self {
+        Self {
+            temp: Arc::new(temp),
+            tables: BTreeMap::new(),
+        }
+    }
+
+    /// Creates an empty temp table living for `scope`. Fails with
+    /// `DuplicateTableName` if a temp table of that name exists.
+    pub fn create(
+        &mut self,
+        name: &str,
+        schema: Schema,
+        scope: TempTableScope,
+    ) -> Result<&mut TempTable> {
+        if self.tables.contains_key(name) {
+            return Err(CrioError::DuplicateTableName(name.to_string()));
+        }
+        let table = TempTable::new(name, schema, Arc::clone(&self.temp));
+        let (table, _) = self
+            .tables
+            .entry(name.to_string())
+            .or_insert((table, scope));
+        Ok(table)
+    }
+
+    /// Returns the temp table called `name`.
+    pub fn get(&self, name: &str) -> Option<&TempTable> {
+        self.tables.get(name).map(|(table, _)| table)
+    }
+
+    /// Returns the temp table called `name` for inserting.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut TempTable> {
+        self.tables.get_mut(name).map(|(table, _)| table)
+    }
+
+    /// Drops the temp table called `name`, failing with `UnknownTable` if
+    /// there is none.
+    pub fn drop_table(&mut self, name: &str) -> Result<()> {
+        self.tables
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| CrioError::UnknownTable(name.to_string()))
+    }
+
+    /// Drops the temp tables scoped to the transaction that just ended.
+    pub fn end_transaction(&mut self) {
+        self.tables
+            .retain(|_, (_, scope)| *scope != TempTableScope::Transaction);
+    }
+
+    /// Returns the names of the temp tables, in order.
+    pub fn names(&self) -> Vec<String> {
+        self.tables.keys().cloned().collect()
+    }
+
+    /// Returns the scratch file the tables are stored in.
+    pub fn temp_file(&self) -> &Arc<TempFileManager> {
+        &self.temp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuple::DataType;
+
+    fn schema() -> Schema {
+        Schema::builder()
+            .column("id", DataType::Integer)
+            .column("name", DataType::VarChar(64))
+            .build()
+    }
+
+    #[test]
+    fn test_temp_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = Arc::new(TempFileManager::new(dir.path().join("t.tmp"), 64).unwrap());
+        let mut table = TempTable::new("t", schema(), Arc::clone(&temp));
+
+        let name = "x".repeat(60);
+        for i in 0..500 {
+            table
+                .insert(vec![Value::Integer(i), Value::String(name.clone())])
+                .unwrap();
+        }
+        assert!(matches!(
+            table.insert(vec![Value::Integer(1)]),
+            Err(CrioError::SchemaMismatch)
+        ));
+        assert_eq!(table.len(), 500);
+        assert!(table.page_count() > 1);
+        assert_eq!(temp.allocated_pages() as usize, table.page_count());
+
+        let ids: Vec<_> = table
+            .scan()
+            .map(|row| row.unwrap().1.value(0).cloned().unwrap())
+            .collect();
+        assert_eq!(ids, (0..500).map(Value::Integer).collect::<Vec<_>>());
+
+        table.truncate().unwrap();
+        assert!(table.is_empty());
+        assert_eq!(table.scan().count(), 0);
+        assert_eq!(temp.allocated_pages(), 0);
+    }
+
+    #[test]
+    fn test_temp_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tables =
+            TempTables::new(TempFileManager::new(dir.path().join("s.tmp"), 8).unwrap());
+
+        tables
+            .create("kept", schema(), TempTableScope::Session)
+            .unwrap()
+            .insert(vec![Value::Integer(1), Value::String("a".into())])
+            .unwrap();
+        tables
+            .create("scratch", schema(), TempTableScope::Transaction)
+            .unwrap()
+            .insert(vec![Value::Integer(2), Value::String("b".into())])
+            .unwrap();
+        assert!(matches!(
+            tables.create("kept", schema(), TempTableScope::Session),
+            Err(CrioError::DuplicateTableName(_))
+        ));
+        assert_eq!(tables.temp_file().allocated_pages(), 2);
+
+        tables.end_transaction();
+        assert_eq!(tables.names(), vec!["kept"]);
+        assert_eq!(tables.temp_file().allocated_pages(), 1);
+        assert_eq!(tables.get("kept").unwrap().scan().count(), 1);
+
+        tables.drop_table("kept").unwrap();
+        assert!(matches!(
+            tables.drop_table("kept"),
+            Err(CrioError::UnknownTable(_))
+        ));
+        assert_eq!(tables.temp_file().allocated_pages(), 0);
+    }
+}
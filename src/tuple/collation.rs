@@ -0,0 +1,97 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// How strings in a column are ordered and compared for equality.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Collation {
+    /// Raw UTF-8 byte order, which is also code point order
+    #[default]
+    Binary,
+
+    /// Code point order after Unicode lowercasing, so "Apple" equals "apple"
+    CaseInsensitive,
+}
+
+impl Collation {
+    /// Compares two strings under this collation.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Binary => a.cmp(b),
+            Collation::CaseInsensitive => fold(a).cmp(fold(b)),
+        }
+    }
+
+    /// Returns bytes whose plain byte order is this collation's order for
+    /// `s`, for use as an index key or in any other bytewise comparison.
+    pub fn sort_key<'a>(&self, s: &'a str) -> Cow<'a, [u8]> {
+        match self {
+            Collation::Binary => Cow::Borrowed(s.as_bytes()),
+            Collation::CaseInsensitive => Cow::Owned(s.to_lowercase().into_bytes()),
+        }
+    }
+
+    /// Returns the on-disk identifier.
+    pub fn id(&self) -> u8 {
+        match self {
+            Collation::Binary => 0,
+            Collation::CaseInsensitive => 1,
+        }
+    }
+
+    /// Returns the collation with on-disk identifier `id`.
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Collation::Binary),
+            1 => Some(Collation::CaseInsensitive),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Collation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Collation::Binary => write!(f, "BINARY"),
+            Collation::CaseInsensitive => write!(f, "CASE_INSENSITIVE"),
+        }
+    }
+}
+
+/// Lowercases `s` lazily, char by char.
+fn fold(s: &str) -> impl Iterator<Item = char> + '_ {
+    s.chars().flat_map(char::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collations() {
+        assert_eq!(Collation::Binary.compare("B", "a"), Ordering::Less);
+        assert_eq!(
+            Collation::CaseInsensitive.compare("B", "a"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            Collation::CaseInsensitive.compare("ÄPFEL", "äpfel"),
+            Ordering::Equal
+        );
+
+        // Sort keys order like compare
+        let mut words = vec!["banana", "Apple", "cherry", "apricot", "Banana"];
+        for collation in [Collation::Binary, Collation::CaseInsensitive] {
+            let mut by_key = words.clone();
+            by_key.sort_by(|a, b| collation.sort_key(a).cmp(&collation.sort_key(b)));
+            words.sort_by(|a, b| collation.compare(a, b));
+            assert_eq!(by_key, words);
+        }
+
+        for collation in [Collation::Binary, Collation::CaseInsensitive] {
+            assert_eq!(Collation::from_id(collation.id()), Some(collation));
+        }
+        assert_eq!(Collation::from_id(9), None);
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt;
 /// Represents the data types supported by the database.
 /// Each type has a fixed or variable size and specific serialization rules.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataType {
     /// Boolean type: 1 byte (0 = false, 1 = true)
     Boolean,
@@ -34,6 +35,13 @@ pub enum DataType {
 
     /// Timestamp: 8 bytes, microseconds since Unix epoch
     Timestamp,
+
+    /// Interval: 16 bytes, months (4) + days (4) + microseconds (8)
+    Interval,
+
+    /// JSON document in a compact binary encoding, up to 64 KB
+    /// Stored as: length (2 bytes) + encoded document (variable)
+    Json,
 }
 
 impl DataType {
@@ -48,8 +56,9 @@ impl DataType {
             | DataType::Float
             | DataType::Double
             | DataType::Char(_)
-            | DataType::Timestamp => true,
-            DataType::VarChar(_) => false,
+            | DataType::Timestamp
+            | DataType::Interval => true,
+            DataType::VarChar(_) | DataType::Json => false,
         }
     }
 
@@ -65,7 +74,8 @@ impl DataType {
             DataType::Double => Some(8),
             DataType::Char(n) => Some(*n as usize),
             DataType::Timestamp => Some(8),
-            DataType::VarChar(_) => None,
+            DataType::Interval => Some(16),
+            DataType::VarChar(_) | DataType::Json => None,
         }
     }
 
@@ -82,8 +92,10 @@ impl DataType {
             DataType::Double => 8,
             DataType::Char(n) => *n as usize,
             DataType::Timestamp => 8,
+            DataType::Interval => 16,
             // 2 bytes for length prefix + max data length
             DataType::VarChar(n) => 2 + *n as usize,
+            DataType::Json => 2 + u16::MAX as usize,
         }
     }
 
@@ -100,6 +112,8 @@ impl DataType {
             DataType::Char(_) => 7,
             DataType::VarChar(_) => 8,
             DataType::Timestamp => 9,
+            DataType::Interval => 10,
+            DataType::Json => 11,
         }
     }
 
@@ -147,6 +161,8 @@ impl DataType {
                 Some((DataType::VarChar(n), 3))
             }
             9 => Some((DataType::Timestamp, 1)),
+            10 => Some((DataType::Interval, 1)),
+            11 => Some((DataType::Json, 1)),
             _ => None,
         }
     }
@@ -165,6 +181,8 @@ impl fmt::Display for DataType {
             DataType::Char(n) => write!(f, "CHAR({})", n),
             DataType::VarChar(n) => write!(f, "VARCHAR({})", n),
             DataType::Timestamp => write!(f, "TIMESTAMP"),
+            DataType::Interval => write!(f, "INTERVAL"),
+            DataType::Json => write!(f, "JSON"),
         }
     }
 }
@@ -179,6 +197,7 @@ mod tests {
         assert!(DataType::Integer.is_fixed_size());
         assert!(DataType::Char(10).is_fixed_size());
         assert!(!DataType::VarChar(100).is_fixed_size());
+        assert!(!DataType::Json.is_fixed_size());
     }
 
     #[test]
@@ -204,6 +223,8 @@ mod tests {
             DataType::Char(50),
             DataType::VarChar(255),
             DataType::Timestamp,
+            DataType::Interval,
+            DataType::Json,
         ];
 
         for dt in types {
@@ -0,0 +1,291 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+
+/// A span of time, kept as months, days and microseconds because their
+/// lengths in absolute time vary: a month has 28 to 31 days.
+///
+/// Adding an interval to a timestamp applies the months first (clamping the
+/// day to the end of a shorter month, so Jan 31 + 1 month is Feb 28 or 29),
+/// then the days, then the microseconds. Timestamps are UTC, so every day is
+/// 24 hours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub micros: i64,
+}
+
+impl Interval {
+    /// Size of an interval serialized as a value: months, days, micros.
+    pub const SIZE: usize = 16;
+
+    pub fn new(months: i32, days: i32, micros: i64) -> Self {
+        Self {
+            months,
+            days,
+            micros,
+        }
+    }
+
+    /// An interval of whole months.
+    pub fn from_months(months: i32) -> Self {
+        Self::new(months, 0, 0)
+    }
+
+    /// An interval of whole days.
+    pub fn from_days(days: i32) -> Self {
+        Self::new(0, days, 0)
+    }
+
+    /// An interval of exact time.
+    pub fn from_micros(micros: i64) -> Self {
+        Self::new(0, 0, micros)
+    }
+
+    /// Returns the interval pointing the other way, or None on overflow.
+    pub fn checked_neg(&self) -> Option<Self> {
+        Some(Self::new(
+            self.months.checked_neg()?,
+            self.days.checked_neg()?,
+            self.micros.checked_neg()?,
+        ))
+    }
+
+    /// Adds two intervals field by field, or None on overflow.
+    pub fn checked_add(&self, other: &Interval) -> Option<Self> {
+        Some(Self::new(
+            self.months.checked_add(other.months)?,
+            self.days.checked_add(other.days)?,
+            self.micros.checked_add(other.micros)?,
+        ))
+    }
+
+    /// Returns the timestamp `self` after `timestamp` (microseconds since the
+    /// Unix epoch), or None on overflow.
+    pub fn add_to(&self, timestamp: i64) -> Option<i64> {
+        let mut timestamp = timestamp;
+        if self.months != 0 {
+            let day = timestamp.div_euclid(MICROS_PER_DAY);
+            let time = timestamp.rem_euclid(MICROS_PER_DAY);
+            let (year, month, day_of_month) = civil_from_days(day);
+
+            let month_index = (year * 12 + (month as i64 - 1)).checked_add(self.months as i64)?;
+            let year = month_index.div_euclid(12);
+            let month = month_index.rem_euclid(12) as u32 + 1;
+            let day_of_month = day_of_month.min(days_in_month(year, month));
+            timestamp = days_from_civil(year, month, day_of_month)
+                .checked_mul(MICROS_PER_DAY)?
+                .checked_add(time)?;
+        }
+        timestamp
+            .checked_add((self.days as i64).checked_mul(MICROS_PER_DAY)?)?
+            .checked_add(self.micros)
+    }
+
+    /// Approximate length in microseconds, counting a month as 30 days.
+    /// Used to order intervals, as in SQL.
+    fn approximate_micros(&self) -> i128 {
+        (self.months as i128 * 30 + self.days as i128) * MICROS_PER_DAY as i128
+            + self.micros as i128
+    }
+
+    /// Orders intervals by approximate length, so 1 month sorts with 30 days.
+    pub fn compare(&self, other: &Interval) -> Ordering {
+        self.approximate_micros().cmp(&other.approximate_micros())
+    }
+
+    /// Serializes as months (4 bytes), days (4 bytes), micros (8 bytes), all
+    /// little-endian.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.months.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.days.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.micros.to_le_bytes());
+        bytes
+    }
+
+    /// Deserializes an interval written by `to_bytes`.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let data = data.get(..Self::SIZE)?;
+        Some(Self::new(
+            i32::from_le_bytes(data[0..4].try_into().unwrap()),
+            i32::from_le_bytes(data[4..8].try_into().unwrap()),
+            i64::from_le_bytes(data[8..16].try_into().unwrap()),
+        ))
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} months {} days {} microseconds",
+            self.months, self.days, self.micros
+        )
+    }
+}
+
+/// A field of a timestamp, for `extract`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeField {
+    Year,
+    /// 1 to 12
+    Month,
+    /// Day of the month, 1 to 31
+    Day,
+    /// 0 to 23
+    Hour,
+    /// 0 to 59
+    Minute,
+    /// 0 to 59
+    Second,
+}
+
+/// Returns one field of a timestamp (microseconds since the Unix epoch, UTC).
+pub fn extract(field: TimeField, timestamp: i64) -> i64 {
+    let day = timestamp.div_euclid(MICROS_PER_DAY);
+    let time = timestamp.rem_euclid(MICROS_PER_DAY);
+    let (year, month, day_of_month) = civil_from_days(day);
+    match field {
+        TimeField::Year => year,
+        TimeField::Month => month as i64,
+        TimeField::Day => day_of_month as i64,
+        TimeField::Hour => time / MICROS_PER_HOUR,
+        TimeField::Minute => time % MICROS_PER_HOUR / MICROS_PER_MINUTE,
+        TimeField::Second => time % MICROS_PER_MINUTE / MICROS_PER_SECOND,
+    }
+}
+
+/// Returns the timestamp of midnight UTC on the given date.
+pub fn timestamp_from_date(year: i64, month: u32, day: u32) -> i64 {
+    days_from_civil(year, month, day) * MICROS_PER_DAY
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (H. Hinnant's
+/// `days_from_civil`).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of `days_from_civil`: (year, month, day) of a day since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(days_from_civil(2000, 2, 29)), (2000, 2, 29));
+        for day in (-800_000..800_000).step_by(997) {
+            let (y, m, d) = civil_from_days(day);
+            assert_eq!(days_from_civil(y, m, d), day);
+        }
+    }
+
+    #[test]
+    fn test_interval_arithmetic() {
+        let jan31 = timestamp_from_date(2024, 1, 31) + 9 * MICROS_PER_HOUR;
+
+        // Month arithmetic clamps to the end of the month and keeps the time
+        let feb = Interval::from_months(1).add_to(jan31).unwrap();
+        assert_eq!(feb, timestamp_from_date(2024, 2, 29) + 9 * MICROS_PER_HOUR);
+        let back = Interval::from_months(-13).add_to(jan31).unwrap();
+        assert_eq!(
+            back,
+            timestamp_from_date(2022, 12, 31) + 9 * MICROS_PER_HOUR
+        );
+
+        let mixed = Interval::new(1, 1, MICROS_PER_HOUR).add_to(jan31).unwrap();
+        assert_eq!(
+            mixed,
+            timestamp_from_date(2024, 3, 1) + 10 * MICROS_PER_HOUR
+        );
+
+        let sum = Interval::from_days(2)
+            .checked_add(&Interval::from_months(1))
+            .unwrap();
+        assert_eq!(sum, Interval::new(1, 2, 0));
+        assert_eq!(sum.checked_neg().unwrap(), Interval::new(-1, -2, 0));
+        assert!(Interval::from_micros(i64::MAX).add_to(1).is_none());
+
+        assert_eq!(
+            Interval::from_months(1).compare(&Interval::from_days(30)),
+            Ordering::Equal
+        );
+        assert_eq!(
+            Interval::from_days(1).compare(&Interval::from_micros(MICROS_PER_HOUR)),
+            Ordering::Greater
+        );
+
+        let interval = Interval::new(-3, 7, -42);
+        assert_eq!(Interval::from_bytes(&interval.to_bytes()), Some(interval));
+    }
+
+    #[test]
+    fn test_extract() {
+        let ts = timestamp_from_date(1999, 12, 31)
+            + 23 * MICROS_PER_HOUR
+            + 59 * MICROS_PER_MINUTE
+            + 58 * MICROS_PER_SECOND
+            + 7;
+        let fields = [
+            TimeField::Year,
+            TimeField::Month,
+            TimeField::Day,
+            TimeField::Hour,
+            TimeField::Minute,
+            TimeField::Second,
+        ];
+        let parts: Vec<_> = fields.iter().map(|&f| extract(f, ts)).collect();
+        assert_eq!(parts, [1999, 12, 31, 23, 59, 58]);
+
+        // Before the epoch
+        assert_eq!(extract(TimeField::Year, -1), 1969);
+        assert_eq!(extract(TimeField::Hour, -1), 23);
+    }
+}
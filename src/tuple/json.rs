@@ -0,0 +1,695 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::common::{CrioError, Result};
+
+use super::Value;
+
+/// Nesting deeper than this is rejected when parsing or decoding, so a
+/// malicious document can't overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+// Binary encoding tags
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_DOUBLE: u8 = 4;
+const TAG_STRING: u8 = 5;
+const TAG_ARRAY: u8 = 6;
+const TAG_OBJECT: u8 = 7;
+
+/// A JSON document, the payload of `Value::Json`.
+///
+/// Integers that fit an i64 are kept apart from other numbers so they
+/// round-trip exactly and can be indexed. Object members keep their order;
+/// a key repeated in the source text keeps its last value.
+///
+/// In tuples a document is stored in a compact binary form: a tag byte per
+/// node, then a zigzag varint for integers, 8 bytes for doubles, a varint
+/// length and UTF-8 bytes for strings, and a varint count then the items
+/// (each object key as a string) for arrays and objects.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Returns the bytes the document holds on the heap: strings, arrays
+    /// and objects.
+    pub(crate) fn heap_size(&self) -> usize {
+        match self {
+            JsonValue::String(s) => s.capacity(),
+            JsonValue::Array(items) => {
+                items.capacity() * std::mem::size_of::<JsonValue>()
+                    + items.iter().map(JsonValue::heap_size).sum::<usize>()
+            }
+            JsonValue::Object(fields) => {
+                fields.capacity() * std::mem::size_of::<(String, JsonValue)>()
+                    + fields
+                        .iter()
+                        .map(|(key, value)| key.capacity() + value.heap_size())
+                        .sum::<usize>()
+            }
+            _ => 0,
+        }
+    }
+
+    /// Parses JSON text.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            text: text.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.pos != parser.text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// Returns the node at `path`, or None if the path leads nowhere.
+    pub fn get(&self, path: &JsonPath) -> Option<&JsonValue> {
+        path.steps
+            .iter()
+            .try_fold(self, |node, step| match (node, step) {
+                (JsonValue::Object(members), PathStep::Key(key)) => {
+                    members.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+                }
+                (JsonValue::Array(items), PathStep::Index(i)) => items.get(*i),
+                _ => None,
+            })
+    }
+
+    /// Converts the node to a Value: scalars to the matching scalar Value
+    /// (integers to BigInt), arrays and objects to `Value::Json`.
+    pub fn to_value(&self) -> Value {
+        match self {
+            JsonValue::Null => Value::Null,
+            JsonValue::Bool(b) => Value::Boolean(*b),
+            JsonValue::Int(v) => Value::BigInt(*v),
+            JsonValue::Double(v) => Value::Double(*v),
+            JsonValue::String(s) => Value::String(s.clone()),
+            JsonValue::Array(_) | JsonValue::Object(_) => Value::Json(self.clone()),
+        }
+    }
+
+    /// Returns the binary encoding of the document.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    /// Decodes a document from exactly the bytes of its binary encoding.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data, pos: 0 };
+        let value = reader.value(0)?;
+        (reader.pos == data.len()).then_some(value)
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            JsonValue::Null => out.push(TAG_NULL),
+            JsonValue::Bool(false) => out.push(TAG_FALSE),
+            JsonValue::Bool(true) => out.push(TAG_TRUE),
+            JsonValue::Int(v) => {
+                out.push(TAG_INT);
+                push_varint(out, ((v << 1) ^ (v >> 63)) as u64);
+            }
+            JsonValue::Double(v) => {
+                out.push(TAG_DOUBLE);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            JsonValue::String(s) => {
+                out.push(TAG_STRING);
+                push_str(out, s);
+            }
+            JsonValue::Array(items) => {
+                out.push(TAG_ARRAY);
+                push_varint(out, items.len() as u64);
+                for item in items {
+                    item.encode(out);
+                }
+            }
+            JsonValue::Object(members) => {
+                out.push(TAG_OBJECT);
+                push_varint(out, members.len() as u64);
+                for (key, value) in members {
+                    push_str(out, key);
+                    value.encode(out);
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for JsonValue {
+    type Err = CrioError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Self::parse(text)
+    }
+}
+
+impl fmt::Display for JsonValue {
+    /// Writes the document as compact JSON text.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(b) => write!(f, "{}", b),
+            JsonValue::Int(v) => write!(f, "{}", v),
+            // Debug keeps a fraction or exponent, so the text reads back as
+            // a double
+            JsonValue::Double(v) if v.is_finite() => write!(f, "{:?}", v),
+            JsonValue::Double(_) => write!(f, "null"),
+            JsonValue::String(s) => write_string(f, s),
+            JsonValue::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// One step of a JsonPath.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PathStep {
+    /// An object member
+    Key(String),
+    /// An array element
+    Index(usize),
+}
+
+/// A path into a JSON document, such as `a.b` or `items[0].id`: object keys
+/// separated by dots, each optionally followed by array indexes in
+/// brackets. A path may also start with an index, as in `[2].name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    steps: Vec<PathStep>,
+}
+
+impl JsonPath {
+    /// Parses a path.
+    pub fn parse(path: &str) -> Result<Self> {
+        let invalid = || CrioError::InvalidJsonPath(path.to_string());
+        let mut steps = Vec::new();
+        for (i, segment) in path.split('.').enumerate() {
+            let (key, mut indexes) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+            if !key.is_empty() {
+                steps.push(PathStep::Key(key.to_string()));
+            } else if i > 0 || indexes.is_empty() {
+                return Err(invalid());
+            }
+            while !indexes.is_empty() {
+                let (index, rest) = indexes[1..].split_once(']').ok_or_else(invalid)?;
+                if !index.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                if !rest.is_empty() && !rest.starts_with('[') {
+                    return Err(invalid());
+                }
+                steps.push(PathStep::Index(index.parse().map_err(|_| invalid())?));
+                indexes = rest;
+            }
+        }
+        Ok(Self { steps })
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            match step {
+                PathStep::Key(key) if i > 0 => write!(f, ".{}", key)?,
+                PathStep::Key(key) => write!(f, "{}", key)?,
+                PathStep::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn push_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn push_str(out: &mut Vec<u8>, s: &str) {
+    push_varint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Recursive descent parser over JSON text.
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &str) -> CrioError {
+        CrioError::InvalidJson(format!("{} at byte {}", reason, self.pos))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    /// Consumes `byte` after any whitespace, if it is next.
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        self.eat_byte(byte)
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<()> {
+        if self.eat(byte) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: JsonValue) -> Result<JsonValue> {
+        if self.text[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<JsonValue> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'n') => self.literal("null", JsonValue::Null),
+            Some(b't') => self.literal("true", JsonValue::Bool(true)),
+            Some(b'f') => self.literal("false", JsonValue::Bool(false)),
+            Some(b'"') => self.string().map(JsonValue::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value(depth + 1)?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(JsonValue::Array(items))
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut members: Vec<(String, JsonValue)> = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        let value = self.value(depth + 1)?;
+                        match members.iter_mut().find(|(k, _)| *k == key) {
+                            Some(member) => member.1 = value,
+                            None => members.push((key, value)),
+                        }
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(JsonValue::Object(members))
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn number(&mut self) -> Result<JsonValue> {
+        let start = self.pos;
+        self.eat_byte(b'-');
+        match self.digits() {
+            0 => return Err(self.error("invalid number")),
+            n if n > 1 && self.text[self.pos - n] == b'0' => {
+                return Err(self.error("leading zero in number"))
+            }
+            _ => {}
+        }
+        let mut integral = true;
+        if self.eat_byte(b'.') {
+            integral = false;
+            if self.digits() == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if self.eat_byte(b'e') || self.eat_byte(b'E') {
+            integral = false;
+            let _ = self.eat_byte(b'+') || self.eat_byte(b'-');
+            if self.digits() == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+
+        // The number is ASCII, so this can't split a character
+        let text = std::str::from_utf8(&self.text[start..self.pos]).unwrap();
+        if integral {
+            if let Ok(v) = text.parse() {
+                return Ok(JsonValue::Int(v));
+            }
+        }
+        match text.parse::<f64>() {
+            Ok(v) if v.is_finite() => Ok(JsonValue::Double(v)),
+            _ => Err(self.error("number out of range")),
+        }
+    }
+
+    /// Consumes `byte` if it is next, without skipping whitespace.
+    fn eat_byte(&mut self, byte: u8) -> bool {
+        let found = self.peek() == Some(byte);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Consumes a run of digits and returns its length.
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn string(&mut self) -> Result<String> {
+        if self.peek() != Some(b'"') {
+            return Err(self.error("expected string"));
+        }
+        self.pos += 1;
+        let mut s = Vec::new();
+        loop {
+            let b = self
+                .peek()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+                    let c = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    s.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                b if b < 0x20 => return Err(self.error("control character in string")),
+                b => s.push(b),
+            }
+        }
+        // The input was a &str, so unescaped bytes are valid UTF-8
+        Ok(String::from_utf8(s).unwrap())
+    }
+
+    /// Reads the hex digits of a `\u` escape, and a second escape after a
+    /// high surrogate.
+    fn unicode_escape(&mut self) -> Result<char> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if !self.text[self.pos..].starts_with(b"\\u") {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.pos += 2;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid code point"))
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .and_then(|d| u32::from_str_radix(d, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+}
+
+/// Decoder for the binary encoding. Returns None on malformed input.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let b = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b < 0x80 {
+                return Some(v);
+            }
+        }
+        None
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.varint()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).ok()
+    }
+
+    fn value(&mut self, depth: usize) -> Option<JsonValue> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        Some(match self.byte()? {
+            TAG_NULL => JsonValue::Null,
+            TAG_FALSE => JsonValue::Bool(false),
+            TAG_TRUE => JsonValue::Bool(true),
+            TAG_INT => {
+                let v = self.varint()?;
+                JsonValue::Int((v >> 1) as i64 ^ -((v & 1) as i64))
+            }
+            TAG_DOUBLE => JsonValue::Double(f64::from_le_bytes(self.bytes(8)?.try_into().ok()?)),
+            TAG_STRING => JsonValue::String(self.string()?),
+            TAG_ARRAY => {
+                let count = self.varint()?;
+                // Every item takes at least a byte, which bounds a corrupt count
+                let mut items = Vec::with_capacity(count.min(self.remaining()) as usize);
+                for _ in 0..count {
+                    items.push(self.value(depth + 1)?);
+                }
+                JsonValue::Array(items)
+            }
+            TAG_OBJECT => {
+                let count = self.varint()?;
+                let mut members = Vec::with_capacity(count.min(self.remaining()) as usize);
+                for _ in 0..count {
+                    let key = self.string()?;
+                    members.push((key, self.value(depth + 1)?));
+                }
+                JsonValue::Object(members)
+            }
+            _ => return None,
+        })
+    }
+
+    fn remaining(&self) -> u64 {
+        (self.data.len() - self.pos) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> JsonPath {
+        JsonPath::parse(path).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let doc = JsonValue::parse(
+            r#" { "name": "ada", "age": 36, "score": 9.5, "tags": ["a", "b\n\u00e9\ud83d\ude00"],
+                  "address": {"city": "London", "zip": null}, "admin": false, "big": 1e400 } "#,
+        );
+        assert!(doc.is_err());
+
+        let doc = JsonValue::parse(
+            r#" { "name": "ada", "age": 36, "score": 9.5, "tags": ["a", "b\n\u00e9\ud83d\ude00"],
+                  "address": {"city": "London", "zip": null}, "admin": false, "n": -12e2 } "#,
+        )
+        .unwrap();
+        assert_eq!(doc.get(&path("age")), Some(&JsonValue::Int(36)));
+        assert_eq!(doc.get(&path("n")), Some(&JsonValue::Double(-1200.0)));
+        assert_eq!(
+            doc.get(&path("tags[1]")),
+            Some(&JsonValue::String("b\né😀".into()))
+        );
+        assert_eq!(
+            doc.to_string(),
+            r#"{"name":"ada","age":36,"score":9.5,"tags":["a","b\né😀"],"address":{"city":"London","zip":null},"admin":false,"n":-1200.0}"#
+        );
+        assert_eq!(JsonValue::parse(&doc.to_string()).unwrap(), doc);
+
+        // The last of repeated keys wins
+        let doc = JsonValue::parse(r#"{"a": 1, "a": 2}"#).unwrap();
+        assert_eq!(
+            doc,
+            JsonValue::Object(vec![("a".into(), JsonValue::Int(2))])
+        );
+
+        for invalid in [
+            "",
+            "{",
+            "[1,]",
+            "01",
+            "1.",
+            ".5",
+            "1e",
+            "tru",
+            "\"a",
+            "{\"a\" 1}",
+            "1 2",
+            "-",
+            "\"\\x\"",
+        ] {
+            assert!(JsonValue::parse(invalid).is_err(), "{}", invalid);
+        }
+        let deep = "[".repeat(MAX_DEPTH + 2) + &"]".repeat(MAX_DEPTH + 2);
+        assert!(JsonValue::parse(&deep).is_err());
+    }
+
+    #[test]
+    fn test_paths() {
+        let doc = JsonValue::parse(r#"{"a": {"b": [10, {"c": true}]}, "x.y": 1}"#).unwrap();
+        assert_eq!(doc.get(&path("a.b[0]")), Some(&JsonValue::Int(10)));
+        assert_eq!(doc.get(&path("a.b[1].c")), Some(&JsonValue::Bool(true)));
+        assert_eq!(doc.get(&path("a.b[2]")), None);
+        assert_eq!(doc.get(&path("a.c")), None);
+        assert_eq!(doc.get(&path("a[0]")), None);
+
+        let array = JsonValue::parse(r#"[[1, 2], {"k": "v"}]"#).unwrap();
+        assert_eq!(array.get(&path("[0][1]")), Some(&JsonValue::Int(2)));
+        assert_eq!(
+            array.get(&path("[1].k")),
+            Some(&JsonValue::String("v".into()))
+        );
+
+        for p in ["a.b[1].c", "[0][1]", "x"] {
+            assert_eq!(path(p).to_string(), p);
+        }
+        for invalid in ["", "a..b", "a.", "a[", "a[x]", "a[1]b", ".a", "a.[0]"] {
+            assert!(JsonPath::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let doc = JsonValue::parse(
+            r#"{"id": -300, "max": 9223372036854775807, "ratio": 0.25, "s": "héllo",
+                "list": [null, true, false, [], {}]}"#,
+        )
+        .unwrap();
+        let bytes = doc.to_bytes();
+        assert_eq!(JsonValue::from_bytes(&bytes), Some(doc));
+        assert!(bytes.len() < 80);
+
+        // Truncated, padded or corrupt encodings are rejected
+        assert_eq!(JsonValue::from_bytes(&bytes[..bytes.len() - 1]), None);
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert_eq!(JsonValue::from_bytes(&padded), None);
+        assert_eq!(
+            JsonValue::from_bytes(&[TAG_ARRAY, 0xff, 0xff, 0xff, 0x0f]),
+            None
+        );
+        assert_eq!(JsonValue::from_bytes(&[9]), None);
+        assert_eq!(JsonValue::from_bytes(&[]), None);
+    }
+}
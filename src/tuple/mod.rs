@@ -1,9 +1,16 @@
+mod collation;
 mod data_type;
+mod interval;
+mod json;
 mod schema;
+#[allow(clippy::module_inception)]
 mod tuple;
 mod value;
 
+pub use collation::Collation;
 pub use data_type::DataType;
-pub use schema::{Column, Schema};
+pub use interval::{extract, timestamp_from_date, Interval, TimeField};
+pub use json::{JsonPath, JsonValue};
+pub use schema::{Column, ColumnDefault, Schema};
 pub use tuple::{Tuple, TupleBuilder};
 pub use value::Value;
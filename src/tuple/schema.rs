@@ -1,10 +1,63 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::DataType;
+use super::{Collation, DataType, Value};
+
+const NULLABLE_FLAG: u8 = 0x01;
+const DEFAULT_FLAG: u8 = 0x02;
+const COLLATION_FLAG: u8 = 0x04;
+
+const DEFAULT_NULL: u8 = 0;
+const DEFAULT_VALUE: u8 = 1;
+const DEFAULT_CURRENT_TIMESTAMP: u8 = 2;
+
+/// A column default, materialized when a row does not set the column.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColumnDefault {
+    /// A constant value
+    Value(Value),
+
+    /// The time of the insert (TIMESTAMP columns only)
+    CurrentTimestamp,
+}
+
+impl ColumnDefault {
+    /// Produces the value to store.
+    pub fn evaluate(&self) -> Value {
+        match self {
+            ColumnDefault::Value(value) => value.clone(),
+            ColumnDefault::CurrentTimestamp => {
+                let micros = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_micros() as i64);
+                Value::Timestamp(micros)
+            }
+        }
+    }
+}
+
+impl From<Value> for ColumnDefault {
+    fn from(value: Value) -> Self {
+        ColumnDefault::Value(value)
+    }
+}
+
+impl fmt::Display for ColumnDefault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnDefault::Value(value) => write!(f, "{}", value),
+            ColumnDefault::CurrentTimestamp => write!(f, "CURRENT_TIMESTAMP"),
+        }
+    }
+}
 
 /// Represents a single column in a table schema.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Column {
     /// Column name
     name: String,
@@ -16,7 +69,16 @@ pub struct Column {
     nullable: bool,
 
     /// Column position in the schema (0-indexed)
+    #[cfg_attr(feature = "serde", serde(skip))]
     ordinal: usize,
+
+    /// Value used when a row does not set the column
+    #[cfg_attr(feature = "serde", serde(default))]
+    default: Option<ColumnDefault>,
+
+    /// How string values are ordered and compared
+    #[cfg_attr(feature = "serde", serde(default))]
+    collation: Collation,
 }
 
 impl Column {
@@ -27,9 +89,30 @@ impl Column {
             data_type,
             nullable,
             ordinal: 0, // Will be set by Schema
+            default: None,
+            collation: Collation::Binary,
         }
     }
 
+    /// Sets the collation used to compare the column's strings.
+    pub fn with_collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    /// Sets the column default. Constant defaults are cast to the column type
+    /// where possible; use `has_valid_default` to check the result.
+    pub fn with_default(mut self, default: impl Into<ColumnDefault>) -> Self {
+        let default = match default.into() {
+            ColumnDefault::Value(value) => {
+                ColumnDefault::Value(value.cast(&self.data_type).unwrap_or(value))
+            }
+            default => default,
+        };
+        self.default = Some(default);
+        self
+    }
+
     /// Returns the column name.
     pub fn name(&self) -> &str {
         &self.name
@@ -50,6 +133,42 @@ impl Column {
         self.ordinal
     }
 
+    /// Returns the column default, if any.
+    pub fn default(&self) -> Option<&ColumnDefault> {
+        self.default.as_ref()
+    }
+
+    /// Returns the column collation.
+    pub fn collation(&self) -> Collation {
+        self.collation
+    }
+
+    /// Compares two values of this column, ordering strings by the column
+    /// collation.
+    pub fn compare(&self, a: &Value, b: &Value) -> Option<Ordering> {
+        a.compare_collated(b, self.collation)
+    }
+
+    /// Returns the value a row gets when it does not set this column: the
+    /// evaluated default, or NULL when there is none.
+    pub fn default_value(&self) -> Value {
+        self.default
+            .as_ref()
+            .map_or(Value::Null, ColumnDefault::evaluate)
+    }
+
+    /// Returns true unless the default cannot be stored in this column: a
+    /// value of another type, a string that is too long, NULL in a NOT NULL
+    /// column, or CURRENT_TIMESTAMP on a non-TIMESTAMP column.
+    pub fn has_valid_default(&self) -> bool {
+        match &self.default {
+            None => true,
+            Some(ColumnDefault::Value(Value::Null)) => self.nullable,
+            Some(ColumnDefault::Value(value)) => value.serialize(&self.data_type).is_some(),
+            Some(ColumnDefault::CurrentTimestamp) => self.data_type == DataType::Timestamp,
+        }
+    }
+
     /// Returns the fixed size of this column, or None for variable-length types.
     pub fn fixed_size(&self) -> Option<usize> {
         self.data_type.fixed_size()
@@ -61,7 +180,13 @@ impl Column {
     }
 
     /// Serializes the column definition to bytes.
-    /// Format: name_len (2 bytes) + name + data_type + nullable (1 byte)
+    /// Format: name_len (2 bytes) + name + data_type + flags (1 byte) + [default]
+    /// + [collation]
+    ///
+    /// Flags bit 0 marks the column nullable, bit 1 says a default follows:
+    /// a kind byte (0 = NULL, 1 = value, 2 = CURRENT_TIMESTAMP), then for
+    /// values the value serialized as the column type. Bit 2 says a collation
+    /// ID byte follows; binary columns omit it.
     pub fn serialize(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
 
@@ -73,8 +198,33 @@ impl Column {
         // Data type
         bytes.extend(self.data_type.serialize());
 
-        // Nullable flag
-        bytes.push(if self.nullable { 1 } else { 0 });
+        // Flags
+        let mut flags = 0;
+        if self.nullable {
+            flags |= NULLABLE_FLAG;
+        }
+        if self.default.is_some() {
+            flags |= DEFAULT_FLAG;
+        }
+        if self.collation != Collation::Binary {
+            flags |= COLLATION_FLAG;
+        }
+        bytes.push(flags);
+
+        // Default
+        match &self.default {
+            None => {}
+            Some(ColumnDefault::Value(Value::Null)) => bytes.push(DEFAULT_NULL),
+            Some(ColumnDefault::Value(value)) => {
+                bytes.push(DEFAULT_VALUE);
+                bytes.extend(value.serialize(&self.data_type).unwrap_or_default());
+            }
+            Some(ColumnDefault::CurrentTimestamp) => bytes.push(DEFAULT_CURRENT_TIMESTAMP),
+        }
+
+        if self.collation != Collation::Binary {
+            bytes.push(self.collation.id());
+        }
 
         bytes
     }
@@ -102,27 +252,66 @@ impl Column {
         let (data_type, dt_size) = DataType::deserialize(&data[offset..])?;
         offset += dt_size;
 
-        // Nullable flag
+        // Flags
         if data.len() < offset + 1 {
             return None;
         }
-        let nullable = data[offset] != 0;
+        let flags = data[offset];
         offset += 1;
 
+        // Default
+        let default = if flags & DEFAULT_FLAG != 0 {
+            let kind = *data.get(offset)?;
+            offset += 1;
+            Some(match kind {
+                DEFAULT_NULL => ColumnDefault::Value(Value::Null),
+                DEFAULT_VALUE => {
+                    let (value, size) = Value::deserialize(&data[offset..], &data_type)?;
+                    offset += size;
+                    ColumnDefault::Value(value)
+                }
+                DEFAULT_CURRENT_TIMESTAMP => ColumnDefault::CurrentTimestamp,
+                _ => return None,
+            })
+        } else {
+            None
+        };
+
+        // Collation
+        let collation = if flags & COLLATION_FLAG != 0 {
+            let id = *data.get(offset)?;
+            offset += 1;
+            Collation::from_id(id)?
+        } else {
+            Collation::Binary
+        };
+
         Some((
             Column {
                 name,
                 data_type,
-                nullable,
+                nullable: flags & NULLABLE_FLAG != 0,
                 ordinal: 0, // Will be set by Schema
+                default,
+                collation,
             },
             offset,
         ))
     }
 }
 
+impl Eq for Column {}
+
 /// Represents the schema of a table, defining its columns and structure.
+///
+/// With the `serde` feature a schema is (de)serialized as its columns only;
+/// everything else is derived from them again by `Schema::new`.
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "SchemaDef", into = "SchemaDef")
+)]
 pub struct Schema {
     /// Ordered list of columns
     columns: Vec<Column>,
@@ -161,7 +350,7 @@ impl Schema {
         }
 
         // Null bitmap: 1 bit per column, rounded up to bytes
-        let null_bitmap_size = (columns.len() + 7) / 8;
+        let null_bitmap_size = columns.len().div_ceil(8);
 
         Self {
             columns,
@@ -204,6 +393,16 @@ impl Schema {
         self.columns.iter()
     }
 
+    /// Returns the first column whose default cannot be stored in it.
+    pub fn invalid_default(&self) -> Option<&Column> {
+        self.columns.iter().find(|c| !c.has_valid_default())
+    }
+
+    /// Returns the value each column gets when a row does not set it.
+    pub fn default_values(&self) -> Vec<Value> {
+        self.columns.iter().map(Column::default_value).collect()
+    }
+
     /// Returns the total size of all fixed-length columns.
     pub fn fixed_size(&self) -> usize {
         self.fixed_size
@@ -293,6 +492,29 @@ impl PartialEq for Schema {
 
 impl Eq for Schema {}
 
+/// The serialized form of a Schema.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SchemaDef {
+    columns: Vec<Column>,
+}
+
+#[cfg(feature = "serde")]
+impl From<SchemaDef> for Schema {
+    fn from(def: SchemaDef) -> Self {
+        Schema::new(def.columns)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Schema> for SchemaDef {
+    fn from(schema: Schema) -> Self {
+        SchemaDef {
+            columns: schema.columns,
+        }
+    }
+}
+
 /// Builder for constructing schemas fluently.
 pub struct SchemaBuilder {
     columns: Vec<Column>,
@@ -318,6 +540,30 @@ impl SchemaBuilder {
         self
     }
 
+    /// Adds a non-nullable column with a default.
+    pub fn column_with_default(
+        mut self,
+        name: impl Into<String>,
+        data_type: DataType,
+        default: impl Into<ColumnDefault>,
+    ) -> Self {
+        self.columns
+            .push(Column::new(name, data_type, false).with_default(default));
+        self
+    }
+
+    /// Adds a non-nullable string column compared under `collation`.
+    pub fn collated_column(
+        mut self,
+        name: impl Into<String>,
+        data_type: DataType,
+        collation: Collation,
+    ) -> Self {
+        self.columns
+            .push(Column::new(name, data_type, false).with_collation(collation));
+        self
+    }
+
     /// Adds a column with explicit nullability.
     pub fn add_column(
         mut self,
@@ -418,6 +664,46 @@ mod tests {
         assert_eq!(schema, recovered);
     }
 
+    #[test]
+    fn test_column_defaults() {
+        let schema = Schema::new(vec![
+            Column::new("id", DataType::Integer, false),
+            Column::new("score", DataType::BigInt, false).with_default(Value::Integer(7)),
+            Column::new("tag", DataType::VarChar(8), false).with_default(Value::from("new")),
+            Column::new("created", DataType::Timestamp, true)
+                .with_default(ColumnDefault::CurrentTimestamp),
+        ]);
+
+        // Constant defaults are cast to the column type
+        assert_eq!(
+            schema.column(1).unwrap().default(),
+            Some(&ColumnDefault::Value(Value::BigInt(7)))
+        );
+        assert_eq!(schema.invalid_default(), None);
+
+        let values = schema.default_values();
+        assert_eq!(values[0], Value::Null);
+        assert_eq!(values[1], Value::BigInt(7));
+        assert_eq!(values[2], Value::String("new".into()));
+        assert!(matches!(values[3], Value::Timestamp(t) if t > 0));
+
+        let recovered = Schema::deserialize(&schema.serialize()).unwrap();
+        assert_eq!(schema, recovered);
+
+        let invalid = [
+            Column::new("a", DataType::Integer, false).with_default(Value::from("x")),
+            Column::new("b", DataType::VarChar(2), false).with_default(Value::from("long")),
+            Column::new("c", DataType::Integer, false).with_default(Value::Null),
+            Column::new("d", DataType::BigInt, false).with_default(ColumnDefault::CurrentTimestamp),
+        ];
+        for column in invalid {
+            assert!(!column.has_valid_default(), "{:?}", column);
+        }
+        assert!(Column::new("e", DataType::Integer, true)
+            .with_default(Value::Null)
+            .has_valid_default());
+    }
+
     #[test]
     fn test_projection() {
         let schema = create_test_schema();
@@ -443,4 +729,79 @@ mod tests {
         assert_eq!(col.data_type(), recovered.data_type());
         assert_eq!(col.is_nullable(), recovered.is_nullable());
     }
+
+    #[test]
+    fn test_column_collation() {
+        let schema = Schema::builder()
+            .collated_column("name", DataType::VarChar(32), Collation::CaseInsensitive)
+            .column("code", DataType::VarChar(32))
+            .build();
+        let name = schema.column(0).unwrap();
+        assert_eq!(name.collation(), Collation::CaseInsensitive);
+        assert_eq!(
+            name.compare(&Value::String("ADA".into()), &Value::String("ada".into())),
+            Some(Ordering::Equal)
+        );
+        let code = schema.column(1).unwrap();
+        assert_eq!(
+            code.compare(&Value::String("ADA".into()), &Value::String("ada".into())),
+            Some(Ordering::Less)
+        );
+
+        // Binary columns serialize as before; others carry their collation
+        assert_eq!(
+            code.serialize(),
+            Column::new("code", DataType::VarChar(32), false).serialize()
+        );
+        let recovered = Schema::deserialize(&schema.serialize()).unwrap();
+        assert_eq!(recovered, schema);
+        assert_eq!(
+            recovered.column(0).unwrap().collation(),
+            Collation::CaseInsensitive
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_schema_serde_round_trip() {
+        use crate::tuple::Interval;
+
+        let schema = Schema::builder()
+            .column("id", DataType::Integer)
+            .collated_column("name", DataType::VarChar(32), Collation::CaseInsensitive)
+            .column_with_default(
+                "added",
+                DataType::Timestamp,
+                ColumnDefault::CurrentTimestamp,
+            )
+            .column_with_default("score", DataType::Double, Value::Double(0.5))
+            .build();
+
+        let json = serde_json::to_string(&schema).unwrap();
+        let decoded: Schema = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, schema);
+        assert_eq!(decoded.column_index("added"), Some(2));
+        assert_eq!(decoded.fixed_size(), schema.fixed_size());
+
+        // Defaults and collation may be left out
+        let decoded: Schema = serde_json::from_str(
+            r#"{"columns": [{"name": "id", "data_type": "Integer", "nullable": false},
+                            {"name": "tag", "data_type": {"VarChar": 8}, "nullable": true}]}"#,
+        )
+        .unwrap();
+        assert_eq!(decoded.column(1).unwrap().ordinal(), 1);
+        assert_eq!(
+            decoded.column(1).unwrap().data_type(),
+            &DataType::VarChar(8)
+        );
+
+        let values = vec![
+            Value::Null,
+            Value::Integer(-7),
+            Value::String("ada".into()),
+            Value::Interval(Interval::new(1, 2, 3)),
+        ];
+        let json = serde_json::to_string(&values).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<Value>>(&json).unwrap(), values);
+    }
 }
@@ -93,6 +93,11 @@ impl Tuple {
         }
     }
 
+    /// Consumes the tuple, returning its values.
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+
     /// Returns the number of columns/values in this tuple.
     pub fn len(&self) -> usize {
         self.values.len()
@@ -103,6 +108,21 @@ impl Tuple {
         self.values.is_empty()
     }
 
+    /// Returns about how many bytes the tuple takes in memory, for charging
+    /// rows executors hold to a `MemoryReservation`.
+    pub fn memory_size(&self) -> usize {
+        let heap: usize = self
+            .values
+            .iter()
+            .map(|value| match value {
+                Value::String(s) => s.capacity(),
+                Value::Json(json) => json.heap_size(),
+                _ => 0,
+            })
+            .sum();
+        std::mem::size_of::<Self>() + self.values.capacity() * std::mem::size_of::<Value>() + heap
+    }
+
     /// Serializes the tuple to bytes for storage.
     pub fn to_bytes(&self) -> Option<Vec<u8>> {
         self.serialize_values()
@@ -288,12 +308,12 @@ pub struct TupleBuilder {
 }
 
 impl TupleBuilder {
-    /// Creates a new tuple builder for the given schema.
+    /// Creates a new tuple builder for the given schema. Columns that are
+    /// never set keep their default, or NULL if they have none.
     pub fn new(schema: Arc<Schema>) -> Self {
-        let count = schema.column_count();
         Self {
+            values: schema.default_values(),
             schema,
-            values: vec![Value::Null; count],
             current_index: 0,
         }
     }
@@ -341,6 +361,7 @@ impl TupleBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tuple::DataType;
 
     fn create_test_schema() -> Arc<Schema> {
         Schema::builder()
@@ -402,6 +423,22 @@ mod tests {
         assert_eq!(tuple.value_by_name("email"), Some(&Value::Null));
     }
 
+    #[test]
+    fn test_tuple_builder_defaults() {
+        let schema = Schema::builder()
+            .column("id", DataType::Integer)
+            .column_with_default("status", DataType::VarChar(10), Value::from("active"))
+            .nullable_column("note", DataType::VarChar(10))
+            .build_arc();
+        let tuple = TupleBuilder::new(schema).set("id", 1i32).build();
+
+        assert_eq!(
+            tuple.value_by_name("status"),
+            Some(&Value::String("active".into()))
+        );
+        assert_eq!(tuple.value_by_name("note"), Some(&Value::Null));
+    }
+
     #[test]
     fn test_serialization_roundtrip() {
         let schema = create_test_schema();
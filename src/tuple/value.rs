@@ -1,11 +1,12 @@
 use std::cmp::Ordering;
 use std::fmt;
 
-use super::DataType;
+use super::{extract, Collation, DataType, Interval, JsonPath, JsonValue, TimeField};
 
 /// Represents a typed value that can be stored in a tuple.
 /// Each variant corresponds to a DataType and holds the actual data.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     /// Null value - can be any type
     Null,
@@ -36,6 +37,12 @@ pub enum Value {
 
     /// Timestamp value (microseconds since Unix epoch)
     Timestamp(i64),
+
+    /// Interval value
+    Interval(Interval),
+
+    /// JSON document
+    Json(JsonValue),
 }
 
 impl Value {
@@ -53,6 +60,8 @@ impl Value {
             Value::Double(_) => Some(DataType::Double),
             Value::String(s) => Some(DataType::VarChar(s.len() as u16)),
             Value::Timestamp(_) => Some(DataType::Timestamp),
+            Value::Interval(_) => Some(DataType::Interval),
+            Value::Json(_) => Some(DataType::Json),
         }
     }
 
@@ -111,6 +120,17 @@ impl Value {
 
             (Value::Timestamp(v), DataType::Timestamp) => Some(v.to_le_bytes().to_vec()),
 
+            (Value::Interval(v), DataType::Interval) => Some(v.to_bytes().to_vec()),
+
+            (Value::Json(doc), DataType::Json) => {
+                let encoded = doc.to_bytes();
+                // Format: length (2 bytes) + encoded document
+                let len = u16::try_from(encoded.len()).ok()?;
+                let mut result = len.to_le_bytes().to_vec();
+                result.extend(encoded);
+                Some(result)
+            }
+
             // Type coercions
             (Value::TinyInt(v), DataType::SmallInt) => Some((*v as i16).to_le_bytes().to_vec()),
             (Value::TinyInt(v), DataType::Integer) => Some((*v as i32).to_le_bytes().to_vec()),
@@ -217,10 +237,24 @@ impl Value {
                 ]);
                 Some((Value::Timestamp(v), 8))
             }
+
+            DataType::Interval => {
+                let v = Interval::from_bytes(data)?;
+                Some((Value::Interval(v), Interval::SIZE))
+            }
+
+            DataType::Json => {
+                if data.len() < 2 {
+                    return None;
+                }
+                let len = u16::from_le_bytes([data[0], data[1]]) as usize;
+                let doc = JsonValue::from_bytes(data.get(2..2 + len)?)?;
+                Some((Value::Json(doc), 2 + len))
+            }
         }
     }
 
-    /// Compares two values for ordering.
+    /// Compares two values for ordering. Strings compare in binary collation.
     /// Returns None if the values are not comparable (different types or null).
     pub fn compare(&self, other: &Value) -> Option<Ordering> {
         match (self, other) {
@@ -236,6 +270,7 @@ impl Value {
             (Value::Double(a), Value::Double(b)) => a.partial_cmp(b),
             (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
             (Value::Timestamp(a), Value::Timestamp(b)) => Some(a.cmp(b)),
+            (Value::Interval(a), Value::Interval(b)) => Some(a.compare(b)),
 
             // Cross-type numeric comparisons (promote to larger type)
             (Value::TinyInt(a), Value::SmallInt(b)) => Some((*a as i16).cmp(b)),
@@ -258,6 +293,65 @@ impl Value {
         }
     }
 
+    /// Compares two values like `compare`, ordering strings by `collation`.
+    pub fn compare_collated(&self, other: &Value, collation: Collation) -> Option<Ordering> {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => Some(collation.compare(a, b)),
+            _ => self.compare(other),
+        }
+    }
+
+    /// Adds two values: an interval to a timestamp (either way round) or two
+    /// intervals. Returns None for other types, NULLs and overflow.
+    pub fn checked_add(&self, other: &Value) -> Option<Value> {
+        match (self, other) {
+            (Value::Timestamp(t), Value::Interval(i))
+            | (Value::Interval(i), Value::Timestamp(t)) => i.add_to(*t).map(Value::Timestamp),
+            (Value::Interval(a), Value::Interval(b)) => a.checked_add(b).map(Value::Interval),
+            _ => None,
+        }
+    }
+
+    /// Subtracts two values: an interval from a timestamp, an interval from
+    /// an interval, or a timestamp from a timestamp, which gives an interval
+    /// of exact microseconds. Returns None for other types, NULLs and
+    /// overflow.
+    pub fn checked_sub(&self, other: &Value) -> Option<Value> {
+        match (self, other) {
+            (Value::Timestamp(t), Value::Interval(i)) => {
+                i.checked_neg()?.add_to(*t).map(Value::Timestamp)
+            }
+            (Value::Interval(a), Value::Interval(b)) => {
+                a.checked_add(&b.checked_neg()?).map(Value::Interval)
+            }
+            (Value::Timestamp(a), Value::Timestamp(b)) => {
+                Some(Value::Interval(Interval::from_micros(a.checked_sub(*b)?)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns one field of a timestamp as an Integer, or None for
+    /// non-timestamp values.
+    pub fn extract(&self, field: TimeField) -> Option<Value> {
+        match self {
+            Value::Timestamp(t) => Some(Value::Integer(extract(field, *t) as i32)),
+            _ => None,
+        }
+    }
+
+    /// Returns the node of a JSON document at `path` (see `JsonPath`), as
+    /// `json_get('a.b')` would: a scalar Value for a scalar, a Json value for
+    /// an array or object, and Null if the path leads nowhere. Returns None
+    /// for non-JSON values and invalid paths.
+    pub fn json_get(&self, path: &str) -> Option<Value> {
+        let Value::Json(doc) = self else {
+            return None;
+        };
+        let path = JsonPath::parse(path).ok()?;
+        Some(doc.get(&path).map_or(Value::Null, JsonValue::to_value))
+    }
+
     /// Attempts to cast this value to the target type.
     /// Returns None if the cast is not possible.
     pub fn cast(&self, target: &DataType) -> Option<Value> {
@@ -301,6 +395,13 @@ impl Value {
                 }
             }
 
+            // JSON from and to its text
+            (Value::String(s), DataType::Json) => JsonValue::parse(s).ok().map(Value::Json),
+            (Value::Json(doc), DataType::VarChar(n)) => {
+                let text = doc.to_string();
+                (text.len() <= *n as usize).then_some(Value::String(text))
+            }
+
             // Same type - no conversion needed
             (v, dt) if v.infer_type().as_ref() == Some(dt) => Some(v.clone()),
 
@@ -322,6 +423,8 @@ impl fmt::Display for Value {
             Value::Double(v) => write!(f, "{}", v),
             Value::String(s) => write!(f, "'{}'", s),
             Value::Timestamp(v) => write!(f, "TIMESTAMP({})", v),
+            Value::Interval(v) => write!(f, "INTERVAL({})", v),
+            Value::Json(doc) => write!(f, "JSON({})", doc),
         }
     }
 }
@@ -375,6 +478,18 @@ impl From<String> for Value {
     }
 }
 
+impl From<Interval> for Value {
+    fn from(v: Interval) -> Self {
+        Value::Interval(v)
+    }
+}
+
+impl From<JsonValue> for Value {
+    fn from(v: JsonValue) -> Self {
+        Value::Json(v)
+    }
+}
+
 impl From<&str> for Value {
     fn from(v: &str) -> Self {
         Value::String(v.to_string())
@@ -384,6 +499,7 @@ impl From<&str> for Value {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tuple::timestamp_from_date;
 
     #[test]
     fn test_integer_serialization() {
@@ -434,6 +550,75 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_timestamp_interval_arithmetic() {
+        let day = 24 * 3600 * 1_000_000i64;
+        let start = Value::Timestamp(timestamp_from_date(2023, 3, 31));
+        let later = start
+            .checked_add(&Value::Interval(Interval::new(1, 2, 0)))
+            .unwrap();
+        assert_eq!(later.extract(TimeField::Month), Some(Value::Integer(5)));
+        assert_eq!(later.extract(TimeField::Day), Some(Value::Integer(2)));
+        assert_eq!(
+            later.checked_sub(&start),
+            Some(Value::Interval(Interval::from_micros(32 * day)))
+        );
+        // May 2 back one month is April 2, then two days back is March 31
+        assert_eq!(
+            later.checked_sub(&Value::Interval(Interval::new(1, 2, 0))),
+            Some(start.clone())
+        );
+        assert_eq!(start.checked_add(&Value::Integer(1)), None);
+        assert_eq!(Value::Integer(1).extract(TimeField::Year), None);
+
+        let interval = Value::Interval(Interval::new(2, -1, 5));
+        let bytes = interval.serialize(&DataType::Interval).unwrap();
+        assert_eq!(
+            Value::deserialize(&bytes, &DataType::Interval),
+            Some((interval, 16))
+        );
+    }
+
+    #[test]
+    fn test_json_value() {
+        let doc = Value::String(r#"{"user": {"id": 7, "tags": ["a"]}, "ok": true}"#.into())
+            .cast(&DataType::Json)
+            .unwrap();
+        assert_eq!(doc.json_get("user.id"), Some(Value::BigInt(7)));
+        assert_eq!(
+            doc.json_get("user.tags[0]"),
+            Some(Value::String("a".into()))
+        );
+        assert_eq!(doc.json_get("ok"), Some(Value::Boolean(true)));
+        assert_eq!(doc.json_get("user.name"), Some(Value::Null));
+        assert_eq!(
+            doc.json_get("user.tags"),
+            Some(Value::Json(JsonValue::Array(vec![JsonValue::String(
+                "a".into()
+            )])))
+        );
+        assert_eq!(doc.json_get("user..id"), None);
+        assert_eq!(Value::Integer(1).json_get("a"), None);
+
+        let bytes = doc.serialize(&DataType::Json).unwrap();
+        assert_eq!(
+            Value::deserialize(&bytes, &DataType::Json),
+            Some((doc.clone(), bytes.len()))
+        );
+        assert_eq!(
+            Value::deserialize(&bytes[..bytes.len() - 1], &DataType::Json),
+            None
+        );
+        assert_eq!(
+            doc.cast(&DataType::VarChar(100)),
+            Some(Value::String(
+                r#"{"user":{"id":7,"tags":["a"]},"ok":true}"#.into()
+            ))
+        );
+        assert_eq!(doc.cast(&DataType::VarChar(10)), None);
+        assert_eq!(Value::String("{".into()).cast(&DataType::Json), None);
+    }
+
     #[test]
     fn test_type_coercion() {
         let val = Value::TinyInt(10);
@@ -0,0 +1,161 @@
+/// Small, fast, seedable PRNG (SplitMix64). Workloads need reproducible
+/// sequences, not cryptographic quality.
+#[derive(Debug, Clone)]
+pub struct FastRng {
+    state: u64,
+}
+
+impl FastRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a float uniformly distributed in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns an integer uniformly distributed in `[0, bound)`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0);
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+/// How workload keys are chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyDistribution {
+    /// Every key equally likely
+    Uniform,
+    /// Skewed towards a few hot keys; YCSB uses a constant of 0.99
+    Zipfian(f64),
+}
+
+/// Draws keys in `[0, item_count)` from a `KeyDistribution`.
+///
+/// Zipfian keys follow Gray et al., "Quickly Generating Billion-Record
+/// Synthetic Databases", and are scrambled with a hash so hot keys spread
+/// across the key space instead of clustering at its start, as YCSB does.
+#[derive(Debug, Clone)]
+pub struct KeyGenerator {
+    item_count: u64,
+    zipfian: Option<ZipfianState>,
+}
+
+#[derive(Debug, Clone)]
+struct ZipfianState {
+    theta: f64,
+    alpha: f64,
+    zeta_n: f64,
+    eta: f64,
+}
+
+impl KeyGenerator {
+    pub fn new(item_count: u64, distribution: KeyDistribution) -> Self {
+        assert!(item_count > 0);
+        let zipfian = match distribution {
+            KeyDistribution::Uniform => None,
+            KeyDistribution::Zipfian(theta) => {
+                assert!(
+                    theta > 0.0 && theta < 1.0,
+                    "zipfian constant must be in (0, 1)"
+                );
+                let zeta_2 = zeta(2, theta);
+                let zeta_n = zeta(item_count, theta);
+                Some(ZipfianState {
+                    theta,
+                    alpha: 1.0 / (1.0 - theta),
+                    zeta_n,
+                    eta: (1.0 - (2.0 / item_count as f64).powf(1.0 - theta))
+                        / (1.0 - zeta_2 / zeta_n),
+                })
+            }
+        };
+        Self {
+            item_count,
+            zipfian,
+        }
+    }
+
+    /// Returns the number of keys drawn from.
+    pub fn item_count(&self) -> u64 {
+        self.item_count
+    }
+
+    pub fn next_key(&self, rng: &mut FastRng) -> u64 {
+        let Some(z) = &self.zipfian else {
+            return rng.below(self.item_count);
+        };
+
+        let u = rng.next_f64();
+        let uz = u * z.zeta_n;
+        let rank = if uz < 1.0 {
+            0
+        } else if uz < 1.0 + 0.5f64.powf(z.theta) {
+            1
+        } else {
+            let n = self.item_count as f64;
+            ((n * (z.eta * u - z.eta + 1.0).powf(z.alpha)) as u64).min(self.item_count - 1)
+        };
+        fnv_hash(rank) % self.item_count
+    }
+}
+
+fn zeta(n: u64, theta: f64) -> f64 {
+    (1..=n).map(|i| 1.0 / (i as f64).powf(theta)).sum()
+}
+
+/// FNV-1a over the bytes of `value`.
+fn fnv_hash(value: u64) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for byte in value.to_le_bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_is_reproducible() {
+        let mut a = FastRng::new(7);
+        let mut b = FastRng::new(7);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert!((0..1000).all(|_| a.below(10) < 10));
+    }
+
+    #[test]
+    fn test_zipfian_is_skewed() {
+        let generator = KeyGenerator::new(1000, KeyDistribution::Zipfian(0.99));
+        let mut rng = FastRng::new(1);
+        let mut counts = vec![0u32; 1000];
+        for _ in 0..100_000 {
+            counts[generator.next_key(&mut rng) as usize] += 1;
+        }
+
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        // The hottest 1% of keys take a large share of the accesses
+        let hot: u32 = counts[..10].iter().sum();
+        assert!(hot > 30_000, "hot keys drew only {}", hot);
+
+        let uniform = KeyGenerator::new(1000, KeyDistribution::Uniform);
+        let mut counts = vec![0u32; 1000];
+        for _ in 0..100_000 {
+            counts[uniform.next_key(&mut rng) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 0 && c < 250));
+    }
+}
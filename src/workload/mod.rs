@@ -0,0 +1,7 @@
+//! Standard workloads for measuring performance across releases.
+
+mod key_generator;
+mod workload_runner;
+
+pub use key_generator::*;
+pub use workload_runner::*;
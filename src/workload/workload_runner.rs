@@ -0,0 +1,454 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::buffer::BufferPoolManager;
+use crate::common::{CrioError, PageId, RecordId, Result};
+use crate::index::BTreeIndex;
+use crate::storage::table::TableHeap;
+
+use super::{FastRng, KeyDistribution, KeyGenerator};
+
+/// TPC-B rows are 100 bytes; history rows are 50.
+const TPCB_ROW_SIZE: usize = 100;
+const TPCB_HISTORY_ROW_SIZE: usize = 50;
+
+/// Structure a YCSB workload runs against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YcsbTarget {
+    /// Each record is a whole page, read and written through the buffer pool
+    BufferPool,
+    /// Records are tuples addressed by record ID
+    TableHeap,
+    /// Records are tuples in a table heap, found through a B+Tree on the key
+    BTree,
+}
+
+/// A YCSB-style key-value workload. Proportions must add up to 1.
+#[derive(Debug, Clone)]
+pub struct YcsbConfig {
+    /// Records loaded before the timed run
+    pub record_count: u32,
+    /// Operations in the timed run
+    pub operation_count: u64,
+    pub read_proportion: f64,
+    pub update_proportion: f64,
+    pub insert_proportion: f64,
+    /// How keys for reads and updates are chosen
+    pub distribution: KeyDistribution,
+    /// Bytes per record value
+    pub value_size: usize,
+}
+
+impl YcsbConfig {
+    /// Workload A: update heavy, 50% reads and 50% updates.
+    pub fn workload_a() -> Self {
+        Self::with_mix(0.5, 0.5, 0.0)
+    }
+
+    /// Workload B: read mostly, 95% reads and 5% updates.
+    pub fn workload_b() -> Self {
+        Self::with_mix(0.95, 0.05, 0.0)
+    }
+
+    /// Workload C: read only.
+    pub fn workload_c() -> Self {
+        Self::with_mix(1.0, 0.0, 0.0)
+    }
+
+    /// Workload D: 95% reads and 5% inserts. Unlike YCSB, reads keep drawing
+    /// from the loaded keys rather than favouring the latest inserts.
+    pub fn workload_d() -> Self {
+        Self::with_mix(0.95, 0.0, 0.05)
+    }
+
+    fn with_mix(read: f64, update: f64, insert: f64) -> Self {
+        Self {
+            record_count: 10_000,
+            operation_count: 100_000,
+            read_proportion: read,
+            update_proportion: update,
+            insert_proportion: insert,
+            distribution: KeyDistribution::Zipfian(0.99),
+            value_size: 100,
+        }
+    }
+}
+
+/// A TPC-B-like workload: every transaction moves an amount into an account
+/// and the owning teller and branch, then appends a history row.
+#[derive(Debug, Clone)]
+pub struct TpcbConfig {
+    /// Scale factor
+    pub branches: u32,
+    pub tellers_per_branch: u32,
+    pub accounts_per_branch: u32,
+    /// Transactions in the timed run
+    pub transaction_count: u64,
+}
+
+impl Default for TpcbConfig {
+    fn default() -> Self {
+        Self {
+            branches: 1,
+            tellers_per_branch: 10,
+            accounts_per_branch: 10_000,
+            transaction_count: 10_000,
+        }
+    }
+}
+
+/// Throughput and latency of one workload run.
+#[derive(Debug, Clone)]
+pub struct WorkloadReport {
+    pub name: String,
+    /// Operations (or transactions) completed
+    pub operations: u64,
+    /// Wall-clock time of the timed run
+    pub elapsed: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl WorkloadReport {
+    fn new(name: String, mut latencies: Vec<Duration>, elapsed: Duration) -> Self {
+        latencies.sort_unstable();
+        let percentile = |p: usize| {
+            if latencies.is_empty() {
+                Duration::ZERO
+            } else {
+                latencies[(latencies.len() - 1) * p / 100]
+            }
+        };
+        Self {
+            name,
+            operations: latencies.len() as u64,
+            elapsed,
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+            max: percentile(100),
+        }
+    }
+
+    /// Returns operations per second.
+    pub fn throughput(&self) -> f64 {
+        self.operations as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for WorkloadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ops in {:.2?} ({:.0} ops/s), latency p50 {:.1?} p95 {:.1?} p99 {:.1?} max {:.1?}",
+            self.name,
+            self.operations,
+            self.elapsed,
+            self.throughput(),
+            self.p50,
+            self.p95,
+            self.p99,
+            self.max
+        )
+    }
+}
+
+/// WorkloadRunner loads and runs standard workloads against a buffer pool so
+/// performance can be compared across releases. Loading is not timed; each
+/// operation of the run is timed individually.
+///
+/// Runs are deterministic for a given seed, apart from timing.
+pub struct WorkloadRunner {
+    bpm: Arc<BufferPoolManager>,
+    seed: u64,
+    /// Table IDs handed to the heaps of successive runs
+    next_table_id: u32,
+}
+
+impl WorkloadRunner {
+    pub fn new(bpm: Arc<BufferPoolManager>) -> Self {
+        Self {
+            bpm,
+            seed: 42,
+            next_table_id: 1,
+        }
+    }
+
+    /// Sets the seed used for keys, operation mix and values.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Loads `config.record_count` records into `target` and runs the
+    /// operation mix against them. Inserts add new keys past the loaded ones;
+    /// reads and updates draw from the loaded keys.
+    pub fn run_ycsb(&mut self, config: &YcsbConfig, target: YcsbTarget) -> Result<WorkloadReport> {
+        let mut rng = FastRng::new(self.seed);
+        let mut store = self.load_ycsb(config, target, &mut rng)?;
+        let keys = KeyGenerator::new(config.record_count as u64, config.distribution);
+        let mut value = vec![0u8; config.value_size];
+
+        let mut latencies = Vec::with_capacity(config.operation_count as usize);
+        let started = Instant::now();
+        for _ in 0..config.operation_count {
+            let choice = rng.next_f64();
+            let op_started = Instant::now();
+            if choice < config.read_proportion {
+                store.read(keys.next_key(&mut rng))?;
+            } else if choice < config.read_proportion + config.update_proportion {
+                value.fill(rng.next_u64() as u8);
+                store.update(keys.next_key(&mut rng), &value)?;
+            } else {
+                store.insert(&value)?;
+            }
+            latencies.push(op_started.elapsed());
+        }
+
+        let name = format!("ycsb {:?}", target);
+        Ok(WorkloadReport::new(name, latencies, started.elapsed()))
+    }
+
+    fn load_ycsb(
+        &mut self,
+        config: &YcsbConfig,
+        target: YcsbTarget,
+        rng: &mut FastRng,
+    ) -> Result<YcsbStore> {
+        let mut store = match target {
+            YcsbTarget::BufferPool => YcsbStore::Pages {
+                bpm: Arc::clone(&self.bpm),
+                pages: Vec::new(),
+            },
+            YcsbTarget::TableHeap => YcsbStore::Heap {
+                heap: self.create_heap()?,
+                rids: Vec::new(),
+            },
+            YcsbTarget::BTree => YcsbStore::Indexed {
+                heap: self.create_heap()?,
+                index: BTreeIndex::new(Arc::clone(&self.bpm))?,
+                next_key: 0,
+            },
+        };
+
+        let mut value = vec![0u8; config.value_size];
+        for _ in 0..config.record_count {
+            value.fill(rng.next_u64() as u8);
+            store.insert(&value)?;
+        }
+        Ok(store)
+    }
+
+    /// Loads the branch, teller and account tables and runs
+    /// `config.transaction_count` TPC-B transactions against them.
+    pub fn run_tpcb(&mut self, config: &TpcbConfig) -> Result<WorkloadReport> {
+        let mut rng = FastRng::new(self.seed);
+        let tellers = config.branches * config.tellers_per_branch;
+        let accounts = config.branches * config.accounts_per_branch;
+
+        let mut load = |count: u32| -> Result<(TableHeap, Vec<RecordId>)> {
+            let heap = self.create_heap()?;
+            let rids = (0..count)
+                .map(|id| heap.insert_tuple(&tpcb_row(id, 0)))
+                .collect::<Result<_>>()?;
+            Ok((heap, rids))
+        };
+        let (branch_heap, branch_rids) = load(config.branches)?;
+        let (teller_heap, teller_rids) = load(tellers)?;
+        let (account_heap, account_rids) = load(accounts)?;
+        let history = self.create_heap()?;
+
+        let mut latencies = Vec::with_capacity(config.transaction_count as usize);
+        let started = Instant::now();
+        for _ in 0..config.transaction_count {
+            let branch = rng.below(config.branches as u64) as u32;
+            let teller = branch * config.tellers_per_branch
+                + rng.below(config.tellers_per_branch as u64) as u32;
+            let account = rng.below(accounts as u64) as u32;
+            let delta = rng.below(199_999) as i64 - 99_999;
+
+            let op_started = Instant::now();
+            add_to_balance(&account_heap, account_rids[account as usize], delta)?;
+            add_to_balance(&teller_heap, teller_rids[teller as usize], delta)?;
+            add_to_balance(&branch_heap, branch_rids[branch as usize], delta)?;
+            history.insert_tuple(&history_row(account, teller, branch, delta))?;
+            latencies.push(op_started.elapsed());
+        }
+
+        Ok(WorkloadReport::new(
+            "tpc-b".to_string(),
+            latencies,
+            started.elapsed(),
+        ))
+    }
+
+    fn create_heap(&mut self) -> Result<TableHeap> {
+        let table_id = self.next_table_id;
+        self.next_table_id += 1;
+        TableHeap::create(Arc::clone(&self.bpm), table_id)
+    }
+}
+
+/// Records of a YCSB run, addressed by key.
+enum YcsbStore {
+    Pages {
+        bpm: Arc<BufferPoolManager>,
+        pages: Vec<PageId>,
+    },
+    Heap {
+        heap: TableHeap,
+        rids: Vec<RecordId>,
+    },
+    Indexed {
+        heap: TableHeap,
+        index: BTreeIndex,
+        next_key: u32,
+    },
+}
+
+impl YcsbStore {
+    fn read(&self, key: u64) -> Result<()> {
+        match self {
+            YcsbStore::Pages { bpm, pages } => {
+                let page_id = pages[key as usize];
+                let guard = bpm
+                    .checked_read_page(page_id)?
+                    .ok_or(CrioError::PageNotFound(page_id))?;
+                std::hint::black_box(guard.data()[0]);
+            }
+            YcsbStore::Heap { heap, rids } => {
+                std::hint::black_box(heap.get_tuple(rids[key as usize])?);
+            }
+            YcsbStore::Indexed { heap, index, .. } => {
+                let rid = index.search(key as u32)?.ok_or(CrioError::KeyNotFound)?;
+                std::hint::black_box(heap.get_tuple(rid)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn update(&self, key: u64, value: &[u8]) -> Result<()> {
+        match self {
+            YcsbStore::Pages { bpm, pages } => {
+                let page_id = pages[key as usize];
+                let mut guard = bpm
+                    .checked_write_page(page_id)?
+                    .ok_or(CrioError::PageNotFound(page_id))?;
+                guard.data_mut()[..value.len()].copy_from_slice(value);
+            }
+            YcsbStore::Heap { heap, rids } => heap.update_tuple(rids[key as usize], value)?,
+            YcsbStore::Indexed { heap, index, .. } => {
+                let rid = index.search(key as u32)?.ok_or(CrioError::KeyNotFound)?;
+                heap.update_tuple(rid, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, value: &[u8]) -> Result<()> {
+        match self {
+            YcsbStore::Pages { bpm, pages } => {
+                let page_id = bpm.new_page()?;
+                let mut guard = bpm
+                    .checked_write_page(page_id)?
+                    .ok_or(CrioError::PageNotFound(page_id))?;
+                guard.data_mut()[..value.len()].copy_from_slice(value);
+                pages.push(page_id);
+            }
+            YcsbStore::Heap { heap, rids } => rids.push(heap.insert_tuple(value)?),
+            YcsbStore::Indexed {
+                heap,
+                index,
+                next_key,
+            } => {
+                let rid = heap.insert_tuple(value)?;
+                index.insert(*next_key, rid)?;
+                *next_key += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `id u32 | balance i64 | filler`
+fn tpcb_row(id: u32, balance: i64) -> Vec<u8> {
+    let mut row = vec![0u8; TPCB_ROW_SIZE];
+    row[..4].copy_from_slice(&id.to_le_bytes());
+    row[4..12].copy_from_slice(&balance.to_le_bytes());
+    row
+}
+
+/// `account u32 | teller u32 | branch u32 | delta i64 | filler`
+fn history_row(account: u32, teller: u32, branch: u32, delta: i64) -> Vec<u8> {
+    let mut row = vec![0u8; TPCB_HISTORY_ROW_SIZE];
+    row[..4].copy_from_slice(&account.to_le_bytes());
+    row[4..8].copy_from_slice(&teller.to_le_bytes());
+    row[8..12].copy_from_slice(&branch.to_le_bytes());
+    row[12..20].copy_from_slice(&delta.to_le_bytes());
+    row
+}
+
+fn add_to_balance(heap: &TableHeap, rid: RecordId, delta: i64) -> Result<()> {
+    let mut row = heap.get_tuple(rid)?;
+    let balance = i64::from_le_bytes(row[4..12].try_into().unwrap()) + delta;
+    row[4..12].copy_from_slice(&balance.to_le_bytes());
+    heap.update_tuple(rid, &row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::disk::MemDiskManager;
+
+    fn runner(pool_size: usize) -> WorkloadRunner {
+        WorkloadRunner::new(Arc::new(BufferPoolManager::new(
+            pool_size,
+            2,
+            Arc::new(MemDiskManager::new()),
+        )))
+    }
+
+    #[test]
+    fn test_ycsb_runs_on_every_target() {
+        let config = YcsbConfig {
+            record_count: 200,
+            operation_count: 1_000,
+            ..YcsbConfig::workload_a()
+        };
+        let mut runner = runner(16);
+        for target in [
+            YcsbTarget::BufferPool,
+            YcsbTarget::TableHeap,
+            YcsbTarget::BTree,
+        ] {
+            let report = runner.run_ycsb(&config, target).unwrap();
+            assert_eq!(report.operations, 1_000);
+            assert!(report.p50 <= report.p99 && report.p99 <= report.max);
+            assert!(report.throughput() > 0.0);
+        }
+
+        let inserts = YcsbConfig {
+            record_count: 100,
+            operation_count: 500,
+            ..YcsbConfig::workload_d()
+        };
+        let report = runner.run_ycsb(&inserts, YcsbTarget::BTree).unwrap();
+        assert_eq!(report.operations, 500);
+    }
+
+    #[test]
+    fn test_tpcb_runs() {
+        let config = TpcbConfig {
+            branches: 2,
+            accounts_per_branch: 500,
+            transaction_count: 500,
+            ..TpcbConfig::default()
+        };
+        let report = runner(32).run_tpcb(&config).unwrap();
+        assert_eq!(report.operations, 500);
+        assert!(report.to_string().starts_with("tpc-b: 500 ops"));
+    }
+}
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use crio::buffer::BufferPoolManager;
 use crio::common::{PageId, RecordId, SlotId};
 use crio::index::BTreeIndex;
-use crio::storage::disk::DiskManager;
+use crio::storage::disk::{DiskManager, MemDiskManager};
 
 use tempfile::NamedTempFile;
 
@@ -25,7 +25,7 @@ fn test_btree_create() {
 #[test]
 fn test_btree_insert_and_search() {
     let (bpm, _temp) = create_bpm(10);
-    let mut index = BTreeIndex::new(bpm.clone()).unwrap();
+    let index = BTreeIndex::new(bpm.clone()).unwrap();
 
     let record1 = RecordId::new(PageId::new(100), SlotId::new(0));
     let record2 = RecordId::new(PageId::new(100), SlotId::new(1));
@@ -44,7 +44,7 @@ fn test_btree_insert_and_search() {
 #[test]
 fn test_btree_insert_many() {
     let (bpm, _temp) = create_bpm(50);
-    let mut index = BTreeIndex::new(bpm.clone()).unwrap();
+    let index = BTreeIndex::new(bpm.clone()).unwrap();
 
     for i in 0..1000 {
         let record = RecordId::new(PageId::new(i), SlotId::new((i % 100) as u16));
@@ -58,10 +58,47 @@ fn test_btree_insert_many() {
     }
 }
 
+#[test]
+fn test_btree_remove() {
+    let (bpm, _temp) = create_bpm(50);
+    let index = BTreeIndex::new(bpm.clone()).unwrap();
+    let record = |i: u32| RecordId::new(PageId::new(i), SlotId::new(0));
+
+    for i in 0..1000 {
+        index.insert(i, record(i)).unwrap();
+    }
+
+    // A key is only removed along with the value it maps to
+    assert!(!index.remove(10, record(11)).unwrap());
+    assert!(!index.remove(5000, record(5000)).unwrap());
+    for i in (0..1000).filter(|i| i % 3 != 0) {
+        assert!(index.remove(i, record(i)).unwrap());
+    }
+    for i in 0..1000 {
+        let expected = (i % 3 == 0).then(|| record(i));
+        assert_eq!(index.search(i).unwrap(), expected, "key {}", i);
+    }
+    let keys: Vec<_> = index
+        .range_scan(0, 30)
+        .unwrap()
+        .into_iter()
+        .map(|(k, _)| k)
+        .collect();
+    assert_eq!(keys, (0..=30).step_by(3).collect::<Vec<_>>());
+
+    // Emptied leaves take keys again
+    for i in 0..300 {
+        index.remove(i, record(i)).unwrap();
+    }
+    assert!(index.range_scan(0, 299).unwrap().is_empty());
+    index.insert(7, record(70)).unwrap();
+    assert_eq!(index.search(7).unwrap(), Some(record(70)));
+}
+
 #[test]
 fn test_btree_insert_reverse() {
     let (bpm, _temp) = create_bpm(50);
-    let mut index = BTreeIndex::new(bpm.clone()).unwrap();
+    let index = BTreeIndex::new(bpm.clone()).unwrap();
 
     for i in (0..100).rev() {
         let record = RecordId::new(PageId::new(i), SlotId::new(0));
@@ -77,7 +114,7 @@ fn test_btree_insert_reverse() {
 #[test]
 fn test_btree_range_scan() {
     let (bpm, _temp) = create_bpm(50);
-    let mut index = BTreeIndex::new(bpm.clone()).unwrap();
+    let index = BTreeIndex::new(bpm.clone()).unwrap();
 
     for i in 0..100 {
         let record = RecordId::new(PageId::new(i), SlotId::new(0));
@@ -100,7 +137,7 @@ fn test_btree_range_scan() {
 #[test]
 fn test_btree_range_scan_empty() {
     let (bpm, _temp) = create_bpm(10);
-    let mut index = BTreeIndex::new(bpm.clone()).unwrap();
+    let index = BTreeIndex::new(bpm.clone()).unwrap();
 
     for i in 0..10 {
         let record = RecordId::new(PageId::new(i), SlotId::new(0));
@@ -114,7 +151,7 @@ fn test_btree_range_scan_empty() {
 #[test]
 fn test_btree_range_scan_all() {
     let (bpm, _temp) = create_bpm(50);
-    let mut index = BTreeIndex::new(bpm.clone()).unwrap();
+    let index = BTreeIndex::new(bpm.clone()).unwrap();
 
     for i in 0..100 {
         let record = RecordId::new(PageId::new(i), SlotId::new(0));
@@ -128,7 +165,7 @@ fn test_btree_range_scan_all() {
 #[test]
 fn test_btree_split() {
     let (bpm, _temp) = create_bpm(100);
-    let mut index = BTreeIndex::new(bpm.clone()).unwrap();
+    let index = BTreeIndex::new(bpm.clone()).unwrap();
 
     for i in 0..200 {
         let record = RecordId::new(PageId::new(i), SlotId::new(0));
@@ -152,7 +189,7 @@ fn test_btree_random_insert() {
     use rand::thread_rng;
 
     let (bpm, _temp) = create_bpm(100);
-    let mut index = BTreeIndex::new(bpm.clone()).unwrap();
+    let index = BTreeIndex::new(bpm.clone()).unwrap();
 
     let mut keys: Vec<u32> = (0..500).collect();
     keys.shuffle(&mut thread_rng());
@@ -181,7 +218,7 @@ fn test_btree_persistence() {
     let root_page_id = {
         let disk_manager = Arc::new(DiskManager::new(&path).unwrap());
         let bpm = Arc::new(BufferPoolManager::new(10, 2, disk_manager.clone()));
-        let mut index = BTreeIndex::new(bpm.clone()).unwrap();
+        let index = BTreeIndex::new(bpm.clone()).unwrap();
 
         for i in 0..50 {
             let record = RecordId::new(PageId::new(i), SlotId::new(0));
@@ -208,3 +245,61 @@ fn test_btree_persistence() {
         }
     }
 }
+
+#[test]
+fn test_btree_in_memory_backend() {
+    // A small pool forces evictions through the in-memory backend
+    let bpm = Arc::new(BufferPoolManager::new(
+        8,
+        2,
+        Arc::new(MemDiskManager::new()),
+    ));
+    let index = BTreeIndex::new(bpm.clone()).unwrap();
+
+    for i in 0..300 {
+        let record = RecordId::new(PageId::new(i), SlotId::new(0));
+        index.insert(i, record).unwrap();
+    }
+
+    for i in 0..300 {
+        let expected = RecordId::new(PageId::new(i), SlotId::new(0));
+        assert_eq!(index.search(i).unwrap(), Some(expected));
+    }
+}
+
+#[test]
+fn test_btree_shared_concurrent_inserts() {
+    let bpm = Arc::new(BufferPoolManager::new(
+        64,
+        2,
+        Arc::new(MemDiskManager::new()),
+    ));
+    let index = Arc::new(BTreeIndex::new(bpm).unwrap());
+    let root = index.root_page_id();
+
+    // Interleaved keys make every thread hit the same leaves and splits
+    let threads: Vec<_> = (0..4u32)
+        .map(|t| {
+            let index = Arc::clone(&index);
+            std::thread::spawn(move || {
+                for i in 0..1000u32 {
+                    let key = i * 4 + t;
+                    let record = RecordId::new(PageId::new(key), SlotId::new(0));
+                    index.insert(key, record).unwrap();
+                    assert_eq!(index.search(key).unwrap(), Some(record));
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_ne!(index.root_page_id(), root);
+    let all = index.range_scan(0, u32::MAX).unwrap();
+    assert_eq!(all.len(), 4000);
+    for (i, (key, record)) in all.into_iter().enumerate() {
+        assert_eq!(key, i as u32);
+        assert_eq!(record.page_id, PageId::new(key));
+    }
+}
@@ -0,0 +1,71 @@
+//! Buffer pool stress tests (run with `--features stress-test`)
+
+#![cfg(feature = "stress-test")]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crio::buffer::{BufferPoolManager, BufferPoolStress, StressConfig};
+use crio::storage::disk::DiskManager;
+
+fn run(pool_size: usize, config: StressConfig) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let disk_manager = Arc::new(DiskManager::new(temp_dir.path().join("stress.db")).unwrap());
+    let bpm = Arc::new(BufferPoolManager::new(pool_size, 2, disk_manager));
+
+    let report = BufferPoolStress::new(Arc::clone(&bpm), config)
+        .run()
+        .unwrap();
+    assert!(report.is_ok(), "{}", report);
+    assert!(report.reads > 0);
+    bpm.shutdown().unwrap();
+}
+
+#[test]
+fn test_stress_read_heavy() {
+    run(
+        24,
+        StressConfig {
+            readers: 8,
+            writers: 1,
+            evictors: 1,
+            duration: Duration::from_millis(500),
+            seed: 1,
+            ..StressConfig::default()
+        },
+    );
+}
+
+#[test]
+fn test_stress_write_heavy() {
+    run(
+        16,
+        StressConfig {
+            readers: 2,
+            writers: 6,
+            evictors: 2,
+            hot_pages: 8,
+            duration: Duration::from_millis(500),
+            seed: 2,
+            ..StressConfig::default()
+        },
+    );
+}
+
+#[test]
+fn test_stress_tiny_pool() {
+    // Fewer frames than threads: fetches regularly find every frame pinned
+    run(
+        6,
+        StressConfig {
+            readers: 4,
+            writers: 3,
+            evictors: 2,
+            hot_pages: 12,
+            cold_pages: 32,
+            duration: Duration::from_millis(500),
+            seed: 3,
+            ..StressConfig::default()
+        },
+    );
+}
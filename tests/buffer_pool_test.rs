@@ -4,8 +4,10 @@ use std::sync::Arc;
 use std::thread;
 
 use crio::buffer::BufferPoolManager;
-use crio::common::{CrioError, PageId};
-use crio::storage::disk::DiskManager;
+use crio::common::{CrioError, PageId, PAGE_SIZE};
+use crio::storage::disk::{
+    DiskManager, Fault, FaultInjectingDiskManager, FaultTrigger, MemObjectStore, ObjectStoreBackend,
+};
 use crio::storage::page::TablePage;
 use tempfile::NamedTempFile;
 
@@ -281,3 +283,124 @@ fn test_buffer_pool_large_workload() {
         assert_eq!(u32::from_le_bytes(id_bytes), pid.as_u32());
     }
 }
+
+#[test]
+fn test_page_guard_downgrade_and_upgrade_keep_pin() {
+    let (bpm, _temp) = create_bpm(10);
+    let page_id = bpm.new_page().unwrap();
+
+    let mut guard = bpm.checked_write_page(page_id).unwrap().unwrap();
+    guard.data_mut()[0] = 1;
+
+    let guard = guard.downgrade();
+    assert_eq!(bpm.get_pin_count(page_id), Some(1));
+    assert_eq!(guard.data()[0], 1);
+
+    let mut guard = guard.try_upgrade().ok().unwrap();
+    assert_eq!(bpm.get_pin_count(page_id), Some(1));
+    guard.data_mut()[0] = 2;
+    drop(guard);
+
+    assert_eq!(bpm.get_pin_count(page_id), Some(0));
+    let guard = bpm.checked_read_page(page_id).unwrap().unwrap();
+    assert_eq!(guard.data()[0], 2);
+}
+
+#[test]
+fn test_buffer_pool_over_custom_backend() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let dm = Arc::new(DiskManager::new(temp_file.path()).unwrap());
+    let fdm = Arc::new(FaultInjectingDiskManager::new(dm));
+    let bpm = BufferPoolManager::new(4, 2, Arc::clone(&fdm) as _);
+
+    let page_id = bpm.new_page().unwrap();
+    {
+        let mut guard = bpm.checked_write_page(page_id).unwrap().unwrap();
+        guard.data_mut()[0] = 0xAB;
+    }
+
+    fdm.inject(FaultTrigger::Page(page_id), Fault::IoError);
+    let err = bpm.flush_page(page_id).unwrap_err();
+    assert!(matches!(err, CrioError::PageWrite { .. }));
+
+    fdm.clear_faults();
+    assert!(bpm.flush_page(page_id).unwrap());
+    assert_eq!(fdm.write_count(), 2);
+}
+
+#[test]
+fn test_buffer_pool_shutdown() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let dm = Arc::new(DiskManager::new(temp_file.path()).unwrap());
+    let bpm = BufferPoolManager::new(4, 2, Arc::clone(&dm) as _);
+
+    let p1 = bpm.new_page().unwrap();
+    let p2 = bpm.new_page().unwrap();
+    bpm.checked_write_page(p1).unwrap().unwrap().data_mut()[0] = 11;
+
+    bpm.shutdown().unwrap();
+    assert!(bpm.is_shut_down());
+    bpm.shutdown().unwrap();
+
+    // Dirty pages were flushed and synced; new I/O is refused
+    let mut data = [0u8; PAGE_SIZE];
+    dm.read_page(p1, &mut data).unwrap();
+    assert_eq!(data[0], 11);
+    assert!(dm.get_num_syncs() > 0);
+    bpm.checked_write_page(p2).unwrap().unwrap().data_mut()[0] = 22;
+    assert!(matches!(
+        bpm.flush_page(p2),
+        Err(CrioError::DiskScheduler(_))
+    ));
+}
+
+#[test]
+fn test_buffer_pool_drop_flushes() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let page_id = {
+        let dm = Arc::new(DiskManager::new(temp_file.path()).unwrap());
+        let bpm = BufferPoolManager::new(4, 2, dm);
+        let page_id = bpm.new_page().unwrap();
+        bpm.checked_write_page(page_id).unwrap().unwrap().data_mut()[0] = 0x5A;
+        page_id
+    };
+
+    let dm = DiskManager::new(temp_file.path()).unwrap();
+    let mut data = [0u8; PAGE_SIZE];
+    dm.read_page(page_id, &mut data).unwrap();
+    assert_eq!(data[0], 0x5A);
+}
+
+#[test]
+fn test_buffer_pool_over_object_store() {
+    let store = Arc::new(MemObjectStore::new());
+    let page_ids: Vec<PageId> = {
+        let backend = ObjectStoreBackend::open(store.clone(), "db")
+            .unwrap()
+            .with_write_buffer_pages(16);
+        let bpm = BufferPoolManager::new(4, 2, Arc::new(backend));
+        let page_ids: Vec<_> = (0..40u8)
+            .map(|i| {
+                let page_id = bpm.new_page().unwrap();
+                bpm.checked_write_page(page_id).unwrap().unwrap().data_mut()[0] = i;
+                page_id
+            })
+            .collect();
+
+        // Evicted pages are read back through the write buffer or the store
+        for (i, &page_id) in page_ids.iter().enumerate() {
+            let guard = bpm.checked_read_page(page_id).unwrap().unwrap();
+            assert_eq!(guard.data()[0], i as u8);
+        }
+        bpm.shutdown().unwrap();
+        page_ids
+    };
+    assert!(store.put_count() > 0);
+
+    let backend = ObjectStoreBackend::open(store, "db").unwrap();
+    let bpm = BufferPoolManager::new(4, 2, Arc::new(backend));
+    for (i, &page_id) in page_ids.iter().enumerate() {
+        let guard = bpm.checked_read_page(page_id).unwrap().unwrap();
+        assert_eq!(guard.data()[0], i as u8);
+    }
+}
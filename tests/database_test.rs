@@ -0,0 +1,2261 @@
+//! Integration tests for the Database facade
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crio::catalog::{PartitionScheme, Privilege};
+use crio::common::{
+    CancellationToken, CrioError, JobHandle, QueryBudget, ResourceLimits, PAGE_SIZE,
+};
+use crio::concurrency::LockMode;
+use crio::db::{
+    AutoVacuumPolicy, CrioConfig, Database, DatabaseManager, DatabaseOptions, IsolationLevel,
+    Session, StatementPlan,
+};
+use crio::execution::{
+    BatchPredicate, CompareOp, Cte, HashJoinExecutor, JoinType, KeyPredicate, OnConflict,
+    RuntimeFilterKind, SeqScanExecutor, SubqueryCache, SubqueryKind, UpsertExecutor,
+};
+use crio::index::TextQuery;
+use crio::storage::disk::{IoBudget, IoClass};
+use crio::storage::table::{TempTableScope, DEFAULT_FILL_FACTOR};
+use crio::tuple::{Collation, Column, DataType, JsonValue, Schema, Tuple, Value};
+
+fn users_schema() -> Schema {
+    Schema::builder()
+        .column("id", DataType::Integer)
+        .column("name", DataType::VarChar(64))
+        .nullable_column("age", DataType::SmallInt)
+        .build()
+}
+
+fn options() -> DatabaseOptions {
+    DatabaseOptions {
+        pool_size: 32,
+        ..DatabaseOptions::default()
+    }
+}
+
+#[test]
+fn test_database_create_insert_scan() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("app.db"), options()).unwrap();
+
+    let users = db.create_table("users", users_schema()).unwrap();
+    let rid = users
+        .insert(vec![
+            Value::Integer(1),
+            Value::String("ada".into()),
+            Value::SmallInt(36),
+        ])
+        .unwrap();
+    let bob = users
+        .insert(vec![
+            Value::Integer(2),
+            Value::String("bob".into()),
+            Value::Null,
+        ])
+        .unwrap();
+
+    let row = users.get(rid).unwrap();
+    assert_eq!(
+        row.value_by_name("name"),
+        Some(&Value::String("ada".into()))
+    );
+    assert_eq!(users.scan().unwrap().len(), 2);
+
+    users.delete(rid).unwrap();
+    assert_eq!(users.scan().unwrap().len(), 1);
+    assert_eq!(users.approx_row_count(), 1);
+    assert_eq!(users.approx_size_bytes(), PAGE_SIZE as u64);
+    let rows = users.get_many(&[bob, rid]).unwrap();
+    assert_eq!(rows[0].as_ref().unwrap().value(0), Some(&Value::Integer(2)));
+    assert!(rows[1].is_none());
+
+    assert!(matches!(
+        users.insert(vec![Value::Integer(3)]),
+        Err(CrioError::SchemaMismatch)
+    ));
+    assert!(matches!(
+        db.table("missing"),
+        Err(CrioError::UnknownTable(_))
+    ));
+    assert!(matches!(
+        db.create_table("users", users_schema()),
+        Err(CrioError::DuplicateTableName(_))
+    ));
+}
+
+#[test]
+fn test_database_index_lookup() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("idx.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+
+    for i in 0..50 {
+        users
+            .insert(vec![
+                Value::Integer(i),
+                Value::String(format!("user{}", i)),
+                Value::Null,
+            ])
+            .unwrap();
+    }
+
+    // Index built from existing rows, then maintained on insert
+    db.create_index("users_id", "users", "id").unwrap();
+    users
+        .insert(vec![
+            Value::Integer(-7),
+            Value::String("neg".into()),
+            Value::Null,
+        ])
+        .unwrap();
+
+    let (_, row) = users
+        .lookup("users_id", &Value::Integer(42))
+        .unwrap()
+        .unwrap();
+    assert_eq!(row.value(1), Some(&Value::String("user42".into())));
+    assert!(users
+        .lookup("users_id", &Value::Integer(-7))
+        .unwrap()
+        .is_some());
+    assert!(users
+        .lookup("users_id", &Value::Integer(500))
+        .unwrap()
+        .is_none());
+
+    assert!(matches!(
+        users.insert(vec![
+            Value::Integer(42),
+            Value::String("dup".into()),
+            Value::Null
+        ]),
+        Err(CrioError::DuplicateKey(_))
+    ));
+    assert_eq!(users.scan().unwrap().len(), 51);
+
+    // Deleted rows disappear from lookups
+    let (rid, _) = users
+        .lookup("users_id", &Value::Integer(3))
+        .unwrap()
+        .unwrap();
+    users.delete(rid).unwrap();
+    assert!(users
+        .lookup("users_id", &Value::Integer(3))
+        .unwrap()
+        .is_none());
+
+    // A deleted row's key is free again, and updates move rows between keys
+    let three = users
+        .insert(vec![
+            Value::Integer(3),
+            Value::String("again".into()),
+            Value::Null,
+        ])
+        .unwrap();
+    users
+        .update(
+            three,
+            vec![
+                Value::Integer(300),
+                Value::String("moved".into()),
+                Value::Null,
+            ],
+        )
+        .unwrap();
+    assert!(users
+        .lookup("users_id", &Value::Integer(3))
+        .unwrap()
+        .is_none());
+    let (rid, row) = users
+        .lookup("users_id", &Value::Integer(300))
+        .unwrap()
+        .unwrap();
+    assert_eq!(rid, three);
+    assert_eq!(row.value(1), Some(&Value::String("moved".into())));
+
+    assert!(matches!(
+        users.update(
+            three,
+            vec![Value::Integer(42), Value::String("dup".into()), Value::Null],
+        ),
+        Err(CrioError::DuplicateKey(_))
+    ));
+    assert_eq!(
+        users.get(three).unwrap().value(0),
+        Some(&Value::Integer(300))
+    );
+}
+
+#[test]
+fn test_database_index_scan_where() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("where.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    db.create_index("users_id", "users", "id").unwrap();
+    for i in -50..500 {
+        users.insert(user(i, "someone", 0)).unwrap();
+    }
+
+    // id IN (42, -3, 7, 42, NULL) OR id BETWEEN 5 AND 8 OR id = 1000
+    let predicate = KeyPredicate::Or(vec![
+        KeyPredicate::In(vec![
+            Value::Integer(42),
+            Value::Integer(-3),
+            Value::Integer(7),
+            Value::Integer(42),
+            Value::Null,
+        ]),
+        KeyPredicate::Between(Value::Integer(5), Value::Integer(8)),
+        KeyPredicate::Eq(Value::BigInt(1000)),
+    ]);
+    let ids: Vec<_> = users
+        .scan_index_where("users_id", &predicate)
+        .unwrap()
+        .into_iter()
+        .map(|(_, row)| row.value(0).cloned().unwrap())
+        .collect();
+    assert_eq!(ids, [-3, 5, 6, 7, 8, 42].map(Value::Integer));
+
+    let empty = KeyPredicate::Between(Value::Integer(10), Value::Integer(0));
+    assert!(users
+        .scan_index_where("users_id", &empty)
+        .unwrap()
+        .is_empty());
+    assert!(matches!(
+        users.scan_index_where("users_id", &KeyPredicate::Eq(Value::String("x".into()))),
+        Err(CrioError::SchemaMismatch)
+    ));
+}
+
+#[test]
+fn test_database_temp_tables() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("temp.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    for i in 0..100 {
+        users.insert(user(i, "someone", (i % 7) as i16)).unwrap();
+    }
+
+    // CREATE TEMP TABLE young AS SELECT * FROM users WHERE age < 3
+    let mut temp_tables = db.temp_tables(64).unwrap();
+    let scratch = temp_tables.temp_file().path().to_path_buf();
+    let young = temp_tables
+        .create("young", users_schema(), TempTableScope::Session)
+        .unwrap();
+    let scan = SeqScanExecutor::new(db.catalog().table("users").unwrap()).unwrap();
+    let inserted = young
+        .insert_all(scan.filter(|row| {
+            row.as_ref().map_or(true, |(_, t)| {
+                t.values()[2].compare(&Value::SmallInt(3)).unwrap().is_lt()
+            })
+        }))
+        .unwrap();
+    assert_eq!(inserted, 44);
+    assert_eq!(temp_tables.get("young").unwrap().scan().count(), 44);
+    // Temp tables never reach the catalog
+    assert!(db.catalog().table("young").is_none());
+
+    assert!(scratch.exists());
+    drop(temp_tables);
+    assert!(!scratch.exists());
+}
+
+#[test]
+fn test_database_cte() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("cte.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    for i in 0..100 {
+        users.insert(user(i, "someone", (i % 3) as i16)).unwrap();
+    }
+    let temp_tables = db.temp_tables(64).unwrap();
+    let temp_file = temp_tables.temp_file();
+    let runs = std::cell::Cell::new(0);
+    let young = || {
+        runs.set(runs.get() + 1);
+        let rows = users.scan()?;
+        Ok(rows
+            .into_iter()
+            .filter(|(_, row)| row.value(2) == Some(&Value::SmallInt(0)))
+            .map(Ok))
+    };
+
+    // WITH young AS (...) SELECT ... FROM young a, young b
+    let mut cte = Cte::new("young", users_schema(), 2, Arc::clone(temp_file), young);
+    assert!(cte.is_materialized());
+    assert_eq!(cte.scan().unwrap().count(), 34);
+    assert_eq!(cte.scan().unwrap().count(), 34);
+    assert_eq!(runs.get(), 1);
+    assert!(temp_file.allocated_pages() > 0);
+    drop(cte);
+    assert_eq!(temp_file.allocated_pages(), 0);
+
+    // Read once, it is inlined and runs per scan
+    let mut cte = Cte::new("young", users_schema(), 1, Arc::clone(temp_file), young);
+    assert!(!cte.is_materialized());
+    assert_eq!(cte.scan().unwrap().count(), 34);
+    assert_eq!(runs.get(), 2);
+    assert_eq!(temp_file.allocated_pages(), 0);
+}
+
+#[test]
+fn test_session() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let archive_path = temp_dir.path().join("archive.db");
+    {
+        let archive = Database::open(&archive_path, options()).unwrap();
+        archive.create_table("events", users_schema()).unwrap();
+        archive.close().unwrap();
+    }
+    let db = Arc::new(Database::open(temp_dir.path().join("session.db"), options()).unwrap());
+    let users = db.create_table("users", users_schema()).unwrap();
+    users.insert(user(1, "ann", 30)).unwrap();
+    db.attach("archive", &archive_path, CrioConfig::default())
+        .unwrap();
+
+    let mut session = Session::new(Arc::clone(&db));
+    let other = Session::new(Arc::clone(&db));
+    assert_ne!(session.id(), other.id());
+
+    // Unqualified names follow the search path; qualified ones don't
+    assert_eq!(session.table("users").unwrap().name(), "users");
+    assert!(session.table("events").is_err());
+    assert!(session.table("archive.events").is_ok());
+    session.set_search_path(&["main", "archive"]);
+    assert_eq!(session.table("events").unwrap().name(), "events");
+
+    assert!(matches!(session.commit(), Err(CrioError::NoTransaction)));
+    assert!(matches!(
+        session.lock_table("users", LockMode::Exclusive),
+        Err(CrioError::NoTransaction)
+    ));
+    session.set_isolation_level(IsolationLevel::Serializable);
+    session.begin().unwrap();
+    assert!(matches!(
+        session.begin(),
+        Err(CrioError::TransactionInProgress)
+    ));
+    assert_eq!(
+        session.transaction_isolation(),
+        Some(IsolationLevel::Serializable)
+    );
+    session.lock_table("users", LockMode::Exclusive).unwrap();
+    let table_id = db.catalog().table("users").unwrap().table_id;
+    assert!(db
+        .lock_manager()
+        .try_lock_table(table_id, LockMode::Shared)
+        .is_none());
+
+    let scope = session.temp_table_scope(true);
+    session
+        .temp_tables()
+        .unwrap()
+        .create("scratch", users_schema(), scope)
+        .unwrap();
+    let scope = session.temp_table_scope(false);
+    session
+        .temp_tables()
+        .unwrap()
+        .create("kept", users_schema(), scope)
+        .unwrap();
+
+    session.commit().unwrap();
+    assert!(!session.in_transaction());
+    assert!(db
+        .lock_manager()
+        .try_lock_table(table_id, LockMode::Shared)
+        .is_some());
+    assert_eq!(session.temp_tables().unwrap().names(), vec!["kept"]);
+
+    let statement = session
+        .prepare("by_id", "SELECT * FROM users WHERE id = $1")
+        .unwrap();
+    assert_eq!(statement.param_count, 1);
+    assert!(matches!(
+        session.prepare("by_id", "SELECT 1"),
+        Err(CrioError::DuplicateStatementName(_))
+    ));
+    assert_eq!(session.prepared_names(), vec!["by_id"]);
+    session.deallocate("by_id").unwrap();
+    assert!(matches!(
+        session.prepared("by_id"),
+        Err(CrioError::UnknownStatement(_))
+    ));
+}
+
+#[test]
+fn test_session_uses_its_table_locks() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Arc::new(Database::open(temp_dir.path().join("locks.db"), options()).unwrap());
+    db.create_table("users", users_schema())
+        .unwrap()
+        .insert(user(1, "ann", 30))
+        .unwrap();
+    db.create_table("orders", users_schema()).unwrap();
+    db.create_index("users_id", "users", "id").unwrap();
+    let table_id = db.catalog().table("users").unwrap().table_id;
+
+    let mut session = Session::new(Arc::clone(&db));
+    session.begin().unwrap();
+    session.lock_table("users", LockMode::Shared).unwrap();
+    session.lock_table("users", LockMode::Shared).unwrap();
+    let users = session.table("users").unwrap();
+    std::thread::scope(|scope| {
+        // A writer queues behind the session's lock, which keeps new shared
+        // locks out; the session's handles still read through its own
+        let writer = scope.spawn(|| {
+            let _lock = db.lock_manager().lock_table(table_id, LockMode::Exclusive);
+        });
+        while db
+            .lock_manager()
+            .try_lock_table(table_id, LockMode::Shared)
+            .is_some()
+        {
+            std::thread::yield_now();
+        }
+        assert_eq!(users.scan().unwrap().len(), 1);
+
+        // An exclusive lock would wait for the session itself
+        assert!(matches!(
+            users.insert(user(2, "bob", 40)),
+            Err(CrioError::LockUpgrade(id)) if id == table_id
+        ));
+        assert!(matches!(
+            session.lock_table("users", LockMode::Exclusive),
+            Err(CrioError::LockUpgrade(_))
+        ));
+
+        session.commit().unwrap();
+        writer.join().unwrap();
+    });
+
+    // An exclusive lock covers writes through the session's handles
+    session.begin().unwrap();
+    session.lock_table("users", LockMode::Exclusive).unwrap();
+    users.insert(user(2, "bob", 40)).unwrap();
+    assert_eq!(users.scan().unwrap().len(), 2);
+    // Other tables and handles from elsewhere lock as usual
+    session
+        .table("orders")
+        .unwrap()
+        .insert(user(1, "x", 0))
+        .unwrap();
+    assert!(db
+        .lock_manager()
+        .try_lock_table(table_id, LockMode::Shared)
+        .is_none());
+    drop(session);
+    assert_eq!(db.table("users").unwrap().scan().unwrap().len(), 2);
+}
+
+#[test]
+fn test_session_cte() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Arc::new(Database::open(temp_dir.path().join("cte.db"), options()).unwrap());
+    let users = db.create_table("users", users_schema()).unwrap();
+    for i in 0..100 {
+        users.insert(user(i, "someone", (i % 3) as i16)).unwrap();
+    }
+    let mut session = Session::new(Arc::clone(&db));
+    let runs = std::cell::Cell::new(0);
+    let young = || {
+        runs.set(runs.get() + 1);
+        let rows = users.scan()?;
+        Ok(rows
+            .into_iter()
+            .filter(|(_, row)| row.value(2) == Some(&Value::SmallInt(0)))
+            .map(Ok))
+    };
+
+    // WITH young AS (...) SELECT ... FROM young a, young b
+    let mut cte = session.cte("young", users_schema(), 2, young).unwrap();
+    assert!(cte.is_materialized());
+    assert_eq!(cte.scan().unwrap().count(), 34);
+    assert_eq!(runs.get(), 1);
+    let temp_file = Arc::clone(session.temp_tables().unwrap().temp_file());
+    assert!(temp_file.allocated_pages() > 0);
+    drop(cte);
+    assert_eq!(temp_file.allocated_pages(), 0);
+}
+
+#[test]
+fn test_session_execute_prepared() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Arc::new(Database::open(temp_dir.path().join("plans.db"), options()).unwrap());
+    let users = db.create_table("users", users_schema()).unwrap();
+    for i in 0..20 {
+        users.insert(user(i, "someone", 0)).unwrap();
+    }
+
+    // Plans look ids up through the index once there is one
+    let plans = std::sync::atomic::AtomicUsize::new(0);
+    let planner = |text: &str| {
+        assert_eq!(text, "select * from users where id = $1");
+        plans.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let plan: StatementPlan = if db.catalog().index("users_id").is_some() {
+            Box::new(|session, params| {
+                let users = session.table("users")?;
+                Ok(users.lookup("users_id", &params[0])?.into_iter().collect())
+            })
+        } else {
+            Box::new(|session, params| {
+                let rows = session.table("users")?.scan()?;
+                Ok(rows
+                    .into_iter()
+                    .filter(|(_, row)| row.value(0) == Some(&params[0]))
+                    .collect())
+            })
+        };
+        Ok((plan, vec!["users".to_string()]))
+    };
+    let ids = |rows: Vec<(_, Tuple)>| -> Vec<Value> {
+        rows.into_iter()
+            .map(|(_, row)| row.values()[0].clone())
+            .collect()
+    };
+
+    let mut session = Session::new(Arc::clone(&db));
+    session
+        .prepare("by_id", "SELECT *  FROM users\nWHERE id = $1;")
+        .unwrap();
+    let rows = session
+        .execute("by_id", &[Value::Integer(7)], planner)
+        .unwrap();
+    assert_eq!(ids(rows), [Value::Integer(7)]);
+    assert!(session
+        .execute("by_id", &[Value::Integer(70)], planner)
+        .unwrap()
+        .is_empty());
+    assert!(matches!(
+        session.execute("by_id", &[], planner),
+        Err(CrioError::ParameterCount {
+            expected: 1,
+            given: 0
+        })
+    ));
+    assert!(matches!(
+        session.execute("missing", &[], planner),
+        Err(CrioError::UnknownStatement(_))
+    ));
+
+    // Other sessions share the plan
+    let mut other = Session::new(Arc::clone(&db));
+    other
+        .prepare("lookup", "select * from users where id = $1")
+        .unwrap();
+    let rows = other
+        .execute("lookup", &[Value::Integer(3)], planner)
+        .unwrap();
+    assert_eq!(ids(rows), [Value::Integer(3)]);
+    assert_eq!(plans.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+    // A new index changes the table, so the statement is planned again
+    db.create_index("users_id", "users", "id").unwrap();
+    let rows = session
+        .execute("by_id", &[Value::Integer(7)], planner)
+        .unwrap();
+    assert_eq!(ids(rows), [Value::Integer(7)]);
+    assert_eq!(plans.load(std::sync::atomic::Ordering::Relaxed), 2);
+    let stats = db.plan_cache().stats();
+    assert_eq!((stats.hits, stats.invalidations, stats.entries), (2, 1, 1));
+}
+
+#[test]
+fn test_session_privileges() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Arc::new(Database::open(temp_dir.path().join("grants.db"), options()).unwrap());
+    let users = db.create_table("users", users_schema()).unwrap();
+    db.create_table("salaries", users_schema()).unwrap();
+    db.create_index("users_id", "users", "id").unwrap();
+    users.insert(user(1, "ann", 30)).unwrap();
+    db.create_role("reader", false).unwrap();
+    db.grant("reader", "users", Privilege::Select.into())
+        .unwrap();
+
+    let mut session = Session::new(Arc::clone(&db));
+    assert!(matches!(
+        session.set_role(Some("ghost")),
+        Err(CrioError::UnknownRole(_))
+    ));
+    session.set_role(Some("reader")).unwrap();
+
+    let users = session.table("users").unwrap();
+    assert_eq!(users.scan().unwrap().len(), 1);
+    assert!(users
+        .lookup("users_id", &Value::Integer(1))
+        .unwrap()
+        .is_some());
+    assert!(matches!(
+        users.insert(user(2, "bob", 40)),
+        Err(CrioError::PermissionDenied { .. })
+    ));
+    let upsert = users.upsert(
+        "users_id",
+        user(1, "ann", 31),
+        &OnConflict::update(|_, proposed| proposed.values().to_vec()),
+    );
+    assert!(matches!(upsert, Err(CrioError::PermissionDenied { .. })));
+    // No privilege at all fails when the table is resolved
+    assert!(matches!(
+        session.table("salaries"),
+        Err(CrioError::PermissionDenied { .. })
+    ));
+
+    // Grants apply to handles made after them
+    db.grant("reader", "users", Privilege::Insert | Privilege::Update)
+        .unwrap();
+    assert!(users.insert(user(2, "bob", 40)).is_err());
+    let users = session.table("users").unwrap();
+    users.insert(user(2, "bob", 40)).unwrap();
+    assert!(matches!(
+        users.delete(users.scan().unwrap()[0].0),
+        Err(CrioError::PermissionDenied { .. })
+    ));
+
+    // Locks need the privileges the locked rows would
+    assert!(users
+        .lock_key_range(
+            "users_id",
+            &Value::Integer(0),
+            &Value::Integer(9),
+            LockMode::Exclusive
+        )
+        .is_ok());
+    session.begin().unwrap();
+    session.lock_table("users", LockMode::Shared).unwrap();
+    session.commit().unwrap();
+    db.create_role("auditor", false).unwrap();
+    db.grant("auditor", "salaries", Privilege::Select.into())
+        .unwrap();
+    let mut auditor = Session::new(Arc::clone(&db));
+    auditor.set_role(Some("auditor")).unwrap();
+    let salaries = auditor.table("salaries").unwrap();
+    assert!(matches!(
+        salaries.lock_key_range(
+            "missing",
+            &Value::Integer(0),
+            &Value::Integer(9),
+            LockMode::Exclusive
+        ),
+        Err(CrioError::PermissionDenied { .. })
+    ));
+    auditor.begin().unwrap();
+    assert!(matches!(
+        auditor.lock_table("salaries", LockMode::Exclusive),
+        Err(CrioError::PermissionDenied { .. })
+    ));
+    auditor.lock_table("salaries", LockMode::Shared).unwrap();
+    auditor.commit().unwrap();
+
+    // Only a superuser role can give up its role
+    assert!(matches!(
+        session.set_role(None),
+        Err(CrioError::RoleChangeDenied(_))
+    ));
+    assert!(matches!(
+        session.set_role(Some("auditor")),
+        Err(CrioError::RoleChangeDenied(_))
+    ));
+    db.create_role("admin", true).unwrap();
+    let mut admin = Session::new(Arc::clone(&db));
+    admin.set_role(Some("admin")).unwrap();
+    admin.set_role(None).unwrap();
+    assert!(admin.table("salaries").is_ok());
+}
+
+#[test]
+fn test_database_key_range_locks() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("ranges.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    db.create_index("users_id", "users", "id").unwrap();
+    for i in (0..20).step_by(2) {
+        users.insert(user(i, "even", 0)).unwrap();
+    }
+    let ids = |rows: Vec<(_, Tuple)>| -> Vec<Value> {
+        rows.into_iter()
+            .map(|(_, row)| row.values()[0].clone())
+            .collect()
+    };
+    let scan = || {
+        ids(users
+            .scan_index("users_id", &Value::Integer(5), &Value::Integer(15))
+            .unwrap())
+    };
+
+    let range = users
+        .lock_key_range(
+            "users_id",
+            &Value::Integer(5),
+            &Value::Integer(15),
+            LockMode::Shared,
+        )
+        .unwrap();
+    let first = scan();
+    assert_eq!(first.len(), 5);
+
+    std::thread::scope(|s| {
+        let insert = s.spawn(|| users.insert(user(7, "phantom", 0)).unwrap());
+        let delete = s.spawn(|| {
+            let (rid, _) = users
+                .lookup("users_id", &Value::Integer(10))
+                .unwrap()
+                .unwrap();
+            users.delete(rid).unwrap();
+        });
+
+        // Keys outside the range are written as usual
+        users.insert(user(31, "outside", 0)).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!insert.is_finished() && !delete.is_finished());
+        assert_eq!(scan(), first);
+
+        drop(range);
+        insert.join().unwrap();
+        delete.join().unwrap();
+    });
+    assert_eq!(scan(), [6, 7, 8, 12, 14].map(Value::Integer).to_vec());
+    assert!(matches!(
+        users.lock_key_range(
+            "users_id",
+            &Value::Null,
+            &Value::Integer(1),
+            LockMode::Shared
+        ),
+        Err(CrioError::SchemaMismatch)
+    ));
+}
+
+#[test]
+fn test_database_query_cancellation() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("cancel.db");
+    let config = CrioConfig::builder()
+        .pool_size(32)
+        .statement_timeout(Duration::ZERO)
+        .build()
+        .unwrap();
+    let db = Database::open(&path, config).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    db.create_index("users_id", "users", "id").unwrap();
+    for i in 0..2000 {
+        users.insert(user(i, "someone", 0)).unwrap();
+    }
+
+    // The configured timeout expires before the first page is read; writes
+    // are not statements that time out
+    assert!(matches!(users.scan(), Err(CrioError::QueryCancelled)));
+    assert!(matches!(
+        users.scan_index("users_id", &Value::Integer(0), &Value::Integer(10)),
+        Err(CrioError::QueryCancelled)
+    ));
+    let users = users.with_statement_timeout(None);
+    assert_eq!(users.scan().unwrap().len(), 2000);
+
+    // A token cancelled from another thread stops a running scan
+    let token = CancellationToken::new();
+    let cancellable = users.with_cancellation(token.clone());
+    std::thread::scope(|s| {
+        let scans = s.spawn(|| loop {
+            match cancellable.scan_eq("name", &Value::String("someone".into())) {
+                Ok(rows) => assert_eq!(rows.len(), 2000),
+                Err(e) => return e,
+            }
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        token.cancel();
+        assert!(matches!(scans.join().unwrap(), CrioError::QueryCancelled));
+    });
+    assert!(matches!(
+        cancellable.scan_range("id", Value::Integer(0)..),
+        Err(CrioError::QueryCancelled)
+    ));
+    // Other handles are unaffected
+    assert_eq!(
+        users
+            .scan_range("id", Value::Integer(1990)..)
+            .unwrap()
+            .len(),
+        10
+    );
+}
+
+#[test]
+fn test_database_query_budget() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("budget.db");
+    let config = CrioConfig::builder()
+        .pool_size(32)
+        .query_limits(ResourceLimits {
+            max_pages_read: Some(4),
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let db = Database::open(&path, config).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    db.create_index("users_id", "users", "id").unwrap();
+    for i in 0..2000 {
+        users.insert(user(i, "someone", 0)).unwrap();
+    }
+    assert!(users.approx_size_bytes() > 4 * PAGE_SIZE as u64);
+
+    // Full scans read more pages than the configured limit; each narrow
+    // scan gets a budget of its own
+    assert!(matches!(
+        users.scan(),
+        Err(CrioError::BudgetExceeded {
+            resource: "pages read",
+            limit: 4
+        })
+    ));
+    for _ in 0..3 {
+        assert_eq!(
+            users
+                .scan_index("users_id", &Value::Integer(0), &Value::Integer(9))
+                .unwrap()
+                .len(),
+            10
+        );
+    }
+
+    // A shared budget holds several scans to one limit
+    let budget = QueryBudget::new(ResourceLimits {
+        max_bytes_allocated: Some(1000),
+        ..Default::default()
+    });
+    let budgeted = users.with_budget(budget.clone());
+    let rows = budgeted
+        .scan_index("users_id", &Value::Integer(0), &Value::Integer(9))
+        .unwrap();
+    let bytes = budget.usage().bytes_allocated;
+    assert!(bytes > 0 && bytes < 1000);
+    assert_eq!(rows.len(), 10);
+    assert!(matches!(
+        budgeted.scan_index("users_id", &Value::Integer(0), &Value::Integer(999)),
+        Err(CrioError::BudgetExceeded { limit: 1000, .. })
+    ));
+
+    // EXPLAIN ANALYZE reports what a statement used
+    let unlimited = users.with_query_limits(ResourceLimits::default());
+    let (rows, report) = unlimited.explain_analyze(|t| t.scan()).unwrap();
+    assert_eq!(rows.len(), 2000);
+    assert_eq!(report.rows, 2000);
+    assert_eq!(
+        report.usage.pages_read,
+        users.approx_size_bytes() / PAGE_SIZE as u64
+    );
+    assert!(report.usage.bytes_allocated >= 2000);
+    assert!(report.to_string().starts_with("rows=2000 time="));
+    let (_, report) = unlimited
+        .explain_analyze(|t| {
+            let mut rows = t.scan_eq("id", &Value::Integer(7))?;
+            rows.extend(t.scan_index("users_id", &Value::Integer(7), &Value::Integer(7))?);
+            Ok(rows)
+        })
+        .unwrap();
+    assert_eq!(report.rows, 2);
+    assert!(report.usage.pages_read > users.approx_size_bytes() / PAGE_SIZE as u64);
+}
+
+#[test]
+fn test_database_export_snapshot() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("source.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    db.create_index("users_id", "users", "id").unwrap();
+    db.create_zone_map("users", &["age"]).unwrap();
+    let mut rids = Vec::new();
+    for i in 0..2000 {
+        rids.push(users.insert(user(i, "someone", (i % 100) as i16)).unwrap());
+    }
+    for rid in rids.iter().step_by(2) {
+        users.delete(*rid).unwrap();
+    }
+    let events = db
+        .create_partitioned_table(
+            "events",
+            users_schema(),
+            "id",
+            PartitionScheme::Range(vec![100]),
+        )
+        .unwrap();
+    for i in 0..200 {
+        events.insert(user(i, "event", 0)).unwrap();
+    }
+
+    let path = temp_dir.path().join("snapshot.db");
+    let stats = db.export_snapshot(&path).unwrap();
+    assert_eq!((stats.tables, stats.rows, stats.indexes), (3, 1200, 1));
+    assert!(db.export_snapshot(&path).is_err());
+    assert!(!temp_dir.path().join("snapshot.db.1").exists());
+
+    let config = CrioConfig::builder()
+        .pool_size(32)
+        .read_only()
+        .build()
+        .unwrap();
+    let snapshot = Database::open(&path, config).unwrap();
+    assert!(snapshot.read_only());
+    assert!(!snapshot.unclean_shutdown());
+    let copy = snapshot.table("users").unwrap();
+    assert_eq!(copy.scan().unwrap().len(), 1000);
+    assert!(copy.approx_size_bytes() < users.approx_size_bytes());
+    let found = copy
+        .scan_index("users_id", &Value::Integer(0), &Value::Integer(9))
+        .unwrap();
+    let ids: Vec<_> = found.iter().map(|(_, t)| t.value(0).cloned()).collect();
+    assert_eq!(ids, [1, 3, 5, 7, 9].map(|id| Some(Value::Integer(id))));
+    let table_id = snapshot.catalog().table("users").unwrap().table_id;
+    assert!(snapshot.catalog().zone_map(table_id).is_some());
+    assert_eq!(
+        snapshot
+            .partitioned_table("events")
+            .unwrap()
+            .scan(..)
+            .unwrap()
+            .len(),
+        200
+    );
+
+    // Every change is refused, and nothing is written on close
+    assert!(matches!(
+        copy.insert(user(5000, "new", 1)),
+        Err(CrioError::ReadOnly)
+    ));
+    assert!(matches!(
+        snapshot.create_table("other", users_schema()),
+        Err(CrioError::ReadOnly)
+    ));
+    let modified = std::fs::metadata(temp_dir.path().join("snapshot.db.0"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    drop(copy);
+    snapshot.close().unwrap();
+    let after = std::fs::metadata(temp_dir.path().join("snapshot.db.0"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    assert_eq!(modified, after);
+}
+
+#[test]
+fn test_export_snapshot_waits_for_writers() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("source.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    users.insert(user(1, "someone", 30)).unwrap();
+    let path = temp_dir.path().join("snapshot.db");
+
+    // A write to a table without indexes holds only a shared lock
+    let table_id = db.catalog().table("users").unwrap().table_id;
+    let write_lock = db.lock_manager().lock_table(table_id, LockMode::Shared);
+    std::thread::scope(|scope| {
+        let export = scope.spawn(|| db.export_snapshot(&path));
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!temp_dir.path().join("snapshot.db.0").exists());
+        drop(write_lock);
+        assert_eq!(export.join().unwrap().unwrap().rows, 1);
+    });
+}
+
+#[test]
+fn test_database_manager() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().join("tenants");
+    let manager = DatabaseManager::new(&dir, options())
+        .unwrap()
+        .with_frame_budget(64);
+
+    // Each tenant has a catalog of its own
+    let acme = manager.open("acme").unwrap();
+    let globex = manager.open("globex").unwrap();
+    acme.create_table("users", users_schema())
+        .unwrap()
+        .insert(user(1, "wile", 40))
+        .unwrap();
+    assert!(globex.table("users").is_err());
+    assert!(Arc::ptr_eq(&acme, &manager.open("acme").unwrap()));
+    assert_eq!(manager.frames_in_use(), 64);
+    assert!(matches!(
+        manager.open("initech"),
+        Err(CrioError::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        manager.open("../escape"),
+        Err(CrioError::InvalidDatabaseName(_))
+    ));
+
+    // A database can only be closed once nobody else holds it
+    assert!(matches!(
+        manager.close("acme"),
+        Err(CrioError::ObjectInUse(_))
+    ));
+    drop(acme);
+    manager.close("acme").unwrap();
+    assert_eq!(manager.open_names(), vec!["globex"]);
+    assert_eq!(manager.names().unwrap(), vec!["acme", "globex"]);
+
+    let acme = manager.open("acme").unwrap();
+    assert_eq!(acme.table("users").unwrap().scan().unwrap().len(), 1);
+    drop(acme);
+
+    drop(globex);
+    manager.drop_database("globex").unwrap();
+    assert!(!manager.exists("globex"));
+    assert!(matches!(
+        manager.drop_database("globex"),
+        Err(CrioError::UnknownDatabase(_))
+    ));
+    manager.close_all().unwrap();
+    assert!(manager.open_names().is_empty());
+}
+
+#[test]
+fn test_database_attach_and_join() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let crm_path = temp_dir.path().join("crm.db");
+    {
+        let crm = Database::open(&crm_path, options()).unwrap();
+        let users = crm.create_table("users", users_schema()).unwrap();
+        for (id, name) in [(1, "ann"), (2, "bob"), (3, "cy")] {
+            users.insert(user(id, name, 30)).unwrap();
+        }
+        crm.close().unwrap();
+    }
+
+    let db = Database::open(temp_dir.path().join("sales.db"), options()).unwrap();
+    let orders_schema = Schema::builder()
+        .column("user_id", DataType::Integer)
+        .column("total", DataType::Integer)
+        .build();
+    let orders = db.create_table("orders", orders_schema).unwrap();
+    for (user_id, total) in [(2, 10), (1, 20), (2, 30), (9, 40)] {
+        orders
+            .insert(vec![Value::Integer(user_id), Value::Integer(total)])
+            .unwrap();
+    }
+
+    let config = CrioConfig::builder()
+        .pool_size(16)
+        .read_only()
+        .build()
+        .unwrap();
+    let crm = db.attach("crm", &crm_path, config.clone()).unwrap();
+    assert!(matches!(
+        db.attach("crm", &crm_path, config),
+        Err(CrioError::DuplicateAlias(_))
+    ));
+    assert_eq!(db.attached_aliases(), vec!["crm"]);
+    assert_eq!(db.table("crm.users").unwrap().scan().unwrap().len(), 3);
+    assert!(db.table("crm.missing").is_err());
+    assert!(db.table("other.users").is_err());
+
+    // Orders of the primary database joined with users of the attached
+    // one, in order of the orders
+    let probe = SeqScanExecutor::new(db.catalog().table("orders").unwrap()).unwrap();
+    let build = SeqScanExecutor::new(crm.catalog().table("users").unwrap()).unwrap();
+    let join = HashJoinExecutor::new(probe, 0, build, 0).unwrap();
+    let joined: Vec<_> = join
+        .map(|row| {
+            let (_, tuple) = row.unwrap();
+            (tuple.value(1).cloned(), tuple.value(3).cloned())
+        })
+        .collect();
+    assert_eq!(
+        joined,
+        vec![
+            (Some(Value::Integer(10)), Some(Value::String("bob".into()))),
+            (Some(Value::Integer(20)), Some(Value::String("ann".into()))),
+            (Some(Value::Integer(30)), Some(Value::String("bob".into()))),
+        ]
+    );
+
+    assert!(matches!(db.detach("crm"), Err(CrioError::ObjectInUse(_))));
+    drop(crm);
+    db.detach("crm").unwrap();
+    assert!(db.attached("crm").is_none());
+    assert!(db.table("crm.users").is_err());
+    assert!(matches!(
+        db.detach("crm"),
+        Err(CrioError::UnknownDatabase(_))
+    ));
+}
+
+#[test]
+fn test_database_reopen() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("reopen.db");
+
+    {
+        let db = Database::open(&path, options()).unwrap();
+        let users = db.create_table("users", users_schema()).unwrap();
+        db.create_index("users_id", "users", "id").unwrap();
+        for i in 0..500 {
+            users
+                .insert(vec![
+                    Value::Integer(i),
+                    Value::String(format!("user{}", i)),
+                    Value::SmallInt(i as i16),
+                ])
+                .unwrap();
+        }
+        db.create_table("orders", users_schema()).unwrap();
+        db.close().unwrap();
+    }
+
+    let db = Database::open(&path, options()).unwrap();
+    assert!(!db.unclean_shutdown());
+    let mut names = db.catalog().table_names();
+    names.sort();
+    assert_eq!(names, vec!["orders", "users"]);
+
+    let users = db.table("users").unwrap();
+    assert_eq!(*users.schema().as_ref(), users_schema());
+    assert_eq!(users.scan().unwrap().len(), 500);
+    assert_eq!(users.approx_row_count(), 500);
+
+    let (_, row) = users
+        .lookup("users_id", &Value::Integer(321))
+        .unwrap()
+        .unwrap();
+    assert_eq!(row.value(2), Some(&Value::SmallInt(321)));
+}
+
+#[test]
+fn test_database_drop_table() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("drop.db");
+    {
+        let db = Database::open(&path, options()).unwrap();
+        let users = db.create_table("users", users_schema()).unwrap();
+        for i in 0..20 {
+            users
+                .insert(vec![
+                    Value::Integer(i),
+                    Value::String(format!("user{}", i)),
+                    Value::Null,
+                ])
+                .unwrap();
+        }
+        db.create_index("users_id", "users", "id").unwrap();
+        db.create_table("orders", users_schema()).unwrap();
+
+        db.drop_index("users_id").unwrap();
+        assert!(matches!(
+            users.lookup("users_id", &Value::Integer(3)),
+            Err(CrioError::UnknownIndex(_))
+        ));
+
+        // A live handle keeps the table from being dropped
+        assert!(matches!(
+            db.drop_table("users"),
+            Err(CrioError::ObjectInUse(_))
+        ));
+        drop(users);
+        db.drop_table("users").unwrap();
+        assert!(matches!(db.table("users"), Err(CrioError::UnknownTable(_))));
+        db.close().unwrap();
+    }
+
+    let db = Database::open(&path, options()).unwrap();
+    assert!(matches!(db.table("users"), Err(CrioError::UnknownTable(_))));
+    assert!(db.table("orders").is_ok());
+    let users = db.create_table("users", users_schema()).unwrap();
+    assert!(users.scan().unwrap().is_empty());
+}
+
+#[test]
+fn test_database_column_defaults() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("app.db");
+    let schema = Schema::builder()
+        .column("id", DataType::Integer)
+        .column_with_default("role", DataType::VarChar(16), Value::from("member"))
+        .nullable_column("age", DataType::SmallInt)
+        .build();
+
+    {
+        let db = Database::open(&path, options()).unwrap();
+        let bad = Schema::builder()
+            .column_with_default("id", DataType::Integer, Value::from("one"))
+            .build();
+        assert!(matches!(
+            db.create_table("bad", bad),
+            Err(CrioError::InvalidDefault(_))
+        ));
+        db.create_table("users", schema).unwrap();
+        db.close().unwrap();
+    }
+
+    let db = Database::open(&path, options()).unwrap();
+    let users = db.table("users").unwrap();
+    let rid = users
+        .insert_columns(vec![("id", Value::Integer(1))])
+        .unwrap();
+    let row = users.get(rid).unwrap();
+    assert_eq!(
+        row.value_by_name("role"),
+        Some(&Value::String("member".into()))
+    );
+    assert_eq!(row.value_by_name("age"), Some(&Value::Null));
+
+    assert!(matches!(
+        users.insert_columns(vec![("role", Value::from("admin"))]),
+        Err(CrioError::MissingValue(_))
+    ));
+    assert!(matches!(
+        users.insert_columns(vec![("id", Value::Integer(2)), ("nope", Value::Null)]),
+        Err(CrioError::UnknownColumn(_))
+    ));
+}
+
+#[test]
+fn test_database_bulk_insert_survives_reopen() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("app.db");
+
+    let table_id = {
+        let db = Database::open(&path, options()).unwrap();
+        let users = db.create_table("users", users_schema()).unwrap();
+        let rows: Vec<_> = (0..500)
+            .map(|i| {
+                let values = vec![
+                    Value::Integer(i),
+                    Value::String(format!("user{}", i)),
+                    Value::Null,
+                ];
+                Tuple::new(Arc::clone(users.schema()), values)
+                    .to_bytes()
+                    .unwrap()
+            })
+            .collect();
+
+        let info = db.catalog().table("users").unwrap();
+        info.heap.bulk_insert(&rows, DEFAULT_FILL_FACTOR).unwrap();
+
+        // Bulk pages come from whole extents owned by the table
+        let extent_pages: u32 = db
+            .disk_manager()
+            .get_table_page_ranges(info.table_id)
+            .iter()
+            .map(|&(_, count)| count)
+            .sum();
+        assert!(extent_pages as usize >= info.heap.page_ids().unwrap().len() - 1);
+
+        let table_id = info.table_id;
+        drop(info);
+        drop(users);
+        db.close().unwrap();
+        table_id
+    };
+
+    let db = Database::open(&path, options()).unwrap();
+    let users = db.table("users").unwrap();
+    assert_eq!(users.table_id(), table_id);
+    let rows = users.scan().unwrap();
+    assert_eq!(rows.len(), 500);
+    assert_eq!(rows[499].1.value(0), Some(&Value::Integer(499)));
+}
+
+fn user(id: i32, name: &str, age: i16) -> Vec<Value> {
+    vec![
+        Value::Integer(id),
+        Value::String(name.into()),
+        Value::SmallInt(age),
+    ]
+}
+
+#[test]
+fn test_database_scan_batches() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("batches.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    for i in 0..3000 {
+        users.insert(user(i, "someone", (i % 90) as i16)).unwrap();
+    }
+
+    let batches = users.scan_batches(None, None).unwrap();
+    assert!(batches.len() >= 3);
+    assert!(batches.iter().all(|b| !b.is_empty() && b.len() <= 1024));
+    assert_eq!(batches.iter().map(|b| b.len()).sum::<usize>(), 3000);
+
+    let adults = BatchPredicate::And(vec![
+        BatchPredicate::Compare(2, CompareOp::GtEq, Value::SmallInt(18)),
+        BatchPredicate::Compare(0, CompareOp::Lt, Value::Integer(900)),
+    ]);
+    let batches = users
+        .scan_batches(Some(adults), Some(&["age", "id"]))
+        .unwrap();
+    let rows: Vec<_> = batches.into_iter().flat_map(|b| b.into_rows()).collect();
+    assert_eq!(rows.len(), 720);
+    assert_eq!(rows[0].1.schema().column_count(), 2);
+    assert_eq!(rows[0].1.value(0), Some(&Value::SmallInt(18)));
+    assert_eq!(rows[0].1.value(1), Some(&Value::Integer(18)));
+
+    assert!(matches!(
+        users.scan_batches(None, Some(&["nope"])),
+        Err(CrioError::UnknownColumn(_))
+    ));
+}
+
+#[test]
+fn test_database_hash_join_runtime_filter() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("join.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    for i in [40, 41, 42, 45] {
+        users.insert(user(i, "someone", 30)).unwrap();
+    }
+    let orders_schema = Schema::builder()
+        .column("user_id", DataType::Integer)
+        .column("note", DataType::VarChar(100))
+        .build();
+    let orders = db.create_table("orders", orders_schema).unwrap();
+    db.create_zone_map("orders", &["user_id"]).unwrap();
+    // Ten orders per user, in user order, so each page holds a few users
+    for i in 0..3000 {
+        orders
+            .insert(vec![Value::Integer(i / 10), Value::String("x".repeat(80))])
+            .unwrap();
+    }
+    let order_pages = orders.approx_size_bytes() / PAGE_SIZE as u64;
+
+    let (rows, report) = orders
+        .explain_analyze(|t| t.hash_join("user_id", &users, "id", JoinType::Inner))
+        .unwrap();
+    assert_eq!(rows.len(), 40);
+    assert_eq!(rows[0].1.schema().column_count(), 5);
+    let filter = report.runtime_filters[0];
+    assert_eq!(filter.kind, Some(RuntimeFilterKind::InList));
+    assert_eq!(filter.keys, 4);
+    // Pages outside users 40..=45 are skipped, the rest filtered by row
+    assert!(filter.pages_skipped > order_pages / 2);
+    assert_eq!(filter.rows_checked - filter.rows_filtered, 40);
+    assert!(report.usage.pages_read < order_pages);
+    assert!(report
+        .to_string()
+        .contains("runtime filter=in-list(4 keys)"));
+
+    // Semi-joins get filters too; anti-joins need every probe row
+    let semi = orders
+        .hash_join("user_id", &users, "id", JoinType::Semi)
+        .unwrap();
+    assert_eq!(semi.len(), 40);
+    let (anti, report) = orders
+        .explain_analyze(|t| t.hash_join("user_id", &users, "id", JoinType::Anti))
+        .unwrap();
+    assert_eq!(anti.len(), 2960);
+    assert_eq!(report.runtime_filters[0].kind, None);
+
+    // A self-join locks the table once
+    let pairs = users
+        .hash_join("id", &users, "id", JoinType::Inner)
+        .unwrap();
+    assert_eq!(pairs.len(), 4);
+    assert!(matches!(
+        orders.hash_join("nope", &users, "id", JoinType::Inner),
+        Err(CrioError::UnknownColumn(_))
+    ));
+}
+
+#[test]
+fn test_database_upsert() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("upsert.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    db.create_index("users_id", "users", "id").unwrap();
+    users.insert(user(1, "ada", 36)).unwrap();
+
+    // DO NOTHING skips the conflicting row and inserts the others
+    let written: Vec<_> = UpsertExecutor::new(
+        users.clone(),
+        "users_id",
+        vec![user(1, "dup", 0), user(2, "bob", 40)],
+        OnConflict::DoNothing,
+    )
+    .collect::<Result<_, _>>()
+    .unwrap();
+    assert_eq!(written.len(), 1);
+    assert_eq!(written[0].1.value(1), Some(&Value::String("bob".into())));
+    let (_, ada) = users
+        .lookup("users_id", &Value::Integer(1))
+        .unwrap()
+        .unwrap();
+    assert_eq!(ada.value(1), Some(&Value::String("ada".into())));
+
+    // DO UPDATE merges the proposed row into the existing one in place
+    let take_name = OnConflict::update(|existing, proposed| {
+        let mut values = existing.values().to_vec();
+        values[1] = proposed.values()[1].clone();
+        values
+    });
+    let written: Vec<_> = UpsertExecutor::new(
+        users.clone(),
+        "users_id",
+        vec![user(1, "ada lovelace", 0), user(3, "cy", 20)],
+        take_name,
+    )
+    .collect::<Result<_, _>>()
+    .unwrap();
+    assert_eq!(written.len(), 2);
+    let (rid, ada) = users
+        .lookup("users_id", &Value::Integer(1))
+        .unwrap()
+        .unwrap();
+    assert_eq!(rid, written[0].0);
+    assert_eq!(ada.values(), user(1, "ada lovelace", 36).as_slice());
+    assert_eq!(users.scan().unwrap().len(), 3);
+
+    // Updates may move a row to a free key, but not onto another row's
+    let rekey = OnConflict::update(|_, _| user(9, "moved", 0));
+    let (rid, _) = users
+        .upsert("users_id", user(2, "bob", 0), &rekey)
+        .unwrap()
+        .unwrap();
+    assert!(users
+        .lookup("users_id", &Value::Integer(2))
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        users
+            .lookup("users_id", &Value::Integer(9))
+            .unwrap()
+            .unwrap()
+            .0,
+        rid
+    );
+    let onto_ada = OnConflict::update(|_, _| user(1, "clash", 0));
+    assert!(matches!(
+        users.upsert("users_id", user(9, "moved", 0), &onto_ada),
+        Err(CrioError::DuplicateKey(_))
+    ));
+    assert!(matches!(
+        users.upsert("missing", user(2, "bob", 0), &OnConflict::DoNothing),
+        Err(CrioError::UnknownIndex(_))
+    ));
+
+    // Concurrent upserts of one key are serialized
+    let increment = OnConflict::update(|existing, _| {
+        let mut values = existing.values().to_vec();
+        if let Value::SmallInt(age) = values[2] {
+            values[2] = Value::SmallInt(age + 1);
+        }
+        values
+    });
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..25 {
+                    users
+                        .upsert("users_id", user(4, "dee", 0), &increment)
+                        .unwrap();
+                }
+            });
+        }
+    });
+    let (_, dee) = users
+        .lookup("users_id", &Value::Integer(4))
+        .unwrap()
+        .unwrap();
+    assert_eq!(dee.value(2), Some(&Value::SmallInt(99)));
+    assert_eq!(users.scan().unwrap().len(), 4);
+}
+
+#[test]
+fn test_database_partitioned_table() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("app.db");
+
+    {
+        let db = Database::open(&path, options()).unwrap();
+        let users = db
+            .create_partitioned_table(
+                "users",
+                users_schema(),
+                "id",
+                PartitionScheme::Range(vec![1000, 2000]),
+            )
+            .unwrap();
+        for id in (0..3000).step_by(10) {
+            users
+                .insert(vec![
+                    Value::Integer(id),
+                    Value::String(format!("user{}", id)),
+                    Value::Null,
+                ])
+                .unwrap();
+        }
+
+        // Rows land in the partition owning their key
+        let middle = users.partition(1).unwrap();
+        assert_eq!(middle.name(), "users_p1");
+        assert_eq!(middle.scan().unwrap().len(), 100);
+
+        let rows = users.scan(1500..2100).unwrap();
+        assert_eq!(rows.len(), 60);
+        assert!(rows
+            .iter()
+            .all(|(_, row)| matches!(row.value(0), Some(&Value::Integer(id)) if (1500..2100).contains(&id))));
+        db.close().unwrap();
+    }
+
+    let db = Database::open(&path, options()).unwrap();
+    let users = db.partitioned_table("users").unwrap();
+    assert_eq!(users.partition_count(), 3);
+    assert_eq!(users.scan(..).unwrap().len(), 300);
+    assert!(matches!(
+        db.drop_table("users"),
+        Err(CrioError::ObjectInUse(_))
+    ));
+    drop(users);
+    db.drop_table("users").unwrap();
+    assert!(matches!(
+        db.partitioned_table("users"),
+        Err(CrioError::UnknownTable(_))
+    ));
+}
+
+#[test]
+fn test_database_shadow_paging() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("shadow.db");
+    let shadow = DatabaseOptions {
+        shadow_paging: true,
+        ..options()
+    };
+
+    {
+        let db = Database::open(&path, shadow.clone()).unwrap();
+        assert!(db.shadow_paging());
+        let users = db.create_table("users", users_schema()).unwrap();
+        db.create_index("users_id", "users", "id").unwrap();
+        for i in 0..200 {
+            users.insert(user(i, &format!("user{}", i), 30)).unwrap();
+        }
+        db.commit().unwrap();
+
+        // Written to disk but never committed
+        for i in 200..400 {
+            users.insert(user(i, &format!("user{}", i), 30)).unwrap();
+        }
+        db.create_table("orders", users_schema()).unwrap();
+        db.flush().unwrap();
+
+        // Crash: nothing is committed on the way out
+        std::mem::forget(users);
+        std::mem::forget(db);
+    }
+
+    {
+        let db = Database::open(&path, shadow.clone()).unwrap();
+        assert!(db.unclean_shutdown());
+        let users = db.table("users").unwrap();
+        assert_eq!(users.scan().unwrap().len(), 200);
+        assert!(users
+            .lookup("users_id", &Value::Integer(300))
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            db.table("orders"),
+            Err(CrioError::UnknownTable(_))
+        ));
+        assert!(crio::check::check_database(&db).is_ok());
+
+        // A clean close commits
+        users.insert(user(1000, "late", 30)).unwrap();
+        drop(users);
+        db.close().unwrap();
+    }
+
+    // The mode is fixed when the database is created
+    let db = Database::open(&path, options()).unwrap();
+    assert!(db.shadow_paging());
+    assert_eq!(db.table("users").unwrap().scan().unwrap().len(), 201);
+    drop(db);
+
+    let plain = Database::open(temp_dir.path().join("plain.db"), options()).unwrap();
+    drop(plain);
+    let plain = Database::open(temp_dir.path().join("plain.db"), shadow).unwrap();
+    assert!(!plain.shadow_paging());
+}
+
+#[test]
+fn test_database_double_write() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("dblwr.db");
+    let dblwr_path = temp_dir.path().join("dblwr.db.dblwr");
+    let double_write = DatabaseOptions {
+        double_write: true,
+        ..options()
+    };
+
+    {
+        let db = Database::open(&path, double_write.clone()).unwrap();
+        let users = db.create_table("users", users_schema()).unwrap();
+        for i in 0..200 {
+            users.insert(user(i, &format!("user{}", i), 30)).unwrap();
+        }
+        db.flush().unwrap();
+
+        // Crash: the last batch is still staged
+        std::mem::forget(users);
+        std::mem::forget(db);
+    }
+    assert!(std::fs::metadata(&dblwr_path).unwrap().len() > 0);
+
+    {
+        let db = Database::open(&path, double_write).unwrap();
+        assert_eq!(db.table("users").unwrap().scan().unwrap().len(), 200);
+        db.close().unwrap();
+    }
+    assert_eq!(std::fs::metadata(&dblwr_path).unwrap().len(), 0);
+
+    // Reopening without double writes restores and removes the file
+    let db = Database::open(&path, options()).unwrap();
+    assert!(!dblwr_path.exists());
+    assert_eq!(db.table("users").unwrap().scan().unwrap().len(), 200);
+    assert!(crio::check::check_database(&db).is_ok());
+}
+
+#[test]
+fn test_database_open_with_config() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("config.db");
+
+    let invalid = CrioConfig {
+        lru_k: 0,
+        ..CrioConfig::default()
+    };
+    assert!(matches!(
+        Database::open(&path, invalid),
+        Err(CrioError::InvalidConfig(_))
+    ));
+
+    let config = CrioConfig::builder()
+        .pool_size(16)
+        .scheduler_queue_depth(4)
+        .build()
+        .unwrap();
+    let db = Database::open(&path, config).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    for i in 0..100 {
+        users.insert(user(i, &format!("user{}", i), 20)).unwrap();
+    }
+    assert_eq!(users.scan().unwrap().len(), 100);
+}
+
+#[test]
+fn test_database_secondary_cache() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("tiered.db");
+    let cache_path = temp_dir.path().join("tiered.cache");
+    let config = CrioConfig::builder()
+        .pool_size(8)
+        .secondary_cache(&cache_path, 0)
+        .build();
+    assert!(matches!(config, Err(CrioError::InvalidConfig(_))));
+
+    let config = CrioConfig::builder()
+        .pool_size(8)
+        .secondary_cache(&cache_path, 256)
+        .build()
+        .unwrap();
+    let db = Database::open(&path, config).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    for i in 0..5000 {
+        users.insert(user(i, &format!("user{}", i), 20)).unwrap();
+    }
+    assert!(cache_path.exists());
+
+    // The table outgrows the pool but not the cache, so rescans are served
+    // from the cache
+    for _ in 0..3 {
+        assert_eq!(users.scan().unwrap().len(), 5000);
+    }
+    let stats = db.buffer_pool().secondary_cache_stats().unwrap();
+    assert!(stats.hits > 0);
+    assert!(stats.cached > 0 && stats.cached <= 256);
+}
+
+#[test]
+fn test_database_json_path_index() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("json.db");
+    let schema = Schema::builder()
+        .column("id", DataType::Integer)
+        .nullable_column("doc", DataType::Json)
+        .build();
+    let event = |id: i32, doc: &str| {
+        vec![
+            Value::Integer(id),
+            Value::Json(JsonValue::parse(doc).unwrap()),
+        ]
+    };
+
+    {
+        let db = Database::open(&path, options()).unwrap();
+        let events = db.create_table("events", schema).unwrap();
+        events
+            .insert(event(1, r#"{"user": {"id": 100}, "kind": "login"}"#))
+            .unwrap();
+        // Rows without an integer at the path are not indexed
+        events.insert(event(2, r#"{"kind": "tick"}"#)).unwrap();
+        events.insert(vec![Value::Integer(3), Value::Null]).unwrap();
+
+        db.create_path_index("events_user", "events", "doc", "user.id")
+            .unwrap();
+        assert!(matches!(
+            db.create_path_index("events_id", "events", "id", "user.id"),
+            Err(CrioError::UnindexableColumn(_))
+        ));
+        assert!(matches!(
+            db.create_path_index("events_bad", "events", "doc", "user..id"),
+            Err(CrioError::InvalidJsonPath(_))
+        ));
+
+        events
+            .insert(event(4, r#"{"user": {"id": 200}, "kind": "logout"}"#))
+            .unwrap();
+        assert!(matches!(
+            events.insert(event(5, r#"{"user": {"id": 100}}"#)),
+            Err(CrioError::DuplicateKey(_))
+        ));
+        db.close().unwrap();
+    }
+
+    let db = Database::open(&path, options()).unwrap();
+    let events = db.table("events").unwrap();
+    let (_, row) = events
+        .lookup("events_user", &Value::Integer(200))
+        .unwrap()
+        .unwrap();
+    assert_eq!(row.value(0), Some(&Value::Integer(4)));
+    assert_eq!(
+        row.value(1).unwrap().json_get("kind"),
+        Some(Value::String("logout".into()))
+    );
+    assert!(events
+        .lookup("events_user", &Value::Integer(300))
+        .unwrap()
+        .is_none());
+    assert_eq!(events.scan().unwrap().len(), 4);
+
+    let indexes = db.scan_system_table("crio_indexes").unwrap();
+    assert_eq!(
+        indexes[0].value(2),
+        Some(&Value::String("doc->'user.id'".into()))
+    );
+}
+
+#[test]
+fn test_database_text_index() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("text.db");
+    let schema = Schema::builder()
+        .column("id", DataType::Integer)
+        .nullable_column("body", DataType::VarChar(200))
+        .build();
+    let doc = |id: i32, body: &str| vec![Value::Integer(id), Value::String(body.into())];
+    let ids = |rows: Vec<(_, Tuple)>| -> Vec<i32> {
+        rows.iter()
+            .map(|(_, row)| match row.value(0) {
+                Some(Value::Integer(id)) => *id,
+                _ => panic!("bad row"),
+            })
+            .collect()
+    };
+
+    {
+        let db = Database::open(&path, options()).unwrap();
+        let docs = db.create_table("docs", schema).unwrap();
+        docs.insert(doc(1, "The buffer pool caches pages")).unwrap();
+        docs.insert(vec![Value::Integer(2), Value::Null]).unwrap();
+        db.create_text_index("docs_body", "docs", "body").unwrap();
+        assert!(matches!(
+            db.create_text_index("docs_id", "docs", "id"),
+            Err(CrioError::UnindexableColumn(_))
+        ));
+        assert!(matches!(
+            db.create_index("docs_body", "docs", "id"),
+            Err(CrioError::DuplicateIndexName(_))
+        ));
+
+        docs.insert(doc(3, "Pages are read into the pool")).unwrap();
+        let deleted = docs.insert(doc(4, "A buffer pool of pages")).unwrap();
+        docs.delete(deleted).unwrap();
+        let edited = docs.insert(doc(5, "Pages of the pool")).unwrap();
+        docs.update(edited, doc(5, "A log of records")).unwrap();
+        assert!(crio::check::check_database(&db).is_ok());
+        db.close().unwrap();
+    }
+
+    let db = Database::open(&path, options()).unwrap();
+    let docs = db.table("docs").unwrap();
+    let search = |query| docs.search_text("docs_body", query).unwrap();
+    assert_eq!(ids(search(TextQuery::Term("PAGES".into()))), vec![1, 3]);
+    assert_eq!(ids(search(TextQuery::Term("log".into()))), vec![5]);
+    assert_eq!(
+        ids(search(TextQuery::Phrase("buffer pool".into()))),
+        vec![1]
+    );
+    assert!(search(TextQuery::Phrase("pool buffer".into())).is_empty());
+    assert!(matches!(
+        docs.search_text("missing", TextQuery::Term("pool".into())),
+        Err(CrioError::UnknownIndex(_))
+    ));
+
+    let indexes = db.scan_system_table("crio_indexes").unwrap();
+    assert_eq!(
+        indexes[0].value(0),
+        Some(&Value::String("docs_body".into()))
+    );
+
+    drop(docs);
+    db.drop_index("docs_body").unwrap();
+    assert!(db.catalog().text_index("docs_body").is_none());
+    assert!(crio::check::check_database(&db).is_ok());
+}
+
+#[test]
+fn test_collated_access_paths() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("collated.db");
+    let schema = Schema::builder()
+        .column("id", DataType::Integer)
+        .collated_column("name", DataType::VarChar(64), Collation::CaseInsensitive)
+        .build();
+
+    {
+        let db = Database::open(&path, options()).unwrap();
+        let users = db.create_table("users", schema).unwrap();
+        for i in 0..500 {
+            users
+                .insert(vec![Value::Integer(i), Value::String(format!("User{}", i))])
+                .unwrap();
+        }
+        db.create_bloom_filter("users", "name").unwrap();
+        db.create_zone_map("users", &["name"]).unwrap();
+        db.close().unwrap();
+    }
+
+    // Every access path treats names that differ only in case as equal
+    let db = Database::open(&path, options()).unwrap();
+    let users = db.table("users").unwrap();
+    let name = Value::String("USER42".into());
+    let rows = users.scan_eq("name", &name).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].1.value(0), Some(&Value::Integer(42)));
+
+    let range = Value::String("user100".into())..=Value::String("USER109".into());
+    let mut ids: Vec<_> = users
+        .scan_range("name", range)
+        .unwrap()
+        .into_iter()
+        .map(|(_, row)| row.values()[0].clone())
+        .collect();
+    ids.sort_by(|a, b| a.compare(b).unwrap());
+    assert_eq!(ids, (100..110).map(Value::Integer).collect::<Vec<_>>());
+
+    let filter = BatchPredicate::Compare(1, CompareOp::Eq, Value::String("uSeR42".into()));
+    let ids: Vec<_> = users
+        .scan_batches(Some(filter), Some(&["id"]))
+        .unwrap()
+        .into_iter()
+        .flat_map(|batch| batch.into_rows())
+        .map(|(_, row)| row.values()[0].clone())
+        .collect();
+    assert_eq!(ids, vec![Value::Integer(42)]);
+    let filter = BatchPredicate::Like(1, "USER4_".into());
+    let rows: usize = users
+        .scan_batches(Some(filter), None)
+        .unwrap()
+        .iter()
+        .map(|batch| batch.len())
+        .sum();
+    assert_eq!(rows, 10);
+
+    // Logins name users in any case; the join keys on the users' collation
+    let logins = Schema::builder()
+        .column("name", DataType::VarChar(64))
+        .build();
+    let logins = db.create_table("logins", logins).unwrap();
+    for name in ["USER7", "user7", "nobody"] {
+        logins.insert(vec![Value::String(name.into())]).unwrap();
+    }
+    let probe = SeqScanExecutor::new(db.catalog().table("logins").unwrap()).unwrap();
+    let build = SeqScanExecutor::new(db.catalog().table("users").unwrap()).unwrap();
+    let joined: Vec<_> = HashJoinExecutor::new(probe, 0, build, 1)
+        .unwrap()
+        .map(|row| row.unwrap().1.values()[1].clone())
+        .collect();
+    assert_eq!(joined, vec![Value::Integer(7), Value::Integer(7)]);
+
+    // So does the runtime filter pushed into the logins scan
+    let logins = db.table("logins").unwrap();
+    let joined = logins
+        .hash_join("name", &users, "name", JoinType::Inner)
+        .unwrap();
+    assert_eq!(joined.len(), 2);
+}
+
+#[test]
+fn test_database_bloom_filter() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("bloom.db");
+
+    {
+        let db = Database::open(&path, options()).unwrap();
+        let users = db.create_table("users", users_schema()).unwrap();
+        for i in 0..500 {
+            users
+                .insert(vec![
+                    Value::Integer(i),
+                    Value::String(format!("user{}", i % 50)),
+                    Value::Null,
+                ])
+                .unwrap();
+        }
+        db.create_bloom_filter("users", "name").unwrap();
+        assert!(matches!(
+            db.create_bloom_filter("users", "id"),
+            Err(CrioError::DuplicateBloomFilter(_))
+        ));
+        db.close().unwrap();
+    }
+
+    let db = Database::open(&path, options()).unwrap();
+    let users = db.table("users").unwrap();
+    let name = Value::String("user7".into());
+    assert_eq!(users.scan_eq("name", &name).unwrap().len(), 10);
+
+    let (record_id, _) = users.scan_eq("id", &Value::Integer(7)).unwrap()[0].clone();
+    users.delete(record_id).unwrap();
+    users
+        .insert(vec![Value::Integer(1000), name.clone(), Value::Null])
+        .unwrap();
+    assert_eq!(users.scan_eq("name", &name).unwrap().len(), 10);
+    assert!(users
+        .scan_eq("name", &Value::String("nobody".into()))
+        .unwrap()
+        .is_empty());
+    users.rebuild_bloom_filter().unwrap();
+    assert_eq!(users.scan_eq("name", &name).unwrap().len(), 10);
+
+    db.drop_bloom_filter("users").unwrap();
+    assert!(db.catalog().bloom_filter(users.table_id()).is_none());
+    assert_eq!(users.scan_eq("name", &name).unwrap().len(), 10);
+}
+
+#[test]
+fn test_database_zone_map() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("zones.db");
+
+    {
+        let db = Database::open(&path, options()).unwrap();
+        let users = db.create_table("users", users_schema()).unwrap();
+        db.create_zone_map("users", &["id", "age"]).unwrap();
+        assert!(matches!(
+            db.create_zone_map("users", &["name"]),
+            Err(CrioError::DuplicateZoneMap(_))
+        ));
+        for i in 0..1000 {
+            users
+                .insert(vec![
+                    Value::Integer(i),
+                    Value::String(format!("user{}", i)),
+                    Value::SmallInt((i % 90) as i16),
+                ])
+                .unwrap();
+        }
+        db.close().unwrap();
+    }
+
+    let db = Database::open(&path, options()).unwrap();
+    let users = db.table("users").unwrap();
+    let rows = users.scan_range("id", Value::Integer(990)..).unwrap();
+    assert_eq!(rows.len(), 10);
+    assert_eq!(
+        users
+            .scan_range("age", Value::Integer(88)..=Value::Integer(200))
+            .unwrap()
+            .len(),
+        22
+    );
+
+    users.delete(rows[0].0).unwrap();
+    users
+        .insert(vec![
+            Value::Integer(5000),
+            Value::String("late".into()),
+            Value::Null,
+        ])
+        .unwrap();
+    assert_eq!(
+        users.scan_range("id", Value::Integer(990)..).unwrap().len(),
+        10
+    );
+    users.rebuild_zone_map().unwrap();
+    assert_eq!(
+        users.scan_range("id", ..Value::Integer(3)).unwrap().len(),
+        3
+    );
+
+    db.drop_zone_map("users").unwrap();
+    assert!(db.catalog().zone_map(users.table_id()).is_none());
+    assert_eq!(
+        users.scan_range("id", Value::Integer(990)..).unwrap().len(),
+        10
+    );
+}
+
+#[test]
+fn test_database_autovacuum() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("vacuum.db");
+    let options = DatabaseOptions {
+        autovacuum: AutoVacuumPolicy {
+            dead_tuple_threshold: 150,
+            interval: Duration::from_millis(10),
+            ..AutoVacuumPolicy::enabled()
+        },
+        io_budgets: vec![(IoClass::Background, IoBudget::new(10_000.0, 16.0))],
+        ..options()
+    };
+
+    let db = Database::open(&path, options).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    let record_ids: Vec<_> = (0..300)
+        .map(|i| {
+            users
+                .insert(vec![
+                    Value::Integer(i),
+                    Value::String(format!("user{}", i)),
+                    Value::Null,
+                ])
+                .unwrap()
+        })
+        .collect();
+
+    // Below the threshold nothing is vacuumed
+    for &record_id in &record_ids[..50] {
+        users.delete(record_id).unwrap();
+    }
+    std::thread::sleep(Duration::from_millis(50));
+    let autovacuum = db.autovacuum().unwrap();
+    assert_eq!(autovacuum.vacuum_count(), 0);
+    assert_eq!(db.catalog().table("users").unwrap().dead_tuples(), 50);
+
+    // The threshold is only reached by the last delete, so no pass can
+    // start partway through
+    for &record_id in &record_ids[50..150] {
+        users.delete(record_id).unwrap();
+    }
+    let deadline = std::time::Instant::now() + Duration::from_secs(10);
+    while autovacuum.vacuum_count() == 0 {
+        assert!(std::time::Instant::now() < deadline, "autovacuum never ran");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(autovacuum.error_count(), 0);
+    assert_eq!(db.catalog().table("users").unwrap().dead_tuples(), 0);
+    assert_eq!(users.scan().unwrap().len(), 150);
+
+    // A manual vacuum finds nothing left
+    assert_eq!(db.vacuum("users").unwrap().tuples_reclaimed, 0);
+    assert!(matches!(
+        db.vacuum("missing"),
+        Err(CrioError::UnknownTable(_))
+    ));
+    db.close().unwrap();
+}
+
+#[test]
+fn test_database_vacuum_indexed_table() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("vacuum_idx.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    db.create_index("users_id", "users", "id").unwrap();
+    let record_ids: Vec<_> = (0..200)
+        .map(|i| users.insert(user(i, "someone", 0)).unwrap())
+        .collect();
+    for &record_id in record_ids.iter().step_by(2) {
+        users.delete(record_id).unwrap();
+    }
+
+    // Deletes took their keys out of the index, so the slots can be reused
+    assert_eq!(db.vacuum("users").unwrap().tuples_reclaimed, 100);
+    for i in 200..300 {
+        users.insert(user(i, "later", 0)).unwrap();
+    }
+    for i in 0..300 {
+        let found = users.lookup("users_id", &Value::Integer(i)).unwrap();
+        if i < 200 && i % 2 == 0 {
+            assert!(found.is_none());
+        } else {
+            let (record_id, row) = found.unwrap();
+            assert_eq!(row.value(0), Some(&Value::Integer(i)));
+            assert_eq!(users.get(record_id).unwrap(), row);
+        }
+    }
+    assert_eq!(users.scan().unwrap().len(), 200);
+}
+
+#[test]
+fn test_database_jobs() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("jobs.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    db.create_index("users_id", "users", "id").unwrap();
+    users
+        .insert(vec![
+            Value::Integer(-1),
+            Value::String("first".into()),
+            Value::Null,
+        ])
+        .unwrap();
+
+    let row = |i: i32| {
+        vec![
+            Value::Integer(i),
+            Value::String(format!("user{}", i)),
+            Value::Null,
+        ]
+    };
+    let job = users.bulk_load((0..2000).map(row).collect());
+    let record_ids = job_result(job);
+    assert_eq!(record_ids.len(), 2000);
+    let (_, tuple) = users
+        .lookup("users_id", &Value::Integer(1234))
+        .unwrap()
+        .unwrap();
+    assert_eq!(tuple.value(1), Some(&Value::String("user1234".into())));
+
+    // A duplicate key fails the whole load before anything is written
+    let job = users.bulk_load(vec![row(5000), row(7)]);
+    assert!(matches!(job.wait(), Err(CrioError::DuplicateKey(_))));
+    assert!(users
+        .lookup("users_id", &Value::Integer(5000))
+        .unwrap()
+        .is_none());
+    assert_eq!(users.scan().unwrap().len(), 2001);
+
+    let job = db.create_index_job("users_id_2", "users", "id").unwrap();
+    let index = job_result(job);
+    assert_eq!(index.name, "users_id_2");
+
+    for &record_id in &record_ids[..500] {
+        users.delete(record_id).unwrap();
+    }
+    let job = db.vacuum_job("users").unwrap();
+    let pages = job_result(job).pages;
+    assert_eq!(pages, users_pages(&db));
+    assert!(db.vacuum_job("missing").is_err());
+}
+
+/// Waits for a job, checking that it reports all its pages done.
+fn job_result<T: Send + 'static>(job: JobHandle<T>) -> T {
+    let progress = Arc::clone(job.progress());
+    let result = job.wait().unwrap();
+    assert!(progress.pages_processed() > 0);
+    assert!(progress.percent() > 90.0, "{}%", progress.percent());
+    result
+}
+
+fn users_pages(db: &Database) -> usize {
+    let info = db.catalog().table("users").unwrap();
+    info.heap.page_ids().unwrap().len()
+}
+
+#[test]
+fn test_database_scan_subquery() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db = Database::open(temp_dir.path().join("subquery.db"), options()).unwrap();
+    let users = db.create_table("users", users_schema()).unwrap();
+    let orders_schema = Schema::builder()
+        .column("total", DataType::Integer)
+        .column("user_id", DataType::Integer)
+        .build();
+    let orders = db.create_table("orders", orders_schema).unwrap();
+    for i in 0..6 {
+        users.insert(user(i, "someone", (i % 2) as i16)).unwrap();
+    }
+    for (total, user_id) in [(10, 1), (30, 3), (50, 5)] {
+        orders
+            .insert(vec![Value::Integer(total), Value::Integer(user_id)])
+            .unwrap();
+    }
+
+    // The subquery reads orders, or users again, while users is scanned
+    let runs = std::cell::Cell::new(0);
+    let orders_of = |params: &[Value]| {
+        runs.set(runs.get() + 1);
+        let rows = orders.scan()?;
+        Ok(rows
+            .into_iter()
+            .filter(|(_, row)| row.value(1) == Some(&params[0]))
+            .map(Ok)
+            .collect::<Vec<_>>())
+    };
+    let ids = |rows: Vec<(_, Tuple)>| -> Vec<Value> {
+        rows.into_iter()
+            .map(|(_, row)| row.values()[0].clone())
+            .collect()
+    };
+
+    // WHERE EXISTS (SELECT * FROM orders WHERE user_id = users.id)
+    let rows = users
+        .scan_subquery(&["id"], SubqueryKind::Exists, SubqueryCache::new(orders_of))
+        .unwrap();
+    assert_eq!(ids(rows), [1, 3, 5].map(Value::Integer));
+    assert_eq!(runs.get(), 6);
+
+    // SELECT *, (SELECT total FROM orders WHERE user_id = users.id)
+    let rows = users
+        .scan_subquery(
+            &["id"],
+            SubqueryKind::Scalar(Column::new("total", DataType::Integer, true)),
+            SubqueryCache::new(orders_of),
+        )
+        .unwrap();
+    let totals: Vec<_> = rows
+        .iter()
+        .map(|(_, row)| row.value_by_name("total").cloned().unwrap())
+        .collect();
+    let total = |t: Option<i32>| t.map_or(Value::Null, Value::Integer);
+    assert_eq!(
+        totals,
+        [None, Some(10), None, Some(30), None, Some(50)].map(total)
+    );
+
+    // Correlated on age, which has two distinct values: two runs in all
+    let runs = std::cell::Cell::new(0);
+    let same_age = SubqueryCache::new(|params: &[Value]| {
+        runs.set(runs.get() + 1);
+        let rows = users.scan()?;
+        Ok(rows
+            .into_iter()
+            .filter(|(_, row)| row.value(0) == Some(&Value::Integer(0)))
+            .filter(|(_, row)| row.value(2) == Some(&params[0]))
+            .map(Ok)
+            .collect::<Vec<_>>())
+    });
+    let rows = users
+        .scan_subquery(&["age"], SubqueryKind::NotExists, same_age)
+        .unwrap();
+    assert_eq!(ids(rows), [1, 3, 5].map(Value::Integer));
+    assert_eq!(runs.get(), 2);
+
+    assert!(matches!(
+        users.scan_subquery(
+            &["nope"],
+            SubqueryKind::Exists,
+            SubqueryCache::new(orders_of)
+        ),
+        Err(CrioError::UnknownColumn(_))
+    ));
+}
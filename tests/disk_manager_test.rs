@@ -3,7 +3,7 @@
 use std::sync::Arc;
 use std::thread;
 
-use crio::common::{PageId, PAGE_SIZE};
+use crio::common::{CrioError, PageId, PAGE_SIZE};
 use crio::storage::disk::{DiskManager, DiskScheduler};
 use tempfile::NamedTempFile;
 
@@ -39,8 +39,8 @@ fn test_disk_manager_read_write_page() {
 
     // Write pattern
     let mut write_data = [0u8; PAGE_SIZE];
-    for i in 0..PAGE_SIZE {
-        write_data[i] = (i % 256) as u8;
+    for (i, byte) in write_data.iter_mut().enumerate() {
+        *byte = (i % 256) as u8;
     }
     dm.write_page(page_id, &write_data).unwrap();
 
@@ -188,6 +188,33 @@ fn test_disk_scheduler_concurrent_requests() {
     }
 }
 
+#[test]
+fn test_disk_manager_unclean_shutdown_detection() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("flag.db");
+
+    // A fresh database has no previous session
+    let dm = DiskManager::new(&path).unwrap();
+    assert!(!dm.unclean_shutdown());
+    dm.allocate_page().unwrap();
+    drop(dm);
+
+    // Dropped without marking a clean shutdown, as after a crash
+    let dm = DiskManager::new(&path).unwrap();
+    assert!(dm.unclean_shutdown());
+    dm.mark_clean_shutdown().unwrap();
+    drop(dm);
+
+    let dm = DiskManager::new(&path).unwrap();
+    assert!(!dm.unclean_shutdown());
+    assert_eq!(dm.get_num_pages(), 2);
+    drop(dm);
+
+    // Opening clears the flag again
+    let dm = DiskManager::new(&path).unwrap();
+    assert!(dm.unclean_shutdown());
+}
+
 #[test]
 fn test_disk_manager_io_stats() {
     let temp_file = NamedTempFile::new().unwrap();
@@ -233,6 +260,92 @@ fn test_disk_manager_large_file() {
     }
 }
 
+#[test]
+fn test_segment_rollover_allocate_page() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("rollover.db");
+    let dm = DiskManager::with_segment_pages(&db_path, 8).unwrap();
+
+    // Directory occupies page 0, so 7 pages fit in file 0
+    let page_ids: Vec<_> = (0..12).map(|_| dm.allocate_page().unwrap()).collect();
+    assert_eq!(page_ids[6], PageId::from_parts(0, 7));
+    assert_eq!(page_ids[7], PageId::from_parts(1, 0));
+    assert_eq!(page_ids[11], PageId::from_parts(1, 4));
+    assert_eq!(dm.num_files(), 2);
+
+    for &pid in &page_ids {
+        let mut data = [0u8; PAGE_SIZE];
+        data[..4].copy_from_slice(&pid.as_u32().to_le_bytes());
+        dm.write_page(pid, &data).unwrap();
+    }
+
+    for &pid in &page_ids {
+        let mut data = [0u8; PAGE_SIZE];
+        dm.read_page(pid, &mut data).unwrap();
+        assert_eq!(
+            u32::from_le_bytes(data[..4].try_into().unwrap()),
+            pid.as_u32()
+        );
+    }
+}
+
+#[test]
+fn test_segment_rollover_cross_file_tables() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("tables.db");
+    let dm = DiskManager::with_segment_pages(&db_path, 16).unwrap();
+
+    // Extent 0 holds the directory page; table 1 gets extent 1 (file 0),
+    // table 2 gets extent 2, which rolls over into file 1.
+    let t1 = dm.allocate_extent_for_table(1).unwrap();
+    let t2 = dm.allocate_extent_for_table(2).unwrap();
+    assert!(t1.iter().all(|pid| pid.file_id() == 0));
+    assert_eq!(t1[0], PageId::from_parts(0, 8));
+    assert!(t2.iter().all(|pid| pid.file_id() == 1));
+    assert_eq!(t2[0], PageId::from_parts(1, 0));
+
+    // Table 1's extent is full, so its next page comes from a fresh extent
+    let p = dm.allocate_page_for_table(1).unwrap();
+    assert_eq!(p, PageId::from_parts(1, 8));
+
+    let ranges = dm.get_table_page_ranges(2);
+    assert_eq!(ranges, vec![(PageId::from_parts(1, 0), 8)]);
+
+    // A sequential write across the segment boundary is rejected
+    let data = vec![0u8; PAGE_SIZE * 2];
+    assert!(dm.write_pages(PageId::from_parts(0, 15), 2, &data).is_err());
+
+    // Reopening validates the directory page, which table allocations must not touch
+    drop(dm);
+    let dm = DiskManager::with_segment_pages(&db_path, 16).unwrap();
+    assert_eq!(dm.num_files(), 2);
+    assert_eq!(dm.get_num_pages(), 25);
+}
+
+#[test]
+fn test_invalid_segment_size() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("invalid.db");
+
+    assert!(DiskManager::with_segment_pages(&db_path, 0).is_err());
+    assert!(DiskManager::with_segment_pages(&db_path, 12).is_err());
+    assert!(DiskManager::with_segment_pages(&db_path, PageId::PAGE_OFFSET_MASK + 9).is_err());
+
+    // The directory page records the segment size a database was created with
+    let dm = DiskManager::with_segment_pages(&db_path, 16).unwrap();
+    dm.allocate_page().unwrap();
+    drop(dm);
+    assert!(matches!(
+        DiskManager::with_segment_pages(&db_path, 32),
+        Err(CrioError::InvalidSegmentSize(32))
+    ));
+    assert!(matches!(
+        DiskManager::open_read_only(&db_path, 32),
+        Err(CrioError::InvalidSegmentSize(32))
+    ));
+    assert!(DiskManager::with_segment_pages(&db_path, 16).is_ok());
+}
+
 #[test]
 fn test_page_id_encoding() {
     assert_eq!(PageId::from_parts(0, 0).file_id(), 0);
@@ -355,6 +468,49 @@ fn test_sequential_io_within_file() {
     }
 }
 
+#[test]
+fn test_vectored_write_scattered_pages() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("vectored.db");
+    let dm = DiskManager::with_segment_pages(&db_path, 8).unwrap();
+
+    let page_ids: Vec<_> = (0..12).map(|_| dm.allocate_page().unwrap()).collect();
+
+    // Runs: (0,2..=4), (0,6), (1,1..=2) -> three writes across two files
+    let targets = [
+        page_ids[1],
+        page_ids[2],
+        page_ids[3],
+        page_ids[5],
+        page_ids[8],
+        page_ids[9],
+    ];
+    let buffers: Vec<Vec<u8>> = targets
+        .iter()
+        .map(|pid| vec![pid.as_u32() as u8; PAGE_SIZE])
+        .collect();
+    let pages: Vec<(PageId, &[u8])> = targets
+        .iter()
+        .zip(&buffers)
+        .map(|(&pid, data)| (pid, data.as_slice()))
+        .collect();
+
+    let writes_before = dm.get_num_writes();
+    dm.write_pages_vectored(&pages).unwrap();
+    assert_eq!(dm.get_num_writes(), writes_before + 3);
+
+    for &pid in &page_ids {
+        let mut data = [0u8; PAGE_SIZE];
+        dm.read_page(pid, &mut data).unwrap();
+        let expected = if targets.contains(&pid) {
+            pid.as_u32() as u8
+        } else {
+            0
+        };
+        assert!(data.iter().all(|&b| b == expected), "page {:?}", pid);
+    }
+}
+
 #[test]
 fn test_sequential_io_boundary_check() {
     let temp_dir = tempfile::tempdir().unwrap();
@@ -369,7 +525,16 @@ fn test_sequential_io_boundary_check() {
 
     let result = dm.write_pages(start_page, too_many_pages as u32, &data);
     assert!(result.is_err());
-    assert!(result.unwrap_err().to_string().contains("file boundary"));
+    let err = result.unwrap_err();
+    assert!(err.to_string().contains("file boundary"));
+    assert!(matches!(
+        err,
+        CrioError::FileBoundaryCrossed {
+            file_id: 0,
+            num_pages: 20,
+            ..
+        }
+    ));
 }
 
 #[test]
@@ -385,6 +550,25 @@ fn test_read_from_nonexistent_file() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_scheduler_propagates_io_errors() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("errors.db");
+    let dm = Arc::new(DiskManager::new(&db_path).unwrap());
+    let scheduler = DiskScheduler::new(dm);
+
+    // Failures in the worker reach the caller with their original variant
+    let mut data = [0u8; PAGE_SIZE];
+    let result = scheduler.schedule_read_sync(PageId::from_parts(5, 0), &mut data);
+    assert!(matches!(result, Err(CrioError::InvalidPageId(_))));
+
+    let start = PageId::from_parts(0, PageId::PAGE_OFFSET_MASK);
+    let bulk = vec![0u8; PAGE_SIZE * 2];
+    let result = scheduler.schedule_write_pages_sync(start, 2, &bulk);
+    assert!(matches!(result, Err(CrioError::FileBoundaryCrossed { .. })));
+    assert_eq!(result.unwrap_err().io_kind(), None);
+}
+
 #[test]
 fn test_concurrent_multi_file_access() {
     let temp_dir = tempfile::tempdir().unwrap();
@@ -421,3 +605,40 @@ fn test_concurrent_multi_file_access() {
         }
     }
 }
+
+#[test]
+fn test_temp_file_manager_isolated_from_segments() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let db_path = temp_dir.path().join("spill.db");
+    let dm = DiskManager::new(&db_path).unwrap();
+    let pages_before = dm.get_num_pages();
+
+    let tfm = dm.create_temp_file_manager(16).unwrap();
+    let temp_path = tfm.path().to_path_buf();
+    assert_ne!(
+        dm.create_temp_file_manager(1).unwrap().path(),
+        temp_path.as_path()
+    );
+
+    for i in 0..16u8 {
+        let page_id = tfm.allocate_page().unwrap();
+        tfm.write_page(page_id, &[i; PAGE_SIZE]).unwrap();
+    }
+    assert!(tfm.allocate_page().is_err());
+
+    // The database itself is unaffected
+    assert_eq!(dm.get_num_pages(), pages_before);
+    assert_eq!(dm.num_files(), 1);
+
+    drop(tfm);
+    assert!(!temp_path.exists());
+
+    // A scratch file left behind under the next name is skipped, not truncated
+    let stale = temp_dir
+        .path()
+        .join(format!("spill.db.tmp.{}.2", std::process::id()));
+    std::fs::write(&stale, b"stale").unwrap();
+    let tfm = dm.create_temp_file_manager(1).unwrap();
+    assert_ne!(tfm.path(), stale.as_path());
+    assert_eq!(std::fs::read(&stale).unwrap(), b"stale");
+}
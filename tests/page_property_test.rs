@@ -0,0 +1,401 @@
+//! Randomized property tests for the page formats
+//!
+//! Each test drives a page through seeded random operation sequences and
+//! checks it against a simple model after every step, so offset arithmetic
+//! bugs (overlapping slot arrays and tuple regions, misplaced values after a
+//! shift) show up with the seed that triggered them.
+
+use std::collections::BTreeMap;
+
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+use crio::common::{CrioError, PageId, RecordId, SlotId, PAGE_SIZE};
+use crio::index::{BTreeNode, BTreeNodeRef, KeyValuePair};
+use crio::storage::page::{SlottedPage, SlottedPageRef};
+
+const SEEDS: u64 = 64;
+const STEPS: usize = 400;
+
+/// Leaf entries are a 4-byte key plus a 6-byte RecordId after a 20-byte header.
+const LEAF_CAPACITY: usize = (PAGE_SIZE - 20) / 10;
+/// Internal nodes hold one more 4-byte child than keys.
+const INTERNAL_CAPACITY: usize = (PAGE_SIZE - 20 - 4) / 8;
+
+#[derive(Debug, Clone, PartialEq)]
+enum SlotState {
+    Live(Vec<u8>),
+    /// Tombstoned tuples keep their bytes until reclaimed
+    Tombstone(usize),
+}
+
+fn random_tuple(rng: &mut StdRng) -> Vec<u8> {
+    // Mostly small tuples, with the occasional large one to hit page-full paths
+    let len = if rng.gen_bool(0.1) {
+        rng.gen_range(500..=1500)
+    } else {
+        rng.gen_range(1..=120)
+    };
+    let mut tuple = vec![0u8; len];
+    rng.fill_bytes(&mut tuple);
+    tuple
+}
+
+fn assert_slotted_matches(data: &[u8], model: &BTreeMap<u16, SlotState>, seed: u64, step: usize) {
+    let page = SlottedPageRef::new(data);
+    let violations = page.check_layout();
+    assert!(
+        violations.is_empty(),
+        "seed {} step {}: {:?}",
+        seed,
+        step,
+        violations
+    );
+
+    for slot in 0..page.num_slots() {
+        let slot_id = SlotId::new(slot);
+        match model.get(&slot) {
+            Some(SlotState::Live(tuple)) => {
+                assert_eq!(
+                    page.get_tuple(slot_id).unwrap(),
+                    tuple.as_slice(),
+                    "seed {} step {} slot {}",
+                    seed,
+                    step,
+                    slot
+                );
+            }
+            Some(SlotState::Tombstone(_)) => {
+                assert!(page.is_tombstone(slot_id), "seed {} step {}", seed, step);
+                assert!(matches!(
+                    page.get_tuple(slot_id),
+                    Err(CrioError::TupleDeleted(_))
+                ));
+            }
+            None => assert!(
+                page.get_tuple(slot_id).is_err(),
+                "seed {} step {}: slot {} should be empty",
+                seed,
+                step,
+                slot
+            ),
+        }
+    }
+
+    let live = model
+        .values()
+        .filter(|s| matches!(s, SlotState::Live(_)))
+        .count();
+    assert_eq!(page.tuple_count(), live, "seed {} step {}", seed, step);
+    assert_eq!(
+        page.free_space(),
+        (page.free_space_end() - page.free_space_start()) as usize
+    );
+}
+
+fn pick_slot(rng: &mut StdRng, model: &BTreeMap<u16, SlotState>) -> Option<u16> {
+    if model.is_empty() {
+        return None;
+    }
+    let index = rng.gen_range(0..model.len());
+    model.keys().nth(index).copied()
+}
+
+#[test]
+fn test_slotted_page_random_operations() {
+    for seed in 0..SEEDS {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut data = [0u8; PAGE_SIZE];
+        let mut model: BTreeMap<u16, SlotState> = BTreeMap::new();
+        SlottedPage::new(&mut data).init(PageId::new(1));
+
+        for step in 0..STEPS {
+            let mut page = SlottedPage::new(&mut data);
+            match rng.gen_range(0..100) {
+                0..=44 => {
+                    let tuple = random_tuple(&mut rng);
+                    if page.can_insert(tuple.len()) {
+                        let slot_id = page.insert_tuple(&tuple).unwrap();
+                        let previous = model.insert(slot_id.as_u16(), SlotState::Live(tuple));
+                        assert!(
+                            previous.is_none(),
+                            "seed {} step {}: slot reused",
+                            seed,
+                            step
+                        );
+                    } else {
+                        assert!(matches!(
+                            page.insert_tuple(&tuple),
+                            Err(CrioError::PageOverflow { .. })
+                        ));
+                    }
+                }
+                45..=59 => {
+                    let Some(slot) = pick_slot(&mut rng, &model) else {
+                        continue;
+                    };
+                    let slot_id = SlotId::new(slot);
+                    match model.get_mut(&slot).unwrap() {
+                        SlotState::Live(tuple) => {
+                            let len = rng.gen_range(1..=tuple.len() + 8);
+                            let mut new_tuple = vec![0u8; len];
+                            rng.fill_bytes(&mut new_tuple);
+                            let result = page.update_tuple(slot_id, &new_tuple);
+                            if len <= tuple.len() {
+                                result.unwrap();
+                                *tuple = new_tuple;
+                            } else {
+                                assert!(matches!(result, Err(CrioError::PageOverflow { .. })));
+                            }
+                        }
+                        SlotState::Tombstone(_) => {
+                            assert!(matches!(
+                                page.update_tuple(slot_id, &[1]),
+                                Err(CrioError::TupleDeleted(_))
+                            ));
+                        }
+                    }
+                }
+                60..=74 => {
+                    let Some(slot) = pick_slot(&mut rng, &model) else {
+                        continue;
+                    };
+                    page.delete_tuple(SlotId::new(slot)).unwrap();
+                    model.remove(&slot);
+                }
+                75..=89 => {
+                    let Some(slot) = pick_slot(&mut rng, &model) else {
+                        continue;
+                    };
+                    let result = page.mark_deleted(SlotId::new(slot));
+                    let state = model.get_mut(&slot).unwrap();
+                    match state {
+                        SlotState::Live(tuple) => {
+                            result.unwrap();
+                            *state = SlotState::Tombstone(tuple.len());
+                        }
+                        SlotState::Tombstone(_) => {
+                            assert!(matches!(result, Err(CrioError::TupleDeleted(_))));
+                        }
+                    }
+                }
+                90..=94 => {
+                    let tombstones = model
+                        .values()
+                        .filter(|s| matches!(s, SlotState::Tombstone(_)))
+                        .count();
+                    assert_eq!(page.reclaim_tombstones(), tombstones);
+                    model.retain(|_, s| matches!(s, SlotState::Live(_)));
+                }
+                _ => {
+                    let before = page.free_space();
+                    page.compact();
+                    assert!(page.free_space() >= before);
+                }
+            }
+
+            assert_slotted_matches(&data, &model, seed, step);
+        }
+
+        // Compaction leaves the tuples packed against the page end
+        let mut page = SlottedPage::new(&mut data);
+        page.compact();
+        let used: usize = model
+            .values()
+            .map(|s| match s {
+                SlotState::Live(tuple) => tuple.len(),
+                SlotState::Tombstone(len) => *len,
+            })
+            .sum();
+        let end = SlottedPageRef::new(&data).free_space_end() as usize;
+        assert_eq!(PAGE_SIZE - end, used, "seed {}", seed);
+        assert_slotted_matches(&data, &model, seed, STEPS);
+    }
+}
+
+fn record_id(key: u32) -> RecordId {
+    RecordId::new(PageId::new(key % 1000), SlotId::new((key % 97) as u16))
+}
+
+/// Draws a key that is not in `keys`. A narrow range makes neighbouring keys
+/// common, and the range ends are included explicitly.
+fn fresh_key(rng: &mut StdRng, keys: &BTreeMap<u32, u32>) -> u32 {
+    loop {
+        let key = match rng.gen_range(0..20) {
+            0 => 0,
+            1 => u32::MAX,
+            2..=9 => rng.gen_range(0..2000),
+            _ => rng.gen(),
+        };
+        if !keys.contains_key(&key) {
+            return key;
+        }
+    }
+}
+
+fn assert_leaf_matches(data: &[u8], model: &BTreeMap<u32, u32>, seed: u64) {
+    let node = BTreeNodeRef::new(data);
+    let violations = node.check_layout();
+    assert!(violations.is_empty(), "seed {}: {:?}", seed, violations);
+    assert_eq!(node.num_keys() as usize, model.len(), "seed {}", seed);
+    for (i, (&key, &value)) in model.iter().enumerate() {
+        assert_eq!(node.get_key(i), key, "seed {} index {}", seed, i);
+        assert_eq!(
+            node.get_value(i),
+            record_id(value),
+            "seed {} index {}",
+            seed,
+            i
+        );
+        assert_eq!(node.search_key(key), i);
+    }
+}
+
+#[test]
+fn test_btree_leaf_random_keys() {
+    for seed in 0..SEEDS {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut data = [0u8; PAGE_SIZE];
+        BTreeNode::new(&mut data).init(PageId::new(2), true);
+
+        let target = rng.gen_range(2..=LEAF_CAPACITY);
+        let mut model = BTreeMap::new();
+        for _ in 0..target {
+            let key = fresh_key(&mut rng, &model);
+            BTreeNode::new(&mut data)
+                .insert_key_value(key, record_id(key))
+                .unwrap();
+            model.insert(key, key);
+            assert_leaf_matches(&data, &model, seed);
+        }
+
+        // Splitting keeps the lower half in place and hands back the rest,
+        // starting with the separator
+        let (separator, right) = BTreeNode::new(&mut data).split_leaf();
+        let mid = model.len() / 2;
+        let mid_key = *model.keys().nth(mid).unwrap();
+        let upper = model.split_off(&mid_key);
+        assert_eq!(separator, *upper.keys().next().unwrap(), "seed {}", seed);
+        assert_leaf_matches(&data, &model, seed);
+
+        let mut right_data = [0u8; PAGE_SIZE];
+        let mut right_node = BTreeNode::new(&mut right_data);
+        right_node.init(PageId::new(3), true);
+        right_node.insert_pairs(&right);
+        assert_leaf_matches(&right_data, &upper, seed);
+    }
+}
+
+#[test]
+fn test_btree_internal_random_keys() {
+    for seed in 0..SEEDS {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut data = [0u8; PAGE_SIZE];
+        BTreeNode::new(&mut data).init(PageId::new(2), false);
+
+        // Model: key -> the child to its right; child 0 sits left of every key
+        let leftmost = PageId::new(10_000);
+        BTreeNode::new(&mut data).insert_keys_children(&[], &[leftmost]);
+
+        let target = rng.gen_range(3..=INTERNAL_CAPACITY);
+        let mut model = BTreeMap::new();
+        for _ in 0..target {
+            let key = fresh_key(&mut rng, &model);
+            let child = key % 5000;
+            BTreeNode::new(&mut data)
+                .insert_key_child(key, PageId::new(child))
+                .unwrap();
+            model.insert(key, child);
+
+            let node = BTreeNodeRef::new(&data);
+            let violations = node.check_layout();
+            assert!(violations.is_empty(), "seed {}: {:?}", seed, violations);
+            assert_eq!(node.get_child(0), leftmost, "seed {}", seed);
+            for (i, (&key, &child)) in model.iter().enumerate() {
+                assert_eq!(node.get_key(i), key, "seed {}", seed);
+                assert_eq!(node.get_child(i + 1), PageId::new(child), "seed {}", seed);
+            }
+        }
+
+        // The separator moves up; the left keeps children 0..=mid and the
+        // right gets the keys after the separator with their children
+        let keys: Vec<u32> = model.keys().copied().collect();
+        let mid = keys.len() / 2;
+        let (separator, right_keys, right_children) = BTreeNode::new(&mut data).split_internal();
+        assert_eq!(separator, keys[mid], "seed {}", seed);
+        assert_eq!(right_keys, keys[mid + 1..], "seed {}", seed);
+        assert_eq!(right_children.len(), right_keys.len() + 1);
+        assert_eq!(right_children[0], PageId::new(model[&separator]));
+
+        let node = BTreeNodeRef::new(&data);
+        assert!(node.check_layout().is_empty(), "seed {}", seed);
+        assert_eq!(node.num_keys() as usize, mid);
+        assert_eq!(node.get_child(0), leftmost);
+        for (i, key) in keys[..mid].iter().enumerate() {
+            assert_eq!(node.get_key(i), *key);
+            assert_eq!(node.get_child(i + 1), PageId::new(model[key]));
+        }
+    }
+}
+
+#[test]
+fn test_layout_checks_survive_garbage() {
+    // The checkers run on pages read from possibly corrupt files, so they must
+    // report problems rather than panic on arbitrary bytes
+    let mut rng = StdRng::seed_from_u64(0xC0FFEE);
+    for _ in 0..2000 {
+        let mut data = [0u8; PAGE_SIZE];
+        if rng.gen_bool(0.5) {
+            rng.fill_bytes(&mut data);
+        } else {
+            // Start from a valid page and flip a few header and slot bytes so
+            // the earlier checks pass more often
+            let mut page = SlottedPage::new(&mut data);
+            page.init(PageId::new(1));
+            for _ in 0..rng.gen_range(1..20) {
+                page.insert_tuple(&random_tuple(&mut rng)).ok();
+            }
+            for _ in 0..rng.gen_range(1..4) {
+                let offset = rng.gen_range(0..64);
+                data[offset] = rng.gen();
+            }
+        }
+
+        SlottedPageRef::new(&data).check_layout();
+        BTreeNodeRef::new(&data).check_layout();
+    }
+}
+
+#[test]
+fn test_btree_insert_pairs_replaces_contents() {
+    // insert_pairs must lay out values for the final key count, whatever the
+    // node held before
+    let mut rng = StdRng::seed_from_u64(7);
+    for _ in 0..SEEDS {
+        let mut data = [0u8; PAGE_SIZE];
+        let mut node = BTreeNode::new(&mut data);
+        node.init(PageId::new(4), true);
+        let stale: Vec<KeyValuePair> = (0..rng.gen_range(0..LEAF_CAPACITY) as u32)
+            .map(|k| KeyValuePair {
+                key: k,
+                value: record_id(k + 1),
+            })
+            .collect();
+        node.insert_pairs(&stale);
+
+        let mut model = BTreeMap::new();
+        for _ in 0..rng.gen_range(0..LEAF_CAPACITY) {
+            let key = fresh_key(&mut rng, &model);
+            model.insert(key, key);
+        }
+        let pairs: Vec<KeyValuePair> = model
+            .keys()
+            .map(|&key| KeyValuePair {
+                key,
+                value: record_id(key),
+            })
+            .collect();
+        BTreeNode::new(&mut data).insert_pairs(&pairs);
+        assert_leaf_matches(&data, &model, 0);
+    }
+}
@@ -80,7 +80,7 @@ fn test_multiple_tuples_in_page() {
     let tuples: Vec<Tuple> = (0..10)
         .map(|i| {
             TupleBuilder::new(schema.clone())
-                .value(i as i32)
+                .value(i)
                 .value(format!("User{}", i))
                 .value(format!("user{}@example.com", i))
                 .value((20 + i) as i16)
@@ -273,8 +273,8 @@ fn test_all_data_types() {
             Value::SmallInt(32000),
             Value::Integer(2_000_000_000),
             Value::BigInt(9_000_000_000_000_000_000),
-            Value::Float(3.14),
-            Value::Double(2.718281828),
+            Value::Float(1.5),
+            Value::Double(2.5e-3),
             Value::String("hello".to_string()),
             Value::String("variable length string".to_string()),
             Value::Timestamp(1703980800000000), // Some timestamp